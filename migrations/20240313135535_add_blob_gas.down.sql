@@ -0,0 +1,3 @@
+ALTER TABLE blocks_next DROP COLUMN blob_base_fee;
+ALTER TABLE blocks_next DROP COLUMN blob_gas_used;
+ALTER TABLE blocks_next DROP COLUMN excess_blob_gas;
@@ -0,0 +1,6 @@
+ALTER TABLE blocks_next ADD COLUMN blob_base_fee INT8;
+ALTER TABLE blocks_next ADD COLUMN blob_gas_used INT4;
+ALTER TABLE blocks_next ADD COLUMN excess_blob_gas INT8;
+
+-- Blocks stored before this have no blob gas, `heal-blob-gas` refetches them from Cancun on and
+-- then drops the burn sums, so they are recomputed including blob fees.
@@ -0,0 +1 @@
+ALTER TABLE blocks_next DROP COLUMN gas_limit;
@@ -0,0 +1 @@
+ALTER TABLE blocks_next ADD COLUMN gas_limit INT4;
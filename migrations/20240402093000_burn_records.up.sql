@@ -0,0 +1,13 @@
+CREATE TABLE
+  burn_records (
+    time_frame text NOT NULL,
+    block_number integer NOT NULL,
+    block_hash text NOT NULL,
+    timestamp timestamptz NOT NULL,
+    burn_wei numeric NOT NULL,
+    burn_usd float8 NOT NULL,
+    CONSTRAINT burn_records_pkey PRIMARY KEY (time_frame, block_number),
+    CONSTRAINT burn_records_block_hash_fkey FOREIGN KEY (block_hash) REFERENCES blocks_next (hash)
+  );
+
+CREATE INDEX burn_records_block_number_idx ON burn_records (block_number);
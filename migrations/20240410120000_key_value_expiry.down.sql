@@ -0,0 +1,3 @@
+DROP INDEX key_value_store_expires_at_idx;
+
+ALTER TABLE key_value_store DROP COLUMN expires_at;
@@ -0,0 +1,4 @@
+ALTER TABLE key_value_store ADD COLUMN expires_at TIMESTAMPTZ;
+
+CREATE INDEX key_value_store_expires_at_idx ON key_value_store (expires_at)
+WHERE expires_at IS NOT NULL;
@@ -0,0 +1,52 @@
+ALTER TABLE eth_supply RENAME TO eth_supply_partitioned;
+
+CREATE TABLE eth_supply (
+  LIKE eth_supply_partitioned INCLUDING DEFAULTS
+);
+
+INSERT INTO eth_supply SELECT * FROM eth_supply_partitioned;
+
+DROP TABLE eth_supply_partitioned;
+
+ALTER TABLE eth_supply ADD PRIMARY KEY (timestamp);
+ALTER TABLE eth_supply ADD CONSTRAINT eth_supply_deposits_slot_fkey
+  FOREIGN KEY (deposits_slot) REFERENCES beacon_states (slot);
+ALTER TABLE eth_supply ADD CONSTRAINT eth_supply_balances_slot_fkey
+  FOREIGN KEY (balances_slot) REFERENCES beacon_states (slot);
+
+CREATE INDEX eth_supply_deposits_slot ON eth_supply (deposits_slot);
+CREATE INDEX eth_supply_balances_slot ON eth_supply (balances_slot);
+
+ALTER TABLE burn_sums DROP CONSTRAINT burn_sums_first_included_block_number_fkey;
+ALTER TABLE burn_sums DROP CONSTRAINT burn_sums_last_included_block_fkey;
+ALTER TABLE burn_records DROP CONSTRAINT burn_records_block_fkey;
+
+DROP TRIGGER blocks_next_hashes_sync ON blocks_next;
+DROP FUNCTION sync_blocks_next_hashes;
+DROP TABLE blocks_next_hashes;
+
+ALTER TABLE blocks_next RENAME TO blocks_next_partitioned;
+
+CREATE TABLE blocks_next (
+  LIKE blocks_next_partitioned INCLUDING DEFAULTS
+);
+
+INSERT INTO blocks_next SELECT * FROM blocks_next_partitioned;
+
+DROP TABLE blocks_next_partitioned;
+
+ALTER TABLE blocks_next ADD PRIMARY KEY (hash);
+ALTER TABLE blocks_next ADD CONSTRAINT blocks_next_number_key UNIQUE (number);
+ALTER TABLE blocks_next ADD CONSTRAINT blocks_next_parent_hash_key UNIQUE (parent_hash);
+
+CREATE INDEX blocks_next_timestamp_idx ON blocks_next (timestamp);
+CREATE INDEX blocks_next_base_fee_per_gas_idx ON blocks_next (base_fee_per_gas);
+
+ALTER TABLE burn_sums ADD CONSTRAINT burn_sums_first_included_block_number_fkey
+  FOREIGN KEY (first_included_block_number) REFERENCES blocks_next (number);
+ALTER TABLE burn_sums ADD CONSTRAINT burn_sums_last_included_block_number_fkey
+  FOREIGN KEY (last_included_block_number) REFERENCES blocks_next (number);
+ALTER TABLE burn_sums ADD CONSTRAINT burn_sums_last_included_block_hash_fkey
+  FOREIGN KEY (last_included_block_hash) REFERENCES blocks_next (hash);
+ALTER TABLE burn_records ADD CONSTRAINT burn_records_block_hash_fkey
+  FOREIGN KEY (block_hash) REFERENCES blocks_next (hash);
@@ -0,0 +1,125 @@
+-- Partitions blocks_next and eth_supply, so rollbacks and range queries only touch the partitions
+-- they need. Blocks are partitioned by number rather than timestamp. Block numbers grow with time,
+-- and partitioning by number keeps the keys other tables reference unique.
+--
+-- Partitions are created ahead of time, here up to block 30M and the next year, from then on by
+-- the block sync. There are no default partitions, a row outside every partition fails to insert,
+-- rather than landing in a default partition which then blocks creating the partition it belongs
+-- in.
+
+ALTER TABLE burn_sums DROP CONSTRAINT burn_sums_first_included_block_number_fkey;
+ALTER TABLE burn_sums DROP CONSTRAINT burn_sums_last_included_block_number_fkey;
+ALTER TABLE burn_sums DROP CONSTRAINT burn_sums_last_included_block_hash_fkey;
+ALTER TABLE burn_records DROP CONSTRAINT burn_records_block_hash_fkey;
+
+ALTER TABLE blocks_next RENAME TO blocks_next_unpartitioned;
+
+CREATE TABLE blocks_next (
+  LIKE blocks_next_unpartitioned INCLUDING DEFAULTS
+) PARTITION BY RANGE (number);
+
+DO $$
+DECLARE
+  partition_start INT4;
+BEGIN
+  FOR partition_start IN
+    SELECT generate_series(first_start, last_start, 1000000)
+    FROM (
+      SELECT
+        0 AS first_start,
+        GREATEST(MAX(number), 30000000) / 1000000 * 1000000 + 1000000 AS last_start
+      FROM blocks_next_unpartitioned
+    ) bounds
+  LOOP
+    EXECUTE format(
+      'CREATE TABLE blocks_next_p%s PARTITION OF blocks_next FOR VALUES FROM (%s) TO (%s)',
+      partition_start / 1000000,
+      partition_start,
+      partition_start + 1000000
+    );
+  END LOOP;
+END $$;
+
+-- Unique constraints on a partitioned table have to include the partition key. To keep hashes and
+-- parent hashes unique across partitions, as they were, they're also kept in a table of their own,
+-- in step with blocks_next through a trigger.
+CREATE TABLE blocks_next_hashes (
+  hash TEXT PRIMARY KEY,
+  parent_hash TEXT UNIQUE NOT NULL
+);
+
+CREATE FUNCTION sync_blocks_next_hashes() RETURNS TRIGGER AS $$
+BEGIN
+  IF TG_OP IN ('UPDATE', 'DELETE') THEN
+    DELETE FROM blocks_next_hashes WHERE hash = OLD.hash;
+  END IF;
+  IF TG_OP IN ('INSERT', 'UPDATE') THEN
+    INSERT INTO blocks_next_hashes (hash, parent_hash) VALUES (NEW.hash, NEW.parent_hash);
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER blocks_next_hashes_sync
+  AFTER INSERT OR UPDATE OF hash, parent_hash OR DELETE ON blocks_next
+  FOR EACH ROW EXECUTE FUNCTION sync_blocks_next_hashes();
+
+INSERT INTO blocks_next SELECT * FROM blocks_next_unpartitioned;
+
+DROP TABLE blocks_next_unpartitioned;
+
+ALTER TABLE blocks_next ADD PRIMARY KEY (number);
+-- What foreign keys to a block reference.
+ALTER TABLE blocks_next ADD CONSTRAINT blocks_next_hash_number_key UNIQUE (hash, number);
+ALTER TABLE blocks_next ADD CONSTRAINT blocks_next_parent_hash_number_key UNIQUE (parent_hash, number);
+
+CREATE INDEX blocks_next_timestamp_idx ON blocks_next (timestamp);
+CREATE INDEX blocks_next_base_fee_per_gas_idx ON blocks_next (base_fee_per_gas);
+
+ALTER TABLE burn_sums ADD CONSTRAINT burn_sums_first_included_block_number_fkey
+  FOREIGN KEY (first_included_block_number) REFERENCES blocks_next (number);
+ALTER TABLE burn_sums ADD CONSTRAINT burn_sums_last_included_block_fkey
+  FOREIGN KEY (last_included_block_hash, last_included_block_number) REFERENCES blocks_next (hash, number);
+ALTER TABLE burn_records ADD CONSTRAINT burn_records_block_fkey
+  FOREIGN KEY (block_hash, block_number) REFERENCES blocks_next (hash, number);
+
+ALTER TABLE eth_supply RENAME TO eth_supply_unpartitioned;
+
+CREATE TABLE eth_supply (
+  LIKE eth_supply_unpartitioned INCLUDING DEFAULTS
+) PARTITION BY RANGE (timestamp);
+
+DO $$
+DECLARE
+  partition_year INT4;
+BEGIN
+  FOR partition_year IN
+    SELECT generate_series(first_year, last_year)
+    FROM (
+      SELECT
+        LEAST(EXTRACT(YEAR FROM MIN(timestamp) AT TIME ZONE 'UTC'), 2020)::INT4 AS first_year,
+        EXTRACT(YEAR FROM NOW() AT TIME ZONE 'UTC')::INT4 + 1 AS last_year
+      FROM eth_supply_unpartitioned
+    ) bounds
+  LOOP
+    EXECUTE format(
+      'CREATE TABLE eth_supply_y%s PARTITION OF eth_supply FOR VALUES FROM (%L) TO (%L)',
+      partition_year,
+      make_timestamptz(partition_year, 1, 1, 0, 0, 0, 'UTC'),
+      make_timestamptz(partition_year + 1, 1, 1, 0, 0, 0, 'UTC')
+    );
+  END LOOP;
+END $$;
+
+INSERT INTO eth_supply SELECT * FROM eth_supply_unpartitioned;
+
+DROP TABLE eth_supply_unpartitioned;
+
+ALTER TABLE eth_supply ADD PRIMARY KEY (timestamp);
+ALTER TABLE eth_supply ADD CONSTRAINT eth_supply_deposits_slot_fkey
+  FOREIGN KEY (deposits_slot) REFERENCES beacon_states (slot);
+ALTER TABLE eth_supply ADD CONSTRAINT eth_supply_balances_slot_fkey
+  FOREIGN KEY (balances_slot) REFERENCES beacon_states (slot);
+
+CREATE INDEX eth_supply_deposits_slot ON eth_supply (deposits_slot);
+CREATE INDEX eth_supply_balances_slot ON eth_supply (balances_slot);
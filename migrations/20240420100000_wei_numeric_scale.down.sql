@@ -0,0 +1,3 @@
+ALTER TABLE eth_supply ALTER COLUMN supply TYPE NUMERIC;
+ALTER TABLE burn_sums ALTER COLUMN sum_wei TYPE NUMERIC;
+ALTER TABLE burn_records ALTER COLUMN burn_wei TYPE NUMERIC;
@@ -0,0 +1,4 @@
+-- Wei amounts are decoded straight into WeiNewtype, make sure they're whole numbers.
+ALTER TABLE eth_supply ALTER COLUMN supply TYPE NUMERIC(78, 0);
+ALTER TABLE burn_sums ALTER COLUMN sum_wei TYPE NUMERIC(78, 0);
+ALTER TABLE burn_records ALTER COLUMN burn_wei TYPE NUMERIC(78, 0);
@@ -0,0 +1 @@
+ALTER TABLE burn_sums ALTER COLUMN sum_usd TYPE float8;
@@ -0,0 +1,2 @@
+-- USD sums are added to and subtracted from on every block, floats drift doing so.
+ALTER TABLE burn_sums ALTER COLUMN sum_usd TYPE NUMERIC(28, 8);
@@ -0,0 +1,22 @@
+-- Only populated when transaction ingestion is enabled, see `ingest_transactions`.
+CREATE TABLE
+  transactions (
+    block_number integer NOT NULL,
+    block_hash text NOT NULL,
+    transaction_index integer NOT NULL,
+    hash text NOT NULL,
+    from_address text NOT NULL,
+    -- NULL for contract creations.
+    to_address text,
+    value_wei NUMERIC(78, 0) NOT NULL,
+    gas_limit integer NOT NULL,
+    gas_used integer NOT NULL,
+    type smallint NOT NULL,
+    -- The first four bytes of the input, NULL for plain transfers.
+    input_selector text,
+    CONSTRAINT transactions_pkey PRIMARY KEY (block_number, transaction_index),
+    CONSTRAINT transactions_block_fkey FOREIGN KEY (block_hash, block_number) REFERENCES blocks_next (hash, number)
+  );
+
+CREATE INDEX transactions_hash_idx ON transactions (hash);
+CREATE INDEX transactions_to_address_idx ON transactions (to_address);
@@ -0,0 +1,15 @@
+-- DepositEvent logs of the beacon chain deposit contract, see `execution_chain::deposit_events`.
+CREATE TABLE
+  deposit_events (
+    deposit_index bigint NOT NULL,
+    block_number integer NOT NULL,
+    block_hash text NOT NULL,
+    transaction_hash text NOT NULL,
+    log_index integer NOT NULL,
+    pubkey text NOT NULL,
+    withdrawal_credentials text NOT NULL,
+    amount_gwei bigint NOT NULL,
+    CONSTRAINT deposit_events_pkey PRIMARY KEY (deposit_index)
+  );
+
+CREATE INDEX deposit_events_block_number_idx ON deposit_events (block_number);
@@ -0,0 +1 @@
+DROP TABLE burn_anomalies;
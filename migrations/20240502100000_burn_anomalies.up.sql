@@ -0,0 +1,18 @@
+-- Blocks and 5 minute windows whose burn is far from the recent mean, see `burn_anomalies`.
+CREATE TABLE
+  burn_anomalies (
+    -- block or window_5m.
+    kind text NOT NULL,
+    -- The block, or for windows, the last block in the window.
+    block_number integer NOT NULL,
+    block_hash text NOT NULL,
+    -- The block timestamp, or for windows, the start of the window.
+    timestamp timestamptz NOT NULL,
+    burn_wei NUMERIC(78, 0) NOT NULL,
+    mean_burn_wei float8 NOT NULL,
+    stddev_burn_wei float8 NOT NULL,
+    z_score float8 NOT NULL,
+    CONSTRAINT burn_anomalies_pkey PRIMARY KEY (kind, block_number)
+  );
+
+CREATE INDEX burn_anomalies_block_number_idx ON burn_anomalies (block_number);
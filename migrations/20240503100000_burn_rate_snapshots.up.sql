@@ -0,0 +1,13 @@
+-- The burn rate per time frame as of the last block in each hour, see `burn_rates`.
+CREATE TABLE
+  burn_rate_snapshots (
+    time_frame text NOT NULL,
+    -- The start of the hour.
+    timestamp timestamptz NOT NULL,
+    block_number integer NOT NULL,
+    eth_per_minute float8 NOT NULL,
+    usd_per_minute float8 NOT NULL,
+    CONSTRAINT burn_rate_snapshots_pkey PRIMARY KEY (time_frame, timestamp)
+  );
+
+CREATE INDEX burn_rate_snapshots_block_number_idx ON burn_rate_snapshots (block_number);
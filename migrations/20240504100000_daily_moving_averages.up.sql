@@ -0,0 +1,18 @@
+-- Burn and issuance per completed day, with their 7 and 30 day moving averages, see
+-- `moving_averages`.
+CREATE TABLE
+  daily_moving_averages (
+    -- The start of the day.
+    timestamp timestamptz NOT NULL,
+    -- The last block of the day.
+    block_number integer NOT NULL,
+    burn_eth float8 NOT NULL,
+    burn_ma_7d float8,
+    burn_ma_30d float8,
+    issuance_eth float8,
+    issuance_ma_7d float8,
+    issuance_ma_30d float8,
+    CONSTRAINT daily_moving_averages_pkey PRIMARY KEY (timestamp)
+  );
+
+CREATE INDEX daily_moving_averages_block_number_idx ON daily_moving_averages (block_number);
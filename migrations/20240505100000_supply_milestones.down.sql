@@ -0,0 +1,3 @@
+DROP INDEX eth_supply_supply_idx;
+
+DROP TABLE supply_milestones;
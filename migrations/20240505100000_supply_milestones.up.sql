@@ -0,0 +1,25 @@
+-- Moments the supply crossed a round number or reached a new high or low, see
+-- `supply_milestones`.
+CREATE TABLE
+  supply_milestones (
+    -- all_time_high, crossed_above, crossed_below or post_merge_low.
+    kind text NOT NULL,
+    slot integer NOT NULL,
+    block_number integer NOT NULL,
+    block_hash text NOT NULL,
+    timestamp timestamptz NOT NULL,
+    supply_wei NUMERIC(78, 0) NOT NULL,
+    -- The round number crossed, only for crossings.
+    threshold_wei NUMERIC(78, 0),
+    CONSTRAINT supply_milestones_pkey PRIMARY KEY (kind, slot)
+  );
+
+-- Each round number is recorded the first time it's crossed in either direction.
+CREATE UNIQUE INDEX supply_milestones_threshold_idx ON supply_milestones (kind, threshold_wei)
+WHERE
+  threshold_wei IS NOT NULL;
+
+CREATE INDEX supply_milestones_slot_idx ON supply_milestones (slot);
+
+-- Finding the highest and lowest supply so far would otherwise scan every slot since the merge.
+CREATE INDEX eth_supply_supply_idx ON eth_supply (supply);
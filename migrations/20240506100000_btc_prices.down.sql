@@ -0,0 +1 @@
+DROP TABLE btc_prices;
@@ -0,0 +1,6 @@
+-- Minute BTCUSD prices, see `btc_price`.
+CREATE TABLE
+  btc_prices (
+    timestamp timestamptz PRIMARY KEY,
+    btcusd float8 NOT NULL
+  );
@@ -0,0 +1,13 @@
+-- ETH and BTC market caps as of the last block of each day, see `market_caps`.
+CREATE TABLE
+  market_cap_snapshots (
+    -- The start of the day.
+    timestamp timestamptz PRIMARY KEY,
+    block_number integer NOT NULL,
+    btc_price float8 NOT NULL,
+    btc_supply float8 NOT NULL,
+    eth_price float8 NOT NULL,
+    eth_supply float8 NOT NULL
+  );
+
+CREATE INDEX market_cap_snapshots_block_number_idx ON market_cap_snapshots (block_number);
@@ -0,0 +1,2 @@
+ALTER TABLE eth_prices DROP COLUMN origin;
+ALTER TABLE eth_prices DROP COLUMN provider;
@@ -0,0 +1,4 @@
+-- Which source supplied each price, and how it got here, see `usd_price::store`. Prices stored
+-- before these were added have neither.
+ALTER TABLE eth_prices ADD COLUMN provider TEXT;
+ALTER TABLE eth_prices ADD COLUMN origin TEXT;
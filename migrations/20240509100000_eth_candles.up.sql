@@ -0,0 +1,11 @@
+-- 1min OHLC candles, and their hourly and daily rollups, see `usd_price::candles`.
+CREATE TABLE
+  eth_candles (
+    resolution TEXT NOT NULL,
+    timestamp timestamptz NOT NULL,
+    open float8 NOT NULL,
+    high float8 NOT NULL,
+    low float8 NOT NULL,
+    close float8 NOT NULL,
+    PRIMARY KEY (resolution, timestamp)
+  );
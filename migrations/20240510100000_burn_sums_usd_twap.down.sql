@@ -0,0 +1 @@
+ALTER TABLE burn_sums DROP COLUMN sum_usd_twap;
@@ -0,0 +1,5 @@
+-- Burn valued with each minute's candle, next to the closest price valuation in sum_usd, see
+-- `burn_sums`. Without candles both valuations are the same, which holds for every existing sum.
+ALTER TABLE burn_sums ADD COLUMN sum_usd_twap NUMERIC(28, 8);
+UPDATE burn_sums SET sum_usd_twap = sum_usd;
+ALTER TABLE burn_sums ALTER COLUMN sum_usd_twap SET NOT NULL;
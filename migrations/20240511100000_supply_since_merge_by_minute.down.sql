@@ -0,0 +1 @@
+DROP TABLE supply_since_merge_by_minute;
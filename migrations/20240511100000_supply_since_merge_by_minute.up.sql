@@ -0,0 +1,17 @@
+-- Last supply of every minute, kept up to date as slots are stored, see `eth_supply::since_merge`.
+CREATE TABLE
+  supply_since_merge_by_minute (
+    timestamp timestamptz PRIMARY KEY,
+    supply NUMERIC NOT NULL
+  );
+
+INSERT INTO
+  supply_since_merge_by_minute (timestamp, supply)
+SELECT DISTINCT
+  ON (DATE_TRUNC('minute', timestamp)) DATE_TRUNC('minute', timestamp),
+  supply
+FROM
+  eth_supply
+ORDER BY
+  DATE_TRUNC('minute', timestamp),
+  timestamp DESC;
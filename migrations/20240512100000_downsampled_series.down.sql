@@ -0,0 +1 @@
+DROP TABLE downsampled_series;
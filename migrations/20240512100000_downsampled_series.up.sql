@@ -0,0 +1,9 @@
+-- Hourly and daily rollups of raw time series, see `downsampling`.
+CREATE TABLE
+  downsampled_series (
+    series TEXT NOT NULL,
+    resolution TEXT NOT NULL,
+    timestamp timestamptz NOT NULL,
+    value NUMERIC NOT NULL,
+    PRIMARY KEY (series, resolution, timestamp)
+  );
@@ -0,0 +1 @@
+ALTER TABLE supply_since_merge_by_minute DROP COLUMN supply_less_pos_issuance;
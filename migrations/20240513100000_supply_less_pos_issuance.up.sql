@@ -0,0 +1,4 @@
+-- The supply minus beacon chain issuance since the merge, proof-of-work issuance is added to it
+-- when reading, see `eth_supply::since_merge`. Minutes already stored are filled in by
+-- `backfill-supply-less-pos-issuance`, which knows the network's merge.
+ALTER TABLE supply_since_merge_by_minute ADD COLUMN supply_less_pos_issuance NUMERIC;
@@ -0,0 +1,17 @@
+-- Last supply of every day, kept up to date as slots are stored, see `eth_supply::daily_deltas`.
+CREATE TABLE
+  supply_by_day (
+    timestamp timestamptz PRIMARY KEY,
+    supply NUMERIC NOT NULL
+  );
+
+INSERT INTO
+  supply_by_day (timestamp, supply)
+SELECT DISTINCT
+  ON (DATE_TRUNC('day', timestamp, 'UTC')) DATE_TRUNC('day', timestamp, 'UTC'),
+  supply
+FROM
+  eth_supply
+ORDER BY
+  DATE_TRUNC('day', timestamp, 'UTC'),
+  timestamp DESC;
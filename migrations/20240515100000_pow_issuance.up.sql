@@ -0,0 +1,9 @@
+-- Issuance of each proof-of-work block, see `execution_chain::pow_issuance`.
+CREATE TABLE
+  pow_issuance (
+    block_number INT PRIMARY KEY,
+    timestamp timestamptz NOT NULL,
+    issuance NUMERIC NOT NULL
+  );
+
+CREATE INDEX pow_issuance_timestamp_idx ON pow_issuance (timestamp);
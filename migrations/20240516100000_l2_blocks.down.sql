@@ -0,0 +1 @@
+DROP TABLE l2_blocks;
@@ -0,0 +1,15 @@
+-- Blocks of the L2 chains we follow, see `l2`.
+CREATE TABLE
+  l2_blocks (
+    chain TEXT NOT NULL,
+    number INT8 NOT NULL,
+    hash TEXT NOT NULL,
+    parent_hash TEXT NOT NULL,
+    timestamp timestamptz NOT NULL,
+    base_fee_per_gas INT8 NOT NULL,
+    gas_used INT8 NOT NULL,
+    l1_data_fee NUMERIC(78) NOT NULL,
+    PRIMARY KEY (chain, number)
+  );
+
+CREATE INDEX l2_blocks_timestamp_idx ON l2_blocks (timestamp);
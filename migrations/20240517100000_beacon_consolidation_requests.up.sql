@@ -0,0 +1,14 @@
+-- Consolidation requests included in beacon blocks since Electra, see `beacon_chain::consolidations`.
+CREATE TABLE
+  beacon_consolidation_requests (
+    slot INT NOT NULL,
+    request_index INT NOT NULL,
+    timestamp timestamptz NOT NULL,
+    source_address TEXT NOT NULL,
+    source_pubkey TEXT NOT NULL,
+    target_pubkey TEXT NOT NULL,
+    amount INT8,
+    PRIMARY KEY (slot, request_index)
+  );
+
+CREATE INDEX beacon_consolidation_requests_timestamp_idx ON beacon_consolidation_requests (timestamp);
@@ -0,0 +1,10 @@
+-- Justified and finalized checkpoints of every synced state, see `beacon_chain::finality`.
+CREATE TABLE
+  beacon_finality (
+    slot INT PRIMARY KEY,
+    timestamp timestamptz NOT NULL,
+    justified_epoch INT NOT NULL,
+    finalized_epoch INT NOT NULL,
+    finalized_block_root TEXT NOT NULL,
+    finality_lag INT NOT NULL
+  );
@@ -0,0 +1,3 @@
+DROP TABLE beacon_validator_pubkeys;
+
+DROP TABLE beacon_validator_rewards;
@@ -0,0 +1,21 @@
+-- Consensus rewards per validator, summed over windows of epochs, see
+-- `beacon_chain::rewards`.
+CREATE TABLE
+  beacon_validator_rewards (
+    validator_index INT NOT NULL,
+    first_epoch INT NOT NULL,
+    last_epoch INT NOT NULL,
+    attestation INT8 NOT NULL,
+    proposer INT8 NOT NULL,
+    sync_committee INT8 NOT NULL,
+    PRIMARY KEY (validator_index, first_epoch)
+  );
+
+CREATE INDEX beacon_validator_rewards_first_epoch_idx ON beacon_validator_rewards (first_epoch);
+
+-- Lets stakers look up their rewards by pubkey.
+CREATE TABLE
+  beacon_validator_pubkeys (
+    validator_index INT PRIMARY KEY,
+    pubkey TEXT NOT NULL UNIQUE
+  );
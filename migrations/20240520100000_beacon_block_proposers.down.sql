@@ -0,0 +1,2 @@
+DROP TABLE validator_entities;
+DROP TABLE beacon_block_proposers;
@@ -0,0 +1,19 @@
+-- The proposer of every synced block and what it earned on the consensus layer, see
+-- `beacon_chain::proposers`.
+CREATE TABLE
+  beacon_block_proposers (
+    slot INT PRIMARY KEY,
+    timestamp timestamptz NOT NULL,
+    proposer_index INT NOT NULL,
+    consensus_reward INT8 NOT NULL,
+    block_hash TEXT
+  );
+
+CREATE INDEX beacon_block_proposers_timestamp_idx ON beacon_block_proposers (timestamp);
+
+-- Known operators of validators, imported by hand with `import-validator-entities`.
+CREATE TABLE
+  validator_entities (
+    validator_index INT PRIMARY KEY,
+    entity TEXT NOT NULL
+  );
@@ -0,0 +1,12 @@
+-- The graffiti of every synced block, with the client and pool recognized in it, see
+-- `beacon_chain::graffiti`.
+CREATE TABLE
+  beacon_block_graffiti (
+    slot INT PRIMARY KEY,
+    timestamp timestamptz NOT NULL,
+    graffiti TEXT NOT NULL,
+    client TEXT,
+    pool TEXT
+  );
+
+CREATE INDEX beacon_block_graffiti_timestamp_idx ON beacon_block_graffiti (timestamp);
@@ -0,0 +1,13 @@
+-- Payloads relays delivered to proposers, with the builder of each, see `censorship`.
+CREATE TABLE
+  relay_payloads (
+    relay TEXT NOT NULL,
+    slot INT NOT NULL,
+    timestamp timestamptz NOT NULL,
+    block_hash TEXT NOT NULL,
+    builder_pubkey TEXT NOT NULL,
+    PRIMARY KEY (relay, slot)
+  );
+
+CREATE INDEX relay_payloads_block_hash_idx ON relay_payloads (block_hash);
+CREATE INDEX relay_payloads_timestamp_idx ON relay_payloads (timestamp);
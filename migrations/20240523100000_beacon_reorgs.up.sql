@@ -0,0 +1,10 @@
+-- Every rollback of synced slots because the chain reorganized, see `beacon_chain::reorgs`. Not
+-- rolled back itself.
+CREATE TABLE
+  beacon_reorgs (
+    slot INT NOT NULL,
+    timestamp timestamptz NOT NULL,
+    depth INT NOT NULL
+  );
+
+CREATE INDEX beacon_reorgs_timestamp_idx ON beacon_reorgs (timestamp);
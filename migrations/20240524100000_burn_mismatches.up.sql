@@ -0,0 +1,10 @@
+-- Blocks whose burn doesn't match the burn recomputed from their receipts, see
+-- `execution_chain::receipt_burn`.
+CREATE TABLE
+  burn_mismatches (
+    block_number INT PRIMARY KEY,
+    block_hash TEXT NOT NULL,
+    timestamp timestamptz NOT NULL,
+    block_burn NUMERIC NOT NULL,
+    receipt_burn NUMERIC NOT NULL
+  );
@@ -0,0 +1 @@
+DROP INDEX transactions_blob_idx;
@@ -0,0 +1,2 @@
+-- Blob transaction stats only look at type 3 transactions, see `blob_transactions`.
+CREATE INDEX transactions_blob_idx ON transactions (block_number) WHERE type = 3;
@@ -0,0 +1,13 @@
+-- Contract creation transactions of synced blocks, see `contract_deployments`. Only populated when
+-- transaction ingestion is enabled.
+CREATE TABLE
+  contract_deployments (
+    block_number INT NOT NULL,
+    transaction_index INT NOT NULL,
+    timestamp timestamptz NOT NULL,
+    deployer TEXT NOT NULL,
+    contract_address TEXT,
+    PRIMARY KEY (block_number, transaction_index)
+  );
+
+CREATE INDEX contract_deployments_timestamp_idx ON contract_deployments (timestamp);
@@ -0,0 +1,3 @@
+-- From the receipt, NULL for transactions stored before. Priority fees are derived from it, see
+-- `fee_suggestions`.
+ALTER TABLE transactions ADD COLUMN effective_gas_price NUMERIC(78, 0);
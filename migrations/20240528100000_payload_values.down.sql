@@ -0,0 +1 @@
+DROP TABLE payload_values;
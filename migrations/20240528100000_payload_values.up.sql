@@ -0,0 +1,18 @@
+-- Relay bids of MEV blocks next to the priority fees of their transactions, see
+-- `mev_blocks::payload_values`.
+CREATE TABLE
+  payload_values (
+    block_hash TEXT PRIMARY KEY,
+    block_number INT NOT NULL,
+    slot INT NOT NULL,
+    timestamp timestamptz NOT NULL,
+    bid_wei NUMERIC(78, 0) NOT NULL,
+    tips_wei NUMERIC(78, 0) NOT NULL,
+    delta_wei NUMERIC(78, 0) GENERATED ALWAYS AS (tips_wei - bid_wei) STORED
+  );
+
+CREATE INDEX payload_values_block_number_idx ON payload_values (block_number);
+CREATE INDEX payload_values_timestamp_idx ON payload_values (timestamp);
+
+-- The endpoint serves the largest deltas first.
+CREATE INDEX payload_values_delta_wei_idx ON payload_values (delta_wei DESC);
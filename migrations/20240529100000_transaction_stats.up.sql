@@ -0,0 +1,27 @@
+-- Transaction counts and transfer volume per synced block, see `transaction_stats`. Summing these
+-- is much cheaper than scanning `transactions` for every time frame on every block.
+CREATE TABLE
+  transaction_stats (
+    block_number INT PRIMARY KEY,
+    timestamp timestamptz NOT NULL,
+    transaction_count INT NOT NULL,
+    transfer_count INT NOT NULL,
+    transfer_volume_wei NUMERIC(78, 0) NOT NULL
+  );
+
+CREATE INDEX transaction_stats_timestamp_idx ON transaction_stats (timestamp);
+
+INSERT INTO
+  transaction_stats
+SELECT
+  blocks_next.number,
+  blocks_next.timestamp,
+  COUNT(*),
+  COUNT(*) FILTER (WHERE transactions.input_selector IS NULL AND transactions.to_address IS NOT NULL AND transactions.value_wei > 0),
+  COALESCE(SUM(transactions.value_wei) FILTER (WHERE transactions.input_selector IS NULL AND transactions.to_address IS NOT NULL AND transactions.value_wei > 0), 0)
+FROM
+  transactions
+  JOIN blocks_next ON blocks_next.number = transactions.block_number
+GROUP BY
+  blocks_next.number,
+  blocks_next.timestamp;
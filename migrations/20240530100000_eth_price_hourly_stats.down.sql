@@ -0,0 +1 @@
+DROP TABLE eth_price_hourly_stats;
@@ -0,0 +1,13 @@
+-- Price aggregates per completed hour, see `usd_price::stats`. Time frame stats combine these with
+-- the prices of the partial hours at either end, instead of scanning every price in the frame.
+CREATE TABLE
+  eth_price_hourly_stats (
+    timestamp timestamptz PRIMARY KEY,
+    first FLOAT8 NOT NULL,
+    last FLOAT8 NOT NULL,
+    high FLOAT8 NOT NULL,
+    low FLOAT8 NOT NULL,
+    return_count INT8 NOT NULL,
+    return_sum FLOAT8 NOT NULL,
+    return_square_sum FLOAT8 NOT NULL
+  );
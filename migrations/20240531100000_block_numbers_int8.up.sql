@@ -0,0 +1,100 @@
+-- Block numbers are stored as INT8, matching `execution_chain::BlockNumber`.
+--
+-- The type of a partition key can't be altered. blocks_next is rebuilt by detaching every
+-- partition, altering it on its own, and attaching it to a new blocks_next with the same bounds.
+-- This rewrites each partition once rather than copying all blocks.
+
+ALTER TABLE burn_sums DROP CONSTRAINT burn_sums_first_included_block_number_fkey;
+ALTER TABLE burn_sums DROP CONSTRAINT burn_sums_last_included_block_fkey;
+ALTER TABLE burn_records DROP CONSTRAINT burn_records_block_fkey;
+ALTER TABLE transactions DROP CONSTRAINT transactions_block_fkey;
+
+-- Dropped here so partitions are altered without their indexes, and recreated on the new
+-- blocks_next below.
+DROP TRIGGER blocks_next_hashes_sync ON blocks_next;
+ALTER TABLE blocks_next DROP CONSTRAINT blocks_next_pkey;
+ALTER TABLE blocks_next DROP CONSTRAINT blocks_next_hash_number_key;
+ALTER TABLE blocks_next DROP CONSTRAINT blocks_next_parent_hash_number_key;
+DROP INDEX blocks_next_timestamp_idx;
+DROP INDEX blocks_next_base_fee_per_gas_idx;
+
+CREATE TEMPORARY TABLE blocks_next_partition_bounds ON COMMIT DROP AS
+SELECT
+  partition.relname AS partition_name,
+  pg_get_expr(partition.relpartbound, partition.oid) AS bound
+FROM
+  pg_inherits
+JOIN pg_class partition ON partition.oid = pg_inherits.inhrelid
+WHERE
+  pg_inherits.inhparent = 'blocks_next'::regclass;
+
+DO $$
+DECLARE
+  partition_name TEXT;
+BEGIN
+  FOR partition_name IN SELECT bounds.partition_name FROM blocks_next_partition_bounds bounds LOOP
+    EXECUTE format('ALTER TABLE blocks_next DETACH PARTITION %I', partition_name);
+    EXECUTE format('ALTER TABLE %I ALTER COLUMN number TYPE INT8', partition_name);
+  END LOOP;
+END $$;
+
+DROP TABLE blocks_next;
+
+-- Partitions start at block 0, blocks_next_p0 always exists.
+CREATE TABLE blocks_next (
+  LIKE blocks_next_p0 INCLUDING DEFAULTS
+) PARTITION BY RANGE (number);
+
+DO $$
+DECLARE
+  partition_name TEXT;
+  bound TEXT;
+BEGIN
+  FOR partition_name, bound IN
+    SELECT bounds.partition_name, bounds.bound FROM blocks_next_partition_bounds bounds
+  LOOP
+    EXECUTE format('ALTER TABLE blocks_next ATTACH PARTITION %I %s', partition_name, bound);
+  END LOOP;
+END $$;
+
+ALTER TABLE blocks_next ADD PRIMARY KEY (number);
+ALTER TABLE blocks_next ADD CONSTRAINT blocks_next_hash_number_key UNIQUE (hash, number);
+ALTER TABLE blocks_next ADD CONSTRAINT blocks_next_parent_hash_number_key UNIQUE (parent_hash, number);
+
+CREATE INDEX blocks_next_timestamp_idx ON blocks_next (timestamp);
+CREATE INDEX blocks_next_base_fee_per_gas_idx ON blocks_next (base_fee_per_gas);
+
+CREATE TRIGGER blocks_next_hashes_sync
+  AFTER INSERT OR UPDATE OF hash, parent_hash OR DELETE ON blocks_next
+  FOR EACH ROW EXECUTE FUNCTION sync_blocks_next_hashes();
+
+ALTER TABLE blocks ALTER COLUMN number TYPE INT8;
+ALTER TABLE burn_anomalies ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE burn_mismatches ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE burn_rate_snapshots ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE burn_records ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE burn_sums
+  ALTER COLUMN first_included_block_number TYPE INT8,
+  ALTER COLUMN last_included_block_number TYPE INT8;
+ALTER TABLE contract_deployments ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE daily_moving_averages ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE deposit_events ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE eth_supply ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE execution_supply ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE execution_supply_deltas ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE market_cap_snapshots ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE mev_blocks ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE payload_values ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE pow_issuance ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE supply_milestones ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE transaction_stats ALTER COLUMN block_number TYPE INT8;
+ALTER TABLE transactions ALTER COLUMN block_number TYPE INT8;
+
+ALTER TABLE burn_sums ADD CONSTRAINT burn_sums_first_included_block_number_fkey
+  FOREIGN KEY (first_included_block_number) REFERENCES blocks_next (number);
+ALTER TABLE burn_sums ADD CONSTRAINT burn_sums_last_included_block_fkey
+  FOREIGN KEY (last_included_block_hash, last_included_block_number) REFERENCES blocks_next (hash, number);
+ALTER TABLE burn_records ADD CONSTRAINT burn_records_block_fkey
+  FOREIGN KEY (block_hash, block_number) REFERENCES blocks_next (hash, number);
+ALTER TABLE transactions ADD CONSTRAINT transactions_block_fkey
+  FOREIGN KEY (block_hash, block_number) REFERENCES blocks_next (hash, number);
@@ -0,0 +1,563 @@
+//! # Analysis
+//! Everything we derive from the chain. Each analysis implements `Analysis` and gets registered in
+//! a list. The sync loops call every registered analysis in order, instead of knowing about each
+//! one, both for new blocks and for rollbacks.
+//!
+//! Analyses run once we're in sync with the chain. They derive their values from stored data, so
+//! running one again for the same block is safe. The tables the syncs maintain in the transaction
+//! storing a block or slot, like burn sums and the eth supply, are registered too, so a reorg
+//! rolls them back with everything else.
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{Acquire, PgConnection, PgPool};
+use tracing::{debug, warn};
+
+#[cfg(feature = "nats")]
+use crate::block_events::BlockEventsPublisher;
+
+use crate::{
+    analytics_sinks::AnalyticsSinksRollback,
+    beacon_chain::{BeaconChainRollback, IssuanceStorePostgres},
+    blob_transactions, blob_usage, burn_anomalies, burn_rates,
+    burn_records::BurnRecordsRollback,
+    burn_sums::{BurnSums, BurnSumsRollback},
+    config,
+    contract_deployments::{self, ContractDeploymentsRollback},
+    eth_supply::{self, EthSupplyRollback, SupplyByDayRollback, SupplySinceMergeRollback},
+    execution_chain::{
+        base_fees, BurnMismatchesRollback, DepositEventsRollback, ExecutionChainRollback,
+        ExecutionNodeBlock, TransactionsRollback,
+    },
+    fee_suggestions, gas_utilization, gauges,
+    market_caps::{self, MarketCapsTracker},
+    mev_blocks::PayloadValuesRollback,
+    moving_averages,
+    performance::TimedExt,
+    rollback::RollbackPoint,
+    supply_milestones::SupplyMilestonesRollback,
+    transaction_stats::{self, TransactionStatsRollback},
+    units::EthNewtype,
+    usd_price::{self, EthPriceStorePostgres},
+    webhooks::Webhooks,
+};
+
+pub struct NewBlockContext<'a> {
+    pub block: &'a ExecutionNodeBlock,
+    pub burn_sums: &'a BurnSums,
+    pub db_pool: &'a PgPool,
+    pub eth_price_store: &'a EthPriceStorePostgres,
+    pub issuance_store: &'a IssuanceStorePostgres,
+}
+
+#[async_trait]
+pub trait Analysis: Send + Sync {
+    /// Used in logs and timings.
+    fn name(&self) -> &'static str;
+
+    /// Tables maintained in the transaction storing the block have nothing left to do here.
+    async fn on_new_block(&self, _context: &NewBlockContext<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Deletes whatever the analysis stored for the chain past the rollback point. Runs in the
+    /// same transaction as the rollback of every other analysis. Analyses ignore points which
+    /// don't apply to the data they store, e.g. a block number analysis ignores slots. Analyses
+    /// which only publish cache values have nothing to roll back, the next block overwrites them.
+    async fn on_rollback(&self, _transaction: &mut PgConnection, _point: &RollbackPoint) {}
+
+    /// Computes whatever the analysis is missing for already stored blocks. Called once when the
+    /// sync starts.
+    async fn backfill(&self, _db_pool: &PgPool) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Analyses {
+    analyses: Vec<Box<dyn Analysis>>,
+}
+
+impl Analyses {
+    /// Analyses run in the order they were registered. Register data before the data it
+    /// references, so rollback deletes don't violate foreign keys.
+    pub fn register(mut self, analysis: impl Analysis + 'static) -> Self {
+        self.analyses.push(Box::new(analysis));
+        self
+    }
+
+    /// A failing analysis is logged and skipped, it shouldn't keep the others from running.
+    pub async fn on_new_block(&self, context: &NewBlockContext<'_>) {
+        for analysis in self.analyses.iter() {
+            let name = analysis.name();
+            debug!(name, "running analysis on new block");
+            analysis
+                .on_new_block(context)
+                .timed(&format!("{name}::on_new_block"))
+                .await
+                .unwrap_or_else(|err| warn!("{name}::on_new_block failed: {err}"));
+        }
+    }
+
+    /// Rolls back every analysis in a single transaction, either everything is rolled back, or
+    /// nothing is.
+    pub async fn rollback(
+        &self,
+        connection: &mut PgConnection,
+        point: &RollbackPoint,
+    ) -> Result<()> {
+        debug!(?point, "rolling back");
+
+        let mut transaction = connection.begin().await?;
+
+        for analysis in self.analyses.iter() {
+            debug!(name = analysis.name(), ?point, "rolling back analysis");
+            analysis.on_rollback(&mut transaction, point).await;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn backfill(&self, db_pool: &PgPool) {
+        for analysis in self.analyses.iter() {
+            let name = analysis.name();
+            debug!(name, "backfilling analysis");
+            analysis
+                .backfill(db_pool)
+                .await
+                .unwrap_or_else(|err| warn!("{name}::backfill failed: {err}"));
+        }
+    }
+}
+
+pub struct BaseFeesAnalysis;
+
+#[async_trait]
+impl Analysis for BaseFeesAnalysis {
+    fn name(&self) -> &'static str {
+        "base_fees"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        base_fees::on_new_block(context.db_pool, context.issuance_store, context.block).await;
+        Ok(())
+    }
+}
+
+pub struct BlobUsageAnalysis;
+
+#[async_trait]
+impl Analysis for BlobUsageAnalysis {
+    fn name(&self) -> &'static str {
+        "blob_usage"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        blob_usage::on_new_block(context.db_pool, context.block).await;
+        Ok(())
+    }
+}
+
+pub struct BlobTransactionsAnalysis;
+
+#[async_trait]
+impl Analysis for BlobTransactionsAnalysis {
+    fn name(&self) -> &'static str {
+        "blob_transactions"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        blob_transactions::on_new_block(context.db_pool, context.block).await;
+        Ok(())
+    }
+}
+
+pub struct ContractDeploymentsAnalysis;
+
+#[async_trait]
+impl Analysis for ContractDeploymentsAnalysis {
+    fn name(&self) -> &'static str {
+        "contract_deployments"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        contract_deployments::on_new_block(context.db_pool, context.block).await;
+        Ok(())
+    }
+}
+
+pub struct FeeSuggestionsAnalysis;
+
+#[async_trait]
+impl Analysis for FeeSuggestionsAnalysis {
+    fn name(&self) -> &'static str {
+        "fee_suggestions"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        fee_suggestions::on_new_block(context.db_pool, context.block).await;
+        Ok(())
+    }
+}
+
+pub struct TransactionStatsAnalysis;
+
+#[async_trait]
+impl Analysis for TransactionStatsAnalysis {
+    fn name(&self) -> &'static str {
+        "transaction_stats"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        transaction_stats::on_new_block(context.db_pool, context.block).await;
+        Ok(())
+    }
+}
+
+pub struct GasUtilizationAnalysis;
+
+#[async_trait]
+impl Analysis for GasUtilizationAnalysis {
+    fn name(&self) -> &'static str {
+        "gas_utilization"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        gas_utilization::on_new_block(context.db_pool, context.block).await;
+        Ok(())
+    }
+}
+
+pub struct BurnAnomaliesAnalysis;
+
+#[async_trait]
+impl Analysis for BurnAnomaliesAnalysis {
+    fn name(&self) -> &'static str {
+        "burn_anomalies"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        burn_anomalies::on_new_block(context.db_pool, context.block).await;
+        Ok(())
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            burn_anomalies::delete_burn_anomalies(transaction, block_number_gte).await;
+        }
+    }
+}
+
+pub struct BurnRatesAnalysis;
+
+#[async_trait]
+impl Analysis for BurnRatesAnalysis {
+    fn name(&self) -> &'static str {
+        "burn_rates"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        burn_rates::on_new_block(context.db_pool, context.burn_sums).await;
+        Ok(())
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            burn_rates::delete_snapshots(transaction, block_number_gte).await;
+        }
+    }
+}
+
+pub struct MovingAveragesAnalysis;
+
+#[async_trait]
+impl Analysis for MovingAveragesAnalysis {
+    fn name(&self) -> &'static str {
+        "moving_averages"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        moving_averages::on_new_block(context.db_pool, context.block).await;
+        Ok(())
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            moving_averages::delete_days(transaction, block_number_gte).await;
+        }
+    }
+
+    /// Starting from London takes a while, better done before we follow the head.
+    async fn backfill(&self, db_pool: &PgPool) -> Result<()> {
+        moving_averages::update_missing_days(db_pool, &Utc::now()).await;
+        Ok(())
+    }
+}
+
+pub struct GaugesAnalysis;
+
+#[async_trait]
+impl Analysis for GaugesAnalysis {
+    fn name(&self) -> &'static str {
+        "gauges"
+    }
+
+    /// The average prices the gauges need are published from here too, so they're only
+    /// calculated once per block.
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        let average_prices =
+            usd_price::get_average_prices(context.eth_price_store, context.block).await;
+        usd_price::publish_average_prices(context.db_pool, &average_prices).await;
+
+        let eth_supply: EthNewtype = eth_supply::last_eth_supply(context.db_pool).await.into();
+        gauges::on_new_block(
+            context.db_pool,
+            &average_prices,
+            context.issuance_store,
+            context.block,
+            context.burn_sums,
+            &eth_supply,
+        )
+        .await
+    }
+}
+
+pub struct EthPriceStatsAnalysis;
+
+#[async_trait]
+impl Analysis for EthPriceStatsAnalysis {
+    fn name(&self) -> &'static str {
+        "eth_price_stats"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        usd_price::update_time_frame_stats(context.db_pool, context.block).await;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MarketCapsAnalysis(MarketCapsTracker);
+
+#[async_trait]
+impl Analysis for MarketCapsAnalysis {
+    fn name(&self) -> &'static str {
+        "market_caps"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        self.0
+            .on_new_block(context.db_pool, context.eth_price_store, context.block)
+            .await
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            market_caps::delete_snapshots(transaction, block_number_gte).await;
+        }
+    }
+}
+
+pub struct WebhooksAnalysis(Webhooks);
+
+#[async_trait]
+impl Analysis for WebhooksAnalysis {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        self.0.on_new_block(context.db_pool, context.block).await
+    }
+}
+
+#[cfg(feature = "nats")]
+pub struct BlockEventsAnalysis(BlockEventsPublisher);
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl Analysis for BlockEventsAnalysis {
+    fn name(&self) -> &'static str {
+        "block_events"
+    }
+
+    async fn on_new_block(&self, context: &NewBlockContext<'_>) -> Result<()> {
+        self.0.on_new_block(context.db_pool, context.block).await
+    }
+}
+
+/// The tables the syncs maintain as they store blocks and slots, they only need rolling back.
+fn stored_chain_analyses() -> Analyses {
+    Analyses::default()
+        .register(BurnSumsRollback)
+        .register(BurnRecordsRollback)
+        .register(TransactionsRollback)
+        .register(BurnMismatchesRollback)
+        .register(ContractDeploymentsRollback)
+        .register(TransactionStatsRollback)
+        .register(PayloadValuesRollback)
+        .register(DepositEventsRollback)
+        .register(ExecutionChainRollback)
+        .register(SupplyMilestonesRollback)
+        .register(EthSupplyRollback)
+        .register(SupplySinceMergeRollback)
+        .register(SupplyByDayRollback)
+        .register(BeaconChainRollback)
+        .register(AnalyticsSinksRollback)
+}
+
+/// The stored chain tables and the analyses which only publish cached values, or store what they
+/// find idempotently. Safe to run again for a block we've seen, which is how warming caches
+/// republishes them. Everything there is to roll back is registered here.
+pub fn cache_analyses() -> Analyses {
+    let analyses = stored_chain_analyses()
+        .register(BaseFeesAnalysis)
+        .register(BlobUsageAnalysis)
+        .register(GasUtilizationAnalysis)
+        .register(BurnRatesAnalysis)
+        .register(BurnAnomaliesAnalysis)
+        .register(MovingAveragesAnalysis)
+        .register(GaugesAnalysis)
+        .register(EthPriceStatsAnalysis)
+        .register(MarketCapsAnalysis::default());
+
+    // Counted from stored transactions.
+    if config::CONFIG.ingest_transactions() {
+        analyses
+            .register(BlobTransactionsAnalysis)
+            .register(TransactionStatsAnalysis)
+            .register(ContractDeploymentsAnalysis)
+            .register(FeeSuggestionsAnalysis)
+    } else {
+        analyses
+    }
+}
+
+/// The analyses the execution sync runs by default. Publishing block events, and calling
+/// webhooks, only run when configured. These reach outside systems, running them again would
+/// repeat what they sent. They have nothing to roll back.
+pub fn built_in_analyses() -> Analyses {
+    let analyses = cache_analyses();
+
+    let analyses = match Webhooks::from_config() {
+        Some(webhooks) => analyses.register(WebhooksAnalysis(webhooks)),
+        None => analyses,
+    };
+
+    #[cfg(feature = "nats")]
+    let analyses = match BlockEventsPublisher::from_config() {
+        Some(publisher) => analyses.register(BlockEventsAnalysis(publisher)),
+        None => analyses,
+    };
+    #[cfg(not(feature = "nats"))]
+    if crate::config::CONFIG.nats_url().is_some() {
+        warn!(
+            "nats_url is configured but the nats feature is disabled, not publishing block events"
+        );
+    }
+
+    analyses
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::anyhow;
+    use test_context::test_context;
+
+    use crate::{
+        db::{self, tests::TestDb},
+        execution_chain::{BlockNumber, ExecutionNodeBlockBuilder},
+    };
+
+    use super::*;
+
+    struct RecordingAnalysis {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<&'static str>>>,
+        should_fail: bool,
+    }
+
+    #[async_trait]
+    impl Analysis for RecordingAnalysis {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn on_new_block(&self, _context: &NewBlockContext<'_>) -> Result<()> {
+            self.calls.lock().unwrap().push(self.name);
+            if self.should_fail {
+                Err(anyhow!("analysis failed"))
+            } else {
+                Ok(())
+            }
+        }
+
+        async fn on_rollback(&self, _transaction: &mut PgConnection, _point: &RollbackPoint) {
+            self.calls.lock().unwrap().push(self.name);
+        }
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn on_new_block_runs_all_analyses_in_order_test(test_db: &TestDb) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let analyses = Analyses::default()
+            .register(RecordingAnalysis {
+                name: "first",
+                calls: calls.clone(),
+                should_fail: true,
+            })
+            .register(RecordingAnalysis {
+                name: "second",
+                calls: calls.clone(),
+                should_fail: false,
+            });
+
+        let block = ExecutionNodeBlockBuilder::new("analyses_in_order").build();
+        let burn_sums = BurnSums::new();
+        let eth_price_store = EthPriceStorePostgres::new(test_db.pool.clone());
+        let issuance_store = IssuanceStorePostgres::new(test_db.pool.clone());
+        let context = NewBlockContext {
+            block: &block,
+            burn_sums: &burn_sums,
+            db_pool: &test_db.pool,
+            eth_price_store: &eth_price_store,
+            issuance_store: &issuance_store,
+        };
+
+        analyses.on_new_block(&context).await;
+
+        assert_eq!(*calls.lock().unwrap(), vec!["first", "second"]);
+    }
+
+    #[tokio::test]
+    async fn rollback_runs_all_analyses_in_order_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let analyses = Analyses::default()
+            .register(RecordingAnalysis {
+                name: "first",
+                calls: calls.clone(),
+                should_fail: false,
+            })
+            .register(RecordingAnalysis {
+                name: "second",
+                calls: calls.clone(),
+                should_fail: false,
+            });
+
+        analyses
+            .rollback(
+                &mut connection,
+                &RollbackPoint::BlockNumbersGte(BlockNumber(0)),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(*calls.lock().unwrap(), vec!["first", "second"]);
+    }
+}
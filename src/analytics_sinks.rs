@@ -0,0 +1,525 @@
+//! # Analytics Sinks
+//! Periodically exports stored rows into a warehouse, so heavy ad-hoc analytics don't run on the
+//! production Postgres. Rows aren't sent as they're computed, the warehouse trails Postgres by up
+//! to an export interval. A sink is enabled by configuring it, `clickhouse_url` for ClickHouse,
+//! `bigquery_dataset` for BigQuery. Each exported table, `blocks_next` and `eth_supply`, goes into
+//! a warehouse table of the same name.
+//!
+//! The `export-analytics` job exports whatever was stored since its last run. Per sink and table
+//! it keeps a cursor, the last exported block number or slot, which only moves once the sink
+//! accepted the rows. A sink that's down, or a sync that's catching up, only delays the export.
+//! The first run starts from the latest stored row, older rows can be loaded with
+//! `export-dataset`.
+//!
+//! Rows are only ever inserted. A rollback moves the cursors back, and the rows replacing the
+//! rolled back ones are inserted with the same key and a later `exported_at`, keep the latest row
+//! per key. In ClickHouse a `ReplacingMergeTree(exported_at) ORDER BY number` table does this for
+//! you.
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use tracing::{debug, warn};
+
+use crate::{
+    analysis::Analysis,
+    config::{self, Config},
+    export::{BlockRow, DatasetRow, EthSupplyRow},
+    key_value_store,
+    rollback::RollbackPoint,
+};
+
+const EXPORT_BATCH_SIZE: i64 = 1_000;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A table we export, in the order its rows are stored.
+pub trait SinkDataset: DatasetRow + Serialize {
+    const TABLE: &'static str;
+    /// Unique and increasing in the order rows are stored.
+    const CURSOR_COLUMN: &'static str;
+
+    fn cursor(&self) -> i64;
+
+    /// The last cursor value left after the rollback, `None` when the rollback doesn't touch the
+    /// table.
+    fn cursor_after_rollback(point: &RollbackPoint) -> Option<i64>;
+}
+
+impl SinkDataset for BlockRow {
+    const TABLE: &'static str = "blocks_next";
+    const CURSOR_COLUMN: &'static str = "number";
+
+    fn cursor(&self) -> i64 {
+        self.number().0
+    }
+
+    fn cursor_after_rollback(point: &RollbackPoint) -> Option<i64> {
+        match point {
+            RollbackPoint::BlockNumbersGte(block_number) => Some(block_number.0 - 1),
+            RollbackPoint::Slot(_) | RollbackPoint::SlotsGte(_) => None,
+        }
+    }
+}
+
+impl SinkDataset for EthSupplyRow {
+    const TABLE: &'static str = "eth_supply";
+    const CURSOR_COLUMN: &'static str = "balances_slot";
+
+    fn cursor(&self) -> i64 {
+        self.balances_slot().into()
+    }
+
+    fn cursor_after_rollback(point: &RollbackPoint) -> Option<i64> {
+        match point {
+            RollbackPoint::BlockNumbersGte(_) => None,
+            RollbackPoint::Slot(slot) | RollbackPoint::SlotsGte(slot) => {
+                Some(i64::from(slot.0) - 1)
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SinkRow<'a, R> {
+    #[serde(flatten)]
+    row: &'a R,
+    exported_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait AnalyticsSink: Send + Sync {
+    /// Used in logs and cursor keys.
+    fn name(&self) -> &'static str;
+
+    async fn insert(&self, table: &str, rows: &[Value]) -> Result<()>;
+}
+
+/// Inserts through the ClickHouse HTTP interface. Credentials, when needed, go in the url, e.g.
+/// `https://clickhouse:8443/?user=analytics&password=secret`.
+pub struct ClickHouseSink {
+    client: reqwest::Client,
+    database: Option<String>,
+    url: String,
+}
+
+impl ClickHouseSink {
+    fn insert_query(&self, table: &str) -> String {
+        match &self.database {
+            Some(database) => format!("INSERT INTO {database}.{table} FORMAT JSONEachRow"),
+            None => format!("INSERT INTO {table} FORMAT JSONEachRow"),
+        }
+    }
+}
+
+fn json_each_row(rows: &[Value]) -> Result<String> {
+    let mut body = String::new();
+    for row in rows {
+        body.push_str(&serde_json::to_string(row)?);
+        body.push('\n');
+    }
+    Ok(body)
+}
+
+#[async_trait]
+impl AnalyticsSink for ClickHouseSink {
+    fn name(&self) -> &'static str {
+        "clickhouse"
+    }
+
+    async fn insert(&self, table: &str, rows: &[Value]) -> Result<()> {
+        let res = self
+            .client
+            .post(&self.url)
+            .query(&[
+                ("query", self.insert_query(table).as_str()),
+                // Timestamps are RFC 3339, which ClickHouse only parses in best effort mode.
+                ("date_time_input_format", "best_effort"),
+            ])
+            .body(json_each_row(rows)?)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "failed to insert into ClickHouse, status: {status}, body: {body}"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+const GCP_METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+#[derive(Deserialize)]
+struct AccessToken {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct InsertAllResponse {
+    #[serde(default, rename = "insertErrors")]
+    insert_errors: Vec<Value>,
+}
+
+/// Streams rows in through the BigQuery `insertAll` API. Authenticates as the service account of
+/// the machine we run on, through the GCP metadata server.
+pub struct BigQuerySink {
+    client: reqwest::Client,
+    dataset: String,
+    project: String,
+}
+
+impl BigQuerySink {
+    /// Takes a dataset reference like `my-project.analytics`.
+    fn new(client: reqwest::Client, dataset_reference: &str) -> Result<Self> {
+        match dataset_reference.split('.').collect::<Vec<_>>()[..] {
+            [project, dataset] => Ok(Self {
+                client,
+                dataset: dataset.to_string(),
+                project: project.to_string(),
+            }),
+            _ => Err(anyhow!(
+                "expected bigquery_dataset as project.dataset, got {dataset_reference}"
+            )),
+        }
+    }
+
+    fn insert_all_url(&self, table: &str) -> String {
+        format!(
+            "https://bigquery.googleapis.com/bigquery/v2/projects/{}/datasets/{}/tables/{table}/insertAll",
+            self.project, self.dataset
+        )
+    }
+
+    async fn get_access_token(&self) -> Result<String> {
+        let token = self
+            .client
+            .get(GCP_METADATA_TOKEN_URL)
+            .header("Metadata-Flavor", "Google")
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<AccessToken>()
+            .await?;
+        Ok(token.access_token)
+    }
+}
+
+fn insert_all_body(rows: &[Value]) -> Value {
+    json!({
+        "rows": rows
+            .iter()
+            .map(|row| json!({ "json": row }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+#[async_trait]
+impl AnalyticsSink for BigQuerySink {
+    fn name(&self) -> &'static str {
+        "bigquery"
+    }
+
+    async fn insert(&self, table: &str, rows: &[Value]) -> Result<()> {
+        let access_token = self.get_access_token().await?;
+        let res = self
+            .client
+            .post(self.insert_all_url(table))
+            .bearer_auth(access_token)
+            .json(&insert_all_body(rows))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<InsertAllResponse>()
+            .await?;
+
+        if !res.insert_errors.is_empty() {
+            return Err(anyhow!(
+                "failed to insert rows into BigQuery, errors: {:?}",
+                res.insert_errors
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn sinks_from_config(config: &Config) -> Vec<Box<dyn AnalyticsSink>> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap();
+    let mut sinks: Vec<Box<dyn AnalyticsSink>> = Vec::new();
+
+    if let Some(url) = config.clickhouse_url() {
+        sinks.push(Box::new(ClickHouseSink {
+            client: client.clone(),
+            database: config.clickhouse_database().map(str::to_string),
+            url: url.to_string(),
+        }));
+    }
+
+    if let Some(dataset_reference) = config.bigquery_dataset() {
+        let sink =
+            BigQuerySink::new(client, dataset_reference).unwrap_or_else(|err| panic!("{err}"));
+        sinks.push(Box::new(sink));
+    }
+
+    sinks
+}
+
+const CURSOR_KEY_PREFIX: &str = "analytics-sinks-cursor";
+
+fn cursor_key(sink: &str, table: &str) -> String {
+    format!("{CURSOR_KEY_PREFIX}-{sink}-{table}")
+}
+
+/// Moves the cursor from `previous` to `next`. Does nothing when a rollback moved the cursor in
+/// the meantime, the rows we exported get exported again.
+async fn advance_cursor(executor: impl PgExecutor<'_>, key: &str, previous: i64, next: i64) {
+    sqlx::query!(
+        "
+        UPDATE key_value_store
+        SET value = to_jsonb($3::INT8)
+        WHERE key = $1
+        AND value = to_jsonb($2::INT8)
+        ",
+        key,
+        previous,
+        next
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Without a cursor we start from the latest stored row.
+async fn get_or_init_cursor<D: SinkDataset>(db_pool: &PgPool, key: &str) -> Result<i64> {
+    if let Some(cursor) = key_value_store::get(db_pool, key).await? {
+        return Ok(cursor);
+    }
+
+    let cursor: Option<i64> = sqlx::query_scalar(&format!(
+        "SELECT MAX({})::INT8 FROM {}",
+        D::CURSOR_COLUMN,
+        D::TABLE
+    ))
+    .fetch_one(db_pool)
+    .await?;
+    let cursor = cursor.unwrap_or(-1);
+    key_value_store::set(db_pool, key, &cursor).await?;
+    Ok(cursor)
+}
+
+/// Exports rows past the cursor until the sink has them all, returns how many were exported.
+async fn export_dataset<D: SinkDataset>(db_pool: &PgPool, sink: &dyn AnalyticsSink) -> Result<u64> {
+    let key = cursor_key(sink.name(), D::TABLE);
+    let mut cursor = get_or_init_cursor::<D>(db_pool, &key).await?;
+    let query = format!(
+        "{} WHERE {column} > $1 ORDER BY {column} LIMIT $2",
+        D::SELECT,
+        column = D::CURSOR_COLUMN
+    );
+    let mut count = 0;
+
+    loop {
+        let rows = sqlx::query(&query)
+            .bind(cursor)
+            .bind(EXPORT_BATCH_SIZE)
+            .map(D::from_row)
+            .fetch_all(db_pool)
+            .await?;
+
+        let Some(last_row) = rows.last() else {
+            return Ok(count);
+        };
+        let next_cursor = last_row.cursor();
+
+        let exported_at = Utc::now();
+        let values = rows
+            .iter()
+            .map(|row| serde_json::to_value(SinkRow { row, exported_at }))
+            .collect::<Result<Vec<_>, _>>()?;
+        sink.insert(D::TABLE, &values).await?;
+
+        advance_cursor(db_pool, &key, cursor, next_cursor).await;
+        debug!(
+            sink = sink.name(),
+            table = D::TABLE,
+            cursor = next_cursor,
+            "exported rows"
+        );
+        cursor = next_cursor;
+        count += rows.len() as u64;
+    }
+}
+
+pub struct AnalyticsSinks {
+    sinks: Vec<Box<dyn AnalyticsSink>>,
+}
+
+impl AnalyticsSinks {
+    /// Returns `None` when no sink is configured.
+    pub fn from_config() -> Option<Self> {
+        let sinks = sinks_from_config(&config::CONFIG);
+        if sinks.is_empty() {
+            None
+        } else {
+            Some(Self { sinks })
+        }
+    }
+
+    /// A failing sink shouldn't keep the others from getting their rows, each is tried. Fails when
+    /// any of them did, the failed ones pick up from their cursor next run.
+    pub async fn export(&self, db_pool: &PgPool) -> Result<()> {
+        let mut failed = Vec::new();
+
+        for sink in self.sinks.iter() {
+            let name = sink.name();
+            let result = async {
+                export_dataset::<BlockRow>(db_pool, sink.as_ref()).await?;
+                export_dataset::<EthSupplyRow>(db_pool, sink.as_ref()).await
+            }
+            .await;
+
+            if let Err(err) = result {
+                warn!(sink = name, "failed to export rows: {err:#}");
+                failed.push(name);
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            bail!("failed to export rows to {}", failed.join(", "))
+        }
+    }
+}
+
+async fn rollback_cursors<D: SinkDataset>(transaction: &mut PgConnection, point: &RollbackPoint) {
+    if let Some(cursor) = D::cursor_after_rollback(point) {
+        sqlx::query!(
+            "
+            UPDATE key_value_store
+            SET value = to_jsonb($2::INT8)
+            WHERE key LIKE $1
+            AND (value #>> '{}')::INT8 > $2
+            ",
+            format!("{CURSOR_KEY_PREFIX}-%-{}", D::TABLE),
+            cursor
+        )
+        .execute(transaction)
+        .await
+        .unwrap();
+    }
+}
+
+/// Moves the cursors of every sink back, so the rows replacing the rolled back ones get exported.
+pub struct AnalyticsSinksRollback;
+
+#[async_trait]
+impl Analysis for AnalyticsSinksRollback {
+    fn name(&self) -> &'static str {
+        "analytics_sinks"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        rollback_cursors::<BlockRow>(transaction, point).await;
+        rollback_cursors::<EthSupplyRow>(transaction, point).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use crate::{beacon_chain::Slot, db, execution_chain::BlockNumber};
+
+    use super::*;
+
+    #[test]
+    fn parse_bigquery_dataset_test() {
+        let sink = BigQuerySink::new(reqwest::Client::new(), "my-project.analytics").unwrap();
+        assert_eq!(
+            sink.insert_all_url("blocks_next"),
+            "https://bigquery.googleapis.com/bigquery/v2/projects/my-project/datasets/analytics/tables/blocks_next/insertAll"
+        );
+        assert!(BigQuerySink::new(reqwest::Client::new(), "my-project.analytics.blocks").is_err());
+    }
+
+    #[test]
+    fn clickhouse_insert_query_test() {
+        let sink = ClickHouseSink {
+            client: reqwest::Client::new(),
+            database: Some("analytics".to_string()),
+            url: "http://localhost:8123".to_string(),
+        };
+        assert_eq!(
+            sink.insert_query("blocks_next"),
+            "INSERT INTO analytics.blocks_next FORMAT JSONEachRow"
+        );
+    }
+
+    #[tokio::test]
+    async fn rollback_cursors_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let blocks_key = cursor_key("test-sink", BlockRow::TABLE);
+        let supply_key = cursor_key("test-sink", EthSupplyRow::TABLE);
+        key_value_store::set(&mut *transaction, &blocks_key, &100)
+            .await
+            .unwrap();
+        key_value_store::set(&mut *transaction, &supply_key, &200)
+            .await
+            .unwrap();
+
+        AnalyticsSinksRollback
+            .on_rollback(
+                &mut transaction,
+                &RollbackPoint::BlockNumbersGte(BlockNumber(90)),
+            )
+            .await;
+        AnalyticsSinksRollback
+            .on_rollback(&mut transaction, &RollbackPoint::SlotsGte(Slot(250)))
+            .await;
+
+        let blocks_cursor: Option<i64> = key_value_store::get(&mut *transaction, &blocks_key)
+            .await
+            .unwrap();
+        let supply_cursor: Option<i64> = key_value_store::get(&mut *transaction, &supply_key)
+            .await
+            .unwrap();
+        assert_eq!(blocks_cursor, Some(89));
+        // Rows past the cursor haven't been exported yet, nothing to move back.
+        assert_eq!(supply_cursor, Some(200));
+    }
+
+    #[tokio::test]
+    async fn advance_cursor_after_rollback_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let key = cursor_key("test-sink", BlockRow::TABLE);
+        key_value_store::set(&mut *transaction, &key, &89)
+            .await
+            .unwrap();
+
+        // An export which read cursor 100 before the rollback moved it to 89.
+        advance_cursor(&mut *transaction, &key, 100, 200).await;
+        let cursor: Option<i64> = key_value_store::get(&mut *transaction, &key).await.unwrap();
+        assert_eq!(cursor, Some(89));
+
+        advance_cursor(&mut *transaction, &key, 89, 150).await;
+        let cursor: Option<i64> = key_value_store::get(&mut *transaction, &key).await.unwrap();
+        assert_eq!(cursor, Some(150));
+    }
+}
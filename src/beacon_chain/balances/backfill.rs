@@ -3,10 +3,15 @@ use pit_wall::Progress;
 use sqlx::PgPool;
 use tracing::{debug, info, warn};
 
-use crate::beacon_chain::{balances, node::BeaconNodeHttp, BeaconNode, Slot};
+use crate::{
+    beacon_chain::{
+        balances, node::BeaconNodeHttp, BeaconNode, Slot, FIRST_POST_LONDON_SLOT, SECONDS_PER_SLOT,
+        SLOTS_PER_EPOCH,
+    },
+    db, log,
+};
 
 const GET_BALANCES_CONCURRENCY_LIMIT: usize = 32;
-const SLOTS_PER_EPOCH: i64 = 32;
 
 pub enum Granularity {
     Day,
@@ -38,9 +43,9 @@ async fn estimate_work_todo(db_pool: &PgPool, granularity: &Granularity, from: &
 
     match granularity {
         Granularity::Slot => slots_count,
-        Granularity::Epoch => slots_count * SLOTS_PER_EPOCH,
-        Granularity::Hour => slots_count / 300,
-        Granularity::Day => slots_count / 7200,
+        Granularity::Epoch => slots_count / *SLOTS_PER_EPOCH as i64,
+        Granularity::Hour => slots_count * *SECONDS_PER_SLOT as i64 / (60 * 60),
+        Granularity::Day => slots_count * *SECONDS_PER_SLOT as i64 / (24 * 60 * 60),
     }
     .try_into()
     .unwrap()
@@ -142,3 +147,60 @@ pub async fn backfill_balances(db_pool: &PgPool, granularity: &Granularity, from
         info!("{}", progress.get_progress_string());
     }
 }
+
+async fn backfill_balances_with_name(
+    name: &str,
+    description: &str,
+    granularity: &Granularity,
+    from: &Slot,
+) {
+    log::init_with_env();
+
+    info!("backfilling {description}");
+
+    let db_pool = db::get_db_pool(name).await;
+
+    backfill_balances(&db_pool, granularity, from).await;
+
+    info!("done backfilling {description}");
+}
+
+pub async fn backfill_hourly_balances() {
+    backfill_balances_with_name(
+        "backfill-hourly-balances",
+        "hourly beacon balances",
+        &Granularity::Hour,
+        &Slot::GENESIS,
+    )
+    .await;
+}
+
+pub async fn backfill_balances_to_london() {
+    backfill_balances_with_name(
+        "backfill-balances-to-london",
+        "beacon balances to london",
+        &Granularity::Slot,
+        &FIRST_POST_LONDON_SLOT,
+    )
+    .await;
+}
+
+pub async fn backfill_daily_balances_to_london() {
+    backfill_balances_with_name(
+        "backfill-daily-balances-to-london",
+        "daily beacon balances to london",
+        &Granularity::Day,
+        &FIRST_POST_LONDON_SLOT,
+    )
+    .await;
+}
+
+pub async fn backfill_hourly_balances_to_london() {
+    backfill_balances_with_name(
+        "backfill-hourly-balances-to-london",
+        "hourly beacon balances to london",
+        &Granularity::Hour,
+        &FIRST_POST_LONDON_SLOT,
+    )
+    .await;
+}
@@ -1,16 +1,117 @@
-use futures::TryStreamExt;
-use pit_wall::Progress;
-use sqlx::postgres::PgPoolOptions;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use sqlx::{postgres::PgPoolOptions, PgPool};
 use tracing::{debug, info};
 
 use crate::{
-    beacon_chain::{blocks, node::BeaconNodeHttp, BeaconNode, FIRST_POST_MERGE_SLOT},
-    db, job_progress, key_value_store, log,
+    beacon_chain::{blocks, node::BeaconNodeHttp, BeaconNode, Slot, FIRST_POST_MERGE_SLOT},
+    db,
+    heal::{self, HealOptions, Healer},
+    log,
 };
 
 const HEAL_BLOCK_HASHES_KEY: &str = "heal-block-hashes";
 
+struct BlockHashesHealer {
+    beacon_node: BeaconNodeHttp,
+    db_pool: PgPool,
+}
+
+#[async_trait]
+impl Healer for BlockHashesHealer {
+    type Item = Slot;
+
+    fn name(&self) -> &'static str {
+        HEAL_BLOCK_HASHES_KEY
+    }
+
+    async fn items_to_heal(&self, checkpoint: Option<Slot>) -> Vec<Slot> {
+        // The checkpoint is the last slot we healed, resume after it.
+        let first_slot = checkpoint.map_or(*FIRST_POST_MERGE_SLOT, |slot| slot + 1);
+
+        sqlx::query!(
+            r#"
+            SELECT
+                slot AS "slot!"
+            FROM
+                beacon_blocks
+            JOIN beacon_states ON
+                beacon_blocks.state_root = beacon_states.state_root
+            WHERE
+                slot >= $1
+            ORDER BY
+                slot ASC
+            "#,
+            first_slot.0
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| Slot(row.slot))
+        .collect()
+    }
+
+    async fn heal_chunk(&self, slots: &[Slot], dry_run: bool) -> Result<()> {
+        let block_roots = sqlx::query!(
+            r#"
+            SELECT
+                block_root
+            FROM
+                beacon_blocks
+            JOIN beacon_states ON
+                beacon_blocks.state_root = beacon_states.state_root
+            WHERE
+                slot >= $1
+            AND
+                slot <= $2
+            ORDER BY
+                slot ASC
+            "#,
+            slots.first().unwrap().0,
+            slots.last().unwrap().0
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.block_root);
+
+        for block_root in block_roots {
+            let block = self
+                .beacon_node
+                .get_block_by_block_root(&block_root)
+                .await?
+                .ok_or_else(|| anyhow!("no block for historic block root {block_root}"))?;
+
+            let block_hash = block
+                .body
+                .execution_payload
+                .ok_or_else(|| anyhow!("no execution payload in post-merge block {block_root}"))?
+                .block_hash;
+
+            if dry_run {
+                debug!(
+                    block_root,
+                    block_hash, "dry run, skipping setting block hash"
+                );
+                continue;
+            }
+
+            debug!(block_root, block_hash, "setting block hash");
+
+            blocks::update_block_hash(&self.db_pool, &block_root, &block_hash).await;
+        }
+
+        Ok(())
+    }
+}
+
 pub async fn heal_block_hashes() {
+    heal_block_hashes_with_dry_run(heal::dry_run_from_args()).await;
+}
+
+pub async fn heal_block_hashes_with_dry_run(dry_run: bool) {
     log::init_with_env();
 
     info!("healing execution block hashes");
@@ -20,82 +121,17 @@ pub async fn heal_block_hashes() {
         .connect(&db::get_db_url_with_name("heal-beacon-states"))
         .await
         .unwrap();
-    let key_value_store = key_value_store::KeyValueStorePostgres::new(db_pool.clone());
-    let job_progress = job_progress::JobProgress::new(HEAL_BLOCK_HASHES_KEY, &key_value_store);
-
-    let beacon_node = BeaconNodeHttp::new();
-    let first_slot = job_progress.get().await.unwrap_or(FIRST_POST_MERGE_SLOT);
-
-    let work_todo = sqlx::query!(
-        r#"
-        SELECT
-            COUNT(*) AS "count!"
-        FROM
-            beacon_blocks
-        JOIN beacon_states ON
-            beacon_blocks.state_root = beacon_states.state_root
-        WHERE
-            slot >= $1
-        AND
-            block_hash IS NULL
-        "#,
-        first_slot.0
-    )
-    .fetch_one(&db_pool)
-    .await
-    .unwrap();
-
-    struct BlockSlotRow {
-        block_root: String,
-        slot: i32,
-    }
 
-    let mut rows = sqlx::query_as!(
-        BlockSlotRow,
-        r#"
-        SELECT
-            block_root,
-            slot AS "slot!"
-        FROM
-            beacon_blocks
-        JOIN beacon_states ON
-            beacon_blocks.state_root = beacon_states.state_root
-        WHERE
-            slot >= $1
-        "#,
-        first_slot.0
-    )
-    .fetch(&db_pool);
-
-    let mut progress = Progress::new("heal-block-hashes", work_todo.count.try_into().unwrap());
-
-    while let Some(row) = rows.try_next().await.unwrap() {
-        let block_root = row.block_root;
-        let slot = row.slot;
-
-        let block = beacon_node
-            .get_block_by_block_root(&block_root)
-            .await
-            .unwrap()
-            .expect("expect block to exist for historic block_root");
-
-        let block_hash = block
-            .body
-            .execution_payload
-            .expect("expect execution payload to exist for post-merge block")
-            .block_hash;
-
-        debug!(block_root, block_hash, "setting block hash");
-
-        blocks::update_block_hash(&db_pool, &block_root, &block_hash).await;
-
-        progress.inc_work_done();
-
-        if slot % 100 == 0 {
-            info!("{}", progress.get_progress_string());
-            job_progress.set(&slot.into()).await;
-        }
-    }
+    let healer = BlockHashesHealer {
+        beacon_node: BeaconNodeHttp::new(),
+        db_pool: db_pool.clone(),
+    };
+
+    let options = HealOptions {
+        chunk_size: 100,
+        dry_run,
+    };
+    heal::heal(&db_pool, &healer, &options).await.unwrap();
 
     info!("done healing beacon block hashes");
 }
@@ -11,6 +11,7 @@ use super::{
 };
 
 pub use heal::heal_block_hashes;
+pub use heal::heal_block_hashes_with_dry_run;
 
 pub const GENESIS_PARENT_ROOT: &str =
     "0x0000000000000000000000000000000000000000000000000000000000000000";
@@ -345,8 +346,12 @@ mod tests {
             &mut *transaction,
             &BeaconBlock {
                 body: BeaconBlockBody {
+                    blob_kzg_commitments: None,
                     deposits: vec![],
                     execution_payload: None,
+                    execution_requests: None,
+                    graffiti: format!("0x{}", "00".repeat(32)),
+                    sync_aggregate: None,
                 },
                 parent_root: GENESIS_PARENT_ROOT.to_string(),
                 slot,
@@ -487,11 +492,15 @@ mod tests {
             &header,
             &BeaconBlock {
                 body: BeaconBlockBody {
+                    blob_kzg_commitments: None,
                     deposits: vec![],
                     execution_payload: Some(ExecutionPayload {
                         block_hash: block_hash.clone(),
                         withdrawals: None,
                     }),
+                    execution_requests: None,
+                    graffiti: format!("0x{}", "00".repeat(32)),
+                    sync_aggregate: None,
                 },
                 parent_root: GENESIS_PARENT_ROOT.to_string(),
                 slot,
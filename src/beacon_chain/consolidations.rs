@@ -0,0 +1,262 @@
+//! # Consolidations
+//! Since Electra, see EIP-7251, a validator can hold up to 2048 ETH of effective balance. The
+//! execution layer requests a consolidation to move the balance of a source validator into a
+//! target validator, exiting the source. A request naming the same validator as source and target
+//! instead switches it to compounding withdrawal credentials, raising its max effective balance.
+//!
+//! Requests are stored as blocks include them. They're processed epochs later and may still fail,
+//! e.g. when the source has pending withdrawals, counts and amounts are of what was requested.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    caching::{self, CacheKey},
+    units::GweiNewtype,
+};
+
+use super::{
+    node::{BeaconBlock, BeaconNode, StateRoot},
+    Slot,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct Consolidation {
+    /// Effective balance of the source validator in the state of the including block. `None` when
+    /// the state doesn't know the source, such a request fails.
+    pub amount: Option<GweiNewtype>,
+    pub source_address: String,
+    pub source_pubkey: String,
+    pub target_pubkey: String,
+}
+
+/// Looks up the source validator of each consolidation request in the block.
+pub async fn get_consolidations(
+    beacon_node: &impl BeaconNode,
+    state_root: &StateRoot,
+    block: &BeaconBlock,
+) -> Result<Vec<Consolidation>> {
+    let mut consolidations = Vec::new();
+
+    for request in block.consolidation_requests() {
+        let amount = beacon_node
+            .get_validator_by_state(state_root, &request.source_pubkey)
+            .await?
+            .map(|validator| validator.effective_balance());
+
+        consolidations.push(Consolidation {
+            amount,
+            source_address: request.source_address.clone(),
+            source_pubkey: request.source_pubkey.clone(),
+            target_pubkey: request.target_pubkey.clone(),
+        });
+    }
+
+    Ok(consolidations)
+}
+
+pub async fn store_consolidations(
+    executor: &mut PgConnection,
+    slot: &Slot,
+    consolidations: &[Consolidation],
+) {
+    for (index, consolidation) in consolidations.iter().enumerate() {
+        sqlx::query!(
+            "
+            INSERT INTO beacon_consolidation_requests (
+                slot,
+                request_index,
+                timestamp,
+                source_address,
+                source_pubkey,
+                target_pubkey,
+                amount
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ",
+            slot.0,
+            index as i32,
+            slot.date_time(),
+            consolidation.source_address,
+            consolidation.source_pubkey,
+            consolidation.target_pubkey,
+            consolidation.amount.map(|amount| amount.0),
+        )
+        .execute(&mut *executor)
+        .await
+        .unwrap();
+    }
+}
+
+pub async fn delete_consolidations(executor: impl PgExecutor<'_>, slot: &Slot) {
+    sqlx::query!(
+        "DELETE FROM beacon_consolidation_requests WHERE slot = $1",
+        slot.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub async fn delete_consolidations_gte(executor: impl PgExecutor<'_>, slot: &Slot) {
+    sqlx::query!(
+        "DELETE FROM beacon_consolidation_requests WHERE slot >= $1",
+        slot.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsolidationsOnDay {
+    /// Summed effective balance of the sources of consolidations proper.
+    pub consolidated_amount: GweiNewtype,
+    /// Requests moving a balance between two validators.
+    pub consolidation_count: i64,
+    pub day: DateTime<Utc>,
+    /// Requests switching a validator to compounding withdrawal credentials.
+    pub switch_to_compounding_count: i64,
+}
+
+async fn get_consolidations_by_day(executor: impl PgExecutor<'_>) -> Vec<ConsolidationsOnDay> {
+    sqlx::query!(
+        r#"
+        SELECT
+            DATE_TRUNC('day', timestamp) AS "day!",
+            COUNT(*) FILTER (WHERE source_pubkey <> target_pubkey) AS "consolidation_count!",
+            COALESCE(
+                SUM(amount) FILTER (WHERE source_pubkey <> target_pubkey),
+                0
+            )::INT8 AS "consolidated_amount!",
+            COUNT(*) FILTER (WHERE source_pubkey = target_pubkey) AS "switch_to_compounding_count!"
+        FROM
+            beacon_consolidation_requests
+        GROUP BY 1
+        ORDER BY 1 ASC
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| ConsolidationsOnDay {
+        consolidated_amount: GweiNewtype(row.consolidated_amount),
+        consolidation_count: row.consolidation_count,
+        day: row.day,
+        switch_to_compounding_count: row.switch_to_compounding_count,
+    })
+    .collect()
+}
+
+pub async fn update_consolidations_by_day(db_pool: &PgPool) {
+    let consolidations_by_day = get_consolidations_by_day(db_pool).await;
+    caching::update_and_publish(
+        db_pool,
+        &CacheKey::ConsolidationsByDay,
+        &consolidations_by_day,
+    )
+    .await;
+    debug!("published consolidations by day");
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, DurationRound};
+    use test_context::test_context;
+
+    use crate::{
+        beacon_chain::{node::ConsolidationRequest, BeaconBlockBuilder, MockBeaconNode},
+        db::tests::TestDb,
+    };
+
+    use super::*;
+
+    fn test_consolidation(source_pubkey: &str, target_pubkey: &str, amount: i64) -> Consolidation {
+        Consolidation {
+            amount: Some(GweiNewtype(amount)),
+            source_address: "0xsource_address".to_string(),
+            source_pubkey: source_pubkey.to_string(),
+            target_pubkey: target_pubkey.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_consolidations_unknown_source_test() {
+        let block = BeaconBlockBuilder::default()
+            .consolidation_requests(vec![ConsolidationRequest {
+                source_address: "0xsource_address".to_string(),
+                source_pubkey: "0xsource".to_string(),
+                target_pubkey: "0xtarget".to_string(),
+            }])
+            .build();
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node
+            .expect_get_validator_by_state()
+            .returning(|_, _| Ok(None));
+
+        let consolidations = get_consolidations(&beacon_node, &"0xstate_root".to_string(), &block)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            consolidations,
+            vec![Consolidation {
+                amount: None,
+                source_address: "0xsource_address".to_string(),
+                source_pubkey: "0xsource".to_string(),
+                target_pubkey: "0xtarget".to_string(),
+            }]
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn consolidations_by_day_test(test_db: &TestDb) {
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        let slot = Slot(9_000_000);
+        store_consolidations(
+            &mut connection,
+            &slot,
+            &[
+                test_consolidation("0xa", "0xb", 32_000_000_000),
+                test_consolidation("0xc", "0xd", 64_000_000_000),
+                test_consolidation("0xe", "0xe", 32_000_000_000),
+            ],
+        )
+        .await;
+
+        let consolidations_by_day = get_consolidations_by_day(&mut *connection).await;
+
+        assert_eq!(
+            consolidations_by_day,
+            vec![ConsolidationsOnDay {
+                consolidated_amount: GweiNewtype(96_000_000_000),
+                consolidation_count: 2,
+                day: slot.date_time().duration_trunc(Duration::days(1)).unwrap(),
+                switch_to_compounding_count: 1,
+            }]
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn delete_consolidations_test(test_db: &TestDb) {
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        let consolidations = [test_consolidation("0xa", "0xb", 32_000_000_000)];
+        store_consolidations(&mut connection, &Slot(9_000_000), &consolidations).await;
+        store_consolidations(&mut connection, &Slot(9_000_001), &consolidations).await;
+
+        delete_consolidations(&mut *connection, &Slot(9_000_001)).await;
+        assert_eq!(
+            get_consolidations_by_day(&mut *connection).await[0].consolidation_count,
+            1
+        );
+
+        delete_consolidations_gte(&mut *connection, &Slot(9_000_000)).await;
+        assert!(get_consolidations_by_day(&mut *connection).await.is_empty());
+    }
+}
@@ -61,15 +61,42 @@ pub async fn get_deposits_sum_by_state_root(
     Ok(deposit_sum_aggregated)
 }
 
+/// The deposits included in blocks up to and including the last stored block. Deposits processed
+/// at genesis are not included.
+pub async fn get_last_deposits_sum(executor: impl PgExecutor<'_>) -> Option<BeaconDepositsSum> {
+    sqlx::query!(
+        "
+            SELECT
+                deposit_sum_aggregated,
+                slot
+            FROM
+                beacon_blocks
+            JOIN beacon_states ON
+                beacon_blocks.state_root = beacon_states.state_root
+            ORDER BY
+                slot DESC
+            LIMIT 1
+        "
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| BeaconDepositsSum {
+        deposits_sum: row.deposit_sum_aggregated.into(),
+        slot: Slot(row.slot),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use sqlx::Acquire;
+    use test_context::test_context;
 
     use crate::{
         beacon_chain::{
             store_block, store_state, BeaconBlockBuilder, BeaconHeaderSignedEnvelopeBuilder,
         },
-        db,
+        db::{self, tests::TestDb},
     };
 
     use super::*;
@@ -108,4 +135,42 @@ mod tests {
 
         assert_eq!(GweiNewtype(1), deposits_sum);
     }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn get_last_deposits_sum_test(test_db: &TestDb) {
+        let mut transaction = test_db.pool.begin().await.unwrap();
+
+        let test_id = "get_last_deposits_sum";
+        let test_header = BeaconHeaderSignedEnvelopeBuilder::new(test_id).build();
+        let test_block = Into::<BeaconBlockBuilder>::into(&test_header).build();
+
+        store_state(
+            &mut *transaction,
+            &test_header.state_root(),
+            &test_header.slot(),
+        )
+        .await;
+
+        store_block(
+            &mut *transaction,
+            &test_block,
+            &GweiNewtype(0),
+            &GweiNewtype(1),
+            &GweiNewtype(0),
+            &GweiNewtype(1),
+            &test_header,
+        )
+        .await;
+
+        let deposits_sum = get_last_deposits_sum(&mut *transaction).await.unwrap();
+
+        assert_eq!(
+            deposits_sum,
+            BeaconDepositsSum {
+                deposits_sum: GweiNewtype(1),
+                slot: test_header.slot(),
+            }
+        );
+    }
 }
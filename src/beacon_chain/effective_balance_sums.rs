@@ -1,10 +1,15 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::PgExecutor;
+use sqlx::{PgExecutor, PgPool};
+use tracing::{debug, info};
 
-use crate::units::{GweiImprecise, GweiNewtype};
+use crate::{
+    caching::{self, CacheKey},
+    db, log,
+    units::{GweiImprecise, GweiNewtype},
+};
 
-use super::{BeaconNode, Slot, StateRoot};
+use super::{BeaconNode, BeaconNodeHttp, Slot, StateRoot};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EffectiveBalanceSum {
@@ -63,6 +68,45 @@ pub async fn store_effective_balance_sum(
     .unwrap();
 }
 
+pub async fn update_effective_balance_sum() {
+    log::init_with_env();
+
+    info!("updating effective balance sum");
+
+    let db_pool = db::get_db_pool("update-effective-balance-sum").await;
+
+    sqlx::migrate!().run(&db_pool).await.unwrap();
+
+    let beacon_node = BeaconNodeHttp::new();
+
+    update_effective_balance_sum_with_pool(&db_pool, &beacon_node).await;
+}
+
+/// Sums the effective balances for the last stored state, stores and publishes the sum.
+pub async fn update_effective_balance_sum_with_pool(
+    db_pool: &PgPool,
+    beacon_node: &impl BeaconNode,
+) {
+    let last_state = super::get_last_state(db_pool)
+        .await
+        .expect("expect at least one beacon slot to be synced before updating effective balances");
+
+    let sum = get_effective_balance_sum(beacon_node, &last_state.state_root).await;
+
+    store_effective_balance_sum(db_pool, &last_state.state_root, &sum).await;
+
+    let effective_balance_sum = EffectiveBalanceSum::new(&last_state.slot, sum);
+
+    debug!("effective balance sum updated {:?}", effective_balance_sum);
+
+    caching::update_and_publish(
+        db_pool,
+        &CacheKey::EffectiveBalanceSum,
+        effective_balance_sum,
+    )
+    .await;
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::{anyhow, Result};
@@ -73,7 +117,8 @@ mod tests {
         beacon_chain::{
             self,
             node::{
-                BeaconBlock, FinalityCheckpoint, Validator, ValidatorBalance, ValidatorEnvelope,
+                BeaconBlock, FinalityCheckpoint, FinalityCheckpoints, PendingDeposit, Validator,
+                ValidatorBalance, ValidatorEnvelope,
             },
             BeaconHeaderSignedEnvelope, BlockId, StateRoot,
         },
@@ -131,6 +176,13 @@ mod tests {
             Err(anyhow!("Not implemented in the MockBeaconNode"))
         }
 
+        async fn get_finality_checkpoints_by_state(
+            &self,
+            _state_root: &str,
+        ) -> Result<Option<FinalityCheckpoints>> {
+            Ok(None)
+        }
+
         async fn get_last_finality_checkpoint(&self) -> Result<FinalityCheckpoint> {
             Err(anyhow!("Not implemented in the MockBeaconNode"))
         }
@@ -143,6 +195,13 @@ mod tests {
             Err(anyhow!("Not implemented in the MockBeaconNode"))
         }
 
+        async fn get_pending_deposits(
+            &self,
+            _state_root: &str,
+        ) -> Result<Option<Vec<PendingDeposit>>> {
+            Ok(None)
+        }
+
         async fn get_state_root_by_slot(&self, _slot: &Slot) -> Result<Option<StateRoot>> {
             Ok(None)
         }
@@ -154,6 +213,14 @@ mod tests {
             Ok(None)
         }
 
+        async fn get_validator_by_state(
+            &self,
+            _state_root: &str,
+            _validator_id: &str,
+        ) -> Result<Option<ValidatorEnvelope>> {
+            Ok(None)
+        }
+
         async fn get_validators_by_state(
             &self,
             _state_root: &str,
@@ -0,0 +1,143 @@
+//! # Finality
+//! Stores the justified and finalized checkpoints of every synced state. Finality lag is how many
+//! epochs the finalized checkpoint trails the epoch of the state. On a healthy chain it's two, the
+//! previous epoch is justified during the current one, and finalized in the next.
+use sqlx::PgExecutor;
+
+use super::{node::FinalityCheckpoints, Epoch, Slot};
+
+pub fn get_finality_lag(slot: &Slot, finalized_epoch: i32) -> i32 {
+    Epoch::from(slot).0 - finalized_epoch
+}
+
+pub async fn store_finality(
+    executor: impl PgExecutor<'_>,
+    slot: &Slot,
+    checkpoints: &FinalityCheckpoints,
+) {
+    sqlx::query!(
+        "
+        INSERT INTO beacon_finality (
+            slot,
+            timestamp,
+            justified_epoch,
+            finalized_epoch,
+            finalized_block_root,
+            finality_lag
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ",
+        slot.0,
+        slot.date_time(),
+        checkpoints.current_justified.epoch,
+        checkpoints.finalized.epoch,
+        checkpoints.finalized.root,
+        get_finality_lag(slot, checkpoints.finalized.epoch),
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub async fn delete_finality(executor: impl PgExecutor<'_>, slot: &Slot) {
+    sqlx::query!("DELETE FROM beacon_finality WHERE slot = $1", slot.0)
+        .execute(executor)
+        .await
+        .unwrap();
+}
+
+pub async fn delete_finalities(executor: impl PgExecutor<'_>, greater_than_or_equal: &Slot) {
+    sqlx::query!(
+        "DELETE FROM beacon_finality WHERE slot >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Finality {
+    pub finality_lag: i32,
+    pub finalized_epoch: i32,
+    pub justified_epoch: i32,
+    pub slot: Slot,
+}
+
+pub async fn get_last_finality(executor: impl PgExecutor<'_>) -> Option<Finality> {
+    sqlx::query!(
+        "
+        SELECT slot, justified_epoch, finalized_epoch, finality_lag
+        FROM beacon_finality
+        ORDER BY slot DESC
+        LIMIT 1
+        ",
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| Finality {
+        finality_lag: row.finality_lag,
+        finalized_epoch: row.finalized_epoch,
+        justified_epoch: row.justified_epoch,
+        slot: Slot(row.slot),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{beacon_chain::node::FinalityCheckpoint, db::tests::TestDb};
+
+    use super::*;
+
+    fn test_checkpoints(justified_epoch: i32, finalized_epoch: i32) -> FinalityCheckpoints {
+        FinalityCheckpoints {
+            current_justified: FinalityCheckpoint {
+                epoch: justified_epoch,
+                root: "0xjustified".to_string(),
+            },
+            finalized: FinalityCheckpoint {
+                epoch: finalized_epoch,
+                root: "0xfinalized".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn get_finality_lag_test() {
+        assert_eq!(get_finality_lag(&Epoch(10).first_slot(), 8), 2);
+        assert_eq!(get_finality_lag(&Epoch(10).last_slot(), 5), 5);
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn last_finality_test(test_db: &TestDb) {
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        let slot = Epoch(10).first_slot();
+        store_finality(&mut *connection, &slot, &test_checkpoints(9, 8)).await;
+        store_finality(&mut *connection, &(slot + 1), &test_checkpoints(9, 7)).await;
+
+        assert_eq!(
+            get_last_finality(&mut *connection).await,
+            Some(Finality {
+                finality_lag: 3,
+                finalized_epoch: 7,
+                justified_epoch: 9,
+                slot: slot + 1,
+            })
+        );
+
+        delete_finality(&mut *connection, &(slot + 1)).await;
+        assert_eq!(
+            get_last_finality(&mut *connection)
+                .await
+                .map(|finality| finality.slot),
+            Some(slot)
+        );
+
+        delete_finalities(&mut *connection, &slot).await;
+        assert_eq!(get_last_finality(&mut *connection).await, None);
+    }
+}
@@ -0,0 +1,309 @@
+//! # Graffiti
+//! Proposers may put 32 bytes of anything in a block, its graffiti. Many clients write their name
+//! and version, or the execution and consensus client codes of the client version graffiti
+//! convention, e.g. `GEabcdLH1234`. Staking pools often write their name. We store the graffiti of
+//! every synced block with the client and pool we recognize in it, and publish counts of both per
+//! time frame, a rough picture of client diversity. Most proposers don't say, shares are of blocks
+//! with a recognized graffiti.
+//!
+//! Only blocks synced since graffiti tracking started have theirs stored, older blocks aren't
+//! backfilled. The counts are published by the scheduler.
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use enum_iterator::all;
+use serde::Serialize;
+use sqlx::{postgres::PgRow, PgExecutor, PgPool, Row};
+use tracing::debug;
+
+use crate::{
+    caching::{self, CacheKey},
+    time_frames::TimeFrame,
+};
+
+use super::Slot;
+
+/// Names, lowercased, as clients write them.
+const CLIENT_NAMES: [(&str, &str); 6] = [
+    ("grandine", "Grandine"),
+    ("lighthouse", "Lighthouse"),
+    ("lodestar", "Lodestar"),
+    ("nimbus", "Nimbus"),
+    ("prysm", "Prysm"),
+    ("teku", "Teku"),
+];
+
+/// Consensus client codes of the client version graffiti convention.
+const CLIENT_CODES: [(&str, &str); 6] = [
+    ("GD", "Grandine"),
+    ("LH", "Lighthouse"),
+    ("LS", "Lodestar"),
+    ("NB", "Nimbus"),
+    ("PM", "Prysm"),
+    ("TK", "Teku"),
+];
+
+/// Execution client codes of the same convention, these lead.
+const EXECUTION_CLIENT_CODES: [&str; 6] = ["BU", "EG", "EJ", "GE", "NM", "RH"];
+
+/// Names, lowercased, as pools write them. Rocket Pool node operators write `RP-` followed by
+/// their client initials and version, matched apart.
+const POOL_NAMES: [(&str, &str); 8] = [
+    ("allnodes", "Allnodes"),
+    ("ankr", "Ankr"),
+    ("everstake", "Everstake"),
+    ("figment", "Figment"),
+    ("kiln", "Kiln"),
+    ("p2p.org", "P2P.org"),
+    ("stakefish", "stakefish"),
+    ("stakewise", "StakeWise"),
+];
+
+/// Decodes the hex encoded bytes as UTF-8, dropping zero bytes, mostly padding, which Postgres
+/// text can't hold. Invalid bytes are replaced.
+pub fn decode_graffiti(graffiti: &str) -> String {
+    let hex = graffiti.trim_start_matches("0x");
+    let bytes = (0..hex.len() / 2)
+        .filter_map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok())
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).replace('\0', "")
+}
+
+/// Matches e.g. `GELH`, or `GEabcdLHef01` with the commits of both clients.
+fn client_from_code(graffiti: &str) -> Option<&'static str> {
+    let execution_code = graffiti.get(0..2)?;
+    if !EXECUTION_CLIENT_CODES.contains(&execution_code) {
+        return None;
+    }
+
+    let rest = &graffiti[2..];
+    let consensus_code = match rest.get(4..6) {
+        Some(code) if rest[..4].chars().all(|c| c.is_ascii_hexdigit()) => code,
+        _ => rest.get(0..2)?,
+    };
+
+    CLIENT_CODES
+        .iter()
+        .find(|(code, _)| *code == consensus_code)
+        .map(|(_, client)| *client)
+}
+
+pub fn recognize_client(graffiti: &str) -> Option<&'static str> {
+    let lowercase = graffiti.to_lowercase();
+    CLIENT_NAMES
+        .iter()
+        .find(|(name, _)| lowercase.contains(name))
+        .map(|(_, client)| *client)
+        .or_else(|| client_from_code(graffiti))
+}
+
+pub fn recognize_pool(graffiti: &str) -> Option<&'static str> {
+    if graffiti.starts_with("RP-") {
+        return Some("Rocket Pool");
+    }
+
+    let lowercase = graffiti.to_lowercase();
+    POOL_NAMES
+        .iter()
+        .find(|(name, _)| lowercase.contains(name))
+        .map(|(_, pool)| *pool)
+}
+
+pub async fn store_graffiti(executor: impl PgExecutor<'_>, slot: &Slot, graffiti: &str) {
+    let graffiti = decode_graffiti(graffiti);
+
+    sqlx::query!(
+        "
+        INSERT INTO beacon_block_graffiti (slot, timestamp, graffiti, client, pool)
+        VALUES ($1, $2, $3, $4, $5)
+        ",
+        slot.0,
+        slot.date_time(),
+        graffiti,
+        recognize_client(&graffiti),
+        recognize_pool(&graffiti),
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub async fn delete_graffiti(executor: impl PgExecutor<'_>, slot: &Slot) {
+    sqlx::query!("DELETE FROM beacon_block_graffiti WHERE slot = $1", slot.0)
+        .execute(executor)
+        .await
+        .unwrap();
+}
+
+pub async fn delete_graffitis(executor: impl PgExecutor<'_>, greater_than_or_equal: &Slot) {
+    sqlx::query!(
+        "DELETE FROM beacon_block_graffiti WHERE slot >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraffitiCount {
+    pub block_count: i64,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraffitiCounts {
+    pub block_count: i64,
+    /// Most blocks first.
+    pub clients: Vec<GraffitiCount>,
+    pub pools: Vec<GraffitiCount>,
+}
+
+async fn get_counts_by(
+    executor: impl PgExecutor<'_>,
+    column: &str,
+    since: &DateTime<Utc>,
+) -> Vec<GraffitiCount> {
+    sqlx::query(&format!(
+        "
+        SELECT {column} AS name, COUNT(*) AS block_count
+        FROM beacon_block_graffiti
+        WHERE timestamp >= $1 AND {column} IS NOT NULL
+        GROUP BY {column}
+        ORDER BY block_count DESC, name ASC
+        "
+    ))
+    .bind(since)
+    .map(|row: PgRow| GraffitiCount {
+        block_count: row.get("block_count"),
+        name: row.get("name"),
+    })
+    .fetch_all(executor)
+    .await
+    .unwrap()
+}
+
+async fn get_graffiti_counts(db_pool: &PgPool, since: &DateTime<Utc>) -> GraffitiCounts {
+    let block_count = sqlx::query!(
+        r#"SELECT COUNT(*) AS "block_count!" FROM beacon_block_graffiti WHERE timestamp >= $1"#,
+        since,
+    )
+    .fetch_one(db_pool)
+    .await
+    .unwrap()
+    .block_count;
+
+    GraffitiCounts {
+        block_count,
+        clients: get_counts_by(db_pool, "client", since).await,
+        pools: get_counts_by(db_pool, "pool", since).await,
+    }
+}
+
+pub async fn update_graffiti_counts(db_pool: &PgPool) {
+    let now = Utc::now();
+    let mut graffiti_counts = HashMap::new();
+
+    for time_frame in all::<TimeFrame>() {
+        let since = time_frame.start_timestamp_at(&now);
+        graffiti_counts.insert(time_frame, get_graffiti_counts(db_pool, &since).await);
+    }
+
+    caching::update_and_publish(db_pool, &CacheKey::GraffitiCounts, &graffiti_counts).await;
+    debug!("published graffiti counts");
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::db::tests::TestDb;
+
+    use super::*;
+
+    fn encode_graffiti(graffiti: &str) -> String {
+        let mut bytes = graffiti.as_bytes().to_vec();
+        bytes.resize(32, 0);
+        let hex = bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        format!("0x{hex}")
+    }
+
+    #[test]
+    fn decode_graffiti_test() {
+        assert_eq!(
+            decode_graffiti(&encode_graffiti("Lighthouse/v5.1.3")),
+            "Lighthouse/v5.1.3"
+        );
+        assert_eq!(decode_graffiti(&encode_graffiti("")), "");
+    }
+
+    #[test]
+    fn recognize_client_test() {
+        assert_eq!(
+            recognize_client("Lighthouse/v5.1.3-3058b96"),
+            Some("Lighthouse")
+        );
+        assert_eq!(recognize_client("teku/v24.4.0"), Some("Teku"));
+        assert_eq!(recognize_client("GELH"), Some("Lighthouse"));
+        assert_eq!(recognize_client("NM1a2bPMc3d4"), Some("Prysm"));
+        assert_eq!(recognize_client("RP-NB v1.13.1"), None);
+        assert_eq!(recognize_client("gm"), None);
+    }
+
+    #[test]
+    fn recognize_pool_test() {
+        assert_eq!(recognize_pool("RP-NB v1.13.1"), Some("Rocket Pool"));
+        assert_eq!(recognize_pool("Stakefish"), Some("stakefish"));
+        assert_eq!(recognize_pool("Lighthouse/v5.1.3"), None);
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn graffiti_counts_test(test_db: &TestDb) {
+        let slot = Slot(9_000_000);
+        let graffitis = ["Lighthouse/v5.1.3", "GELH", "Kiln teku", ""];
+        for (offset, graffiti) in graffitis.iter().enumerate() {
+            store_graffiti(
+                &test_db.pool,
+                &(slot + offset as i32),
+                &encode_graffiti(graffiti),
+            )
+            .await;
+        }
+
+        assert_eq!(
+            get_graffiti_counts(&test_db.pool, &slot.date_time()).await,
+            GraffitiCounts {
+                block_count: 4,
+                clients: vec![
+                    GraffitiCount {
+                        block_count: 2,
+                        name: "Lighthouse".to_string(),
+                    },
+                    GraffitiCount {
+                        block_count: 1,
+                        name: "Teku".to_string(),
+                    },
+                ],
+                pools: vec![GraffitiCount {
+                    block_count: 1,
+                    name: "Kiln".to_string(),
+                }],
+            }
+        );
+
+        delete_graffiti(&test_db.pool, &(slot + 3)).await;
+        delete_graffitis(&test_db.pool, &(slot + 1)).await;
+        assert_eq!(
+            get_graffiti_counts(&test_db.pool, &slot.date_time())
+                .await
+                .block_count,
+            1
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! # Health score
+//! Sums up how the chain, and our view of it, is doing in a single score from 0 to 100 for status
+//! pages, with the components it's made of:
+//!
+//! - participation, the share of the sync committee signing recent blocks.
+//! - finality lag, how many epochs the last finalized checkpoint trails, two when healthy.
+//! - missed slots, the share of slots without a block over the last hour.
+//! - reorgs, how many times synced slots were rolled back over the last hour.
+//! - sync lag, how many slots our last synced state trails the head of the beacon node.
+//!
+//! Each component scores 100 at or better than a healthy value and falls linearly to 0 at a
+//! value that needs attention. The score is their weighted average, a component we have no data
+//! for, e.g. participation before Altair, is left out.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::debug;
+
+use crate::{
+    caching::{self, CacheKey},
+    time_frames::LimitedTimeFrame,
+};
+
+use super::{
+    finality, get_last_state,
+    node::{BeaconNode, BeaconNodeHttp},
+    reorgs, slot_stats, Slot,
+};
+
+/// Recent blocks whose sync committee participation is averaged.
+const PARTICIPATION_SLOT_COUNT: i32 = 8;
+
+const DEGRADED_THRESHOLD: f64 = 60.0;
+const HEALTHY_THRESHOLD: f64 = 90.0;
+
+/// Scores 100 at `healthy` and 0 at `unhealthy`, linearly in between. Works for components where
+/// lower is better as well as higher.
+fn linear_score(value: f64, healthy: f64, unhealthy: f64) -> f64 {
+    let share = (value - unhealthy) / (healthy - unhealthy);
+    share.clamp(0.0, 1.0) * 100.0
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthComponent {
+    pub name: &'static str,
+    pub score: f64,
+    pub value: f64,
+    /// Share of the total score, before leaving out components without data.
+    pub weight: f64,
+}
+
+fn participation_component(participation: f64) -> HealthComponent {
+    HealthComponent {
+        name: "participation",
+        score: linear_score(participation, 0.95, 0.5),
+        value: participation,
+        weight: 0.3,
+    }
+}
+
+fn finality_lag_component(finality_lag: i32) -> HealthComponent {
+    HealthComponent {
+        name: "finality_lag",
+        score: linear_score(finality_lag as f64, 2.0, 10.0),
+        value: finality_lag as f64,
+        weight: 0.25,
+    }
+}
+
+fn missed_slots_component(missed_slot_rate: f64) -> HealthComponent {
+    HealthComponent {
+        name: "missed_slots",
+        score: linear_score(missed_slot_rate, 0.01, 0.2),
+        value: missed_slot_rate,
+        weight: 0.2,
+    }
+}
+
+fn sync_lag_component(sync_lag: i32) -> HealthComponent {
+    HealthComponent {
+        name: "sync_lag",
+        score: linear_score(sync_lag as f64, 2.0, 50.0),
+        value: sync_lag as f64,
+        weight: 0.15,
+    }
+}
+
+fn reorgs_component(reorg_count: i64) -> HealthComponent {
+    HealthComponent {
+        name: "reorgs",
+        score: linear_score(reorg_count as f64, 0.0, 4.0),
+        value: reorg_count as f64,
+        weight: 0.1,
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+impl From<f64> for HealthStatus {
+    fn from(score: f64) -> Self {
+        if score >= HEALTHY_THRESHOLD {
+            HealthStatus::Healthy
+        } else if score >= DEGRADED_THRESHOLD {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Unhealthy
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthScore {
+    pub components: Vec<HealthComponent>,
+    pub score: f64,
+    pub status: HealthStatus,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// `None` without components.
+fn get_health_score(
+    components: Vec<HealthComponent>,
+    timestamp: DateTime<Utc>,
+) -> Option<HealthScore> {
+    let total_weight = components
+        .iter()
+        .map(|component| component.weight)
+        .sum::<f64>();
+    if total_weight == 0.0 {
+        return None;
+    }
+
+    let score = components
+        .iter()
+        .map(|component| component.score * component.weight)
+        .sum::<f64>()
+        / total_weight;
+
+    Some(HealthScore {
+        components,
+        score,
+        status: score.into(),
+        timestamp,
+    })
+}
+
+/// Averaged over the recent blocks, `None` when none of them has a sync aggregate.
+async fn get_participation(beacon_node: &BeaconNodeHttp, head_slot: &Slot) -> Result<Option<f64>> {
+    let mut participations = vec![];
+
+    for slot in Slot::range_inclusive(*head_slot - (PARTICIPATION_SLOT_COUNT - 1), *head_slot) {
+        if let Some(participation) = beacon_node
+            .get_block_by_slot(&slot)
+            .await?
+            .and_then(|block| block.sync_participation())
+        {
+            participations.push(participation);
+        }
+    }
+
+    let participation = (!participations.is_empty())
+        .then(|| participations.iter().sum::<f64>() / participations.len() as f64);
+
+    Ok(participation)
+}
+
+pub async fn update_health_score(db_pool: &PgPool, beacon_node: &BeaconNodeHttp) -> Result<()> {
+    let now = Utc::now();
+    let head_slot = beacon_node.get_last_header().await?.slot();
+    let mut components = vec![];
+
+    if let Some(participation) = get_participation(beacon_node, &head_slot).await? {
+        components.push(participation_component(participation));
+    }
+
+    if let Some(finality) = finality::get_last_finality(db_pool).await {
+        components.push(finality_lag_component(finality.finality_lag));
+    }
+
+    let slot_stats =
+        slot_stats::get_slot_stats(db_pool, LimitedTimeFrame::Hour1.slot_count() as i32).await;
+    if slot_stats.slot_count > 0 {
+        components.push(missed_slots_component(slot_stats.missed_slot_rate));
+    }
+
+    let reorg_count =
+        reorgs::get_reorg_count_since(db_pool, &(now - LimitedTimeFrame::Hour1.duration())).await;
+    components.push(reorgs_component(reorg_count));
+
+    if let Some(last_state) = get_last_state(db_pool).await {
+        components.push(sync_lag_component(head_slot.0 - last_state.slot.0));
+    }
+
+    if let Some(health_score) = get_health_score(components, now) {
+        caching::update_and_publish(db_pool, &CacheKey::HealthScore, &health_score).await;
+        debug!(score = health_score.score, "published health score");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_score_test() {
+        assert_eq!(linear_score(0.99, 0.95, 0.5), 100.0);
+        assert_eq!(linear_score(0.75, 1.0, 0.5), 50.0);
+        assert_eq!(linear_score(0.3, 0.95, 0.5), 0.0);
+        assert_eq!(linear_score(6.0, 2.0, 10.0), 50.0);
+        assert_eq!(linear_score(12.0, 2.0, 10.0), 0.0);
+    }
+
+    #[test]
+    fn health_status_test() {
+        assert_eq!(HealthStatus::from(95.0), HealthStatus::Healthy);
+        assert_eq!(HealthStatus::from(60.0), HealthStatus::Degraded);
+        assert_eq!(HealthStatus::from(10.0), HealthStatus::Unhealthy);
+    }
+
+    #[test]
+    fn health_score_test() {
+        let timestamp = Utc::now();
+        let health_score = get_health_score(
+            vec![
+                finality_lag_component(2),
+                missed_slots_component(0.01),
+                reorgs_component(2),
+            ],
+            timestamp,
+        )
+        .unwrap();
+
+        // Participation and sync lag are left out, the rest weigh 0.55 together, reorgs score 50.
+        assert!((health_score.score - 50.0 / 0.55).abs() < 1e-9);
+        assert_eq!(health_score.status, HealthStatus::Healthy);
+        assert_eq!(health_score.components.len(), 3);
+
+        assert_eq!(get_health_score(vec![], timestamp), None);
+    }
+}
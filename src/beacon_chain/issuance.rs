@@ -6,9 +6,22 @@
 //! This module looks up issuance by time, regardless of the block it's done for. It'd be healthy
 //! to change this to looking up by slots and blocks, or alternatively time relative to the current
 //! block.
+//!
+//! Next to the issuance we observe, it projects issuance from the protocol curve, what validators
+//! earn at full participation for a given amount staked. Used for the current stake in the
+//! issuance estimate, and for hypothetical stakes at `/api/v2/fees/issuance-scenarios`.
+use std::collections::HashMap;
+
 use async_trait::async_trait;
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use chrono::{DateTime, Utc};
 use futures::join;
+use lazy_static::lazy_static;
 use serde::Serialize;
 use sqlx::{postgres::types::PgInterval, PgExecutor, PgPool};
 use thiserror::Error;
@@ -20,10 +33,13 @@ use crate::{
     execution_chain::ExecutionNodeBlock,
     log,
     time_frames::TimeFrame,
-    units::GweiNewtype,
+    units::{GweiNewtype, GWEI_PER_ETH_F64},
 };
 
-use super::Slot;
+use super::{
+    rewards::{self, EPOCHS_PER_YEAR, SLOTS_PER_YEAR},
+    Slot, SECONDS_PER_SLOT,
+};
 
 pub async fn store_issuance(
     executor: impl PgExecutor<'_>,
@@ -227,48 +243,166 @@ impl IssuanceStore for IssuanceStorePostgres {
     }
 }
 
-const SLOTS_PER_MINUTE: u64 = 5;
-const MINUTES_PER_HOUR: u64 = 60;
-const HOURS_PER_DAY: u64 = 24;
-const DAYS_PER_WEEK: u64 = 7;
-const SLOTS_PER_WEEK: f64 =
-    (SLOTS_PER_MINUTE * MINUTES_PER_HOUR * HOURS_PER_DAY * DAYS_PER_WEEK) as f64;
+const SECONDS_PER_WEEK: u64 = 7 * 24 * 60 * 60;
+
+const MAX_EFFECTIVE_BALANCE_ETH: f64 = 32.0;
+
+lazy_static! {
+    static ref SLOTS_PER_WEEK: f64 = SECONDS_PER_WEEK as f64 / *SECONDS_PER_SLOT as f64;
+}
+
+/// Most scenarios served per request.
+const MAX_SCENARIOS: usize = 100;
+
+/// Issuance at full participation for an amount staked, the protocol curve.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct IssuanceScenario {
+    apr: f64,
+    issuance_per_slot_gwei: f64,
+    issuance_per_year_eth: f64,
+    staked_eth: f64,
+    validator_count: f64,
+}
+
+impl IssuanceScenario {
+    /// Follows the issuance curve of `rewards::get_issuance_per_epoch`.
+    fn from_staked_eth(staked_eth: f64) -> Self {
+        let staked_gwei = staked_eth * GWEI_PER_ETH_F64;
+        let issuance_per_year_gwei =
+            rewards::get_issuance_per_epoch(staked_gwei) * *EPOCHS_PER_YEAR;
+
+        Self {
+            apr: issuance_per_year_gwei / staked_gwei,
+            issuance_per_slot_gwei: issuance_per_year_gwei / *SLOTS_PER_YEAR,
+            issuance_per_year_eth: issuance_per_year_gwei / GWEI_PER_ETH_F64,
+            staked_eth,
+            validator_count: staked_eth / MAX_EFFECTIVE_BALANCE_ETH,
+        }
+    }
+
+    fn from_validator_count(validator_count: f64) -> Self {
+        Self::from_staked_eth(validator_count * MAX_EFFECTIVE_BALANCE_ETH)
+    }
+}
+
+/// Parses a comma separated list of positive amounts, `None` when any isn't one, or there are
+/// none or too many.
+fn parse_amounts(param: &str) -> Option<Vec<f64>> {
+    let amounts = param
+        .split(',')
+        .map(|amount| amount.trim().parse::<f64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    let is_valid = !amounts.is_empty()
+        && amounts.len() <= MAX_SCENARIOS
+        && amounts
+            .iter()
+            .all(|amount| amount.is_finite() && *amount > 0.0);
+
+    is_valid.then_some(amounts)
+}
+
+/// Takes either `staked_eth` or `validators`, a comma separated list of hypothetical amounts
+/// staked, in ETH, or numbers of validators at the max effective balance, at most 100. Returns a
+/// scenario for each, in the same order.
+pub async fn issuance_scenarios(
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let scenarios = match (params.get("staked_eth"), params.get("validators")) {
+        (Some(staked_eth), None) => parse_amounts(staked_eth).map(|amounts| {
+            amounts
+                .into_iter()
+                .map(IssuanceScenario::from_staked_eth)
+                .collect::<Vec<_>>()
+        }),
+        (None, Some(validators)) => parse_amounts(validators).map(|amounts| {
+            amounts
+                .into_iter()
+                .map(IssuanceScenario::from_validator_count)
+                .collect::<Vec<_>>()
+        }),
+        _ => None,
+    };
+
+    match scenarios {
+        Some(scenarios) => {
+            // Only depends on the amounts asked for.
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static("public, max-age=86400, stale-while-revalidate=86400"),
+            );
+
+            (headers, Json(scenarios)).into_response()
+        }
+        None => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+/// As last stored by the effective balance sum job.
+async fn get_last_effective_balance_sum(executor: impl PgExecutor<'_>) -> Option<GweiNewtype> {
+    sqlx::query!(
+        r#"
+        SELECT effective_balance_sum AS "effective_balance_sum!"
+        FROM beacon_states
+        WHERE effective_balance_sum IS NOT NULL
+        ORDER BY slot DESC
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| GweiNewtype(row.effective_balance_sum))
+}
 
 #[derive(Debug, Serialize)]
 struct IssuanceEstimate {
     slot: Slot,
     timestamp: DateTime<Utc>,
     issuance_per_slot_gwei: f64,
+    /// The protocol curve at the last effective balance sum, `None` before one is stored.
+    protocol_issuance: Option<IssuanceScenario>,
 }
 
 async fn get_issuance_per_slot_estimate(issuance_store: &impl IssuanceStore) -> f64 {
     let last_week_issuance = issuance_store.weekly_issuance().await;
-    last_week_issuance.0 as f64 / SLOTS_PER_WEEK
+    last_week_issuance.0 as f64 / *SLOTS_PER_WEEK
 }
 
 pub async fn update_issuance_estimate() {
     log::init_with_env();
 
+    let db_pool = db::get_db_pool("update-issuance-estimate").await;
+
+    update_issuance_estimate_with_pool(&db_pool).await;
+}
+
+pub async fn update_issuance_estimate_with_pool(db_pool: &PgPool) {
     info!("updating issuance estimate");
 
-    let db_pool = db::get_db_pool("update-issuance-estimate").await;
     let issuance_store = IssuanceStorePostgres::new(db_pool.clone());
 
     let issuance_per_slot_gwei = get_issuance_per_slot_estimate(&issuance_store).await;
     debug!("issuance per slot estimate: {}", issuance_per_slot_gwei);
-    let slot = super::get_last_state(&db_pool)
+    let slot = super::get_last_state(db_pool)
         .await
         .expect("expect last state to exist in order to update issuance estimate")
         .slot;
 
+    let protocol_issuance = get_last_effective_balance_sum(db_pool)
+        .await
+        .map(|sum| IssuanceScenario::from_staked_eth(sum.0 as f64 / GWEI_PER_ETH_F64));
+
     let timestamp = slot.date_time();
     let issuance_estimate = IssuanceEstimate {
         slot,
         timestamp,
         issuance_per_slot_gwei,
+        protocol_issuance,
     };
 
-    caching::update_and_publish(&db_pool, &CacheKey::IssuanceEstimate, issuance_estimate).await;
+    caching::update_and_publish(db_pool, &CacheKey::IssuanceEstimate, issuance_estimate).await;
 
     info!("updated issuance estimate");
 }
@@ -280,7 +414,9 @@ mod tests {
 
     use super::*;
     use crate::{
-        beacon_chain::{states::store_state, GweiInTime},
+        beacon_chain::{
+            effective_balance_sums::store_effective_balance_sum, states::store_state, GweiInTime,
+        },
         db,
     };
 
@@ -478,6 +614,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn issuance_scenario_test() {
+        let scenario = IssuanceScenario::from_staked_eth(32_000_000.0);
+
+        // About 166.3 ETH a year times the square root of the ETH staked.
+        assert!((scenario.issuance_per_year_eth - 940_865.85).abs() < 0.01);
+        assert!((scenario.apr - 0.0294).abs() < 0.0001);
+        assert_eq!(scenario.validator_count, 1_000_000.0);
+        assert_eq!(
+            IssuanceScenario::from_validator_count(1_000_000.0),
+            scenario
+        );
+    }
+
+    #[test]
+    fn parse_amounts_test() {
+        assert_eq!(
+            parse_amounts("16000000, 32000000"),
+            Some(vec![16_000_000.0, 32_000_000.0])
+        );
+        assert_eq!(parse_amounts("32000000,abc"), None);
+        assert_eq!(parse_amounts("0"), None);
+        assert_eq!(parse_amounts(""), None);
+        assert_eq!(parse_amounts(&vec!["1"; MAX_SCENARIOS + 1].join(",")), None);
+    }
+
+    #[tokio::test]
+    async fn get_last_effective_balance_sum_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        store_state(&mut *transaction, "0xtest_issuance_scenario_1", &Slot(3599)).await;
+        store_state(&mut *transaction, "0xtest_issuance_scenario_2", &Slot(3600)).await;
+        store_effective_balance_sum(
+            &mut *transaction,
+            "0xtest_issuance_scenario_1",
+            &GweiNewtype(100),
+        )
+        .await;
+
+        assert_eq!(
+            get_last_effective_balance_sum(&mut *transaction).await,
+            Some(GweiNewtype(100))
+        );
+    }
+
     #[tokio::test]
     async fn get_last_week_issuance_test() {
         let issuance_store = IssuanceStoreTest {};
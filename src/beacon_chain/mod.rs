@@ -1,9 +1,17 @@
 pub mod balances;
 mod blocks;
+pub mod consolidations;
 mod deposits;
 pub mod effective_balance_sums;
+pub mod finality;
+pub mod graffiti;
+pub mod health_score;
 mod issuance;
 mod node;
+pub mod proposers;
+mod reorgs;
+pub mod rewards;
+mod slot_stats;
 pub mod states;
 mod store;
 mod sync;
@@ -11,6 +19,10 @@ mod units;
 mod withdrawals;
 
 pub use balances::backfill;
+pub use balances::backfill::backfill_balances_to_london;
+pub use balances::backfill::backfill_daily_balances_to_london;
+pub use balances::backfill::backfill_hourly_balances;
+pub use balances::backfill::backfill_hourly_balances_to_london;
 pub use balances::get_balances_by_state_root;
 pub use balances::get_validator_balances_by_start_of_day;
 pub use balances::store_validators_balance;
@@ -20,15 +32,20 @@ pub use balances::BeaconBalancesSum;
 pub use blocks::get_block_before_slot;
 pub use blocks::get_block_by_slot;
 pub use blocks::heal_block_hashes;
+pub use blocks::heal_block_hashes_with_dry_run;
 pub use blocks::store_block;
 pub use blocks::GENESIS_PARENT_ROOT;
 
 use chrono::DateTime;
 use chrono::Utc;
 pub use deposits::get_deposits_sum_by_state_root;
+pub use deposits::get_last_deposits_sum;
 pub use deposits::BeaconDepositsSum;
 
+pub use issuance::issuance_scenarios;
+pub use issuance::store_issuance;
 pub use issuance::update_issuance_estimate;
+pub use issuance::update_issuance_estimate_with_pool;
 pub use issuance::IssuanceStore;
 pub use issuance::IssuanceStorePostgres;
 
@@ -41,32 +58,39 @@ pub use node::BeaconNode;
 pub use node::BeaconNodeHttp;
 pub use node::BlockId;
 pub use node::MockBeaconNode;
+pub use node::PendingDeposit;
 pub use node::StateRoot;
 
 pub use states::get_last_state;
 pub use states::get_state_root_by_slot;
 pub use states::heal_beacon_states;
+pub use states::heal_beacon_states_with_dry_run;
 pub use states::store_state;
 
 pub use store::{BeaconStore, BeaconStorePostgres};
 
 pub use sync::sync_beacon_states;
+pub use sync::sync_slot_by_state_root;
+pub use sync::BeaconChainRollback;
 
 pub use units::slot_from_string;
+pub use units::Epoch;
 pub use units::Slot;
 
 use lazy_static::lazy_static;
 use serde::Serialize;
 
-use crate::env;
-
-pub const FIRST_POST_MERGE_SLOT: Slot = Slot(4700013);
-pub const FIRST_POST_LONDON_SLOT: Slot = Slot(1778566);
+use crate::{config, network::NETWORK_CONSTANTS};
 
 lazy_static! {
-    static ref BEACON_URL: String = env::get_env_var_unsafe("BEACON_URL");
-    pub static ref GENESIS_TIMESTAMP: DateTime<Utc> = "2020-12-01T12:00:23Z".parse().unwrap();
-    pub static ref SHAPELLA_SLOT: Slot = Slot(6209536);
+    static ref BEACON_URL: String = config::CONFIG.beacon_url().to_string();
+    pub static ref FIRST_POST_LONDON_SLOT: Slot = NETWORK_CONSTANTS.first_post_london_slot;
+    pub static ref FIRST_POST_MERGE_SLOT: Slot = NETWORK_CONSTANTS.first_post_merge_slot;
+    pub static ref GENESIS_TIMESTAMP: DateTime<Utc> = NETWORK_CONSTANTS.beacon_genesis_timestamp;
+    pub static ref SECONDS_PER_SLOT: i32 = NETWORK_CONSTANTS.seconds_per_slot;
+    pub static ref SLOTS_PER_EPOCH: i32 = NETWORK_CONSTANTS.slots_per_epoch;
+    pub static ref SECONDS_PER_EPOCH: i32 = *SLOTS_PER_EPOCH * *SECONDS_PER_SLOT;
+    pub static ref SHAPELLA_SLOT: Slot = NETWORK_CONSTANTS.shapella_slot;
 }
 
 #[derive(Serialize)]
@@ -0,0 +1,202 @@
+//! Events the beacon node pushes over `/eth/v1/events`, as server-sent events.
+use anyhow::Result;
+use futures::Stream;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::{
+    beacon_chain::{slot_from_string, Slot, BEACON_URL},
+    json_codecs::i32_from_string,
+};
+
+use super::{BeaconHeaderSignedEnvelope, BeaconNodeHttp};
+
+const TOPICS: [&str; 3] = ["head", "finalized_checkpoint", "reorg"];
+
+fn make_events_url() -> String {
+    let topics = TOPICS
+        .iter()
+        .map(|topic| format!("topics={topic}"))
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}/eth/v1/events?{}", *BEACON_URL, topics)
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct HeadEvent {
+    #[serde(deserialize_with = "slot_from_string")]
+    pub slot: Slot,
+    /// block_root
+    pub block: String,
+    pub state: String,
+}
+
+impl From<BeaconHeaderSignedEnvelope> for HeadEvent {
+    fn from(envelope: BeaconHeaderSignedEnvelope) -> Self {
+        Self {
+            state: envelope.header.message.state_root,
+            block: envelope.root,
+            slot: envelope.header.message.slot,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct FinalizedCheckpointEvent {
+    /// block_root
+    pub block: String,
+    pub state: String,
+    #[serde(deserialize_with = "i32_from_string")]
+    pub epoch: i32,
+}
+
+/// The node switched heads to a block that doesn't descend from the old head.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ReorgEvent {
+    #[serde(deserialize_with = "slot_from_string")]
+    pub slot: Slot,
+    /// Number of slots between the new head and the common ancestor.
+    #[serde(deserialize_with = "i32_from_string")]
+    pub depth: i32,
+    pub old_head_block: String,
+    pub new_head_block: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BeaconEvent {
+    FinalizedCheckpoint(FinalizedCheckpointEvent),
+    Head(HeadEvent),
+    Reorg(ReorgEvent),
+}
+
+/// `None` for topics we didn't subscribe to.
+fn decode_event(event_type: &str, data: &str) -> Result<Option<BeaconEvent>> {
+    let event = match event_type {
+        "finalized_checkpoint" => Some(BeaconEvent::FinalizedCheckpoint(serde_json::from_str(
+            data,
+        )?)),
+        "head" => Some(BeaconEvent::Head(serde_json::from_str(data)?)),
+        "reorg" => Some(BeaconEvent::Reorg(serde_json::from_str(data)?)),
+        _ => None,
+    };
+    Ok(event)
+}
+
+impl BeaconNodeHttp {
+    /// Subscribes to head, finalized checkpoint and reorg events. The stream ends when the
+    /// subscription fails, callers are expected to fall back to polling.
+    pub fn stream_events(&self) -> impl Stream<Item = BeaconEvent> {
+        let url = reqwest::Url::parse(&make_events_url()).unwrap();
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+
+        // The eventsource client blocks, and reconnects by itself when the node closes the
+        // stream cleanly.
+        tokio::task::spawn_blocking(move || {
+            let client = eventsource::reqwest::Client::new(url);
+
+            for event in client {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(err) => {
+                        warn!(%err, "beacon events subscription failed");
+                        break;
+                    }
+                };
+
+                let event_type = match event.event_type {
+                    Some(event_type) => event_type,
+                    None => {
+                        debug!("received an empty server event");
+                        continue;
+                    }
+                };
+
+                match decode_event(&event_type, &event.data) {
+                    Ok(Some(event)) => {
+                        // The receiver may stop listening, e.g. on shutdown.
+                        if tx.unbounded_send(event).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => warn!(event_type, "received a server event we didn't ask for"),
+                    Err(err) => warn!(event_type, %err, "failed to decode server event"),
+                }
+            }
+        });
+
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_head_event_test() {
+        let event = decode_event(
+            "head",
+            r#"{"slot":"10","block":"0xblock","state":"0xstate","epoch_transition":false}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            event,
+            Some(BeaconEvent::Head(HeadEvent {
+                slot: Slot(10),
+                block: "0xblock".to_string(),
+                state: "0xstate".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn decode_reorg_event_test() {
+        let event = decode_event(
+            "reorg",
+            r#"{
+                "slot": "200",
+                "depth": "2",
+                "old_head_block": "0xold_block",
+                "new_head_block": "0xnew_block",
+                "old_head_state": "0xold_state",
+                "new_head_state": "0xnew_state",
+                "epoch": "6"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            event,
+            Some(BeaconEvent::Reorg(ReorgEvent {
+                slot: Slot(200),
+                depth: 2,
+                old_head_block: "0xold_block".to_string(),
+                new_head_block: "0xnew_block".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn decode_finalized_checkpoint_event_test() {
+        let event = decode_event(
+            "finalized_checkpoint",
+            r#"{"block":"0xblock","state":"0xstate","epoch":"2"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            event,
+            Some(BeaconEvent::FinalizedCheckpoint(FinalizedCheckpointEvent {
+                block: "0xblock".to_string(),
+                state: "0xstate".to_string(),
+                epoch: 2,
+            }))
+        );
+    }
+
+    #[test]
+    fn decode_unknown_event_test() {
+        assert_eq!(decode_event("attestation", "{}").unwrap(), None);
+    }
+}
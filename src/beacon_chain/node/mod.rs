@@ -1,11 +1,13 @@
 //! Functions that know how to communicate with  a BeaconChain node to get various pieces of data.
 //! Currently, many calls taking a state_root as input do not acknowledge that a state_root may
 //! disappear at any time. They should be updated to do so.
+pub mod events;
+pub mod rewards;
 pub mod test_utils;
 
 use std::fmt::Display;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
 use chrono::Utc;
 use mockall::automock;
@@ -13,8 +15,8 @@ use reqwest::StatusCode;
 use serde::Deserialize;
 
 use crate::{
-    execution_chain::BlockHash, json_codecs::i32_from_string, performance::TimedExt,
-    units::GweiNewtype,
+    execution_chain::BlockHash, health::NodeProbe, json_codecs::i32_from_string,
+    performance::TimedExt, units::GweiNewtype,
 };
 
 use super::{slot_from_string, Slot, BEACON_URL};
@@ -73,10 +75,41 @@ pub struct ExecutionPayload {
     pub withdrawals: Option<Vec<Withdrawal>>,
 }
 
+/// Request to move the balance of one validator into another, since Electra, see EIP-7251.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConsolidationRequest {
+    pub source_address: String,
+    pub source_pubkey: String,
+    pub target_pubkey: String,
+}
+
+/// Requests the execution layer made of the beacon chain, since Electra. Only consolidations are
+/// decoded, deposit and withdrawal requests are left to the deposit and withdrawal tracking.
+#[derive(Debug, Deserialize)]
+pub struct ExecutionRequests {
+    #[serde(default)]
+    pub consolidations: Vec<ConsolidationRequest>,
+}
+
+/// Which members of the sync committee, 512 validators, signed the parent block, since Altair.
+#[derive(Debug, Deserialize)]
+pub struct SyncAggregate {
+    /// Hex encoded bitvector.
+    pub sync_committee_bits: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct BeaconBlockBody {
+    /// Since Deneb.
+    pub blob_kzg_commitments: Option<Vec<String>>,
     pub deposits: Vec<Deposit>,
     pub execution_payload: Option<ExecutionPayload>,
+    /// Since Electra.
+    pub execution_requests: Option<ExecutionRequests>,
+    /// 32 bytes, hex encoded, set freely by the proposer.
+    pub graffiti: String,
+    /// Since Altair.
+    pub sync_aggregate: Option<SyncAggregate>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -110,6 +143,27 @@ impl BeaconBlock {
             .as_ref()
             .and_then(|execution_payload| execution_payload.withdrawals.as_ref())
     }
+
+    /// Empty before Electra.
+    pub fn consolidation_requests(&self) -> &[ConsolidationRequest] {
+        self.body
+            .execution_requests
+            .as_ref()
+            .map(|execution_requests| execution_requests.consolidations.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Share of the sync committee that signed, `None` before Altair.
+    pub fn sync_participation(&self) -> Option<f64> {
+        let bits = &self.body.sync_aggregate.as_ref()?.sync_committee_bits;
+        let hex = bits.trim_start_matches("0x");
+        let set_bits = (0..hex.len() / 2)
+            .filter_map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok())
+            .map(|byte| byte.count_ones())
+            .sum::<u32>();
+        let total_bits = hex.len() / 2 * 8;
+        (total_bits > 0).then(|| set_bits as f64 / total_bits as f64)
+    }
 }
 
 /// A signed envelope.
@@ -118,12 +172,56 @@ struct BeaconBlockSignedEnvelope {
     message: BeaconBlock,
 }
 
+/// Beacon chain forks, as named by the `version` of versioned responses. Forks newer than we know
+/// decode as `Unknown`, which sorts after every fork we do know.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum BeaconFork {
+    Phase0,
+    Altair,
+    Bellatrix,
+    Capella,
+    Deneb,
+    Electra,
+    Fulu,
+    #[serde(other)]
+    Unknown,
+}
+
 /// A versioned envelope.
 #[derive(Deserialize)]
 struct BeaconBlockVersionedEnvelope {
+    version: BeaconFork,
     data: BeaconBlockSignedEnvelope,
 }
 
+impl BeaconBlockVersionedEnvelope {
+    /// Fields added by a fork decode as optional, so blocks from before it decode too. A block
+    /// from the fork on missing one means we misunderstand the node, which we'd rather hear about
+    /// than store a block without its blobs or requests.
+    fn into_block(self) -> Result<BeaconBlock> {
+        let block = self.data.message;
+
+        if self.version >= BeaconFork::Deneb && block.body.blob_kzg_commitments.is_none() {
+            bail!(
+                "{:?} block at slot {} is missing blob_kzg_commitments",
+                self.version,
+                block.slot
+            );
+        }
+
+        if self.version >= BeaconFork::Electra && block.body.execution_requests.is_none() {
+            bail!(
+                "{:?} block at slot {} is missing execution_requests",
+                self.version,
+                block.slot
+            );
+        }
+
+        Ok(block)
+    }
+}
+
 fn make_blocks_url(block_id: &BlockId) -> String {
     let block_id_text = match block_id {
         BlockId::BlockRoot(str) => str.to_owned(),
@@ -169,6 +267,26 @@ fn make_validator_balances_by_state_url(state_root: &str) -> String {
     )
 }
 
+/// A deposit waiting in the Electra pending deposits queue of a state.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct PendingDeposit {
+    pub amount: GweiNewtype,
+    #[serde(deserialize_with = "slot_from_string")]
+    pub slot: Slot,
+}
+
+#[derive(Debug, Deserialize)]
+struct PendingDepositsEnvelope {
+    data: Vec<PendingDeposit>,
+}
+
+fn make_pending_deposits_by_state_url(state_root: &str) -> String {
+    format!(
+        "{}/eth/v1/beacon/states/{}/pending_deposits",
+        *BEACON_URL, state_root
+    )
+}
+
 #[derive(Debug, Deserialize, PartialEq, Eq)]
 pub struct BeaconHeader {
     #[serde(deserialize_with = "slot_from_string")]
@@ -258,6 +376,18 @@ struct ValidatorsEnvelope {
     data: Vec<ValidatorEnvelope>,
 }
 
+fn make_validator_by_state_url(state_root: &str, validator_id: &str) -> String {
+    format!(
+        "{}/eth/v1/beacon/states/{}/validators/{}",
+        *BEACON_URL, state_root, validator_id
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidatorByIdEnvelope {
+    data: ValidatorEnvelope,
+}
+
 fn make_finality_checkpoint_url() -> String {
     format!(
         "{}/eth/v1/beacon/states/head/finality_checkpoints",
@@ -265,18 +395,25 @@ fn make_finality_checkpoint_url() -> String {
     )
 }
 
-#[derive(Deserialize)]
+fn make_finality_checkpoints_by_state_url(state_root: &str) -> String {
+    format!(
+        "{}/eth/v1/beacon/states/{}/finality_checkpoints",
+        *BEACON_URL, state_root
+    )
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
 pub struct FinalityCheckpoint {
-    #[allow(dead_code)]
     #[serde(deserialize_with = "i32_from_string")]
-    epoch: i32,
-    #[allow(dead_code)]
-    root: String,
+    pub epoch: i32,
+    /// block_root
+    pub root: String,
 }
 
-#[derive(Deserialize)]
-struct FinalityCheckpoints {
-    finalized: FinalityCheckpoint,
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct FinalityCheckpoints {
+    pub current_justified: FinalityCheckpoint,
+    pub finalized: FinalityCheckpoint,
 }
 
 #[derive(Deserialize)]
@@ -312,14 +449,26 @@ pub trait BeaconNode {
         slot: &Slot,
     ) -> Result<Option<BeaconHeaderSignedEnvelope>>;
     async fn get_last_block(&self) -> Result<BeaconBlock>;
+    async fn get_finality_checkpoints_by_state(
+        &self,
+        state_root: &str,
+    ) -> Result<Option<FinalityCheckpoints>>;
     async fn get_last_finality_checkpoint(&self) -> Result<FinalityCheckpoint>;
     async fn get_last_finalized_block(&self) -> Result<BeaconBlock>;
     async fn get_last_header(&self) -> Result<BeaconHeaderSignedEnvelope>;
+    /// `None` for unknown states. States before Electra have no pending deposits queue.
+    async fn get_pending_deposits(&self, state_root: &str) -> Result<Option<Vec<PendingDeposit>>>;
     async fn get_state_root_by_slot(&self, slot: &Slot) -> Result<Option<StateRoot>>;
     async fn get_validator_balances(
         &self,
         state_root: &str,
     ) -> Result<Option<Vec<ValidatorBalance>>>;
+    /// Takes a validator index or pubkey, `None` when the state has no such validator.
+    async fn get_validator_by_state(
+        &self,
+        state_root: &str,
+        validator_id: &str,
+    ) -> Result<Option<ValidatorEnvelope>>;
     async fn get_validators_by_state(&self, state_root: &str) -> Result<Vec<ValidatorEnvelope>>;
 }
 
@@ -340,8 +489,8 @@ impl BeaconNodeHttp {
             StatusCode::OK => {
                 let block = res
                     .json::<BeaconBlockVersionedEnvelope>()
-                    .await
-                    .map(|envelope| envelope.data.message)?;
+                    .await?
+                    .into_block()?;
                 Ok(Some(block))
             }
             status => Err(anyhow!(
@@ -438,6 +587,31 @@ impl BeaconNode for BeaconNodeHttp {
         }
     }
 
+    async fn get_pending_deposits(&self, state_root: &str) -> Result<Option<Vec<PendingDeposit>>> {
+        let url = make_pending_deposits_by_state_url(state_root);
+
+        let res = self
+            .client
+            .get(&url)
+            .send()
+            .timed("get_pending_deposits")
+            .await?;
+
+        match res.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::OK => {
+                let envelope = res.json::<PendingDepositsEnvelope>().await?;
+                Ok(Some(envelope.data))
+            }
+            status => Err(anyhow!(
+                "failed to fetch pending deposits by state_root. state_root = {} status = {} url = {}",
+                state_root,
+                status,
+                res.url()
+            )),
+        }
+    }
+
     async fn get_header(&self, block_id: &BlockId) -> Result<Option<BeaconHeaderSignedEnvelope>> {
         let url = make_header_by_block_id_url(block_id);
 
@@ -508,7 +682,28 @@ impl BeaconNode for BeaconNodeHttp {
             .map(|header| header.expect("expect beacon chain head to always point to a block"))
     }
 
-    #[allow(dead_code)]
+    async fn get_finality_checkpoints_by_state(
+        &self,
+        state_root: &str,
+    ) -> Result<Option<FinalityCheckpoints>> {
+        let url = make_finality_checkpoints_by_state_url(state_root);
+
+        let res = self.client.get(&url).send().await?;
+
+        match res.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::OK => {
+                let envelope = res.json::<CheckpointEnvelope>().await?;
+                Ok(Some(envelope.data))
+            }
+            status => Err(anyhow!(
+                "failed to fetch finality checkpoints by state. state_root = {} status = {}",
+                state_root,
+                status
+            )),
+        }
+    }
+
     async fn get_last_finality_checkpoint(&self) -> Result<FinalityCheckpoint> {
         let url = make_finality_checkpoint_url();
         self.client
@@ -534,6 +729,37 @@ impl BeaconNode for BeaconNodeHttp {
             .map(|envelope| envelope.data)
             .map_err(Into::into)
     }
+
+    async fn get_validator_by_state(
+        &self,
+        state_root: &str,
+        validator_id: &str,
+    ) -> Result<Option<ValidatorEnvelope>> {
+        let url = make_validator_by_state_url(state_root, validator_id);
+
+        let res = self.client.get(&url).send().await?;
+
+        match res.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::OK => {
+                let envelope = res.json::<ValidatorByIdEnvelope>().await?;
+                Ok(Some(envelope.data))
+            }
+            status => Err(anyhow!(
+                "failed to fetch validator by state. state_root = {} validator_id = {} status = {}",
+                state_root,
+                validator_id,
+                status
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl NodeProbe for BeaconNodeHttp {
+    async fn is_reachable(&self) -> bool {
+        self.get_last_header().await.is_ok()
+    }
 }
 
 #[cfg(test)]
@@ -549,7 +775,105 @@ pub mod tests {
                 .unwrap();
         let reader = BufReader::new(file);
 
-        serde_json::from_reader::<BufReader<File>, BeaconBlockVersionedEnvelope>(reader).unwrap();
+        let envelope =
+            serde_json::from_reader::<BufReader<File>, BeaconBlockVersionedEnvelope>(reader)
+                .unwrap();
+        assert_eq!(envelope.version, BeaconFork::Phase0);
+        envelope.into_block().unwrap();
+    }
+
+    fn versioned_block_json(version: &str, body: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "version": version,
+            "data": {
+                "message": {
+                    "slot": "11649024",
+                    "parent_root": "0xparent_root",
+                    "state_root": "0xstate_root",
+                    "body": body
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn decode_electra_block_test() {
+        // Three quarters of the sync committee signed.
+        let sync_committee_bits = format!("0x{}{}", "ff".repeat(48), "00".repeat(16));
+        let json = versioned_block_json(
+            "electra",
+            serde_json::json!({
+                "deposits": [],
+                "execution_payload": { "block_hash": "0xblock_hash", "withdrawals": [] },
+                "blob_kzg_commitments": ["0xcommitment"],
+                "graffiti": "0x0000000000000000000000000000000000000000000000000000000000000000",
+                "sync_aggregate": {
+                    "sync_committee_bits": sync_committee_bits,
+                    "sync_committee_signature": "0xsignature"
+                },
+                "execution_requests": {
+                    "deposits": [],
+                    "withdrawals": [],
+                    "consolidations": [{
+                        "source_address": "0xsource_address",
+                        "source_pubkey": "0xsource_pubkey",
+                        "target_pubkey": "0xtarget_pubkey"
+                    }]
+                }
+            }),
+        );
+
+        let block = serde_json::from_value::<BeaconBlockVersionedEnvelope>(json)
+            .unwrap()
+            .into_block()
+            .unwrap();
+
+        assert_eq!(block.sync_participation(), Some(0.75));
+        let execution_requests = block.body.execution_requests.unwrap();
+        assert_eq!(execution_requests.consolidations.len(), 1);
+        assert_eq!(block.body.blob_kzg_commitments.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn decode_deneb_block_missing_commitments_test() {
+        let json = versioned_block_json(
+            "deneb",
+            serde_json::json!({
+                "deposits": [],
+                "execution_payload": { "block_hash": "0xblock_hash", "withdrawals": [] },
+                "graffiti": "0x0000000000000000000000000000000000000000000000000000000000000000"
+            }),
+        );
+
+        let err = serde_json::from_value::<BeaconBlockVersionedEnvelope>(json)
+            .unwrap()
+            .into_block()
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Deneb block at slot 11649024 is missing blob_kzg_commitments"
+        );
+    }
+
+    #[test]
+    fn decode_unknown_fork_test() {
+        let json = versioned_block_json(
+            "gloas",
+            serde_json::json!({
+                "deposits": [],
+                "execution_payload": { "block_hash": "0xblock_hash", "withdrawals": [] },
+                "blob_kzg_commitments": [],
+                "execution_requests": {},
+                "graffiti": "0x0000000000000000000000000000000000000000000000000000000000000000"
+            }),
+        );
+
+        let envelope = serde_json::from_value::<BeaconBlockVersionedEnvelope>(json).unwrap();
+
+        assert_eq!(envelope.version, BeaconFork::Unknown);
+        assert!(envelope.version > BeaconFork::Electra);
+        envelope.into_block().unwrap();
     }
 
     #[test]
@@ -644,6 +968,26 @@ pub mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn decode_finality_checkpoints() {
+        let json = serde_json::json!({
+            "execution_optimistic": false,
+            "finalized": false,
+            "data": {
+                "previous_justified": { "epoch": "9", "root": "0xprevious" },
+                "current_justified": { "epoch": "10", "root": "0xjustified" },
+                "finalized": { "epoch": "9", "root": "0xfinalized" }
+            }
+        });
+
+        let checkpoints = serde_json::from_value::<CheckpointEnvelope>(json)
+            .unwrap()
+            .data;
+
+        assert_eq!(checkpoints.current_justified.epoch, 10);
+        assert_eq!(checkpoints.finalized.root, "0xfinalized");
+    }
+
     #[tokio::test]
     async fn get_last_finality_checkpoint_test() {
         let beacon_node = BeaconNodeHttp::new();
@@ -0,0 +1,232 @@
+//! The standard rewards endpoints, `/eth/v1/beacon/rewards`. Amounts are in Gwei and negative for
+//! penalties.
+use anyhow::{anyhow, Result};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    beacon_chain::{Epoch, Slot, BEACON_URL},
+    json_codecs::i32_from_string,
+    units::GweiNewtype,
+};
+
+use super::BeaconNodeHttp;
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct AttestationReward {
+    pub head: GweiNewtype,
+    /// Only before Altair.
+    pub inclusion_delay: Option<GweiNewtype>,
+    pub inactivity: GweiNewtype,
+    pub source: GweiNewtype,
+    pub target: GweiNewtype,
+    #[serde(deserialize_with = "i32_from_string")]
+    pub validator_index: i32,
+}
+
+impl AttestationReward {
+    pub fn total(&self) -> GweiNewtype {
+        self.head
+            + self.inclusion_delay.unwrap_or(GweiNewtype(0))
+            + self.inactivity
+            + self.source
+            + self.target
+    }
+}
+
+#[derive(Deserialize)]
+struct AttestationRewards {
+    total_rewards: Vec<AttestationReward>,
+}
+
+#[derive(Deserialize)]
+struct AttestationRewardsEnvelope {
+    data: AttestationRewards,
+}
+
+/// What the proposer of a block earned on the consensus layer for it.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct BlockReward {
+    #[serde(deserialize_with = "i32_from_string")]
+    pub proposer_index: i32,
+    pub total: GweiNewtype,
+}
+
+#[derive(Deserialize)]
+struct BlockRewardEnvelope {
+    data: BlockReward,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SyncCommitteeReward {
+    pub reward: GweiNewtype,
+    #[serde(deserialize_with = "i32_from_string")]
+    pub validator_index: i32,
+}
+
+#[derive(Deserialize)]
+struct SyncCommitteeRewardsEnvelope {
+    data: Vec<SyncCommitteeReward>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ValidatorPubkey {
+    pub pubkey: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ValidatorIdentity {
+    #[serde(deserialize_with = "i32_from_string")]
+    pub index: i32,
+    pub validator: ValidatorPubkey,
+}
+
+#[derive(Deserialize)]
+struct ValidatorIdentitiesEnvelope {
+    data: Vec<ValidatorIdentity>,
+}
+
+impl BeaconNodeHttp {
+    /// Rewards of every validator for attesting in the epoch. Available once the next epoch has
+    /// been processed.
+    pub async fn get_attestation_rewards(&self, epoch: &Epoch) -> Result<Vec<AttestationReward>> {
+        let url = format!(
+            "{}/eth/v1/beacon/rewards/attestations/{}",
+            *BEACON_URL, epoch
+        );
+        self.client
+            .post(&url)
+            .json(&json!([]))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<AttestationRewardsEnvelope>()
+            .await
+            .map(|envelope| envelope.data.total_rewards)
+            .map_err(Into::into)
+    }
+
+    /// `None` when the slot was missed.
+    pub async fn get_block_reward(&self, slot: &Slot) -> Result<Option<BlockReward>> {
+        let url = format!("{}/eth/v1/beacon/rewards/blocks/{}", *BEACON_URL, slot);
+
+        let res = self.client.get(&url).send().await?;
+
+        match res.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::OK => {
+                let envelope = res.json::<BlockRewardEnvelope>().await?;
+                Ok(Some(envelope.data))
+            }
+            status => Err(anyhow!(
+                "failed to fetch block reward. slot = {} status = {}",
+                slot,
+                status
+            )),
+        }
+    }
+
+    /// Rewards of every sync committee member for the block in the slot, `None` when the slot was
+    /// missed.
+    pub async fn get_sync_committee_rewards(
+        &self,
+        slot: &Slot,
+    ) -> Result<Option<Vec<SyncCommitteeReward>>> {
+        let url = format!(
+            "{}/eth/v1/beacon/rewards/sync_committee/{}",
+            *BEACON_URL, slot
+        );
+
+        let res = self.client.post(&url).json(&json!([])).send().await?;
+
+        match res.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::OK => {
+                let envelope = res.json::<SyncCommitteeRewardsEnvelope>().await?;
+                Ok(Some(envelope.data))
+            }
+            status => Err(anyhow!(
+                "failed to fetch sync committee rewards. slot = {} status = {}",
+                slot,
+                status
+            )),
+        }
+    }
+
+    /// Index and pubkey of every validator in the state at the slot.
+    pub async fn get_validator_identities(&self, slot: &Slot) -> Result<Vec<ValidatorIdentity>> {
+        let url = format!("{}/eth/v1/beacon/states/{}/validators", *BEACON_URL, slot);
+        self.client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ValidatorIdentitiesEnvelope>()
+            .await
+            .map(|envelope| envelope.data)
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_attestation_rewards_test() {
+        let json = json!({
+            "execution_optimistic": false,
+            "finalized": true,
+            "data": {
+                "ideal_rewards": [],
+                "total_rewards": [
+                    {
+                        "validator_index": "7",
+                        "head": "2000",
+                        "target": "2000",
+                        "source": "4000",
+                        "inactivity": "-100"
+                    }
+                ]
+            }
+        });
+
+        let rewards = serde_json::from_value::<AttestationRewardsEnvelope>(json)
+            .unwrap()
+            .data
+            .total_rewards;
+
+        assert_eq!(rewards[0].validator_index, 7);
+        assert_eq!(rewards[0].inclusion_delay, None);
+        assert_eq!(rewards[0].total(), GweiNewtype(7900));
+    }
+
+    #[test]
+    fn decode_block_reward_test() {
+        let json = json!({
+            "execution_optimistic": false,
+            "finalized": true,
+            "data": {
+                "proposer_index": "12",
+                "total": "40000000",
+                "attestations": "35000000",
+                "sync_aggregate": "5000000",
+                "proposer_slashings": "0",
+                "attester_slashings": "0"
+            }
+        });
+
+        let reward = serde_json::from_value::<BlockRewardEnvelope>(json)
+            .unwrap()
+            .data;
+
+        assert_eq!(
+            reward,
+            BlockReward {
+                proposer_index: 12,
+                total: GweiNewtype(40_000_000),
+            }
+        );
+    }
+}
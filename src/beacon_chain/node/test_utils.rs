@@ -4,6 +4,7 @@ use super::*;
 
 pub struct BeaconBlockBuilder {
     block_hash: Option<BlockHash>,
+    consolidation_requests: Option<Vec<ConsolidationRequest>>,
     deposits: Vec<GweiNewtype>,
     parent_root: BlockRoot,
     slot: Slot,
@@ -14,6 +15,7 @@ pub struct BeaconBlockBuilder {
 impl Default for BeaconBlockBuilder {
     fn default() -> Self {
         Self {
+            consolidation_requests: None,
             deposits: vec![],
             parent_root: GENESIS_PARENT_ROOT.to_string(),
             slot: Slot(0),
@@ -35,6 +37,15 @@ impl BeaconBlockBuilder {
         self
     }
 
+    /// Makes the block an Electra block carrying the given requests.
+    pub fn consolidation_requests(
+        mut self,
+        consolidation_requests: Vec<ConsolidationRequest>,
+    ) -> Self {
+        self.consolidation_requests = Some(consolidation_requests);
+        self
+    }
+
     pub fn slot(mut self, slot: Slot) -> Self {
         self.slot = slot;
         self
@@ -56,8 +67,14 @@ impl BeaconBlockBuilder {
 
         BeaconBlock {
             body: BeaconBlockBody {
+                blob_kzg_commitments: None,
                 deposits,
                 execution_payload,
+                execution_requests: self
+                    .consolidation_requests
+                    .map(|consolidations| ExecutionRequests { consolidations }),
+                graffiti: format!("0x{}", "00".repeat(32)),
+                sync_aggregate: None,
             },
             parent_root: self.parent_root,
             slot: self.slot,
@@ -70,6 +87,7 @@ impl From<&BeaconHeaderSignedEnvelope> for BeaconBlockBuilder {
     fn from(header: &BeaconHeaderSignedEnvelope) -> Self {
         Self {
             block_hash: None,
+            consolidation_requests: None,
             deposits: vec![],
             parent_root: header.parent_root(),
             slot: header.slot(),
@@ -0,0 +1,387 @@
+//! # Proposers
+//! Stores who proposed every synced block and what it earned them, and publishes a leaderboard of
+//! proposers per time frame, ranked by blocks and by rewards. Validators whose operator we know,
+//! see `validator_entities`, are grouped under that entity, others rank by validator index.
+//!
+//! Consensus rewards come from the beacon node. Execution rewards are the payment to the proposer
+//! of blocks bought through a relay we track, see `mev_blocks`, and the priority fees of other
+//! blocks, which we take to be locally built. Priority fees come from stored transactions, see
+//! `execution_chain::transactions`, blocks without them count as no execution reward.
+//!
+//! Proposers are stored as blocks sync, there's no backfill, growing time frames cover what's
+//! stored. The leaderboard is published by the scheduler, not on every slot.
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use enum_iterator::all;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, PgExecutor, PgPool, Row};
+use tracing::{debug, info};
+
+use crate::{
+    caching::{self, CacheKey},
+    db, log,
+    time_frames::TimeFrame,
+    units::GweiNewtype,
+};
+
+use super::{node::rewards::BlockReward, Slot};
+
+/// Proposers per ranking.
+const LEADERBOARD_SIZE: i64 = 100;
+
+pub async fn store_proposer(
+    executor: impl PgExecutor<'_>,
+    slot: &Slot,
+    block_hash: Option<&String>,
+    block_reward: &BlockReward,
+) {
+    sqlx::query!(
+        "
+        INSERT INTO beacon_block_proposers (
+            slot,
+            timestamp,
+            proposer_index,
+            consensus_reward,
+            block_hash
+        )
+        VALUES ($1, $2, $3, $4, $5)
+        ",
+        slot.0,
+        slot.date_time(),
+        block_reward.proposer_index,
+        block_reward.total.0,
+        block_hash,
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub async fn delete_proposer(executor: impl PgExecutor<'_>, slot: &Slot) {
+    sqlx::query!("DELETE FROM beacon_block_proposers WHERE slot = $1", slot.0)
+        .execute(executor)
+        .await
+        .unwrap();
+}
+
+pub async fn delete_proposers(executor: impl PgExecutor<'_>, greater_than_or_equal: &Slot) {
+    sqlx::query!(
+        "DELETE FROM beacon_block_proposers WHERE slot >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidatorEntity {
+    validator_index: i32,
+    entity: String,
+}
+
+async fn store_validator_entities(executor: impl PgExecutor<'_>, entities: &[ValidatorEntity]) {
+    let validator_indices = entities
+        .iter()
+        .map(|entity| entity.validator_index)
+        .collect::<Vec<_>>();
+    let names = entities
+        .iter()
+        .map(|entity| entity.entity.clone())
+        .collect::<Vec<_>>();
+
+    sqlx::query!(
+        "
+        INSERT INTO validator_entities (validator_index, entity)
+        SELECT * FROM UNNEST($1::int[], $2::text[])
+        ON CONFLICT (validator_index) DO UPDATE SET entity = EXCLUDED.entity
+        ",
+        &validator_indices,
+        &names,
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Reads `validator_index,entity` rows, with a header, from the CSV at the given path. Known
+/// validators get their entity replaced.
+pub async fn import_validator_entities(path: &str) -> Result<()> {
+    log::init_with_env();
+
+    let entities = csv::Reader::from_path(path)?
+        .deserialize()
+        .collect::<Result<Vec<ValidatorEntity>, _>>()?;
+
+    let db_pool = db::get_db_pool("import-validator-entities").await;
+    store_validator_entities(&db_pool, &entities).await;
+
+    info!(count = entities.len(), path, "imported validator entities");
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposerRank {
+    pub block_count: i64,
+    pub consensus_rewards: GweiNewtype,
+    pub execution_rewards: GweiNewtype,
+    /// Whether `proposer` names an entity rather than a validator index.
+    pub is_entity: bool,
+    pub proposer: String,
+    pub total_rewards: GweiNewtype,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposerLeaderboard {
+    pub by_blocks: Vec<ProposerRank>,
+    pub by_rewards: Vec<ProposerRank>,
+}
+
+enum Ranking {
+    Blocks,
+    Rewards,
+}
+
+async fn get_proposer_ranks(
+    executor: impl PgExecutor<'_>,
+    since: &DateTime<Utc>,
+    ranking: Ranking,
+) -> Vec<ProposerRank> {
+    let order_by = match ranking {
+        Ranking::Blocks => "block_count DESC, total_rewards DESC",
+        Ranking::Rewards => "total_rewards DESC, block_count DESC",
+    };
+
+    sqlx::query(&format!(
+        "
+        WITH proposer_blocks AS (
+            SELECT
+                COALESCE(
+                    validator_entities.entity,
+                    beacon_block_proposers.proposer_index::TEXT
+                ) AS proposer,
+                validator_entities.entity IS NOT NULL AS is_entity,
+                beacon_block_proposers.consensus_reward,
+                COALESCE(
+                    ROUND(mev_blocks.bid_wei / 1e9),
+                    ROUND(local_blocks.tips_wei / 1e9),
+                    0
+                )::INT8 AS execution_reward
+            FROM beacon_block_proposers
+            LEFT JOIN validator_entities
+                ON validator_entities.validator_index = beacon_block_proposers.proposer_index
+            LEFT JOIN mev_blocks ON mev_blocks.block_hash = beacon_block_proposers.block_hash
+            LEFT JOIN LATERAL (
+                SELECT
+                    SUM(
+                        (transactions.effective_gas_price - blocks_next.base_fee_per_gas)
+                        * transactions.gas_used
+                    ) AS tips_wei
+                FROM blocks_next
+                JOIN transactions
+                    ON transactions.block_number = blocks_next.number
+                    AND transactions.block_hash = blocks_next.hash
+                WHERE blocks_next.hash = beacon_block_proposers.block_hash
+            ) local_blocks ON mev_blocks.block_hash IS NULL
+            WHERE beacon_block_proposers.timestamp >= $1
+        )
+        SELECT
+            proposer,
+            BOOL_OR(is_entity) AS is_entity,
+            COUNT(*) AS block_count,
+            SUM(consensus_reward)::INT8 AS consensus_rewards,
+            SUM(execution_reward)::INT8 AS execution_rewards,
+            SUM(consensus_reward + execution_reward)::INT8 AS total_rewards
+        FROM proposer_blocks
+        GROUP BY proposer
+        ORDER BY {order_by}, proposer ASC
+        LIMIT $2
+        "
+    ))
+    .bind(since)
+    .bind(LEADERBOARD_SIZE)
+    .map(|row: PgRow| ProposerRank {
+        block_count: row.get("block_count"),
+        consensus_rewards: GweiNewtype(row.get("consensus_rewards")),
+        execution_rewards: GweiNewtype(row.get("execution_rewards")),
+        is_entity: row.get("is_entity"),
+        proposer: row.get("proposer"),
+        total_rewards: GweiNewtype(row.get("total_rewards")),
+    })
+    .fetch_all(executor)
+    .await
+    .unwrap()
+}
+
+async fn get_proposer_leaderboard(db_pool: &PgPool, since: &DateTime<Utc>) -> ProposerLeaderboard {
+    ProposerLeaderboard {
+        by_blocks: get_proposer_ranks(db_pool, since, Ranking::Blocks).await,
+        by_rewards: get_proposer_ranks(db_pool, since, Ranking::Rewards).await,
+    }
+}
+
+pub async fn update_proposer_leaderboard(db_pool: &PgPool) {
+    let now = Utc::now();
+    let mut leaderboards = HashMap::new();
+
+    for time_frame in all::<TimeFrame>() {
+        let since = time_frame.start_timestamp_at(&now);
+        leaderboards.insert(time_frame, get_proposer_leaderboard(db_pool, &since).await);
+    }
+
+    caching::update_and_publish(db_pool, &CacheKey::ProposerLeaderboard, &leaderboards).await;
+    debug!("published proposer leaderboard");
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{
+            block_store, store_transactions, BlockTransactionBuilder, ExecutionNodeBlockBuilder,
+        },
+        units::WeiNewtype,
+    };
+
+    use super::*;
+
+    fn test_block_reward(proposer_index: i32, total: i64) -> BlockReward {
+        BlockReward {
+            proposer_index,
+            total: GweiNewtype(total),
+        }
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn proposer_leaderboard_test(test_db: &TestDb) {
+        let slot = Slot(9_000_000);
+        let since = slot.date_time();
+        let block_hash = "0xmev_block".to_string();
+
+        store_proposer(&test_db.pool, &slot, None, &test_block_reward(1, 10)).await;
+        store_proposer(&test_db.pool, &(slot + 1), None, &test_block_reward(2, 10)).await;
+        store_proposer(
+            &test_db.pool,
+            &(slot + 2),
+            Some(&block_hash),
+            &test_block_reward(3, 20),
+        )
+        .await;
+        sqlx::query!(
+            "
+            INSERT INTO mev_blocks (bid_wei, block_hash, block_number, slot, timestamp)
+            VALUES (5000000000, $1, 1, $2, $3)
+            ",
+            block_hash,
+            (slot + 2i32).0,
+            (slot + 2i32).date_time(),
+        )
+        .execute(&test_db.pool)
+        .await
+        .unwrap();
+        store_validator_entities(
+            &test_db.pool,
+            &[
+                ValidatorEntity {
+                    validator_index: 1,
+                    entity: "Pool".to_string(),
+                },
+                ValidatorEntity {
+                    validator_index: 2,
+                    entity: "Pool".to_string(),
+                },
+            ],
+        )
+        .await;
+
+        let leaderboard = get_proposer_leaderboard(&test_db.pool, &since).await;
+
+        assert_eq!(
+            leaderboard.by_blocks,
+            vec![
+                ProposerRank {
+                    block_count: 2,
+                    consensus_rewards: GweiNewtype(20),
+                    execution_rewards: GweiNewtype(0),
+                    is_entity: true,
+                    proposer: "Pool".to_string(),
+                    total_rewards: GweiNewtype(20),
+                },
+                ProposerRank {
+                    block_count: 1,
+                    consensus_rewards: GweiNewtype(20),
+                    execution_rewards: GweiNewtype(5),
+                    is_entity: false,
+                    proposer: "3".to_string(),
+                    total_rewards: GweiNewtype(25),
+                },
+            ]
+        );
+        assert_eq!(
+            leaderboard
+                .by_rewards
+                .iter()
+                .map(|rank| rank.proposer.as_str())
+                .collect::<Vec<_>>(),
+            vec!["3", "Pool"]
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn local_block_execution_reward_test(test_db: &TestDb) {
+        let slot = Slot(9_000_000);
+        let block = ExecutionNodeBlockBuilder::new("local_block_execution_reward")
+            .with_base_fee_per_gas(100)
+            .build();
+        block_store::store_block(&test_db.pool, &block, 1.0).await;
+        // A tip of 2 Gwei per gas, 10 gas used.
+        store_transactions(
+            &test_db.pool,
+            &block,
+            &[
+                BlockTransactionBuilder::new("local_block_execution_reward", 0)
+                    .with_effective_gas_price(WeiNewtype(2_000_000_100))
+                    .with_gas_used(10)
+                    .build(),
+            ],
+        )
+        .await;
+        store_proposer(
+            &test_db.pool,
+            &slot,
+            Some(&block.hash),
+            &test_block_reward(1, 10),
+        )
+        .await;
+
+        let leaderboard = get_proposer_leaderboard(&test_db.pool, &slot.date_time()).await;
+
+        assert_eq!(leaderboard.by_blocks[0].execution_rewards, GweiNewtype(20));
+        assert_eq!(leaderboard.by_blocks[0].total_rewards, GweiNewtype(30));
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn delete_proposers_test(test_db: &TestDb) {
+        let slot = Slot(9_000_000);
+        store_proposer(&test_db.pool, &slot, None, &test_block_reward(1, 10)).await;
+        store_proposer(&test_db.pool, &(slot + 1), None, &test_block_reward(1, 10)).await;
+
+        delete_proposer(&test_db.pool, &(slot + 1)).await;
+        let leaderboard = get_proposer_leaderboard(&test_db.pool, &slot.date_time()).await;
+        assert_eq!(leaderboard.by_blocks[0].block_count, 1);
+
+        delete_proposers(&test_db.pool, &slot).await;
+        let leaderboard = get_proposer_leaderboard(&test_db.pool, &slot.date_time()).await;
+        assert!(leaderboard.by_blocks.is_empty());
+    }
+}
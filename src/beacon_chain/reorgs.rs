@@ -0,0 +1,57 @@
+//! # Reorgs
+//! Records every time sync finds the chain reorganized under the slots it stored and rolls them
+//! back. The depth is the number of stored slots that no longer matched the chain.
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+
+use super::Slot;
+
+/// `slot` is the slot being synced when the reorg was found.
+pub async fn store_reorg(executor: impl PgExecutor<'_>, slot: &Slot, depth: i32) {
+    sqlx::query!(
+        "INSERT INTO beacon_reorgs (slot, timestamp, depth) VALUES ($1, $2, $3)",
+        slot.0,
+        slot.date_time(),
+        depth
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub async fn get_reorg_count_since(executor: impl PgExecutor<'_>, since: &DateTime<Utc>) -> i64 {
+    sqlx::query!(
+        r#"SELECT COUNT(*) AS "reorg_count!" FROM beacon_reorgs WHERE timestamp >= $1"#,
+        since
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .reorg_count
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::db::tests::TestDb;
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn reorg_count_test(test_db: &TestDb) {
+        let slot = Slot(9_000_000);
+        store_reorg(&test_db.pool, &slot, 1).await;
+        store_reorg(&test_db.pool, &(slot + 10i32), 2).await;
+
+        assert_eq!(
+            get_reorg_count_since(&test_db.pool, &slot.date_time()).await,
+            2
+        );
+        assert_eq!(
+            get_reorg_count_since(&test_db.pool, &(slot + 1i32).date_time()).await,
+            1
+        );
+    }
+}
@@ -0,0 +1,655 @@
+//! # Rewards
+//! What validators earn. `update_validator_rewards` publishes what a validator can expect to earn
+//! in a year, from issuance, tips and MEV, and stores the consensus rewards every validator
+//! actually earned, so stakers can audit their returns against our data.
+//!
+//! Consensus rewards per validator are split into attestation, proposer and sync committee
+//! rewards. They're summed over windows of a day's worth of epochs, and only for finalized
+//! windows, so they never need rolling back. Windows are synced from the last finalized one when
+//! none are stored, there's no backfill. Execution layer rewards, tips and MEV, go to the fee
+//! recipient and aren't included.
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use cached::proc_macro::cached;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool};
+use tracing::{debug, info};
+
+use crate::{
+    caching::{self, CacheKey},
+    db,
+    execution_chain::LONDON_HARD_FORK_TIMESTAMP,
+    log,
+    mev_blocks::{self, MevBlocksStorePostgres, RelayApiHttp},
+    serve::StateExtension,
+    units::{EthNewtype, GweiImprecise, GweiNewtype, WeiNewtype, GWEI_PER_ETH_F64},
+};
+
+use super::{
+    balances,
+    node::{
+        rewards::{BlockReward, ValidatorIdentity},
+        BeaconNode, BeaconNodeHttp,
+    },
+    Epoch, Slot, SECONDS_PER_EPOCH, SECONDS_PER_SLOT,
+};
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatorReward {
+    annual_reward: GweiImprecise,
+    apr: f64,
+}
+
+fn get_days_since_london() -> i64 {
+    (Utc::now() - *LONDON_HARD_FORK_TIMESTAMP).num_days()
+}
+
+async fn get_tips_since_london(executor: impl PgExecutor<'_>) -> sqlx::Result<GweiNewtype> {
+    sqlx::query!(
+        r#"
+            SELECT SUM(tips) / 1e9 AS "tips_since_london!" FROM blocks
+        "#,
+    )
+    .fetch_one(executor)
+    .await
+    .map(|row| GweiNewtype(row.tips_since_london.round() as i64))
+}
+
+async fn get_tips_reward(
+    executor: impl PgExecutor<'_>,
+    effective_balance_sum: GweiNewtype,
+) -> sqlx::Result<ValidatorReward> {
+    let GweiNewtype(tips_since_london) = get_tips_since_london(executor).await?;
+    debug!("tips since london {}", tips_since_london);
+
+    let tips_per_year = tips_since_london as f64 / get_days_since_london() as f64 * 365.25;
+    let single_validator_share =
+        (32_f64 * EthNewtype::GWEI_PER_ETH as f64) / effective_balance_sum.0 as f64;
+    debug!("single validator share {}", tips_since_london);
+
+    let tips_earned_per_year_per_validator = tips_per_year * single_validator_share;
+    debug!(
+        "tips earned per year per validator {}",
+        tips_earned_per_year_per_validator
+    );
+
+    let apr = tips_earned_per_year_per_validator / (32 * EthNewtype::GWEI_PER_ETH) as f64;
+    debug!("tips APR {}", apr);
+
+    Ok(ValidatorReward {
+        annual_reward: GweiImprecise(tips_earned_per_year_per_validator),
+        apr,
+    })
+}
+
+const MAX_EFFECTIVE_BALANCE: f64 = 32f64 * GWEI_PER_ETH_F64;
+
+lazy_static! {
+    pub static ref SLOTS_PER_YEAR: f64 = 365.25 * 24.0 * 60.0 * 60.0 / *SECONDS_PER_SLOT as f64;
+    static ref EPOCHS_PER_DAY: f64 = (24 * 60 * 60) as f64 / *SECONDS_PER_EPOCH as f64;
+    pub static ref EPOCHS_PER_YEAR: f64 = 365.25 * *EPOCHS_PER_DAY;
+}
+
+pub const BASE_REWARD_FACTOR: u8 = 64;
+
+/// Issuance per epoch at full participation, in Gwei. Every validator earns its base reward, its
+/// effective balance times the base reward factor over the square root of the balance at stake.
+pub fn get_issuance_per_epoch(balance_at_stake: f64) -> f64 {
+    ((BASE_REWARD_FACTOR as f64 * balance_at_stake) / balance_at_stake.sqrt().floor()).trunc()
+}
+
+// Consider staying in Gwei until the last moment instead of converting early.
+pub fn get_issuance_reward(GweiNewtype(effective_balance_sum): GweiNewtype) -> ValidatorReward {
+    let active_validators = effective_balance_sum as f64 / GWEI_PER_ETH_F64 / 32f64;
+
+    // Balance at stake (Gwei)
+    let max_balance_at_stake = active_validators * MAX_EFFECTIVE_BALANCE;
+
+    let max_issuance_per_epoch = get_issuance_per_epoch(max_balance_at_stake);
+    let max_issuance_per_year = max_issuance_per_epoch * *EPOCHS_PER_YEAR;
+
+    let annual_reward = max_issuance_per_year / active_validators;
+    let apr = max_issuance_per_year / effective_balance_sum as f64;
+
+    debug!(
+        "total effective balance: {} ETH",
+        effective_balance_sum as f64 / GWEI_PER_ETH_F64
+    );
+    debug!("nr of active validators: {}", active_validators);
+    debug!(
+        "max issuance per epoch: {} ETH",
+        max_issuance_per_epoch / GWEI_PER_ETH_F64
+    );
+    debug!(
+        "max issuance per year: {} ETH",
+        max_issuance_per_year / GWEI_PER_ETH_F64
+    );
+    debug!("APR: {:.2}%", apr * 100f64);
+
+    ValidatorReward {
+        annual_reward: GweiImprecise(annual_reward),
+        apr,
+    }
+}
+
+async fn get_mev_reward(
+    executor: impl PgExecutor<'_>,
+    effective_balance_sum: GweiNewtype,
+) -> Result<ValidatorReward> {
+    let mev_per_slot: EthNewtype = sqlx::query!(
+        "
+        SELECT AVG(bid_wei)::TEXT
+        FROM mev_blocks
+        WHERE timestamp > NOW() - INTERVAL '6 months'
+        "
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .avg
+    .context("expect at least one block in mev_blocks table before computing MEV reward")?
+    .parse::<WeiNewtype>()
+    .context("failed to parse MEV per slot as Wei")?
+    .into();
+
+    let effective_balance_sum_eth: EthNewtype = effective_balance_sum.into();
+    let active_validators: f64 = (effective_balance_sum_eth.0 / 32.0).floor();
+    let annual_reward_eth = mev_per_slot.0 * *SLOTS_PER_YEAR / active_validators;
+    let annual_reward = EthNewtype(annual_reward_eth).into();
+    let apr = annual_reward_eth / 32f64;
+
+    debug!(
+        "average MEV per slot in the last 6 months: {} ETH",
+        mev_per_slot.0
+    );
+    debug!(
+        "total effective balance: {} ETH",
+        effective_balance_sum_eth.0
+    );
+    debug!("nr of active validators: {}", active_validators);
+    debug!("MEV annual reward: {} ETH", annual_reward_eth);
+    debug!("MEV APR: {:.2}%", apr * 100f64);
+
+    Ok(ValidatorReward { annual_reward, apr })
+}
+
+#[derive(Debug, Serialize)]
+struct ValidatorRewards {
+    issuance: ValidatorReward,
+    tips: ValidatorReward,
+    mev: Option<ValidatorReward>,
+}
+
+async fn get_validator_rewards(db_pool: &PgPool, beacon_node: &BeaconNodeHttp) -> ValidatorRewards {
+    let last_effective_balance_sum =
+        balances::get_last_effective_balance_sum(db_pool, beacon_node).await;
+    let issuance_reward = get_issuance_reward(last_effective_balance_sum);
+    let tips_reward = get_tips_reward(db_pool, last_effective_balance_sum)
+        .await
+        .unwrap();
+    let mev = get_mev_reward(db_pool, last_effective_balance_sum)
+        .await
+        .unwrap();
+
+    ValidatorRewards {
+        issuance: issuance_reward,
+        mev: Some(mev),
+        tips: tips_reward,
+    }
+}
+
+/// One day on the Ethereum networks.
+const EPOCHS_PER_WINDOW: i32 = 225;
+/// Rows per insert, keeps the bound arrays a reasonable size.
+const INSERT_CHUNK_SIZE: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RewardComponents {
+    pub attestation: GweiNewtype,
+    pub proposer: GweiNewtype,
+    pub sync_committee: GweiNewtype,
+}
+
+impl RewardComponents {
+    fn zero() -> Self {
+        Self {
+            attestation: GweiNewtype(0),
+            proposer: GweiNewtype(0),
+            sync_committee: GweiNewtype(0),
+        }
+    }
+}
+
+fn window_first_epoch(epoch: &Epoch) -> Epoch {
+    Epoch(epoch.0 - epoch.0 % EPOCHS_PER_WINDOW)
+}
+
+/// Attestation rewards for an epoch are known once the next epoch is processed, waiting for the
+/// window to finalize covers that.
+fn is_window_finalized(first_epoch: &Epoch, finalized_epoch: &Epoch) -> bool {
+    first_epoch.0 + EPOCHS_PER_WINDOW < finalized_epoch.0
+}
+
+/// Block rewards the proposer sync stored for slots in the range, by slot.
+async fn get_stored_block_rewards(
+    executor: impl PgExecutor<'_>,
+    first_slot: &Slot,
+    last_slot: &Slot,
+) -> HashMap<i32, BlockReward> {
+    sqlx::query!(
+        "
+        SELECT slot, proposer_index, consensus_reward
+        FROM beacon_block_proposers
+        WHERE slot >= $1 AND slot <= $2
+        ",
+        first_slot.0,
+        last_slot.0
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| {
+        (
+            row.slot,
+            BlockReward {
+                proposer_index: row.proposer_index,
+                total: GweiNewtype(row.consensus_reward),
+            },
+        )
+    })
+    .collect()
+}
+
+/// Attestation rewards take one call per epoch. Proposer rewards come from what the proposer sync
+/// stored, only slots it skipped are asked for. Sync committee rewards are only asked for slots
+/// with a block, missed slots have none.
+async fn get_window_rewards(
+    db_pool: &PgPool,
+    beacon_node: &BeaconNodeHttp,
+    first_epoch: &Epoch,
+) -> Result<HashMap<i32, RewardComponents>> {
+    let mut rewards = HashMap::<i32, RewardComponents>::new();
+
+    let last_epoch = Epoch(first_epoch.0 + EPOCHS_PER_WINDOW - 1);
+    let mut stored_block_rewards =
+        get_stored_block_rewards(db_pool, &first_epoch.first_slot(), &last_epoch.last_slot()).await;
+
+    for epoch in (first_epoch.0..first_epoch.0 + EPOCHS_PER_WINDOW).map(Epoch) {
+        for reward in beacon_node.get_attestation_rewards(&epoch).await? {
+            let components = rewards
+                .entry(reward.validator_index)
+                .or_insert_with(RewardComponents::zero);
+            components.attestation = components.attestation + reward.total();
+        }
+
+        for slot in (epoch.first_slot().0..=epoch.last_slot().0).map(Slot) {
+            let block_reward = match stored_block_rewards.remove(&slot.0) {
+                Some(block_reward) => block_reward,
+                None => match beacon_node.get_block_reward(&slot).await? {
+                    Some(block_reward) => block_reward,
+                    None => continue,
+                },
+            };
+
+            let components = rewards
+                .entry(block_reward.proposer_index)
+                .or_insert_with(RewardComponents::zero);
+            components.proposer = components.proposer + block_reward.total;
+
+            for reward in beacon_node
+                .get_sync_committee_rewards(&slot)
+                .await?
+                .unwrap_or_default()
+            {
+                let components = rewards
+                    .entry(reward.validator_index)
+                    .or_insert_with(RewardComponents::zero);
+                components.sync_committee = components.sync_committee + reward.reward;
+            }
+        }
+
+        debug!(%epoch, "summed validator rewards for epoch");
+    }
+
+    Ok(rewards)
+}
+
+async fn store_window_rewards(
+    db_pool: &PgPool,
+    first_epoch: &Epoch,
+    rewards: &HashMap<i32, RewardComponents>,
+) {
+    let rewards = rewards.iter().collect::<Vec<_>>();
+    let mut transaction = db_pool.begin().await.unwrap();
+
+    for chunk in rewards.chunks(INSERT_CHUNK_SIZE) {
+        let validator_indices = chunk.iter().map(|(index, _)| **index).collect::<Vec<_>>();
+        let attestation = chunk
+            .iter()
+            .map(|(_, components)| components.attestation.0)
+            .collect::<Vec<_>>();
+        let proposer = chunk
+            .iter()
+            .map(|(_, components)| components.proposer.0)
+            .collect::<Vec<_>>();
+        let sync_committee = chunk
+            .iter()
+            .map(|(_, components)| components.sync_committee.0)
+            .collect::<Vec<_>>();
+
+        sqlx::query!(
+            "
+            INSERT INTO beacon_validator_rewards (
+                validator_index,
+                first_epoch,
+                last_epoch,
+                attestation,
+                proposer,
+                sync_committee
+            )
+            SELECT validator_index, $2, $3, attestation, proposer, sync_committee
+            FROM UNNEST($1::int[], $4::int8[], $5::int8[], $6::int8[])
+                AS rewards (validator_index, attestation, proposer, sync_committee)
+            ",
+            &validator_indices,
+            first_epoch.0,
+            first_epoch.0 + EPOCHS_PER_WINDOW - 1,
+            &attestation,
+            &proposer,
+            &sync_committee
+        )
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+    }
+
+    transaction.commit().await.unwrap();
+}
+
+async fn store_validator_pubkeys(db_pool: &PgPool, identities: &[ValidatorIdentity]) {
+    for chunk in identities.chunks(INSERT_CHUNK_SIZE) {
+        let validator_indices = chunk
+            .iter()
+            .map(|identity| identity.index)
+            .collect::<Vec<_>>();
+        let pubkeys = chunk
+            .iter()
+            .map(|identity| identity.validator.pubkey.clone())
+            .collect::<Vec<_>>();
+
+        sqlx::query!(
+            "
+            INSERT INTO beacon_validator_pubkeys (validator_index, pubkey)
+            SELECT * FROM UNNEST($1::int[], $2::text[])
+            ON CONFLICT (validator_index) DO NOTHING
+            ",
+            &validator_indices,
+            &pubkeys
+        )
+        .execute(db_pool)
+        .await
+        .unwrap();
+    }
+}
+
+async fn get_last_window_first_epoch(executor: impl PgExecutor<'_>) -> Option<Epoch> {
+    sqlx::query!("SELECT MAX(first_epoch) AS first_epoch FROM beacon_validator_rewards")
+        .fetch_one(executor)
+        .await
+        .unwrap()
+        .first_epoch
+        .map(Epoch)
+}
+
+/// Stores the rewards of every finalized window not yet stored.
+async fn sync_validator_rewards(db_pool: &PgPool, beacon_node: &BeaconNodeHttp) -> Result<()> {
+    let finalized_epoch = Epoch(beacon_node.get_last_finality_checkpoint().await?.epoch);
+
+    let mut first_epoch = match get_last_window_first_epoch(db_pool).await {
+        Some(last_first_epoch) => Epoch(last_first_epoch.0 + EPOCHS_PER_WINDOW),
+        None => {
+            let first_epoch = Epoch(window_first_epoch(&finalized_epoch).0 - EPOCHS_PER_WINDOW);
+            info!(%first_epoch, "no validator rewards stored, starting from last finalized window");
+            first_epoch
+        }
+    };
+
+    while is_window_finalized(&first_epoch, &finalized_epoch) {
+        info!(%first_epoch, "syncing validator rewards window");
+
+        let rewards = get_window_rewards(db_pool, beacon_node, &first_epoch).await?;
+        let last_slot = Epoch(first_epoch.0 + EPOCHS_PER_WINDOW - 1).last_slot();
+        let identities = beacon_node.get_validator_identities(&last_slot).await?;
+        store_validator_pubkeys(db_pool, &identities).await;
+        store_window_rewards(db_pool, &first_epoch, &rewards).await;
+
+        first_epoch = Epoch(first_epoch.0 + EPOCHS_PER_WINDOW);
+    }
+
+    Ok(())
+}
+
+/// Syncs MEV blocks, which the expected rewards include, then updates the validator rewards.
+pub async fn update_validator_rewards() -> Result<()> {
+    log::init_with_env();
+
+    info!("updating validator rewards");
+
+    let db_pool = db::get_db_pool("update-validator-rewards").await;
+
+    sqlx::migrate!().run(&db_pool).await.unwrap();
+
+    let beacon_node = BeaconNodeHttp::new();
+    let relay_api = RelayApiHttp::new();
+    let mev_blocks_store = MevBlocksStorePostgres::new(db_pool.clone());
+
+    mev_blocks::sync_mev_blocks(&mev_blocks_store, &beacon_node, &relay_api).await;
+
+    update_validator_rewards_with_pool(&db_pool, &beacon_node).await?;
+
+    info!("done updating validator rewards");
+
+    Ok(())
+}
+
+/// Publishes the expected rewards of a validator, then stores the rewards of every validator for
+/// windows finalized since the last run.
+pub async fn update_validator_rewards_with_pool(
+    db_pool: &PgPool,
+    beacon_node: &BeaconNodeHttp,
+) -> Result<()> {
+    let validator_rewards = get_validator_rewards(db_pool, beacon_node).await;
+    debug!("validator rewards: {:?}", validator_rewards);
+
+    caching::update_and_publish(db_pool, &CacheKey::ValidatorRewards, validator_rewards).await;
+
+    sync_validator_rewards(db_pool, beacon_node).await
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatorRewardsWindow {
+    pub attestation: GweiNewtype,
+    pub first_epoch: Epoch,
+    pub last_epoch: Epoch,
+    pub proposer: GweiNewtype,
+    pub sync_committee: GweiNewtype,
+    /// Start of the first epoch.
+    pub timestamp: DateTime<Utc>,
+    pub total: GweiNewtype,
+}
+
+/// Takes a validator index or a 0x-prefixed pubkey.
+async fn get_validator_reward_windows(
+    executor: impl PgExecutor<'_>,
+    validator: &str,
+) -> Vec<ValidatorRewardsWindow> {
+    let validator_index = validator.parse::<i32>().ok();
+    let pubkey = validator_index.is_none().then(|| validator.to_lowercase());
+
+    sqlx::query!(
+        "
+        SELECT first_epoch, last_epoch, attestation, proposer, sync_committee
+        FROM beacon_validator_rewards
+        WHERE validator_index = COALESCE(
+            $1,
+            (SELECT validator_index FROM beacon_validator_pubkeys WHERE pubkey = $2)
+        )
+        ORDER BY first_epoch ASC
+        ",
+        validator_index,
+        pubkey
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| {
+        let first_epoch = Epoch(row.first_epoch);
+        let attestation = GweiNewtype(row.attestation);
+        let proposer = GweiNewtype(row.proposer);
+        let sync_committee = GweiNewtype(row.sync_committee);
+        ValidatorRewardsWindow {
+            attestation,
+            first_epoch,
+            last_epoch: Epoch(row.last_epoch),
+            proposer,
+            sync_committee,
+            timestamp: first_epoch.first_slot().date_time(),
+            total: attestation + proposer + sync_committee,
+        }
+    })
+    .collect()
+}
+
+// Windows are added once a day.
+#[cached(
+    key = "String",
+    convert = r#"{validator.to_lowercase()}"#,
+    time = 600,
+    size = 10_000
+)]
+async fn get_validator_reward_windows_cached(
+    db_pool: &PgPool,
+    validator: &str,
+) -> Vec<ValidatorRewardsWindow> {
+    get_validator_reward_windows(db_pool, validator).await
+}
+
+#[derive(Deserialize)]
+pub struct ValidatorRewardsParams {
+    validator: String,
+}
+
+/// Takes a `validator` index or pubkey. Not found when we have no rewards for it.
+pub async fn validator_rewards(
+    state: StateExtension,
+    Query(params): Query<ValidatorRewardsParams>,
+) -> impl IntoResponse {
+    let rewards = get_validator_reward_windows_cached(&state.read_db_pool, &params.validator).await;
+
+    if rewards.is_empty() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=600, stale-while-revalidate=3600"),
+    );
+
+    (headers, Json(rewards)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{beacon_chain::proposers, db::tests::TestDb};
+
+    use super::*;
+
+    #[test]
+    fn window_first_epoch_test() {
+        assert_eq!(window_first_epoch(&Epoch(0)), Epoch(0));
+        assert_eq!(window_first_epoch(&Epoch(224)), Epoch(0));
+        assert_eq!(window_first_epoch(&Epoch(226)), Epoch(225));
+    }
+
+    #[test]
+    fn is_window_finalized_test() {
+        assert!(!is_window_finalized(&Epoch(0), &Epoch(225)));
+        assert!(is_window_finalized(&Epoch(0), &Epoch(226)));
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn stored_block_rewards_test(test_db: &TestDb) {
+        let block_reward = BlockReward {
+            proposer_index: 7,
+            total: GweiNewtype(30),
+        };
+        proposers::store_proposer(&test_db.pool, &Slot(101), None, &block_reward).await;
+        proposers::store_proposer(&test_db.pool, &Slot(200), None, &block_reward).await;
+
+        assert_eq!(
+            get_stored_block_rewards(&test_db.pool, &Slot(100), &Slot(199)).await,
+            HashMap::from([(101, block_reward)])
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn validator_rewards_test(test_db: &TestDb) {
+        let rewards = HashMap::from([(
+            7,
+            RewardComponents {
+                attestation: GweiNewtype(100),
+                proposer: GweiNewtype(20),
+                sync_committee: GweiNewtype(-5),
+            },
+        )]);
+        store_window_rewards(&test_db.pool, &Epoch(225), &rewards).await;
+        let identities = serde_json::from_value::<Vec<ValidatorIdentity>>(serde_json::json!([
+            { "index": "7", "validator": { "pubkey": "0xabc" } }
+        ]))
+        .unwrap();
+        store_validator_pubkeys(&test_db.pool, &identities).await;
+
+        let expected = vec![ValidatorRewardsWindow {
+            attestation: GweiNewtype(100),
+            first_epoch: Epoch(225),
+            last_epoch: Epoch(449),
+            proposer: GweiNewtype(20),
+            sync_committee: GweiNewtype(-5),
+            timestamp: Epoch(225).first_slot().date_time(),
+            total: GweiNewtype(115),
+        }];
+        assert_eq!(
+            get_validator_reward_windows(&test_db.pool, "7").await,
+            expected
+        );
+        assert_eq!(
+            get_validator_reward_windows(&test_db.pool, "0xABC").await,
+            expected
+        );
+        assert!(get_validator_reward_windows(&test_db.pool, "8")
+            .await
+            .is_empty());
+        assert_eq!(
+            get_last_window_first_epoch(&test_db.pool).await,
+            Some(Epoch(225))
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! # Slot stats
+//! How regularly blocks arrive: the average interval between blocks, the share of slots missed,
+//! and the longest gap between two blocks, per limited time frame up to the last synced slot.
+//! Published as chain health on every head, growing time frames would mean scanning every stored
+//! state each slot and are left out.
+use std::collections::HashMap;
+
+use enum_iterator::all;
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    caching::{self, CacheKey},
+    time_frames::{LimitedTimeFrame, TimeFrame},
+};
+
+use super::{Slot, SECONDS_PER_SLOT};
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotStats {
+    /// `None` with fewer than two blocks.
+    pub average_block_interval_seconds: Option<f64>,
+    pub block_count: i64,
+    /// Between two blocks in the time frame, a gap of one slot when none were missed.
+    pub longest_gap_seconds: Option<i64>,
+    pub missed_slot_count: i64,
+    pub missed_slot_rate: f64,
+    pub slot_count: i64,
+}
+
+/// Over the last `slot_count` stored slots.
+pub async fn get_slot_stats(executor: impl PgExecutor<'_>, slot_count: i32) -> SlotStats {
+    let row = sqlx::query!(
+        r#"
+        WITH slots AS (
+            SELECT
+                beacon_states.slot,
+                beacon_blocks.state_root IS NOT NULL AS has_block
+            FROM beacon_states
+            LEFT JOIN beacon_blocks ON beacon_blocks.state_root = beacon_states.state_root
+            WHERE beacon_states.slot > (SELECT MAX(slot) FROM beacon_states) - $1
+        ), block_slots AS (
+            SELECT
+                slot,
+                slot - LAG(slot) OVER (ORDER BY slot) AS slots_since_previous
+            FROM slots
+            WHERE has_block
+        )
+        SELECT
+            (SELECT COUNT(*) FROM slots) AS "slot_count!",
+            (SELECT COUNT(*) FROM block_slots) AS "block_count!",
+            (SELECT MIN(slot) FROM block_slots) AS first_block_slot,
+            (SELECT MAX(slot) FROM block_slots) AS last_block_slot,
+            (SELECT MAX(slots_since_previous) FROM block_slots) AS longest_gap_slots
+        "#,
+        slot_count,
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap();
+
+    let slot_count = row.slot_count;
+    let block_count = row.block_count;
+    let first_block_slot = row.first_block_slot.map(Slot);
+    let last_block_slot = row.last_block_slot.map(Slot);
+
+    let average_block_interval_seconds = match (first_block_slot, last_block_slot) {
+        (Some(first), Some(last)) if block_count > 1 => {
+            Some((last.0 - first.0) as f64 * *SECONDS_PER_SLOT as f64 / (block_count - 1) as f64)
+        }
+        _ => None,
+    };
+    let missed_slot_count = slot_count - block_count;
+
+    SlotStats {
+        average_block_interval_seconds,
+        block_count,
+        longest_gap_seconds: row
+            .longest_gap_slots
+            .map(|slots| slots as i64 * *SECONDS_PER_SLOT as i64),
+        missed_slot_count,
+        missed_slot_rate: if slot_count == 0 {
+            0.0
+        } else {
+            missed_slot_count as f64 / slot_count as f64
+        },
+        slot_count,
+    }
+}
+
+pub async fn update_chain_health(db_pool: &PgPool) {
+    let mut slot_stats = HashMap::new();
+
+    for limited_time_frame in all::<LimitedTimeFrame>() {
+        slot_stats.insert(
+            TimeFrame::Limited(limited_time_frame),
+            get_slot_stats(db_pool, limited_time_frame.slot_count() as i32).await,
+        );
+    }
+
+    caching::update_and_publish(db_pool, &CacheKey::ChainHealth, &slot_stats).await;
+    debug!("published chain health");
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        beacon_chain::{
+            store_state, tests::store_custom_test_block, BeaconBlockBuilder,
+            BeaconHeaderSignedEnvelopeBuilder,
+        },
+        db::tests::TestDb,
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn slot_stats_test(test_db: &TestDb) {
+        let mut connection = test_db.pool.acquire().await.unwrap();
+
+        // Blocks in slots 0, 1 and 4, slots 2 and 3 missed.
+        for slot in [0, 1, 4].map(Slot) {
+            let header = BeaconHeaderSignedEnvelopeBuilder::new(&format!("slot_stats_{slot}"))
+                .slot(&slot)
+                .build();
+            let block = Into::<BeaconBlockBuilder>::into(&header).build();
+            store_custom_test_block(&mut connection, &header, &block).await;
+        }
+        for slot in [2, 3].map(Slot) {
+            store_state(&mut *connection, &format!("0xslot_stats_{slot}"), &slot).await;
+        }
+
+        assert_eq!(
+            get_slot_stats(&mut *connection, 10).await,
+            SlotStats {
+                average_block_interval_seconds: Some(24.0),
+                block_count: 3,
+                longest_gap_seconds: Some(36),
+                missed_slot_count: 2,
+                missed_slot_rate: 0.4,
+                slot_count: 5,
+            }
+        );
+        assert_eq!(get_slot_stats(&mut *connection, 1).await.slot_count, 1);
+    }
+}
@@ -1,57 +1,49 @@
 use std::collections::HashMap;
 
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use tracing::{info, warn};
+
 use crate::{
-    beacon_chain::{self, node::BeaconNodeHttp, sync, Slot},
-    job_progress::JobProgress,
-    key_value_store::KeyValueStorePostgres,
+    beacon_chain::{self, node::BeaconNodeHttp, sync, BeaconNode, Slot},
+    db,
+    heal::{self, HealOptions, Healer},
+    log,
 };
-use pit_wall::Progress;
-use sqlx::postgres::PgPoolOptions;
-use tracing::{debug, info, warn};
-
-use crate::{beacon_chain::BeaconNode, db, log};
 
 // The first slot we have stored.
 const FIRST_STORED_ETH_SUPPLY_SLOT: Slot = Slot(0);
 
 const HEAL_BEACON_STATES_KEY: &str = "heal-beacon-states";
 
-pub async fn heal_beacon_states() {
-    log::init_with_env();
+struct BeaconStatesHealer {
+    beacon_node: BeaconNodeHttp,
+    db_pool: PgPool,
+}
 
-    info!("healing reorged states");
+#[async_trait]
+impl Healer for BeaconStatesHealer {
+    type Item = Slot;
 
-    let db_pool = PgPoolOptions::new()
-        .max_connections(1)
-        .connect(&db::get_db_url_with_name("heal-beacon-states"))
-        .await
-        .unwrap();
-    let key_value_store = KeyValueStorePostgres::new(db_pool.clone());
-    let job_progress = JobProgress::new(HEAL_BEACON_STATES_KEY, &key_value_store);
+    fn name(&self) -> &'static str {
+        HEAL_BEACON_STATES_KEY
+    }
 
-    let beacon_node = BeaconNodeHttp::new();
-    let last_slot = beacon_chain::get_last_state(&db_pool)
-        .await
-        .expect("a beacon state should be stored before trying to heal any")
-        .slot
-        .0;
-    let last_checked = job_progress.get().await;
-    let starting_slot = last_checked.unwrap_or(FIRST_STORED_ETH_SUPPLY_SLOT).0;
-
-    debug!(
-        %starting_slot,
-        %last_slot,
-        "checking first stored slot to last slot for gaps"
-    );
-
-    let work_todo: u64 = (last_slot - starting_slot) as u64;
-    let mut progress = Progress::new("heal-beacon-states", work_todo);
-
-    let slots = (starting_slot..=last_slot).collect::<Vec<i32>>();
-
-    for chunk in slots.chunks(10000) {
-        let first = chunk.first().unwrap();
-        let last = chunk.last().unwrap();
+    async fn items_to_heal(&self, checkpoint: Option<Slot>) -> Vec<Slot> {
+        let last_slot = beacon_chain::get_last_state(&self.db_pool)
+            .await
+            .expect("a beacon state should be stored before trying to heal any")
+            .slot;
+        // The checkpoint is the last slot we healed, resume after it.
+        let starting_slot = checkpoint.map_or(FIRST_STORED_ETH_SUPPLY_SLOT, |slot| slot + 1);
+
+        Slot::range_inclusive(starting_slot, last_slot).collect()
+    }
+
+    async fn heal_chunk(&self, slots: &[Slot], dry_run: bool) -> Result<()> {
+        let first = slots.first().unwrap();
+        let last = slots.last().unwrap();
         let stored_states = sqlx::query!(
             "
                 SELECT
@@ -66,41 +58,68 @@ pub async fn heal_beacon_states() {
                 ORDER BY
                     slot ASC
             ",
-            *first,
-            *last
+            first.0,
+            last.0
         )
-        .fetch_all(&db_pool)
+        .fetch_all(&self.db_pool)
         .await
         .unwrap()
         .into_iter()
         .map(|row| (row.slot, row.state_root))
         .collect::<HashMap<i32, String>>();
 
-        for slot in *first..=*last {
-            let stored_state_root = stored_states.get(&slot).unwrap();
-            let state_root = beacon_node
-                .get_state_root_by_slot(&slot.into())
-                .await
-                .unwrap()
-                .expect("expect state_root to exist for historic slots");
+        for slot in slots {
+            let stored_state_root = stored_states.get(&slot.0).unwrap();
+            let state_root = self
+                .beacon_node
+                .get_state_root_by_slot(slot)
+                .await?
+                .ok_or_else(|| anyhow!("no state root for historic slot {slot}"))?;
 
             if *stored_state_root != state_root {
+                if dry_run {
+                    warn!(%slot, "state root mismatch, dry run, skipping resync");
+                    continue;
+                }
+
                 warn!("state root mismatch, rolling back stored and resyncing");
-                sync::rollback_slot(&mut db_pool.acquire().await.unwrap(), &slot.into())
-                    .await
-                    .unwrap();
-                sync::sync_slot_by_state_root(&db_pool, &beacon_node, &state_root, &slot.into())
-                    .await
-                    .unwrap();
+                let mut connection = self.db_pool.acquire().await?;
+                sync::rollback_slot(&mut connection, slot).await?;
+                sync::sync_slot_by_state_root(&self.db_pool, &self.beacon_node, &state_root, slot)
+                    .await?;
                 info!(%slot, "healed state at slot");
             }
-
-            progress.inc_work_done();
         }
 
-        job_progress.set(&last.into()).await;
-        info!("{}", progress.get_progress_string());
+        Ok(())
     }
+}
+
+pub async fn heal_beacon_states() {
+    heal_beacon_states_with_dry_run(heal::dry_run_from_args()).await;
+}
+
+pub async fn heal_beacon_states_with_dry_run(dry_run: bool) {
+    log::init_with_env();
+
+    info!("healing reorged states");
+
+    let db_pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&db::get_db_url_with_name("heal-beacon-states"))
+        .await
+        .unwrap();
+
+    let healer = BeaconStatesHealer {
+        beacon_node: BeaconNodeHttp::new(),
+        db_pool: db_pool.clone(),
+    };
+
+    let options = HealOptions {
+        chunk_size: 10000,
+        dry_run,
+    };
+    heal::heal(&db_pool, &healer, &options).await.unwrap();
 
     info!("done healing beacon states");
 }
@@ -5,6 +5,7 @@ use sqlx::PgExecutor;
 use super::Slot;
 
 pub use heal::heal_beacon_states;
+pub use heal::heal_beacon_states_with_dry_run;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct BeaconState {
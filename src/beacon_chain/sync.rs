@@ -1,25 +1,35 @@
 use anyhow::{anyhow, Result};
+use async_trait::async_trait;
 use chrono::Duration;
 use futures::{stream, SinkExt, Stream, StreamExt};
 use lazy_static::lazy_static;
-use serde::Deserialize;
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use sqlx::{Acquire, PgConnection, PgExecutor};
-use std::{cmp::Ordering, collections::VecDeque};
+use sqlx::{PgConnection, PgExecutor};
+use std::{cmp::Ordering, collections::VecDeque, sync::Arc};
 use tracing::{debug, info, warn};
 
-use crate::beacon_chain::withdrawals;
+use crate::beacon_chain::{
+    consolidations, finality, graffiti, proposers, reorgs, slot_stats, withdrawals,
+};
 use crate::{
-    beacon_chain::{balances, deposits, issuance, slot_from_string},
+    analysis::{self, Analysis},
+    beacon_chain::{balances, deposits, issuance},
     db,
-    json_codecs::i32_from_string,
+    health::{self, SyncHealth},
     log,
     performance::TimedExt,
+    rollback::RollbackPoint,
+    shutdown::ShutdownSignal,
 };
-use crate::{eth_supply, supply_dashboard_analysis};
+use crate::{eth_supply, supply_dashboard_analysis, supply_milestones};
 
-use super::node::{BeaconBlock, BeaconNode, BeaconNodeHttp, StateRoot, ValidatorBalance};
-use super::{blocks, states, BeaconHeaderSignedEnvelope, Slot, BEACON_URL};
+use super::consolidations::Consolidation;
+use super::node::{
+    events::{BeaconEvent, HeadEvent},
+    rewards::BlockReward,
+    BeaconBlock, BeaconNode, BeaconNodeHttp, FinalityCheckpoints, StateRoot, ValidatorBalance,
+};
+use super::{blocks, states, BeaconHeaderSignedEnvelope, Slot};
 
 lazy_static! {
     static ref BLOCK_LAG_LIMIT: Duration = Duration::minutes(5);
@@ -84,6 +94,9 @@ impl Iterator for SlotRangeIntoIterator {
 }
 
 struct SyncData {
+    block_reward: Option<BlockReward>,
+    consolidations: Vec<Consolidation>,
+    finality_checkpoints: Option<FinalityCheckpoints>,
     header_block_tuple: Option<(BeaconHeaderSignedEnvelope, BeaconBlock)>,
     validator_balances: Option<Vec<ValidatorBalance>>,
 }
@@ -127,6 +140,30 @@ async fn gather_sync_data(
         }
     };
 
+    let consolidations = match header_block_tuple {
+        None => vec![],
+        Some((_, ref block)) => {
+            consolidations::get_consolidations(beacon_node, state_root, block).await?
+        }
+    };
+
+    // Nodes only serve block rewards since Altair, a reward we can't get shouldn't stop the sync.
+    // Like validator balances, rewards are computed from the state, which is slow for older slots.
+    let block_reward = match header_block_tuple {
+        None => None,
+        Some(_) if sync_lag > &BLOCK_LAG_LIMIT => {
+            warn!(%sync_lag, "block lag over limit, skipping get_block_reward");
+            None
+        }
+        Some(_) => match beacon_node.get_block_reward(slot).await {
+            Ok(block_reward) => block_reward,
+            Err(err) => {
+                warn!(%slot, %err, "failed to get block reward, skipping proposer");
+                None
+            }
+        },
+    };
+
     // Whenever we fall behind, getting validator balances for older slots from lighthouse takes a
     // long time. This means if we fall behind too far we never catch up, as syncing one slot now
     // takes longer than it takes for a new slot to appear (12 seconds).
@@ -146,7 +183,25 @@ async fn gather_sync_data(
         }
     };
 
+    // Like validator balances, checkpoints for older states are slow to get, and nodes may have
+    // pruned the state altogether. Finality is only interesting near the head, missing some is
+    // fine.
+    let finality_checkpoints = if sync_lag > &BLOCK_LAG_LIMIT {
+        None
+    } else {
+        let finality_checkpoints = beacon_node
+            .get_finality_checkpoints_by_state(state_root)
+            .await?;
+        if finality_checkpoints.is_none() {
+            warn!(%slot, state_root, "no finality checkpoints for state, skipping finality");
+        }
+        finality_checkpoints
+    };
+
     let sync_data = SyncData {
+        block_reward,
+        consolidations,
+        finality_checkpoints,
         header_block_tuple,
         validator_balances,
     };
@@ -174,6 +229,9 @@ pub async fn sync_slot_by_state_root(
     debug!(%sync_lag, "beacon sync lag");
 
     let SyncData {
+        block_reward,
+        consolidations,
+        finality_checkpoints,
         header_block_tuple,
         validator_balances,
     } = gather_sync_data(beacon_node, state_root, slot, &sync_lag).await?;
@@ -226,9 +284,28 @@ pub async fn sync_slot_by_state_root(
                 header,
             )
             .await;
+
+            consolidations::store_consolidations(&mut transaction, slot, &consolidations).await;
+            graffiti::store_graffiti(&mut *transaction, slot, &block.body.graffiti).await;
+
+            if let Some(ref block_reward) = block_reward {
+                proposers::store_proposer(
+                    &mut *transaction,
+                    slot,
+                    block.block_hash(),
+                    block_reward,
+                )
+                .await;
+            }
         }
     }
 
+    if let Some(ref finality_checkpoints) = finality_checkpoints {
+        finality::store_finality(&mut *transaction, slot, finality_checkpoints).await;
+    }
+
+    let mut new_milestones = Vec::new();
+
     if let Some(ref validator_balances) = validator_balances {
         debug!("validator balances present");
         let validator_balances_sum = balances::sum_validator_balances(validator_balances);
@@ -259,11 +336,16 @@ pub async fn sync_slot_by_state_root(
             .await;
         }
 
-        eth_supply::sync_eth_supply(&mut transaction, slot).await;
+        let supply_slots = eth_supply::sync_eth_supply(&mut transaction, slot).await;
+        new_milestones = supply_milestones::check_slots(&mut transaction, &supply_slots).await;
     }
 
     transaction.commit().await?;
 
+    if !new_milestones.is_empty() {
+        supply_milestones::on_new_milestones(db_pool, &new_milestones).await;
+    }
+
     let last_on_chain_state_root = beacon_node
         .get_last_header()
         .await?
@@ -283,79 +365,124 @@ pub async fn sync_slot_by_state_root(
 
 async fn update_deferrable_analysis(db_pool: &PgPool) -> Result<()> {
     supply_dashboard_analysis::update_cache(db_pool).await?;
+    consolidations::update_consolidations_by_day(db_pool).await;
+    slot_stats::update_chain_health(db_pool).await;
 
     Ok(())
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct FinalizedCheckpointEvent {
-    block: String,
-    state: String,
-    #[serde(deserialize_with = "i32_from_string")]
-    epoch: i32,
-}
+/// How often to ask for the head while the events subscription is down.
+const HEAD_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(4);
+/// How long to poll before subscribing to events again.
+const RESUBSCRIBE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
 
-#[derive(Clone, Debug, Deserialize, PartialEq)]
-struct HeadEvent {
-    #[serde(deserialize_with = "slot_from_string")]
-    slot: Slot,
-    /// block_root
-    block: String,
-    state: String,
+/// Turns heads, from events or polling, into slots to sync.
+struct HeadSlots {
+    last_block_root: Option<String>,
+    last_slot: Slot,
 }
 
-impl From<BeaconHeaderSignedEnvelope> for HeadEvent {
-    fn from(envelope: BeaconHeaderSignedEnvelope) -> Self {
+impl HeadSlots {
+    fn new(slot_to_follow: Slot) -> Self {
         Self {
-            state: envelope.header.message.state_root,
-            block: envelope.root,
-            slot: envelope.header.message.slot,
+            last_block_root: None,
+            last_slot: slot_to_follow,
         }
     }
-}
 
-async fn stream_slots(slot_to_follow: Slot) -> impl Stream<Item = Slot> {
-    let url_string = format!("{}/eth/v1/events/?topics=head", *BEACON_URL);
-    let url = reqwest::Url::parse(&url_string).unwrap();
+    /// The head slot, preceded by any slots between the last head and this one. A head at or
+    /// below the last slot is passed on as is, the sync checks whether it replaced what we stored.
+    fn slots_through(&mut self, head: &HeadEvent) -> Vec<Slot> {
+        // Polling sees the same head more than once.
+        if self.last_block_root.as_ref() == Some(&head.block) {
+            return vec![];
+        }
 
-    let client = eventsource::reqwest::Client::new(url);
-    let (mut tx, rx) = futures::channel::mpsc::unbounded();
+        debug!(slot = %head.slot, block_root = head.block, state_root = head.state, "new head");
 
-    tokio::spawn(async move {
-        let mut last_slot = slot_to_follow;
+        let slots = if head.slot > self.last_slot {
+            // Detect forward gaps in received slots, and fill them in.
+            if head.slot != self.last_slot + 1 {
+                debug!(
+                    head_slot = head.slot.to_string(),
+                    last_slot = %self.last_slot,
+                    "head slot is more than one ahead of last_slot"
+                );
+            }
+            Slot::range_inclusive(self.last_slot + 1, head.slot).collect()
+        } else {
+            vec![head.slot]
+        };
+
+        self.last_block_root = Some(head.block.clone());
+        self.last_slot = head.slot;
 
-        for event in client {
-            let event = event.unwrap();
-            match event.event_type {
-                Some(ref event_type) if event_type == "head" => {
-                    let head = serde_json::from_str::<HeadEvent>(&event.data).unwrap();
+        slots
+    }
+}
 
-                    debug!(?head, "received new head");
+/// Follows heads through the beacon node's events. Whenever the subscription fails, polls for the
+/// head instead, and tries to subscribe again after a while.
+///
+/// Reorgs are only logged, the sync notices the stored slots no longer match the chain when the
+/// next head comes in and rolls back then.
+async fn stream_slots(slot_to_follow: Slot) -> impl Stream<Item = Slot> {
+    let (mut tx, rx) = futures::channel::mpsc::unbounded();
 
-                    // Detect forward gaps in received slots, and fill them in.
-                    if head.slot > last_slot && head.slot != last_slot + 1 {
+    tokio::spawn(async move {
+        let beacon_node = BeaconNodeHttp::new();
+        let mut head_slots = HeadSlots::new(slot_to_follow);
+
+        loop {
+            let mut events = beacon_node.stream_events();
+            while let Some(event) = events.next().await {
+                match event {
+                    BeaconEvent::FinalizedCheckpoint(checkpoint) => {
                         debug!(
-                            head_slot = head.slot.to_string(),
-                            %last_slot, "head slot is more than one ahead of last_slot"
+                            epoch = checkpoint.epoch,
+                            block_root = checkpoint.block,
+                            state_root = checkpoint.state,
+                            "new finalized checkpoint"
                         );
-
-                        for missing_slot in (last_slot + 1).0..head.slot.0 {
-                            debug!(missing_slot, "adding missing slot to slots stream");
-                            tx.send(Slot(missing_slot)).await.unwrap();
+                    }
+                    BeaconEvent::Head(head) => {
+                        for slot in head_slots.slots_through(&head) {
+                            // The receiver may stop listening, e.g. on shutdown.
+                            if tx.send(slot).await.is_err() {
+                                return;
+                            }
                         }
                     }
-
-                    last_slot = head.slot;
-                    tx.send(head.slot).await.unwrap();
-                }
-                Some(event) => {
-                    warn!(event, "received a server event that was not a head");
+                    BeaconEvent::Reorg(reorg) => {
+                        warn!(
+                            slot = %reorg.slot,
+                            depth = reorg.depth,
+                            old_head_block = reorg.old_head_block,
+                            new_head_block = reorg.new_head_block,
+                            "beacon node reorged"
+                        );
+                    }
                 }
-                None => {
-                    debug!("received an empty server event");
+            }
+
+            warn!("beacon events subscription ended, polling for heads");
+
+            let poll_until = std::time::Instant::now() + RESUBSCRIBE_INTERVAL;
+            while std::time::Instant::now() < poll_until {
+                match beacon_node.get_last_header().await {
+                    Ok(header) => {
+                        for slot in head_slots.slots_through(&HeadEvent::from(header)) {
+                            if tx.send(slot).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(err) => warn!(%err, "failed to poll for beacon head"),
                 }
+                tokio::time::sleep(HEAD_POLL_INTERVAL).await;
             }
+
+            debug!("subscribing to beacon events again");
         }
     });
 
@@ -395,35 +522,59 @@ async fn stream_slots_from_last(db_pool: &PgPool) -> impl Stream<Item = Slot> {
     stream_slots_from(&next_slot_to_sync).await
 }
 
+pub struct BeaconChainRollback;
+
+#[async_trait]
+impl Analysis for BeaconChainRollback {
+    fn name(&self) -> &'static str {
+        "beacon_chain"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        match point {
+            RollbackPoint::BlockNumbersGte(_) => (),
+            RollbackPoint::Slot(slot) => {
+                consolidations::delete_consolidations(&mut *transaction, slot).await;
+                finality::delete_finality(&mut *transaction, slot).await;
+                proposers::delete_proposer(&mut *transaction, slot).await;
+                graffiti::delete_graffiti(&mut *transaction, slot).await;
+                blocks::delete_block(&mut *transaction, slot).await;
+                issuance::delete_issuance(&mut *transaction, slot).await;
+                balances::delete_validator_sum(&mut *transaction, slot).await;
+                states::delete_state(&mut *transaction, slot).await;
+            }
+            RollbackPoint::SlotsGte(greater_than_or_equal) => {
+                consolidations::delete_consolidations_gte(&mut *transaction, greater_than_or_equal)
+                    .await;
+                finality::delete_finalities(&mut *transaction, greater_than_or_equal).await;
+                proposers::delete_proposers(&mut *transaction, greater_than_or_equal).await;
+                graffiti::delete_graffitis(&mut *transaction, greater_than_or_equal).await;
+                blocks::delete_blocks(&mut *transaction, greater_than_or_equal).await;
+                issuance::delete_issuances(&mut *transaction, greater_than_or_equal).await;
+                balances::delete_validator_sums(&mut *transaction, greater_than_or_equal).await;
+                states::delete_states(&mut *transaction, greater_than_or_equal).await;
+            }
+        }
+    }
+}
+
+// Every analysis with something to roll back is among the cache analyses, the others reach
+// outside systems.
 pub async fn rollback_slots(
     executor: &mut PgConnection,
     greater_than_or_equal: &Slot,
 ) -> Result<()> {
     debug!("rolling back data based on slots gte {greater_than_or_equal}");
-    let mut transaction = executor.begin().await?;
-    eth_supply::rollback_supply_from_slot(&mut transaction, greater_than_or_equal)
+    analysis::cache_analyses()
+        .rollback(executor, &RollbackPoint::SlotsGte(*greater_than_or_equal))
         .await
-        .unwrap();
-    blocks::delete_blocks(&mut *transaction, greater_than_or_equal).await;
-    issuance::delete_issuances(&mut *transaction, greater_than_or_equal).await;
-    balances::delete_validator_sums(&mut *transaction, greater_than_or_equal).await;
-    states::delete_states(&mut *transaction, greater_than_or_equal).await;
-    transaction.commit().await?;
-    Ok(())
 }
 
 pub async fn rollback_slot(executor: &mut PgConnection, slot: &Slot) -> Result<()> {
     debug!("rolling back data based on slot {slot}");
-    let mut transaction = executor.begin().await?;
-    eth_supply::rollback_supply_slot(&mut transaction, slot)
+    analysis::cache_analyses()
+        .rollback(executor, &RollbackPoint::Slot(*slot))
         .await
-        .unwrap();
-    blocks::delete_block(&mut *transaction, slot).await;
-    issuance::delete_issuance(&mut *transaction, slot).await;
-    balances::delete_validator_sum(&mut *transaction, slot).await;
-    states::delete_state(&mut *transaction, slot).await;
-    transaction.commit().await?;
-    Ok(())
 }
 
 async fn estimate_slots_remaining(
@@ -434,7 +585,7 @@ async fn estimate_slots_remaining(
     let last_synced_slot = states::get_last_state(executor)
         .await
         .map_or(Slot(0), |state| state.slot);
-    last_on_chain.slot().0 - last_synced_slot.0
+    last_on_chain.slot() - last_synced_slot
 }
 
 /// Searches backwards from the starting candidate to find a slot where stored and on-chain
@@ -485,6 +636,8 @@ pub async fn sync_beacon_states() -> Result<()> {
 
     info!("syncing beacon states");
 
+    let shutdown_signal = ShutdownSignal::listen();
+
     let db_pool = PgPoolOptions::new()
         .max_connections(3)
         .connect(&db::get_db_url_with_name("sync-beacon-states"))
@@ -495,7 +648,14 @@ pub async fn sync_beacon_states() -> Result<()> {
 
     let beacon_node = BeaconNodeHttp::new();
 
-    let mut slots_stream = stream_slots_from_last(&db_pool).await;
+    let sync_health = Arc::new(SyncHealth::new(Duration::minutes(5)));
+    tokio::spawn(health::serve_sync_probes(
+        sync_health.clone(),
+        db_pool.clone(),
+        Arc::new(beacon_node.clone()),
+    ));
+
+    let mut slots_stream = shutdown_signal.stop_stream(stream_slots_from_last(&db_pool).await);
 
     // This queue allows us to queue slots to sync as we need between processing new head events.
     // The two expected scenarios are:
@@ -522,6 +682,11 @@ pub async fn sync_beacon_states() -> Result<()> {
         // the stream to the queue.
         // To be able to push things onto the queue during the loop, the lock should not be held.
         while let Some(slot) = slots_queue.pop_front() {
+            // Slots are synced one at a time, on restart we continue from the last stored slot.
+            if shutdown_signal.is_requested() {
+                break;
+            }
+
             debug!(%slot, "analyzing next slot on the queue");
 
             let on_chain_state_root = beacon_node
@@ -556,6 +721,8 @@ pub async fn sync_beacon_states() -> Result<()> {
                 sync_slot_by_state_root(&db_pool, &beacon_node, &on_chain_state_root, &slot)
                     .timed("sync_slot_by_state_root")
                     .await?;
+
+                sync_health.set_synced();
             } else {
                 // 2. roll back to last matching state_root, queue all slots up to and including
                 //    current for sync.
@@ -571,9 +738,10 @@ pub async fn sync_beacon_states() -> Result<()> {
                 warn!(slot = last_matching_slot.0, "rolling back to slot");
 
                 rollback_slots(&mut *db_pool.acquire().await?, &first_invalid_slot).await?;
+                reorgs::store_reorg(&db_pool, &slot, slot.0 - first_invalid_slot.0).await;
 
-                for invalid_slot in (first_invalid_slot.0..=slot.0).rev() {
-                    slots_queue.push_front(invalid_slot.into());
+                for invalid_slot in Slot::range_inclusive(first_invalid_slot, slot).rev() {
+                    slots_queue.push_front(invalid_slot);
                 }
             }
         }
@@ -581,6 +749,9 @@ pub async fn sync_beacon_states() -> Result<()> {
         progress.inc_work_done();
     }
 
+    info!("stopped syncing beacon states");
+    db_pool.close().await;
+
     Ok(())
 }
 
@@ -597,6 +768,36 @@ mod tests {
         assert_eq!(range, vec![Slot(1), Slot(2), Slot(3), Slot(4)]);
     }
 
+    fn test_head(slot: i32, block: &str) -> HeadEvent {
+        HeadEvent {
+            slot: Slot(slot),
+            block: block.to_string(),
+            state: format!("{block}_state"),
+        }
+    }
+
+    #[test]
+    fn head_slots_test() {
+        let mut head_slots = HeadSlots::new(Slot(10));
+
+        assert_eq!(
+            head_slots.slots_through(&test_head(11, "0xa")),
+            vec![Slot(11)]
+        );
+        // A repeated head, as polling sees it.
+        assert!(head_slots.slots_through(&test_head(11, "0xa")).is_empty());
+        // A missed slot.
+        assert_eq!(
+            head_slots.slots_through(&test_head(13, "0xb")),
+            vec![Slot(12), Slot(13)]
+        );
+        // A reorg replacing the head.
+        assert_eq!(
+            head_slots.slots_through(&test_head(13, "0xc")),
+            vec![Slot(13)]
+        );
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn stream_slots_from_test() {
         let slots_stream = stream_slots_from(&Slot(4_300_000)).await;
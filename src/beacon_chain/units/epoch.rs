@@ -0,0 +1,101 @@
+use std::{fmt::Display, ops::Sub};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::beacon_chain::{SECONDS_PER_EPOCH, SLOTS_PER_EPOCH};
+
+use super::Slot;
+
+// Beacon chain epochs are 32 slots, 6.4 minutes, starting from genesis. Like slots, i32 lasts for
+// centuries.
+#[derive(
+    Clone, Copy, Debug, Deserialize, Eq, Ord, PartialOrd, PartialEq, Serialize, sqlx::Type,
+)]
+#[sqlx(transparent)]
+pub struct Epoch(pub i32);
+
+impl Epoch {
+    pub fn first_slot(&self) -> Slot {
+        Slot(self.0 * *SLOTS_PER_EPOCH)
+    }
+
+    pub fn last_slot(&self) -> Slot {
+        Slot((self.0 + 1) * *SLOTS_PER_EPOCH - 1)
+    }
+
+    /// Timestamp of the first slot of the epoch.
+    pub fn start_date_time(&self) -> DateTime<Utc> {
+        self.first_slot().date_time()
+    }
+
+    /// Timestamp of the first slot of the next epoch, the epoch itself ends just before.
+    pub fn end_date_time(&self) -> DateTime<Utc> {
+        Self(self.0 + 1).start_date_time()
+    }
+
+    /// Number of epochs in the duration, including a last partial one as a fraction.
+    pub fn count_in(duration: &Duration) -> f64 {
+        duration.num_milliseconds() as f64 / (*SECONDS_PER_EPOCH as f64 * 1000.0)
+    }
+}
+
+impl Display for Epoch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Number of epochs between two epochs.
+impl Sub<Epoch> for Epoch {
+    type Output = i32;
+
+    fn sub(self, rhs: Epoch) -> Self::Output {
+        self.0 - rhs.0
+    }
+}
+
+impl From<Slot> for Epoch {
+    fn from(slot: Slot) -> Self {
+        Self(slot.0 / *SLOTS_PER_EPOCH)
+    }
+}
+
+impl From<&Slot> for Epoch {
+    fn from(slot: &Slot) -> Self {
+        Into::<Epoch>::into(*slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_test() {
+        assert_eq!(Epoch(0).first_slot(), Slot(0));
+        assert_eq!(Epoch(0).last_slot(), Slot(31));
+        assert_eq!(Epoch(2).first_slot(), Slot(64));
+        assert_eq!(Epoch::from(Slot(63)), Epoch(1));
+        assert_eq!(Epoch::from(Slot(64)), Epoch(2));
+    }
+
+    #[test]
+    fn date_time_test() {
+        assert_eq!(
+            Epoch(0).start_date_time(),
+            "2020-12-01T12:00:23Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(
+            Epoch(0).end_date_time(),
+            "2020-12-01T12:06:47Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(Epoch(0).end_date_time(), Epoch(1).start_date_time());
+    }
+
+    #[test]
+    fn count_in_test() {
+        assert_eq!(Epoch::count_in(&Duration::days(1)), 225.0);
+        assert_eq!(Epoch::count_in(&Duration::minutes(5)), 0.78125);
+    }
+}
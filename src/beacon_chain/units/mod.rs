@@ -1,4 +1,6 @@
+mod epoch;
 mod slot;
 
+pub use epoch::Epoch;
 pub use slot::slot_from_string;
 pub use slot::Slot;
@@ -8,7 +8,9 @@ use std::{
 
 use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
 
-use crate::beacon_chain::GENESIS_TIMESTAMP;
+use crate::beacon_chain::{GENESIS_TIMESTAMP, SECONDS_PER_SLOT, SLOTS_PER_EPOCH};
+
+use super::Epoch;
 
 // Beacon chain slots are defined as 12 second periods starting from genesis. With u32 our program
 // would overflow when the slot number passes 2_147_483_647. i32::MAX * 12 seconds = ~817 years.
@@ -20,7 +22,6 @@ pub struct Slot(pub i32);
 
 impl Slot {
     pub const GENESIS: Self = Self(0);
-    pub const SECONDS_PER_SLOT: i32 = 12;
 
     pub fn date_time(&self) -> DateTime<Utc> {
         self.into()
@@ -28,10 +29,10 @@ impl Slot {
 
     pub fn from_date_time(date_time: &DateTime<Utc>) -> Option<Self> {
         let seconds_since_genesis = date_time.timestamp() - GENESIS_TIMESTAMP.timestamp();
-        if seconds_since_genesis % Self::SECONDS_PER_SLOT as i64 != 0 {
+        if seconds_since_genesis % *SECONDS_PER_SLOT as i64 != 0 {
             None
         } else {
-            let slots_since_genesis = seconds_since_genesis / Self::SECONDS_PER_SLOT as i64;
+            let slots_since_genesis = seconds_since_genesis / *SECONDS_PER_SLOT as i64;
             Some(Self(slots_since_genesis as i32))
         }
     }
@@ -39,12 +40,12 @@ impl Slot {
     /// Returns the most recent slot before the given date_time.
     pub fn from_date_time_rounded_down(date_time: &DateTime<Utc>) -> Self {
         let diff_seconds = *date_time - *GENESIS_TIMESTAMP;
-        let slot = diff_seconds.num_seconds() / Slot::SECONDS_PER_SLOT as i64;
+        let slot = diff_seconds.num_seconds() / *SECONDS_PER_SLOT as i64;
         Self(slot as i32)
     }
 
     pub fn is_first_of_epoch(&self) -> bool {
-        self.0 % 32 == 0
+        self.0 % *SLOTS_PER_EPOCH == 0
     }
 
     pub fn is_first_of_day(&self) -> bool {
@@ -94,8 +95,13 @@ impl Slot {
         unimplemented!()
     }
 
-    pub fn epoch(&self) -> i32 {
-        self.0 / 32
+    pub fn epoch(&self) -> Epoch {
+        self.into()
+    }
+
+    /// Every slot from first to last, including both.
+    pub fn range_inclusive(first: Slot, last: Slot) -> impl DoubleEndedIterator<Item = Slot> {
+        (first.0..=last.0).map(Slot)
     }
 }
 
@@ -121,6 +127,31 @@ impl Sub<i32> for Slot {
     }
 }
 
+impl Add<i64> for Slot {
+    type Output = Self;
+
+    fn add(self, rhs: i64) -> Self::Output {
+        Self(self.0 + i32::try_from(rhs).expect("expect slot offset to fit in i32"))
+    }
+}
+
+impl Sub<i64> for Slot {
+    type Output = Self;
+
+    fn sub(self, rhs: i64) -> Self::Output {
+        Self(self.0 - i32::try_from(rhs).expect("expect slot offset to fit in i32"))
+    }
+}
+
+/// Number of slots between two slots.
+impl Sub<Slot> for Slot {
+    type Output = i32;
+
+    fn sub(self, rhs: Slot) -> Self::Output {
+        self.0 - rhs.0
+    }
+}
+
 impl Mul<i32> for Slot {
     type Output = Self;
 
@@ -139,7 +170,7 @@ impl Rem<i32> for Slot {
 
 impl From<Slot> for DateTime<Utc> {
     fn from(slot: Slot) -> Self {
-        let seconds = slot.0 as i64 * Slot::SECONDS_PER_SLOT as i64;
+        let seconds = slot.0 as i64 * *SECONDS_PER_SLOT as i64;
         *GENESIS_TIMESTAMP + Duration::seconds(seconds)
     }
 }
@@ -284,10 +315,28 @@ mod tests {
         let slot_a = Slot(3);
         let slot_b = Slot(7);
 
-        assert_eq!((slot_a + 5).0, 8);
-        assert_eq!((slot_a - 2).0, 1);
+        assert_eq!((slot_a + 5i32).0, 8);
+        assert_eq!((slot_a - 2i32).0, 1);
         assert_eq!((slot_a * 3).0, 9);
         assert_eq!((slot_b % 3).0, 1);
+        assert_eq!(slot_a + 5i64, Slot(8));
+        assert_eq!(slot_a - 2i64, Slot(1));
+        assert_eq!(slot_b - slot_a, 4);
+    }
+
+    #[test]
+    fn range_inclusive_test() {
+        assert_eq!(
+            Slot::range_inclusive(Slot(1), Slot(3)).collect::<Vec<_>>(),
+            vec![Slot(1), Slot(2), Slot(3)]
+        );
+        assert_eq!(
+            Slot::range_inclusive(Slot(1), Slot(3))
+                .rev()
+                .collect::<Vec<_>>(),
+            vec![Slot(3), Slot(2), Slot(1)]
+        );
+        assert_eq!(Slot::range_inclusive(Slot(2), Slot(1)).count(), 0);
     }
 
     #[test]
@@ -301,9 +350,9 @@ mod tests {
 
     #[test]
     fn epoch_calculation() {
-        assert_eq!(Slot(0).epoch(), 0);
-        assert_eq!(Slot(32).epoch(), 1);
-        assert_eq!(Slot(320).epoch(), 10);
+        assert_eq!(Slot(0).epoch(), Epoch(0));
+        assert_eq!(Slot(32).epoch(), Epoch(1));
+        assert_eq!(Slot(320).epoch(), Epoch(10));
     }
 
     #[test]
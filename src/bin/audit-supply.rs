@@ -0,0 +1,4 @@
+#[tokio::main]
+pub async fn main() {
+    eth_analysis::audit_supply().await;
+}
@@ -0,0 +1,4 @@
+#[tokio::main]
+pub async fn main() {
+    eth_analysis::backfill_burn_sums().await;
+}
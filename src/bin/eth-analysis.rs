@@ -0,0 +1,4 @@
+#[tokio::main]
+pub async fn main() -> Result<(), anyhow::Error> {
+    eth_analysis::run_cli().await
+}
@@ -0,0 +1,5 @@
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    eth_analysis::export_dataset().await?;
+    Ok(())
+}
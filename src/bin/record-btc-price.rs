@@ -0,0 +1,5 @@
+#[tokio::main]
+pub async fn main() -> Result<(), anyhow::Error> {
+    eth_analysis::record_btc_price().await?;
+    Ok(())
+}
@@ -0,0 +1,189 @@
+//! # Blob Transactions
+//! How much rollups use blobs, counted from the EIP-4844, type 3, transactions we store. For the
+//! latest block and every time frame we publish the number of blob transactions, the execution
+//! gas they used, and the blob gas and blob fees of their blocks. Only blob transactions use blob
+//! gas, block totals are theirs.
+//!
+//! Reads stored transactions, see `execution_chain::transactions`.
+use std::{cmp::max, collections::HashMap};
+
+use cached::proc_macro::cached;
+use chrono::{DateTime, Utc};
+use enum_iterator::all;
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    caching::{self, CacheKey},
+    execution_chain::{BlockNumber, ExecutionNodeBlock, CANCUN_HARD_FORK_TIMESTAMP},
+    time_frames::TimeFrame,
+    units::WeiNewtype,
+};
+
+const BLOB_TRANSACTION_TYPE: i16 = 3;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BlobTransactionStats {
+    blob_fees: WeiNewtype,
+    blob_gas_used: i64,
+    block_count: i64,
+    /// Execution gas used by the blob transactions.
+    gas_used: i64,
+    transaction_count: i64,
+}
+
+#[derive(Serialize)]
+struct BlobTransactions {
+    block: BlobTransactionStats,
+    block_number: BlockNumber,
+    #[serde(flatten)]
+    time_frames: HashMap<TimeFrame, BlobTransactionStats>,
+}
+
+async fn get_blob_transaction_stats(
+    executor: impl PgExecutor<'_>,
+    start_timestamp: &DateTime<Utc>,
+) -> BlobTransactionStats {
+    sqlx::query_as!(
+        BlobTransactionStats,
+        r#"
+        WITH blocks AS (
+            SELECT number, blob_gas_used, blob_base_fee
+            FROM blocks_next
+            WHERE timestamp >= $1 AND blob_gas_used IS NOT NULL
+        ), blob_transactions AS (
+            SELECT gas_used
+            FROM transactions
+            WHERE type = $2 AND block_number IN (SELECT number FROM blocks)
+        )
+        SELECT
+            (SELECT COUNT(*) FROM blocks) AS "block_count!",
+            (SELECT COALESCE(SUM(blob_gas_used), 0)::INT8 FROM blocks) AS "blob_gas_used!",
+            (
+                SELECT COALESCE(SUM(blob_gas_used::NUMERIC * blob_base_fee::NUMERIC), 0)
+                FROM blocks
+            )::NUMERIC(78, 0) AS "blob_fees!: WeiNewtype",
+            (SELECT COUNT(*) FROM blob_transactions) AS "transaction_count!",
+            (SELECT COALESCE(SUM(gas_used), 0)::INT8 FROM blob_transactions) AS "gas_used!"
+        "#,
+        start_timestamp,
+        BLOB_TRANSACTION_TYPE,
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+}
+
+// Growing time frames are slow to compute and barely change from block to block.
+#[cached(
+    key = "String",
+    convert = r#"{start_timestamp.to_string()}"#,
+    time = 3600
+)]
+async fn get_blob_transaction_stats_cached_1h(
+    db_pool: &PgPool,
+    start_timestamp: &DateTime<Utc>,
+) -> BlobTransactionStats {
+    get_blob_transaction_stats(db_pool, start_timestamp).await
+}
+
+pub async fn on_new_block(db_pool: &PgPool, block: &ExecutionNodeBlock) {
+    debug!("calculating blob transaction stats");
+
+    let mut time_frames = HashMap::new();
+    for time_frame in all::<TimeFrame>() {
+        // There are no blobs before Cancun.
+        let start_timestamp = max(
+            time_frame.start_timestamp(block),
+            *CANCUN_HARD_FORK_TIMESTAMP,
+        );
+        let stats = match time_frame {
+            TimeFrame::Growing(_) => {
+                get_blob_transaction_stats_cached_1h(db_pool, &start_timestamp).await
+            }
+            TimeFrame::Limited(_) => get_blob_transaction_stats(db_pool, &start_timestamp).await,
+        };
+        time_frames.insert(time_frame, stats);
+    }
+
+    let blob_transactions = BlobTransactions {
+        // Blocks are synced in order, none come after this one.
+        block: get_blob_transaction_stats(db_pool, &block.timestamp).await,
+        block_number: block.number,
+        time_frames,
+    };
+
+    caching::update_and_publish(db_pool, &CacheKey::BlobTransactions, blob_transactions).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{
+            block_store, store_transactions, BlockTransactionBuilder, ExecutionNodeBlockBuilder,
+            GAS_PER_BLOB,
+        },
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn blob_transaction_stats_test(test_db: &TestDb) {
+        let block_1 = ExecutionNodeBlockBuilder::new("blob_transaction_stats")
+            .with_timestamp(&CANCUN_HARD_FORK_TIMESTAMP)
+            .with_blob_gas_used(2 * GAS_PER_BLOB)
+            .with_excess_blob_gas(0)
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_blob_gas_used(GAS_PER_BLOB)
+            .with_excess_blob_gas(0)
+            .build();
+
+        block_store::store_block(&test_db.pool, &block_1, 1.0).await;
+        block_store::store_block(&test_db.pool, &block_2, 1.0).await;
+        store_transactions(
+            &test_db.pool,
+            &block_1,
+            &[
+                BlockTransactionBuilder::new("blob_transactions", 0).build(),
+                BlockTransactionBuilder::new("blob_transactions", 1)
+                    .with_transaction_type(3)
+                    .build(),
+            ],
+        )
+        .await;
+        store_transactions(
+            &test_db.pool,
+            &block_2,
+            &[BlockTransactionBuilder::new("blob_transactions", 0)
+                .with_transaction_type(3)
+                .build()],
+        )
+        .await;
+
+        let stats = get_blob_transaction_stats(&test_db.pool, &CANCUN_HARD_FORK_TIMESTAMP).await;
+
+        // With no excess blob gas, the blob base fee is the minimum of 1 wei.
+        assert_eq!(
+            stats,
+            BlobTransactionStats {
+                blob_fees: WeiNewtype(3 * GAS_PER_BLOB as i128),
+                blob_gas_used: 3 * GAS_PER_BLOB as i64,
+                block_count: 2,
+                gas_used: 42_000,
+                transaction_count: 2,
+            }
+        );
+        assert_eq!(
+            get_blob_transaction_stats(&test_db.pool, &block_2.timestamp)
+                .await
+                .transaction_count,
+            1
+        );
+    }
+}
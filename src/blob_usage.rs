@@ -0,0 +1,244 @@
+//! # Blob Usage
+//! Charts how saturated the blob market is. For every time frame we publish a series of blob
+//! counts, and blob gas used relative to the blob gas target. Short time frames get a point per
+//! block, longer ones are binned.
+use std::{cmp::max, collections::HashMap};
+
+use cached::proc_macro::cached;
+use chrono::{DateTime, Utc};
+use enum_iterator::all;
+use futures::future::join_all;
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    caching::{self, CacheKey},
+    execution_chain::{
+        self, BlockNumber, ExecutionNodeBlock, CANCUN_HARD_FORK_TIMESTAMP, GAS_PER_BLOB,
+    },
+    performance::TimedExt,
+    time_frames::{LimitedTimeFrame, TimeFrame},
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BlobUsageAtTime {
+    blob_count: i64,
+    block_count: i64,
+    // Only set when the point represents a single block.
+    block_number: Option<BlockNumber>,
+    timestamp: DateTime<Utc>,
+    /// Blob gas used divided by the blob gas target, 1.0 means the blob market is exactly at
+    /// target, above 1.0 the blob base fee is rising.
+    target_utilization: f64,
+}
+
+impl BlobUsageAtTime {
+    /// `None` before Cancun, e.g. for the bin of the day Cancun activated, which starts before it.
+    fn new(
+        block_number: Option<BlockNumber>,
+        timestamp: DateTime<Utc>,
+        block_count: i64,
+        blob_gas_used: i64,
+    ) -> Option<Self> {
+        let target_blob_gas_per_block =
+            execution_chain::blob_params_at(&timestamp)?.target_blob_gas_per_block;
+        let target_utilization =
+            blob_gas_used as f64 / (target_blob_gas_per_block as f64 * block_count as f64);
+        Some(Self {
+            blob_count: blob_gas_used / GAS_PER_BLOB as i64,
+            block_count,
+            block_number,
+            timestamp,
+            target_utilization,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct BlobUsage {
+    block_number: BlockNumber,
+    #[serde(flatten)]
+    time_frames: HashMap<TimeFrame, Vec<BlobUsageAtTime>>,
+}
+
+async fn blob_usage_per_block(
+    executor: impl PgExecutor<'_>,
+    start_timestamp: &DateTime<Utc>,
+) -> Vec<BlobUsageAtTime> {
+    sqlx::query!(
+        r#"
+        SELECT
+            number,
+            timestamp,
+            blob_gas_used::INT8 AS "blob_gas_used!"
+        FROM
+            blocks_next
+        WHERE
+            timestamp >= $1
+        AND
+            blob_gas_used IS NOT NULL
+        ORDER BY number ASC
+        "#,
+        start_timestamp
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .filter_map(|row| {
+        BlobUsageAtTime::new(
+            Some(BlockNumber(row.number)),
+            row.timestamp,
+            1,
+            row.blob_gas_used,
+        )
+    })
+    .collect()
+}
+
+async fn blob_usage_binned(
+    executor: impl PgExecutor<'_>,
+    start_timestamp: &DateTime<Utc>,
+    bin_interval: &str,
+) -> Vec<BlobUsageAtTime> {
+    sqlx::query!(
+        r#"
+        SELECT
+            DATE_BIN($1::TEXT::INTERVAL, timestamp, '2022-01-01') AS "bin_timestamp!",
+            COUNT(*) AS "block_count!",
+            SUM(blob_gas_used)::INT8 AS "blob_gas_used!"
+        FROM
+            blocks_next
+        WHERE
+            timestamp >= $2
+        AND
+            blob_gas_used IS NOT NULL
+        GROUP BY 1
+        ORDER BY 1 ASC
+        "#,
+        bin_interval,
+        start_timestamp
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .filter_map(|row| {
+        BlobUsageAtTime::new(None, row.bin_timestamp, row.block_count, row.blob_gas_used)
+    })
+    .collect()
+}
+
+// Growing time frames are slow to compute and barely change from block to block.
+#[cached(
+    key = "String",
+    convert = r#"{start_timestamp.to_string()}"#,
+    time = 3600
+)]
+async fn blob_usage_by_day_cached_1h(
+    db_pool: &PgPool,
+    start_timestamp: &DateTime<Utc>,
+) -> Vec<BlobUsageAtTime> {
+    blob_usage_binned(db_pool, start_timestamp, "1 day").await
+}
+
+async fn blob_usage_from_time_frame(
+    db_pool: &PgPool,
+    block: &ExecutionNodeBlock,
+    time_frame: &TimeFrame,
+) -> Vec<BlobUsageAtTime> {
+    // There are no blobs before Cancun.
+    let start_timestamp = max(
+        time_frame.start_timestamp(block),
+        *CANCUN_HARD_FORK_TIMESTAMP,
+    );
+
+    match time_frame {
+        TimeFrame::Limited(LimitedTimeFrame::Minute5)
+        | TimeFrame::Limited(LimitedTimeFrame::Hour1) => {
+            blob_usage_per_block(db_pool, &start_timestamp).await
+        }
+        TimeFrame::Limited(LimitedTimeFrame::Day1) => {
+            blob_usage_binned(db_pool, &start_timestamp, "1 minute").await
+        }
+        TimeFrame::Limited(LimitedTimeFrame::Day7) => {
+            blob_usage_binned(db_pool, &start_timestamp, "5 minutes").await
+        }
+        TimeFrame::Limited(LimitedTimeFrame::Day30) => {
+            blob_usage_binned(db_pool, &start_timestamp, "1 hour").await
+        }
+        TimeFrame::Growing(_) => blob_usage_by_day_cached_1h(db_pool, &start_timestamp).await,
+    }
+}
+
+pub async fn on_new_block(db_pool: &PgPool, block: &ExecutionNodeBlock) {
+    debug!("calculating blob usage");
+
+    let futures = all::<TimeFrame>().map(|time_frame| async move {
+        let usage = blob_usage_from_time_frame(db_pool, block, &time_frame)
+            .timed(&format!("blob_usage_from_time_frame_{time_frame}"))
+            .await;
+        (time_frame, usage)
+    });
+    let time_frames = join_all(futures).await.into_iter().collect();
+
+    let blob_usage = BlobUsage {
+        block_number: block.number,
+        time_frames,
+    };
+
+    caching::update_and_publish(db_pool, &CacheKey::BlobUsage, blob_usage).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{block_store, ExecutionNodeBlockBuilder},
+    };
+
+    use super::*;
+
+    #[test]
+    fn blob_usage_at_time_test() {
+        let usage = BlobUsageAtTime::new(
+            Some(BlockNumber(1)),
+            *CANCUN_HARD_FORK_TIMESTAMP,
+            1,
+            6 * GAS_PER_BLOB as i64,
+        )
+        .unwrap();
+        assert_eq!(usage.blob_count, 6);
+        assert_eq!(usage.target_utilization, 2.0);
+
+        let pre_cancun = *CANCUN_HARD_FORK_TIMESTAMP - chrono::Duration::hours(1);
+        assert_eq!(BlobUsageAtTime::new(None, pre_cancun, 1, 0), None);
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn blob_usage_per_block_test(test_db: &TestDb) {
+        let block_1 = ExecutionNodeBlockBuilder::new("blob_usage_per_block")
+            .with_timestamp(&CANCUN_HARD_FORK_TIMESTAMP)
+            .with_blob_gas_used(3 * GAS_PER_BLOB)
+            .with_excess_blob_gas(0)
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_blob_gas_used(0)
+            .with_excess_blob_gas(0)
+            .build();
+
+        block_store::store_block(&test_db.pool, &block_1, 1.0).await;
+        block_store::store_block(&test_db.pool, &block_2, 1.0).await;
+
+        let usage = blob_usage_per_block(&test_db.pool, &CANCUN_HARD_FORK_TIMESTAMP).await;
+
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].blob_count, 3);
+        assert_eq!(usage[0].target_utilization, 1.0);
+        assert_eq!(usage[1].blob_count, 0);
+    }
+}
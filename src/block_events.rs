@@ -0,0 +1,158 @@
+//! # Block Events
+//! Publishes an event with the burn, tips, supply delta and price of every new head to NATS, so
+//! other services can consume our analytics without polling our DB. Enabled by building with the
+//! `nats` feature and configuring a `nats_url`. Events go to the `nats_subject` subject,
+//! `eth-analysis.blocks` by default.
+//!
+//! Rolled back blocks aren't retracted, consumers see the block replacing them as a new event
+//! with the same number and a different hash.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tokio::sync::OnceCell;
+
+use crate::{
+    execution_chain::{BlockNumber, ExecutionNode, ExecutionNodeBlock, TransactionReceipt},
+    units::WeiNewtype,
+};
+
+const DEFAULT_NATS_SUBJECT: &str = "eth-analysis.blocks";
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BlockEvent {
+    pub burn: WeiNewtype,
+    pub eth_price: f64,
+    pub hash: String,
+    pub number: BlockNumber,
+    pub parent_hash: String,
+    /// `None` when the supply delta sync hasn't reached the block yet.
+    pub supply_delta: Option<WeiNewtype>,
+    pub timestamp: DateTime<Utc>,
+    pub tips: WeiNewtype,
+}
+
+/// What transactions paid on top of the base fee, which goes to the block proposer.
+fn tips_from_receipts(base_fee_per_gas: u64, receipts: &[TransactionReceipt]) -> WeiNewtype {
+    WeiNewtype(
+        receipts
+            .iter()
+            .map(|receipt| {
+                let tip_per_gas = receipt.effective_gas_price - base_fee_per_gas as u128;
+                (tip_per_gas * receipt.gas_used as u128) as i128
+            })
+            .sum(),
+    )
+}
+
+async fn get_supply_delta(executor: impl PgExecutor<'_>, block_hash: &str) -> Option<WeiNewtype> {
+    sqlx::query!(
+        r#"
+        SELECT supply_delta AS "supply_delta: WeiNewtype"
+        FROM execution_supply_deltas
+        WHERE block_hash = $1
+        "#,
+        block_hash
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| row.supply_delta)
+}
+
+async fn get_eth_price(executor: impl PgExecutor<'_>, block_hash: &str) -> f64 {
+    sqlx::query!(
+        "SELECT eth_price FROM blocks_next WHERE hash = $1",
+        block_hash
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .eth_price
+}
+
+pub struct BlockEventsPublisher {
+    client: OnceCell<async_nats::Client>,
+    execution_node: OnceCell<ExecutionNode>,
+    subject: String,
+    url: String,
+}
+
+impl BlockEventsPublisher {
+    /// Returns `None` when no `nats_url` is configured.
+    pub fn from_config() -> Option<Self> {
+        let config = &crate::config::CONFIG;
+        config.nats_url().map(|url| Self {
+            client: OnceCell::new(),
+            execution_node: OnceCell::new(),
+            subject: config
+                .nats_subject()
+                .unwrap_or(DEFAULT_NATS_SUBJECT)
+                .to_string(),
+            url: url.to_string(),
+        })
+    }
+
+    async fn build_event(
+        &self,
+        db_pool: &PgPool,
+        block: &ExecutionNodeBlock,
+    ) -> Result<BlockEvent> {
+        // Receipts are the only place the effective gas price, and so the tip, can be found.
+        let execution_node = self
+            .execution_node
+            .get_or_init(ExecutionNode::connect)
+            .await;
+        let receipts = execution_node
+            .get_transaction_receipts_for_block(block)
+            .await?;
+
+        Ok(BlockEvent {
+            burn: block.burn(),
+            eth_price: get_eth_price(db_pool, &block.hash).await,
+            hash: block.hash.clone(),
+            number: block.number,
+            parent_hash: block.parent_hash.clone(),
+            supply_delta: get_supply_delta(db_pool, &block.hash).await,
+            timestamp: block.timestamp,
+            tips: tips_from_receipts(block.base_fee_per_gas, &receipts),
+        })
+    }
+
+    pub async fn on_new_block(&self, db_pool: &PgPool, block: &ExecutionNodeBlock) -> Result<()> {
+        let event = self.build_event(db_pool, block).await?;
+
+        let client = self
+            .client
+            .get_or_try_init(|| async_nats::connect(self.url.as_str()))
+            .await?;
+        client
+            .publish(self.subject.clone(), serde_json::to_vec(&event)?.into())
+            .await?;
+        client.flush().await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt(effective_gas_price: u128, gas_used: i32) -> TransactionReceipt {
+        TransactionReceipt {
+            contract_address: None,
+            block_number: BlockNumber::GENESIS,
+            effective_gas_price,
+            gas_used,
+            to: None,
+            transaction_hash: "0xtest".to_string(),
+        }
+    }
+
+    #[test]
+    fn tips_from_receipts_test() {
+        let receipts = vec![receipt(12, 21_000), receipt(10, 50_000)];
+        assert_eq!(tips_from_receipts(10, &receipts), WeiNewtype(2 * 21_000));
+    }
+}
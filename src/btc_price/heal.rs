@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{Duration, DurationRound, TimeZone, Utc};
+use futures::stream::{self, StreamExt};
+use sqlx::PgPool;
+use tracing::{debug, info, warn};
+
+use crate::{
+    db, execution_chain,
+    heal::{self, HealOptions, Healer},
+    log,
+};
+
+use super::{get_closest_price_by_minute, store};
+
+const CONCURRENT_REQUESTS: usize = 8;
+
+const HEAL_BTC_PRICES_KEY: &str = "heal-btc-prices";
+
+struct BtcPricesHealer {
+    db_pool: PgPool,
+    max_distance: Duration,
+}
+
+#[async_trait]
+impl Healer for BtcPricesHealer {
+    /// The unix timestamp of a minute without a price.
+    type Item = i64;
+
+    fn name(&self) -> &'static str {
+        HEAL_BTC_PRICES_KEY
+    }
+
+    async fn items_to_heal(&self, checkpoint: Option<i64>) -> Vec<i64> {
+        info!("getting all btc prices");
+        let known_minutes = store::get_stored_minutes(&self.db_pool)
+            .await
+            .iter()
+            .map(|timestamp| timestamp.timestamp())
+            .collect::<HashSet<_>>();
+
+        if known_minutes.is_empty() {
+            warn!("no btc prices found, healing every minute since London")
+        }
+
+        let london_minute = execution_chain::LONDON_HARD_FORK_TIMESTAMP
+            .duration_round(Duration::minutes(1))
+            .unwrap();
+        let minutes_since_london = (Utc::now().duration_round(Duration::minutes(1)).unwrap()
+            - london_minute)
+            .num_minutes();
+
+        let missing_minutes_timestamps = (0..minutes_since_london)
+            .map(|minutes| london_minute.timestamp() + minutes * 60)
+            .filter(|timestamp| checkpoint.is_none_or(|checkpoint| *timestamp > checkpoint))
+            .filter(|timestamp| !known_minutes.contains(timestamp))
+            .collect::<Vec<i64>>();
+
+        info!("found {} missing minutes", missing_minutes_timestamps.len());
+
+        missing_minutes_timestamps
+    }
+
+    async fn heal_chunk(&self, timestamps: &[i64], dry_run: bool) -> Result<()> {
+        let mut missing_minutes_stream = stream::iter(timestamps.iter().copied())
+            .map(|timestamp| async move {
+                let timestamp_date_time = Utc.timestamp_opt(timestamp, 0).unwrap();
+                let usd = get_closest_price_by_minute(timestamp_date_time, self.max_distance).await;
+                (usd, timestamp_date_time)
+            })
+            .buffer_unordered(CONCURRENT_REQUESTS);
+
+        while let Some((usd, timestamp)) = missing_minutes_stream.next().await {
+            match usd {
+                None => warn!(%timestamp, "no btc price available for minute"),
+                Some(_) if dry_run => {
+                    debug!(%timestamp, "dry run, skipping storing btc price");
+                }
+                Some(usd) => store::store_price(&self.db_pool, &timestamp, usd).await,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn heal_btc_prices() {
+    let max_distance_in_minutes: i64 = std::env::args()
+        .skip(1)
+        .find_map(|str| str.parse::<i64>().ok())
+        .unwrap_or(10);
+    heal_btc_prices_with_max_distance(max_distance_in_minutes, heal::dry_run_from_args()).await;
+}
+
+pub async fn heal_btc_prices_with_max_distance(max_distance_in_minutes: i64, dry_run: bool) {
+    log::init_with_env();
+
+    let db_pool = db::get_db_pool("heal-btc-prices").await;
+
+    heal_btc_prices_with_pool(
+        &db_pool,
+        max_distance_in_minutes,
+        &HealOptions {
+            chunk_size: 1000,
+            dry_run,
+        },
+    )
+    .await
+    .unwrap();
+}
+
+pub async fn heal_btc_prices_with_pool(
+    db_pool: &PgPool,
+    max_distance_in_minutes: i64,
+    options: &HealOptions,
+) -> Result<()> {
+    info!("healing missing btc prices");
+
+    let healer = BtcPricesHealer {
+        db_pool: db_pool.clone(),
+        max_distance: Duration::minutes(max_distance_in_minutes),
+    };
+
+    heal::heal(db_pool, &healer, options).await?;
+
+    info!("done healing btc prices");
+
+    Ok(())
+}
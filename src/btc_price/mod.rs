@@ -0,0 +1,152 @@
+//! # BTC Price
+//! Records the BTCUSD price every minute, like `usd_price` does for ETH, so we can compare ETH to
+//! BTC. Prices come from Bybit, falling back to Coinbase when Bybit fails us, through the same
+//! clients we use for ETH.
+mod heal;
+mod store;
+
+pub use heal::heal_btc_prices;
+pub use heal::heal_btc_prices_with_max_distance;
+pub use heal::heal_btc_prices_with_pool;
+
+pub use store::get_price_by_timestamp;
+
+use anyhow::{Context, Result};
+use backoff::{self, Error, ExponentialBackoff};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use crate::{
+    caching::{self, CacheKey},
+    db, log,
+    usd_price::{bybit, coinbase},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BtcPrice {
+    pub timestamp: DateTime<Utc>,
+    pub usd: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BtcPriceStats {
+    timestamp: DateTime<Utc>,
+    usd: f64,
+    h24_change: f64,
+}
+
+/// We use the open of each 1min candle, like we do for ETH.
+async fn get_candles(start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<BtcPrice>> {
+    let candles = backoff::future::retry(ExponentialBackoff::default(), || async {
+        match bybit::get_candles("BTCUSD", start, end).await {
+            Ok(candles) if !candles.is_empty() => return Ok(candles),
+            Ok(_) => debug!(%start, %end, "bybit returned no btc candles, trying coinbase"),
+            Err(err) => info!(%err, "error getting btc candles from bybit, trying coinbase"),
+        }
+
+        coinbase::get_candles("BTC-USD", start, end)
+            .await
+            .map_err(|err| {
+                info!(%err, "error getting btc candles from coinbase, retrying");
+                Error::transient(err)
+            })
+    })
+    .await?;
+
+    Ok(candles
+        .into_iter()
+        .map(|candle| BtcPrice {
+            timestamp: candle.timestamp,
+            usd: candle.open,
+        })
+        .collect())
+}
+
+// Return current 1min candle open price
+async fn get_btc_price() -> Result<BtcPrice> {
+    let end = Utc::now();
+    let start = end - Duration::minutes(1);
+    get_candles(start, end).await.and_then(|candles| {
+        candles
+            .into_iter()
+            .last()
+            .context("tried to retrieve last element in empty array")
+    })
+}
+
+pub async fn get_closest_price_by_minute(
+    target_minute_rounded: DateTime<Utc>,
+    max_distance: Duration,
+) -> Option<f64> {
+    let start = target_minute_rounded - max_distance;
+    let end = target_minute_rounded + max_distance;
+
+    let candles = get_candles(start, end).await.unwrap_or_default();
+
+    // On a tie the older price wins, min_by_key keeps the first.
+    candles
+        .iter()
+        .min_by_key(|price| {
+            (target_minute_rounded - price.timestamp)
+                .num_seconds()
+                .abs()
+        })
+        .map(|price| price.usd)
+}
+
+fn calc_h24_change(current_price: &BtcPrice, price_h24_ago: &BtcPrice) -> f64 {
+    (current_price.usd - price_h24_ago.usd) / price_h24_ago.usd
+}
+
+async fn update_btc_price_with_most_recent(
+    db_pool: &PgPool,
+    last_price: &mut Option<BtcPrice>,
+) -> Result<()> {
+    let most_recent_price = get_btc_price().await?;
+    if last_price.as_ref() == Some(&most_recent_price) {
+        debug!(
+            price = most_recent_price.usd,
+            minute = most_recent_price.timestamp.to_string(),
+            "most recent btc price is equal to last stored price, skipping",
+        );
+        return Ok(());
+    }
+
+    store::store_price(db_pool, &most_recent_price.timestamp, most_recent_price.usd).await;
+
+    match store::get_price_h24_ago(db_pool, &Utc::now(), &Duration::minutes(10)).await {
+        Some(price_h24_ago) => {
+            let btc_price_stats = BtcPriceStats {
+                timestamp: most_recent_price.timestamp,
+                usd: most_recent_price.usd,
+                h24_change: calc_h24_change(&most_recent_price, &price_h24_ago),
+            };
+            caching::update_and_publish(db_pool, &CacheKey::BtcPrice, btc_price_stats).await;
+        }
+        // Right after we start recording, or when a gap hasn't been healed yet.
+        None => warn!("no btc price from 24h ago, skipping btc price stats"),
+    }
+
+    *last_price = Some(most_recent_price);
+
+    Ok(())
+}
+
+pub async fn record_btc_price() -> Result<()> {
+    log::init_with_env();
+
+    info!("recording btc prices");
+
+    let db_pool = db::get_db_pool("record-btc-price").await;
+
+    let mut last_price = store::get_most_recent_price(&db_pool).await;
+
+    loop {
+        update_btc_price_with_most_recent(&db_pool, &mut last_price).await?;
+        sleep(std::time::Duration::from_secs(10)).await;
+    }
+}
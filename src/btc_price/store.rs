@@ -0,0 +1,151 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use sqlx::PgExecutor;
+
+use super::BtcPrice;
+
+pub async fn store_price(executor: impl PgExecutor<'_>, timestamp: &DateTime<Utc>, usd: f64) {
+    sqlx::query!(
+        "
+        INSERT INTO
+            btc_prices (timestamp, btcusd)
+        VALUES ($1, $2)
+        ON CONFLICT (timestamp) DO UPDATE SET
+            btcusd = excluded.btcusd
+        ",
+        timestamp,
+        usd
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub async fn get_most_recent_price(executor: impl PgExecutor<'_>) -> Option<BtcPrice> {
+    sqlx::query_as!(
+        BtcPrice,
+        "
+        SELECT timestamp, btcusd AS usd
+        FROM btc_prices
+        ORDER BY timestamp DESC
+        LIMIT 1
+        "
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+}
+
+/// The price closest to 24 hours before `now`, if one is within `age_limit` of it.
+pub async fn get_price_h24_ago(
+    executor: impl PgExecutor<'_>,
+    now: &DateTime<Utc>,
+    age_limit: &Duration,
+) -> Option<BtcPrice> {
+    let target = *now - Duration::hours(24);
+    sqlx::query_as!(
+        BtcPrice,
+        "
+        SELECT timestamp, btcusd AS usd
+        FROM btc_prices
+        WHERE timestamp >= $1 AND timestamp <= $2
+        ORDER BY ABS(EXTRACT(epoch FROM (timestamp - $3)))
+        LIMIT 1
+        ",
+        target - *age_limit,
+        target + *age_limit,
+        target
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+}
+
+/// The price of the minute the timestamp is in, or the closest one within 20 minutes.
+pub async fn get_price_by_timestamp(
+    executor: impl PgExecutor<'_>,
+    timestamp: &DateTime<Utc>,
+) -> Option<f64> {
+    let minute = timestamp.duration_trunc(Duration::minutes(1)).unwrap();
+    sqlx::query!(
+        "
+        SELECT btcusd
+        FROM btc_prices
+        WHERE timestamp >= $1 AND timestamp <= $2
+        ORDER BY ABS(EXTRACT(epoch FROM (timestamp - $3)))
+        LIMIT 1
+        ",
+        minute - Duration::minutes(20),
+        minute + Duration::minutes(20),
+        minute
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| row.btcusd)
+}
+
+pub async fn get_stored_minutes(executor: impl PgExecutor<'_>) -> Vec<DateTime<Utc>> {
+    sqlx::query!("SELECT timestamp FROM btc_prices")
+        .fetch_all(executor)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.timestamp)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::SubsecRound;
+    use test_context::test_context;
+
+    use crate::db::tests::TestDb;
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn get_most_recent_price_test(test_db: &TestDb) {
+        let now = Utc::now().trunc_subsecs(0);
+        store_price(&test_db.pool, &(now - Duration::minutes(1)), 1.0).await;
+        store_price(&test_db.pool, &now, 2.0).await;
+
+        let price = get_most_recent_price(&test_db.pool).await;
+
+        assert_eq!(
+            price,
+            Some(BtcPrice {
+                timestamp: now,
+                usd: 2.0
+            })
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn get_price_h24_ago_test(test_db: &TestDb) {
+        let now = Utc::now().trunc_subsecs(0);
+        store_price(&test_db.pool, &(now - Duration::hours(25)), 1.0).await;
+
+        let price = get_price_h24_ago(&test_db.pool, &now, &Duration::minutes(10)).await;
+        assert_eq!(price, None);
+
+        store_price(&test_db.pool, &(now - Duration::hours(24)), 2.0).await;
+
+        let price = get_price_h24_ago(&test_db.pool, &now, &Duration::minutes(10)).await;
+        assert_eq!(price.map(|price| price.usd), Some(2.0));
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn get_price_by_timestamp_test(test_db: &TestDb) {
+        let minute = "2024-05-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        store_price(&test_db.pool, &(minute - Duration::minutes(3)), 1.0).await;
+
+        let usd = get_price_by_timestamp(&test_db.pool, &(minute + Duration::seconds(30))).await;
+        assert_eq!(usd, Some(1.0));
+
+        let usd = get_price_by_timestamp(&test_db.pool, &(minute + Duration::hours(1))).await;
+        assert_eq!(usd, None);
+    }
+}
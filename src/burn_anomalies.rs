@@ -0,0 +1,411 @@
+//! # Burn Anomalies
+//! Flags blocks, and 5 minute windows, whose burn is far from the mean of the last day. Anything
+//! more than `Z_SCORE_THRESHOLD` standard deviations out is stored, and the most recent anomalies
+//! are published for investigation.
+//!
+//! A window is checked once the first block past its end comes in.
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::Serialize;
+use sqlx::{postgres::PgRow, PgExecutor, PgPool, Row};
+use tracing::{debug, info, warn};
+
+use crate::{
+    caching::{self, CacheKey},
+    execution_chain::{BlockNumber, ExecutionNodeBlock, BURN_WEI_SQL},
+    units::WeiNewtype,
+};
+
+/// About a day of blocks.
+const ROLLING_BLOCK_COUNT: i64 = 7_200;
+const MIN_SAMPLE_COUNT: i64 = 100;
+const PUBLISHED_ANOMALY_COUNT: i64 = 100;
+const Z_SCORE_THRESHOLD: f64 = 4.0;
+
+fn window_duration() -> Duration {
+    Duration::minutes(5)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BurnAnomalyKind {
+    #[serde(rename = "block")]
+    Block,
+    #[serde(rename = "window_5m")]
+    Window5m,
+}
+
+impl BurnAnomalyKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::Window5m => "window_5m",
+        }
+    }
+}
+
+impl FromStr for BurnAnomalyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(Self::Block),
+            "window_5m" => Ok(Self::Window5m),
+            unknown => Err(format!("unknown burn anomaly kind {unknown}")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BurnAnomaly {
+    pub block_hash: String,
+    pub block_number: BlockNumber,
+    pub burn: WeiNewtype,
+    pub kind: BurnAnomalyKind,
+    pub mean_burn: f64,
+    pub stddev_burn: f64,
+    /// The block timestamp, or for windows, the start of the window.
+    pub timestamp: DateTime<Utc>,
+    pub z_score: f64,
+}
+
+#[derive(Debug, PartialEq)]
+struct BurnStats {
+    count: i64,
+    mean: f64,
+    stddev: f64,
+}
+
+impl BurnStats {
+    /// `None` when we have too few samples, or they don't vary, to call anything anomalous.
+    fn anomalous_z_score(&self, burn: &WeiNewtype) -> Option<f64> {
+        if self.count < MIN_SAMPLE_COUNT || self.stddev == 0.0 {
+            return None;
+        }
+        let z_score = (burn.0 as f64 - self.mean) / self.stddev;
+        (z_score.abs() >= Z_SCORE_THRESHOLD).then_some(z_score)
+    }
+}
+
+/// Burn stats of the blocks before the given one.
+async fn get_block_burn_stats(
+    executor: impl PgExecutor<'_>,
+    block_number: BlockNumber,
+) -> BurnStats {
+    sqlx::query(&format!(
+        "
+        SELECT
+            COUNT(*) AS count,
+            COALESCE(AVG(burn)::FLOAT8, 0) AS mean,
+            COALESCE(STDDEV_POP(burn)::FLOAT8, 0) AS stddev
+        FROM (
+            SELECT {BURN_WEI_SQL} AS burn
+            FROM blocks_next
+            WHERE number >= $1 AND number < $2
+        ) blocks
+        "
+    ))
+    .bind(block_number - ROLLING_BLOCK_COUNT)
+    .bind(block_number)
+    .map(|row: PgRow| BurnStats {
+        count: row.get("count"),
+        mean: row.get("mean"),
+        stddev: row.get("stddev"),
+    })
+    .fetch_one(executor)
+    .await
+    .unwrap()
+}
+
+/// Burn stats of the 5 minute windows in the day before the given window, and the burn of the
+/// window itself.
+async fn get_window_burn_stats(
+    executor: impl PgExecutor<'_>,
+    window_start: &DateTime<Utc>,
+) -> (BurnStats, WeiNewtype) {
+    sqlx::query(&format!(
+        "
+        WITH windows AS (
+            SELECT
+                date_bin('5 minutes', timestamp, '2000-01-01') AS window_start,
+                SUM({BURN_WEI_SQL}) AS burn
+            FROM blocks_next
+            WHERE timestamp >= $1::TIMESTAMPTZ - INTERVAL '1 day'
+            AND timestamp < $1::TIMESTAMPTZ + INTERVAL '5 minutes'
+            GROUP BY 1
+        )
+        SELECT
+            COUNT(*) FILTER (WHERE window_start < $1) AS count,
+            COALESCE(AVG(burn) FILTER (WHERE window_start < $1), 0)::FLOAT8 AS mean,
+            COALESCE(STDDEV_POP(burn) FILTER (WHERE window_start < $1), 0)::FLOAT8 AS stddev,
+            COALESCE(SUM(burn) FILTER (WHERE window_start = $1), 0) AS window_burn
+        FROM windows
+        "
+    ))
+    .bind(window_start)
+    .map(|row: PgRow| {
+        let stats = BurnStats {
+            count: row.get("count"),
+            mean: row.get("mean"),
+            stddev: row.get("stddev"),
+        };
+        (stats, row.get("window_burn"))
+    })
+    .fetch_one(executor)
+    .await
+    .unwrap()
+}
+
+async fn get_block_timestamp(
+    executor: impl PgExecutor<'_>,
+    block_hash: &str,
+) -> Option<DateTime<Utc>> {
+    sqlx::query!(
+        "SELECT timestamp FROM blocks_next WHERE hash = $1",
+        block_hash
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| row.timestamp)
+}
+
+async fn store_burn_anomaly(executor: impl PgExecutor<'_>, anomaly: &BurnAnomaly) {
+    sqlx::query!(
+        "
+        INSERT INTO burn_anomalies (
+            kind,
+            block_number,
+            block_hash,
+            timestamp,
+            burn_wei,
+            mean_burn_wei,
+            stddev_burn_wei,
+            z_score
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (kind, block_number) DO NOTHING
+        ",
+        anomaly.kind.as_str(),
+        anomaly.block_number.0,
+        anomaly.block_hash,
+        anomaly.timestamp,
+        anomaly.burn as WeiNewtype,
+        anomaly.mean_burn,
+        anomaly.stddev_burn,
+        anomaly.z_score
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub async fn get_burn_anomalies(executor: impl PgExecutor<'_>, limit: i64) -> Vec<BurnAnomaly> {
+    sqlx::query!(
+        r#"
+        SELECT
+            kind,
+            block_number,
+            block_hash,
+            timestamp,
+            burn_wei AS "burn_wei: WeiNewtype",
+            mean_burn_wei,
+            stddev_burn_wei,
+            z_score
+        FROM burn_anomalies
+        ORDER BY block_number DESC, kind
+        LIMIT $1
+        "#,
+        limit
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .filter_map(|row| {
+        let block_number = BlockNumber(row.block_number);
+        // A row we can't make sense of shouldn't keep the others from being published.
+        let kind = row
+            .kind
+            .parse::<BurnAnomalyKind>()
+            .map_err(|err| warn!(%block_number, %err, "skipping stored burn anomaly"))
+            .ok()?;
+        Some(BurnAnomaly {
+            block_hash: row.block_hash,
+            block_number,
+            burn: row.burn_wei,
+            kind,
+            mean_burn: row.mean_burn_wei,
+            stddev_burn: row.stddev_burn_wei,
+            timestamp: row.timestamp,
+            z_score: row.z_score,
+        })
+    })
+    .collect()
+}
+
+pub async fn delete_burn_anomalies(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: &BlockNumber,
+) {
+    sqlx::query!(
+        "DELETE FROM burn_anomalies WHERE block_number >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+async fn check_block(db_pool: &PgPool, block: &ExecutionNodeBlock) -> Option<BurnAnomaly> {
+    let stats = get_block_burn_stats(db_pool, block.number).await;
+    let burn = block.burn();
+    stats.anomalous_z_score(&burn).map(|z_score| BurnAnomaly {
+        block_hash: block.hash.clone(),
+        block_number: block.number,
+        burn,
+        kind: BurnAnomalyKind::Block,
+        mean_burn: stats.mean,
+        stddev_burn: stats.stddev,
+        timestamp: block.timestamp,
+        z_score,
+    })
+}
+
+/// Checks the window the parent block closed, when this block is the first past it.
+async fn check_closed_window(db_pool: &PgPool, block: &ExecutionNodeBlock) -> Option<BurnAnomaly> {
+    let window_end = block.timestamp.duration_trunc(window_duration()).unwrap();
+    let parent_timestamp = get_block_timestamp(db_pool, &block.parent_hash).await?;
+    if parent_timestamp >= window_end {
+        return None;
+    }
+
+    let window_start = window_end - window_duration();
+    let (stats, burn) = get_window_burn_stats(db_pool, &window_start).await;
+    stats.anomalous_z_score(&burn).map(|z_score| BurnAnomaly {
+        block_hash: block.parent_hash.clone(),
+        block_number: block.number - 1,
+        burn,
+        kind: BurnAnomalyKind::Window5m,
+        mean_burn: stats.mean,
+        stddev_burn: stats.stddev,
+        timestamp: window_start,
+        z_score,
+    })
+}
+
+pub async fn on_new_block(db_pool: &PgPool, block: &ExecutionNodeBlock) {
+    let block_anomaly = check_block(db_pool, block).await;
+    let window_anomaly = check_closed_window(db_pool, block).await;
+
+    let anomalies = [block_anomaly, window_anomaly]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    if anomalies.is_empty() {
+        debug!(%block.number, "no burn anomalies");
+        return;
+    }
+
+    for anomaly in anomalies.iter() {
+        info!(
+            kind = anomaly.kind.as_str(),
+            %anomaly.block_number,
+            burn = %anomaly.burn,
+            anomaly.z_score,
+            "found burn anomaly"
+        );
+        store_burn_anomaly(db_pool, anomaly).await;
+    }
+
+    let burn_anomalies = get_burn_anomalies(db_pool, PUBLISHED_ANOMALY_COUNT).await;
+    caching::update_and_publish(db_pool, &CacheKey::BurnAnomalies, burn_anomalies).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{block_store, ExecutionNodeBlockBuilder},
+    };
+
+    use super::*;
+
+    #[test]
+    fn parse_burn_anomaly_kind_test() {
+        for kind in [BurnAnomalyKind::Block, BurnAnomalyKind::Window5m] {
+            assert_eq!(kind.as_str().parse::<BurnAnomalyKind>(), Ok(kind));
+        }
+        assert!("window_1h".parse::<BurnAnomalyKind>().is_err());
+    }
+
+    #[test]
+    fn anomalous_z_score_test() {
+        let stats = BurnStats {
+            count: 1_000,
+            mean: 100.0,
+            stddev: 10.0,
+        };
+        assert_eq!(stats.anomalous_z_score(&WeiNewtype(150)), Some(5.0));
+        assert_eq!(stats.anomalous_z_score(&WeiNewtype(50)), Some(-5.0));
+        assert_eq!(stats.anomalous_z_score(&WeiNewtype(120)), None);
+
+        let few_samples = BurnStats { count: 10, ..stats };
+        assert_eq!(few_samples.anomalous_z_score(&WeiNewtype(150)), None);
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn get_block_burn_stats_test(test_db: &TestDb) {
+        let block_1 = ExecutionNodeBlockBuilder::new("burn_stats")
+            .with_number(BlockNumber(1))
+            .with_gas_used(1)
+            .with_base_fee_per_gas(10)
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_gas_used(1)
+            .with_base_fee_per_gas(30)
+            .build();
+        let block_3 = ExecutionNodeBlockBuilder::from_parent(&block_2)
+            .with_gas_used(1)
+            .with_base_fee_per_gas(1_000)
+            .build();
+        block_store::store_block(&test_db.pool, &block_1, 0.0).await;
+        block_store::store_block(&test_db.pool, &block_2, 0.0).await;
+        block_store::store_block(&test_db.pool, &block_3, 0.0).await;
+
+        let stats = get_block_burn_stats(&test_db.pool, block_3.number).await;
+
+        assert_eq!(
+            stats,
+            BurnStats {
+                count: 2,
+                mean: 20.0,
+                stddev: 10.0,
+            }
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn store_and_delete_burn_anomalies_test(test_db: &TestDb) {
+        let anomaly = BurnAnomaly {
+            block_hash: "0xburn_anomaly".to_string(),
+            block_number: BlockNumber(10),
+            burn: WeiNewtype::from_eth(5),
+            kind: BurnAnomalyKind::Block,
+            mean_burn: 1e18,
+            stddev_burn: 1e17,
+            timestamp: Utc::now().duration_trunc(Duration::seconds(1)).unwrap(),
+            z_score: 40.0,
+        };
+        store_burn_anomaly(&test_db.pool, &anomaly).await;
+        assert_eq!(get_burn_anomalies(&test_db.pool, 10).await, vec![anomaly]);
+
+        delete_burn_anomalies(&test_db.pool, &BlockNumber(10)).await;
+        assert!(get_burn_anomalies(&test_db.pool, 10).await.is_empty());
+    }
+}
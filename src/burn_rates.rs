@@ -1,8 +1,11 @@
+//! # Burn Rates
+//! Publishes the current burn rate per time frame, and keeps an hourly snapshot of each so trends
+//! can be charted. The snapshot of an hour is the rate as of the last block in it.
 use std::collections::HashMap;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, DurationRound, Utc};
 use serde::Serialize;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use tracing::debug;
 
 use crate::burn_sums::{BurnSum, BurnSums};
@@ -13,13 +16,16 @@ use crate::time_frames::TimeFrame;
 type EthPerMinute = f64;
 type UsdPerMinute = f64;
 
-#[derive(Debug, Serialize)]
+/// How far back the published snapshots go.
+const SNAPSHOT_DAYS: i64 = 30;
+
+#[derive(Debug, PartialEq, Serialize)]
 pub struct EthUsdRate {
     eth_per_minute: EthPerMinute,
     usd_per_minute: UsdPerMinute,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct BurnRate {
     block_number: BlockNumber,
     rate: EthUsdRate,
@@ -28,6 +34,9 @@ pub struct BurnRate {
 
 pub type BurnRates = HashMap<TimeFrame, BurnRate>;
 
+/// Hourly snapshots per time frame, oldest first. Snapshot timestamps are the start of the hour.
+pub type BurnRatesOverTime = HashMap<TimeFrame, Vec<BurnRate>>;
+
 impl From<(&TimeFrame, &BurnSum)> for BurnRate {
     fn from((time_frame, burn_sum): (&TimeFrame, &BurnSum)) -> Self {
         let minutes = time_frame.duration().num_minutes() as f64;
@@ -44,6 +53,86 @@ impl From<(&TimeFrame, &BurnSum)> for BurnRate {
     }
 }
 
+async fn store_snapshot(
+    executor: impl PgExecutor<'_>,
+    time_frame: &TimeFrame,
+    burn_rate: &BurnRate,
+) {
+    sqlx::query!(
+        "
+        INSERT INTO burn_rate_snapshots (
+            time_frame,
+            timestamp,
+            block_number,
+            eth_per_minute,
+            usd_per_minute
+        )
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (time_frame, timestamp) DO UPDATE SET
+            block_number = EXCLUDED.block_number,
+            eth_per_minute = EXCLUDED.eth_per_minute,
+            usd_per_minute = EXCLUDED.usd_per_minute
+        ",
+        time_frame.to_string(),
+        burn_rate
+            .timestamp
+            .duration_trunc(Duration::hours(1))
+            .unwrap(),
+        burn_rate.block_number.0,
+        burn_rate.rate.eth_per_minute,
+        burn_rate.rate.usd_per_minute
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+async fn get_burn_rates_over_time(
+    executor: impl PgExecutor<'_>,
+    since: &DateTime<Utc>,
+) -> BurnRatesOverTime {
+    let rows = sqlx::query!(
+        "
+        SELECT time_frame, timestamp, block_number, eth_per_minute, usd_per_minute
+        FROM burn_rate_snapshots
+        WHERE timestamp >= $1
+        ORDER BY timestamp ASC
+        ",
+        since
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap();
+
+    let mut burn_rates_over_time = BurnRatesOverTime::new();
+    for row in rows {
+        let time_frame = row.time_frame.parse::<TimeFrame>().unwrap();
+        let burn_rate = BurnRate {
+            block_number: BlockNumber(row.block_number),
+            rate: EthUsdRate {
+                eth_per_minute: row.eth_per_minute,
+                usd_per_minute: row.usd_per_minute,
+            },
+            timestamp: row.timestamp,
+        };
+        burn_rates_over_time
+            .entry(time_frame)
+            .or_default()
+            .push(burn_rate);
+    }
+    burn_rates_over_time
+}
+
+pub async fn delete_snapshots(executor: impl PgExecutor<'_>, greater_than_or_equal: &BlockNumber) {
+    sqlx::query!(
+        "DELETE FROM burn_rate_snapshots WHERE block_number >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
 pub async fn on_new_block(db_pool: &PgPool, burn_sums: &BurnSums) {
     debug!("calculating new burn rates");
 
@@ -52,5 +141,77 @@ pub async fn on_new_block(db_pool: &PgPool, burn_sums: &BurnSums) {
         .map(|(tf, bs)| -> (TimeFrame, BurnRate) { (*tf, (tf, bs).into()) })
         .collect();
 
+    for (time_frame, burn_rate) in burn_rates.iter() {
+        store_snapshot(db_pool, time_frame, burn_rate).await;
+    }
+
+    let latest_timestamp = burn_rates
+        .values()
+        .map(|burn_rate| burn_rate.timestamp)
+        .max();
+
     caching::update_and_publish(db_pool, &CacheKey::BurnRates, burn_rates).await;
+
+    if let Some(latest_timestamp) = latest_timestamp {
+        let since = latest_timestamp - Duration::days(SNAPSHOT_DAYS);
+        let burn_rates_over_time = get_burn_rates_over_time(db_pool, &since).await;
+        caching::update_and_publish(db_pool, &CacheKey::BurnRatesOverTime, burn_rates_over_time)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use test_context::test_context;
+
+    use crate::{db::tests::TestDb, time_frames::LimitedTimeFrame};
+
+    use super::*;
+
+    fn burn_rate(block_number: BlockNumber, minute: u32, eth_per_minute: f64) -> BurnRate {
+        BurnRate {
+            block_number,
+            rate: EthUsdRate {
+                eth_per_minute,
+                usd_per_minute: eth_per_minute * 3000.0,
+            },
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 1, 12, minute, 0).unwrap(),
+        }
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn last_block_in_hour_is_snapshot_test(test_db: &TestDb) {
+        let time_frame = TimeFrame::Limited(LimitedTimeFrame::Day1);
+        store_snapshot(
+            &test_db.pool,
+            &time_frame,
+            &burn_rate(BlockNumber(1), 10, 1.0),
+        )
+        .await;
+        store_snapshot(
+            &test_db.pool,
+            &time_frame,
+            &burn_rate(BlockNumber(2), 50, 2.0),
+        )
+        .await;
+
+        let since = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let burn_rates_over_time = get_burn_rates_over_time(&test_db.pool, &since).await;
+
+        let hour_start = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        assert_eq!(
+            burn_rates_over_time.get(&time_frame).unwrap(),
+            &vec![BurnRate {
+                timestamp: hour_start,
+                ..burn_rate(BlockNumber(2), 50, 2.0)
+            }]
+        );
+
+        delete_snapshots(&test_db.pool, &BlockNumber(2)).await;
+        assert!(get_burn_rates_over_time(&test_db.pool, &since)
+            .await
+            .is_empty());
+    }
 }
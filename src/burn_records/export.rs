@@ -0,0 +1,138 @@
+use std::fs::File;
+
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+
+#[cfg(feature = "parquet")]
+use arrow::{
+    array::{
+        ArrayRef, Decimal128Array, Float64Array, Int64Array, StringArray, TimestampMicrosecondArray,
+    },
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    record_batch::RecordBatch,
+};
+use chrono::{DateTime, Utc};
+use enum_iterator::all;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    db,
+    execution_chain::BlockNumber,
+    log,
+    row_writer::{ExportFormat, ExportRow, RowWriter},
+    time_frames::TimeFrame,
+    units::WeiNewtype,
+};
+
+use super::{
+    store::{BurnRecordStore, BurnRecordStorePostgres},
+    BurnRecord,
+};
+
+#[derive(Serialize)]
+struct BurnRecordRow {
+    time_frame: String,
+    block_hash: String,
+    block_number: BlockNumber,
+    burn_usd: f64,
+    burn_wei: WeiNewtype,
+    timestamp: DateTime<Utc>,
+}
+
+impl BurnRecordRow {
+    fn new(time_frame: &TimeFrame, burn_record: BurnRecord) -> Self {
+        Self {
+            time_frame: time_frame.to_string(),
+            block_hash: burn_record.block_hash,
+            block_number: burn_record.block_number,
+            burn_usd: burn_record.burn_usd.0,
+            burn_wei: burn_record.burn_wei,
+            timestamp: burn_record.timestamp,
+        }
+    }
+}
+
+impl ExportRow for BurnRecordRow {
+    #[cfg(feature = "parquet")]
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("time_frame", DataType::Utf8, false),
+            Field::new("block_hash", DataType::Utf8, false),
+            Field::new("block_number", DataType::Int64, false),
+            Field::new("burn_usd", DataType::Float64, false),
+            Field::new("burn_wei", DataType::Decimal128(38, 0), false),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                false,
+            ),
+        ]))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn to_record_batch(rows: &[Self]) -> RecordBatch {
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.time_frame),
+                )) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.block_hash),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|row| row.block_number.0),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    rows.iter().map(|row| row.burn_usd),
+                )),
+                Arc::new(
+                    Decimal128Array::from_iter_values(rows.iter().map(|row| row.burn_wei.0))
+                        .with_precision_and_scale(38, 0)
+                        .unwrap(),
+                ),
+                Arc::new(
+                    TimestampMicrosecondArray::from_iter_values(
+                        rows.iter().map(|row| row.timestamp.timestamp_micros()),
+                    )
+                    .with_timezone("UTC"),
+                ),
+            ],
+        )
+        .unwrap()
+    }
+}
+
+/// Writes the current records of every time frame to a file.
+pub async fn export_burn_records() {
+    export_burn_records_with_format(ExportFormat::from_args()).await;
+}
+
+pub async fn export_burn_records_with_format(format: ExportFormat) {
+    log::init_with_env();
+
+    let timestamp = crate::time::get_timestamp();
+
+    info!(%format, "exporting burn records {timestamp}");
+
+    let db_pool = db::get_db_pool("export-burn-records").await;
+    let mut connection = db_pool.acquire().await.unwrap();
+    let burn_record_store = BurnRecordStorePostgres;
+
+    let file_path = format!("burn_records_{timestamp}.{}", format.extension());
+    let mut row_writer = RowWriter::create(File::create(&file_path).unwrap(), format);
+
+    for time_frame in all::<TimeFrame>() {
+        let records = burn_record_store
+            .records(&mut connection, &time_frame)
+            .await;
+        for burn_record in records {
+            row_writer.write(BurnRecordRow::new(&time_frame, burn_record));
+        }
+    }
+
+    row_writer.finish();
+
+    info!(file_path, "done exporting burn records");
+}
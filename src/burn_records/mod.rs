@@ -0,0 +1,206 @@
+//! # Burn Records
+//! Keeps the blocks which burned the most ETH, for every time frame. Records are updated
+//! incrementally. A new block only enters the records when it burned more than the current
+//! lowest record. When records expire, or are rolled back, we fall back to recomputing the records
+//! for a time frame from scratch.
+mod export;
+mod store;
+
+pub use export::export_burn_records;
+pub use export::export_burn_records_with_format;
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use enum_iterator::all;
+use serde::Serialize;
+use sqlx::PgConnection;
+use tracing::debug;
+
+use crate::{
+    analysis::Analysis,
+    caching::{self, CacheKey},
+    execution_chain::{BlockNumber, ExecutionNodeBlock},
+    performance::TimedExt,
+    rollback::RollbackPoint,
+    time_frames::TimeFrame,
+    units::{UsdNewtype, WeiNewtype},
+};
+
+use self::store::{BurnRecordStore, BurnRecordStorePostgres};
+
+const RECORD_LIMIT: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BurnRecord {
+    block_hash: String,
+    block_number: BlockNumber,
+    burn_usd: UsdNewtype,
+    burn_wei: WeiNewtype,
+    timestamp: DateTime<Utc>,
+}
+
+pub type BurnRecords = HashMap<TimeFrame, Vec<BurnRecord>>;
+
+pub async fn on_rollback(connection: &mut PgConnection, block_number_gte: &BlockNumber) {
+    BurnRecordStorePostgres::delete_new_records_tx(connection, block_number_gte).await;
+}
+
+pub struct BurnRecordsRollback;
+
+#[async_trait]
+impl Analysis for BurnRecordsRollback {
+    fn name(&self) -> &'static str {
+        "burn_records"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            on_rollback(transaction, block_number_gte).await;
+        }
+    }
+}
+
+async fn update_records_for_time_frame(
+    burn_record_store: &impl BurnRecordStore,
+    connection: &mut PgConnection,
+    block: &ExecutionNodeBlock,
+    new_record: &BurnRecord,
+    time_frame: &TimeFrame,
+) {
+    if let TimeFrame::Limited(limited_time_frame) = time_frame {
+        let age_limit = block.timestamp - limited_time_frame.duration();
+        burn_record_store
+            .delete_expired_records(connection, time_frame, &age_limit)
+            .await;
+    }
+
+    let records = burn_record_store.records(connection, time_frame).await;
+
+    // Having fewer records than the limit means records expired, were rolled back, or we never
+    // computed records for this time frame. Either way, the blocks which should replace them are
+    // unknown, so we recompute.
+    if records.len() < RECORD_LIMIT {
+        debug!(%time_frame, "too few burn records, recomputing from blocks");
+        let top_records = burn_record_store
+            .top_records_since(
+                connection,
+                &time_frame.start_timestamp(block),
+                RECORD_LIMIT as i64,
+            )
+            .await;
+        burn_record_store
+            .replace_records(connection, time_frame, &top_records)
+            .await;
+        return;
+    }
+
+    let lowest_record = records
+        .last()
+        .expect("expect records to be non-empty after length check");
+    if new_record.burn_wei.0 > lowest_record.burn_wei.0 {
+        debug!(%time_frame, block_number = %new_record.block_number, "new burn record");
+        burn_record_store
+            .add_record(connection, time_frame, new_record)
+            .await;
+        burn_record_store
+            .trim_records(connection, time_frame, RECORD_LIMIT as i64)
+            .await;
+    }
+}
+
+/// Updates the burn records with a block that has already been stored. Expects to run in the same
+/// transaction the block was stored in.
+pub async fn on_new_block(
+    connection: &mut PgConnection,
+    block: &ExecutionNodeBlock,
+) -> BurnRecords {
+    let burn_record_store = BurnRecordStorePostgres;
+
+    let new_record = burn_record_store
+        .record_from_block(connection, &block.number)
+        .await;
+
+    let mut burn_records = BurnRecords::new();
+    for time_frame in all::<TimeFrame>() {
+        update_records_for_time_frame(
+            &burn_record_store,
+            connection,
+            block,
+            &new_record,
+            &time_frame,
+        )
+        .timed(&format!("update_burn_records_{time_frame}"))
+        .await;
+        let records = burn_record_store.records(connection, &time_frame).await;
+        burn_records.insert(time_frame, records);
+    }
+
+    caching::update_and_publish_tx(connection, &CacheKey::BurnRecords, &burn_records).await;
+
+    burn_records
+}
+
+/// Publishes the records as currently stored, without taking in a new block.
+pub async fn publish_stored_records(connection: &mut PgConnection) {
+    let burn_record_store = BurnRecordStorePostgres;
+
+    let mut burn_records = BurnRecords::new();
+    for time_frame in all::<TimeFrame>() {
+        let records = burn_record_store.records(connection, &time_frame).await;
+        burn_records.insert(time_frame, records);
+    }
+
+    caching::update_and_publish_tx(connection, &CacheKey::BurnRecords, &burn_records).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Acquire;
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{block_store, ExecutionNodeBlockBuilder},
+        time_frames::GrowingTimeFrame,
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn on_new_block_rollback_test(test_db: &TestDb) {
+        let burn_record_store = BurnRecordStorePostgres;
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        let time_frame = TimeFrame::Growing(GrowingTimeFrame::SinceMerge);
+
+        let block_1 = ExecutionNodeBlockBuilder::new("burn_records_rollback")
+            .with_burn(WeiNewtype::from_eth(1))
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_burn(WeiNewtype::from_eth(2))
+            .build();
+
+        block_store::store_block(&mut *connection, &block_1, 1.0).await;
+        on_new_block(&mut connection, &block_1).await;
+        block_store::store_block(&mut *connection, &block_2, 1.0).await;
+        on_new_block(&mut connection, &block_2).await;
+
+        let records = burn_record_store
+            .records(&mut connection, &time_frame)
+            .await;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].block_number, block_2.number);
+
+        let mut transaction = connection.begin().await.unwrap();
+        on_rollback(&mut transaction, &block_2.number).await;
+        transaction.commit().await.unwrap();
+
+        let records = burn_record_store
+            .records(&mut connection, &time_frame)
+            .await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].block_number, block_1.number);
+    }
+}
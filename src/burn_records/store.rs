@@ -0,0 +1,410 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgRow, Acquire, PgConnection, Row};
+
+use crate::{
+    execution_chain::{BlockNumber, BURN_WEI_SQL},
+    time_frames::TimeFrame,
+    units::WeiNewtype,
+};
+
+use super::BurnRecord;
+
+fn burn_record_from_row(row: PgRow) -> BurnRecord {
+    BurnRecord {
+        block_hash: row.get("block_hash"),
+        block_number: row.get("block_number"),
+        burn_usd: row.get::<f64, _>("burn_usd").into(),
+        burn_wei: row.get("burn_wei"),
+        timestamp: row.get("timestamp"),
+    }
+}
+
+#[async_trait]
+pub trait BurnRecordStore {
+    async fn add_record(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+        burn_record: &BurnRecord,
+    );
+    async fn delete_expired_records(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+        age_limit: &DateTime<Utc>,
+    );
+    async fn delete_new_records_tx<'a>(
+        transaction: &'a mut PgConnection,
+        block_number_gte: &'a BlockNumber,
+    );
+    async fn record_from_block(
+        &self,
+        connection: &mut PgConnection,
+        block_number: &BlockNumber,
+    ) -> BurnRecord;
+    async fn records(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+    ) -> Vec<BurnRecord>;
+    async fn replace_records(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+        burn_records: &[BurnRecord],
+    );
+    async fn top_records_since(
+        &self,
+        connection: &mut PgConnection,
+        timestamp: &DateTime<Utc>,
+        limit: i64,
+    ) -> Vec<BurnRecord>;
+    async fn trim_records(&self, connection: &mut PgConnection, time_frame: &TimeFrame, limit: i64);
+}
+
+pub struct BurnRecordStorePostgres;
+
+#[async_trait]
+impl BurnRecordStore for BurnRecordStorePostgres {
+    async fn add_record(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+        burn_record: &BurnRecord,
+    ) {
+        sqlx::query!(
+            "
+            INSERT INTO burn_records (
+                time_frame,
+                block_number,
+                block_hash,
+                timestamp,
+                burn_wei,
+                burn_usd
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ",
+            time_frame.to_string(),
+            burn_record.block_number.0,
+            burn_record.block_hash,
+            burn_record.timestamp,
+            burn_record.burn_wei as WeiNewtype,
+            burn_record.burn_usd.0
+        )
+        .execute(&mut *connection)
+        .await
+        .unwrap();
+    }
+
+    async fn delete_expired_records(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+        age_limit: &DateTime<Utc>,
+    ) {
+        sqlx::query!(
+            "
+            DELETE FROM burn_records
+            WHERE time_frame = $1
+            AND timestamp < $2
+            ",
+            time_frame.to_string(),
+            age_limit
+        )
+        .execute(&mut *connection)
+        .await
+        .unwrap();
+    }
+
+    async fn delete_new_records_tx<'a>(
+        transaction: &'a mut PgConnection,
+        block_number_gte: &'a BlockNumber,
+    ) {
+        sqlx::query!(
+            "
+            DELETE FROM burn_records
+            WHERE block_number >= $1
+            ",
+            block_number_gte.0
+        )
+        .execute(transaction)
+        .await
+        .unwrap();
+    }
+
+    async fn record_from_block(
+        &self,
+        connection: &mut PgConnection,
+        block_number: &BlockNumber,
+    ) -> BurnRecord {
+        sqlx::query(&format!(
+            "
+            WITH block_burn AS (
+                SELECT
+                    hash,
+                    number,
+                    timestamp,
+                    eth_price,
+                    {BURN_WEI_SQL} AS burn_wei
+                FROM blocks_next
+                WHERE number = $1
+            )
+            SELECT
+                hash AS block_hash,
+                number AS block_number,
+                timestamp,
+                burn_wei,
+                (burn_wei / 1e18 * eth_price)::FLOAT8 AS burn_usd
+            FROM block_burn
+            "
+        ))
+        .bind(block_number)
+        .map(burn_record_from_row)
+        .fetch_one(&mut *connection)
+        .await
+        .unwrap()
+    }
+
+    async fn records(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+    ) -> Vec<BurnRecord> {
+        sqlx::query!(
+            r#"
+            SELECT
+                block_hash,
+                block_number,
+                timestamp,
+                burn_wei AS "burn_wei: WeiNewtype",
+                burn_usd
+            FROM burn_records
+            WHERE time_frame = $1
+            ORDER BY burn_records.burn_wei DESC
+            "#,
+            time_frame.to_string()
+        )
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| BurnRecord {
+            block_hash: row.block_hash,
+            block_number: BlockNumber(row.block_number),
+            burn_usd: row.burn_usd.into(),
+            burn_wei: row.burn_wei,
+            timestamp: row.timestamp,
+        })
+        .collect()
+    }
+
+    async fn replace_records(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+        burn_records: &[BurnRecord],
+    ) {
+        let mut transaction = connection.begin().await.unwrap();
+
+        sqlx::query!(
+            "
+            DELETE FROM burn_records
+            WHERE time_frame = $1
+            ",
+            time_frame.to_string()
+        )
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+
+        let mut block_numbers: Vec<i64> = Vec::new();
+        let mut block_hashes: Vec<String> = Vec::new();
+        let mut timestamps: Vec<DateTime<Utc>> = Vec::new();
+        let mut burns_wei: Vec<WeiNewtype> = Vec::new();
+        let mut burns_usd: Vec<f64> = Vec::new();
+        for burn_record in burn_records {
+            block_numbers.push(burn_record.block_number.0);
+            block_hashes.push(burn_record.block_hash.clone());
+            timestamps.push(burn_record.timestamp);
+            burns_wei.push(burn_record.burn_wei);
+            burns_usd.push(burn_record.burn_usd.0);
+        }
+
+        sqlx::query!(
+            "
+            INSERT INTO burn_records (
+                time_frame,
+                block_number,
+                block_hash,
+                timestamp,
+                burn_wei,
+                burn_usd
+            )
+            SELECT $1, * FROM UNNEST (
+                $2::int8[],
+                $3::text[],
+                $4::timestamptz[],
+                $5::numeric[],
+                $6::float8[]
+            )
+            ",
+            time_frame.to_string(),
+            &block_numbers,
+            &block_hashes,
+            &timestamps,
+            &burns_wei as &[WeiNewtype],
+            &burns_usd
+        )
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+
+        transaction.commit().await.unwrap();
+    }
+
+    async fn top_records_since(
+        &self,
+        connection: &mut PgConnection,
+        timestamp: &DateTime<Utc>,
+        limit: i64,
+    ) -> Vec<BurnRecord> {
+        sqlx::query(&format!(
+            "
+            WITH block_burns AS (
+                SELECT
+                    hash,
+                    number,
+                    timestamp,
+                    eth_price,
+                    {BURN_WEI_SQL} AS burn_wei
+                FROM blocks_next
+                WHERE timestamp >= $1
+            )
+            SELECT
+                hash AS block_hash,
+                number AS block_number,
+                timestamp,
+                burn_wei,
+                (burn_wei / 1e18 * eth_price)::FLOAT8 AS burn_usd
+            FROM block_burns
+            ORDER BY block_burns.burn_wei DESC
+            LIMIT $2
+            "
+        ))
+        .bind(timestamp)
+        .bind(limit)
+        .map(burn_record_from_row)
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap()
+    }
+
+    async fn trim_records(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+        limit: i64,
+    ) {
+        sqlx::query!(
+            "
+            DELETE FROM burn_records
+            WHERE time_frame = $1
+            AND block_number NOT IN (
+                SELECT block_number
+                FROM burn_records
+                WHERE time_frame = $1
+                ORDER BY burn_wei DESC
+                LIMIT $2
+            )
+            ",
+            time_frame.to_string(),
+            limit
+        )
+        .execute(&mut *connection)
+        .await
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{block_store, ExecutionNodeBlockBuilder},
+        time_frames::GrowingTimeFrame,
+        units::UsdNewtype,
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn top_records_since_test(test_db: &TestDb) {
+        let burn_record_store = BurnRecordStorePostgres;
+        let mut connection = test_db.pool.acquire().await.unwrap();
+
+        let block_1 = ExecutionNodeBlockBuilder::new("top_records_since")
+            .with_burn(WeiNewtype::from_eth(1))
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_burn(WeiNewtype::from_eth(3))
+            .build();
+        let block_3 = ExecutionNodeBlockBuilder::from_parent(&block_2)
+            .with_burn(WeiNewtype::from_eth(2))
+            .build();
+
+        block_store::store_block(&mut *connection, &block_1, 1.0).await;
+        block_store::store_block(&mut *connection, &block_2, 1.0).await;
+        block_store::store_block(&mut *connection, &block_3, 1.0).await;
+
+        let records = burn_record_store
+            .top_records_since(&mut connection, &block_1.timestamp, 2)
+            .await;
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].block_number, block_2.number);
+        assert_eq!(records[0].burn_wei, WeiNewtype::from_eth(3));
+        assert_eq!(records[0].burn_usd, UsdNewtype(3.0));
+        assert_eq!(records[1].block_number, block_3.number);
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn trim_records_test(test_db: &TestDb) {
+        let burn_record_store = BurnRecordStorePostgres;
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        let time_frame = TimeFrame::Growing(GrowingTimeFrame::SinceBurn);
+
+        let block_1 = ExecutionNodeBlockBuilder::new("trim_records")
+            .with_burn(WeiNewtype::from_eth(1))
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_burn(WeiNewtype::from_eth(2))
+            .build();
+
+        block_store::store_block(&mut *connection, &block_1, 1.0).await;
+        block_store::store_block(&mut *connection, &block_2, 1.0).await;
+
+        for block in [&block_1, &block_2] {
+            let burn_record = burn_record_store
+                .record_from_block(&mut connection, &block.number)
+                .await;
+            burn_record_store
+                .add_record(&mut connection, &time_frame, &burn_record)
+                .await;
+        }
+
+        burn_record_store
+            .trim_records(&mut connection, &time_frame, 1)
+            .await;
+
+        let records = burn_record_store
+            .records(&mut connection, &time_frame)
+            .await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].block_number, block_2.number);
+    }
+}
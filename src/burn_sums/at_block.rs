@@ -0,0 +1,228 @@
+//! Burn sums as they stood at a past block. Reproduces what the dashboard showed at the block, and
+//! differing from what was published points at a bug in adding and expiring burn.
+//!
+//! The stored sums only go back `REORG_LIMIT` blocks. For older blocks we compute the sums from
+//! `blocks_next`, but only for limited time frames, a growing time frame would read every block
+//! since its hard fork. The handler reads from the replica.
+use axum::{
+    extract::Query,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use enum_iterator::all;
+use reqwest::header;
+use serde::Deserialize;
+use sqlx::PgConnection;
+
+use crate::{
+    config,
+    execution_chain::{block_store, BlockNumber, BlockRange},
+    serve::StateExtension,
+    time_frames::{LimitedTimeFrame, TimeFrame},
+    units::{UsdDecimal, WeiNewtype},
+};
+
+use super::{
+    burn_sums_from_vec,
+    store::{BurnSumStore, BurnSumStorePostgres},
+    BurnSumRecord, BurnSums,
+};
+
+/// The sums the sync stored for the block, empty when they've been deleted.
+async fn get_stored_burn_sums(
+    connection: &mut PgConnection,
+    block_hash: &str,
+    block_number: BlockNumber,
+) -> Vec<BurnSumRecord> {
+    sqlx::query!(
+        r#"
+        SELECT
+            time_frame,
+            first_included_block_number,
+            last_included_block_number,
+            last_included_block_hash,
+            timestamp,
+            sum_usd AS "sum_usd: UsdDecimal",
+            sum_usd_twap AS "sum_usd_twap: UsdDecimal",
+            sum_wei AS "sum_wei: WeiNewtype"
+        FROM burn_sums
+        WHERE last_included_block_number = $1
+        AND last_included_block_hash = $2
+        "#,
+        block_number.0,
+        block_hash
+    )
+    .fetch_all(connection)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| BurnSumRecord {
+        first_included_block_number: BlockNumber(row.first_included_block_number),
+        last_included_block_hash: row.last_included_block_hash,
+        last_included_block_number: BlockNumber(row.last_included_block_number),
+        sum_usd: row.sum_usd,
+        sum_usd_twap: row.sum_usd_twap,
+        sum_wei: row.sum_wei,
+        time_frame: row.time_frame.parse().unwrap(),
+        timestamp: row.timestamp,
+    })
+    .collect()
+}
+
+/// Every time frame when the sums for the block are still stored, otherwise only the limited time
+/// frames. `None` when we don't have the block.
+pub async fn burn_sums_at_block(
+    connection: &mut PgConnection,
+    block_number: BlockNumber,
+) -> Option<BurnSums> {
+    let burn_sum_store = BurnSumStorePostgres;
+
+    let block = sqlx::query!(
+        "SELECT hash, timestamp FROM blocks_next WHERE number = $1",
+        block_number.0
+    )
+    .fetch_optional(&mut *connection)
+    .await
+    .unwrap()?;
+    let (block_hash, timestamp) = (block.hash, block.timestamp);
+
+    let stored_burn_sums = get_stored_burn_sums(connection, &block_hash, block_number).await;
+    if !stored_burn_sums.is_empty() {
+        return Some(burn_sums_from_vec(
+            &stored_burn_sums,
+            config::CONFIG.burn_usd_valuation(),
+        ));
+    }
+
+    let mut burn_sum_records = Vec::new();
+    for limited_time_frame in all::<LimitedTimeFrame>() {
+        let first_included_block_number = block_store::first_number_after_or_at(
+            &mut *connection,
+            &(timestamp - limited_time_frame.duration()),
+        )
+        .await
+        .expect("expect blocks to be available when calculating burn sums at a block");
+
+        let range = BlockRange::new(first_included_block_number, block_number);
+        let (sum_wei, sum_usd) = burn_sum_store
+            .burn_sum_from_block_range(connection, &range)
+            .await;
+        let sum_usd_twap = burn_sum_store
+            .burn_sum_usd_twap_from_block_range(connection, &range)
+            .await;
+
+        burn_sum_records.push(BurnSumRecord {
+            first_included_block_number,
+            last_included_block_hash: block_hash.clone(),
+            last_included_block_number: block_number,
+            sum_usd,
+            sum_usd_twap,
+            sum_wei,
+            time_frame: TimeFrame::Limited(limited_time_frame),
+            timestamp,
+        });
+    }
+
+    Some(burn_sums_from_vec(
+        &burn_sum_records,
+        config::CONFIG.burn_usd_valuation(),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct BurnSumsAtBlockParams {
+    block_number: BlockNumber,
+}
+
+pub async fn burn_sums_at_block_handler(
+    state: StateExtension,
+    Query(params): Query<BurnSumsAtBlockParams>,
+) -> impl IntoResponse {
+    let mut connection = state.read_db_pool.acquire().await.unwrap();
+
+    match burn_sums_at_block(&mut connection, params.block_number).await {
+        None => StatusCode::NOT_FOUND.into_response(),
+        Some(burn_sums) => {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static("public, max-age=600, stale-while-revalidate=86400"),
+            );
+
+            (headers, Json(burn_sums)).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb, execution_chain::ExecutionNodeBlockBuilder,
+        time_frames::GrowingTimeFrame, units::EthNewtype,
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn burn_sums_at_block_test(test_db: &TestDb) {
+        let block_1 = ExecutionNodeBlockBuilder::new("burn_sums_at_block")
+            .with_burn(WeiNewtype::from_eth(1))
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_burn(WeiNewtype::from_eth(2))
+            .build();
+
+        block_store::store_block(&test_db.pool, &block_1, 1.0).await;
+        block_store::store_block(&test_db.pool, &block_2, 1.0).await;
+
+        let mut connection = test_db.pool.acquire().await.unwrap();
+
+        let burn_sums = burn_sums_at_block(&mut connection, block_1.number)
+            .await
+            .unwrap();
+        let day_sum = &burn_sums[TimeFrame::Limited(LimitedTimeFrame::Day1)];
+        assert_eq!(day_sum.block_number, block_1.number);
+        assert_eq!(day_sum.sum.eth, EthNewtype(1.0));
+        assert!(!burn_sums.contains_key(&TimeFrame::Growing(GrowingTimeFrame::SinceBurn)));
+
+        let burn_sums = burn_sums_at_block(&mut connection, block_2.number)
+            .await
+            .unwrap();
+        assert_eq!(
+            burn_sums[TimeFrame::Limited(LimitedTimeFrame::Day1)]
+                .sum
+                .eth,
+            EthNewtype(3.0)
+        );
+
+        assert!(burn_sums_at_block(&mut connection, block_2.number + 1)
+            .await
+            .is_none());
+
+        // Sums the sync stored win, growing time frames included.
+        let since_merge = TimeFrame::Growing(GrowingTimeFrame::SinceMerge);
+        BurnSumStorePostgres
+            .store_burn_sums(
+                &mut connection,
+                &[BurnSumRecord {
+                    first_included_block_number: block_1.number,
+                    last_included_block_hash: block_2.hash.clone(),
+                    last_included_block_number: block_2.number,
+                    sum_usd: UsdDecimal(20.into()),
+                    sum_usd_twap: UsdDecimal(20.into()),
+                    sum_wei: WeiNewtype::from_eth(10),
+                    time_frame: since_merge,
+                    timestamp: block_2.timestamp,
+                }],
+            )
+            .await;
+        let burn_sums = burn_sums_at_block(&mut connection, block_2.number)
+            .await
+            .unwrap();
+        assert_eq!(burn_sums[since_merge].sum.eth, EthNewtype(10.0));
+    }
+}
@@ -0,0 +1,175 @@
+//! Walks the stored blocks and adds each to the burn sums, as the sync does for new heads, without
+//! publishing. Seeds `burn_sums` for a fresh deployment from an existing `blocks_next`.
+//!
+//! Sums are stored a chunk of blocks per transaction, so the stored sums are the progress. A
+//! restart continues after the last chunk summed. Without stored sums, we start from the block
+//! given, or `REORG_LIMIT` blocks before the last. Like the sync, we only keep the sums of the last
+//! `REORG_LIMIT` blocks. Stop the sync while backfilling, both add to the last stored sums.
+use std::num::NonZeroU32;
+
+use pit_wall::Progress;
+use sqlx::{Connection, PgConnection};
+use tracing::{info, warn};
+
+use crate::{
+    db,
+    execution_chain::{block_store, BlockNumber, BlockRange},
+    log,
+};
+
+use super::{
+    store::{BurnSumStorePostgres, REORG_LIMIT},
+    store_sums_for_block,
+};
+
+const CHUNK_SIZE: NonZeroU32 = NonZeroU32::new(1000).unwrap();
+
+async fn get_last_summed_block_number(connection: &mut PgConnection) -> Option<BlockNumber> {
+    sqlx::query!("SELECT MAX(last_included_block_number) AS block_number FROM burn_sums")
+        .fetch_one(connection)
+        .await
+        .unwrap()
+        .block_number
+        .map(BlockNumber)
+}
+
+async fn backfill_block_range(
+    connection: &mut PgConnection,
+    block_range: &BlockRange,
+    progress: &mut Progress,
+) {
+    let burn_sum_store = BurnSumStorePostgres;
+
+    for chunk in block_range.chunks(CHUNK_SIZE) {
+        let mut transaction = connection.begin().await.unwrap();
+
+        for block_number in chunk.clone() {
+            let block = block_store::get_block_by_number(&mut *transaction, &block_number)
+                .await
+                .expect("expect blocks to be stored without gaps when backfilling burn sums");
+            store_sums_for_block(&burn_sum_store, &mut transaction, &block).await;
+        }
+
+        transaction.commit().await.unwrap();
+
+        progress.inc_work_done_by(chunk.len().try_into().unwrap());
+        info!("{}", progress.get_progress_string());
+    }
+}
+
+pub async fn backfill_burn_sums() {
+    let from = std::env::args()
+        .skip(1)
+        .find_map(|str| str.parse::<BlockNumber>().ok());
+    backfill_burn_sums_from(from).await;
+}
+
+/// `from` is only used when no burn sums are stored, the last 100 blocks when `None`.
+pub async fn backfill_burn_sums_from(from: Option<BlockNumber>) {
+    log::init_with_env();
+
+    let db_pool = db::get_db_pool("backfill-burn-sums").await;
+    let mut connection = db_pool.acquire().await.unwrap();
+
+    let last_block_number = block_store::get_last_block_number(&mut *connection)
+        .await
+        .expect("expect blocks to be stored when backfilling burn sums");
+
+    let start = match get_last_summed_block_number(&mut connection).await {
+        Some(last_summed_block_number) => {
+            if from.is_some() {
+                warn!(
+                    %last_summed_block_number,
+                    "burn sums already stored, continuing after them instead"
+                );
+            }
+            last_summed_block_number + 1
+        }
+        None => from
+            .unwrap_or(last_block_number - REORG_LIMIT)
+            .max(BlockNumber::GENESIS),
+    };
+
+    if start > last_block_number {
+        info!(%last_block_number, "burn sums up to date");
+        return;
+    }
+
+    let block_range = BlockRange::new(start, last_block_number);
+    info!(%block_range, "backfilling burn sums");
+
+    let mut progress = Progress::new("backfill-burn-sums", block_range.len().try_into().unwrap());
+
+    backfill_block_range(&mut connection, &block_range, &mut progress).await;
+
+    info!("done backfilling burn sums");
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use test_context::test_context;
+
+    use crate::{
+        burn_sums::store::BurnSumStore,
+        db::tests::TestDb,
+        execution_chain::{ExecutionNodeBlockBuilder, SHAPELLA_BLOCK_NUMBER},
+        time_frames::{GrowingTimeFrame, LimitedTimeFrame, TimeFrame},
+        units::WeiNewtype,
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn backfill_block_range_test(test_db: &TestDb) {
+        // Growing time frames sum from their start block, which has to be stored. Before the day
+        // of the blocks we backfill, to stay out of the day sum.
+        let start_timestamp = GrowingTimeFrame::SinceMerge.start_timestamp() - Duration::days(2);
+        let mut parent = ExecutionNodeBlockBuilder::new("backfill_burn_sums_start")
+            .with_timestamp(&start_timestamp)
+            .build();
+        for growing_time_frame in enum_iterator::all::<GrowingTimeFrame>() {
+            let start_block = ExecutionNodeBlockBuilder::from_parent(&parent)
+                .with_number(growing_time_frame.start_block_number())
+                .with_timestamp(&start_timestamp)
+                .build();
+            block_store::store_block(&test_db.pool, &start_block, 1.0).await;
+            parent = start_block;
+        }
+
+        let block_1 = ExecutionNodeBlockBuilder::from_parent(&parent)
+            .with_number(*SHAPELLA_BLOCK_NUMBER + 1)
+            .with_timestamp(&GrowingTimeFrame::SinceMerge.start_timestamp())
+            .with_burn(WeiNewtype::from_eth(1))
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_burn(WeiNewtype::from_eth(2))
+            .build();
+
+        block_store::store_block(&test_db.pool, &block_1, 1.0).await;
+        block_store::store_block(&test_db.pool, &block_2, 1.0).await;
+
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        let mut progress = Progress::new("backfill-burn-sums-test", 2);
+
+        backfill_block_range(
+            &mut connection,
+            &BlockRange::new(block_1.number, block_2.number),
+            &mut progress,
+        )
+        .await;
+
+        assert_eq!(
+            get_last_summed_block_number(&mut connection).await,
+            Some(block_2.number)
+        );
+
+        let day_sum = BurnSumStorePostgres
+            .last_burn_sum(&mut connection, &TimeFrame::Limited(LimitedTimeFrame::Day1))
+            .await
+            .unwrap();
+        assert_eq!(day_sum.first_included_block_number, block_1.number);
+        assert_eq!(day_sum.sum_wei, WeiNewtype::from_eth(3));
+    }
+}
@@ -1,31 +1,66 @@
 //! # Burn Totals
 //! This module sums total burn. Limited, and growing time frames. Records are mere burn sums with
 //! some metadata. Not "highest" or "lowest" out of all.
-
+//!
+//! Burn is valued in USD two ways, both are stored. With the price closest to each block, and
+//! with the average of the last closed minute candle before each block, a TWAP. Which one is
+//! published is configured with `burn_usd_valuation`, comparing the two shows how much the
+//! methodology matters.
+
+mod at_block;
+mod backfill;
+mod range;
 mod store;
+mod verify;
 
 use std::{cmp::Ordering, collections::HashMap, ops::Index};
 
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use enum_iterator::all;
-use futures::future::join_all;
-use serde::Serialize;
-use sqlx::{PgConnection, PgPool};
-use tracing::debug;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use tracing::{debug, warn};
 
 use crate::{
+    analysis::Analysis,
     burn_sums::store::BurnSumStore,
     caching::{self, CacheKey},
-    execution_chain::{
-        BlockNumber, BlockRange, BlockStore, BlockStorePostgres, ExecutionNodeBlock,
-    },
+    config,
+    execution_chain::{block_store, BlockNumber, BlockRange, ExecutionNodeBlock},
     performance::TimedExt,
+    rollback::RollbackPoint,
     time_frames::{LimitedTimeFrame, TimeFrame},
-    units::{EthNewtype, UsdNewtype, WeiNewtype},
+    units::{EthNewtype, UsdDecimal, UsdNewtype, WeiNewtype},
 };
 
 use self::store::BurnSumStorePostgres;
 
+pub use at_block::burn_sums_at_block_handler;
+
+pub use backfill::backfill_burn_sums;
+pub use backfill::backfill_burn_sums_from;
+
+#[cfg(feature = "graphql")]
+pub use range::burn_sum_from_range;
+pub use range::parse_range;
+pub use range::sum_burn;
+pub use range::sum_burn_between;
+pub use range::BurnSumRange;
+pub use verify::verify_burn_sums;
+pub use verify::verify_burn_sums_with_repair;
+
+/// How burn sums published in USD are valued.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsdValuation {
+    /// Each block's burn at the price closest to it.
+    #[default]
+    ClosestPrice,
+    /// Each block's burn at the average of the candle of the minute before its own.
+    Twap,
+}
+
 #[derive(Debug, PartialEq)]
 struct WeiUsdAmount {
     wei: WeiNewtype,
@@ -58,13 +93,16 @@ pub struct BurnSum {
 
 pub type BurnSums = HashMap<TimeFrame, BurnSum>;
 
-fn burn_sums_from_vec(records: &[BurnSumRecord]) -> BurnSums {
+fn burn_sums_from_vec(records: &[BurnSumRecord], usd_valuation: UsdValuation) -> BurnSums {
     records
         .iter()
         .map(|record| {
             let time_frame = record.time_frame;
             let eth = record.sum_wei.into();
-            let usd = record.sum_usd;
+            let usd = match usd_valuation {
+                UsdValuation::ClosestPrice => record.sum_usd.into(),
+                UsdValuation::Twap => record.sum_usd_twap.into(),
+            };
             let eth_usd_amount = EthUsdAmount { eth, usd };
             let burn_sum = BurnSum {
                 block_number: record.last_included_block_number,
@@ -90,7 +128,8 @@ pub struct BurnSumRecord {
     first_included_block_number: BlockNumber,
     last_included_block_hash: String,
     last_included_block_number: BlockNumber,
-    sum_usd: UsdNewtype,
+    sum_usd: UsdDecimal,
+    sum_usd_twap: UsdDecimal,
     sum_wei: WeiNewtype,
     time_frame: TimeFrame,
     timestamp: DateTime<Utc>,
@@ -100,23 +139,38 @@ pub async fn on_rollback(connection: &mut PgConnection, block_number_gte: &Block
     BurnSumStorePostgres::delete_new_sums_tx(connection, block_number_gte).await;
 }
 
+pub struct BurnSumsRollback;
+
+#[async_trait]
+impl Analysis for BurnSumsRollback {
+    fn name(&self) -> &'static str {
+        "burn_sums"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            on_rollback(transaction, block_number_gte).await;
+        }
+    }
+}
+
 async fn expired_burn_from(
-    block_store: &impl BlockStore,
     burn_sum_store: &impl BurnSumStore,
+    connection: &mut PgConnection,
     last_burn_sum: &BurnSumRecord,
     block: &ExecutionNodeBlock,
     limited_time_frame: &LimitedTimeFrame,
-) -> Option<(BlockNumber, WeiNewtype, UsdNewtype)> {
+) -> Option<(BlockNumber, WeiNewtype, UsdDecimal, UsdDecimal)> {
     // The first included block for the next sum may have jumped forward zero or
     // more blocks. Meaning zero or more blocks are now considered expired but
     // still included for this limited time frame sum.
     let age_limit = block.timestamp - limited_time_frame.duration();
-    let first_included_block_number = block_store
-        .first_number_after_or_at(&age_limit)
-        .await
-        .expect(
-            "failed to get first block number after or at block.timestamp - limited_time_frame",
-        );
+    let first_included_block_number =
+        block_store::first_number_after_or_at(&mut *connection, &age_limit)
+            .await
+            .expect(
+                "failed to get first block number after or at block.timestamp - limited_time_frame",
+            );
 
     match first_included_block_number.cmp(&last_burn_sum.first_included_block_number) {
         Ordering::Less => {
@@ -139,7 +193,10 @@ async fn expired_burn_from(
             );
 
             let (expired_included_burn_wei, expired_included_burn_usd) = burn_sum_store
-                .burn_sum_from_block_range(&expired_block_range)
+                .burn_sum_from_block_range(connection, &expired_block_range)
+                .await;
+            let expired_included_burn_usd_twap = burn_sum_store
+                .burn_sum_usd_twap_from_block_range(connection, &expired_block_range)
                 .await;
 
             debug!(%expired_block_range, %expired_included_burn_wei, %expired_included_burn_usd, %limited_time_frame, "expired burn");
@@ -148,36 +205,50 @@ async fn expired_burn_from(
                 first_included_block_number,
                 expired_included_burn_wei,
                 expired_included_burn_usd,
+                expired_included_burn_usd_twap,
             ))
         }
     }
 }
 
 async fn calc_new_burn_sum_record_from_scratch(
-    block_store: &impl BlockStore,
     burn_sum_store: &impl BurnSumStore,
+    connection: &mut PgConnection,
     block: &ExecutionNodeBlock,
     time_frame: &TimeFrame,
 ) -> BurnSumRecord {
     debug!(%block.number, %block.hash, %time_frame, "calculating new burn sum record from scratch");
-    let range = BlockRange::from_block_and_time_frame(block_store, block, time_frame)
+    let first_included_block_number = match time_frame {
+        TimeFrame::Limited(limited_time_frame) => block_store::first_number_after_or_at(
+            &mut *connection,
+            &(block.timestamp - limited_time_frame.duration()),
+        )
         .await
-        .expect("expect blocks to be available when calculating new burn sum from scratch");
-    let (sum_wei, sum_usd) = burn_sum_store.burn_sum_from_block_range(&range).await;
+        .expect("expect blocks to be available when calculating new burn sum from scratch"),
+        TimeFrame::Growing(growing_time_frame) => growing_time_frame.start_block_number(),
+    };
+    let range = BlockRange::new(first_included_block_number, block.number);
+    let (sum_wei, sum_usd) = burn_sum_store
+        .burn_sum_from_block_range(connection, &range)
+        .await;
+    let sum_usd_twap = burn_sum_store
+        .burn_sum_usd_twap_from_block_range(connection, &range)
+        .await;
     BurnSumRecord {
         first_included_block_number: range.start,
         last_included_block_hash: block.hash.clone(),
         last_included_block_number: range.end,
         sum_wei,
         sum_usd,
+        sum_usd_twap,
         time_frame: *time_frame,
         timestamp: block.timestamp,
     }
 }
 
 async fn calc_new_burn_sum_record_from_last(
-    block_store: &impl BlockStore,
     burn_sum_store: &impl BurnSumStore,
+    connection: &mut PgConnection,
     last_burn_sum: &BurnSumRecord,
     block: &ExecutionNodeBlock,
     time_frame: &TimeFrame,
@@ -186,14 +257,17 @@ async fn calc_new_burn_sum_record_from_last(
     let new_burn_range =
         BlockRange::new(last_burn_sum.last_included_block_number + 1, block.number);
     let (new_burn_wei, new_burn_usd) = burn_sum_store
-        .burn_sum_from_block_range(&new_burn_range)
+        .burn_sum_from_block_range(connection, &new_burn_range)
+        .await;
+    let new_burn_usd_twap = burn_sum_store
+        .burn_sum_usd_twap_from_block_range(connection, &new_burn_range)
         .await;
 
     let expired_burn_sum = match time_frame {
         TimeFrame::Limited(limited_time_frame) => {
             expired_burn_from(
-                block_store,
                 burn_sum_store,
+                connection,
                 last_burn_sum,
                 block,
                 limited_time_frame,
@@ -203,21 +277,42 @@ async fn calc_new_burn_sum_record_from_last(
         TimeFrame::Growing(_) => None,
     };
 
-    let (sum_wei, sum_usd) = match expired_burn_sum {
-        Some((_, expired_burn_sum_wei, expired_burn_sum_usd)) => {
-            let sum_wei = new_burn_wei - expired_burn_sum_wei + last_burn_sum.sum_wei;
+    let (sum_wei, sum_usd, sum_usd_twap) = match expired_burn_sum {
+        Some((_, expired_burn_sum_wei, expired_burn_sum_usd, expired_burn_sum_usd_twap)) => {
+            let sum_wei = new_burn_wei
+                .checked_sub(expired_burn_sum_wei)
+                .and_then(|sum_wei| sum_wei.checked_add(last_burn_sum.sum_wei));
             let sum_usd = new_burn_usd - expired_burn_sum_usd + last_burn_sum.sum_usd;
-            (sum_wei, sum_usd)
+            let sum_usd_twap =
+                new_burn_usd_twap - expired_burn_sum_usd_twap + last_burn_sum.sum_usd_twap;
+            (sum_wei, sum_usd, sum_usd_twap)
         }
         None => {
-            let sum_wei = new_burn_wei + last_burn_sum.sum_wei;
+            let sum_wei = new_burn_wei.checked_add(last_burn_sum.sum_wei);
             let sum_usd = new_burn_usd + last_burn_sum.sum_usd;
-            (sum_wei, sum_usd)
+            let sum_usd_twap = new_burn_usd_twap + last_burn_sum.sum_usd_twap;
+            (sum_wei, sum_usd, sum_usd_twap)
+        }
+    };
+
+    // A bad stored sum or block shouldn't take down the sync, recalculating from the blocks
+    // themselves replaces whatever went wrong.
+    let sum_wei = match sum_wei {
+        Ok(sum_wei) => sum_wei,
+        Err(err) => {
+            warn!(%err, %time_frame, "failed to add burn to last sum, recalculating from scratch");
+            return calc_new_burn_sum_record_from_scratch(
+                burn_sum_store,
+                connection,
+                block,
+                time_frame,
+            )
+            .await;
         }
     };
 
     let first_included_block_number = expired_burn_sum
-        .map(|(first_included_block_number, _, _)| first_included_block_number)
+        .map(|(first_included_block_number, _, _, _)| first_included_block_number)
         // If there is no expired burn, the first included did not change.
         .unwrap_or(last_burn_sum.first_included_block_number);
 
@@ -227,22 +322,23 @@ async fn calc_new_burn_sum_record_from_last(
         last_included_block_number: new_burn_range.end,
         sum_wei,
         sum_usd,
+        sum_usd_twap,
         time_frame: *time_frame,
         timestamp: block.timestamp,
     }
 }
 
 async fn burn_sum_from_block(
-    block_store: &impl BlockStore,
     burn_sum_store: &impl BurnSumStore,
+    connection: &mut PgConnection,
     block: &ExecutionNodeBlock,
     time_frame: TimeFrame,
 ) -> BurnSumRecord {
-    match burn_sum_store.last_burn_sum(&time_frame).await {
+    match burn_sum_store.last_burn_sum(connection, &time_frame).await {
         Some(last_burn_sum) => {
             calc_new_burn_sum_record_from_last(
-                block_store,
                 burn_sum_store,
+                connection,
                 &last_burn_sum,
                 block,
                 &time_frame,
@@ -250,32 +346,136 @@ async fn burn_sum_from_block(
             .await
         }
         None => {
-            calc_new_burn_sum_record_from_scratch(block_store, burn_sum_store, block, &time_frame)
+            calc_new_burn_sum_record_from_scratch(burn_sum_store, connection, block, &time_frame)
                 .await
         }
     }
 }
 
-pub async fn on_new_block(db_pool: &PgPool, block: &ExecutionNodeBlock) -> BurnSums {
-    let block_store = BlockStorePostgres::new(db_pool.clone());
-    let burn_sum_store = BurnSumStorePostgres::new(db_pool.clone());
-
-    let futures = all::<TimeFrame>().map(|time_frame| {
-        burn_sum_from_block(&block_store, &burn_sum_store, block, time_frame)
+/// Adds the burn of the block to the last sum of every time frame, and stores the new sums. Time
+/// frames are computed one after the other, as they share the connection.
+async fn store_sums_for_block(
+    burn_sum_store: &impl BurnSumStore,
+    connection: &mut PgConnection,
+    block: &ExecutionNodeBlock,
+) -> Vec<BurnSumRecord> {
+    let mut burn_sum_records = Vec::new();
+    for time_frame in all::<TimeFrame>() {
+        let burn_sum_record = burn_sum_from_block(burn_sum_store, connection, block, time_frame)
             .timed(&format!("calc_new_burn_sum_record_{time_frame}"))
-    });
-    let burn_sum_records = join_all(futures).await;
+            .await;
+        burn_sum_records.push(burn_sum_record);
+    }
 
-    burn_sum_store.store_burn_sums(&burn_sum_records).await;
+    burn_sum_store
+        .store_burn_sums(connection, &burn_sum_records)
+        .await;
 
     // Drop old sums.
-    burn_sum_store.delete_old_sums(block.number).await;
+    burn_sum_store
+        .delete_old_sums(connection, block.number)
+        .await;
 
-    let burn_sums = burn_sums_from_vec(&burn_sum_records);
+    burn_sum_records
+}
+
+/// Expects to run in the same transaction the block was stored in.
+pub async fn on_new_block(connection: &mut PgConnection, block: &ExecutionNodeBlock) -> BurnSums {
+    let burn_sum_store = BurnSumStorePostgres;
+
+    let burn_sum_records = store_sums_for_block(&burn_sum_store, connection, block).await;
+
+    let burn_sums = burn_sums_from_vec(&burn_sum_records, config::CONFIG.burn_usd_valuation());
 
     debug!("calculated new burn sums");
 
-    caching::update_and_publish(db_pool, &CacheKey::BurnSums, &burn_sums).await;
+    caching::update_and_publish_tx(connection, &CacheKey::BurnSums, &burn_sums).await;
 
     burn_sums
 }
+
+/// The last stored sums. Returns `None` when a time frame has no sum stored yet.
+pub async fn last_burn_sums(connection: &mut PgConnection) -> Option<BurnSums> {
+    let burn_sum_store = BurnSumStorePostgres;
+
+    let mut burn_sum_records = Vec::new();
+    for time_frame in all::<TimeFrame>() {
+        let burn_sum_record = burn_sum_store
+            .last_burn_sum(connection, &time_frame)
+            .await?;
+        burn_sum_records.push(burn_sum_record);
+    }
+
+    Some(burn_sums_from_vec(
+        &burn_sum_records,
+        config::CONFIG.burn_usd_valuation(),
+    ))
+}
+
+/// Publishes the last stored sums, without taking in a new block. Returns `None` when a time frame
+/// has no sum stored yet.
+pub async fn publish_stored_sums(connection: &mut PgConnection) -> Option<BurnSums> {
+    let burn_sums = last_burn_sums(connection).await?;
+
+    caching::update_and_publish_tx(connection, &CacheKey::BurnSums, &burn_sums).await;
+
+    Some(burn_sums)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::time_frames::{GrowingTimeFrame, LimitedTimeFrame};
+
+    use super::*;
+
+    #[test]
+    fn burn_sums_serialize_test() {
+        let timestamp = "2024-03-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let burn_sums: BurnSums = all::<TimeFrame>()
+            .map(|time_frame| {
+                let burn_sum = BurnSum {
+                    block_number: BlockNumber(1),
+                    sum: EthUsdAmount {
+                        eth: EthNewtype(1.0),
+                        usd: UsdNewtype(3000.0),
+                    },
+                    timestamp,
+                };
+                (time_frame, burn_sum)
+            })
+            .collect();
+
+        // The frontend reads these keys, adding a time frame adds a key, renaming breaks it.
+        let json = serde_json::to_value(&burn_sums).unwrap();
+        let mut keys = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "d1",
+                "d30",
+                "d7",
+                "h1",
+                "m5",
+                "since_burn",
+                "since_merge",
+                "since_shapella"
+            ]
+        );
+        assert_eq!(json["d1"]["sum"]["eth"], 1.0);
+
+        assert_eq!(
+            burn_sums[TimeFrame::Growing(GrowingTimeFrame::SinceMerge)].block_number,
+            BlockNumber(1)
+        );
+        assert_eq!(
+            burn_sums[TimeFrame::Limited(LimitedTimeFrame::Minute5)].timestamp,
+            timestamp
+        );
+    }
+}
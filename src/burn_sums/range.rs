@@ -0,0 +1,152 @@
+//! Burn sums over arbitrary ranges, computed directly from `blocks_next`. Meant for analysts
+//! asking questions like "how much was burned in March", which don't map to a time frame.
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::{
+    db,
+    execution_chain::{BlockNumber, BlockRange},
+    log,
+};
+
+use super::{
+    store::{BurnSumStore, BurnSumStorePostgres},
+    EthUsdAmount,
+};
+
+/// Block ranges include both the first and last block. Time ranges include the start but exclude
+/// the end, so summing consecutive months never counts a block twice.
+#[derive(Debug, Clone)]
+pub enum BurnSumRange {
+    Blocks(BlockRange),
+    Time {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+}
+
+pub async fn burn_sum_from_range(db_pool: &PgPool, range: &BurnSumRange) -> EthUsdAmount {
+    let burn_sum_store = BurnSumStorePostgres;
+    let mut connection = db_pool.acquire().await.unwrap();
+
+    let (wei, usd) = match range {
+        BurnSumRange::Blocks(block_range) => {
+            burn_sum_store
+                .burn_sum_from_block_range(&mut connection, block_range)
+                .await
+        }
+        BurnSumRange::Time { start, end } => {
+            burn_sum_store
+                .burn_sum_from_time_range(&mut connection, start, end)
+                .await
+        }
+    };
+
+    EthUsdAmount {
+        eth: wei.into(),
+        usd: usd.into(),
+    }
+}
+
+enum Bound {
+    BlockNumber(BlockNumber),
+    Timestamp(DateTime<Utc>),
+}
+
+/// Accepts a block number, a date like 2024-03-01, or an RFC 3339 timestamp.
+fn parse_bound(str: &str) -> Result<Bound> {
+    if let Ok(block_number) = str.parse::<BlockNumber>() {
+        return Ok(Bound::BlockNumber(block_number));
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(str, "%Y-%m-%d") {
+        let timestamp = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+        return Ok(Bound::Timestamp(timestamp));
+    }
+
+    str.parse::<DateTime<Utc>>()
+        .map(Bound::Timestamp)
+        .map_err(|_| anyhow!("expected block number, date or timestamp, got {str}"))
+}
+
+pub fn parse_range(start: &str, end: &str) -> Result<BurnSumRange> {
+    match (parse_bound(start)?, parse_bound(end)?) {
+        (Bound::BlockNumber(start), Bound::BlockNumber(end)) => {
+            if start > end {
+                bail!("start block {start} is after end block {end}");
+            }
+            Ok(BurnSumRange::Blocks(BlockRange::new(start, end)))
+        }
+        (Bound::Timestamp(start), Bound::Timestamp(end)) => {
+            if start > end {
+                bail!("start {start} is after end {end}");
+            }
+            Ok(BurnSumRange::Time { start, end })
+        }
+        _ => bail!("start and end should both be block numbers, or both be dates or timestamps"),
+    }
+}
+
+pub async fn sum_burn() {
+    let args = std::env::args().collect::<Vec<String>>();
+    let (start, end) = match (args.get(1), args.get(2)) {
+        (Some(start), Some(end)) => (start, end),
+        _ => panic!("usage: sum-burn <start> <end>, where both are block numbers, or both are dates or timestamps"),
+    };
+    sum_burn_between(start, end).await;
+}
+
+pub async fn sum_burn_between(start: &str, end: &str) {
+    log::init_with_env();
+
+    let range = parse_range(start, end).unwrap();
+
+    let db_pool = db::get_read_db_pool("sum-burn").await;
+
+    let burn_sum = burn_sum_from_range(&db_pool, &range).await;
+
+    info!(?range, eth = %burn_sum.eth, usd = %burn_sum.usd, "summed burn");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_block_range_test() {
+        let range = parse_range("100", "200").unwrap();
+        assert!(matches!(
+            range,
+            BurnSumRange::Blocks(BlockRange {
+                start: BlockNumber(100),
+                end: BlockNumber(200)
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_date_range_test() {
+        let range = parse_range("2024-03-01", "2024-04-01T00:00:00Z").unwrap();
+        match range {
+            BurnSumRange::Time { start, end } => {
+                assert_eq!(
+                    start,
+                    "2024-03-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+                );
+                assert_eq!(
+                    end,
+                    "2024-04-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+                );
+            }
+            BurnSumRange::Blocks(_) => panic!("expected time range"),
+        }
+    }
+
+    #[test]
+    fn parse_mixed_range_test() {
+        assert!(parse_range("100", "2024-04-01").is_err());
+        assert!(parse_range("200", "100").is_err());
+    }
+}
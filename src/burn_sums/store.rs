@@ -1,91 +1,225 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use sqlx::{PgConnection, PgPool};
+use sqlx::PgConnection;
 use tracing::debug;
 
 use crate::{
     execution_chain::{BlockNumber, BlockRange},
     time_frames::TimeFrame,
-    units::{UsdNewtype, WeiNewtype},
+    units::{UsdDecimal, WeiNewtype},
 };
 
 use super::BurnSumRecord;
 
-const REORG_LIMIT: i32 = 100;
+/// Sums this many blocks older than the last block are dropped, only those may still be rolled
+/// back to.
+pub const REORG_LIMIT: i64 = 100;
 
 #[async_trait]
 pub trait BurnSumStore {
-    async fn burn_sum_from_block_range(&self, block_range: &BlockRange)
-        -> (WeiNewtype, UsdNewtype);
-    async fn delete_old_sums(&self, last_block: BlockNumber);
-    async fn last_burn_sum(&self, time_frame: &TimeFrame) -> Option<BurnSumRecord>;
-    async fn store_burn_sums(&self, burn_sum: &[BurnSumRecord]);
+    async fn burn_sum_from_block_range(
+        &self,
+        connection: &mut PgConnection,
+        block_range: &BlockRange,
+    ) -> (WeiNewtype, UsdDecimal);
+    async fn burn_sum_usd_twap_from_block_range(
+        &self,
+        connection: &mut PgConnection,
+        block_range: &BlockRange,
+    ) -> UsdDecimal;
+    async fn burn_sum_from_time_range(
+        &self,
+        connection: &mut PgConnection,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> (WeiNewtype, UsdDecimal);
+    async fn burn_sums(&self, connection: &mut PgConnection) -> Vec<BurnSumRecord>;
+    async fn delete_old_sums(&self, connection: &mut PgConnection, last_block: BlockNumber);
+    async fn last_burn_sum(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+    ) -> Option<BurnSumRecord>;
+    async fn store_burn_sums(&self, connection: &mut PgConnection, burn_sum: &[BurnSumRecord]);
+    async fn update_burn_sum(&self, connection: &mut PgConnection, burn_sum: &BurnSumRecord);
     async fn delete_new_sums_tx<'a>(
         transaction: &'a mut PgConnection,
         block_number_gte: &'a BlockNumber,
     );
 }
 
-pub struct BurnSumStorePostgres {
-    db_pool: PgPool,
-}
-
-impl BurnSumStorePostgres {
-    pub fn new(db_pool: PgPool) -> Self {
-        Self { db_pool }
-    }
-}
+pub struct BurnSumStorePostgres;
 
 #[async_trait]
 impl BurnSumStore for BurnSumStorePostgres {
     async fn burn_sum_from_block_range(
         &self,
+        connection: &mut PgConnection,
         block_range: &BlockRange,
-    ) -> (WeiNewtype, UsdNewtype) {
+    ) -> (WeiNewtype, UsdDecimal) {
         let row = sqlx::query!(
             r#"
             SELECT
-                SUM(base_fee_per_gas::NUMERIC(78) * gas_used::NUMERIC(78))::TEXT
-                    AS "burn_sum_wei!",
-                SUM(base_fee_per_gas::NUMERIC(78) * gas_used::NUMERIC(78) / 1e18 * eth_price)
-                    AS "burn_sum_usd!"
+                COALESCE(SUM(
+                    base_fee_per_gas::NUMERIC(78) * gas_used::NUMERIC(78)
+                    + COALESCE(blob_base_fee::NUMERIC(78) * blob_gas_used::NUMERIC(78), 0)
+                ), 0) AS "burn_sum_wei!: WeiNewtype",
+                COALESCE(SUM(
+                    (
+                        base_fee_per_gas::NUMERIC(78) * gas_used::NUMERIC(78)
+                        + COALESCE(blob_base_fee::NUMERIC(78) * blob_gas_used::NUMERIC(78), 0)
+                    ) / 1e18 * eth_price::NUMERIC
+                ), 0)::NUMERIC(28, 8) AS "burn_sum_usd!: UsdDecimal"
             FROM
                 blocks_next
             WHERE
                 number >= $1 AND number <= $2
             "#,
-            block_range.start,
-            block_range.end
+            block_range.start.0,
+            block_range.end.0
         )
-        .fetch_one(&self.db_pool)
+        .fetch_one(&mut *connection)
         .await
         .unwrap();
 
-        let wei = row.burn_sum_wei.parse().unwrap();
-        let usd = row.burn_sum_usd.into();
+        let wei = row.burn_sum_wei;
+        let usd = row.burn_sum_usd;
 
         (wei, usd)
     }
 
-    async fn delete_old_sums(&self, last_block: BlockNumber) {
+    /// Values the burn of each block with the average of the open, high, low and close of the
+    /// candle of the minute before its own. The block's own minute is usually still open when we
+    /// sum it, and its candle changes until the minute ends, only closed candles give a sum that
+    /// stays right. Blocks without such a candle are valued with their own price.
+    async fn burn_sum_usd_twap_from_block_range(
+        &self,
+        connection: &mut PgConnection,
+        block_range: &BlockRange,
+    ) -> UsdDecimal {
+        sqlx::query!(
+            r#"
+            SELECT
+                COALESCE(SUM(
+                    (
+                        base_fee_per_gas::NUMERIC(78) * gas_used::NUMERIC(78)
+                        + COALESCE(blob_base_fee::NUMERIC(78) * blob_gas_used::NUMERIC(78), 0)
+                    ) / 1e18 * COALESCE(
+                        (eth_candles.open + eth_candles.high + eth_candles.low + eth_candles.close)
+                            / 4,
+                        blocks_next.eth_price
+                    )::NUMERIC
+                ), 0)::NUMERIC(28, 8) AS "burn_sum_usd_twap!: UsdDecimal"
+            FROM
+                blocks_next
+            LEFT JOIN eth_candles ON
+                eth_candles.resolution = 'm1'
+                AND eth_candles.timestamp
+                    = DATE_TRUNC('minute', blocks_next.timestamp) - INTERVAL '1 minute'
+            WHERE
+                number >= $1 AND number <= $2
+            "#,
+            block_range.start.0,
+            block_range.end.0
+        )
+        .fetch_one(&mut *connection)
+        .await
+        .unwrap()
+        .burn_sum_usd_twap
+    }
+
+    /// Sums the burn of blocks with start <= timestamp < end.
+    async fn burn_sum_from_time_range(
+        &self,
+        connection: &mut PgConnection,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> (WeiNewtype, UsdDecimal) {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                COALESCE(SUM(
+                    base_fee_per_gas::NUMERIC(78) * gas_used::NUMERIC(78)
+                    + COALESCE(blob_base_fee::NUMERIC(78) * blob_gas_used::NUMERIC(78), 0)
+                ), 0) AS "burn_sum_wei!: WeiNewtype",
+                COALESCE(SUM(
+                    (
+                        base_fee_per_gas::NUMERIC(78) * gas_used::NUMERIC(78)
+                        + COALESCE(blob_base_fee::NUMERIC(78) * blob_gas_used::NUMERIC(78), 0)
+                    ) / 1e18 * eth_price::NUMERIC
+                ), 0)::NUMERIC(28, 8) AS "burn_sum_usd!: UsdDecimal"
+            FROM
+                blocks_next
+            WHERE
+                timestamp >= $1 AND timestamp < $2
+            "#,
+            start,
+            end
+        )
+        .fetch_one(&mut *connection)
+        .await
+        .unwrap();
+
+        (row.burn_sum_wei, row.burn_sum_usd)
+    }
+
+    /// Returns all stored sums, for every time frame.
+    async fn burn_sums(&self, connection: &mut PgConnection) -> Vec<BurnSumRecord> {
+        sqlx::query!(
+            r#"
+            SELECT
+                time_frame,
+                first_included_block_number,
+                last_included_block_number,
+                last_included_block_hash,
+                timestamp,
+                sum_usd AS "sum_usd: UsdDecimal",
+                sum_usd_twap AS "sum_usd_twap: UsdDecimal",
+                sum_wei AS "sum_wei: WeiNewtype"
+            FROM burn_sums
+            ORDER BY last_included_block_number ASC, time_frame ASC
+            "#,
+        )
+        .fetch_all(&mut *connection)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| BurnSumRecord {
+            first_included_block_number: BlockNumber(row.first_included_block_number),
+            last_included_block_hash: row.last_included_block_hash,
+            last_included_block_number: BlockNumber(row.last_included_block_number),
+            sum_usd: row.sum_usd,
+            sum_usd_twap: row.sum_usd_twap,
+            sum_wei: row.sum_wei,
+            time_frame: row.time_frame.parse().unwrap(),
+            timestamp: row.timestamp,
+        })
+        .collect()
+    }
+
+    async fn delete_old_sums(&self, connection: &mut PgConnection, last_block: BlockNumber) {
         let block_number_limit = last_block - REORG_LIMIT;
 
-        debug!(block_number_limit, "deleting old sums");
+        debug!(%block_number_limit, "deleting old sums");
 
         sqlx::query!(
             "
             DELETE FROM burn_sums
             WHERE last_included_block_number < $1
             ",
-            block_number_limit
+            block_number_limit.0
         )
-        .execute(&self.db_pool)
+        .execute(&mut *connection)
         .await
         .unwrap();
     }
 
     /// Returns the last sum which relates to a canonical block.
-    async fn last_burn_sum(&self, time_frame: &TimeFrame) -> Option<BurnSumRecord> {
+    async fn last_burn_sum(
+        &self,
+        connection: &mut PgConnection,
+        time_frame: &TimeFrame,
+    ) -> Option<BurnSumRecord> {
         let row = sqlx::query!(
             r#"
             SELECT
@@ -93,8 +227,9 @@ impl BurnSumStore for BurnSumStorePostgres {
                 last_included_block_number,
                 last_included_block_hash,
                 timestamp,
-                sum_usd,
-                sum_wei::TEXT AS "sum_wei!"
+                sum_usd AS "sum_usd: UsdDecimal",
+                sum_usd_twap AS "sum_usd_twap: UsdDecimal",
+                sum_wei AS "sum_wei: WeiNewtype"
             FROM burn_sums
             WHERE time_frame = $1
             ORDER BY last_included_block_number DESC
@@ -102,37 +237,40 @@ impl BurnSumStore for BurnSumStorePostgres {
             "#,
             time_frame.to_string()
         )
-        .fetch_optional(&self.db_pool)
+        .fetch_optional(&mut *connection)
         .await
         .unwrap();
 
         row.map(|row| BurnSumRecord {
-            first_included_block_number: row.first_included_block_number,
+            first_included_block_number: BlockNumber(row.first_included_block_number),
             last_included_block_hash: row.last_included_block_hash,
-            last_included_block_number: row.last_included_block_number,
-            sum_usd: row.sum_usd.into(),
-            sum_wei: row.sum_wei.parse().unwrap(),
+            last_included_block_number: BlockNumber(row.last_included_block_number),
+            sum_usd: row.sum_usd,
+            sum_usd_twap: row.sum_usd_twap,
+            sum_wei: row.sum_wei,
             time_frame: *time_frame,
             timestamp: row.timestamp,
         })
     }
 
-    async fn store_burn_sums(&self, burn_sum: &[BurnSumRecord]) {
+    async fn store_burn_sums(&self, connection: &mut PgConnection, burn_sum: &[BurnSumRecord]) {
         let mut v1: Vec<String> = Vec::new();
         let mut v2: Vec<BlockNumber> = Vec::new();
         let mut v3: Vec<BlockNumber> = Vec::new();
         let mut v4: Vec<String> = Vec::new();
         let mut v5: Vec<DateTime<Utc>> = Vec::new();
-        let mut v6: Vec<f64> = Vec::new();
-        let mut v7: Vec<String> = Vec::new();
+        let mut v6: Vec<UsdDecimal> = Vec::new();
+        let mut v7: Vec<WeiNewtype> = Vec::new();
+        let mut v8: Vec<UsdDecimal> = Vec::new();
         burn_sum.iter().for_each(|burn_sum| {
             v1.push(burn_sum.time_frame.to_string());
             v2.push(burn_sum.first_included_block_number);
             v3.push(burn_sum.last_included_block_number);
             v4.push(burn_sum.last_included_block_hash.to_owned());
             v5.push(burn_sum.timestamp);
-            v6.push(burn_sum.sum_usd.0);
-            v7.push(Into::<String>::into(burn_sum.sum_wei) as String);
+            v6.push(burn_sum.sum_usd);
+            v7.push(burn_sum.sum_wei);
+            v8.push(burn_sum.sum_usd_twap);
         });
         sqlx::query!(
             "
@@ -143,27 +281,54 @@ impl BurnSumStore for BurnSumStorePostgres {
                 last_included_block_hash,
                 timestamp,
                 sum_usd,
-                sum_wei
+                sum_wei,
+                sum_usd_twap
             )
             SELECT * FROM UNNEST (
                 $1::text[],
-                $2::int[],
-                $3::int[],
+                $2::int8[],
+                $3::int8[],
                 $4::text[],
                 $5::timestamptz[],
-                $6::float8[],
-                $7::numeric[]
+                $6::numeric[],
+                $7::numeric[],
+                $8::numeric[]
             )
             ",
             &v1,
-            &v2,
-            &v3,
+            &v2 as &[BlockNumber],
+            &v3 as &[BlockNumber],
             &v4,
             &v5,
-            &v6,
-            &v7 as &[String]
+            &v6 as &[UsdDecimal],
+            &v7 as &[WeiNewtype],
+            &v8 as &[UsdDecimal]
         )
-        .execute(&self.db_pool)
+        .execute(&mut *connection)
+        .await
+        .unwrap();
+    }
+
+    async fn update_burn_sum(&self, connection: &mut PgConnection, burn_sum: &BurnSumRecord) {
+        sqlx::query!(
+            "
+            UPDATE burn_sums
+            SET
+                first_included_block_number = $3,
+                sum_usd = $4,
+                sum_wei = $5,
+                sum_usd_twap = $6
+            WHERE time_frame = $1
+            AND last_included_block_number = $2
+            ",
+            burn_sum.time_frame.to_string(),
+            burn_sum.last_included_block_number.0,
+            burn_sum.first_included_block_number.0,
+            burn_sum.sum_usd as UsdDecimal,
+            burn_sum.sum_wei as WeiNewtype,
+            burn_sum.sum_usd_twap as UsdDecimal
+        )
+        .execute(&mut *connection)
         .await
         .unwrap();
     }
@@ -179,7 +344,7 @@ impl BurnSumStore for BurnSumStorePostgres {
             WHERE
                 last_included_block_number >= $1
             ",
-            block_number_gte
+            block_number_gte.0
         )
         .execute(transaction)
         .await
@@ -189,6 +354,7 @@ impl BurnSumStore for BurnSumStorePostgres {
 
 #[cfg(test)]
 mod tests {
+    use rust_decimal::Decimal;
     use test_context::test_context;
 
     use crate::{
@@ -201,7 +367,8 @@ mod tests {
     #[test_context(TestDb)]
     #[tokio::test]
     async fn burn_sum_from_block_range_test(test_db: &TestDb) {
-        let burn_sum_store = BurnSumStorePostgres::new(test_db.pool.clone());
+        let burn_sum_store = BurnSumStorePostgres;
+        let mut connection = test_db.pool.acquire().await.unwrap();
 
         let test_id = "burn_sum_from_time_frame";
 
@@ -212,17 +379,109 @@ mod tests {
             .with_burn(WeiNewtype::from_eth(2))
             .build();
 
-        block_store::store_block(&test_db.pool, &block_1, 1.0).await;
-        block_store::store_block(&test_db.pool, &block_2, 2.0).await;
+        block_store::store_block(&mut *connection, &block_1, 1.0).await;
+        block_store::store_block(&mut *connection, &block_2, 2.0).await;
 
         let (burn_sum_wei, burn_sum_usd) = burn_sum_store
-            .burn_sum_from_block_range(&BlockRange {
-                start: block_1.number,
-                end: block_2.number,
-            })
+            .burn_sum_from_block_range(
+                &mut connection,
+                &BlockRange {
+                    start: block_1.number,
+                    end: block_2.number,
+                },
+            )
             .await;
 
         assert_eq!(burn_sum_wei, WeiNewtype::from_eth(3));
-        assert_eq!(burn_sum_usd, UsdNewtype(5.0));
+        assert_eq!(burn_sum_usd, UsdDecimal(Decimal::new(5, 0)));
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn burn_sum_from_empty_block_range_test(test_db: &TestDb) {
+        let burn_sum_store = BurnSumStorePostgres;
+        let mut connection = test_db.pool.acquire().await.unwrap();
+
+        let (burn_sum_wei, burn_sum_usd) = burn_sum_store
+            .burn_sum_from_block_range(
+                &mut connection,
+                &BlockRange::new(BlockNumber(0), BlockNumber(10)),
+            )
+            .await;
+
+        assert_eq!(burn_sum_wei, WeiNewtype(0));
+        assert_eq!(burn_sum_usd, UsdDecimal(Decimal::new(0, 0)));
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn burn_sum_usd_twap_from_block_range_test(test_db: &TestDb) {
+        let burn_sum_store = BurnSumStorePostgres;
+        let mut connection = test_db.pool.acquire().await.unwrap();
+
+        // Twelve seconds apart, in different minutes.
+        let block_1 = ExecutionNodeBlockBuilder::new("burn_sum_usd_twap_from_block_range")
+            .with_burn(WeiNewtype::from_eth(1))
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_burn(WeiNewtype::from_eth(2))
+            .build();
+
+        block_store::store_block(&mut *connection, &block_1, 1.0).await;
+        block_store::store_block(&mut *connection, &block_2, 2.0).await;
+
+        // Only the minute before the first block's has a candle, its average is 3.
+        sqlx::query!(
+            "
+            INSERT INTO eth_candles (resolution, timestamp, open, high, low, close)
+            VALUES (
+                'm1',
+                DATE_TRUNC('minute', $1::timestamptz) - INTERVAL '1 minute',
+                2.0, 4.0, 2.0, 4.0
+            )
+            ",
+            block_1.timestamp
+        )
+        .execute(&mut *connection)
+        .await
+        .unwrap();
+
+        let burn_sum_usd_twap = burn_sum_store
+            .burn_sum_usd_twap_from_block_range(
+                &mut connection,
+                &BlockRange::new(block_1.number, block_2.number),
+            )
+            .await;
+
+        assert_eq!(burn_sum_usd_twap, UsdDecimal(Decimal::new(7, 0)));
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn burn_sum_from_time_range_test(test_db: &TestDb) {
+        let burn_sum_store = BurnSumStorePostgres;
+        let mut connection = test_db.pool.acquire().await.unwrap();
+
+        let block_1 = ExecutionNodeBlockBuilder::new("burn_sum_from_time_range")
+            .with_burn(WeiNewtype::from_eth(1))
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_burn(WeiNewtype::from_eth(2))
+            .build();
+
+        block_store::store_block(&mut *connection, &block_1, 1.0).await;
+        block_store::store_block(&mut *connection, &block_2, 2.0).await;
+
+        // The end is exclusive, block_2 falls outside the range.
+        let (burn_sum_wei, burn_sum_usd) = burn_sum_store
+            .burn_sum_from_time_range(&mut connection, &block_1.timestamp, &block_2.timestamp)
+            .await;
+        assert_eq!(burn_sum_wei, WeiNewtype::from_eth(1));
+        assert_eq!(burn_sum_usd, UsdDecimal(Decimal::new(1, 0)));
+
+        let (burn_sum_wei, _) = burn_sum_store
+            .burn_sum_from_time_range(&mut connection, &block_2.timestamp, &block_1.timestamp)
+            .await;
+        assert_eq!(burn_sum_wei, WeiNewtype(0));
     }
 }
@@ -0,0 +1,171 @@
+//! Burn sums are computed incrementally, adding new burn and subtracting expired burn. Small
+//! mistakes in that logic compound silently. Here we recompute every stored sum from raw block
+//! data and report, or repair, any that diverge.
+use rust_decimal::Decimal;
+use sqlx::PgConnection;
+use tracing::{error, info};
+
+use crate::{
+    db,
+    execution_chain::{block_store, BlockRange},
+    log,
+    time_frames::TimeFrame,
+};
+
+use super::{
+    store::{BurnSumStore, BurnSumStorePostgres},
+    BurnSumRecord,
+};
+
+// Every USD sum we add or subtract is rounded to 8 decimals, summed in a different order they
+// differ slightly.
+const USD_TOLERANCE: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+
+/// Returns the correct record when the stored one diverges from the raw block data.
+async fn verify_burn_sum(
+    burn_sum_store: &impl BurnSumStore,
+    connection: &mut PgConnection,
+    burn_sum: &BurnSumRecord,
+) -> Option<BurnSumRecord> {
+    let first_included_block_number = match burn_sum.time_frame {
+        TimeFrame::Growing(growing_time_frame) => growing_time_frame.start_block_number(),
+        TimeFrame::Limited(limited_time_frame) => block_store::first_number_after_or_at(
+            &mut *connection,
+            &(burn_sum.timestamp - limited_time_frame.duration()),
+        )
+        .await
+        .expect("expect first included block to be stored for stored burn sum"),
+    };
+
+    let block_range = BlockRange::new(
+        first_included_block_number,
+        burn_sum.last_included_block_number,
+    );
+    let (sum_wei, sum_usd) = burn_sum_store
+        .burn_sum_from_block_range(connection, &block_range)
+        .await;
+    let sum_usd_twap = burn_sum_store
+        .burn_sum_usd_twap_from_block_range(connection, &block_range)
+        .await;
+
+    let is_valid = first_included_block_number == burn_sum.first_included_block_number
+        && sum_wei == burn_sum.sum_wei
+        && (sum_usd.0 - burn_sum.sum_usd.0).abs() <= USD_TOLERANCE
+        && (sum_usd_twap.0 - burn_sum.sum_usd_twap.0).abs() <= USD_TOLERANCE;
+
+    if is_valid {
+        None
+    } else {
+        Some(BurnSumRecord {
+            first_included_block_number,
+            last_included_block_hash: burn_sum.last_included_block_hash.clone(),
+            last_included_block_number: burn_sum.last_included_block_number,
+            sum_usd,
+            sum_usd_twap,
+            sum_wei,
+            time_frame: burn_sum.time_frame,
+            timestamp: burn_sum.timestamp,
+        })
+    }
+}
+
+pub async fn verify_burn_sums() {
+    let should_repair = std::env::args().any(|arg| arg == "--repair");
+    verify_burn_sums_with_repair(should_repair).await;
+}
+
+pub async fn verify_burn_sums_with_repair(should_repair: bool) {
+    log::init_with_env();
+
+    info!(should_repair, "verifying burn sums");
+
+    let db_pool = db::get_db_pool("verify-burn-sums").await;
+    let burn_sum_store = BurnSumStorePostgres;
+    let mut connection = db_pool.acquire().await.unwrap();
+
+    let burn_sums = burn_sum_store.burn_sums(&mut connection).await;
+
+    let mut divergent_count = 0;
+    for burn_sum in burn_sums.iter() {
+        if let Some(expected) = verify_burn_sum(&burn_sum_store, &mut connection, burn_sum).await {
+            divergent_count += 1;
+
+            error!(
+                time_frame = %burn_sum.time_frame,
+                last_included_block_number = %burn_sum.last_included_block_number,
+                stored_first_included_block_number = %burn_sum.first_included_block_number,
+                expected_first_included_block_number = %expected.first_included_block_number,
+                stored_sum_wei = %burn_sum.sum_wei,
+                expected_sum_wei = %expected.sum_wei,
+                stored_sum_usd = %burn_sum.sum_usd,
+                expected_sum_usd = %expected.sum_usd,
+                stored_sum_usd_twap = %burn_sum.sum_usd_twap,
+                expected_sum_usd_twap = %expected.sum_usd_twap,
+                "burn sum diverges from block data"
+            );
+
+            if should_repair {
+                burn_sum_store
+                    .update_burn_sum(&mut connection, &expected)
+                    .await;
+            }
+        }
+    }
+
+    info!(
+        checked_count = burn_sums.len(),
+        divergent_count, "done verifying burn sums"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::ExecutionNodeBlockBuilder,
+        time_frames::LimitedTimeFrame,
+        units::{UsdDecimal, WeiNewtype},
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn verify_burn_sum_test(test_db: &TestDb) {
+        let burn_sum_store = BurnSumStorePostgres;
+
+        let block_1 = ExecutionNodeBlockBuilder::new("verify_burn_sum")
+            .with_burn(WeiNewtype::from_eth(1))
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_burn(WeiNewtype::from_eth(2))
+            .build();
+
+        block_store::store_block(&test_db.pool, &block_1, 1.0).await;
+        block_store::store_block(&test_db.pool, &block_2, 1.0).await;
+
+        let mut connection = test_db.pool.acquire().await.unwrap();
+
+        let mut burn_sum = BurnSumRecord {
+            first_included_block_number: block_1.number,
+            last_included_block_hash: block_2.hash.clone(),
+            last_included_block_number: block_2.number,
+            sum_usd: UsdDecimal(Decimal::new(3, 0)),
+            sum_usd_twap: UsdDecimal(Decimal::new(3, 0)),
+            sum_wei: WeiNewtype::from_eth(3),
+            time_frame: TimeFrame::Limited(LimitedTimeFrame::Day1),
+            timestamp: block_2.timestamp,
+        };
+
+        let expected = verify_burn_sum(&burn_sum_store, &mut connection, &burn_sum).await;
+        assert!(expected.is_none());
+
+        burn_sum.sum_wei = WeiNewtype::from_eth(4);
+        let expected = verify_burn_sum(&burn_sum_store, &mut connection, &burn_sum)
+            .await
+            .unwrap();
+        assert_eq!(expected.sum_wei, WeiNewtype::from_eth(3));
+    }
+}
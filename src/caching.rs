@@ -1,18 +1,29 @@
-use std::{fmt::Display, str::FromStr};
+#[cfg(feature = "redis")]
+mod redis_store;
+
+use std::{fmt::Display, str::FromStr, sync::Arc};
 
 use anyhow::Result;
+use async_trait::async_trait;
 use enum_iterator::Sequence;
+use futures::{stream::BoxStream, StreamExt};
 use serde::Serialize;
 use serde_json::Value;
-use sqlx::{PgExecutor, PgPool};
+use sqlx::{PgConnection, PgExecutor, PgPool};
 use thiserror::Error;
-use tracing::debug;
+use tracing::{debug, trace};
 
 use crate::{
-    key_value_store::{self, KeyValueStore},
+    config, db,
+    key_value_store::{self, KeyValueStore, KeyValueStorePostgres},
     time_frames::{GrowingTimeFrame, LimitedTimeFrame, TimeFrame},
 };
 
+#[cfg(feature = "redis")]
+pub use redis_store::{relay_cache_updates_to_redis, CacheStoreRedis};
+
+const CACHE_UPDATE_CHANNEL: &str = "cache-update";
+
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Sequence)]
 pub enum CacheKey {
     AverageEthPrice,
@@ -21,26 +32,86 @@ pub enum CacheKey {
     BaseFeePerGasBarrier,
     BaseFeePerGasStats,
     BaseFeePerGasStatsTimeFrame(TimeFrame),
+    BlobTransactions,
+    BlobUsage,
     BlockLag,
+    BtcPrice,
+    BuilderCensorship,
+    BurnAnomalies,
+    BurnMovingAverages,
     BurnRates,
+    BurnRatesOverTime,
+    BurnRecords,
     BurnSums,
+    ChainHealth,
+    CombinedFees,
+    ConsolidationsByDay,
+    ContractDeployments,
+    DailySupplyDeltas,
     EffectiveBalanceSum,
     EthPrice,
+    EthPriceSources,
+    EthPriceStats,
+    FeeSuggestion,
+    GasUtilization,
     GaugeRates,
+    GraffitiCounts,
+    HealthScore,
     SupplyParts,
     IssuanceBreakdown,
     IssuanceEstimate,
+    IssuanceMovingAverages,
+    L2Fees,
+    MarketCaps,
+    MarketCapsOverTime,
+    NextBaseFee,
+    PendingDeposits,
+    PowSupplySinceMerge,
+    ProposerLeaderboard,
     SupplyChanges,
     SupplyDashboardAnalysis,
+    SupplyMilestones,
     SupplyOverTime,
     SupplyProjectionInputs,
     SupplySinceMerge,
     TotalDifficultyProgress,
+    TransactionStats,
+    TransactionStatsByDay,
     ValidatorRewards,
 }
 
+/// Splits an optional version suffix off a stored key, e.g. `burn-sums-v2`. Keys without one are
+/// version 1.
+fn split_version(key: &str) -> (&str, u32) {
+    key.rsplit_once("-v")
+        .and_then(|(name, version)| version.parse().ok().map(|version| (name, version)))
+        .unwrap_or((key, 1))
+}
+
 impl CacheKey {
-    pub fn to_db_key(self) -> &'static str {
+    /// Bump when the shape of a value changes in a way older readers can't handle. The new shape
+    /// is then stored under its own key, e.g. `burn-sums-v2`, next to the previous one. To roll
+    /// out without breaking older frontends, keep writing the previous shape with
+    /// `update_and_publish_version` until nothing reads it anymore.
+    pub fn version(self) -> u32 {
+        // No value has changed shape yet.
+        1
+    }
+
+    /// The key the current version of this value is stored under.
+    pub fn to_db_key(self) -> String {
+        self.to_db_key_for_version(self.version())
+    }
+
+    /// Version 1 keys carry no suffix, they predate versioning.
+    pub fn to_db_key_for_version(self, version: u32) -> String {
+        match version {
+            1 => self.name().to_string(),
+            version => format!("{}-v{version}", self.name()),
+        }
+    }
+
+    fn name(self) -> &'static str {
         use CacheKey::*;
         use GrowingTimeFrame::*;
         use LimitedTimeFrame::*;
@@ -55,27 +126,58 @@ impl CacheKey {
             BaseFeePerGasStatsTimeFrame(time_frame) => match time_frame {
                 Growing(SinceBurn) => "base-fee-per-gas-stats-since_burn",
                 Growing(SinceMerge) => "base-fee-per-gas-stats-since_merge",
+                Growing(SinceShapella) => "base-fee-per-gas-stats-since_shapella",
                 Limited(Minute5) => "base-fee-per-gas-stats-m5",
                 Limited(Hour1) => "base-fee-per-gas-stats-h1",
                 Limited(Day1) => "base-fee-per-gas-stats-d1",
                 Limited(Day7) => "base-fee-per-gas-stats-d7",
                 Limited(Day30) => "base-fee-per-gas-stats-d30",
             },
+            BlobTransactions => "blob-transactions",
+            BlobUsage => "blob-usage",
             BlockLag => "block-lag",
+            BtcPrice => "btc-price",
+            BuilderCensorship => "builder-censorship",
+            BurnAnomalies => "burn-anomalies",
+            BurnMovingAverages => "burn-moving-averages",
             BurnRates => "burn-rates",
+            BurnRatesOverTime => "burn-rates-over-time",
+            BurnRecords => "burn-records",
             BurnSums => "burn-sums",
+            ChainHealth => "chain-health",
+            CombinedFees => "combined-fees",
+            ConsolidationsByDay => "consolidations-by-day",
+            ContractDeployments => "contract-deployments",
+            DailySupplyDeltas => "daily-supply-deltas",
             EffectiveBalanceSum => "effective-balance-sum",
             EthPrice => "eth-price",
+            EthPriceSources => "eth-price-sources",
+            EthPriceStats => "eth-price-time-frame-stats",
+            FeeSuggestion => "fee-suggestion",
+            GasUtilization => "gas-utilization",
             GaugeRates => "gauge-rates",
+            GraffitiCounts => "graffiti-counts",
+            HealthScore => "health-score",
             IssuanceBreakdown => "issuance-breakdown",
             IssuanceEstimate => "issuance-estimate",
+            IssuanceMovingAverages => "issuance-moving-averages",
+            L2Fees => "l2-fees",
+            MarketCaps => "market-caps",
+            MarketCapsOverTime => "market-caps-over-time",
+            NextBaseFee => "next-base-fee",
+            PendingDeposits => "pending-deposits",
+            PowSupplySinceMerge => "pow-supply-since-merge",
+            ProposerLeaderboard => "proposer-leaderboard",
             SupplyChanges => "supply-changes",
             SupplyDashboardAnalysis => "supply-dashboard-analysis",
+            SupplyMilestones => "supply-milestones",
             SupplyOverTime => "supply-over-time",
             SupplyParts => "supply-parts",
             SupplyProjectionInputs => "supply-projection-inputs",
             SupplySinceMerge => "supply-since-merge",
             TotalDifficultyProgress => "total-difficulty-progress",
+            TransactionStats => "transaction-stats",
+            TransactionStatsByDay => "transaction-stats-by-day",
             ValidatorRewards => "validator-rewards",
         }
     }
@@ -91,12 +193,12 @@ impl Display for CacheKey {
 pub enum ParseCacheKeyError {
     #[error("failed to parse cache key {0}")]
     UnknownCacheKey(String),
+    #[error("cache key {0} is not the current version")]
+    OtherVersion(String),
 }
 
-impl FromStr for CacheKey {
-    type Err = ParseCacheKeyError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl CacheKey {
+    fn from_name(s: &str) -> Result<Self, ParseCacheKeyError> {
         match s {
             "average-eth-price" => Ok(Self::AverageEthPrice),
             "base-fee-over-time" => Ok(Self::BaseFeeOverTime),
@@ -104,21 +206,51 @@ impl FromStr for CacheKey {
             "base-fee-per-gas" => Ok(Self::BaseFeePerGas),
             "base-fee-per-gas-barrier" => Ok(Self::BaseFeePerGasBarrier),
             "base-fee-per-gas-stats" => Ok(Self::BaseFeePerGasStats),
+            "blob-transactions" => Ok(Self::BlobTransactions),
+            "blob-usage" => Ok(Self::BlobUsage),
             "block-lag" => Ok(Self::BlockLag),
+            "btc-price" => Ok(Self::BtcPrice),
+            "builder-censorship" => Ok(Self::BuilderCensorship),
+            "burn-anomalies" => Ok(Self::BurnAnomalies),
+            "burn-moving-averages" => Ok(Self::BurnMovingAverages),
             "burn-rates" => Ok(Self::BurnRates),
+            "burn-rates-over-time" => Ok(Self::BurnRatesOverTime),
+            "burn-records" => Ok(Self::BurnRecords),
             "burn-sums" => Ok(Self::BurnSums),
+            "chain-health" => Ok(Self::ChainHealth),
+            "combined-fees" => Ok(Self::CombinedFees),
+            "consolidations-by-day" => Ok(Self::ConsolidationsByDay),
+            "contract-deployments" => Ok(Self::ContractDeployments),
+            "daily-supply-deltas" => Ok(Self::DailySupplyDeltas),
             "effective-balance-sum" => Ok(Self::EffectiveBalanceSum),
             "eth-price" => Ok(Self::EthPrice),
+            "eth-price-sources" => Ok(Self::EthPriceSources),
+            "eth-price-time-frame-stats" => Ok(Self::EthPriceStats),
+            "fee-suggestion" => Ok(Self::FeeSuggestion),
+            "gas-utilization" => Ok(Self::GasUtilization),
             "gauge-rates" => Ok(Self::GaugeRates),
+            "graffiti-counts" => Ok(Self::GraffitiCounts),
+            "health-score" => Ok(Self::HealthScore),
             "issuance-breakdown" => Ok(Self::IssuanceBreakdown),
             "issuance-estimate" => Ok(Self::IssuanceEstimate),
+            "issuance-moving-averages" => Ok(Self::IssuanceMovingAverages),
+            "l2-fees" => Ok(Self::L2Fees),
+            "market-caps" => Ok(Self::MarketCaps),
+            "market-caps-over-time" => Ok(Self::MarketCapsOverTime),
+            "next-base-fee" => Ok(Self::NextBaseFee),
+            "pending-deposits" => Ok(Self::PendingDeposits),
+            "pow-supply-since-merge" => Ok(Self::PowSupplySinceMerge),
+            "proposer-leaderboard" => Ok(Self::ProposerLeaderboard),
             "supply-changes" => Ok(Self::SupplyChanges),
             "supply-dashboard-analysis" => Ok(Self::SupplyDashboardAnalysis),
+            "supply-milestones" => Ok(Self::SupplyMilestones),
             "supply-over-time" => Ok(Self::SupplyOverTime),
             "supply-parts" => Ok(Self::SupplyParts),
             "supply-projection-inputs" => Ok(Self::SupplyProjectionInputs),
             "supply-since-merge" => Ok(Self::SupplySinceMerge),
             "total-difficulty-progress" => Ok(Self::TotalDifficultyProgress),
+            "transaction-stats" => Ok(Self::TransactionStats),
+            "transaction-stats-by-day" => Ok(Self::TransactionStatsByDay),
             "validator-rewards" => Ok(Self::ValidatorRewards),
             unknown_key if unknown_key.starts_with("base-fee-per-gas-stats-") => unknown_key
                 .split('-')
@@ -137,14 +269,32 @@ impl FromStr for CacheKey {
     }
 }
 
+impl FromStr for CacheKey {
+    type Err = ParseCacheKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, version) = split_version(s);
+        let cache_key = Self::from_name(name)?;
+        if version == cache_key.version() {
+            Ok(cache_key)
+        } else {
+            Err(ParseCacheKeyError::OtherVersion(s.to_string()))
+        }
+    }
+}
+
 pub async fn publish_cache_update<'a>(executor: impl PgExecutor<'a>, key: &CacheKey) {
-    debug!(?key, "publishing cache update");
+    publish_db_key_update(executor, &key.to_db_key()).await;
+}
+
+async fn publish_db_key_update<'a>(executor: impl PgExecutor<'a>, db_key: &str) {
+    debug!(db_key, "publishing cache update");
 
     sqlx::query!(
         "
             SELECT pg_notify('cache-update', $1)
         ",
-        key.to_db_key()
+        db_key
     )
     .execute(executor)
     .await
@@ -155,7 +305,7 @@ pub async fn get_serialized_caching_value(
     key_value_store: &impl KeyValueStore,
     cache_key: &CacheKey,
 ) -> Option<Value> {
-    key_value_store.get_value(cache_key.to_db_key()).await
+    key_value_store.get_value(&cache_key.to_db_key()).await
 }
 
 pub async fn set_value<'a>(
@@ -163,12 +313,9 @@ pub async fn set_value<'a>(
     cache_key: &CacheKey,
     value: impl Serialize,
 ) {
-    key_value_store::set_value(
-        executor,
-        cache_key.to_db_key(),
-        &serde_json::to_value(value).expect("expect value to be serializable"),
-    )
-    .await;
+    key_value_store::set(executor, &cache_key.to_db_key(), &value)
+        .await
+        .expect("expect cached value to be serializable");
 }
 
 pub async fn update_and_publish(db_pool: &PgPool, cache_key: &CacheKey, value: impl Serialize) {
@@ -176,11 +323,125 @@ pub async fn update_and_publish(db_pool: &PgPool, cache_key: &CacheKey, value: i
     publish_cache_update(db_pool, cache_key).await;
 }
 
+/// Stores and publishes a value in the shape of a specific version of the key. Used to keep serving
+/// the previous version while a shape change rolls out.
+pub async fn update_and_publish_version(
+    db_pool: &PgPool,
+    cache_key: &CacheKey,
+    version: u32,
+    value: impl Serialize,
+) {
+    let db_key = cache_key.to_db_key_for_version(version);
+    key_value_store::set(db_pool, &db_key, &value)
+        .await
+        .expect("expect cached value to be serializable");
+    publish_db_key_update(db_pool, &db_key).await;
+}
+
+/// Like `update_and_publish`, but as part of a larger transaction. Postgres holds back the
+/// notification until the transaction commits, so listeners never see a value that gets rolled
+/// back.
+pub async fn update_and_publish_tx(
+    transaction: &mut PgConnection,
+    cache_key: &CacheKey,
+    value: impl Serialize,
+) {
+    set_value(&mut *transaction, cache_key, value).await;
+    publish_cache_update(&mut *transaction, cache_key).await;
+}
+
+fn parse_cache_update(payload: &str) -> Option<CacheKey> {
+    match payload.parse::<CacheKey>() {
+        Ok(cache_key) => Some(cache_key),
+        Err(ParseCacheKeyError::UnknownCacheKey(cache_key)) => {
+            trace!(%cache_key, "unsupported cache update, skipping");
+            None
+        }
+        Err(ParseCacheKeyError::OtherVersion(cache_key)) => {
+            trace!(%cache_key, "cache update for another version, skipping");
+            None
+        }
+    }
+}
+
+/// Where cached values live and where updates to them are announced. Analyses always write to
+/// Postgres, which stays the source of truth. Serving may read from another store, like Redis,
+/// which is kept up to date by relaying the Postgres updates.
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, cache_key: &CacheKey) -> Option<Value>;
+
+    async fn set_and_publish(&self, cache_key: &CacheKey, value: &Value);
+
+    /// Stream of keys whose value was updated.
+    async fn subscribe(&self) -> BoxStream<'static, CacheKey>;
+}
+
+pub struct CacheStorePostgres {
+    db_pool: PgPool,
+}
+
+impl CacheStorePostgres {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl CacheStore for CacheStorePostgres {
+    async fn get(&self, cache_key: &CacheKey) -> Option<Value> {
+        let key_value_store = KeyValueStorePostgres::new(self.db_pool.clone());
+        get_serialized_caching_value(&key_value_store, cache_key).await
+    }
+
+    async fn set_and_publish(&self, cache_key: &CacheKey, value: &Value) {
+        update_and_publish(&self.db_pool, cache_key, value).await;
+    }
+
+    async fn subscribe(&self) -> BoxStream<'static, CacheKey> {
+        let mut listener =
+            sqlx::postgres::PgListener::connect(&db::get_db_url_with_name("cache-update-listener"))
+                .await
+                .unwrap();
+        listener.listen(CACHE_UPDATE_CHANNEL).await.unwrap();
+        debug!("listening for postgres cache updates");
+
+        listener
+            .into_stream()
+            .filter_map(|notification| async move {
+                parse_cache_update(notification.unwrap().payload())
+            })
+            .boxed()
+    }
+}
+
+/// Redis when a `redis_url` is configured and the `redis` feature is enabled, Postgres otherwise.
+pub async fn cache_store_from_config(db_pool: &PgPool) -> Arc<dyn CacheStore> {
+    match config::CONFIG.redis_url() {
+        #[cfg(feature = "redis")]
+        Some(redis_url) => {
+            debug!("using redis cache store");
+            Arc::new(CacheStoreRedis::connect(redis_url).await)
+        }
+        #[cfg(not(feature = "redis"))]
+        Some(_) => {
+            tracing::warn!(
+                "redis_url is configured but the redis feature is disabled, using postgres"
+            );
+            Arc::new(CacheStorePostgres::new(db_pool.clone()))
+        }
+        None => Arc::new(CacheStorePostgres::new(db_pool.clone())),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use serde::{Deserialize, Serialize};
 
-    use crate::{db, key_value_store::KeyValueStorePostgres};
+    use crate::{
+        db,
+        key_value_store::{KeyValueStoreExt, KeyValueStorePostgres},
+    };
 
     use super::*;
 
@@ -224,8 +485,9 @@ mod tests {
         };
 
         key_value_store
-            .set_serializable_value(CacheKey::BaseFeePerGasStats.to_db_key(), &test_json)
-            .await;
+            .set(&CacheKey::BaseFeePerGasStats.to_db_key(), &test_json)
+            .await
+            .unwrap();
 
         let raw_value: Value =
             get_serialized_caching_value(&key_value_store, &CacheKey::BaseFeePerGasStats)
@@ -253,8 +515,9 @@ mod tests {
         .await;
 
         let caching_value = key_value_store
-            .get_deserializable_value::<TestJson>(CacheKey::BaseFeePerGasStats.to_db_key())
+            .get::<TestJson>(&CacheKey::BaseFeePerGasStats.to_db_key())
             .await
+            .unwrap()
             .unwrap();
 
         assert_eq!(caching_value, test_json);
@@ -262,6 +525,40 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn cache_store_postgres_test() {
+        let test_db = db::tests::TestDb::new().await;
+        let cache_store = CacheStorePostgres::new(test_db.pool.clone());
+
+        let value = serde_json::json!({ "name": "alex", "age": 29 });
+        cache_store
+            .set_and_publish(&CacheKey::SupplyParts, &value)
+            .await;
+
+        assert_eq!(cache_store.get(&CacheKey::SupplyParts).await, Some(value));
+    }
+
+    #[test]
+    fn parse_cache_update_test() {
+        assert_eq!(
+            parse_cache_update("supply-parts"),
+            Some(CacheKey::SupplyParts)
+        );
+        assert_eq!(parse_cache_update("unknown-key"), None);
+    }
+
+    #[test]
+    fn versioned_db_key_test() {
+        assert_eq!(CacheKey::BurnSums.to_db_key(), "burn-sums");
+        assert_eq!(CacheKey::BurnSums.to_db_key_for_version(2), "burn-sums-v2");
+        assert_eq!(split_version("burn-sums-v2"), ("burn-sums", 2));
+        assert_eq!(split_version("validator-rewards"), ("validator-rewards", 1));
+        assert!(matches!(
+            "burn-sums-v2".parse::<CacheKey>(),
+            Err(ParseCacheKeyError::OtherVersion(_))
+        ));
+    }
+
     #[test]
     fn parse_base_fees_time_frame_test() {
         assert_eq!(
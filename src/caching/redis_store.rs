@@ -0,0 +1,108 @@
+//! Redis backed cache store. Analyses keep writing to Postgres, a single relay copies each update
+//! into Redis and publishes it there. Serve instances read and subscribe to Redis, so adding
+//! instances adds no load on Postgres.
+use async_trait::async_trait;
+use enum_iterator::all;
+use futures::{stream::BoxStream, StreamExt};
+use redis::{aio::MultiplexedConnection, AsyncCommands, Client};
+use serde_json::Value;
+use tracing::{debug, info, warn};
+
+use crate::{config, db, log};
+
+use super::{parse_cache_update, CacheKey, CacheStore, CacheStorePostgres, CACHE_UPDATE_CHANNEL};
+
+fn redis_key(cache_key: &CacheKey) -> String {
+    format!("cache:{}", cache_key.to_db_key())
+}
+
+pub struct CacheStoreRedis {
+    client: Client,
+    connection: MultiplexedConnection,
+}
+
+impl CacheStoreRedis {
+    pub async fn connect(redis_url: &str) -> Self {
+        let client = Client::open(redis_url).expect("expect redis_url to be a valid redis url");
+        let connection = client.get_multiplexed_tokio_connection().await.unwrap();
+        Self { client, connection }
+    }
+}
+
+#[async_trait]
+impl CacheStore for CacheStoreRedis {
+    async fn get(&self, cache_key: &CacheKey) -> Option<Value> {
+        let mut connection = self.connection.clone();
+        let value: Option<String> = connection.get(redis_key(cache_key)).await.unwrap();
+        value.map(|value| serde_json::from_str(&value).expect("expect cached value to be json"))
+    }
+
+    async fn set_and_publish(&self, cache_key: &CacheKey, value: &Value) {
+        let mut connection = self.connection.clone();
+        connection
+            .set::<_, _, ()>(redis_key(cache_key), value.to_string())
+            .await
+            .unwrap();
+        connection
+            .publish::<_, _, ()>(CACHE_UPDATE_CHANNEL, cache_key.to_db_key())
+            .await
+            .unwrap();
+    }
+
+    async fn subscribe(&self) -> BoxStream<'static, CacheKey> {
+        let mut pubsub = self
+            .client
+            .get_async_connection()
+            .await
+            .unwrap()
+            .into_pubsub();
+        pubsub.subscribe(CACHE_UPDATE_CHANNEL).await.unwrap();
+        debug!("listening for redis cache updates");
+
+        pubsub
+            .into_on_message()
+            .filter_map(|message| async move {
+                let payload: String = message.get_payload().unwrap();
+                parse_cache_update(&payload)
+            })
+            .boxed()
+    }
+}
+
+pub async fn relay_cache_updates_to_redis() {
+    log::init_with_env();
+
+    info!("relaying cache updates to redis");
+
+    let redis_url = config::CONFIG
+        .redis_url()
+        .expect("expect redis_url to be configured when relaying cache updates");
+    let redis_store = CacheStoreRedis::connect(redis_url).await;
+
+    let db_pool = db::get_db_pool("relay-cache-updates-to-redis").await;
+    let postgres_store = CacheStorePostgres::new(db_pool);
+
+    // Subscribe before copying the current values so no update is missed in between.
+    let mut updates = postgres_store.subscribe().await;
+
+    for cache_key in all::<CacheKey>() {
+        if let Some(value) = postgres_store.get(&cache_key).await {
+            redis_store.set_and_publish(&cache_key, &value).await;
+        }
+    }
+
+    info!("copied current cache values to redis");
+
+    while let Some(cache_key) = updates.next().await {
+        match postgres_store.get(&cache_key).await {
+            Some(value) => {
+                debug!(%cache_key, "relaying cache update");
+                redis_store.set_and_publish(&cache_key, &value).await;
+            }
+            None => warn!(
+                %cache_key,
+                "got a cache update to relay, but DB had no value to give"
+            ),
+        }
+    }
+}
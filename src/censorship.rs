@@ -0,0 +1,477 @@
+//! # Censorship
+//! Which blocks were built by builders that censor, e.g. leave out transactions touching
+//! OFAC-sanctioned addresses. We store the payloads relays in the config file delivered, through
+//! the standard relay data API, with the builder of each. A block counts as censoring when its
+//! builder is in `censoring_builders`, or a relay marked `censoring` delivered it, such relays
+//! only pass compliant blocks. Blocks no relay we know of delivered count as locally built.
+//!
+//! Shares are published per time frame and per day, covering blocks since the first stored
+//! payload. The first run goes back `MAX_PAGES_WITHOUT_PAYLOADS` pages per relay, later runs
+//! continue from the last stored payload.
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use enum_iterator::all;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, PgExecutor, PgPool, Row};
+use tracing::{debug, info, warn};
+
+use crate::{
+    beacon_chain::{slot_from_string, Slot},
+    caching::{self, CacheKey},
+    config,
+    time_frames::TimeFrame,
+};
+
+/// Most relays don't return more per request.
+const PAGE_SIZE: usize = 200;
+/// About two weeks of blocks delivered by a popular relay.
+const MAX_PAGES_WITHOUT_PAYLOADS: usize = 200;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RelayConfig {
+    /// Stored with each payload, e.g. `flashbots`.
+    pub name: String,
+    pub url: String,
+    /// Whether the relay only delivers blocks which pass an OFAC filter.
+    #[serde(default)]
+    pub censoring: bool,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct DeliveredPayload {
+    block_hash: String,
+    builder_pubkey: String,
+    #[serde(deserialize_with = "slot_from_string")]
+    slot: Slot,
+}
+
+/// Newest first, from the cursor slot down when given.
+async fn get_delivered_payloads(
+    client: &reqwest::Client,
+    relay: &RelayConfig,
+    cursor: Option<Slot>,
+) -> Result<Vec<DeliveredPayload>> {
+    let mut url = format!(
+        "{}/relay/v1/data/bidtraces/proposer_payload_delivered?limit={}",
+        relay.url, PAGE_SIZE
+    );
+    if let Some(cursor) = cursor {
+        url.push_str(&format!("&cursor={cursor}"));
+    }
+
+    client
+        .get(&url)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Vec<DeliveredPayload>>()
+        .await
+        .map_err(Into::into)
+}
+
+async fn get_last_payload_slot(executor: impl PgExecutor<'_>, relay: &str) -> Option<Slot> {
+    sqlx::query!(
+        "SELECT MAX(slot) AS slot FROM relay_payloads WHERE relay = $1",
+        relay
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .slot
+    .map(Slot)
+}
+
+async fn store_payloads(executor: impl PgExecutor<'_>, relay: &str, payloads: &[DeliveredPayload]) {
+    let slots = payloads
+        .iter()
+        .map(|payload| payload.slot.0)
+        .collect::<Vec<_>>();
+    let timestamps = payloads
+        .iter()
+        .map(|payload| payload.slot.date_time())
+        .collect::<Vec<_>>();
+    let block_hashes = payloads
+        .iter()
+        .map(|payload| payload.block_hash.clone())
+        .collect::<Vec<_>>();
+    let builder_pubkeys = payloads
+        .iter()
+        .map(|payload| payload.builder_pubkey.clone())
+        .collect::<Vec<_>>();
+
+    sqlx::query!(
+        "
+        INSERT INTO relay_payloads (relay, slot, timestamp, block_hash, builder_pubkey)
+        SELECT $1, * FROM UNNEST($2::int[], $3::timestamptz[], $4::text[], $5::text[])
+        ON CONFLICT (relay, slot) DO NOTHING
+        ",
+        relay,
+        &slots,
+        &timestamps,
+        &block_hashes,
+        &builder_pubkeys,
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Pages back from the newest payload until we reach the last one stored. Payloads are only stored
+/// once the walk completes, a walk failing halfway would otherwise store the newest pages, and the
+/// next run would stop at them, leaving the older pages missing for good.
+async fn sync_relay_payloads(
+    db_pool: &PgPool,
+    client: &reqwest::Client,
+    relay: &RelayConfig,
+) -> Result<()> {
+    let last_slot = get_last_payload_slot(db_pool, &relay.name).await;
+    let mut cursor = None;
+    let mut page_count = 0;
+    let mut new_payloads = Vec::new();
+
+    loop {
+        let payloads = get_delivered_payloads(client, relay, cursor).await?;
+        page_count += 1;
+
+        let lowest_slot = match payloads.iter().map(|payload| payload.slot).min() {
+            Some(lowest_slot) => lowest_slot,
+            None => break,
+        };
+        let is_last_page = payloads.len() < PAGE_SIZE;
+
+        new_payloads.extend(
+            payloads
+                .into_iter()
+                .filter(|payload| last_slot.is_none_or(|last_slot| payload.slot > last_slot)),
+        );
+
+        let reached_stored = last_slot.is_some_and(|last_slot| lowest_slot <= last_slot);
+        let reached_max_pages = last_slot.is_none() && page_count >= MAX_PAGES_WITHOUT_PAYLOADS;
+        if is_last_page || reached_stored || reached_max_pages {
+            break;
+        }
+
+        cursor = Some(lowest_slot - 1);
+    }
+
+    store_payloads(db_pool, &relay.name, &new_payloads).await;
+
+    debug!(
+        relay = relay.name,
+        page_count,
+        payload_count = new_payloads.len(),
+        "synced relay payloads"
+    );
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuilderCensorship {
+    pub block_count: i64,
+    pub censoring_count: i64,
+    /// Of all blocks, including locally built ones.
+    pub censoring_share: f64,
+    pub local_count: i64,
+    pub non_censoring_count: i64,
+}
+
+impl BuilderCensorship {
+    fn from_row(row: &PgRow) -> Self {
+        let block_count: i64 = row.get("block_count");
+        let censoring_count: i64 = row.get("censoring_count");
+        let non_censoring_count: i64 = row.get("non_censoring_count");
+        Self {
+            block_count,
+            censoring_count,
+            censoring_share: if block_count == 0 {
+                0.0
+            } else {
+                censoring_count as f64 / block_count as f64
+            },
+            local_count: block_count - censoring_count - non_censoring_count,
+            non_censoring_count,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuilderCensorshipOnDay {
+    #[serde(flatten)]
+    pub censorship: BuilderCensorship,
+    pub day: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuilderCensorshipStats {
+    pub by_day: Vec<BuilderCensorshipOnDay>,
+    pub by_time_frame: HashMap<TimeFrame, BuilderCensorship>,
+}
+
+/// Execution blocks since the first stored payload, and since the given timestamp when not null,
+/// classified by whether a censoring builder or relay was involved.
+const CLASSIFIED_BLOCKS: &str = "
+    WITH payloads AS (
+        SELECT
+            block_hash,
+            BOOL_OR(relay = ANY($2) OR builder_pubkey = ANY($3)) AS censoring
+        FROM relay_payloads
+        GROUP BY block_hash
+    )
+    SELECT
+        blocks_next.timestamp,
+        payloads.censoring IS TRUE AS censoring,
+        payloads.censoring IS FALSE AS non_censoring
+    FROM blocks_next
+    LEFT JOIN payloads ON payloads.block_hash = blocks_next.hash
+    WHERE blocks_next.timestamp >= GREATEST(
+        $1,
+        (SELECT MIN(timestamp) FROM relay_payloads)
+    )
+";
+
+struct CensorshipLists {
+    censoring_builders: Vec<String>,
+    censoring_relays: Vec<String>,
+}
+
+impl CensorshipLists {
+    fn from_config() -> Self {
+        Self {
+            censoring_builders: config::CONFIG.censoring_builders().to_vec(),
+            censoring_relays: config::CONFIG
+                .relays()
+                .iter()
+                .filter(|relay| relay.censoring)
+                .map(|relay| relay.name.clone())
+                .collect(),
+        }
+    }
+}
+
+async fn get_builder_censorship(
+    executor: impl PgExecutor<'_>,
+    lists: &CensorshipLists,
+    since: &DateTime<Utc>,
+) -> BuilderCensorship {
+    sqlx::query(&format!(
+        "
+        WITH classified_blocks AS ({CLASSIFIED_BLOCKS})
+        SELECT
+            COUNT(*) AS block_count,
+            COUNT(*) FILTER (WHERE censoring) AS censoring_count,
+            COUNT(*) FILTER (WHERE non_censoring) AS non_censoring_count
+        FROM classified_blocks
+        "
+    ))
+    .bind(since)
+    .bind(&lists.censoring_relays)
+    .bind(&lists.censoring_builders)
+    .map(|row: PgRow| BuilderCensorship::from_row(&row))
+    .fetch_one(executor)
+    .await
+    .unwrap()
+}
+
+async fn get_builder_censorship_by_day(
+    executor: impl PgExecutor<'_>,
+    lists: &CensorshipLists,
+) -> Vec<BuilderCensorshipOnDay> {
+    sqlx::query(&format!(
+        "
+        WITH classified_blocks AS ({CLASSIFIED_BLOCKS})
+        SELECT
+            DATE_TRUNC('day', timestamp) AS day,
+            COUNT(*) AS block_count,
+            COUNT(*) FILTER (WHERE censoring) AS censoring_count,
+            COUNT(*) FILTER (WHERE non_censoring) AS non_censoring_count
+        FROM classified_blocks
+        GROUP BY day
+        ORDER BY day ASC
+        "
+    ))
+    .bind(None::<DateTime<Utc>>)
+    .bind(&lists.censoring_relays)
+    .bind(&lists.censoring_builders)
+    .map(|row: PgRow| BuilderCensorshipOnDay {
+        censorship: BuilderCensorship::from_row(&row),
+        day: row.get("day"),
+    })
+    .fetch_all(executor)
+    .await
+    .unwrap()
+}
+
+async fn update_builder_censorship(db_pool: &PgPool, lists: &CensorshipLists) {
+    let now = Utc::now();
+    let mut by_time_frame = HashMap::new();
+
+    for time_frame in all::<TimeFrame>() {
+        let since = time_frame.start_timestamp_at(&now);
+        by_time_frame.insert(
+            time_frame,
+            get_builder_censorship(db_pool, lists, &since).await,
+        );
+    }
+
+    let stats = BuilderCensorshipStats {
+        by_day: get_builder_censorship_by_day(db_pool, lists).await,
+        by_time_frame,
+    };
+
+    caching::update_and_publish(db_pool, &CacheKey::BuilderCensorship, &stats).await;
+    debug!("published builder censorship");
+}
+
+/// Syncs the payloads of every relay in the config file, then publishes censorship shares. A relay
+/// failing to respond doesn't hold up the others.
+pub async fn update_censorship(db_pool: &PgPool) -> Result<()> {
+    let relays = config::CONFIG.relays();
+    if relays.is_empty() {
+        info!("no relays configured, skipping censorship update");
+        return Ok(());
+    }
+
+    let client = reqwest::Client::new();
+    for relay in relays {
+        if let Err(err) = sync_relay_payloads(db_pool, &client, relay).await {
+            warn!(relay = relay.name, %err, "failed to sync relay payloads");
+        }
+    }
+
+    update_builder_censorship(db_pool, &CensorshipLists::from_config()).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, DurationRound};
+    use test_context::test_context;
+
+    use crate::db::tests::TestDb;
+
+    use super::*;
+
+    async fn store_test_block(executor: impl PgExecutor<'_>, hash: &str, number: i32) {
+        sqlx::query!(
+            "
+            INSERT INTO blocks_next (
+                base_fee_per_gas,
+                difficulty,
+                eth_price,
+                gas_used,
+                hash,
+                number,
+                parent_hash,
+                timestamp,
+                total_difficulty
+            )
+            VALUES (0, 0, 0, 0, $1, $2, $3, $4, 0)
+            ",
+            hash,
+            i64::from(number),
+            format!("{hash}_parent"),
+            Slot(9_000_000 + number).date_time(),
+        )
+        .execute(executor)
+        .await
+        .unwrap();
+    }
+
+    fn test_payload(slot: i32, block_hash: &str, builder_pubkey: &str) -> DeliveredPayload {
+        DeliveredPayload {
+            block_hash: block_hash.to_string(),
+            builder_pubkey: builder_pubkey.to_string(),
+            slot: Slot(slot),
+        }
+    }
+
+    #[test]
+    fn decode_delivered_payloads_test() {
+        let payloads = serde_json::from_str::<Vec<DeliveredPayload>>(
+            r#"[{
+                "slot": "9000000",
+                "parent_hash": "0xparent",
+                "block_hash": "0xblock",
+                "builder_pubkey": "0xbuilder",
+                "proposer_pubkey": "0xproposer",
+                "proposer_fee_recipient": "0xrecipient",
+                "gas_limit": "30000000",
+                "gas_used": "12000000",
+                "value": "1000",
+                "block_number": "18000000",
+                "num_tx": "120"
+            }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            payloads,
+            vec![test_payload(9_000_000, "0xblock", "0xbuilder")]
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn builder_censorship_test(test_db: &TestDb) {
+        for number in 0..4 {
+            store_test_block(&test_db.pool, &format!("0xblock{number}"), number).await;
+        }
+        // Censoring through the relay, through the builder, not censoring, and a local block.
+        store_payloads(
+            &test_db.pool,
+            "censoring_relay",
+            &[test_payload(9_000_000, "0xblock0", "0xbuilder")],
+        )
+        .await;
+        store_payloads(
+            &test_db.pool,
+            "open_relay",
+            &[
+                test_payload(9_000_000, "0xblock0", "0xbuilder"),
+                test_payload(9_000_001, "0xblock1", "0xcensoring_builder"),
+                test_payload(9_000_002, "0xblock2", "0xbuilder"),
+            ],
+        )
+        .await;
+        let lists = CensorshipLists {
+            censoring_builders: vec!["0xcensoring_builder".to_string()],
+            censoring_relays: vec!["censoring_relay".to_string()],
+        };
+
+        let expected = BuilderCensorship {
+            block_count: 4,
+            censoring_count: 2,
+            censoring_share: 0.5,
+            local_count: 1,
+            non_censoring_count: 1,
+        };
+        assert_eq!(
+            get_builder_censorship(&test_db.pool, &lists, &Slot(0).date_time()).await,
+            expected
+        );
+        assert_eq!(
+            get_builder_censorship_by_day(&test_db.pool, &lists).await,
+            vec![BuilderCensorshipOnDay {
+                censorship: expected,
+                day: Slot(9_000_000)
+                    .date_time()
+                    .duration_trunc(Duration::days(1))
+                    .unwrap(),
+            }]
+        );
+        assert_eq!(
+            get_last_payload_slot(&test_db.pool, "open_relay").await,
+            Some(Slot(9_000_002))
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! # CLI
+//! A single `eth-analysis` binary exposing the services and tools as subcommands. The thin
+//! binaries in `src/bin` still exist, deployments invoking them keep working.
+use std::num::NonZeroU32;
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+use crate::{
+    beacon_chain, btc_price, burn_records, burn_sums, data_integrity, eth_supply, execution_chain,
+    execution_chain::BlockNumber, export, issuance_breakdown, l2, phoenix,
+    row_writer::ExportFormat, scheduler, serve, update_by_hand, usd_price, warm_caches,
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "eth-analysis", about = "Ethereum analysis services and tools")]
+struct Cli {
+    /// Overrides DATABASE_URL from the env or config file.
+    #[arg(long, global = true)]
+    database_url: Option<String>,
+    /// Overrides RUST_LOG, e.g. `info,sqlx=warn`.
+    #[arg(long, global = true)]
+    log_level: Option<String>,
+    /// Tools which support it log what they would change, without changing anything.
+    #[arg(long, global = true)]
+    dry_run: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Recomputes the supply up to the given block from its parts and prints the first block and
+    /// slot that diverge, up to the last supply delta by default.
+    AuditSupply {
+        block_number: Option<BlockNumber>,
+    },
+    /// Stores the validator balances sum of every stored beacon state from the first post London
+    /// slot on.
+    BackfillBalancesToLondon,
+    /// Adds every stored block after the last burn sums to the sums, from the given block number
+    /// when none are stored, the last 100 blocks by default.
+    BackfillBurnSums {
+        from: Option<BlockNumber>,
+    },
+    BackfillDailyBalancesToLondon,
+    /// Sums stored supply deltas into execution balances sums, up to the supply snapshot.
+    BackfillExecutionSupply,
+    BackfillGaps,
+    BackfillHourlyBalances,
+    BackfillHourlyBalancesToLondon,
+    /// Stores the proof-of-work issuance of every block from the given block number up to the
+    /// merge, about a month of blocks by default.
+    BackfillPowIssuance {
+        from: Option<BlockNumber>,
+    },
+    /// Stores the genesis allocation and every supply delta up to the snapshot the sync starts
+    /// from, so the execution supply reaches back to block 0.
+    BackfillSupplyDeltas,
+    /// Fills in the supply minus beacon chain issuance of supply since merge minutes stored
+    /// before it was kept.
+    BackfillSupplyLessPosIssuance,
+    CheckBeaconStateGaps,
+    CheckBlocksGaps {
+        /// Fetch and store missing blocks.
+        #[arg(long)]
+        refetch: bool,
+    },
+    ExportBlocksFromAugust {
+        /// csv, jsonl or parquet, parquet needs the parquet feature.
+        #[arg(long, default_value = "csv")]
+        format: ExportFormat,
+    },
+    ExportBlocksFromLondon,
+    ExportBurnRecords {
+        /// csv, jsonl or parquet, parquet needs the parquet feature.
+        #[arg(long, default_value = "csv")]
+        format: ExportFormat,
+    },
+    ExportDailySupplySinceMerge,
+    /// Exports beacon_issuance, blocks_next, eth_prices, eth_supply or execution_supply_deltas
+    /// between two block numbers, or two dates or timestamps.
+    ExportDataset {
+        dataset: String,
+        start: String,
+        end: String,
+        /// csv, jsonl or parquet, parquet needs the parquet feature.
+        #[arg(long, default_value = "csv")]
+        format: ExportFormat,
+    },
+    ExportExecutionSupplyDeltas {
+        /// csv, jsonl or parquet, parquet needs the parquet feature.
+        #[arg(long, default_value = "csv")]
+        format: ExportFormat,
+    },
+    ExportThousandthEpochSupply,
+    FillEthSupplyGaps,
+    HealBeaconStates,
+    /// Refetches stored blocks from Cancun on which have no blob gas, then drops the burn sums so
+    /// they are recomputed including blob fees.
+    HealBlobGas,
+    HealBlockHashes,
+    HealBtcPrices {
+        /// How far in minutes a price may be from the minute it is used for.
+        #[arg(default_value_t = 10)]
+        max_distance_in_minutes: i64,
+    },
+    HealEthPrices {
+        /// How far in minutes a price may be from the minute it is used for.
+        #[arg(default_value_t = 10)]
+        max_distance_in_minutes: i64,
+        /// Price sources to ask, in order, e.g. `bybit,kraken`. Those in the config file, or every
+        /// source, by default.
+        #[arg(long, value_delimiter = ',')]
+        sources: Option<Vec<String>>,
+        /// Only heal minutes from this date or timestamp on.
+        #[arg(long)]
+        start: Option<String>,
+        /// Only heal minutes before this date or timestamp.
+        #[arg(long)]
+        end: Option<String>,
+    },
+    /// Stores the operators of validators from a CSV of `validator_index,entity` rows, so the
+    /// proposer leaderboard can group their blocks.
+    ImportValidatorEntities {
+        path: String,
+    },
+    MonitorCriticalServices,
+    RecordBtcPrice,
+    RecordEthPrice,
+    /// Copies cache updates from Postgres into Redis, for serve instances reading from Redis.
+    #[cfg(feature = "redis")]
+    RelayCacheUpdatesToRedis,
+    ResyncEthPrices {
+        /// How far in minutes a price may be from the minute it is used for.
+        #[arg(default_value_t = 10)]
+        max_distance_in_minutes: i64,
+    },
+    /// Runs periodic jobs, like updating the issuance estimate.
+    RunScheduler,
+    Serve,
+    /// Sums the burn between two block numbers, or two dates or timestamps.
+    SumBurn {
+        start: String,
+        end: String,
+    },
+    /// Sums the supply deltas in a CSV exported by `export-execution-supply-deltas`.
+    SummaryFromDeltasCsv,
+    SyncBeaconStates,
+    SyncExecutionBlocks,
+    SyncExecutionSupplyDeltas,
+    /// Follows the L2 chains in the config file and publishes their fees next to the L1 burn.
+    SyncL2Fees,
+    UpdateByHand,
+    UpdateEffectiveBalanceSum,
+    UpdateIssuanceBreakdown,
+    UpdateIssuanceEstimate,
+    UpdateSupplyProjectionInputs,
+    /// Syncs MEV blocks, publishes expected validator rewards and stores those earned.
+    UpdateValidatorRewards,
+    VerifyBurnSums {
+        /// Overwrite stored sums which don't match a sum from scratch.
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Compares how stored execution balances sums move against stored supply deltas.
+    VerifyExecutionSupply {
+        /// Blocks to check at a time, drift is reported per range.
+        #[arg(long, default_value = "10000")]
+        range_size: NonZeroU32,
+    },
+    /// Recomputes and publishes cached values from stored data.
+    WarmCaches,
+    WriteExecutionHeadsLog,
+    WriteExecutionSupplyDeltasLog {
+        /// csv, jsonl or parquet, parquet needs the parquet feature.
+        #[arg(long, default_value = "csv")]
+        format: ExportFormat,
+    },
+}
+
+// Shared flags are passed on through the env, which is where config and logging read them from.
+// Runs before anything reads the config.
+fn apply_shared_flags(cli: &Cli) {
+    if let Some(database_url) = &cli.database_url {
+        std::env::set_var("DATABASE_URL", database_url);
+    }
+    if let Some(log_level) = &cli.log_level {
+        std::env::set_var("RUST_LOG", log_level);
+    }
+}
+
+pub async fn run_cli() -> Result<()> {
+    let cli = Cli::parse();
+    apply_shared_flags(&cli);
+    let dry_run = cli.dry_run;
+
+    match cli.command {
+        Command::AuditSupply { block_number } => eth_supply::audit_supply_at(block_number).await,
+        Command::BackfillBalancesToLondon => beacon_chain::backfill_balances_to_london().await,
+        Command::BackfillBurnSums { from } => burn_sums::backfill_burn_sums_from(from).await,
+        Command::BackfillDailyBalancesToLondon => {
+            beacon_chain::backfill_daily_balances_to_london().await
+        }
+        Command::BackfillExecutionSupply => execution_chain::backfill_execution_supply().await,
+        Command::BackfillGaps => data_integrity::backfill_gaps().await,
+        Command::BackfillHourlyBalances => beacon_chain::backfill_hourly_balances().await,
+        Command::BackfillHourlyBalancesToLondon => {
+            beacon_chain::backfill_hourly_balances_to_london().await
+        }
+        Command::BackfillPowIssuance { from } => {
+            execution_chain::backfill_pow_issuance_from(from).await
+        }
+        Command::BackfillSupplyDeltas => execution_chain::backfill_supply_deltas().await,
+        Command::BackfillSupplyLessPosIssuance => {
+            eth_supply::backfill_supply_less_pos_issuance().await
+        }
+        Command::CheckBeaconStateGaps => data_integrity::check_beacon_state_gaps().await?,
+        Command::CheckBlocksGaps { refetch } => {
+            data_integrity::check_blocks_gaps_with_refetch(refetch).await?
+        }
+        Command::ExportBlocksFromAugust { format } => {
+            execution_chain::export_blocks_from_august_with_format(format).await?
+        }
+        Command::ExportBlocksFromLondon => execution_chain::export_blocks_from_london().await?,
+        Command::ExportBurnRecords { format } => {
+            burn_records::export_burn_records_with_format(format).await
+        }
+        Command::ExportDailySupplySinceMerge => eth_supply::export_daily_supply_since_merge().await,
+        Command::ExportDataset {
+            dataset,
+            start,
+            end,
+            format,
+        } => export::export_between(&dataset, &start, &end, format).await?,
+        Command::ExportExecutionSupplyDeltas { format } => {
+            execution_chain::export_execution_supply_deltas_with_format(format).await
+        }
+        Command::ExportThousandthEpochSupply => eth_supply::export_thousandth_epoch_supply().await,
+        Command::FillEthSupplyGaps => eth_supply::fill_eth_supply_gaps().await?,
+        Command::HealBeaconStates => beacon_chain::heal_beacon_states_with_dry_run(dry_run).await,
+        Command::HealBlobGas => execution_chain::heal_blob_gas_with_dry_run(dry_run).await,
+        Command::HealBlockHashes => beacon_chain::heal_block_hashes_with_dry_run(dry_run).await,
+        Command::HealBtcPrices {
+            max_distance_in_minutes,
+        } => btc_price::heal_btc_prices_with_max_distance(max_distance_in_minutes, dry_run).await,
+        Command::HealEthPrices {
+            max_distance_in_minutes,
+            sources,
+            start,
+            end,
+        } => {
+            let heal_eth_prices_options = usd_price::HealEthPricesOptions::from_config()
+                .with_flags(sources, start.as_deref(), end.as_deref())?;
+            usd_price::heal_eth_prices_with_options(
+                max_distance_in_minutes,
+                dry_run,
+                &heal_eth_prices_options,
+            )
+            .await
+        }
+        Command::ImportValidatorEntities { path } => {
+            beacon_chain::proposers::import_validator_entities(&path).await?
+        }
+        Command::MonitorCriticalServices => phoenix::monitor_critical_services().await,
+        Command::RecordBtcPrice => btc_price::record_btc_price().await?,
+        Command::RecordEthPrice => usd_price::record_eth_price().await?,
+        #[cfg(feature = "redis")]
+        Command::RelayCacheUpdatesToRedis => crate::caching::relay_cache_updates_to_redis().await,
+        Command::ResyncEthPrices {
+            max_distance_in_minutes,
+        } => usd_price::resync_all_with_max_distance(max_distance_in_minutes).await,
+        Command::RunScheduler => scheduler::run_scheduler().await,
+        Command::Serve => serve::start_server().await,
+        Command::SumBurn { start, end } => burn_sums::sum_burn_between(&start, &end).await,
+        Command::SummaryFromDeltasCsv => execution_chain::summary_from_deltas_csv().await,
+        Command::SyncBeaconStates => beacon_chain::sync_beacon_states().await?,
+        Command::SyncExecutionBlocks => execution_chain::sync_execution_blocks().await,
+        Command::SyncExecutionSupplyDeltas => execution_chain::sync_execution_supply_deltas().await,
+        Command::SyncL2Fees => l2::sync_l2_fees().await,
+        Command::UpdateByHand => update_by_hand::run_cli().await?,
+        Command::UpdateEffectiveBalanceSum => {
+            beacon_chain::effective_balance_sums::update_effective_balance_sum().await
+        }
+        Command::UpdateIssuanceBreakdown => issuance_breakdown::update_issuance_breakdown().await?,
+        Command::UpdateIssuanceEstimate => beacon_chain::update_issuance_estimate().await,
+        Command::UpdateSupplyProjectionInputs => {
+            eth_supply::update_supply_projection_inputs().await
+        }
+        Command::UpdateValidatorRewards => {
+            beacon_chain::rewards::update_validator_rewards().await?
+        }
+        Command::VerifyBurnSums { repair } => burn_sums::verify_burn_sums_with_repair(repair).await,
+        Command::VerifyExecutionSupply { range_size } => {
+            execution_chain::verify_execution_supply_with_range_size(range_size).await
+        }
+        Command::WarmCaches => warm_caches::warm_caches().await,
+        Command::WriteExecutionHeadsLog => execution_chain::write_execution_heads_log().await,
+        Command::WriteExecutionSupplyDeltasLog { format } => {
+            execution_chain::write_execution_supply_deltas_log_with_format(format).await
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn verify_cli_test() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parse_shared_flags_after_subcommand_test() {
+        let cli = Cli::parse_from([
+            "eth-analysis",
+            "heal-block-hashes",
+            "--dry-run",
+            "--database-url",
+            "postgresql://localhost/testdb",
+        ]);
+        assert!(cli.dry_run);
+        assert_eq!(
+            cli.database_url.as_deref(),
+            Some("postgresql://localhost/testdb")
+        );
+        assert!(matches!(cli.command, Command::HealBlockHashes));
+    }
+
+    #[test]
+    fn reject_empty_range_size_test() {
+        let result = Cli::try_parse_from([
+            "eth-analysis",
+            "verify-execution-supply",
+            "--range-size",
+            "0",
+        ]);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,636 @@
+//! # Config
+//! Settings shared by all binaries. When `CONFIG_PATH` is set, settings are read from that file
+//! first, YAML when it ends in `.yaml` or `.yml`, TOML otherwise. Environment variables always take
+//! precedence, so deployments which only use the environment keep working unchanged.
+//!
+//! Field names in the file are the lowercase versions of the environment variables, e.g.
+//! `database_url` for `DATABASE_URL`.
+use std::{collections::HashMap, fs, path::Path};
+
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    burn_sums::UsdValuation,
+    censorship::RelayConfig,
+    env,
+    l2::L2ChainConfig,
+    network::{Network, NetworkOverrides},
+    webhooks::WebhookConfig,
+};
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read config file {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("failed to parse config file {path}: {source}")]
+    Parse {
+        path: String,
+        source: toml::de::Error,
+    },
+    #[error("failed to parse config file {path}: {source}")]
+    ParseYaml {
+        path: String,
+        source: serde_yaml::Error,
+    },
+    #[error("missing required config fields, set them in the config file or env: {}", .0.join(", "))]
+    MissingFields(Vec<&'static str>),
+    #[error("unknown alert sinks for phoenix monitor {monitor}: {}, expected any of {}", .sinks.join(", "), ALERT_SINKS.join(", "))]
+    UnknownAlertSinks { monitor: String, sinks: Vec<String> },
+}
+
+/// Overrides the default schedule of a scheduled job. Only read from the config file, under
+/// `[jobs.<job name>]`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobConfig {
+    pub enabled: Option<bool>,
+    pub interval_secs: Option<u64>,
+    pub jitter_secs: Option<u64>,
+}
+
+/// A different max age during part of the day, e.g. when updates are expected to be slower.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaxAgeOverride {
+    /// Start of the window, `HH:MM` in UTC.
+    pub from: String,
+    /// End of the window, exclusive, `HH:MM` in UTC. May be before `from` to wrap past midnight.
+    pub to: String,
+    pub max_age_secs: u64,
+}
+
+/// Per-monitor phoenix settings. Only read from the config file, under
+/// `[phoenix.monitors.<monitor name>]`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonitorConfig {
+    /// How long a monitor may go without updates before we alert.
+    pub max_age_secs: Option<u64>,
+    #[serde(default)]
+    pub max_age_overrides: Vec<MaxAgeOverride>,
+    /// For sync lag monitors, how many blocks or slots we may be behind the node head. For the
+    /// finality lag monitor, how many epochs finality may trail.
+    pub max_lag: Option<u64>,
+    /// Names of the alert sinks to notify, e.g. `["telegram"]`. All configured sinks when absent.
+    pub sinks: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhoenixConfig {
+    #[serde(default)]
+    pub monitors: HashMap<String, MonitorConfig>,
+}
+
+/// How long raw rows of a downsampled series are kept. Only read from the config file, under
+/// `[retention.<series name>]`, see `downsampling`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub raw_retention_days: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    beacon_url: Option<String>,
+    /// A BigQuery dataset to export rows into, e.g. `my-project.analytics`, see `analytics_sinks`.
+    bigquery_dataset: Option<String>,
+    /// Only read from the config file. `closest_price` or `twap`, see `burn_sums`.
+    #[serde(default)]
+    burn_usd_valuation: UsdValuation,
+    /// Only read from the config file. Pubkeys of builders known to censor, see `censorship`.
+    #[serde(default)]
+    censoring_builders: Vec<String>,
+    /// Cross-check the burn of synced blocks against their receipts, see
+    /// `execution_chain::receipt_burn`.
+    #[serde(default)]
+    check_receipt_burn: bool,
+    clickhouse_database: Option<String>,
+    /// ClickHouse HTTP url to export rows into, see `analytics_sinks`.
+    clickhouse_url: Option<String>,
+    database_read_url: Option<String>,
+    database_url: Option<String>,
+    discord_webhook_url: Option<String>,
+    /// Only read from the config file. Cross-validates recorded ETH prices when set, see
+    /// `usd_price::sources`.
+    eth_price_max_divergence_percent: Option<f64>,
+    etherscan_api_key: Option<String>,
+    geth_url: Option<String>,
+    /// Only read from the config file. Price sources `heal-eth-prices` asks, in order, e.g.
+    /// `["bybit", "kraken"]`. Every source when absent.
+    heal_eth_price_sources: Option<Vec<String>>,
+    /// Store every transaction of synced blocks, see `execution_chain::transactions`.
+    #[serde(default)]
+    ingest_transactions: bool,
+    #[serde(default)]
+    jobs: HashMap<String, JobConfig>,
+    /// Only read from the config file, see `l2`.
+    #[serde(default)]
+    l2_chains: Vec<L2ChainConfig>,
+    #[serde(default)]
+    log_perf: bool,
+    /// NATS server to publish block events to, see `block_events`.
+    nats_url: Option<String>,
+    nats_subject: Option<String>,
+    /// `mainnet`, `holesky` or `sepolia`, see `network`.
+    #[serde(default)]
+    network: Network,
+    /// Only read from the config file. Genesis and fork points the network doesn't come with.
+    #[serde(default)]
+    network_overrides: NetworkOverrides,
+    opsgenie_api_key: Option<String>,
+    pagerduty_routing_key: Option<String>,
+    #[serde(default)]
+    phoenix: PhoenixConfig,
+    port: Option<String>,
+    #[serde(default)]
+    pretty_print: bool,
+    /// Port the sync services serve their health probes on, see `health`.
+    probe_port: Option<String>,
+    redis_url: Option<String>,
+    /// Only read from the config file, see `censorship`.
+    #[serde(default)]
+    relays: Vec<RelayConfig>,
+    #[serde(default)]
+    retention: HashMap<String, RetentionConfig>,
+    /// Only read from the config file, see `supply_milestones`.
+    supply_milestone_step_eth: Option<u64>,
+    telegram_bot_token: Option<String>,
+    telegram_chat_id: Option<String>,
+    /// Only read from the config file, see `webhooks`.
+    #[serde(default)]
+    webhooks: Vec<WebhookConfig>,
+}
+
+type StringField = (
+    &'static str,
+    fn(&Config) -> &Option<String>,
+    fn(&mut Config) -> &mut Option<String>,
+);
+
+type BoolField = (&'static str, fn(&mut Config) -> &mut bool);
+
+/// String settings which may be set in the env, under the uppercase version of their name.
+const STRING_FIELDS: [StringField; 18] = [
+    ("beacon_url", |c| &c.beacon_url, |c| &mut c.beacon_url),
+    (
+        "bigquery_dataset",
+        |c| &c.bigquery_dataset,
+        |c| &mut c.bigquery_dataset,
+    ),
+    (
+        "clickhouse_database",
+        |c| &c.clickhouse_database,
+        |c| &mut c.clickhouse_database,
+    ),
+    (
+        "clickhouse_url",
+        |c| &c.clickhouse_url,
+        |c| &mut c.clickhouse_url,
+    ),
+    (
+        "database_read_url",
+        |c| &c.database_read_url,
+        |c| &mut c.database_read_url,
+    ),
+    ("database_url", |c| &c.database_url, |c| &mut c.database_url),
+    (
+        "discord_webhook_url",
+        |c| &c.discord_webhook_url,
+        |c| &mut c.discord_webhook_url,
+    ),
+    (
+        "etherscan_api_key",
+        |c| &c.etherscan_api_key,
+        |c| &mut c.etherscan_api_key,
+    ),
+    ("geth_url", |c| &c.geth_url, |c| &mut c.geth_url),
+    ("nats_subject", |c| &c.nats_subject, |c| &mut c.nats_subject),
+    ("nats_url", |c| &c.nats_url, |c| &mut c.nats_url),
+    (
+        "opsgenie_api_key",
+        |c| &c.opsgenie_api_key,
+        |c| &mut c.opsgenie_api_key,
+    ),
+    (
+        "pagerduty_routing_key",
+        |c| &c.pagerduty_routing_key,
+        |c| &mut c.pagerduty_routing_key,
+    ),
+    ("port", |c| &c.port, |c| &mut c.port),
+    ("probe_port", |c| &c.probe_port, |c| &mut c.probe_port),
+    ("redis_url", |c| &c.redis_url, |c| &mut c.redis_url),
+    (
+        "telegram_bot_token",
+        |c| &c.telegram_bot_token,
+        |c| &mut c.telegram_bot_token,
+    ),
+    (
+        "telegram_chat_id",
+        |c| &c.telegram_chat_id,
+        |c| &mut c.telegram_chat_id,
+    ),
+];
+
+/// Flags which may be set in the env, under the uppercase version of their name.
+const BOOL_FIELDS: [BoolField; 4] = [
+    ("check_receipt_burn", |c| &mut c.check_receipt_burn),
+    ("ingest_transactions", |c| &mut c.ingest_transactions),
+    ("log_perf", |c| &mut c.log_perf),
+    ("pretty_print", |c| &mut c.pretty_print),
+];
+
+// Every binary talks to the database, these are checked on load. Others are checked when first
+// used, as most binaries only need a few.
+const REQUIRED_FIELDS: [&str; 1] = ["database_url"];
+
+/// Names of the sinks phoenix can alert, see `phoenix::alerts`.
+const ALERT_SINKS: [&str; 4] = ["discord", "opsgenie", "pagerduty", "telegram"];
+
+impl Config {
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let path_str = path.display().to_string();
+        let str = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path_str.clone(),
+            source,
+        })?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => {
+                serde_yaml::from_str(&str).map_err(|source| ConfigError::ParseYaml {
+                    path: path_str,
+                    source,
+                })
+            }
+            _ => toml::from_str(&str).map_err(|source| ConfigError::Parse {
+                path: path_str,
+                source,
+            }),
+        }
+    }
+
+    fn apply_env_overrides(&mut self) {
+        for (name, _, get_mut) in STRING_FIELDS {
+            if let Some(value) = env::get_env_var(&name.to_uppercase()) {
+                *get_mut(self) = Some(value);
+            }
+        }
+
+        for (name, get_mut) in BOOL_FIELDS {
+            let key = name.to_uppercase();
+            if env::get_env_var(&key).is_some() {
+                *get_mut(self) = env::get_env_bool(&key);
+            }
+        }
+
+        if let Some(network) = env::get_env_var("NETWORK") {
+            self.network = network.parse().unwrap_or_else(|err| panic!("{err}"));
+        }
+    }
+
+    fn field_value(&self, field: &str) -> Option<&String> {
+        let (_, get, _) = STRING_FIELDS
+            .into_iter()
+            .find(|(name, _, _)| *name == field)
+            .unwrap_or_else(|| panic!("unknown config field {field}"));
+        get(self).as_ref()
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let missing_fields = REQUIRED_FIELDS
+            .into_iter()
+            .filter(|field| self.field_value(field).is_none())
+            .collect::<Vec<_>>();
+
+        if !missing_fields.is_empty() {
+            return Err(ConfigError::MissingFields(missing_fields));
+        }
+
+        // A misspelled sink would silently drop the monitor's alerts.
+        for (monitor, monitor_config) in &self.phoenix.monitors {
+            let unknown_sinks = monitor_config
+                .sinks
+                .iter()
+                .flatten()
+                .filter(|sink| !ALERT_SINKS.contains(&sink.as_str()))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if !unknown_sinks.is_empty() {
+                return Err(ConfigError::UnknownAlertSinks {
+                    monitor: monitor.clone(),
+                    sinks: unknown_sinks,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match env::get_env_var("CONFIG_PATH") {
+            Some(path) => Self::from_file(Path::new(&path))?,
+            None => Self::default(),
+        };
+        config.apply_env_overrides();
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn require(&self, field: &'static str) -> &str {
+        self.field_value(field)
+            .unwrap_or_else(|| panic!("{}", ConfigError::MissingFields(vec![field])))
+    }
+
+    pub fn beacon_url(&self) -> &str {
+        self.require("beacon_url")
+    }
+
+    pub fn bigquery_dataset(&self) -> Option<&str> {
+        self.bigquery_dataset.as_deref()
+    }
+
+    pub fn burn_usd_valuation(&self) -> UsdValuation {
+        self.burn_usd_valuation
+    }
+
+    pub fn censoring_builders(&self) -> &[String] {
+        &self.censoring_builders
+    }
+
+    pub fn check_receipt_burn(&self) -> bool {
+        self.check_receipt_burn
+    }
+
+    pub fn clickhouse_database(&self) -> Option<&str> {
+        self.clickhouse_database.as_deref()
+    }
+
+    pub fn clickhouse_url(&self) -> Option<&str> {
+        self.clickhouse_url.as_deref()
+    }
+
+    pub fn database_read_url(&self) -> Option<&str> {
+        self.database_read_url.as_deref()
+    }
+
+    pub fn database_url(&self) -> &str {
+        self.require("database_url")
+    }
+
+    pub fn discord_webhook_url(&self) -> Option<&str> {
+        self.discord_webhook_url.as_deref()
+    }
+
+    pub fn eth_price_max_divergence_percent(&self) -> Option<f64> {
+        self.eth_price_max_divergence_percent
+    }
+
+    pub fn etherscan_api_key(&self) -> &str {
+        self.require("etherscan_api_key")
+    }
+
+    pub fn geth_url(&self) -> &str {
+        self.require("geth_url")
+    }
+
+    pub fn heal_eth_price_sources(&self) -> Option<&[String]> {
+        self.heal_eth_price_sources.as_deref()
+    }
+
+    pub fn ingest_transactions(&self) -> bool {
+        self.ingest_transactions
+    }
+
+    pub fn job(&self, name: &str) -> Option<&JobConfig> {
+        self.jobs.get(name)
+    }
+
+    pub fn l2_chains(&self) -> &[L2ChainConfig] {
+        &self.l2_chains
+    }
+
+    pub fn log_perf(&self) -> bool {
+        self.log_perf
+    }
+
+    #[cfg(feature = "nats")]
+    pub fn nats_subject(&self) -> Option<&str> {
+        self.nats_subject.as_deref()
+    }
+
+    pub fn nats_url(&self) -> Option<&str> {
+        self.nats_url.as_deref()
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn network_overrides(&self) -> &NetworkOverrides {
+        &self.network_overrides
+    }
+
+    pub fn opsgenie_api_key(&self) -> Option<&str> {
+        self.opsgenie_api_key.as_deref()
+    }
+
+    pub fn pagerduty_routing_key(&self) -> Option<&str> {
+        self.pagerduty_routing_key.as_deref()
+    }
+
+    pub fn phoenix(&self) -> &PhoenixConfig {
+        &self.phoenix
+    }
+
+    pub fn port(&self) -> Option<&str> {
+        self.port.as_deref()
+    }
+
+    pub fn probe_port(&self) -> Option<&str> {
+        self.probe_port.as_deref()
+    }
+
+    pub fn pretty_print(&self) -> bool {
+        self.pretty_print
+    }
+
+    pub fn redis_url(&self) -> Option<&str> {
+        self.redis_url.as_deref()
+    }
+
+    pub fn relays(&self) -> &[RelayConfig] {
+        &self.relays
+    }
+
+    pub fn retention(&self, series: &str) -> Option<&RetentionConfig> {
+        self.retention.get(series)
+    }
+
+    pub fn supply_milestone_step_eth(&self) -> Option<u64> {
+        self.supply_milestone_step_eth
+    }
+
+    pub fn telegram_bot_token(&self) -> Option<&str> {
+        self.telegram_bot_token.as_deref()
+    }
+
+    pub fn telegram_chat_id(&self) -> Option<&str> {
+        self.telegram_chat_id.as_deref()
+    }
+
+    pub fn webhooks(&self) -> &[WebhookConfig] {
+        &self.webhooks
+    }
+}
+
+lazy_static! {
+    pub static ref CONFIG: Config = Config::load().unwrap_or_else(|err| panic!("{err}"));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{execution_chain::BlockNumber, l2::L2Kind};
+
+    use super::*;
+
+    #[test]
+    fn parse_config_test() {
+        let config: Config = toml::from_str(
+            r#"
+            database_url = "postgresql://localhost/testdb"
+            log_perf = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.database_url(), "postgresql://localhost/testdb");
+        assert!(config.log_perf());
+        assert!(config.port().is_none());
+    }
+
+    #[test]
+    fn parse_job_config_test() {
+        let config: Config = toml::from_str(
+            r#"
+            [jobs.update-issuance-estimate]
+            interval_secs = 600
+            "#,
+        )
+        .unwrap();
+        let job_config = config.job("update-issuance-estimate").unwrap();
+        assert_eq!(job_config.interval_secs, Some(600));
+        assert!(job_config.enabled.is_none());
+        assert!(config.job("heal-eth-prices").is_none());
+    }
+
+    #[test]
+    fn parse_l2_chains_test() {
+        let config: Config = toml::from_str(
+            r#"
+            [[l2_chains]]
+            kind = "op_stack"
+            name = "base"
+            rpc_url = "https://base.example"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.l2_chains().len(), 1);
+        assert_eq!(config.l2_chains()[0].kind, L2Kind::OpStack);
+    }
+
+    #[test]
+    fn parse_relays_test() {
+        let config: Config = toml::from_str(
+            r#"
+            censoring_builders = ["0xbuilder"]
+
+            [[relays]]
+            name = "flashbots"
+            url = "https://relay.example"
+            censoring = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.censoring_builders(), ["0xbuilder".to_string()]);
+        assert_eq!(config.relays().len(), 1);
+        assert!(config.relays()[0].censoring);
+    }
+
+    #[test]
+    fn parse_network_test() {
+        let config: Config = toml::from_str(
+            r#"
+            network = "holesky"
+
+            [network_overrides]
+            shapella_block_number = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.network(), Network::Holesky);
+        assert_eq!(
+            config.network_overrides().shapella_block_number,
+            Some(BlockNumber(10))
+        );
+        assert_eq!(Config::default().network(), Network::Mainnet);
+    }
+
+    #[test]
+    fn parse_yaml_config_test() {
+        let path = std::env::temp_dir().join("eth-analysis-parse-yaml-config-test.yaml");
+        fs::write(
+            &path,
+            "database_url: postgresql://localhost/testdb\njobs:\n  heal-eth-prices:\n    enabled: false\n",
+        )
+        .unwrap();
+        let config = Config::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(config.database_url(), "postgresql://localhost/testdb");
+        assert_eq!(config.job("heal-eth-prices").unwrap().enabled, Some(false));
+    }
+
+    #[test]
+    fn string_fields_are_sorted_and_unique_test() {
+        let names = STRING_FIELDS.map(|(name, _, _)| name);
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn unknown_field_test() {
+        let result = toml::from_str::<Config>(r#"databse_url = "postgresql://localhost""#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn missing_fields_test() {
+        let config = Config::default();
+        let err = config.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "missing required config fields, set them in the config file or env: database_url"
+        );
+    }
+
+    #[test]
+    fn unknown_alert_sinks_test() {
+        let config: Config = toml::from_str(
+            r#"
+            database_url = "postgresql://localhost/testdb"
+
+            [phoenix.monitors.eth-price-stats]
+            sinks = ["telegram", "slack"]
+            "#,
+        )
+        .unwrap();
+        let err = config.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown alert sinks for phoenix monitor eth-price-stats: slack, expected any of discord, opsgenie, pagerduty, telegram"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! # Contract Deployments
+//! Stores the contract creation transactions, those without a recipient, of every synced block,
+//! and publishes how many contracts were deployed per day and per time frame. Contracts created
+//! by other contracts don't show up as transactions and aren't counted. Neither do we look at the
+//! receipt status, creations which reverted are counted too.
+//!
+//! Stored from ingested transactions, see `execution_chain::transactions`.
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use cached::proc_macro::{cached, once};
+use chrono::{DateTime, Utc};
+use enum_iterator::all;
+use serde::Serialize;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    analysis::Analysis,
+    caching::{self, CacheKey},
+    execution_chain::{BlockNumber, BlockTransaction, ExecutionNodeBlock},
+    rollback::RollbackPoint,
+    time_frames::TimeFrame,
+};
+
+pub async fn store_contract_deployments(
+    executor: impl PgExecutor<'_>,
+    block: &ExecutionNodeBlock,
+    transactions: &[BlockTransaction],
+) {
+    let creations = transactions
+        .iter()
+        .filter(|transaction| transaction.to.is_none())
+        .collect::<Vec<_>>();
+
+    if creations.is_empty() {
+        return;
+    }
+
+    let transaction_indices = creations
+        .iter()
+        .map(|transaction| transaction.transaction_index)
+        .collect::<Vec<_>>();
+    let deployers = creations
+        .iter()
+        .map(|transaction| transaction.from.clone())
+        .collect::<Vec<_>>();
+    let contract_addresses = creations
+        .iter()
+        .map(|transaction| transaction.contract_address.clone())
+        .collect::<Vec<_>>();
+
+    sqlx::query!(
+        "
+        INSERT INTO contract_deployments (
+            block_number,
+            timestamp,
+            transaction_index,
+            deployer,
+            contract_address
+        )
+        SELECT $1, $2, * FROM UNNEST($3::int[], $4::text[], $5::text[])
+        ",
+        block.number.0,
+        block.timestamp,
+        &transaction_indices,
+        &deployers,
+        &contract_addresses as &[Option<String>],
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+async fn delete_contract_deployments(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: &BlockNumber,
+) {
+    sqlx::query!(
+        "DELETE FROM contract_deployments WHERE block_number >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub struct ContractDeploymentsRollback;
+
+#[async_trait]
+impl Analysis for ContractDeploymentsRollback {
+    fn name(&self) -> &'static str {
+        "contract_deployments"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            delete_contract_deployments(transaction, block_number_gte).await;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ContractDeploymentsOnDay {
+    contract_count: i64,
+    /// Start of the day.
+    timestamp: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct ContractDeployments {
+    block_number: BlockNumber,
+    by_day: Vec<ContractDeploymentsOnDay>,
+    by_time_frame: HashMap<TimeFrame, i64>,
+}
+
+async fn get_contract_count(executor: impl PgExecutor<'_>, start_timestamp: &DateTime<Utc>) -> i64 {
+    sqlx::query!(
+        r#"SELECT COUNT(*) AS "contract_count!" FROM contract_deployments WHERE timestamp >= $1"#,
+        start_timestamp
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .contract_count
+}
+
+async fn get_contract_deployments_by_day(
+    executor: impl PgExecutor<'_>,
+) -> Vec<ContractDeploymentsOnDay> {
+    sqlx::query_as!(
+        ContractDeploymentsOnDay,
+        r#"
+        SELECT
+            DATE_TRUNC('day', timestamp) AS "timestamp!",
+            COUNT(*) AS "contract_count!"
+        FROM contract_deployments
+        GROUP BY 1
+        ORDER BY 1 ASC
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+}
+
+// Growing time frames barely change from block to block.
+#[cached(
+    key = "String",
+    convert = r#"{start_timestamp.to_string()}"#,
+    time = 3600
+)]
+async fn get_contract_count_cached_1h(db_pool: &PgPool, start_timestamp: &DateTime<Utc>) -> i64 {
+    get_contract_count(db_pool, start_timestamp).await
+}
+
+// Past days don't change, today's count lagging an hour is fine for a daily chart.
+#[once(time = 3600)]
+async fn get_contract_deployments_by_day_cached_1h(
+    db_pool: &PgPool,
+) -> Vec<ContractDeploymentsOnDay> {
+    get_contract_deployments_by_day(db_pool).await
+}
+
+pub async fn on_new_block(db_pool: &PgPool, block: &ExecutionNodeBlock) {
+    debug!("calculating contract deployments");
+
+    let mut by_time_frame = HashMap::new();
+    for time_frame in all::<TimeFrame>() {
+        let start_timestamp = time_frame.start_timestamp(block);
+        let contract_count = match time_frame {
+            TimeFrame::Growing(_) => get_contract_count_cached_1h(db_pool, &start_timestamp).await,
+            TimeFrame::Limited(_) => get_contract_count(db_pool, &start_timestamp).await,
+        };
+        by_time_frame.insert(time_frame, contract_count);
+    }
+
+    let contract_deployments = ContractDeployments {
+        block_number: block.number,
+        by_day: get_contract_deployments_by_day_cached_1h(db_pool).await,
+        by_time_frame,
+    };
+
+    caching::update_and_publish(
+        db_pool,
+        &CacheKey::ContractDeployments,
+        contract_deployments,
+    )
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, DurationRound};
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{BlockTransactionBuilder, ExecutionNodeBlockBuilder},
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn contract_deployments_test(test_db: &TestDb) {
+        let block = ExecutionNodeBlockBuilder::new("contract_deployments").build();
+        store_contract_deployments(
+            &test_db.pool,
+            &block,
+            &[
+                BlockTransactionBuilder::new("contract_deployments", 0)
+                    .with_contract_address("0xcontract_0")
+                    .build(),
+                BlockTransactionBuilder::new("contract_deployments", 1).build(),
+                BlockTransactionBuilder::new("contract_deployments", 2)
+                    .with_contract_address("0xcontract_2")
+                    .build(),
+            ],
+        )
+        .await;
+
+        assert_eq!(get_contract_count(&test_db.pool, &block.timestamp).await, 2);
+        assert_eq!(
+            get_contract_deployments_by_day(&test_db.pool).await,
+            vec![ContractDeploymentsOnDay {
+                contract_count: 2,
+                timestamp: block.timestamp.duration_trunc(Duration::days(1)).unwrap(),
+            }]
+        );
+
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        ContractDeploymentsRollback
+            .on_rollback(
+                &mut connection,
+                &RollbackPoint::BlockNumbersGte(block.number),
+            )
+            .await;
+        assert_eq!(
+            get_contract_count(&mut *connection, &block.timestamp).await,
+            0
+        );
+    }
+}
@@ -1,5 +1,8 @@
+mod backfill_gaps;
 mod check_beacon_state_gaps;
 mod check_blocks_gaps;
 
+pub use backfill_gaps::backfill_gaps;
 pub use check_beacon_state_gaps::check_beacon_state_gaps;
 pub use check_blocks_gaps::check_blocks_gaps;
+pub use check_blocks_gaps::check_blocks_gaps_with_refetch;
@@ -0,0 +1,208 @@
+//! Runs alongside the sync services. Every cycle we look for gaps in `blocks_next` and
+//! `beacon_states`, and fill them by fetching what is missing from the nodes. Requests are rate
+//! limited so backfilling after an incident doesn't starve the sync services of node capacity.
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio::time::sleep;
+use tracing::{debug, error, info};
+
+use crate::{
+    beacon_chain::{self, BeaconNode, BeaconNodeHttp, Slot},
+    db,
+    execution_chain::{self, BlockNumber, ExecutionNode},
+    log,
+    usd_price::{EthPriceStore, EthPriceStorePostgres},
+};
+
+const CYCLE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const REQUEST_INTERVAL: Duration = Duration::from_millis(200);
+// Caps the work per cycle, large gaps are filled over several cycles.
+const MAX_BACKFILL_PER_CYCLE: i64 = 1000;
+
+/// An inclusive range of missing numbers.
+#[derive(Debug, PartialEq)]
+struct Gap {
+    start: i64,
+    end: i64,
+}
+
+async fn execution_gaps(db_pool: &PgPool) -> Vec<Gap> {
+    sqlx::query_as!(
+        Gap,
+        r#"
+        SELECT
+            number + 1 AS "start!",
+            next_number - 1 AS "end!"
+        FROM (
+            SELECT
+                number,
+                LEAD(number) OVER (ORDER BY number) AS next_number
+            FROM blocks_next
+        ) numbers
+        WHERE next_number > number + 1
+        ORDER BY 1 ASC
+        LIMIT $1
+        "#,
+        MAX_BACKFILL_PER_CYCLE
+    )
+    .fetch_all(db_pool)
+    .await
+    .unwrap()
+}
+
+async fn beacon_gaps(db_pool: &PgPool) -> Vec<Gap> {
+    sqlx::query_as!(
+        Gap,
+        r#"
+        SELECT
+            (slot + 1)::INT8 AS "start!",
+            (next_slot - 1)::INT8 AS "end!"
+        FROM (
+            SELECT
+                slot,
+                LEAD(slot) OVER (ORDER BY slot) AS next_slot
+            FROM beacon_states
+        ) slots
+        WHERE next_slot > slot + 1
+        ORDER BY 1 ASC
+        LIMIT $1
+        "#,
+        MAX_BACKFILL_PER_CYCLE
+    )
+    .fetch_all(db_pool)
+    .await
+    .unwrap()
+}
+
+fn numbers_from_gaps(gaps: &[Gap]) -> impl Iterator<Item = i64> + '_ {
+    gaps.iter()
+        .flat_map(|gap| gap.start..=gap.end)
+        .take(MAX_BACKFILL_PER_CYCLE as usize)
+}
+
+async fn backfill_execution_gaps(
+    db_pool: &PgPool,
+    execution_node: &ExecutionNode,
+    eth_price_store: &impl EthPriceStore,
+) {
+    let gaps = execution_gaps(db_pool).await;
+    if gaps.is_empty() {
+        debug!("no execution block gaps");
+        return;
+    }
+
+    info!(gap_count = gaps.len(), "backfilling execution block gaps");
+
+    for number in numbers_from_gaps(&gaps).map(BlockNumber) {
+        let block = match execution_node.get_block_by_number(&number).await {
+            Some(block) => block,
+            None => {
+                error!(%number, "missing block not available on-chain, skipping");
+                continue;
+            }
+        };
+
+        match eth_price_store.get_eth_price_by_block(&block).await {
+            Ok(eth_price) => {
+                execution_chain::store_block(db_pool, &block, eth_price).await;
+                debug!(%number, "backfilled execution block");
+            }
+            Err(err) => error!(%number, %err, "no eth price for missing block, skipping"),
+        }
+
+        sleep(REQUEST_INTERVAL).await;
+    }
+}
+
+async fn backfill_beacon_gaps(db_pool: &PgPool, beacon_node: &BeaconNodeHttp) {
+    let gaps = beacon_gaps(db_pool).await;
+    if gaps.is_empty() {
+        debug!("no beacon state gaps");
+        return;
+    }
+
+    info!(gap_count = gaps.len(), "backfilling beacon state gaps");
+
+    // Slots come from an INT4 column, they fit in an i32.
+    for slot in numbers_from_gaps(&gaps).map(|slot| Slot(slot as i32)) {
+        let state_root = match beacon_node.get_state_root_by_slot(&slot).await {
+            Ok(Some(state_root)) => state_root,
+            Ok(None) => {
+                error!(%slot, "missing state root not available on-chain, skipping");
+                continue;
+            }
+            Err(err) => {
+                error!(%slot, %err, "failed to get state root, skipping");
+                continue;
+            }
+        };
+
+        match beacon_chain::sync_slot_by_state_root(db_pool, beacon_node, &state_root, &slot).await
+        {
+            Ok(()) => debug!(%slot, "backfilled beacon state"),
+            Err(err) => error!(%slot, %err, "failed to backfill beacon state"),
+        }
+
+        sleep(REQUEST_INTERVAL).await;
+    }
+}
+
+pub async fn backfill_gaps() {
+    log::init_with_env();
+
+    info!("starting gap backfill daemon");
+
+    let db_pool = db::get_db_pool("backfill-gaps").await;
+    let execution_node = ExecutionNode::connect().await;
+    let beacon_node = BeaconNodeHttp::new();
+    let eth_price_store = EthPriceStorePostgres::new(db_pool.clone());
+
+    loop {
+        backfill_execution_gaps(&db_pool, &execution_node, &eth_price_store).await;
+        backfill_beacon_gaps(&db_pool, &beacon_node).await;
+
+        sleep(CYCLE_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{block_store, ExecutionNodeBlockBuilder},
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn execution_gaps_test(test_db: &TestDb) {
+        let block_1 = ExecutionNodeBlockBuilder::new("execution_gaps").build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1).build();
+        let block_3 = ExecutionNodeBlockBuilder::from_parent(&block_2).build();
+        let block_4 = ExecutionNodeBlockBuilder::from_parent(&block_3).build();
+
+        block_store::store_block(&test_db.pool, &block_1, 1.0).await;
+        block_store::store_block(&test_db.pool, &block_4, 1.0).await;
+
+        let gaps = execution_gaps(&test_db.pool).await;
+
+        assert_eq!(
+            gaps,
+            vec![Gap {
+                start: block_2.number.0,
+                end: block_3.number.0
+            }]
+        );
+    }
+
+    #[test]
+    fn numbers_from_gaps_test() {
+        let gaps = vec![Gap { start: 1, end: 2 }, Gap { start: 5, end: 5 }];
+        let numbers = numbers_from_gaps(&gaps).collect::<Vec<i64>>();
+        assert_eq!(numbers, vec![1, 2, 5]);
+    }
+}
@@ -1,54 +1,135 @@
 use anyhow::Result;
-use futures::{StreamExt, TryStreamExt};
 use pit_wall::Progress;
-use sqlx::{Connection, Row};
-use tracing::{debug, error, info};
+use sqlx::PgPool;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    db,
+    execution_chain::{self, BlockNumber, ExecutionNode},
+    log,
+};
+
+struct StoredBlock {
+    number: BlockNumber,
+    hash: String,
+    parent_hash: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Discontinuities {
+    missing_numbers: Vec<BlockNumber>,
+    /// Blocks whose parent hash does not match the hash of the block stored before them.
+    parent_mismatches: Vec<BlockNumber>,
+}
 
-use crate::{db, execution_chain::ExecutionNode, log};
+/// Expects blocks ordered by number.
+fn find_discontinuities(blocks: &[StoredBlock]) -> Discontinuities {
+    let mut discontinuities = Discontinuities::default();
+
+    for pair in blocks.windows(2) {
+        let (last, current) = (&pair[0], &pair[1]);
+
+        if current.number != last.number + 1 {
+            error!(
+                last_number = %last.number,
+                current_number = %current.number,
+                "missing blocks"
+            );
+            discontinuities
+                .missing_numbers
+                .extend(BlockNumber::range_inclusive(
+                    last.number + 1,
+                    current.number - 1,
+                ));
+        } else if current.parent_hash != last.hash {
+            error!(
+                number = %current.number,
+                parent_hash = current.parent_hash,
+                last_hash = last.hash,
+                "parent hash does not match last block hash"
+            );
+            discontinuities.parent_mismatches.push(current.number);
+        }
+    }
+
+    discontinuities
+}
 
+/// Checks `blocks_next` for missing block numbers, blocks that don't link to their parent, and
+/// blocks which no longer match the chain. Pass `--refetch` to fetch and store missing blocks
+/// before the slow on-chain check. Mismatching blocks are only reported, replacing them means
+/// rolling back everything after.
 pub async fn check_blocks_gaps() -> Result<()> {
+    let should_refetch = std::env::args().any(|arg| arg == "--refetch");
+    check_blocks_gaps_with_refetch(should_refetch).await
+}
+
+async fn refetch_missing_blocks(
+    db_pool: &PgPool,
+    execution_node: &ExecutionNode,
+    missing_numbers: &[BlockNumber],
+) {
+    info!(count = missing_numbers.len(), "refetching missing blocks");
+
+    for number in missing_numbers {
+        let block = execution_node
+            .get_block_by_number(number)
+            .await
+            .expect("expect missing historic block to be available on-chain");
+        execution_chain::store_missing_block(db_pool, execution_node, &block).await;
+        debug!(%number, "stored missing block");
+    }
+
+    info!("done refetching missing blocks");
+}
+
+pub async fn check_blocks_gaps_with_refetch(should_refetch: bool) -> Result<()> {
     log::init_with_env();
 
-    info!("checking for gaps in blocks");
-
-    let mut connection =
-        sqlx::PgConnection::connect(&db::get_db_url_with_name("check-block-gaps")).await?;
-
-    // Store blocks fetched, we run through them twice.
-    let mut blocks = vec![];
-
-    // Fast check by number.
-    info!("fast checking stored block numbers for gaps");
-
-    {
-        let mut rows = sqlx::query(
-            "
-            SELECT number, hash FROM blocks
-            ORDER BY number ASC
-        ",
-        )
-        .fetch(&mut connection)
-        .map(|row| {
-            row.map(|row| {
-                let number: i32 = row.get("number");
-                let hash: String = row.get("hash");
-                (number, hash)
-            })
-        });
-
-        let mut last_number = None;
-        while let Some((number, hash)) = rows.try_next().await? {
-            blocks.push((number, hash));
-            if let Some(last_number) = last_number {
-                if last_number != number - 1 {
-                    error!("last number: {last_number}, current number: {number}")
-                }
-            }
+    info!(should_refetch, "checking for gaps in blocks");
+
+    let db_pool = db::get_db_pool("check-block-gaps").await;
+
+    // Fast check by number and parent hash.
+    info!("fast checking stored blocks for gaps and parent hash mismatches");
+
+    let blocks = sqlx::query!(
+        "
+        SELECT number, hash, parent_hash FROM blocks_next
+        ORDER BY number ASC
+        "
+    )
+    .fetch_all(&db_pool)
+    .await?
+    .into_iter()
+    .map(|row| StoredBlock {
+        number: BlockNumber(row.number),
+        hash: row.hash,
+        parent_hash: row.parent_hash,
+    })
+    .collect::<Vec<_>>();
+
+    let discontinuities = find_discontinuities(&blocks);
+
+    info!(
+        missing_count = discontinuities.missing_numbers.len(),
+        parent_mismatch_count = discontinuities.parent_mismatches.len(),
+        "done checking blocks for gaps"
+    );
+
+    if !discontinuities.parent_mismatches.is_empty() {
+        warn!(
+            parent_mismatches = ?discontinuities.parent_mismatches,
+            "found blocks not linking to their parent, candidates for refetching after a rollback"
+        );
+    }
 
-            last_number = Some(number);
-        }
+    let execution_node = ExecutionNode::connect().await;
 
-        info!("done checking blocks for gaps");
+    if should_refetch && !discontinuities.missing_numbers.is_empty() {
+        refetch_missing_blocks(&db_pool, &execution_node, &discontinuities.missing_numbers).await;
+    } else if !discontinuities.missing_numbers.is_empty() {
+        warn!("found missing blocks, rerun with --refetch to fetch and store them");
     }
 
     // Slow check on-chain by hash.
@@ -56,13 +137,19 @@ pub async fn check_blocks_gaps() -> Result<()> {
 
     let mut progress = Progress::new("check on-chain hashes", blocks.len().try_into().unwrap());
 
-    let execution_node = ExecutionNode::connect().await;
-
-    for (number, stored_hash) in blocks {
-        let on_chain = execution_node.get_block_by_number(&number).await.unwrap();
-
-        if stored_hash != on_chain.hash {
-            error!(stored_hash, on_chain.hash, "block mismatch",);
+    for block in blocks.iter() {
+        let on_chain = execution_node
+            .get_block_by_number(&block.number)
+            .await
+            .unwrap();
+
+        if block.hash != on_chain.hash {
+            error!(
+                number = %block.number,
+                stored_hash = block.hash,
+                on_chain.hash,
+                "block mismatch"
+            );
         }
 
         if progress.work_done % 100 == 0 && progress.work_done != 0 {
@@ -76,3 +163,34 @@ pub async fn check_blocks_gaps() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_block(number: BlockNumber, hash: &str, parent_hash: &str) -> StoredBlock {
+        StoredBlock {
+            number,
+            hash: hash.to_string(),
+            parent_hash: parent_hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn find_discontinuities_test() {
+        let blocks = vec![
+            make_block(BlockNumber(1), "0x1", "0x0"),
+            make_block(BlockNumber(2), "0x2", "0x1"),
+            make_block(BlockNumber(5), "0x5", "0x4"),
+            make_block(BlockNumber(6), "0x6", "0xbad"),
+        ];
+
+        assert_eq!(
+            find_discontinuities(&blocks),
+            Discontinuities {
+                missing_numbers: vec![BlockNumber(3), BlockNumber(4)],
+                parent_mismatches: vec![BlockNumber(6)],
+            }
+        );
+    }
+}
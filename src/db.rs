@@ -1,10 +1,10 @@
 use lazy_static::lazy_static;
 use sqlx::PgPool;
 
-use crate::env;
+use crate::config;
 
 lazy_static! {
-    pub static ref DB_URL: String = env::get_env_var_unsafe("DATABASE_URL");
+    pub static ref DB_URL: String = config::CONFIG.database_url().to_string();
 }
 
 pub fn get_db_url_with_name(name: &str) -> String {
@@ -17,6 +17,21 @@ pub async fn get_db_pool(name: &str) -> PgPool {
         .expect("expect DB to be available to connect")
 }
 
+/// Pool for heavy read-only queries, like burn sums over arbitrary ranges and supply over time,
+/// so they don't compete with the sync write path. Connects to the read replica when
+/// `database_read_url` is configured, to the primary otherwise. Replicas may lag slightly behind,
+/// work which reads what it or the sync just wrote should read from the primary instead.
+pub async fn get_read_db_pool(name: &str) -> PgPool {
+    match config::CONFIG.database_read_url() {
+        Some(database_read_url) => {
+            PgPool::connect(&format!("{database_read_url}?application_name={name}"))
+                .await
+                .expect("expect read replica to be available to connect")
+        }
+        None => get_db_pool(name).await,
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use async_trait::async_trait;
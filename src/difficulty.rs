@@ -0,0 +1,179 @@
+//! # Difficulty
+//! Historical proof-of-work difficulty from the pre-merge blocks we store. Serves per hour or per
+//! day the total difficulty reached, the average block difficulty, and an estimate of the network
+//! hashrate, the average difficulty over the average time between blocks. Total difficulty stopped
+//! growing at the merge, these series end there.
+//!
+//! Block sync starts in August 2022, the series cover what's stored before the merge.
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap, HeaderValue},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgExecutor;
+
+use crate::{execution_chain::MERGE_BLOCK_NUMBER, serve::StateExtension};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DifficultyInterval {
+    Hour,
+    #[default]
+    Day,
+}
+
+impl DifficultyInterval {
+    /// As understood by `date_trunc`.
+    fn field(&self) -> &'static str {
+        match self {
+            DifficultyInterval::Hour => "hour",
+            DifficultyInterval::Day => "day",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DifficultyPoint {
+    pub average_difficulty: f64,
+    pub block_count: i64,
+    /// Hashes per second, `None` with fewer than two blocks in the interval.
+    pub hashrate: Option<f64>,
+    /// Start of the interval.
+    pub timestamp: DateTime<Utc>,
+    /// At the last block of the interval.
+    pub total_difficulty: f64,
+}
+
+async fn get_difficulty_series(
+    executor: impl PgExecutor<'_>,
+    interval: DifficultyInterval,
+) -> Vec<DifficultyPoint> {
+    sqlx::query!(
+        r#"
+        SELECT
+            date_trunc($1, timestamp) AS "interval_start!",
+            COUNT(*) AS "block_count!",
+            AVG(difficulty)::FLOAT8 AS "average_difficulty!",
+            MAX(total_difficulty)::FLOAT8 AS "total_difficulty!",
+            EXTRACT(EPOCH FROM MAX(timestamp) - MIN(timestamp))::FLOAT8 AS "seconds!"
+        FROM blocks_next
+        WHERE number < $2
+        GROUP BY 1
+        ORDER BY 1 ASC
+        "#,
+        interval.field(),
+        MERGE_BLOCK_NUMBER.0,
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| {
+        let hashrate = (row.block_count > 1 && row.seconds > 0.0)
+            .then(|| row.average_difficulty / (row.seconds / (row.block_count - 1) as f64));
+
+        DifficultyPoint {
+            average_difficulty: row.average_difficulty,
+            block_count: row.block_count,
+            hashrate,
+            timestamp: row.interval_start,
+            total_difficulty: row.total_difficulty,
+        }
+    })
+    .collect()
+}
+
+#[derive(Deserialize)]
+pub struct DifficultyParams {
+    #[serde(default)]
+    interval: DifficultyInterval,
+}
+
+/// Takes an `interval` of `hour` or `day`, the default.
+pub async fn difficulty(
+    state: StateExtension,
+    Query(params): Query<DifficultyParams>,
+) -> impl IntoResponse {
+    let series = get_difficulty_series(&state.db_pool, params.interval).await;
+
+    // Pre-merge blocks don't change.
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=86400, stale-while-revalidate=86400"),
+    );
+
+    (headers, Json(series))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{block_store, ExecutionNodeBlock, ExecutionNodeBlockBuilder},
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn difficulty_series_test(test_db: &TestDb) {
+        let day_start = Utc.with_ymd_and_hms(2022, 8, 1, 0, 0, 0).unwrap();
+        let timestamps = [
+            day_start,
+            day_start + Duration::seconds(12),
+            day_start + Duration::seconds(24),
+            day_start + Duration::days(1),
+        ];
+
+        let mut parent: Option<ExecutionNodeBlock> = None;
+        for (index, timestamp) in timestamps.iter().enumerate() {
+            let builder = match &parent {
+                Some(parent) => ExecutionNodeBlockBuilder::from_parent(parent),
+                None => ExecutionNodeBlockBuilder::new("difficulty_series"),
+            };
+            let block = ExecutionNodeBlock {
+                difficulty: 12,
+                total_difficulty: 12 * (index as u128 + 1),
+                ..builder.with_timestamp(timestamp).build()
+            };
+            block_store::store_block(&test_db.pool, &block, 1.0).await;
+            parent = Some(block);
+        }
+
+        let series = get_difficulty_series(&test_db.pool, DifficultyInterval::Day).await;
+
+        assert_eq!(
+            series,
+            vec![
+                DifficultyPoint {
+                    average_difficulty: 12.0,
+                    block_count: 3,
+                    hashrate: Some(1.0),
+                    timestamp: day_start,
+                    total_difficulty: 36.0,
+                },
+                DifficultyPoint {
+                    average_difficulty: 12.0,
+                    block_count: 1,
+                    hashrate: None,
+                    timestamp: day_start + Duration::days(1),
+                    total_difficulty: 48.0,
+                },
+            ]
+        );
+        assert_eq!(
+            get_difficulty_series(&test_db.pool, DifficultyInterval::Hour)
+                .await
+                .len(),
+            2
+        );
+    }
+}
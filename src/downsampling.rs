@@ -0,0 +1,346 @@
+//! # Downsampling
+//! Rolls raw time series, a point per slot or per minute, up into hourly and daily points in
+//! `downsampled_series`, so long ranges don't need every raw row. The `downsample` job picks up
+//! from the last bucket it wrote, which may have been incomplete, its first run rolls up all
+//! history.
+//!
+//! Raw rows may be pruned once rolled up, by setting a retention window per series in the config
+//! file. Nothing is pruned by default.
+//!
+//! ```toml
+//! [retention.eth_prices]
+//! raw_retention_days = 90
+//! ```
+//!
+//! Pruned rows are gone for good. Readers of history, like average prices, price stats, supply
+//! milestones and the GraphQL series, read `hourly_or_raw_sql`, the hourly points before the first
+//! raw row and raw rows after. Pruning stops at the start of a day, so the hourly points line up
+//! with the raw rows. Exports and lookups of single rows only see the window, and
+//! `heal-eth-prices` will fetch pruned prices again unless given a `--start`.
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, DurationRound, TimeZone, Utc};
+use sqlx::{PgExecutor, PgPool};
+use tracing::{debug, info};
+
+use crate::config;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregation {
+    /// The last value in the bucket, for levels like supply.
+    Last,
+    Mean,
+}
+
+impl Aggregation {
+    fn sql(&self) -> &'static str {
+        match self {
+            Self::Last => "(ARRAY_AGG(value ORDER BY timestamp DESC))[1]",
+            Self::Mean => "AVG(value)",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hour => "h1",
+            Self::Day => "d1",
+        }
+    }
+
+    /// The `DATE_TRUNC` field.
+    fn trunc_field(&self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+        }
+    }
+}
+
+const RESOLUTIONS: [Resolution; 2] = [Resolution::Hour, Resolution::Day];
+
+/// A table with a `timestamp` column and a value to roll up.
+#[derive(Debug)]
+pub struct RawSeries {
+    /// Name in `downsampled_series`, and under `[retention]` in the config file.
+    pub name: &'static str,
+    pub table: &'static str,
+    pub value_column: &'static str,
+    pub aggregation: Aggregation,
+}
+
+pub const ETH_PRICES: RawSeries = RawSeries {
+    name: "eth_prices",
+    table: "eth_prices",
+    value_column: "ethusd",
+    aggregation: Aggregation::Mean,
+};
+
+pub const ETH_SUPPLY: RawSeries = RawSeries {
+    name: "eth_supply",
+    table: "eth_supply",
+    value_column: "supply",
+    aggregation: Aggregation::Last,
+};
+
+pub const VALIDATOR_BALANCES: RawSeries = RawSeries {
+    name: "validator_balances",
+    table: "beacon_validators_balance",
+    value_column: "gwei",
+    aggregation: Aggregation::Last,
+};
+
+pub const RAW_SERIES: [RawSeries; 3] = [ETH_PRICES, ETH_SUPPLY, VALIDATOR_BALANCES];
+
+/// Selects `timestamp`, `value` as NUMERIC, and `is_hourly` for the whole series: hourly points
+/// where raw rows were pruned, raw rows after. Without retention there are no hourly points before
+/// the first raw row, and this is just the raw rows. Meant as a subquery.
+pub fn hourly_or_raw_sql(series: &RawSeries) -> String {
+    format!(
+        "
+        SELECT timestamp, value, TRUE AS is_hourly
+        FROM downsampled_series
+        WHERE series = '{name}'
+        AND resolution = '{resolution}'
+        AND timestamp < (SELECT MIN(timestamp) FROM {table})
+        UNION ALL
+        SELECT timestamp, {value_column}::NUMERIC AS value, FALSE AS is_hourly
+        FROM {table}
+        ",
+        name = series.name,
+        resolution = Resolution::Hour.as_str(),
+        table = series.table,
+        value_column = series.value_column,
+    )
+}
+
+/// Start of the last bucket we wrote.
+async fn get_last_bucket(
+    executor: impl PgExecutor<'_>,
+    series: &RawSeries,
+    resolution: Resolution,
+) -> Option<DateTime<Utc>> {
+    sqlx::query!(
+        "
+        SELECT MAX(timestamp) AS timestamp
+        FROM downsampled_series
+        WHERE series = $1
+        AND resolution = $2
+        ",
+        series.name,
+        resolution.as_str()
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .timestamp
+}
+
+/// Writes every bucket from the one containing `from`, returns how many were written.
+async fn downsample_from(
+    executor: impl PgExecutor<'_>,
+    series: &RawSeries,
+    resolution: Resolution,
+    from: DateTime<Utc>,
+) -> u64 {
+    sqlx::query(&format!(
+        "
+        INSERT INTO downsampled_series (series, resolution, timestamp, value)
+        SELECT $1, $2, bucket, {aggregate}
+        FROM (
+            SELECT
+                DATE_TRUNC($3, timestamp AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS bucket,
+                timestamp,
+                {value_column}::NUMERIC AS value
+            FROM {table}
+            WHERE timestamp >= DATE_TRUNC($3, $4 AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'
+        ) raw
+        GROUP BY bucket
+        ON CONFLICT (series, resolution, timestamp) DO UPDATE SET
+            value = excluded.value
+        ",
+        aggregate = series.aggregation.sql(),
+        table = series.table,
+        value_column = series.value_column,
+    ))
+    .bind(series.name)
+    .bind(resolution.as_str())
+    .bind(resolution.trunc_field())
+    .bind(from)
+    .execute(executor)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+async fn prune_raw(
+    executor: impl PgExecutor<'_>,
+    series: &RawSeries,
+    before: DateTime<Utc>,
+) -> u64 {
+    sqlx::query(&format!(
+        "DELETE FROM {} WHERE timestamp < $1",
+        series.table
+    ))
+    .bind(before)
+    .execute(executor)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
+/// Raw rows before this may go. Only rows in complete daily buckets are pruned, the last daily
+/// bucket may still be missing rows, and what's left starts on a day.
+fn prune_before(
+    now: DateTime<Utc>,
+    raw_retention_days: u64,
+    last_daily_bucket: DateTime<Utc>,
+) -> DateTime<Utc> {
+    let window_start = (now - Duration::days(raw_retention_days as i64))
+        .duration_trunc(Duration::days(1))
+        .unwrap();
+    window_start.min(last_daily_bucket)
+}
+
+pub async fn downsample(db_pool: &PgPool) -> Result<()> {
+    for series in RAW_SERIES.iter() {
+        for resolution in RESOLUTIONS {
+            let from = get_last_bucket(db_pool, series, resolution)
+                .await
+                .unwrap_or_else(|| Utc.timestamp_opt(0, 0).unwrap());
+            let written = downsample_from(db_pool, series, resolution, from).await;
+            debug!(
+                series = series.name,
+                resolution = resolution.as_str(),
+                %from,
+                written,
+                "downsampled series"
+            );
+        }
+
+        let raw_retention_days = match config::CONFIG.retention(series.name) {
+            Some(retention_config) => retention_config.raw_retention_days,
+            None => continue,
+        };
+        if raw_retention_days == 0 {
+            bail!(
+                "raw_retention_days for {} should be at least 1",
+                series.name
+            );
+        }
+
+        let last_daily_bucket = match get_last_bucket(db_pool, series, Resolution::Day).await {
+            Some(last_daily_bucket) => last_daily_bucket,
+            None => continue,
+        };
+        let before = prune_before(Utc::now(), raw_retention_days, last_daily_bucket);
+        let pruned = prune_raw(db_pool, series, before).await;
+        info!(series = series.name, %before, pruned, "pruned raw rows");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{postgres::PgRow, Row};
+    use test_context::test_context;
+
+    use crate::db::tests::TestDb;
+
+    use super::*;
+
+    async fn get_values(
+        executor: impl PgExecutor<'_>,
+        series: &RawSeries,
+        resolution: Resolution,
+    ) -> Vec<(DateTime<Utc>, f64)> {
+        sqlx::query!(
+            r#"
+            SELECT timestamp, value::FLOAT8 AS "value!"
+            FROM downsampled_series
+            WHERE series = $1
+            AND resolution = $2
+            ORDER BY timestamp ASC
+            "#,
+            series.name,
+            resolution.as_str()
+        )
+        .fetch_all(executor)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.timestamp, row.value))
+        .collect()
+    }
+
+    #[test]
+    fn prune_before_test() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        let today = Utc.with_ymd_and_hms(2024, 3, 10, 0, 0, 0).unwrap();
+        assert_eq!(
+            prune_before(now, 2, today),
+            Utc.with_ymd_and_hms(2024, 3, 8, 0, 0, 0).unwrap()
+        );
+
+        // The rollup is behind, keep everything it hasn't finished.
+        let behind = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        assert_eq!(prune_before(now, 2, behind), behind);
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn downsample_eth_prices_test(test_db: &TestDb) {
+        sqlx::query!(
+            "
+            INSERT INTO eth_prices (timestamp, ethusd, provider, origin)
+            VALUES
+                ('2024-03-01T00:00:00Z', 3000.0, 'bybit', 'recorded'),
+                ('2024-03-01T00:59:00Z', 3100.0, 'bybit', 'recorded'),
+                ('2024-03-01T01:00:00Z', 3400.0, 'bybit', 'recorded')
+            "
+        )
+        .execute(&test_db.pool)
+        .await
+        .unwrap();
+
+        let series = &ETH_PRICES;
+        let from = Utc.with_ymd_and_hms(2024, 3, 1, 0, 30, 0).unwrap();
+        downsample_from(&test_db.pool, series, Resolution::Hour, from).await;
+        downsample_from(&test_db.pool, series, Resolution::Day, from).await;
+
+        let first_hour = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let second_hour = Utc.with_ymd_and_hms(2024, 3, 1, 1, 0, 0).unwrap();
+        assert_eq!(
+            get_values(&test_db.pool, series, Resolution::Hour).await,
+            vec![(first_hour, 3050.0), (second_hour, 3400.0)]
+        );
+        assert_eq!(
+            get_values(&test_db.pool, series, Resolution::Day).await,
+            vec![(first_hour, 9500.0 / 3.0)]
+        );
+        assert_eq!(
+            get_last_bucket(&test_db.pool, series, Resolution::Hour).await,
+            Some(second_hour)
+        );
+
+        let pruned = prune_raw(&test_db.pool, series, second_hour).await;
+        assert_eq!(pruned, 2);
+
+        let values = sqlx::query(&format!(
+            "SELECT timestamp, value::FLOAT8 AS value FROM ({}) series ORDER BY timestamp",
+            hourly_or_raw_sql(series)
+        ))
+        .map(|row: PgRow| (row.get("timestamp"), row.get("value")))
+        .fetch_all(&test_db.pool)
+        .await
+        .unwrap();
+        assert_eq!(values, vec![(first_hour, 3050.0), (second_hour, 3400.0)]);
+    }
+}
@@ -6,7 +6,16 @@ use std::env;
 use lazy_static::lazy_static;
 use tracing::{debug, warn};
 
-const SECRET_LOG_BLACKLIST: [&str; 3] = ["DATABASE_URL", "OPSGENIE_API_KEY", "ETHERSCAN_API_KEY"];
+const SECRET_LOG_BLACKLIST: [&str; 8] = [
+    "DATABASE_READ_URL",
+    "DATABASE_URL",
+    "DISCORD_WEBHOOK_URL",
+    "ETHERSCAN_API_KEY",
+    "OPSGENIE_API_KEY",
+    "PAGERDUTY_ROUTING_KEY",
+    "REDIS_URL",
+    "TELEGRAM_BOT_TOKEN",
+];
 
 /// Get an environment variable, encoding found or missing as Option, and panic otherwise.
 pub fn get_env_var(key: &str) -> Option<String> {
@@ -31,11 +40,6 @@ pub fn get_env_var(key: &str) -> Option<String> {
     var
 }
 
-/// Get an environment variable we can't run without.
-pub fn get_env_var_unsafe(key: &str) -> String {
-    get_env_var(key).unwrap_or_else(|| panic!("{key} should be in env"))
-}
-
 /// Some things are different between environments. Urls we contact, timeouts we use, data we have.
 /// This enum is the main way to create these branches in our logic.
 #[derive(PartialEq, Eq)]
@@ -80,20 +84,6 @@ pub fn get_env_bool(key: &str) -> bool {
 mod tests {
     use super::*;
 
-    #[test]
-    #[should_panic]
-    fn get_env_var_unsafe_panics_test() {
-        get_env_var_unsafe("DOESNT_EXIST");
-    }
-
-    #[test]
-    fn get_env_var_unsafe_test() {
-        let test_key = "TEST_KEY_UNSAFE";
-        let test_value = "my-env-value";
-        std::env::set_var(test_key, test_value);
-        assert_eq!(get_env_var_unsafe(test_key), test_value);
-    }
-
     #[test]
     fn get_env_var_safe_some_test() {
         let test_key = "TEST_KEY_SAFE_SOME";
@@ -0,0 +1,402 @@
+//! Reconciles the stored supply up to a block with what it's made of. The execution balances sum
+//! is recomputed from the supply deltas, from genesis when the genesis allocation is stored, from
+//! the snapshot the delta sync starts at otherwise, and compared with `execution_supply` block by
+//! block. Every `eth_supply` up to the block is recomputed from the execution balances sum, the
+//! beacon balances and the beacon deposits it was made of.
+//!
+//! `audit-supply` takes a block number, the last block we have a delta for by default, and prints
+//! a report with the first block and slot that diverge.
+use std::fmt;
+
+use sqlx::{PgExecutor, PgPool};
+use tracing::info;
+
+use crate::{
+    beacon_chain::Slot, db, execution_chain::BlockNumber, log, network::NETWORK_CONSTANTS,
+    units::WeiNewtype,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct DivergentBlock {
+    pub block_number: BlockNumber,
+    pub recomputed: WeiNewtype,
+    /// `None` when the balances sum is missing.
+    pub stored: Option<WeiNewtype>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DivergentSlot {
+    pub block_number: BlockNumber,
+    /// `None` when one of the parts is missing.
+    pub recomputed: Option<WeiNewtype>,
+    pub slot: Slot,
+    pub stored: WeiNewtype,
+}
+
+#[derive(Debug)]
+pub struct SupplyAudit {
+    pub block_number: BlockNumber,
+    pub checked_slots_count: i64,
+    /// First block whose delta we add.
+    pub deltas_start: BlockNumber,
+    pub first_divergent_block: Option<DivergentBlock>,
+    pub first_divergent_slot: Option<DivergentSlot>,
+    pub missing_deltas_count: i64,
+    pub recomputed_balances_sum: WeiNewtype,
+    pub stored_balances_sum: Option<WeiNewtype>,
+}
+
+fn format_wei(wei: &Option<WeiNewtype>) -> String {
+    match wei {
+        Some(wei) => wei.to_string(),
+        None => "missing".to_string(),
+    }
+}
+
+impl fmt::Display for SupplyAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "supply audit at block {}", self.block_number)?;
+        writeln!(f, "execution balances sum")?;
+        writeln!(
+            f,
+            "  recomputed from deltas since block {}: {}",
+            self.deltas_start, self.recomputed_balances_sum
+        )?;
+        writeln!(f, "  stored: {}", format_wei(&self.stored_balances_sum))?;
+        writeln!(f, "  missing deltas: {}", self.missing_deltas_count)?;
+        match &self.first_divergent_block {
+            None => writeln!(f, "  first divergent block: none")?,
+            Some(block) => writeln!(
+                f,
+                "  first divergent block: {}, stored {}, recomputed {}",
+                block.block_number,
+                format_wei(&block.stored),
+                block.recomputed
+            )?,
+        }
+        writeln!(f, "eth supply")?;
+        writeln!(f, "  slots checked: {}", self.checked_slots_count)?;
+        match &self.first_divergent_slot {
+            None => write!(f, "  first divergent slot: none"),
+            Some(slot) => write!(
+                f,
+                "  first divergent slot: {}, block {}, stored {}, recomputed {}",
+                slot.slot,
+                slot.block_number,
+                slot.stored,
+                format_wei(&slot.recomputed)
+            ),
+        }
+    }
+}
+
+/// The first block whose delta we add, and the balances sum before it.
+async fn get_deltas_start(executor: impl PgExecutor<'_>) -> (BlockNumber, WeiNewtype) {
+    let has_genesis = sqlx::query!(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM execution_supply_deltas WHERE block_number = 0) AS "exists!"
+        "#
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .exists;
+
+    // Without a snapshot the sync itself starts from genesis.
+    match NETWORK_CONSTANTS.supply_snapshot {
+        Some(snapshot) if !has_genesis => {
+            (snapshot.block_number + 1, WeiNewtype(snapshot.balances_sum))
+        }
+        _ => (BlockNumber::GENESIS, WeiNewtype(0)),
+    }
+}
+
+/// Count and sum of the deltas in the range.
+async fn get_deltas_sum(
+    executor: impl PgExecutor<'_>,
+    start: BlockNumber,
+    end: BlockNumber,
+) -> (i64, WeiNewtype) {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) AS "deltas_count!",
+            COALESCE(SUM(supply_delta), 0) AS "deltas_sum!: WeiNewtype"
+        FROM execution_supply_deltas
+        WHERE block_number >= $1
+        AND block_number <= $2
+        "#,
+        start.0,
+        end.0
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap();
+
+    (row.deltas_count, row.deltas_sum)
+}
+
+async fn get_stored_balances_sum(
+    executor: impl PgExecutor<'_>,
+    block_number: BlockNumber,
+) -> Option<WeiNewtype> {
+    sqlx::query!(
+        r#"
+        SELECT execution_supply.balances_sum AS "balances_sum: WeiNewtype"
+        FROM execution_supply_deltas
+        JOIN execution_supply ON execution_supply.block_hash = execution_supply_deltas.block_hash
+        WHERE execution_supply_deltas.block_number = $1
+        "#,
+        block_number.0
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| row.balances_sum)
+}
+
+async fn get_first_divergent_block(
+    executor: impl PgExecutor<'_>,
+    start: BlockNumber,
+    start_balances_sum: WeiNewtype,
+    end: BlockNumber,
+) -> Option<DivergentBlock> {
+    sqlx::query!(
+        r#"
+        SELECT
+            block_number AS "block_number!",
+            recomputed AS "recomputed!: WeiNewtype",
+            stored AS "stored?: WeiNewtype"
+        FROM (
+            SELECT
+                execution_supply_deltas.block_number,
+                $1 + SUM(execution_supply_deltas.supply_delta) OVER (
+                    ORDER BY execution_supply_deltas.block_number
+                ) AS recomputed,
+                execution_supply.balances_sum AS stored
+            FROM execution_supply_deltas
+            LEFT JOIN execution_supply ON
+                execution_supply.block_hash = execution_supply_deltas.block_hash
+            WHERE execution_supply_deltas.block_number >= $2
+            AND execution_supply_deltas.block_number <= $3
+        ) sums
+        WHERE stored IS DISTINCT FROM recomputed
+        ORDER BY block_number ASC
+        LIMIT 1
+        "#,
+        start_balances_sum as WeiNewtype,
+        start.0,
+        end.0
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| DivergentBlock {
+        block_number: BlockNumber(row.block_number),
+        recomputed: row.recomputed,
+        stored: row.stored,
+    })
+}
+
+async fn get_checked_slots_count(executor: impl PgExecutor<'_>, end: BlockNumber) -> i64 {
+    sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM eth_supply WHERE block_number <= $1"#,
+        end.0
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .count
+}
+
+/// Supply is the execution balances sum, plus the beacon balances, minus the beacon deposits. The
+/// deposits are those of the beacon block carrying the execution block, for empty slots too.
+async fn get_first_divergent_slot(
+    executor: impl PgExecutor<'_>,
+    end: BlockNumber,
+) -> Option<DivergentSlot> {
+    sqlx::query!(
+        r#"
+        SELECT
+            block_number AS "block_number!",
+            recomputed AS "recomputed?: WeiNewtype",
+            slot AS "slot!",
+            stored AS "stored!: WeiNewtype"
+        FROM (
+            SELECT
+                eth_supply.block_number,
+                execution_supply.balances_sum + (
+                    beacon_validators_balance.gwei - beacon_blocks.deposit_sum_aggregated
+                ) * 1e9 AS recomputed,
+                eth_supply.balances_slot AS slot,
+                eth_supply.supply AS stored,
+                eth_supply.timestamp
+            FROM eth_supply
+            JOIN blocks_next ON blocks_next.number = eth_supply.block_number
+            LEFT JOIN execution_supply ON execution_supply.block_hash = blocks_next.hash
+            LEFT JOIN beacon_blocks ON beacon_blocks.block_hash = blocks_next.hash
+            LEFT JOIN beacon_states ON beacon_states.slot = eth_supply.balances_slot
+            LEFT JOIN beacon_validators_balance ON
+                beacon_validators_balance.state_root = beacon_states.state_root
+            WHERE eth_supply.block_number <= $1
+        ) supplies
+        WHERE stored IS DISTINCT FROM recomputed
+        ORDER BY timestamp ASC
+        LIMIT 1
+        "#,
+        end.0
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| DivergentSlot {
+        block_number: BlockNumber(row.block_number),
+        recomputed: row.recomputed,
+        slot: Slot(row.slot),
+        stored: row.stored,
+    })
+}
+
+pub async fn get_supply_audit(db_pool: &PgPool, block_number: BlockNumber) -> SupplyAudit {
+    let (deltas_start, start_balances_sum) = get_deltas_start(db_pool).await;
+    let (deltas_count, deltas_sum) = get_deltas_sum(db_pool, deltas_start, block_number).await;
+    let expected_deltas_count = (block_number - deltas_start + 1).max(0);
+
+    SupplyAudit {
+        block_number,
+        checked_slots_count: get_checked_slots_count(db_pool, block_number).await,
+        deltas_start,
+        first_divergent_block: get_first_divergent_block(
+            db_pool,
+            deltas_start,
+            start_balances_sum,
+            block_number,
+        )
+        .await,
+        first_divergent_slot: get_first_divergent_slot(db_pool, block_number).await,
+        missing_deltas_count: expected_deltas_count - deltas_count,
+        recomputed_balances_sum: start_balances_sum + deltas_sum,
+        stored_balances_sum: get_stored_balances_sum(db_pool, block_number).await,
+    }
+}
+
+pub async fn audit_supply() {
+    let block_number = std::env::args()
+        .skip(1)
+        .find_map(|str| str.parse::<BlockNumber>().ok());
+    audit_supply_at(block_number).await;
+}
+
+/// Audits up to the last block with a supply delta when `block_number` is `None`.
+pub async fn audit_supply_at(block_number: Option<BlockNumber>) {
+    log::init_with_env();
+
+    let db_pool = db::get_db_pool("audit-supply").await;
+
+    let block_number = match block_number {
+        Some(block_number) => block_number,
+        None => {
+            let last_delta_number = sqlx::query!(
+                "SELECT MAX(block_number) AS block_number FROM execution_supply_deltas"
+            )
+            .fetch_one(&db_pool)
+            .await
+            .unwrap()
+            .block_number
+            .map(BlockNumber);
+            match last_delta_number {
+                Some(last_delta_number) => last_delta_number,
+                None => {
+                    info!("no supply deltas stored, nothing to audit");
+                    return;
+                }
+            }
+        }
+    };
+
+    info!(%block_number, "auditing supply");
+
+    let supply_audit = get_supply_audit(&db_pool, block_number).await;
+
+    println!("{supply_audit}");
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::db::tests::TestDb;
+
+    use super::*;
+
+    async fn store_delta_and_sum(
+        db_pool: &PgPool,
+        block_number: BlockNumber,
+        supply_delta: i128,
+        balances_sum: i128,
+    ) {
+        sqlx::query!(
+            "
+            INSERT INTO execution_supply_deltas (
+                block_hash,
+                block_number,
+                fee_burn,
+                fixed_reward,
+                parent_hash,
+                self_destruct,
+                supply_delta,
+                uncles_reward
+            )
+            VALUES ($1, $2, 0, 0, $3, 0, $4, 0)
+            ",
+            format!("0xblock{block_number}"),
+            block_number.0,
+            format!("0xblock{}", block_number - 1),
+            WeiNewtype(supply_delta) as WeiNewtype
+        )
+        .execute(db_pool)
+        .await
+        .unwrap();
+
+        sqlx::query!(
+            "
+            INSERT INTO execution_supply (block_hash, block_number, balances_sum)
+            VALUES ($1, $2, $3)
+            ",
+            format!("0xblock{block_number}"),
+            block_number.0,
+            WeiNewtype(balances_sum) as WeiNewtype
+        )
+        .execute(db_pool)
+        .await
+        .unwrap();
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn supply_audit_test(test_db: &TestDb) {
+        store_delta_and_sum(&test_db.pool, BlockNumber(0), 100, 100).await;
+        store_delta_and_sum(&test_db.pool, BlockNumber(1), 5, 105).await;
+        // Off by one.
+        store_delta_and_sum(&test_db.pool, BlockNumber(2), 5, 111).await;
+        store_delta_and_sum(&test_db.pool, BlockNumber(4), 5, 116).await;
+
+        let supply_audit = get_supply_audit(&test_db.pool, BlockNumber(1)).await;
+        assert_eq!(supply_audit.recomputed_balances_sum, WeiNewtype(105));
+        assert_eq!(supply_audit.stored_balances_sum, Some(WeiNewtype(105)));
+        assert_eq!(supply_audit.first_divergent_block, None);
+
+        let supply_audit = get_supply_audit(&test_db.pool, BlockNumber(4)).await;
+        assert_eq!(supply_audit.deltas_start, BlockNumber(0));
+        assert_eq!(supply_audit.missing_deltas_count, 1);
+        assert_eq!(
+            supply_audit.first_divergent_block,
+            Some(DivergentBlock {
+                block_number: BlockNumber(2),
+                recomputed: WeiNewtype(110),
+                stored: Some(WeiNewtype(111)),
+            })
+        );
+        assert_eq!(supply_audit.first_divergent_slot, None);
+    }
+}
@@ -0,0 +1,105 @@
+//! Series holding the last supply of each bucket of time, like each minute since the merge, or
+//! each day. Rebuilding such a series from `eth_supply` scans every slot. Instead the bucket of
+//! each slot is updated as the slot is stored, and only the buckets a rollback touches are rebuilt.
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use sqlx::PgConnection;
+
+use crate::{beacon_chain::Slot, rollback::RollbackPoint};
+
+#[async_trait]
+pub trait SupplyBuckets: Sync {
+    /// Keyed by the start of each bucket in a `timestamp` column.
+    const TABLE: &'static str;
+
+    fn bucket_size() -> Duration;
+
+    /// Sets the bucket starting at `bucket` to the supply stored at `timestamp`.
+    async fn store_supply_at(
+        &self,
+        connection: &mut PgConnection,
+        bucket: &DateTime<Utc>,
+        timestamp: &DateTime<Utc>,
+    );
+}
+
+pub fn bucket_of<B: SupplyBuckets>(timestamp: &DateTime<Utc>) -> DateTime<Utc> {
+    timestamp.duration_trunc(B::bucket_size()).unwrap()
+}
+
+/// Sets the bucket of the slot to the slot's supply. Slots are stored in order, so the last slot
+/// of a bucket wins.
+pub async fn store_slot<B: SupplyBuckets>(buckets: &B, connection: &mut PgConnection, slot: &Slot) {
+    let timestamp = slot.date_time();
+    buckets
+        .store_supply_at(connection, &bucket_of::<B>(&timestamp), &timestamp)
+        .await;
+}
+
+/// Recalculates the bucket from what's left in `eth_supply`, removing it when nothing is.
+async fn rebuild_bucket<B: SupplyBuckets>(
+    buckets: &B,
+    connection: &mut PgConnection,
+    bucket: &DateTime<Utc>,
+) {
+    sqlx::query(&format!("DELETE FROM {} WHERE timestamp = $1", B::TABLE))
+        .bind(bucket)
+        .execute(&mut *connection)
+        .await
+        .unwrap();
+
+    let last_timestamp = sqlx::query!(
+        "
+        SELECT MAX(timestamp) AS timestamp
+        FROM eth_supply
+        WHERE timestamp >= $1
+        AND timestamp < $2
+        ",
+        bucket,
+        *bucket + B::bucket_size()
+    )
+    .fetch_one(&mut *connection)
+    .await
+    .unwrap()
+    .timestamp;
+
+    if let Some(last_timestamp) = last_timestamp {
+        buckets
+            .store_supply_at(connection, bucket, &last_timestamp)
+            .await;
+    }
+}
+
+async fn rollback_from_slot<B: SupplyBuckets>(
+    buckets: &B,
+    connection: &mut PgConnection,
+    slot_gte: &Slot,
+) {
+    let bucket = bucket_of::<B>(&slot_gte.date_time());
+
+    sqlx::query(&format!("DELETE FROM {} WHERE timestamp > $1", B::TABLE))
+        .bind(bucket)
+        .execute(&mut *connection)
+        .await
+        .unwrap();
+
+    // Slots before the rollback point may share its bucket.
+    rebuild_bucket(buckets, connection, &bucket).await;
+}
+
+/// Runs after the `eth_supply` rollback, the buckets are rebuilt from what it left.
+pub async fn on_rollback<B: SupplyBuckets>(
+    buckets: &B,
+    connection: &mut PgConnection,
+    point: &RollbackPoint,
+) {
+    match point {
+        RollbackPoint::BlockNumbersGte(_) => (),
+        RollbackPoint::Slot(slot) => {
+            rebuild_bucket(buckets, connection, &bucket_of::<B>(&slot.date_time())).await;
+        }
+        RollbackPoint::SlotsGte(slot_gte) => {
+            rollback_from_slot(buckets, connection, slot_gte).await;
+        }
+    }
+}
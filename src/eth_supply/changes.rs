@@ -37,6 +37,7 @@ impl From<&Vec<SupplyAtTime>> for SupplyChange {
         let last = supply_at_time
             .last()
             .expect("expect at least one supply in d1");
+        // Decimal ETH converts back to wei exactly.
         let change = last.supply - first.supply;
 
         SupplyChange {
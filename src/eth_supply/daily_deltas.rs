@@ -0,0 +1,225 @@
+//! Net supply change per day, issuance minus burn, as the change in the last supply of each day.
+//! Like the supply since the merge, the last supply of each day is kept in `supply_by_day`, see
+//! `buckets`, so the series never scans `eth_supply`.
+//!
+//! The whole series is published under `CacheKey::DailySupplyDeltas`, ranges are served by
+//! `daily_supply_deltas`.
+use async_trait::async_trait;
+use axum::{
+    extract::Query,
+    http::{HeaderMap, HeaderValue},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use reqwest::header;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgExecutor};
+
+use crate::{
+    analysis::Analysis,
+    beacon_chain::Slot,
+    execution_chain::BlockNumber,
+    rollback::RollbackPoint,
+    serve::StateExtension,
+    units::{EthDecimal, WeiNewtype},
+};
+
+use super::buckets::{self, SupplyBuckets};
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DailySupplyDelta {
+    pub supply_delta: EthDecimal,
+    /// Start of the day.
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct DailySupplyDeltas {
+    pub block_number: BlockNumber,
+    pub deltas: Vec<DailySupplyDelta>,
+    pub slot: Slot,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// The last supply of each day.
+pub struct SupplyByDay;
+
+#[async_trait]
+impl SupplyBuckets for SupplyByDay {
+    const TABLE: &'static str = "supply_by_day";
+
+    fn bucket_size() -> Duration {
+        Duration::days(1)
+    }
+
+    async fn store_supply_at(
+        &self,
+        connection: &mut PgConnection,
+        bucket: &DateTime<Utc>,
+        timestamp: &DateTime<Utc>,
+    ) {
+        sqlx::query!(
+            "
+            INSERT INTO supply_by_day (timestamp, supply)
+            SELECT $1, supply
+            FROM eth_supply
+            WHERE timestamp = $2
+            ON CONFLICT (timestamp) DO UPDATE SET
+                supply = excluded.supply
+            ",
+            bucket,
+            timestamp
+        )
+        .execute(connection)
+        .await
+        .unwrap();
+    }
+}
+
+/// Oldest first. Days from `start`, up to but not including `end`. The first day we have a supply
+/// for has no delta.
+pub async fn get_daily_supply_deltas(
+    executor: impl PgExecutor<'_>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Vec<DailySupplyDelta> {
+    sqlx::query!(
+        r#"
+        SELECT timestamp AS "timestamp!", supply_delta AS "supply_delta!: WeiNewtype"
+        FROM (
+            SELECT
+                timestamp,
+                supply - LAG(supply) OVER (ORDER BY timestamp) AS supply_delta
+            FROM supply_by_day
+        ) deltas
+        WHERE supply_delta IS NOT NULL
+        AND ($1::TIMESTAMPTZ IS NULL OR timestamp >= $1)
+        AND ($2::TIMESTAMPTZ IS NULL OR timestamp < $2)
+        ORDER BY timestamp ASC
+        "#,
+        start,
+        end
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| DailySupplyDelta {
+        supply_delta: row.supply_delta.into(),
+        timestamp: row.timestamp,
+    })
+    .collect()
+}
+
+#[derive(Deserialize)]
+pub struct DailySupplyDeltasParams {
+    end: Option<DateTime<Utc>>,
+    start: Option<DateTime<Utc>>,
+}
+
+/// Takes optional RFC 3339 `start` and `end` timestamps, the whole series without them.
+pub async fn daily_supply_deltas(
+    state: StateExtension,
+    Query(params): Query<DailySupplyDeltasParams>,
+) -> impl IntoResponse {
+    let deltas = get_daily_supply_deltas(&state.db_pool, params.start, params.end).await;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=60, stale-while-revalidate=600"),
+    );
+
+    (headers, Json(deltas)).into_response()
+}
+
+/// Runs after the `eth_supply` rollback, the days are rebuilt from what it left.
+pub struct SupplyByDayRollback;
+
+#[async_trait]
+impl Analysis for SupplyByDayRollback {
+    fn name(&self) -> &'static str {
+        "supply_by_day"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        buckets::on_rollback(&SupplyByDay, transaction, point).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use sqlx::Acquire;
+
+    use crate::{
+        db,
+        eth_supply::{
+            rollback_supply_from_slot,
+            test::{make_test_block, store_test_supply_for_slot},
+        },
+        execution_chain,
+        units::EthNewtype,
+    };
+
+    use super::*;
+
+    fn day_of(timestamp: &DateTime<Utc>) -> DateTime<Utc> {
+        buckets::bucket_of::<SupplyByDay>(timestamp)
+    }
+
+    #[tokio::test]
+    async fn daily_supply_deltas_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        execution_chain::store_block(&mut *transaction, &make_test_block(), 0.0).await;
+
+        // Slot 0 is at 12:00:23, slot 3599 is the first of the next day, a day holds 7200 slots so
+        // slot 10799 is the first of the day after.
+        for (slot, supply) in [
+            (Slot(0), 10.0),
+            (Slot(1), 11.0),
+            (Slot(3599), 15.0),
+            (Slot(10799), 12.0),
+        ] {
+            store_test_supply_for_slot(&mut transaction, &slot, EthNewtype(supply)).await;
+            buckets::store_slot(&SupplyByDay, &mut transaction, &slot).await;
+        }
+
+        let second_day = day_of(&Slot(3599).date_time());
+        let third_day = day_of(&Slot(10799).date_time());
+        assert_eq!(
+            get_daily_supply_deltas(&mut *transaction, None, None).await,
+            vec![
+                DailySupplyDelta {
+                    supply_delta: EthDecimal(Decimal::new(4, 0)),
+                    timestamp: second_day,
+                },
+                DailySupplyDelta {
+                    supply_delta: EthDecimal(Decimal::new(-3, 0)),
+                    timestamp: third_day,
+                },
+            ]
+        );
+        assert_eq!(
+            get_daily_supply_deltas(&mut *transaction, None, Some(third_day))
+                .await
+                .len(),
+            1
+        );
+
+        rollback_supply_from_slot(&mut transaction, &Slot(10799))
+            .await
+            .unwrap();
+        SupplyByDayRollback
+            .on_rollback(&mut transaction, &RollbackPoint::SlotsGte(Slot(10799)))
+            .await;
+
+        assert_eq!(
+            get_daily_supply_deltas(&mut *transaction, Some(third_day), None).await,
+            vec![]
+        );
+    }
+}
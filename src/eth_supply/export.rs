@@ -4,7 +4,7 @@ use chrono::{DateTime, Duration, Utc};
 use serde::Serialize;
 
 use crate::{
-    beacon_chain::{self, Slot},
+    beacon_chain::{self, Epoch, Slot},
     db,
     time_frames::{GrowingTimeFrame, TimeFrame},
     units::EthNewtype,
@@ -14,7 +14,7 @@ use super::over_time::SupplyAtTime;
 
 #[derive(Debug, Serialize)]
 struct SupplyAtSlot {
-    epoch: i32,
+    epoch: Epoch,
     slot: Slot,
     supply: EthNewtype,
     timestamp: DateTime<Utc>,
@@ -22,7 +22,7 @@ struct SupplyAtSlot {
 
 #[derive(Debug, Serialize)]
 struct Row {
-    epoch: i32,
+    epoch: Epoch,
     epoch_delta: i32,
     slot: Slot,
     supply: EthNewtype,
@@ -35,7 +35,7 @@ impl From<SupplyAtTime> for SupplyAtSlot {
     fn from(supply_at_time: SupplyAtTime) -> Self {
         let slot = Slot::from_date_time_rounded_down(&supply_at_time.timestamp);
         let epoch = slot.epoch();
-        let supply = supply_at_time.supply;
+        let supply = supply_at_time.supply.into();
         Self {
             epoch,
             slot,
@@ -45,8 +45,6 @@ impl From<SupplyAtTime> for SupplyAtSlot {
     }
 }
 
-const SLOTS_PER_EPOCH: i32 = 32;
-
 // export every thousandth epoch.
 // uses a combination of daily glassnode data, and our own eth_supply table
 pub async fn export_thousandth_epoch_supply() {
@@ -95,7 +93,7 @@ pub async fn export_thousandth_epoch_supply() {
         ORDER BY timestamp ASC
         ",
         first_recent_supply.timestamp
-            - Duration::seconds((1000 * SLOTS_PER_EPOCH * Slot::SECONDS_PER_SLOT).into()),
+            - Duration::seconds((1000 * *beacon_chain::SECONDS_PER_EPOCH).into()),
         *beacon_chain::GENESIS_TIMESTAMP
     )
     .fetch_all(&db_pool)
@@ -128,7 +126,7 @@ pub async fn export_thousandth_epoch_supply() {
     let supply = early_supply.into_iter().chain(recent_supply.into_iter());
 
     let mut csv_writer = csv::Writer::from_path("eth_supply.csv").unwrap();
-    let mut last_epoch: Option<i32> = None;
+    let mut last_epoch: Option<Epoch> = None;
     for supply in supply {
         let row = Row {
             epoch: supply.epoch,
@@ -167,7 +165,7 @@ pub async fn export_daily_supply_since_merge() {
         csv::Writer::from_path(format!("eth_supply_since_merge_{timestamp}.csv")).unwrap();
     for supply in supply {
         let row = SupplyAtTimeRow {
-            supply: supply.supply,
+            supply: supply.supply.into(),
             timestamp: supply.timestamp,
             timestamp_unix: supply.timestamp.timestamp(),
         };
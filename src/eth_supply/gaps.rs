@@ -15,11 +15,9 @@ use crate::{
     db,
     eth_supply::{self, SupplyPartsError, SupplyPartsStore},
     log,
+    network::NETWORK_CONSTANTS,
 };
 
-// The first slot we have stored.
-const FIRST_STORED_ETH_SUPPLY_SLOT: Slot = Slot(4697813);
-
 pub async fn fill_gaps() -> Result<()> {
     log::init_with_env();
 
@@ -34,18 +32,20 @@ pub async fn fill_gaps() -> Result<()> {
         .expect("a beacon state should be stored before trying to fill any gaps")
         .slot;
 
+    let first_stored_slot = NETWORK_CONSTANTS.first_stored_eth_supply_slot;
+
     debug!(
-        %FIRST_STORED_ETH_SUPPLY_SLOT,
+        %first_stored_slot,
         %last_slot,
         "checking first stored slot to last slot for gaps"
     );
 
-    let work_todo = last_slot.0 - FIRST_STORED_ETH_SUPPLY_SLOT.0;
+    let work_todo = last_slot - first_stored_slot;
     let mut progress = Progress::new("sync-eth-supply-gas", work_todo.try_into().unwrap());
 
     let supply_parts_store = SupplyPartsStore::new(&db_pool);
 
-    for slot in (FIRST_STORED_ETH_SUPPLY_SLOT.0..=last_slot.0).map(Slot) {
+    for slot in Slot::range_inclusive(first_stored_slot, last_slot) {
         let stored_eth_supply = eth_supply::get_supply_exists_by_slot(&db_pool, &slot).await?;
         if !stored_eth_supply {
             info!(%slot, "missing eth_supply, filling gap");
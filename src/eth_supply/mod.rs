@@ -1,15 +1,28 @@
+mod audit;
+mod buckets;
 mod changes;
+mod daily_deltas;
 mod export;
 mod gaps;
 mod over_time;
 mod parts;
+mod projection_inputs;
+mod since_merge;
 mod store;
 mod sync;
 #[cfg(test)]
 mod test;
 
+pub use audit::audit_supply;
+pub use audit::audit_supply_at;
+
 pub use changes::SupplyChanges;
 
+pub use daily_deltas::daily_supply_deltas;
+pub use daily_deltas::get_daily_supply_deltas;
+pub use daily_deltas::DailySupplyDeltas;
+pub use daily_deltas::SupplyByDayRollback;
+
 pub use export::export_daily_supply_since_merge;
 pub use export::export_thousandth_epoch_supply;
 
@@ -24,11 +37,21 @@ pub use parts::SupplyParts;
 pub use parts::SupplyPartsError;
 pub use parts::SupplyPartsStore;
 
+pub use projection_inputs::update_supply_projection_inputs;
+
+pub use since_merge::backfill_supply_less_pos_issuance;
+pub use since_merge::get_pow_supply_since_merge_by_minute;
+pub use since_merge::get_supply_since_merge_by_minute;
+pub use since_merge::PowSupplySinceMerge;
+pub use since_merge::SupplySinceMerge;
+pub use since_merge::SupplySinceMergeRollback;
+
 pub use store::get_last_stored_supply_slot;
 pub use store::get_supply_exists_by_slot;
 pub use store::last_eth_supply;
 pub use store::rollback_supply_from_slot;
 pub use store::rollback_supply_slot;
 pub use store::store;
+pub use store::EthSupplyRollback;
 
 pub use sync::sync_eth_supply;
@@ -8,10 +8,9 @@ use tracing::debug;
 
 use crate::{
     beacon_chain::Slot,
-    eth_time,
     execution_chain::{self, BlockNumber},
     time_frames::{GrowingTimeFrame, LimitedTimeFrame, TimeFrame},
-    units::EthNewtype,
+    units::{EthDecimal, EthNewtype, WeiNewtype},
 };
 
 use GrowingTimeFrame::*;
@@ -19,16 +18,22 @@ use LimitedTimeFrame::*;
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct SupplyAtTime {
-    pub supply: EthNewtype,
+    pub supply: EthDecimal,
     pub timestamp: DateTime<Utc>,
 }
 
+fn glassnode_supply(supply: f64) -> EthDecimal {
+    EthNewtype(supply)
+        .try_into()
+        .expect("expect glassnode supply to fit in a decimal")
+}
+
 async fn get_last_supply_point(executor: impl PgExecutor<'_>) -> SupplyAtTime {
     sqlx::query!(
         "
         SELECT
             timestamp,
-            supply::FLOAT8 / 1e18 AS \"supply!\"
+            supply AS \"supply!: WeiNewtype\"
         FROM
             eth_supply
         ORDER BY timestamp DESC
@@ -38,7 +43,7 @@ async fn get_last_supply_point(executor: impl PgExecutor<'_>) -> SupplyAtTime {
     .fetch_one(executor)
     .await
     .map(|row| SupplyAtTime {
-        supply: EthNewtype(row.supply),
+        supply: row.supply.into(),
         timestamp: row.timestamp,
     })
     .unwrap()
@@ -84,7 +89,7 @@ async fn get_early_supply_since_burn(
     .into_iter()
     .map(|row| SupplyAtTime {
         timestamp: row.timestamp,
-        supply: EthNewtype(row.supply),
+        supply: glassnode_supply(row.supply),
     })
     .collect()
 }
@@ -101,7 +106,7 @@ pub async fn from_time_frame(
                 -- The column we output is rounded to whole days for convenience.
                 SELECT
                     DISTINCT ON (DATE_TRUNC('day', timestamp)) DATE_TRUNC('day', timestamp) AS \"day_timestamp!\",
-                    supply::FLOAT8 / 1e18 AS \"supply!\"
+                    supply AS \"supply!: WeiNewtype\"
                 FROM
                     eth_supply
                 ORDER BY
@@ -115,17 +120,17 @@ pub async fn from_time_frame(
             .map(|row| {
                 SupplyAtTime {
                     timestamp: row.day_timestamp,
-                    supply: EthNewtype(row.supply),
+                    supply: row.supply.into(),
                 }
             })
             .collect()
         },
-        TimeFrame::Growing(SinceMerge) => {
+        TimeFrame::Growing(growing_time_frame @ (SinceMerge | SinceShapella)) => {
             sqlx::query!(
                 "
                 SELECT
                     DISTINCT ON (DATE_TRUNC('day', timestamp)) DATE_TRUNC('day', timestamp) AS \"day_timestamp!\",
-                    supply::FLOAT8 / 1e18 AS \"supply!\"
+                    supply AS \"supply!: WeiNewtype\"
                 FROM
                     eth_supply
                 WHERE
@@ -133,7 +138,7 @@ pub async fn from_time_frame(
                 ORDER BY
                     DATE_TRUNC('day', timestamp) ASC
                 ",
-                *eth_time::MERGE_HARD_FORK_TIMESTAMP
+                growing_time_frame.start_timestamp()
             )
             .fetch_all(executor)
             .await
@@ -142,7 +147,7 @@ pub async fn from_time_frame(
             .map(|row| {
                 SupplyAtTime {
                     timestamp: row.day_timestamp,
-                    supply: EthNewtype(row.supply),
+                    supply: row.supply.into(),
                 }
             })
             .collect()
@@ -153,7 +158,7 @@ pub async fn from_time_frame(
                 "
                 SELECT
                     timestamp,
-                    supply::FLOAT8 / 1e18 AS \"supply!\"
+                    supply AS \"supply!: WeiNewtype\"
                 FROM
                     eth_supply
                 WHERE
@@ -170,7 +175,7 @@ pub async fn from_time_frame(
             .map(|row| {
                 SupplyAtTime {
                     timestamp: row.timestamp,
-                    supply: EthNewtype(row.supply),
+                    supply: row.supply.into(),
                 }
             })
             .collect()
@@ -180,7 +185,7 @@ pub async fn from_time_frame(
                 "
                 SELECT
                     DISTINCT ON (date_bin('384 seconds', timestamp, '2022-1-1')) date_bin('384 seconds', timestamp, '2022-1-1') AS \"epoch_timestamp!\",
-                    supply::FLOAT8 / 1e18 AS \"supply!\"
+                    supply AS \"supply!: WeiNewtype\"
                 FROM
                     eth_supply
                 WHERE
@@ -197,7 +202,7 @@ pub async fn from_time_frame(
             .map(|row| {
                 SupplyAtTime {
                     timestamp: row.epoch_timestamp,
-                    supply: EthNewtype(row.supply),
+                    supply: row.supply.into(),
                 }
             })
             .collect()
@@ -207,7 +212,7 @@ pub async fn from_time_frame(
                 "
                 SELECT
                     DISTINCT ON (DATE_BIN('5 minutes', timestamp, '2022-01-01')) DATE_BIN('5 minutes', timestamp, '2022-01-01') AS \"five_minute_timestamp!\",
-                    supply::FLOAT8 / 1e18 AS \"supply!\"
+                    supply AS \"supply!: WeiNewtype\"
                 FROM
                     eth_supply
                 WHERE
@@ -224,7 +229,7 @@ pub async fn from_time_frame(
                 .map(|row| {
                     SupplyAtTime {
                         timestamp: row.five_minute_timestamp,
-                        supply: EthNewtype(row.supply),
+                        supply: row.supply.into(),
                     }
                 })
                 .collect()
@@ -234,7 +239,7 @@ pub async fn from_time_frame(
                 "
                 SELECT
                     DISTINCT ON (DATE_TRUNC('hour', timestamp)) DATE_TRUNC('hour', timestamp) AS \"hour_timestamp!\",
-                    supply::FLOAT8 / 1e18 AS \"supply!\"
+                    supply AS \"supply!: WeiNewtype\"
                 FROM
                     eth_supply
                 WHERE
@@ -251,7 +256,7 @@ pub async fn from_time_frame(
                 .map(|row| {
                 SupplyAtTime {
                     timestamp: row.hour_timestamp,
-                    supply: EthNewtype(row.supply),
+                    supply: row.supply.into(),
                 }
             })
             .collect()
@@ -352,7 +357,7 @@ pub async fn get_daily_supply(db_pool: &PgPool) -> Vec<SupplyAtTime> {
     .into_iter()
     .map(|row| SupplyAtTime {
         timestamp: row.timestamp,
-        supply: EthNewtype(row.supply),
+        supply: glassnode_supply(row.supply),
     })
     .collect();
 
@@ -372,7 +377,9 @@ mod tests {
     use chrono::{Duration, DurationRound, SubsecRound};
     use sqlx::Acquire;
 
-    use crate::{db, eth_supply::test::store_test_eth_supply, units::EthNewtype};
+    use rust_decimal::Decimal;
+
+    use crate::{db, eth_supply::test::store_test_eth_supply};
 
     use super::*;
 
@@ -389,7 +396,7 @@ mod tests {
 
         let test_supply_at_time = SupplyAtTime {
             timestamp: reverse_timestamp,
-            supply: EthNewtype(10.0),
+            supply: EthDecimal(Decimal::new(10, 0)),
         };
 
         store_test_eth_supply(&mut *transaction, &test_slot, EthNewtype(10.0))
@@ -413,13 +420,13 @@ mod tests {
                 .duration_trunc(Duration::days(1))
                 .unwrap()
                 - Duration::days(1),
-            supply: EthNewtype(10.0),
+            supply: EthDecimal(Decimal::new(10, 0)),
         };
 
         sqlx::query!(
             "INSERT INTO daily_supply_glassnode (timestamp, supply) VALUES ($1, $2)",
             test_supply_at_time.timestamp,
-            test_supply_at_time.supply.0
+            EthNewtype::from(test_supply_at_time.supply).0
         )
         .execute(&mut *transaction)
         .await
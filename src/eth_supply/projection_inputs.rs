@@ -2,51 +2,37 @@ use std::fs::File;
 
 use anyhow::anyhow;
 use chrono::{DateTime, Utc};
-use eth_analysis::{
-    beacon_chain::{self, GweiInTime},
-    caching::{self, CacheKey},
-    db, eth_supply, log,
-    units::GWEI_PER_ETH_F64,
-    SupplyAtTime,
-};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use sqlx::Decode;
 use tracing::{debug, info};
 
+use crate::{
+    beacon_chain,
+    caching::{self, CacheKey},
+    db, log,
+    units::{EthNewtype, GWEI_PER_ETH_F64},
+};
+
+use super::{over_time, SupplyAtTime};
+
 lazy_static! {
     static ref SUPPLY_LOWER_LIMIT_DATE_TIME: DateTime<Utc> =
         ("2015-07-30T00:00:00Z").parse::<DateTime<Utc>>().unwrap();
 }
 
-#[derive(Decode)]
-pub struct GweiInTimeRow {
-    pub timestamp: DateTime<Utc>,
-    pub gwei: i64,
-}
-
-impl From<&GweiInTimeRow> for GweiInTime {
-    fn from(row: &GweiInTimeRow) -> Self {
-        Self {
-            t: row.timestamp.timestamp() as u64,
-            v: row.gwei,
-        }
-    }
-}
-
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct TimestampValuePoint {
-    pub t: u64,
+struct TimestampValuePoint {
+    t: u64,
     // fraction
-    pub v: f64,
+    v: f64,
 }
 
 impl From<SupplyAtTime> for TimestampValuePoint {
     fn from(supply_at_time: SupplyAtTime) -> Self {
         TimestampValuePoint {
             t: supply_at_time.timestamp.timestamp() as u64,
-            v: supply_at_time.supply.0,
+            v: EthNewtype::from(supply_at_time.supply).0,
         }
     }
 }
@@ -59,8 +45,7 @@ struct SupplyProjectionInputs {
     in_beacon_validators_by_day: Vec<TimestampValuePoint>,
 }
 
-#[tokio::main]
-pub async fn main() {
+pub async fn update_supply_projection_inputs() {
     log::init_with_env();
 
     info!("updating supply projection inputs");
@@ -71,10 +56,9 @@ pub async fn main() {
 
     // We originally got this data from the Glassnode API when we paid for it. We don't pay
     // anymore, so no more new data.
-    let in_contracts_by_day_file =
-        File::open("src/bin/update-supply-projection-inputs/in_contracts_by_day.json")
-            .map_err(|e| anyhow!("failed to open in_contracts_by_day.json: {}", e))
-            .unwrap();
+    let in_contracts_by_day_file = File::open("src/eth_supply/in_contracts_by_day.json")
+        .map_err(|e| anyhow!("failed to open in_contracts_by_day.json: {}", e))
+        .unwrap();
     let json_value: Value = serde_json::from_reader(in_contracts_by_day_file).unwrap();
     let in_contracts_by_day: Vec<TimestampValuePoint> = serde_json::from_value(json_value).unwrap();
 
@@ -98,7 +82,7 @@ pub async fn main() {
         in_beacon_validators_by_day.len()
     );
 
-    let supply_by_day: Vec<TimestampValuePoint> = eth_supply::get_daily_supply(&db_pool)
+    let supply_by_day: Vec<TimestampValuePoint> = over_time::get_daily_supply(&db_pool)
         .await
         .into_iter()
         .filter(|point| point.timestamp >= *SUPPLY_LOWER_LIMIT_DATE_TIME)
@@ -0,0 +1,287 @@
+//! ETH supply since the merge, one point per minute, the last supply of each minute. Rebuilding
+//! the series from `eth_supply` scans every slot since the merge. Instead we keep it in
+//! `supply_since_merge_by_minute`, see `buckets`.
+//!
+//! We also serve what the supply would have been had Ethereum stayed on proof-of-work. Next to the
+//! supply we keep the supply minus beacon chain issuance since the merge, and add the daily
+//! proof-of-work issuance, see `execution_chain::pow_issuance`, from the start of each minute when
+//! reading. Minutes without beacon issuance have none. Minutes stored before we kept it are
+//! filled in by `backfill-supply-less-pos-issuance`.
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::Serialize;
+use sqlx::{PgConnection, PgExecutor};
+use tracing::{debug, info};
+
+use crate::{
+    analysis::Analysis,
+    beacon_chain::{Slot, FIRST_POST_MERGE_SLOT},
+    db,
+    execution_chain::BlockNumber,
+    log,
+    rollback::RollbackPoint,
+    units::{EthNewtype, WeiNewtype},
+};
+
+use super::{
+    buckets::{self, SupplyBuckets},
+    SupplyAtTime,
+};
+
+#[derive(Serialize)]
+pub struct SupplySinceMerge {
+    pub block_number: BlockNumber,
+    pub slot: Slot,
+    pub supply_by_minute: Vec<SupplyAtTime>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct PowSupplySinceMerge {
+    pub block_number: BlockNumber,
+    pub slot: Slot,
+    pub pow_supply_by_minute: Vec<SupplyAtTime>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// The last supply of each minute, next to it the supply minus beacon chain issuance since the
+/// merge.
+pub struct SupplyByMinute;
+
+#[async_trait]
+impl SupplyBuckets for SupplyByMinute {
+    const TABLE: &'static str = "supply_since_merge_by_minute";
+
+    fn bucket_size() -> Duration {
+        Duration::minutes(1)
+    }
+
+    async fn store_supply_at(
+        &self,
+        connection: &mut PgConnection,
+        bucket: &DateTime<Utc>,
+        timestamp: &DateTime<Utc>,
+    ) {
+        sqlx::query!(
+            "
+            INSERT INTO supply_since_merge_by_minute (timestamp, supply, supply_less_pos_issuance)
+            SELECT
+                $1,
+                supply,
+                supply - (
+                    (
+                        SELECT gwei FROM beacon_issuance
+                        WHERE timestamp <= $2
+                        ORDER BY timestamp DESC
+                        LIMIT 1
+                    ) - (
+                        SELECT gwei FROM beacon_issuance
+                        WHERE timestamp <= $3
+                        ORDER BY timestamp DESC
+                        LIMIT 1
+                    )
+                ) * 1e9
+            FROM eth_supply
+            WHERE timestamp = $2
+            ON CONFLICT (timestamp) DO UPDATE SET
+                supply = excluded.supply,
+                supply_less_pos_issuance = excluded.supply_less_pos_issuance
+            ",
+            bucket,
+            timestamp,
+            FIRST_POST_MERGE_SLOT.date_time()
+        )
+        .execute(connection)
+        .await
+        .unwrap();
+    }
+}
+
+/// Fills in the supply minus beacon chain issuance of minutes stored before we kept it, a day at a
+/// time. Minutes which already have it are left alone, so a restart continues where it stopped.
+pub async fn backfill_supply_less_pos_issuance() {
+    log::init_with_env();
+
+    let db_pool = db::get_db_pool("backfill-supply-less-pos-issuance").await;
+    let merge_timestamp = FIRST_POST_MERGE_SLOT.date_time();
+    let now = Utc::now();
+
+    let mut start = merge_timestamp;
+    while start < now {
+        let end = start.duration_trunc(Duration::days(1)).unwrap() + Duration::days(1);
+
+        let updated = sqlx::query!(
+            "
+            UPDATE supply_since_merge_by_minute
+            SET
+                supply_less_pos_issuance = last_supply.supply - (
+                    (
+                        SELECT gwei FROM beacon_issuance
+                        WHERE timestamp <= last_supply.timestamp
+                        ORDER BY timestamp DESC
+                        LIMIT 1
+                    ) - (
+                        SELECT gwei FROM beacon_issuance
+                        WHERE timestamp <= $3
+                        ORDER BY timestamp DESC
+                        LIMIT 1
+                    )
+                ) * 1e9
+            FROM (
+                SELECT DISTINCT ON (DATE_TRUNC('minute', timestamp))
+                    DATE_TRUNC('minute', timestamp) AS minute,
+                    timestamp,
+                    supply
+                FROM eth_supply
+                WHERE timestamp >= $1
+                AND timestamp < $2
+                ORDER BY DATE_TRUNC('minute', timestamp), timestamp DESC
+            ) last_supply
+            WHERE supply_since_merge_by_minute.timestamp = last_supply.minute
+            AND supply_since_merge_by_minute.supply_less_pos_issuance IS NULL
+            ",
+            start,
+            end,
+            merge_timestamp
+        )
+        .execute(&db_pool)
+        .await
+        .unwrap()
+        .rows_affected();
+
+        debug!(%start, updated, "backfilled supply less proof-of-stake issuance");
+
+        start = end;
+    }
+
+    info!("done backfilling supply less proof-of-stake issuance");
+}
+
+pub async fn get_supply_since_merge_by_minute(executor: impl PgExecutor<'_>) -> Vec<SupplyAtTime> {
+    sqlx::query!(
+        r#"
+        SELECT timestamp, supply AS "supply: WeiNewtype"
+        FROM supply_since_merge_by_minute
+        ORDER BY timestamp ASC
+        "#
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| SupplyAtTime {
+        supply: row.supply.into(),
+        timestamp: row.timestamp,
+    })
+    .collect()
+}
+
+pub async fn get_pow_supply_since_merge_by_minute(
+    executor: impl PgExecutor<'_>,
+    daily_pow_issuance: EthNewtype,
+) -> Vec<SupplyAtTime> {
+    sqlx::query!(
+        r#"
+        SELECT
+            timestamp,
+            supply_less_pos_issuance + ROUND(
+                $1::FLOAT8::NUMERIC * EXTRACT(EPOCH FROM timestamp - $2)::NUMERIC / 86400 * 1e18
+            ) AS "pow_supply!: WeiNewtype"
+        FROM supply_since_merge_by_minute
+        WHERE supply_less_pos_issuance IS NOT NULL
+        ORDER BY timestamp ASC
+        "#,
+        daily_pow_issuance.0,
+        FIRST_POST_MERGE_SLOT.date_time()
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| SupplyAtTime {
+        supply: row.pow_supply.into(),
+        timestamp: row.timestamp,
+    })
+    .collect()
+}
+
+/// Runs after the `eth_supply` rollback, the minutes are rebuilt from what it left.
+pub struct SupplySinceMergeRollback;
+
+#[async_trait]
+impl Analysis for SupplySinceMergeRollback {
+    fn name(&self) -> &'static str {
+        "supply_since_merge_by_minute"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        buckets::on_rollback(&SupplyByMinute, transaction, point).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use sqlx::Acquire;
+
+    use crate::{
+        db,
+        eth_supply::{
+            rollback_supply_from_slot,
+            test::{make_test_block, store_test_supply_for_slot},
+        },
+        execution_chain,
+        units::EthDecimal,
+    };
+
+    use super::*;
+
+    fn minute_of(timestamp: &DateTime<Utc>) -> DateTime<Utc> {
+        buckets::bucket_of::<SupplyByMinute>(timestamp)
+    }
+
+    #[tokio::test]
+    async fn supply_since_merge_by_minute_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        execution_chain::store_block(&mut *transaction, &make_test_block(), 0.0).await;
+
+        // Slots are twelve seconds apart, slots 0 and 1 share a minute, slot 5 is in the next.
+        for (slot, supply) in [(Slot(0), 10.0), (Slot(1), 11.0), (Slot(5), 12.0)] {
+            store_test_supply_for_slot(&mut transaction, &slot, EthNewtype(supply)).await;
+            buckets::store_slot(&SupplyByMinute, &mut transaction, &slot).await;
+        }
+
+        let supply_by_minute = get_supply_since_merge_by_minute(&mut *transaction).await;
+        assert_eq!(
+            supply_by_minute,
+            vec![
+                SupplyAtTime {
+                    supply: EthDecimal(Decimal::new(11, 0)),
+                    timestamp: minute_of(&Slot(0).date_time()),
+                },
+                SupplyAtTime {
+                    supply: EthDecimal(Decimal::new(12, 0)),
+                    timestamp: minute_of(&Slot(5).date_time()),
+                },
+            ]
+        );
+
+        rollback_supply_from_slot(&mut transaction, &Slot(1))
+            .await
+            .unwrap();
+        SupplySinceMergeRollback
+            .on_rollback(&mut transaction, &RollbackPoint::SlotsGte(Slot(1)))
+            .await;
+
+        let supply_by_minute = get_supply_since_merge_by_minute(&mut *transaction).await;
+        assert_eq!(
+            supply_by_minute,
+            vec![SupplyAtTime {
+                supply: EthDecimal(Decimal::new(10, 0)),
+                timestamp: minute_of(&Slot(0).date_time()),
+            }]
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use sqlx::postgres::PgQueryResult;
 use sqlx::{Acquire, PgConnection, PgExecutor};
 use tracing::debug;
@@ -7,10 +8,16 @@ use crate::beacon_chain::Slot;
 use crate::units::{GweiNewtype, WeiNewtype};
 
 use crate::execution_chain::BlockNumber;
+use crate::{analysis::Analysis, rollback::RollbackPoint};
 
+use super::buckets;
+use super::daily_deltas::SupplyByDay;
 use super::parts::SupplyPartsError;
+use super::since_merge::SupplyByMinute;
 use super::SupplyPartsStore;
 
+// Supply is stored at the time of its slot. Bounding deletes by time as well lets Postgres skip
+// the yearly partitions which can't hold the slot.
 pub async fn rollback_supply_from_slot(
     executor: &mut PgConnection,
     greater_than_or_equal: &Slot,
@@ -20,10 +27,11 @@ pub async fn rollback_supply_from_slot(
         DELETE FROM
             eth_supply
         WHERE
-            deposits_slot >= $1
-            OR balances_slot >= $1
+            timestamp >= $2
+            AND (deposits_slot >= $1 OR balances_slot >= $1)
         ",
-        greater_than_or_equal.0
+        greater_than_or_equal.0,
+        greater_than_or_equal.date_time()
     )
     .execute(executor)
     .await
@@ -38,15 +46,39 @@ pub async fn rollback_supply_slot(
         DELETE FROM
             eth_supply
         WHERE
-            deposits_slot = $1
-            OR balances_slot = $1
+            timestamp = $2
+            AND (deposits_slot = $1 OR balances_slot = $1)
         ",
-        greater_than_or_equal.0
+        greater_than_or_equal.0,
+        greater_than_or_equal.date_time()
     )
     .execute(executor)
     .await
 }
 
+pub struct EthSupplyRollback;
+
+#[async_trait]
+impl Analysis for EthSupplyRollback {
+    fn name(&self) -> &'static str {
+        "eth_supply"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        match point {
+            RollbackPoint::BlockNumbersGte(_) => (),
+            RollbackPoint::Slot(slot) => {
+                rollback_supply_slot(transaction, slot).await.unwrap();
+            }
+            RollbackPoint::SlotsGte(slot_gte) => {
+                rollback_supply_from_slot(transaction, slot_gte)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+}
+
 pub async fn store(
     executor: impl PgExecutor<'_>,
     slot: &Slot,
@@ -57,7 +89,7 @@ pub async fn store(
 ) -> sqlx::Result<PgQueryResult> {
     let timestamp = slot.date_time();
 
-    debug!(%timestamp, %slot, block_number, %execution_balances_sum, %beacon_deposits_sum, %beacon_balances_sum, "storing eth supply");
+    debug!(%timestamp, %slot, %block_number, %execution_balances_sum, %beacon_deposits_sum, %beacon_balances_sum, "storing eth supply");
 
     let supply = *execution_balances_sum + beacon_balances_sum.into() - beacon_deposits_sum.into();
 
@@ -70,13 +102,13 @@ pub async fn store(
             balances_slot,
             supply
         )
-        VALUES ($1, $2, $3, $4, $5::NUMERIC)
+        VALUES ($1, $2, $3, $4, $5)
         ",
         timestamp,
-        *block_number,
+        block_number.0,
         slot.0,
         slot.0,
-        Into::<String>::into(supply) as String,
+        supply as WeiNewtype,
     )
     .execute(executor)
     .await
@@ -137,6 +169,9 @@ pub async fn store_supply_for_slot(executor_acq: &mut PgConnection, slot: &Slot)
             )
             .await
             .unwrap();
+
+            buckets::store_slot(&SupplyByMinute, executor_acq, slot).await;
+            buckets::store_slot(&SupplyByDay, executor_acq, slot).await;
         }
     };
 }
@@ -168,7 +203,7 @@ pub async fn last_eth_supply(executor: impl PgExecutor<'_>) -> WeiNewtype {
     sqlx::query!(
         r#"
         SELECT
-            supply::TEXT AS "supply!"
+            supply AS "supply: WeiNewtype"
         FROM
             eth_supply
         ORDER BY
@@ -180,8 +215,6 @@ pub async fn last_eth_supply(executor: impl PgExecutor<'_>) -> WeiNewtype {
     .await
     .unwrap()
     .supply
-    .parse::<WeiNewtype>()
-    .unwrap()
 }
 
 #[cfg(test)]
@@ -203,10 +236,13 @@ mod tests {
     fn make_test_block() -> ExecutionNodeBlock {
         ExecutionNodeBlock {
             base_fee_per_gas: 0,
+            blob_gas_used: None,
             difficulty: 0,
+            excess_blob_gas: None,
+            gas_limit: 0,
             gas_used: 0,
             hash: "0xtest".to_string(),
-            number: 0,
+            number: BlockNumber(0),
             parent_hash: "0xparent".to_string(),
             timestamp: Utc::now().trunc_subsecs(0),
             total_difficulty: 10,
@@ -257,7 +293,7 @@ mod tests {
 
         let supply_delta_test = SupplyDelta {
             supply_delta: 1,
-            block_number: 0,
+            block_number: BlockNumber(0),
             block_hash: test_block.block_hash().unwrap().to_string(),
             fee_burn: 0,
             fixed_reward: 0,
@@ -323,7 +359,7 @@ mod tests {
 
         let supply_parts = SupplyParts::new(
             &slot,
-            &0,
+            &BlockNumber(0),
             Into::<WeiNewtype>::into(GweiNewtype(10)),
             GweiNewtype(20),
             GweiNewtype(5),
@@ -332,7 +368,7 @@ mod tests {
         store(
             &mut *transaction,
             &slot,
-            &0,
+            &BlockNumber(0),
             &supply_parts.execution_balances_sum,
             &supply_parts.beacon_balances_sum,
             &supply_parts.beacon_deposits_sum,
@@ -363,7 +399,7 @@ mod tests {
 
         let supply_parts = SupplyParts::new(
             &slot,
-            &0,
+            &BlockNumber(0),
             GweiNewtype(10).into(),
             GweiNewtype(20),
             GweiNewtype(5),
@@ -372,7 +408,7 @@ mod tests {
         store(
             &mut *transaction,
             &slot,
-            &0,
+            &BlockNumber(0),
             &supply_parts.execution_balances_sum,
             &supply_parts.beacon_balances_sum,
             &supply_parts.beacon_deposits_sum,
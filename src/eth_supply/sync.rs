@@ -7,8 +7,9 @@ use crate::beacon_chain::{Slot, FIRST_POST_MERGE_SLOT};
 
 use super::store;
 
-/// Stores an eth supply for a given slot.
-pub async fn sync_eth_supply(executor: &mut PgConnection, slot: &Slot) {
+/// Stores an eth supply for a given slot, and any slots before it we're missing. Returns the slots
+/// stored.
+pub async fn sync_eth_supply(executor: &mut PgConnection, slot: &Slot) -> Vec<Slot> {
     let last_stored_execution_balances_slot =
         store::get_last_stored_balances_slot(executor.acquire().await.unwrap()).await;
 
@@ -29,19 +30,20 @@ pub async fn sync_eth_supply(executor: &mut PgConnection, slot: &Slot) {
             match last_stored_supply_slot {
                 None => {
                     debug!(
-                        %FIRST_POST_MERGE_SLOT,
+                        first_post_merge_slot = %*FIRST_POST_MERGE_SLOT,
                         "eth supply has never been stored, starting from FIRST_POST_MERGE_SLOT"
                     );
-                    let range = FIRST_POST_MERGE_SLOT.0..=sync_limit.0;
-                    let slots: Vec<_> = range.map(Slot).collect();
+                    let slots: Vec<_> =
+                        Slot::range_inclusive(*FIRST_POST_MERGE_SLOT, sync_limit).collect();
                     Some(slots)
                 }
                 Some(last_stored_supply_slot) => match last_stored_supply_slot.cmp(&sync_limit) {
                     Ordering::Less => {
                         debug!("execution balances have updated, storing eth supply for new slots");
                         let first = last_stored_supply_slot + 1;
-                        let range = first.0..=last_stored_execution_balances_slot.0;
-                        let slots = range.map(Slot).collect();
+                        let slots =
+                            Slot::range_inclusive(first, last_stored_execution_balances_slot)
+                                .collect();
                         Some(slots)
                     }
                     Ordering::Equal => {
@@ -57,13 +59,14 @@ pub async fn sync_eth_supply(executor: &mut PgConnection, slot: &Slot) {
         }
     };
 
-    if let Some(slots_to_store) = slots_to_store {
-        for slot in slots_to_store {
-            debug!(
-                %slot,
-                "storing eth supply for newly available execution balance slot"
-            );
-            store::store_supply_for_slot(executor, &slot).await;
-        }
+    let slots_to_store = slots_to_store.unwrap_or_default();
+    for slot in slots_to_store.iter() {
+        debug!(
+            %slot,
+            "storing eth supply for newly available execution balance slot"
+        );
+        store::store_supply_for_slot(executor, slot).await;
     }
+
+    slots_to_store
 }
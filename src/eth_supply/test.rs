@@ -4,17 +4,20 @@ use sqlx::{Acquire, PgConnection};
 
 use crate::beacon_chain::Slot;
 use crate::beacon_chain::{self, BeaconBalancesSum, BeaconDepositsSum};
-use crate::execution_chain::{self, ExecutionBalancesSum, ExecutionNodeBlock};
+use crate::execution_chain::{self, BlockNumber, ExecutionBalancesSum, ExecutionNodeBlock};
 use crate::units::{EthNewtype, GweiNewtype};
 
 // Replace with shared testing helper that helps easily build the right mock block.
 pub fn make_test_block() -> ExecutionNodeBlock {
     ExecutionNodeBlock {
         base_fee_per_gas: 0,
+        blob_gas_used: None,
         difficulty: 0,
+        excess_blob_gas: None,
+        gas_limit: 0,
         gas_used: 0,
         hash: "0xtest".to_string(),
-        number: 0,
+        number: BlockNumber(0),
         parent_hash: "0xparent".to_string(),
         timestamp: Utc::now().trunc_subsecs(0),
         total_difficulty: 10,
@@ -35,7 +38,7 @@ pub async fn store_test_eth_supply(
     beacon_chain::store_state(connection.acquire().await.unwrap(), state_root, slot).await;
 
     let execution_balances_sum = ExecutionBalancesSum {
-        block_number: 0,
+        block_number: BlockNumber(0),
         balances_sum: eth_supply.into(),
     };
     let beacon_balances_sum = BeaconBalancesSum {
@@ -59,3 +62,24 @@ pub async fn store_test_eth_supply(
 
     Ok(())
 }
+
+/// Stores a supply for the slot on the test block, which should be stored already. Unlike
+/// `store_test_eth_supply` this may be called for several slots.
+pub async fn store_test_supply_for_slot(
+    connection: &mut PgConnection,
+    slot: &Slot,
+    supply: EthNewtype,
+) {
+    let state_root = format!("0x{}_state_root", slot.0);
+    beacon_chain::store_state(connection.acquire().await.unwrap(), &state_root, slot).await;
+    super::store(
+        connection,
+        slot,
+        &BlockNumber(0),
+        &supply.into(),
+        &GweiNewtype(0),
+        &GweiNewtype(0),
+    )
+    .await
+    .unwrap();
+}
@@ -3,10 +3,10 @@ use format_url::FormatUrl;
 use lazy_static::lazy_static;
 use serde::Deserialize;
 
-use crate::{env, units::WeiNewtype, usd_price::EthPrice};
+use crate::{config, units::WeiNewtype, usd_price::EthPrice};
 
 lazy_static! {
-    static ref ETHERSCAN_API_KEY: String = env::get_env_var_unsafe("ETHERSCAN_API_KEY");
+    static ref ETHERSCAN_API_KEY: String = config::CONFIG.etherscan_api_key().to_string();
 }
 
 const ETHERSCAN_API: &str = "https://api.etherscan.io/api";
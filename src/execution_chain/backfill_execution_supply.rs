@@ -0,0 +1,190 @@
+use std::num::NonZeroU32;
+
+use sqlx::PgPool;
+use tracing::{debug, info};
+
+use crate::{
+    db,
+    job_progress::JobProgress,
+    key_value_store::KeyValueStorePostgres,
+    log,
+    units::{Wei, WeiNewtype},
+};
+
+use super::{BlockNumber, BlockRange, GENESIS_BLOCK_HASH, GENESIS_SUPPLY};
+
+const BACKFILL_EXECUTION_SUPPLY_KEY: &str = "backfill-execution-supply";
+
+const BLOCK_NUMBER_MIN_BEFORE_BACKFILL: BlockNumber = BlockNumber(15082719);
+
+async fn bulk_insert_execution_supplies(
+    pool: &PgPool,
+    execution_supplies: &[(String, BlockNumber, i128)],
+) {
+    let block_hashes = execution_supplies
+        .iter()
+        .map(|(block_hash, _, _)| block_hash.as_str())
+        .collect::<Vec<_>>();
+    let block_numbers = execution_supplies
+        .iter()
+        .map(|(_, block_number, _)| *block_number)
+        .collect::<Vec<_>>();
+    let balances_sums = execution_supplies
+        .iter()
+        .map(|(_, _, balances_sum)| balances_sum.to_string())
+        .collect::<Vec<_>>();
+
+    sqlx::query!(
+        "
+        INSERT INTO execution_supply (block_hash, block_number, balances_sum)
+        SELECT * FROM UNNEST($1::text[], $2::int8[], $3::numeric[])
+        ON CONFLICT (block_hash) DO UPDATE SET
+            balances_sum = excluded.balances_sum,
+            block_number = excluded.block_number
+        ",
+        &block_hashes[..] as &[&str],
+        &block_numbers[..] as &[BlockNumber],
+        &balances_sums as &Vec<String>,
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// Sums the stored supply deltas into balances sums, from genesis up to the block the supply
+/// snapshot was taken at.
+pub async fn backfill_execution_supply() {
+    log::init_with_env();
+
+    let db_pool = db::get_db_pool("backfill-execution-supply").await;
+
+    let key_value_store = KeyValueStorePostgres::new(db_pool.clone());
+    let job_progress = JobProgress::new(BACKFILL_EXECUTION_SUPPLY_KEY, &key_value_store);
+
+    let last_synced_block: Option<BlockNumber> = job_progress.get().await;
+
+    let mut last_supply: (String, BlockNumber, Wei) = match last_synced_block {
+        None => {
+            let genesis_supply = GENESIS_SUPPLY.expect(
+                "expect genesis supply to be known, set genesis_supply under [network_overrides]",
+            );
+
+            // Store genensis supply and return it as the last synced block.
+            sqlx::query!(
+                "
+                INSERT INTO execution_supply (block_hash, block_number, balances_sum)
+                VALUES ($1, 0, $2)
+                ON CONFLICT (block_hash) DO NOTHING
+                ",
+                GENESIS_BLOCK_HASH.as_str(),
+                genesis_supply as WeiNewtype,
+            )
+            .execute(&db_pool)
+            .await
+            .unwrap();
+
+            job_progress.set(&BlockNumber::GENESIS).await;
+
+            (
+                GENESIS_BLOCK_HASH.clone(),
+                BlockNumber::GENESIS,
+                genesis_supply.0,
+            )
+        }
+        Some(last_synced_block) => {
+            // Get the supply of the last synced block from the db.
+            let row = sqlx::query!(
+                "
+                SELECT
+                    block_hash,
+                    block_number,
+                    balances_sum::TEXT
+                FROM
+                    execution_supply
+                WHERE
+                    block_number = $1
+                ",
+                last_synced_block.0
+            )
+            .fetch_one(&db_pool)
+            .await
+            .unwrap();
+            let balances_sum = row.balances_sum.unwrap().parse::<Wei>().unwrap();
+            (row.block_hash, BlockNumber(row.block_number), balances_sum)
+        }
+    };
+
+    let work_todo =
+        BLOCK_NUMBER_MIN_BEFORE_BACKFILL - last_synced_block.unwrap_or(BlockNumber::GENESIS);
+    let mut progress =
+        pit_wall::Progress::new("backfill-execution-supply", work_todo.try_into().unwrap());
+    info!(?work_todo, "backfilling execution supply");
+
+    debug!(
+        balances_sum = last_supply.2,
+        block_number = %last_supply.1,
+        "found earliest stored supply"
+    );
+
+    const BULK_INSERT_SIZE: NonZeroU32 = NonZeroU32::new(10000).unwrap();
+
+    if last_supply.1 >= BLOCK_NUMBER_MIN_BEFORE_BACKFILL - 1 {
+        info!("execution supply already backfilled");
+        return;
+    }
+
+    let block_range = BlockRange::new(last_supply.1 + 1, BLOCK_NUMBER_MIN_BEFORE_BACKFILL - 1);
+
+    // To get execution supply n, we add execution supply n - 1 to the delta for block n.
+    for next_range in block_range.chunks(BULK_INSERT_SIZE) {
+        debug!("storing execution supplies for {:?}", next_range);
+
+        let supply_deltas = sqlx::query!(
+            "
+            SELECT block_number, block_hash, parent_hash, supply_delta::TEXT FROM execution_supply_deltas
+            WHERE block_number >= $1 AND block_number <= $2
+            ORDER BY block_number ASC
+            ",
+            next_range.start.0,
+            next_range.end.0,
+        )
+        .fetch_all(&db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| {
+            let supply_delta = row.supply_delta.unwrap().parse::<Wei>().unwrap();
+            (
+                BlockNumber(row.block_number),
+                row.block_hash,
+                row.parent_hash,
+                supply_delta,
+            )
+        })
+        .collect::<Vec<_>>();
+
+        let new_execution_supplies: Vec<(String, BlockNumber, Wei)> = supply_deltas
+            .iter()
+            .map(|row| {
+                // We calculate the next supply by taking the last supply we synced,
+                // then take the delta for that block, and adding the delta we get the
+                // next execution supply.
+                let block_number = row.0;
+                let block_hash = row.1.clone();
+                let balances_sum = last_supply.2 + row.3;
+
+                last_supply = (block_hash.clone(), block_number, balances_sum);
+
+                (block_hash, block_number, balances_sum)
+            })
+            .collect();
+
+        bulk_insert_execution_supplies(&db_pool, &new_execution_supplies).await;
+
+        job_progress.set(&last_supply.1).await;
+
+        progress.inc_work_done_by(supply_deltas.len().try_into().unwrap());
+        debug!(?last_supply, "stored execution supplies");
+        debug!("{}", progress.get_progress_string());
+    }
+}
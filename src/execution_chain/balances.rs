@@ -44,7 +44,7 @@ pub async fn get_execution_balances_by_hash(
     .map(|row: PgRow| {
         let balances_sum: WeiNewtype = (row.get::<String, _>("balances_sum")).parse().unwrap();
         let block_hash = row.get::<String, _>("block_hash");
-        let block_number = row.get::<i32, _>("block_number");
+        let block_number = row.get::<BlockNumber, _>("block_number");
 
         ExecutionSupply {
             balances_sum,
@@ -87,7 +87,7 @@ mod tests {
 
         let supply_delta_test = SupplyDelta {
             supply_delta: 1,
-            block_number: 0,
+            block_number: BlockNumber(0),
             block_hash: block_hash.clone(),
             fee_burn: 0,
             fixed_reward: 0,
@@ -105,7 +105,7 @@ mod tests {
         assert_eq!(
             ExecutionSupply {
                 block_hash,
-                block_number: 0,
+                block_number: BlockNumber(0),
                 balances_sum: WeiNewtype(1)
             },
             balances
@@ -1,5 +1,6 @@
 mod barrier;
 mod last;
+mod next;
 mod over_time;
 pub mod routes;
 mod stats;
@@ -42,9 +43,10 @@ pub async fn on_new_block(
     issuance_store: &impl IssuanceStore,
     block: &ExecutionNodeBlock,
 ) {
-    let (barrier, ()) = join!(
+    let (barrier, (), ()) = join!(
         barrier::get_barrier(issuance_store),
         last::update_last_base_fee(db_pool, block).timed("update_last_base_fee"),
+        next::update_next_base_fee(db_pool, block).timed("update_next_base_fee"),
     );
 
     join!(
@@ -0,0 +1,135 @@
+use chrono::Duration;
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::debug;
+
+use crate::{
+    beacon_chain::SECONDS_PER_SLOT,
+    caching::{self, CacheKey},
+    execution_chain::{self, BlockNumber, ExecutionNodeBlock},
+};
+
+const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+const ELASTICITY_MULTIPLIER: u64 = 2;
+
+#[derive(Debug, PartialEq, Serialize)]
+struct NextBaseFee {
+    blob_base_fee: Option<u64>,
+    block_number: BlockNumber,
+    wei: u64,
+}
+
+/// Applies the EIP-1559 base fee update rule to the given parent block.
+fn next_base_fee_per_gas(block: &ExecutionNodeBlock) -> u64 {
+    let gas_target = block.gas_limit as u64 / ELASTICITY_MULTIPLIER;
+    let gas_used = block.gas_used as u64;
+    let base_fee_per_gas = block.base_fee_per_gas;
+
+    // A gas limit below the elasticity multiplier leaves no target to move towards.
+    if gas_used == gas_target || gas_target == 0 {
+        base_fee_per_gas
+    } else if gas_used > gas_target {
+        let delta = (base_fee_per_gas as u128 * (gas_used - gas_target) as u128
+            / gas_target as u128
+            / BASE_FEE_MAX_CHANGE_DENOMINATOR as u128) as u64;
+        base_fee_per_gas + delta.max(1)
+    } else {
+        let delta = (base_fee_per_gas as u128 * (gas_target - gas_used) as u128
+            / gas_target as u128
+            / BASE_FEE_MAX_CHANGE_DENOMINATOR as u128) as u64;
+        base_fee_per_gas - delta
+    }
+}
+
+fn next_blob_base_fee(block: &ExecutionNodeBlock) -> Option<u64> {
+    // The next block may be the first under new blob params.
+    let next_timestamp = block.timestamp + Duration::seconds((*SECONDS_PER_SLOT).into());
+    let params = execution_chain::blob_params_at(&next_timestamp)?;
+    block.next_excess_blob_gas().map(|next_excess_blob_gas| {
+        let blob_base_fee =
+            execution_chain::blob_base_fee_from_excess_blob_gas(next_excess_blob_gas, &params);
+        u64::try_from(blob_base_fee).expect("expect blob base fee to fit in u64")
+    })
+}
+
+pub async fn update_next_base_fee(db_pool: &PgPool, block: &ExecutionNodeBlock) {
+    debug!("updating next base fee");
+
+    let next_base_fee = NextBaseFee {
+        blob_base_fee: next_blob_base_fee(block),
+        block_number: block.number + 1,
+        wei: next_base_fee_per_gas(block),
+    };
+
+    caching::update_and_publish(db_pool, &CacheKey::NextBaseFee, next_base_fee).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::execution_chain::{ExecutionNodeBlockBuilder, CANCUN_HARD_FORK_TIMESTAMP};
+
+    use super::*;
+
+    fn make_test_block(gas_used: i32) -> ExecutionNodeBlock {
+        ExecutionNodeBlockBuilder::new("next_base_fee")
+            .with_base_fee_per_gas(1000)
+            .with_gas_limit(100)
+            .with_gas_used(gas_used)
+            .build()
+    }
+
+    #[test]
+    fn next_base_fee_per_gas_at_target_test() {
+        assert_eq!(next_base_fee_per_gas(&make_test_block(50)), 1000);
+    }
+
+    #[test]
+    fn next_base_fee_per_gas_full_test() {
+        assert_eq!(next_base_fee_per_gas(&make_test_block(100)), 1125);
+    }
+
+    #[test]
+    fn next_base_fee_per_gas_empty_test() {
+        assert_eq!(next_base_fee_per_gas(&make_test_block(0)), 875);
+    }
+
+    #[test]
+    fn next_base_fee_per_gas_min_increase_test() {
+        let block = ExecutionNodeBlockBuilder::new("next_base_fee_min_increase")
+            .with_base_fee_per_gas(7)
+            .with_gas_limit(100)
+            .with_gas_used(51)
+            .build();
+        assert_eq!(next_base_fee_per_gas(&block), 8);
+    }
+
+    #[test]
+    fn next_base_fee_per_gas_zero_gas_limit_test() {
+        let block = ExecutionNodeBlockBuilder::new("next_base_fee_zero_gas_limit")
+            .with_base_fee_per_gas(1000)
+            .with_gas_limit(1)
+            .with_gas_used(1)
+            .build();
+        assert_eq!(next_base_fee_per_gas(&block), 1000);
+
+        let block = ExecutionNodeBlockBuilder::new("next_base_fee_zero_gas_limit")
+            .with_base_fee_per_gas(1000)
+            .with_gas_limit(0)
+            .with_gas_used(10)
+            .build();
+        assert_eq!(next_base_fee_per_gas(&block), 1000);
+    }
+
+    #[test]
+    fn next_blob_base_fee_test() {
+        let block = ExecutionNodeBlockBuilder::new("next_blob_base_fee")
+            .with_timestamp(&CANCUN_HARD_FORK_TIMESTAMP)
+            .with_blob_gas_used(0)
+            .with_excess_blob_gas(0)
+            .build();
+        assert_eq!(next_blob_base_fee(&block), Some(1));
+
+        let pre_cancun_block = ExecutionNodeBlockBuilder::new("next_blob_base_fee").build();
+        assert_eq!(next_blob_base_fee(&pre_cancun_block), None);
+    }
+}
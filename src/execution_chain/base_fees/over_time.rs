@@ -91,7 +91,7 @@ async fn from_time_frame(
             )
             .bind(ltf.postgres_interval())
             .map(|row: PgRow| {
-                let block_number: BlockNumber = row.get::<i32, _>("number");
+                let block_number: BlockNumber = row.get("number");
                 let timestamp: DateTime<Utc> = row.get::<DateTime<Utc>, _>("timestamp");
                 let wei = row.get::<f64, _>("base_fee_per_gas");
                 BaseFeeAtTime {
@@ -246,10 +246,13 @@ mod tests {
     fn make_test_block() -> ExecutionNodeBlock {
         ExecutionNodeBlock {
             base_fee_per_gas: 1,
+            blob_gas_used: None,
             difficulty: 0,
+            excess_blob_gas: None,
+            gas_limit: 0,
             gas_used: 0,
             hash: "0xtest".to_string(),
-            number: 0,
+            number: BlockNumber(0),
             parent_hash: "0xparent".to_string(),
             timestamp: Utc::now().trunc_subsecs(0),
             total_difficulty: 10,
@@ -301,7 +304,7 @@ mod tests {
         let test_block_2 = ExecutionNodeBlock {
             hash: "0xtest2".to_string(),
             parent_hash: "0xtest".to_string(),
-            number: 1,
+            number: BlockNumber(1),
             timestamp: Utc::now().trunc_subsecs(0) + Duration::days(1),
             ..make_test_block()
         };
@@ -316,12 +319,12 @@ mod tests {
             vec![
                 BaseFeeAtTime {
                     wei: 1.0,
-                    block_number: Some(0),
+                    block_number: Some(BlockNumber(0)),
                     timestamp: test_block_1.timestamp
                 },
                 BaseFeeAtTime {
                     wei: 1.0,
-                    block_number: Some(1),
+                    block_number: Some(BlockNumber(1)),
                     timestamp: test_block_2.timestamp
                 }
             ]
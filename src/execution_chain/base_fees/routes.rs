@@ -10,7 +10,7 @@ use tracing::warn;
 use crate::{
     caching::CacheKey,
     serve::{self, StateExtension},
-    time_frames::{GrowingTimeFrame, LimitedTimeFrame, TimeFrame},
+    time_frames::{LimitedTimeFrame, TimeFrame},
 };
 
 struct CachePair {
@@ -20,7 +20,6 @@ struct CachePair {
 
 lazy_static! {
     static ref CACHE_DURATION_MAP: HashMap<TimeFrame, CachePair> = {
-        use GrowingTimeFrame::*;
         use LimitedTimeFrame::*;
         use TimeFrame::*;
 
@@ -31,8 +30,7 @@ lazy_static! {
                 Limited(Day1) => (Duration::minutes(5), Duration::hours(1)),
                 Limited(Day7) => (Duration::minutes(5), Duration::days(1)),
                 Limited(Day30) => (Duration::minutes(30), Duration::days(2)),
-                Growing(SinceBurn) => (Duration::hours(1), Duration::days(14)),
-                Growing(SinceMerge) => (Duration::hours(1), Duration::days(14)),
+                Growing(_) => (Duration::hours(1), Duration::days(14)),
             };
             (
                 time_frame,
@@ -15,8 +15,6 @@ use crate::{
     units::WeiF64,
 };
 
-use GrowingTimeFrame::*;
-
 use super::barrier::Barrier;
 
 async fn base_fee_per_gas_average(executor: impl PgExecutor<'_>, time_frame: &TimeFrame) -> WeiF64 {
@@ -32,7 +30,7 @@ async fn base_fee_per_gas_average(executor: impl PgExecutor<'_>, time_frame: &Ti
                     WHERE
                         number >= $1
                     "#,
-                    growing_time_frame.start_block_number()
+                    growing_time_frame.start_block_number().0
                 )
                 .fetch_one(executor)
                 .await.unwrap().average
@@ -70,7 +68,7 @@ async fn base_fee_per_gas_min(
     time_frame: &TimeFrame,
 ) -> (BlockNumber, WeiF64) {
     match time_frame {
-        TimeFrame::Growing(SinceMerge) => sqlx::query!(
+        TimeFrame::Growing(growing_time_frame) => sqlx::query!(
             r#"
             SELECT
                 number,
@@ -78,29 +76,15 @@ async fn base_fee_per_gas_min(
             FROM
                 blocks_next
             WHERE
-                timestamp >= '2022-09-15T06:42:42Z'::TIMESTAMPTZ
-            ORDER BY base_fee_per_gas ASC
-            LIMIT 1
-            "#,
-        )
-        .fetch_one(executor)
-        .await
-        .map(|row| (row.number, row.base_fee_per_gas as f64))
-        .unwrap(),
-        TimeFrame::Growing(SinceBurn) => sqlx::query!(
-            r#"
-            SELECT
-                number,
-                base_fee_per_gas AS "base_fee_per_gas!"
-            FROM
-                blocks_next
+                timestamp >= $1
             ORDER BY base_fee_per_gas ASC
             LIMIT 1
             "#,
+            growing_time_frame.start_timestamp()
         )
         .fetch_one(executor)
         .await
-        .map(|row| (row.number, row.base_fee_per_gas as f64))
+        .map(|row| (BlockNumber(row.number), row.base_fee_per_gas as f64))
         .unwrap(),
         TimeFrame::Limited(limited_time_frame) => sqlx::query!(
             r#"
@@ -118,7 +102,7 @@ async fn base_fee_per_gas_min(
         )
         .fetch_one(executor)
         .await
-        .map(|row| (row.number, row.base_fee_per_gas as f64))
+        .map(|row| (BlockNumber(row.number), row.base_fee_per_gas as f64))
         .unwrap(),
     }
 }
@@ -145,7 +129,7 @@ async fn base_fee_per_gas_max(
         )
         .fetch_one(executor)
         .await
-        .map(|row| (row.number, row.base_fee_per_gas as f64))
+        .map(|row| (BlockNumber(row.number), row.base_fee_per_gas as f64))
         .unwrap(),
         TimeFrame::Limited(limited_time_frame) => sqlx::query!(
             r#"
@@ -163,7 +147,7 @@ async fn base_fee_per_gas_max(
         )
         .fetch_one(executor)
         .await
-        .map(|row| (row.number, row.base_fee_per_gas as f64))
+        .map(|row| (BlockNumber(row.number), row.base_fee_per_gas as f64))
         .unwrap(),
     }
 }
@@ -229,10 +213,7 @@ impl BaseFeePerGasStats {
         block: &ExecutionNodeBlock,
     ) -> Self {
         match time_frame {
-            TimeFrame::Growing(SinceBurn) => {
-                base_fee_per_gas_stats_one_hour_cached(db_pool, time_frame, block).await
-            }
-            TimeFrame::Growing(SinceMerge) => {
+            TimeFrame::Growing(_) => {
                 base_fee_per_gas_stats_one_hour_cached(db_pool, time_frame, block).await
             }
             TimeFrame::Limited(LimitedTimeFrame::Day30) => {
@@ -283,9 +264,10 @@ pub async fn update_base_fee_stats(
 
     debug!("updating base fee over time");
 
-    let (since_burn, since_merge, d30, d7, d1, h1, m5) = join!(
+    let (since_burn, since_merge, since_shapella, d30, d7, d1, h1, m5) = join!(
         BaseFeePerGasStats::from_time_frame_cached(executor, &Growing(SinceBurn), block,),
         BaseFeePerGasStats::from_time_frame_cached(executor, &Growing(SinceMerge), block,),
+        BaseFeePerGasStats::from_time_frame_cached(executor, &Growing(SinceShapella), block,),
         BaseFeePerGasStats::from_time_frame_cached(executor, &Limited(Day30), block,),
         BaseFeePerGasStats::from_time_frame_cached(executor, &Limited(Day7), block,),
         BaseFeePerGasStats::from_time_frame_cached(executor, &Limited(Day1), block,),
@@ -298,6 +280,7 @@ pub async fn update_base_fee_stats(
     let mut base_fee_per_gas_stats = HashMap::new();
     base_fee_per_gas_stats.insert(Growing(SinceBurn), since_burn.clone());
     base_fee_per_gas_stats.insert(Growing(SinceMerge), since_merge.clone());
+    base_fee_per_gas_stats.insert(Growing(SinceShapella), since_shapella);
     base_fee_per_gas_stats.insert(Limited(Day30), d30.clone());
     base_fee_per_gas_stats.insert(Limited(Day7), d7.clone());
     base_fee_per_gas_stats.insert(Limited(Day1), d1.clone());
@@ -432,13 +415,13 @@ mod tests {
             &TimeFrame::Limited(LimitedTimeFrame::Hour1),
         )
         .await;
-        assert_eq!(base_fee_per_gas_min, (0, 10.0));
+        assert_eq!(base_fee_per_gas_min, (BlockNumber(0), 10.0));
 
         let base_fee_per_gas_max = base_fee_per_gas_max(
             &mut *transaction,
             &TimeFrame::Limited(LimitedTimeFrame::Hour1),
         )
         .await;
-        assert_eq!(base_fee_per_gas_max, (1, 20.0));
+        assert_eq!(base_fee_per_gas_max, (BlockNumber(1), 20.0));
     }
 }
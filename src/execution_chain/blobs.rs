@@ -0,0 +1,259 @@
+//! # Blobs
+//! EIP-4844 introduced a second fee market for blob gas. Like the base fee, the blob base fee is
+//! burned. Unlike the base fee, it is not part of the block header, but derived from the
+//! `excess_blob_gas` field using the `fake_exponential` function from the EIP.
+//!
+//! From Osaka on, EIP-7918 keeps the blob base fee from dropping far below the execution base
+//! fee. While blobs are cheaper than `BLOB_BASE_COST` execution gas, excess blob gas only grows.
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+
+use crate::{network::NETWORK_CONSTANTS, units::WeiNewtype};
+
+use super::ExecutionNodeBlock;
+
+pub const GAS_PER_BLOB: i32 = 131_072;
+const MIN_BLOB_BASE_FEE: u128 = 1;
+const BLOB_BASE_COST: u128 = 1 << 13;
+
+/// The blob market parameters, which change with some hard forks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlobParams {
+    pub base_fee_update_fraction: u128,
+    pub max_blob_gas_per_block: i32,
+    /// Whether the EIP-7918 reserve price applies.
+    pub reserve_price: bool,
+    pub target_blob_gas_per_block: i32,
+}
+
+const CANCUN_BLOB_PARAMS: BlobParams = BlobParams {
+    base_fee_update_fraction: 3_338_477,
+    max_blob_gas_per_block: 6 * GAS_PER_BLOB,
+    reserve_price: false,
+    target_blob_gas_per_block: 3 * GAS_PER_BLOB,
+};
+
+// EIP-7691 raised the blob target and max, and slowed down how fast the blob base fee moves.
+const PRAGUE_BLOB_PARAMS: BlobParams = BlobParams {
+    base_fee_update_fraction: 5_007_716,
+    max_blob_gas_per_block: 9 * GAS_PER_BLOB,
+    reserve_price: false,
+    target_blob_gas_per_block: 6 * GAS_PER_BLOB,
+};
+
+const OSAKA_BLOB_PARAMS: BlobParams = BlobParams {
+    reserve_price: true,
+    ..PRAGUE_BLOB_PARAMS
+};
+
+// From Osaka on, blob parameter only (BPO) forks raise the blob target and max by themselves.
+const BPO1_BLOB_PARAMS: BlobParams = BlobParams {
+    base_fee_update_fraction: 8_346_193,
+    max_blob_gas_per_block: 15 * GAS_PER_BLOB,
+    reserve_price: true,
+    target_blob_gas_per_block: 10 * GAS_PER_BLOB,
+};
+
+const BPO2_BLOB_PARAMS: BlobParams = BlobParams {
+    base_fee_update_fraction: 11_684_671,
+    max_blob_gas_per_block: 21 * GAS_PER_BLOB,
+    reserve_price: true,
+    target_blob_gas_per_block: 14 * GAS_PER_BLOB,
+};
+
+lazy_static! {
+    pub static ref CANCUN_HARD_FORK_TIMESTAMP: DateTime<Utc> = NETWORK_CONSTANTS.cancun_timestamp;
+    pub static ref PRAGUE_HARD_FORK_TIMESTAMP: DateTime<Utc> = NETWORK_CONSTANTS.prague_timestamp;
+    /// When each set of blob parameters activates, latest first.
+    static ref BLOB_SCHEDULE: [(DateTime<Utc>, BlobParams); 5] = [
+        (NETWORK_CONSTANTS.bpo2_timestamp, BPO2_BLOB_PARAMS),
+        (NETWORK_CONSTANTS.bpo1_timestamp, BPO1_BLOB_PARAMS),
+        (NETWORK_CONSTANTS.osaka_timestamp, OSAKA_BLOB_PARAMS),
+        (NETWORK_CONSTANTS.prague_timestamp, PRAGUE_BLOB_PARAMS),
+        (NETWORK_CONSTANTS.cancun_timestamp, CANCUN_BLOB_PARAMS),
+    ];
+}
+
+/// Returns the blob parameters active at the given time, `None` before Cancun.
+pub fn blob_params_at(timestamp: &DateTime<Utc>) -> Option<BlobParams> {
+    BLOB_SCHEDULE
+        .iter()
+        .find(|(activation, _)| timestamp >= activation)
+        .map(|(_, params)| *params)
+}
+
+/// Approximates factor * e ** (numerator / denominator) using Taylor expansion, exactly as
+/// specified in EIP-4844.
+fn fake_exponential(factor: u128, numerator: u128, denominator: u128) -> u128 {
+    let mut i = 1;
+    let mut output = 0;
+    let mut numerator_accum = factor * denominator;
+    while numerator_accum > 0 {
+        output += numerator_accum;
+        numerator_accum = (numerator_accum * numerator) / (denominator * i);
+        i += 1;
+    }
+    output / denominator
+}
+
+pub fn blob_base_fee_from_excess_blob_gas(excess_blob_gas: u64, params: &BlobParams) -> u128 {
+    fake_exponential(
+        MIN_BLOB_BASE_FEE,
+        excess_blob_gas.into(),
+        params.base_fee_update_fraction,
+    )
+}
+
+impl ExecutionNodeBlock {
+    /// The price paid, and burned, per unit of blob gas in this block. Blocks before Cancun have
+    /// no blob base fee.
+    pub fn blob_base_fee(&self) -> Option<u128> {
+        let params = blob_params_at(&self.timestamp)?;
+        self.excess_blob_gas
+            .map(|excess_blob_gas| blob_base_fee_from_excess_blob_gas(excess_blob_gas, &params))
+    }
+
+    /// The excess blob gas the child of this block will have.
+    pub fn next_excess_blob_gas(&self) -> Option<u64> {
+        let params = blob_params_at(&self.timestamp)?;
+        let excess_blob_gas = self.excess_blob_gas?;
+        let blob_gas_used = self.blob_gas_used? as u64;
+        let target_blob_gas = params.target_blob_gas_per_block as u64;
+        let max_blob_gas = params.max_blob_gas_per_block as u64;
+
+        if excess_blob_gas + blob_gas_used < target_blob_gas {
+            return Some(0);
+        }
+
+        let blob_base_fee = blob_base_fee_from_excess_blob_gas(excess_blob_gas, &params);
+        let is_below_reserve_price = params.reserve_price
+            && BLOB_BASE_COST * self.base_fee_per_gas as u128
+                > GAS_PER_BLOB as u128 * blob_base_fee;
+
+        if is_below_reserve_price {
+            // Only the target share of the used blob gas is taken off again.
+            Some(excess_blob_gas + blob_gas_used * (max_blob_gas - target_blob_gas) / max_blob_gas)
+        } else {
+            Some(excess_blob_gas + blob_gas_used - target_blob_gas)
+        }
+    }
+
+    pub fn blob_fee_burn(&self) -> WeiNewtype {
+        match (self.blob_base_fee(), self.blob_gas_used) {
+            (Some(blob_base_fee), Some(blob_gas_used)) => {
+                WeiNewtype((blob_base_fee * blob_gas_used as u128) as i128)
+            }
+            _ => WeiNewtype(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::execution_chain::ExecutionNodeBlockBuilder;
+
+    use super::*;
+
+    #[test]
+    fn fake_exponential_test() {
+        let fraction = CANCUN_BLOB_PARAMS.base_fee_update_fraction;
+        assert_eq!(fake_exponential(1, 0, fraction), 1);
+        assert_eq!(fake_exponential(1, 2u128.pow(24), fraction), 152);
+        assert_eq!(fake_exponential(1, 10 * fraction, fraction), 22026);
+    }
+
+    #[test]
+    fn blob_params_at_test() {
+        let pre_cancun = *CANCUN_HARD_FORK_TIMESTAMP - chrono::Duration::seconds(12);
+        assert_eq!(blob_params_at(&pre_cancun), None);
+        assert_eq!(
+            blob_params_at(&CANCUN_HARD_FORK_TIMESTAMP),
+            Some(CANCUN_BLOB_PARAMS)
+        );
+        assert_eq!(
+            blob_params_at(&PRAGUE_HARD_FORK_TIMESTAMP),
+            Some(PRAGUE_BLOB_PARAMS)
+        );
+        assert_eq!(
+            blob_params_at(&NETWORK_CONSTANTS.osaka_timestamp),
+            Some(OSAKA_BLOB_PARAMS)
+        );
+        let pre_bpo1 = NETWORK_CONSTANTS.bpo1_timestamp - chrono::Duration::seconds(12);
+        assert_eq!(blob_params_at(&pre_bpo1), Some(OSAKA_BLOB_PARAMS));
+        assert_eq!(
+            blob_params_at(&NETWORK_CONSTANTS.bpo1_timestamp),
+            Some(BPO1_BLOB_PARAMS)
+        );
+        assert_eq!(
+            blob_params_at(&NETWORK_CONSTANTS.bpo2_timestamp),
+            Some(BPO2_BLOB_PARAMS)
+        );
+    }
+
+    #[test]
+    fn blob_fee_burn_test() {
+        let block = ExecutionNodeBlockBuilder::new("blob_fee_burn")
+            .with_timestamp(&CANCUN_HARD_FORK_TIMESTAMP)
+            .with_blob_gas_used(2 * GAS_PER_BLOB)
+            .with_excess_blob_gas(10 * CANCUN_BLOB_PARAMS.base_fee_update_fraction as u64)
+            .build();
+
+        assert_eq!(block.blob_base_fee(), Some(22026));
+        assert_eq!(
+            block.blob_fee_burn(),
+            WeiNewtype(22026 * 2 * GAS_PER_BLOB as i128)
+        );
+    }
+
+    #[test]
+    fn next_excess_blob_gas_test() {
+        let block = ExecutionNodeBlockBuilder::new("next_excess_blob_gas")
+            .with_timestamp(&CANCUN_HARD_FORK_TIMESTAMP)
+            .with_blob_gas_used(6 * GAS_PER_BLOB)
+            .with_excess_blob_gas(0)
+            .build();
+        assert_eq!(block.next_excess_blob_gas(), Some(3 * GAS_PER_BLOB as u64));
+
+        let block = ExecutionNodeBlockBuilder::from_parent(&block)
+            .with_blob_gas_used(0)
+            .with_excess_blob_gas(GAS_PER_BLOB as u64)
+            .build();
+        assert_eq!(block.next_excess_blob_gas(), Some(0));
+    }
+
+    #[test]
+    fn next_excess_blob_gas_reserve_price_test() {
+        // At the minimum blob base fee of 1 wei, a base fee above 16 wei puts blobs below the
+        // reserve price.
+        let block = ExecutionNodeBlockBuilder::new("next_excess_blob_gas_reserve_price")
+            .with_timestamp(&NETWORK_CONSTANTS.osaka_timestamp)
+            .with_base_fee_per_gas(1_000_000_000)
+            .with_blob_gas_used(6 * GAS_PER_BLOB)
+            .with_excess_blob_gas(0)
+            .build();
+        assert_eq!(block.next_excess_blob_gas(), Some(2 * GAS_PER_BLOB as u64));
+
+        let block = ExecutionNodeBlockBuilder::from_parent(&block)
+            .with_base_fee_per_gas(16)
+            .with_blob_gas_used(6 * GAS_PER_BLOB)
+            .with_excess_blob_gas(0)
+            .build();
+        assert_eq!(block.next_excess_blob_gas(), Some(0));
+
+        // Before Osaka there is no reserve price.
+        let block = ExecutionNodeBlockBuilder::new("next_excess_blob_gas_pre_osaka")
+            .with_timestamp(&PRAGUE_HARD_FORK_TIMESTAMP)
+            .with_base_fee_per_gas(1_000_000_000)
+            .with_blob_gas_used(6 * GAS_PER_BLOB)
+            .with_excess_blob_gas(0)
+            .build();
+        assert_eq!(block.next_excess_blob_gas(), Some(0));
+    }
+
+    #[test]
+    fn blob_fee_burn_pre_cancun_test() {
+        let block = ExecutionNodeBlockBuilder::new("blob_fee_burn_pre_cancun").build();
+        assert_eq!(block.blob_base_fee(), None);
+        assert_eq!(block.blob_fee_burn(), WeiNewtype(0));
+    }
+}
@@ -1,14 +1,14 @@
-use std::fmt::{Display, Formatter};
-
-use crate::{
-    execution_chain::{LONDON_HARD_FORK_BLOCK_NUMBER, MERGE_BLOCK_NUMBER},
-    time_frames::{GrowingTimeFrame, TimeFrame},
+use std::{
+    fmt::{Display, Formatter},
+    num::NonZeroU32,
 };
 
+use crate::time_frames::TimeFrame;
+
 use super::{block_store_next::BlockStore, BlockNumber, ExecutionNodeBlock};
 
 /// A range of blocks. The range is inclusive of both the first and last.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BlockRange {
     pub start: BlockNumber,
     pub end: BlockNumber,
@@ -31,7 +31,6 @@ impl BlockRange {
         block: &ExecutionNodeBlock,
         time_frame: &TimeFrame,
     ) -> Option<Self> {
-        use GrowingTimeFrame::*;
         use TimeFrame::*;
 
         let range = match time_frame {
@@ -43,12 +42,8 @@ impl BlockRange {
                     end: block.number,
                 }
             }
-            Growing(SinceMerge) => Self {
-                start: MERGE_BLOCK_NUMBER,
-                end: block.number,
-            },
-            Growing(SinceBurn) => Self {
-                start: LONDON_HARD_FORK_BLOCK_NUMBER,
+            Growing(growing_time_frame) => Self {
+                start: growing_time_frame.start_block_number(),
                 end: block.number,
             },
         };
@@ -65,27 +60,59 @@ impl BlockRange {
         block: &ExecutionNodeBlock,
         time_frame: &TimeFrame,
     ) -> Self {
-        use GrowingTimeFrame::*;
         use TimeFrame::*;
 
         match time_frame {
             Limited(limited_time_frame) => {
-                let first = block.number - limited_time_frame.slot_count() as i32 + 1;
+                let first = block.number - limited_time_frame.slot_count() as i64 + 1;
                 Self {
                     start: first,
                     end: block.number,
                 }
             }
-            Growing(SinceMerge) => Self {
-                start: MERGE_BLOCK_NUMBER,
-                end: block.number,
-            },
-            Growing(SinceBurn) => Self {
-                start: LONDON_HARD_FORK_BLOCK_NUMBER,
+            Growing(growing_time_frame) => Self {
+                start: growing_time_frame.start_block_number(),
                 end: block.number,
             },
         }
     }
+
+    /// Number of blocks in the range. A range always holds at least one block, so there is no
+    /// `is_empty`.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        (self.end - self.start + 1) as usize
+    }
+
+    pub fn contains(&self, block_number: &BlockNumber) -> bool {
+        self.start <= *block_number && *block_number <= self.end
+    }
+
+    /// The blocks both ranges hold, `None` when they don't overlap.
+    pub fn intersect(&self, other: &BlockRange) -> Option<BlockRange> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        if start <= end {
+            Some(BlockRange::new(start, end))
+        } else {
+            None
+        }
+    }
+
+    /// Consecutive ranges of `size` blocks covering this range, the last may be shorter. Lets
+    /// backfills batch their work and writes.
+    pub fn chunks(&self, size: NonZeroU32) -> impl Iterator<Item = BlockRange> {
+        let end = self.end;
+        let size = i64::from(size.get());
+        (self.start.0..=self.end.0)
+            .step_by(size as usize)
+            .map(move |start| {
+                BlockRange::new(
+                    BlockNumber(start),
+                    (BlockNumber(start) + (size - 1)).min(end),
+                )
+            })
+    }
 }
 
 impl Display for BlockRange {
@@ -115,8 +142,8 @@ impl Iterator for BlockRangeIntoIterator {
     type Item = BlockNumber;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.block_range.start + self.index as BlockNumber <= self.block_range.end {
-            let block_number: BlockNumber = self.block_range.start + self.index as BlockNumber;
+        if self.block_range.start + self.index as i64 <= self.block_range.end {
+            let block_number: BlockNumber = self.block_range.start + self.index as i64;
             self.index += 1;
             Some(block_number)
         } else {
@@ -134,12 +161,6 @@ mod tests {
 
     use super::*;
 
-    impl BlockRange {
-        pub fn count(&self) -> usize {
-            (self.end - self.start + 1) as usize
-        }
-    }
-
     struct MockBlockStore {
         first_number_after_or_at: BlockNumber,
     }
@@ -164,10 +185,13 @@ mod tests {
         async fn last(&self) -> ExecutionNodeBlock {
             ExecutionNodeBlock {
                 base_fee_per_gas: 0,
+                blob_gas_used: None,
                 difficulty: 0,
+                excess_blob_gas: None,
+                gas_limit: 0,
                 gas_used: 0,
                 hash: "".to_string(),
-                number: 27,
+                number: BlockNumber(27),
                 parent_hash: "".to_string(),
                 timestamp: Utc::now(),
                 total_difficulty: 0,
@@ -178,10 +202,89 @@ mod tests {
 
     #[test]
     fn block_range_iterable_test() {
-        let range = (BlockRange::new(1, 4))
+        let range = (BlockRange::new(BlockNumber(1), BlockNumber(4)))
             .into_iter()
             .collect::<Vec<BlockNumber>>();
-        assert_eq!(range, vec![1, 2, 3, 4]);
+        assert_eq!(
+            range,
+            vec![
+                BlockNumber(1),
+                BlockNumber(2),
+                BlockNumber(3),
+                BlockNumber(4)
+            ]
+        );
+    }
+
+    #[test]
+    fn len_test() {
+        assert_eq!(BlockRange::new(BlockNumber(1), BlockNumber(1)).len(), 1);
+        assert_eq!(BlockRange::new(BlockNumber(1), BlockNumber(4)).len(), 4);
+    }
+
+    #[test]
+    fn contains_test() {
+        let range = BlockRange::new(BlockNumber(1), BlockNumber(4));
+        assert!(!range.contains(&BlockNumber(0)));
+        assert!(range.contains(&BlockNumber(1)));
+        assert!(range.contains(&BlockNumber(4)));
+        assert!(!range.contains(&BlockNumber(5)));
+    }
+
+    #[test]
+    fn intersect_test() {
+        let range = BlockRange::new(BlockNumber(1), BlockNumber(10));
+        assert_eq!(
+            range.intersect(&BlockRange::new(BlockNumber(5), BlockNumber(15))),
+            Some(BlockRange::new(BlockNumber(5), BlockNumber(10)))
+        );
+        assert_eq!(
+            range.intersect(&BlockRange::new(BlockNumber(3), BlockNumber(4))),
+            Some(BlockRange::new(BlockNumber(3), BlockNumber(4)))
+        );
+        assert_eq!(
+            range.intersect(&BlockRange::new(BlockNumber(10), BlockNumber(12))),
+            Some(BlockRange::new(BlockNumber(10), BlockNumber(10)))
+        );
+        assert_eq!(
+            range.intersect(&BlockRange::new(BlockNumber(11), BlockNumber(12))),
+            None
+        );
+    }
+
+    #[test]
+    fn chunks_test() {
+        let size = NonZeroU32::new(10).unwrap();
+        let chunks = BlockRange::new(BlockNumber(1), BlockNumber(25))
+            .chunks(size)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            vec![
+                BlockRange::new(BlockNumber(1), BlockNumber(10)),
+                BlockRange::new(BlockNumber(11), BlockNumber(20)),
+                BlockRange::new(BlockNumber(21), BlockNumber(25))
+            ]
+        );
+
+        let chunks = BlockRange::new(BlockNumber(1), BlockNumber(20))
+            .chunks(size)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            vec![
+                BlockRange::new(BlockNumber(1), BlockNumber(10)),
+                BlockRange::new(BlockNumber(11), BlockNumber(20))
+            ]
+        );
+
+        let chunks = BlockRange::new(BlockNumber(1), BlockNumber(20))
+            .chunks(NonZeroU32::MAX)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            vec![BlockRange::new(BlockNumber(1), BlockNumber(20))]
+        );
     }
 
     #[tokio::test]
@@ -197,14 +300,17 @@ mod tests {
 
         let block_range = BlockRange::from_block_and_time_frame(
             &MockBlockStore {
-                first_number_after_or_at: first_inside,
+                first_number_after_or_at: BlockNumber(first_inside),
             },
             &ExecutionNodeBlock {
                 base_fee_per_gas: 0,
+                blob_gas_used: None,
                 difficulty: 0,
+                excess_blob_gas: None,
+                gas_limit: 0,
                 gas_used: 0,
                 hash: "".to_string(),
-                number: last_inside,
+                number: BlockNumber(last_inside),
                 parent_hash: "".to_string(),
                 timestamp: Utc::now(),
                 total_difficulty: 0,
@@ -215,11 +321,11 @@ mod tests {
         .await
         .unwrap();
 
-        assert_eq!(block_range.count(), 25);
-        assert!(block_range.start > first_outside_before);
-        assert!(block_range.start <= first_inside);
-        assert!(block_range.end >= last_inside);
-        assert!(block_range.end < first_outside_after);
+        assert_eq!(block_range.len(), 25);
+        assert!(block_range.start > BlockNumber(first_outside_before));
+        assert!(block_range.start <= BlockNumber(first_inside));
+        assert!(block_range.end >= BlockNumber(last_inside));
+        assert!(block_range.end < BlockNumber(first_outside_after));
     }
 
     #[test]
@@ -236,10 +342,13 @@ mod tests {
         let block_range = BlockRange::estimate_from_block_and_time_frame(
             &ExecutionNodeBlock {
                 base_fee_per_gas: 0,
+                blob_gas_used: None,
                 difficulty: 0,
+                excess_blob_gas: None,
+                gas_limit: 0,
                 gas_used: 0,
                 hash: "".to_string(),
-                number: last_inside,
+                number: BlockNumber(last_inside),
                 parent_hash: "".to_string(),
                 timestamp: Utc::now(),
                 total_difficulty: 0,
@@ -248,10 +357,10 @@ mod tests {
             &TimeFrame::Limited(LimitedTimeFrame::Minute5),
         );
 
-        assert_eq!(block_range.count(), 25);
-        assert!(block_range.start > first_outside_before);
-        assert!(block_range.start <= first_inside);
-        assert!(block_range.end >= last_inside);
-        assert!(block_range.end < first_outside_after);
+        assert_eq!(block_range.len(), 25);
+        assert!(block_range.start > BlockNumber(first_outside_before));
+        assert!(block_range.start <= BlockNumber(first_inside));
+        assert!(block_range.end >= BlockNumber(last_inside));
+        assert!(block_range.end < BlockNumber(first_outside_after));
     }
 }
@@ -1,14 +1,27 @@
+use async_trait::async_trait;
 use chrono::{DateTime, SubsecRound, Utc};
-use sqlx::PgExecutor;
+use sqlx::{PgConnection, PgExecutor};
+
+use crate::{analysis::Analysis, rollback::RollbackPoint};
 
 use super::node::{BlockNumber, ExecutionNodeBlock};
 
+/// The burn of a `blocks_next` row in wei, the base fee and from Cancun on the blob fee, as a SQL
+/// expression. Blocks before Cancun have no blob base fee, for them only the base fee counts.
+pub const BURN_WEI_SQL: &str = "
+    base_fee_per_gas::NUMERIC(78) * gas_used::NUMERIC(78)
+    + COALESCE(blob_base_fee::NUMERIC(78) * blob_gas_used::NUMERIC(78), 0)
+";
+
 struct ExecutionBlockRow {
     base_fee_per_gas: i64,
+    blob_gas_used: Option<i32>,
     difficulty: i64,
+    excess_blob_gas: Option<i64>,
+    gas_limit: Option<i32>,
     gas_used: i32,
     hash: String,
-    number: i32,
+    number: i64,
     parent_hash: String,
     timestamp: DateTime<Utc>,
     total_difficulty: String,
@@ -18,10 +31,16 @@ impl From<ExecutionBlockRow> for ExecutionNodeBlock {
     fn from(row: ExecutionBlockRow) -> Self {
         Self {
             base_fee_per_gas: row.base_fee_per_gas as u64,
+            blob_gas_used: row.blob_gas_used,
             difficulty: row.difficulty as u64,
+            excess_blob_gas: row
+                .excess_blob_gas
+                .map(|excess_blob_gas| excess_blob_gas as u64),
+            // Blocks stored before we tracked the gas limit have none.
+            gas_limit: row.gas_limit.unwrap_or(0),
             gas_used: row.gas_used,
             hash: row.hash,
-            number: row.number,
+            number: BlockNumber(row.number),
             parent_hash: row.parent_hash,
             timestamp: row.timestamp,
             total_difficulty: row.total_difficulty.parse::<u128>().unwrap(),
@@ -37,13 +56,28 @@ pub async fn delete_blocks(executor: impl PgExecutor<'_>, greater_than_or_equal:
         DELETE FROM blocks_next
         WHERE number >= $1
         ",
-        *greater_than_or_equal
+        greater_than_or_equal.0
     )
     .execute(executor)
     .await
     .unwrap();
 }
 
+pub struct ExecutionChainRollback;
+
+#[async_trait]
+impl Analysis for ExecutionChainRollback {
+    fn name(&self) -> &'static str {
+        "execution_chain"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            delete_blocks(transaction, block_number_gte).await;
+        }
+    }
+}
+
 pub async fn get_last_block_number(executor: impl PgExecutor<'_>) -> Option<BlockNumber> {
     sqlx::query!(
         "
@@ -58,7 +92,63 @@ pub async fn get_last_block_number(executor: impl PgExecutor<'_>) -> Option<Bloc
     .fetch_optional(executor)
     .await
     .unwrap()
-    .map(|row| row.number)
+    .map(|row| BlockNumber(row.number))
+}
+
+pub async fn first_number_after_or_at(
+    executor: impl PgExecutor<'_>,
+    timestamp: &DateTime<Utc>,
+) -> Option<BlockNumber> {
+    sqlx::query!(
+        "
+        SELECT
+            number
+        FROM
+            blocks_next
+        WHERE
+            timestamp >= $1
+        ORDER BY
+            timestamp ASC
+        LIMIT 1
+        ",
+        timestamp
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| BlockNumber(row.number))
+}
+
+pub async fn get_block_by_number(
+    executor: impl PgExecutor<'_>,
+    block_number: &BlockNumber,
+) -> Option<ExecutionNodeBlock> {
+    sqlx::query_as!(
+        ExecutionBlockRow,
+        r#"
+        SELECT
+            base_fee_per_gas,
+            blob_gas_used,
+            difficulty,
+            excess_blob_gas,
+            gas_limit,
+            gas_used,
+            hash,
+            number,
+            parent_hash,
+            timestamp,
+            total_difficulty::TEXT AS "total_difficulty!"
+        FROM
+            blocks_next
+        WHERE
+            number = $1
+        "#,
+        block_number.0
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| row.into())
 }
 
 pub async fn store_block(
@@ -71,8 +161,12 @@ pub async fn store_block(
         INSERT INTO
             blocks_next (
                 base_fee_per_gas,
+                blob_base_fee,
+                blob_gas_used,
                 difficulty,
                 eth_price,
+                excess_blob_gas,
+                gas_limit,
                 gas_used,
                 hash,
                 number,
@@ -80,12 +174,20 @@ pub async fn store_block(
                 timestamp,
                 total_difficulty
             )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9::NUMERIC)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13::NUMERIC)
         ",
     )
     .bind(block.base_fee_per_gas as i64)
+    .bind(block.blob_base_fee().map(|blob_base_fee| {
+        i64::try_from(blob_base_fee).expect("expect blob base fee to fit in i64")
+    }))
+    .bind(block.blob_gas_used)
     .bind(block.difficulty as i64)
     .bind(eth_price)
+    .bind(block.excess_blob_gas.map(|excess_blob_gas| {
+        i64::try_from(excess_blob_gas).expect("expect excess blob gas to fit in i64")
+    }))
+    .bind(block.gas_limit)
     .bind(block.gas_used)
     .bind(block.hash.clone())
     .bind(block.number)
@@ -97,6 +199,40 @@ pub async fn store_block(
     .unwrap();
 }
 
+/// Sets the blob gas fields of a stored block. Returns false when no block with this number and
+/// hash is stored, e.g. because it was reorged out.
+pub async fn update_blob_gas(executor: impl PgExecutor<'_>, block: &ExecutionNodeBlock) -> bool {
+    let rows_affected = sqlx::query!(
+        "
+        UPDATE
+            blocks_next
+        SET
+            blob_base_fee = $1,
+            blob_gas_used = $2,
+            excess_blob_gas = $3
+        WHERE
+            number = $4
+        AND
+            hash = $5
+        ",
+        block.blob_base_fee().map(|blob_base_fee| {
+            i64::try_from(blob_base_fee).expect("expect blob base fee to fit in i64")
+        }),
+        block.blob_gas_used,
+        block.excess_blob_gas.map(|excess_blob_gas| {
+            i64::try_from(excess_blob_gas).expect("expect excess blob gas to fit in i64")
+        }),
+        block.number.0,
+        block.hash
+    )
+    .execute(executor)
+    .await
+    .unwrap()
+    .rows_affected();
+
+    rows_affected != 0
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::{Duration, DurationRound, Utc};
@@ -121,10 +257,13 @@ mod tests {
     fn make_test_block() -> ExecutionNodeBlock {
         ExecutionNodeBlock {
             base_fee_per_gas: 0,
+            blob_gas_used: None,
             difficulty: 0,
+            excess_blob_gas: None,
+            gas_limit: 0,
             gas_used: 0,
             hash: "0xtest".to_string(),
-            number: 0,
+            number: BlockNumber(0),
             parent_hash: "0xparent".to_string(),
             timestamp: Utc::now().duration_round(Duration::seconds(1)).unwrap(),
             total_difficulty: 0,
@@ -132,35 +271,6 @@ mod tests {
         }
     }
 
-    pub async fn get_block_by_number(
-        executor: impl PgExecutor<'_>,
-        block_number: &BlockNumber,
-    ) -> Option<ExecutionNodeBlock> {
-        sqlx::query_as!(
-            ExecutionBlockRow,
-            r#"
-            SELECT
-                base_fee_per_gas,
-                difficulty,
-                gas_used,
-                hash,
-                number,
-                parent_hash,
-                timestamp,
-                total_difficulty::TEXT AS "total_difficulty!"
-            FROM
-                blocks_next
-            WHERE
-                number = $1
-            "#,
-            *block_number
-        )
-        .fetch_optional(executor)
-        .await
-        .unwrap()
-        .map(|row| row.into())
-    }
-
     #[tokio::test]
     async fn store_block_test() {
         let mut db = db::tests::get_test_db_connection().await;
@@ -171,7 +281,9 @@ mod tests {
 
         store_block(&mut *transaction, &test_block, 0.0).await;
         assert_eq!(
-            get_block_by_number(&mut *transaction, &0).await.unwrap(),
+            get_block_by_number(&mut *transaction, &BlockNumber(0))
+                .await
+                .unwrap(),
             test_block
         );
     }
@@ -186,7 +298,7 @@ mod tests {
             &mut *transaction,
             &ExecutionNodeBlock {
                 hash: "0xtest1".to_string(),
-                number: 1,
+                number: BlockNumber(1),
                 parent_hash: "0xtest".to_string(),
                 ..test_block
             },
@@ -196,7 +308,7 @@ mod tests {
 
         assert_eq!(len(&mut *transaction,).await, 2);
 
-        delete_blocks(&mut *transaction, &0).await;
+        delete_blocks(&mut *transaction, &BlockNumber(0)).await;
         assert_eq!(len(&mut *transaction,).await, 0);
     }
 
@@ -209,7 +321,10 @@ mod tests {
             r#"
             SELECT
                 base_fee_per_gas,
+                blob_gas_used,
                 difficulty,
+                excess_blob_gas,
+                gas_limit,
                 gas_used,
                 hash,
                 number,
@@ -229,6 +344,33 @@ mod tests {
         .map(|row| row.into())
     }
 
+    #[tokio::test]
+    async fn update_blob_gas_test() {
+        let mut db = db::tests::get_test_db_connection().await;
+        let mut transaction = db.begin().await.unwrap();
+        let test_block = make_test_block();
+        store_block(&mut *transaction, &test_block, 0.0).await;
+
+        let healed_block = ExecutionNodeBlock {
+            blob_gas_used: Some(131_072),
+            excess_blob_gas: Some(0),
+            ..test_block.clone()
+        };
+        assert!(update_blob_gas(&mut *transaction, &healed_block).await);
+        assert_eq!(
+            get_block_by_number(&mut *transaction, &BlockNumber(0))
+                .await
+                .unwrap(),
+            healed_block
+        );
+
+        let reorged_block = ExecutionNodeBlock {
+            hash: "0xreorged".to_string(),
+            ..healed_block
+        };
+        assert!(!update_blob_gas(&mut *transaction, &reorged_block).await);
+    }
+
     #[tokio::test]
     async fn get_block_by_hash_test() {
         let mut db = db::tests::get_test_db_connection().await;
@@ -267,6 +409,6 @@ mod tests {
 
         store_block(&mut *transaction, &test_block, 0.0).await;
         let last_block_number = get_last_block_number(&mut *transaction).await;
-        assert_eq!(last_block_number, Some(0));
+        assert_eq!(last_block_number, Some(BlockNumber(0)));
     }
 }
@@ -56,8 +56,8 @@ impl BlockStorePostgres {
             AND
                 number <= $2
             ",
-            block_range.start,
-            block_range.end
+            block_range.start.0,
+            block_range.end.0
         )
         .execute(&self.db_pool)
         .await
@@ -80,7 +80,7 @@ impl BlockStorePostgres {
             WHERE
                 number = $1
             "#,
-            block_number
+            block_number.0
         )
         .fetch_one(&self.db_pool)
         .await
@@ -103,24 +103,7 @@ impl BlockStorePostgres {
 #[async_trait]
 impl BlockStore for BlockStorePostgres {
     async fn first_number_after_or_at(&self, timestamp: &DateTime<Utc>) -> Option<BlockNumber> {
-        sqlx::query!(
-            "
-            SELECT
-                number
-            FROM
-                blocks_next
-            WHERE
-                timestamp >= $1
-            ORDER BY
-                timestamp ASC
-            LIMIT 1
-            ",
-            timestamp
-        )
-        .fetch_optional(&self.db_pool)
-        .await
-        .unwrap()
-        .map(|row| row.number)
+        block_store::first_number_after_or_at(&self.db_pool, timestamp).await
     }
 
     async fn last(&self) -> ExecutionNodeBlock {
@@ -128,8 +111,11 @@ impl BlockStore for BlockStorePostgres {
             r#"
             SELECT
                 base_fee_per_gas,
+                blob_gas_used,
                 difficulty,
                 eth_price,
+                excess_blob_gas,
+                gas_limit,
                 gas_used,
                 hash,
                 number,
@@ -147,10 +133,15 @@ impl BlockStore for BlockStorePostgres {
         .await
         .map(|row| ExecutionNodeBlock {
             base_fee_per_gas: row.base_fee_per_gas as u64,
+            blob_gas_used: row.blob_gas_used,
             difficulty: row.difficulty as u64,
+            excess_blob_gas: row
+                .excess_blob_gas
+                .map(|excess_blob_gas| excess_blob_gas as u64),
+            gas_limit: row.gas_limit.unwrap_or(0),
             gas_used: row.gas_used,
             hash: row.hash,
-            number: row.number,
+            number: BlockNumber(row.number),
             parent_hash: row.parent_hash,
             timestamp: row.timestamp,
             total_difficulty: row.total_difficulty.parse().unwrap(),
@@ -169,7 +160,7 @@ impl BlockStore for BlockStorePostgres {
             WHERE
                 number = $1
             "#,
-            block_number
+            block_number.0
         )
         .fetch_optional(&self.db_pool)
         .await
@@ -186,7 +177,7 @@ impl BlockStore for BlockStorePostgres {
                 WHERE number = $1
             ) AS "exists!"
             "#,
-            block_number
+            block_number.0
         )
         .fetch_one(&self.db_pool)
         .await
@@ -211,14 +202,14 @@ mod tests {
 
         let test_number = 1351;
         let test_block = ExecutionNodeBlockBuilder::new(test_id)
-            .with_number(test_number)
+            .with_number(BlockNumber(test_number))
             .build();
 
-        assert!(!block_store.number_exists(&test_number).await);
+        assert!(!block_store.number_exists(&BlockNumber(test_number)).await);
 
         block_store.add(&test_block, 0.0).await;
 
-        assert!(block_store.number_exists(&test_number).await);
+        assert!(block_store.number_exists(&BlockNumber(test_number)).await);
     }
 
     #[test_context(TestDb)]
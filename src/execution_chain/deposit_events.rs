@@ -0,0 +1,545 @@
+//! # Deposit Events
+//! Every deposit into the beacon chain deposit contract emits a `DepositEvent`. We store them,
+//! and as the beacon chain processes deposits strictly in order, compare their running sum to the
+//! deposits included in beacon blocks. Deposits past that point are pending, they've left the
+//! execution chain but not yet arrived on the beacon chain.
+//!
+//! From Pectra on, deposits are no longer included in beacon blocks. EIP-6110 has the beacon chain
+//! pick them up from execution blocks, into a pending deposits queue in its state, processed at a
+//! limited rate. Past the fork we read that queue instead.
+//!
+//! Events are only ingested up to the last stored block, so execution chain rollbacks cover them.
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use tracing::{debug, info};
+
+use crate::{
+    analysis::Analysis,
+    beacon_chain::{self, BeaconNode, Slot},
+    caching::{self, CacheKey},
+    network::NETWORK_CONSTANTS,
+    rollback::RollbackPoint,
+    units::GweiNewtype,
+};
+
+use super::{BlockNumber, BlockRange, ExecutionNode, ExecutionNodeLog, LogFilter};
+
+pub const DEPOSIT_CONTRACT_ADDRESS: &str = "0x00000000219ab540356cbb839cbe05303d7705fa";
+const DEPOSIT_CONTRACT_DEPLOYMENT_BLOCK: BlockNumber = BlockNumber(11_052_984);
+
+/// keccak256("DepositEvent(bytes,bytes,bytes,bytes,bytes)")
+const DEPOSIT_EVENT_TOPIC: &str =
+    "0x649bbc62d0e31342afea4e5cd82d4049e7e1ee912fc0889aa790803be39038c5";
+
+/// Deposits before this index were processed in the genesis state, not in blocks.
+const GENESIS_DEPOSIT_COUNT: i64 = 21_073;
+
+/// Blocks we request and store at a time when catching up.
+const INGEST_RANGE_SIZE: i64 = 100_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DepositEvent {
+    pub amount: GweiNewtype,
+    pub block_hash: String,
+    pub block_number: BlockNumber,
+    pub deposit_index: i64,
+    pub log_index: i32,
+    pub pubkey: String,
+    pub transaction_hash: String,
+    pub withdrawal_credentials: String,
+}
+
+/// Reads the 32 byte word at the given byte offset as a number. Offsets and lengths in the event
+/// data are small, the last eight bytes hold them.
+fn read_word(data: &str, byte_offset: usize) -> usize {
+    let word = &data[byte_offset * 2..byte_offset * 2 + 64];
+    usize::from_str_radix(&word[48..], 16).expect("expect event data words to be hex")
+}
+
+/// Reads the nth dynamic `bytes` field, as hex without prefix.
+fn read_bytes_field(data: &str, index: usize) -> &str {
+    let offset = read_word(data, index * 32);
+    let length = read_word(data, offset);
+    let start = (offset + 32) * 2;
+    &data[start..start + length * 2]
+}
+
+/// The contract encodes amount and index as little-endian eight byte integers.
+fn read_little_endian_u64(hex: &str) -> u64 {
+    let mut bytes = [0u8; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .expect("expect little-endian integer to be hex");
+    }
+    u64::from_le_bytes(bytes)
+}
+
+impl From<ExecutionNodeLog> for DepositEvent {
+    fn from(log: ExecutionNodeLog) -> Self {
+        let data = log
+            .data
+            .strip_prefix("0x")
+            .expect("expect log data to be 0x prefixed");
+
+        let amount = read_little_endian_u64(read_bytes_field(data, 2));
+        let deposit_index = read_little_endian_u64(read_bytes_field(data, 4));
+
+        Self {
+            amount: GweiNewtype(amount.try_into().unwrap()),
+            block_hash: log.block_hash,
+            block_number: log.block_number,
+            deposit_index: deposit_index.try_into().unwrap(),
+            log_index: log.log_index,
+            pubkey: format!("0x{}", read_bytes_field(data, 0)),
+            transaction_hash: log.transaction_hash,
+            withdrawal_credentials: format!("0x{}", read_bytes_field(data, 1)),
+        }
+    }
+}
+
+async fn get_last_deposit_event_block_number(executor: impl PgExecutor<'_>) -> Option<BlockNumber> {
+    sqlx::query!("SELECT MAX(block_number) AS block_number FROM deposit_events")
+        .fetch_one(executor)
+        .await
+        .unwrap()
+        .block_number
+        .map(BlockNumber)
+}
+
+pub async fn store_deposit_events(executor: impl PgExecutor<'_>, deposit_events: &[DepositEvent]) {
+    let mut deposit_indices = Vec::new();
+    let mut block_numbers = Vec::new();
+    let mut block_hashes = Vec::new();
+    let mut transaction_hashes = Vec::new();
+    let mut log_indices = Vec::new();
+    let mut pubkeys = Vec::new();
+    let mut withdrawal_credentials = Vec::new();
+    let mut amounts = Vec::new();
+    for deposit_event in deposit_events {
+        deposit_indices.push(deposit_event.deposit_index);
+        block_numbers.push(deposit_event.block_number.0);
+        block_hashes.push(deposit_event.block_hash.clone());
+        transaction_hashes.push(deposit_event.transaction_hash.clone());
+        log_indices.push(deposit_event.log_index);
+        pubkeys.push(deposit_event.pubkey.clone());
+        withdrawal_credentials.push(deposit_event.withdrawal_credentials.clone());
+        amounts.push(deposit_event.amount.0);
+    }
+
+    sqlx::query!(
+        "
+        INSERT INTO deposit_events (
+            deposit_index,
+            block_number,
+            block_hash,
+            transaction_hash,
+            log_index,
+            pubkey,
+            withdrawal_credentials,
+            amount_gwei
+        )
+        SELECT * FROM UNNEST (
+            $1::bigint[],
+            $2::int8[],
+            $3::text[],
+            $4::text[],
+            $5::int[],
+            $6::text[],
+            $7::text[],
+            $8::bigint[]
+        )
+        ON CONFLICT (deposit_index) DO NOTHING
+        ",
+        &deposit_indices,
+        &block_numbers,
+        &block_hashes,
+        &transaction_hashes,
+        &log_indices,
+        &pubkeys,
+        &withdrawal_credentials,
+        &amounts
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub async fn delete_deposit_events(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: &BlockNumber,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM deposit_events
+        WHERE block_number >= $1
+        ",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub struct DepositEventsRollback;
+
+#[async_trait]
+impl Analysis for DepositEventsRollback {
+    fn name(&self) -> &'static str {
+        "deposit_events"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            delete_deposit_events(transaction, block_number_gte).await;
+        }
+    }
+}
+
+/// Ingests deposit events from where we left off, up to the last stored block.
+pub async fn ingest_deposit_events(db_pool: &PgPool, execution_node: &ExecutionNode) -> Result<()> {
+    let last_block_number = match super::get_last_block_number(db_pool).await {
+        Some(last_block_number) => last_block_number,
+        None => {
+            debug!("no execution blocks stored, skipping deposit event ingestion");
+            return Ok(());
+        }
+    };
+
+    let mut start = get_last_deposit_event_block_number(db_pool)
+        .await
+        .map_or(DEPOSIT_CONTRACT_DEPLOYMENT_BLOCK, |block_number| {
+            block_number + 1
+        });
+
+    let filter = LogFilter {
+        address: Some(DEPOSIT_CONTRACT_ADDRESS.to_string()),
+        topics: vec![Some(DEPOSIT_EVENT_TOPIC.to_string())],
+    };
+
+    while start <= last_block_number {
+        let block_range = BlockRange::new(
+            start,
+            (start + INGEST_RANGE_SIZE - 1).min(last_block_number),
+        );
+        let deposit_events: Vec<DepositEvent> = execution_node
+            .get_logs(&filter, &block_range)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        debug!(%block_range, count = deposit_events.len(), "ingesting deposit events");
+        store_deposit_events(db_pool, &deposit_events).await;
+        start = block_range.end + 1;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct DepositEventsSplit {
+    pending_count: i64,
+    pending_sum: GweiNewtype,
+    processed_sum: GweiNewtype,
+}
+
+/// Splits the post-genesis deposit events at the point their running sum reaches the given sum of
+/// deposits processed by the beacon chain.
+async fn split_deposit_events(
+    executor: impl PgExecutor<'_>,
+    processed_sum: GweiNewtype,
+) -> DepositEventsSplit {
+    let row = sqlx::query!(
+        r#"
+        WITH post_genesis AS (
+            SELECT
+                amount_gwei,
+                SUM(amount_gwei) OVER (ORDER BY deposit_index) AS running_sum_gwei
+            FROM
+                deposit_events
+            WHERE
+                deposit_index >= $1
+        )
+        SELECT
+            COUNT(*) FILTER (WHERE running_sum_gwei > $2::BIGINT) AS "pending_count!",
+            COALESCE(SUM(amount_gwei) FILTER (WHERE running_sum_gwei > $2::BIGINT), 0)::BIGINT AS "pending_sum!",
+            COALESCE(MAX(running_sum_gwei) FILTER (WHERE running_sum_gwei <= $2::BIGINT), 0)::BIGINT AS "processed_sum!"
+        FROM
+            post_genesis
+        "#,
+        GENESIS_DEPOSIT_COUNT,
+        processed_sum.0
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap();
+
+    DepositEventsSplit {
+        pending_count: row.pending_count,
+        pending_sum: GweiNewtype(row.pending_sum),
+        processed_sum: GweiNewtype(row.processed_sum),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingDeposits {
+    pub count: i64,
+    pub slot: Slot,
+    pub sum: GweiNewtype,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Reconciles stored deposit events against the deposits included in beacon blocks. Fails when
+/// no prefix of the events adds up to the beacon chain sum, which means we're missing events.
+async fn reconcile_deposit_events(executor: &mut PgConnection) -> Result<PendingDeposits> {
+    let beacon_deposits_sum = beacon_chain::get_last_deposits_sum(&mut *executor)
+        .await
+        .ok_or_else(|| anyhow!("no beacon blocks stored, can't reconcile deposits"))?;
+
+    let split = split_deposit_events(&mut *executor, beacon_deposits_sum.deposits_sum).await;
+    if split.processed_sum != beacon_deposits_sum.deposits_sum {
+        return Err(anyhow!(
+            "deposit events don't add up to the beacon chain deposits sum at slot {}, events sum to {} up to that point, beacon chain has {}",
+            beacon_deposits_sum.slot,
+            split.processed_sum,
+            beacon_deposits_sum.deposits_sum
+        ));
+    }
+
+    Ok(PendingDeposits {
+        count: split.pending_count,
+        slot: beacon_deposits_sum.slot,
+        sum: split.pending_sum,
+        timestamp: beacon_deposits_sum.slot.date_time(),
+    })
+}
+
+async fn get_pending_deposits_from_state(
+    beacon_node: &impl BeaconNode,
+    header: &beacon_chain::BeaconHeaderSignedEnvelope,
+) -> Result<PendingDeposits> {
+    let state_root = header.state_root();
+    let pending_deposits = beacon_node
+        .get_pending_deposits(&state_root)
+        .await?
+        .ok_or_else(|| anyhow!("no state {state_root} to read pending deposits from"))?;
+
+    Ok(PendingDeposits {
+        count: pending_deposits.len() as i64,
+        slot: header.slot(),
+        sum: pending_deposits
+            .iter()
+            .fold(GweiNewtype(0), |sum, pending_deposit| {
+                sum + pending_deposit.amount
+            }),
+        timestamp: header.slot().date_time(),
+    })
+}
+
+/// Before Pectra, reconciles deposit events against beacon blocks, after, reads the pending
+/// deposits queue of the head state.
+pub async fn get_pending_deposits(
+    executor: &mut PgConnection,
+    beacon_node: &impl BeaconNode,
+) -> Result<PendingDeposits> {
+    let header = beacon_node.get_last_header().await?;
+    if header.slot().date_time() >= NETWORK_CONSTANTS.prague_timestamp {
+        get_pending_deposits_from_state(beacon_node, &header).await
+    } else {
+        reconcile_deposit_events(executor).await
+    }
+}
+
+pub async fn update_pending_deposits(
+    db_pool: &PgPool,
+    execution_node: &ExecutionNode,
+    beacon_node: &impl BeaconNode,
+) -> Result<()> {
+    ingest_deposit_events(db_pool, execution_node).await?;
+
+    let mut connection = db_pool.acquire().await?;
+    let pending_deposits = get_pending_deposits(&mut connection, beacon_node).await?;
+    info!(
+        count = pending_deposits.count,
+        sum = %pending_deposits.sum,
+        "reconciled deposit events"
+    );
+
+    caching::update_and_publish(db_pool, &CacheKey::PendingDeposits, pending_deposits).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        beacon_chain::{BeaconHeaderSignedEnvelopeBuilder, MockBeaconNode, PendingDeposit},
+        db::tests::TestDb,
+    };
+
+    use super::*;
+
+    fn make_deposit_log(amount_le: &str, index_le: &str) -> ExecutionNodeLog {
+        let pubkey = "ab".repeat(48);
+        let withdrawal_credentials = "cd".repeat(32);
+        let signature = "ef".repeat(96);
+        let pad = |hex: &str| format!("{hex:0<64}");
+        let data = [
+            format!("{:064x}", 0xa0),
+            format!("{:064x}", 0x100),
+            format!("{:064x}", 0x140),
+            format!("{:064x}", 0x180),
+            format!("{:064x}", 0x200),
+            format!("{:064x}", 48),
+            pad(&pubkey[..64]),
+            pad(&pubkey[64..]),
+            format!("{:064x}", 32),
+            withdrawal_credentials.clone(),
+            format!("{:064x}", 8),
+            pad(amount_le),
+            format!("{:064x}", 96),
+            signature[..64].to_string(),
+            signature[64..128].to_string(),
+            signature[128..].to_string(),
+            format!("{:064x}", 8),
+            pad(index_le),
+        ]
+        .concat();
+
+        ExecutionNodeLog {
+            address: DEPOSIT_CONTRACT_ADDRESS.to_string(),
+            block_hash: "0xblock".to_string(),
+            block_number: BlockNumber(11_185_311),
+            data: format!("0x{data}"),
+            log_index: 3,
+            removed: false,
+            topics: vec![DEPOSIT_EVENT_TOPIC.to_string()],
+            transaction_hash: "0xtransaction".to_string(),
+            transaction_index: 1,
+        }
+    }
+
+    fn make_deposit_event(deposit_index: i64, amount: GweiNewtype) -> DepositEvent {
+        DepositEvent {
+            amount,
+            block_hash: format!("0xblock{deposit_index}"),
+            block_number: BlockNumber(20_000_000) + deposit_index as i64,
+            deposit_index,
+            log_index: 0,
+            pubkey: format!("0xpubkey{deposit_index}"),
+            transaction_hash: format!("0xtransaction{deposit_index}"),
+            withdrawal_credentials: "0xcredentials".to_string(),
+        }
+    }
+
+    #[test]
+    fn decode_deposit_event_test() {
+        // 32 ETH in Gwei, and index 5, little-endian.
+        let log = make_deposit_log("0040597307000000", "0500000000000000");
+
+        let deposit_event = DepositEvent::from(log);
+
+        assert_eq!(deposit_event.amount, GweiNewtype(32_000_000_000));
+        assert_eq!(deposit_event.deposit_index, 5);
+        assert_eq!(deposit_event.pubkey, format!("0x{}", "ab".repeat(48)));
+        assert_eq!(
+            deposit_event.withdrawal_credentials,
+            format!("0x{}", "cd".repeat(32))
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn split_deposit_events_test(test_db: &TestDb) {
+        let deposit_events: Vec<_> = (GENESIS_DEPOSIT_COUNT - 1..GENESIS_DEPOSIT_COUNT + 3)
+            .map(|deposit_index| make_deposit_event(deposit_index, GweiNewtype(32_000_000_000)))
+            .collect();
+        store_deposit_events(&test_db.pool, &deposit_events).await;
+
+        let split = split_deposit_events(&test_db.pool, GweiNewtype(64_000_000_000)).await;
+
+        // The genesis deposit doesn't count, two more were processed, one is pending.
+        assert_eq!(
+            split,
+            DepositEventsSplit {
+                pending_count: 1,
+                pending_sum: GweiNewtype(32_000_000_000),
+                processed_sum: GweiNewtype(64_000_000_000),
+            }
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn get_pending_deposits_after_pectra_test(test_db: &TestDb) {
+        let slot = Slot::from_date_time(&NETWORK_CONSTANTS.prague_timestamp).unwrap();
+        let header = BeaconHeaderSignedEnvelopeBuilder::new("get_pending_deposits_after_pectra")
+            .slot(&slot)
+            .build();
+        let state_root = header.state_root();
+
+        let mut beacon_node = MockBeaconNode::new();
+        beacon_node
+            .expect_get_last_header()
+            .times(1)
+            .return_once(|| Ok(header));
+        beacon_node
+            .expect_get_pending_deposits()
+            .withf(move |requested_state_root| requested_state_root == state_root)
+            .times(1)
+            .return_once(move |_| {
+                Ok(Some(vec![
+                    PendingDeposit {
+                        amount: GweiNewtype(32_000_000_000),
+                        slot,
+                    },
+                    PendingDeposit {
+                        amount: GweiNewtype(1_000_000_000),
+                        slot,
+                    },
+                ]))
+            });
+
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        let pending_deposits = get_pending_deposits(&mut connection, &beacon_node)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            pending_deposits,
+            PendingDeposits {
+                count: 2,
+                slot,
+                sum: GweiNewtype(33_000_000_000),
+                timestamp: slot.date_time(),
+            }
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn rollback_deposit_events_test(test_db: &TestDb) {
+        let deposit_events: Vec<_> = (0..2)
+            .map(|deposit_index| make_deposit_event(deposit_index, GweiNewtype(1_000_000_000)))
+            .collect();
+        store_deposit_events(&test_db.pool, &deposit_events).await;
+
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        DepositEventsRollback
+            .on_rollback(
+                &mut connection,
+                &RollbackPoint::BlockNumbersGte(deposit_events[1].block_number),
+            )
+            .await;
+
+        assert_eq!(
+            get_last_deposit_event_block_number(&mut *connection).await,
+            Some(deposit_events[0].block_number)
+        );
+    }
+}
@@ -1,7 +1,11 @@
 use crate::{
     execution_chain::{node::BlockNumber, LONDON_HARD_FORK_BLOCK_NUMBER},
     log,
+    network::NETWORK_CONSTANTS,
+    row_writer::{ExportFormat, ExportRow, RowWriter},
 };
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
 use std::{
     cmp::Ordering,
     fs::{self, File},
@@ -10,14 +14,21 @@ use std::{
 };
 
 use anyhow::Result;
+#[cfg(feature = "parquet")]
+use arrow::{
+    array::{
+        ArrayRef, Decimal128Array, Float64Array, Int32Array, Int64Array, StringArray,
+        TimestampMicrosecondArray, UInt64Array,
+    },
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    record_batch::RecordBatch,
+};
 use chrono::{DateTime, Utc};
 use futures::{SinkExt, Stream, StreamExt};
 use pit_wall::Progress;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, trace, warn};
 
-use crate::execution_chain::sync::EXECUTION_BLOCK_NUMBER_AUG_1ST;
-
 use super::{
     node::{Difficulty, ExecutionNodeBlock, TotalDifficulty},
     BlockRange, ExecutionNode,
@@ -78,10 +89,80 @@ struct OutRow {
     total_difficulty: TotalDifficulty,
 }
 
+impl ExportRow for OutRow {
+    #[cfg(feature = "parquet")]
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("base_fee_per_gas", DataType::UInt64, false),
+            Field::new("difficulty", DataType::UInt64, false),
+            Field::new("eth_price", DataType::Float64, false),
+            Field::new("gas_used", DataType::Int32, false),
+            Field::new("hash", DataType::Utf8, false),
+            Field::new("number", DataType::Int64, false),
+            Field::new("parent_hash", DataType::Utf8, false),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                false,
+            ),
+            Field::new("total_difficulty", DataType::Decimal128(38, 0), false),
+        ]))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn to_record_batch(rows: &[Self]) -> RecordBatch {
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(UInt64Array::from_iter_values(
+                    rows.iter().map(|row| row.base_fee_per_gas),
+                )) as ArrayRef,
+                Arc::new(UInt64Array::from_iter_values(
+                    rows.iter().map(|row| row.difficulty),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    rows.iter().map(|row| row.eth_price),
+                )),
+                Arc::new(Int32Array::from_iter_values(
+                    rows.iter().map(|row| row.gas_used),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.hash),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|row| row.number.0),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.parent_hash),
+                )),
+                Arc::new(
+                    TimestampMicrosecondArray::from_iter_values(
+                        rows.iter().map(|row| row.timestamp.timestamp_micros()),
+                    )
+                    .with_timezone("UTC"),
+                ),
+                Arc::new(
+                    Decimal128Array::from_iter_values(
+                        rows.iter()
+                            .map(|row| row.total_difficulty.try_into().unwrap()),
+                    )
+                    .with_precision_and_scale(38, 0)
+                    .unwrap(),
+                ),
+            ],
+        )
+        .unwrap()
+    }
+}
+
 // We have the one after this in our DB already.
-const EARLIEST_STORED_DB_BLOCK_NUMBER: BlockNumber = 15429946;
+const EARLIEST_STORED_DB_BLOCK_NUMBER: BlockNumber = BlockNumber(15429946);
 
-async fn export_blocks_from(gte_block_number: BlockNumber, to_path: &str) -> Result<()> {
+async fn export_blocks_from(
+    gte_block_number: BlockNumber,
+    to_path: &str,
+    format: ExportFormat,
+) -> Result<()> {
     debug!("loading eth prices");
 
     let mut eth_prices_csv = csv::Reader::from_path("eth_prices.csv")
@@ -109,7 +190,7 @@ async fn export_blocks_from(gte_block_number: BlockNumber, to_path: &str) -> Res
         .create(true)
         .open(to_path)
         .unwrap();
-    let mut csv_writer = csv::Writer::from_writer(file);
+    let mut row_writer = RowWriter::create(file, format);
 
     let mut closest_price_index = 0;
     let mut closest_price = eth_prices
@@ -117,7 +198,7 @@ async fn export_blocks_from(gte_block_number: BlockNumber, to_path: &str) -> Res
         .expect("eth prices should have at least one price");
 
     while let Some(block) = historic_stream.next().await {
-        debug!(block.number, "exporting block");
+        debug!(%block.number, "exporting block");
 
         // Calculate the distance between the current block and the closest price we had for the last block.
         let mut distance_closest = closest_price
@@ -179,28 +260,33 @@ async fn export_blocks_from(gte_block_number: BlockNumber, to_path: &str) -> Res
             timestamp: block.timestamp,
             total_difficulty: block.total_difficulty,
         };
-        csv_writer.serialize(out).unwrap();
+        row_writer.write(out);
 
         progress.inc_work_done();
-        if block.number % 100 == 0 {
+        if block.number.0 % 100 == 0 {
             info!("{}", progress.get_progress_string());
-            csv_writer.flush().unwrap();
+            row_writer.flush();
         }
     }
 
-    // A CSV writer maintains an internal buffer, so it's important
-    // to flush the buffer when you're done.
-    csv_writer.flush().unwrap();
+    // Writers buffer rows, and Parquet files need their footer, finish before exiting.
+    row_writer.finish();
 
     Ok(())
 }
 
 pub async fn export_blocks_from_august() -> Result<()> {
+    export_blocks_from_august_with_format(ExportFormat::from_args()).await
+}
+
+/// Exports blocks from where the sync starts, 2022-08-01 on mainnet.
+pub async fn export_blocks_from_august_with_format(format: ExportFormat) -> Result<()> {
     log::init_with_env();
 
     info!(
-        august_block_number = EXECUTION_BLOCK_NUMBER_AUG_1ST,
-        "writing blocks from august to CSV"
+        start_block_number = %NETWORK_CONSTANTS.sync_start_block_number,
+        %format,
+        "writing blocks from august"
     );
 
     let timestamp = SystemTime::now()
@@ -210,9 +296,14 @@ pub async fn export_blocks_from_august() -> Result<()> {
 
     debug!("loading eth prices");
 
-    let file_path = format!("blocks_from_august_{timestamp}.csv");
+    let file_path = format!("blocks_from_august_{timestamp}.{}", format.extension());
 
-    export_blocks_from(EXECUTION_BLOCK_NUMBER_AUG_1ST, &file_path).await?;
+    export_blocks_from(
+        NETWORK_CONSTANTS.sync_start_block_number,
+        &file_path,
+        format,
+    )
+    .await?;
 
     Ok(())
 }
@@ -221,17 +312,19 @@ pub async fn export_blocks_from_london() -> Result<()> {
     log::init_with_env();
 
     info!(
-        earliest_stored = EARLIEST_STORED_DB_BLOCK_NUMBER,
+        earliest_stored = %EARLIEST_STORED_DB_BLOCK_NUMBER,
         "writing blocks from london to earliest stored, to CSV"
     );
 
+    // Resumes by reading back the file it appends to, which only works for CSV.
     let file_path = "blocks_from_london.csv";
 
     let file = File::open(file_path);
     match file {
         Err(_err) => {
             info!("first run, starting at london hardfork");
-            export_blocks_from(LONDON_HARD_FORK_BLOCK_NUMBER, file_path).await?;
+            export_blocks_from(*LONDON_HARD_FORK_BLOCK_NUMBER, file_path, ExportFormat::Csv)
+                .await?;
         }
         Ok(file) => {
             // Because we interrupt the writing sometimes the last row may be malformed, if a file
@@ -248,9 +341,9 @@ pub async fn export_blocks_from_london() -> Result<()> {
                 .deserialize()
                 .last()
                 .map(|row: Result<OutRow, _>| row.unwrap().number)
-                .unwrap_or(LONDON_HARD_FORK_BLOCK_NUMBER);
-            info!(last_stored_block_number, "picking up from previous run");
-            export_blocks_from(last_stored_block_number + 1, file_path).await?;
+                .unwrap_or(*LONDON_HARD_FORK_BLOCK_NUMBER);
+            info!(%last_stored_block_number, "picking up from previous run");
+            export_blocks_from(last_stored_block_number + 1, file_path, ExportFormat::Csv).await?;
         }
     };
 
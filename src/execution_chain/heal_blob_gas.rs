@@ -0,0 +1,124 @@
+//! Blocks stored before we tracked blob gas have no blob gas fields, so burn sums computed from
+//! them miss the blob fee burn. Refetches every such block from Cancun on, stores its blob gas
+//! fields, and when done drops the burn sums so the sync recomputes them from scratch.
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use sqlx::PgPool;
+use tracing::{debug, info, warn};
+
+use crate::{
+    db,
+    heal::{self, HealOptions, Healer},
+    log,
+};
+
+use super::{block_store, BlockNumber, ExecutionNode, CANCUN_HARD_FORK_TIMESTAMP};
+
+const HEAL_BLOB_GAS_KEY: &str = "heal-blob-gas";
+
+struct BlobGasHealer {
+    db_pool: PgPool,
+    execution_node: ExecutionNode,
+}
+
+#[async_trait]
+impl Healer for BlobGasHealer {
+    type Item = BlockNumber;
+
+    fn name(&self) -> &'static str {
+        HEAL_BLOB_GAS_KEY
+    }
+
+    async fn items_to_heal(&self, checkpoint: Option<BlockNumber>) -> Vec<BlockNumber> {
+        sqlx::query!(
+            "
+            SELECT
+                number
+            FROM
+                blocks_next
+            WHERE
+                timestamp >= $1
+            AND
+                blob_gas_used IS NULL
+            AND
+                number > $2
+            ORDER BY
+                number ASC
+            ",
+            *CANCUN_HARD_FORK_TIMESTAMP,
+            checkpoint.unwrap_or(BlockNumber(-1)).0
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| BlockNumber(row.number))
+        .collect()
+    }
+
+    async fn heal_chunk(&self, block_numbers: &[BlockNumber], dry_run: bool) -> Result<()> {
+        for block_number in block_numbers {
+            let block = self
+                .execution_node
+                .get_block_by_number(block_number)
+                .await
+                .ok_or_else(|| anyhow!("no block on chain for stored block {block_number}"))?;
+
+            if dry_run {
+                debug!(
+                    %block_number,
+                    blob_gas_used = block.blob_gas_used,
+                    "dry run, skipping setting blob gas"
+                );
+                continue;
+            }
+
+            if !block_store::update_blob_gas(&self.db_pool, &block).await {
+                warn!(
+                    %block_number,
+                    block_hash = block.hash,
+                    "stored block no longer on chain, skipping"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn heal_blob_gas() {
+    heal_blob_gas_with_dry_run(heal::dry_run_from_args()).await;
+}
+
+pub async fn heal_blob_gas_with_dry_run(dry_run: bool) {
+    log::init_with_env();
+
+    info!("healing execution block blob gas");
+
+    let db_pool = db::get_db_pool("heal-blob-gas").await;
+
+    let healer = BlobGasHealer {
+        db_pool: db_pool.clone(),
+        execution_node: ExecutionNode::connect().await,
+    };
+
+    let options = HealOptions {
+        chunk_size: 100,
+        dry_run,
+    };
+    heal::heal(&db_pool, &healer, &options).await.unwrap();
+
+    if dry_run {
+        info!("dry run, skipping dropping burn sums");
+    } else {
+        // Burn sums build on the last stored sum, dropping them makes the sync recompute them from
+        // the healed blocks.
+        sqlx::query!("TRUNCATE TABLE burn_sums")
+            .execute(&db_pool)
+            .await
+            .unwrap();
+        info!("dropped burn sums, the sync recomputes them on the next block");
+    }
+
+    info!("done healing execution block blob gas");
+}
@@ -1,65 +1,117 @@
+mod backfill_execution_supply;
 mod balances;
-mod base_fees;
+pub mod base_fees;
+mod blobs;
 mod block_range;
 pub mod block_store;
 mod block_store_next;
+mod deposit_events;
 mod export_blocks;
+mod heal_blob_gas;
 mod logs;
 mod node;
+mod pow_issuance;
+mod receipt_burn;
 pub mod routes;
 pub mod supply_deltas;
 mod sync;
+mod transactions;
+
+pub use backfill_execution_supply::backfill_execution_supply;
 
 pub use balances::get_execution_balances_by_hash;
 pub use balances::ExecutionBalancesSum;
 
 pub use base_fees::routes as base_fees_routes;
 
+pub use blobs::blob_base_fee_from_excess_blob_gas;
+pub use blobs::blob_params_at;
+pub use blobs::BlobParams;
+pub use blobs::CANCUN_HARD_FORK_TIMESTAMP;
+pub use blobs::GAS_PER_BLOB;
+
 pub use block_range::BlockRange;
 
 pub use block_store::delete_blocks;
 pub use block_store::get_last_block_number;
 pub use block_store::store_block;
+pub use block_store::ExecutionChainRollback;
+pub use block_store::BURN_WEI_SQL;
 
 pub use block_store_next::BlockStore;
 pub use block_store_next::BlockStorePostgres;
 
+pub use deposit_events::get_pending_deposits;
+pub use deposit_events::update_pending_deposits;
+pub use deposit_events::DepositEventsRollback;
+pub use deposit_events::PendingDeposits;
+
 pub use export_blocks::export_blocks_from_august;
+pub use export_blocks::export_blocks_from_august_with_format;
 pub use export_blocks::export_blocks_from_london;
 
+pub use heal_blob_gas::heal_blob_gas;
+pub use heal_blob_gas::heal_blob_gas_with_dry_run;
+
 use lazy_static::lazy_static;
 pub use logs::write_heads_log as write_execution_heads_log;
 
+pub use node::decoders;
 pub use node::stream_heads_from;
 pub use node::stream_new_heads;
 pub use node::BlockHash;
 pub use node::BlockNumber;
+pub use node::ExecutionClient;
 pub use node::ExecutionNode;
 pub use node::ExecutionNodeBlock;
+pub use node::ExecutionNodeLog;
+pub use node::ExecutionNodeTransaction;
+pub use node::GetLogsError;
+pub use node::LogFilter;
 pub use node::TotalDifficulty;
+pub use node::TransactionReceipt;
 
 #[cfg(test)]
 pub use node::ExecutionNodeBlockBuilder;
 
+#[cfg(test)]
+pub use transactions::tests::BlockTransactionBuilder;
+
+pub use pow_issuance::backfill_pow_issuance;
+pub use pow_issuance::backfill_pow_issuance_from;
+pub use pow_issuance::get_daily_pow_issuance;
+pub use pow_issuance::get_pow_issuance_between;
+
+pub use receipt_burn::check_receipt_burn;
+pub use receipt_burn::BurnMismatchesRollback;
+
 pub use supply_deltas::add_delta;
+pub use supply_deltas::backfill_supply_deltas;
 pub use supply_deltas::export_deltas as export_execution_supply_deltas;
+pub use supply_deltas::export_deltas_with_format as export_execution_supply_deltas_with_format;
 pub use supply_deltas::stream_supply_deltas_from;
 pub use supply_deltas::summary_from_deltas_csv;
 pub use supply_deltas::sync_deltas as sync_execution_supply_deltas;
+pub use supply_deltas::verify_execution_supply;
+pub use supply_deltas::verify_execution_supply_with_range_size;
 pub use supply_deltas::write_deltas_log as write_execution_supply_deltas_log;
+pub use supply_deltas::write_deltas_log_with_format as write_execution_supply_deltas_log_with_format;
 pub use supply_deltas::SupplyDelta;
 
+pub use sync::store_missing_block;
 pub use sync::sync_blocks as sync_execution_blocks;
+pub use sync::sync_blocks_with_analyses as sync_execution_blocks_with_analyses;
+
+pub use transactions::get_block_transactions;
+pub use transactions::store_transactions;
+pub use transactions::BlockTransaction;
+pub use transactions::TransactionsRollback;
 
 use chrono::DateTime;
 use chrono::Utc;
 
-use crate::units::WeiNewtype;
+use crate::{network::NETWORK_CONSTANTS, units::WeiNewtype};
 
-pub const LONDON_HARD_FORK_BLOCK_HASH: &str =
-    "0x9b83c12c69edb74f6c8dd5d052765c1adf940e320bd1291696e6fa07829eee71";
-pub const LONDON_HARD_FORK_BLOCK_NUMBER: BlockNumber = 12965000;
-pub const MERGE_BLOCK_NUMBER: i32 = 15_537_394;
 #[allow(dead_code)]
 pub const TOTAL_TERMINAL_DIFFICULTY: u128 = 58750000000000000000000;
 
@@ -74,11 +126,17 @@ const MERGE_SLOT_SUPPLY: WeiNewtype = WeiNewtype(120_521_140_924_621_298_474_538
 #[allow(dead_code)]
 const LONDON_SLOT_SUPPLY_ESTIMATE: WeiNewtype = WeiNewtype(117_397_725_113_869_100_000_000_000);
 
-pub const GENESIS_SUPPLY: WeiNewtype = WeiNewtype(72_009_990_499_480_000_000_000_000);
-
 lazy_static! {
-    pub static ref LONDON_HARD_FORK_TIMESTAMP: DateTime<Utc> =
-        "2021-08-05T12:33:42Z".parse::<DateTime<Utc>>().unwrap();
-    pub static ref PARIS_HARD_FORK_TIMESTAMP: DateTime<Utc> =
-        "2022-09-15T06:42:59Z".parse::<DateTime<Utc>>().unwrap();
+    pub static ref GENESIS_BLOCK_HASH: String = NETWORK_CONSTANTS.genesis_block_hash.clone();
+    /// Used by tools which replay the execution supply from genesis, only known for mainnet.
+    pub static ref GENESIS_SUPPLY: Option<WeiNewtype> = NETWORK_CONSTANTS.genesis_supply;
+    pub static ref LONDON_HARD_FORK_BLOCK_HASH: String =
+        NETWORK_CONSTANTS.london_block_hash.clone();
+    pub static ref LONDON_HARD_FORK_BLOCK_NUMBER: BlockNumber =
+        NETWORK_CONSTANTS.london_block_number;
+    pub static ref LONDON_HARD_FORK_TIMESTAMP: DateTime<Utc> = NETWORK_CONSTANTS.london_timestamp;
+    pub static ref MERGE_BLOCK_NUMBER: BlockNumber = NETWORK_CONSTANTS.merge_block_number;
+    pub static ref PARIS_HARD_FORK_TIMESTAMP: DateTime<Utc> = NETWORK_CONSTANTS.paris_timestamp;
+    /// The first execution block included in a Shapella beacon block, see `SHAPELLA_SLOT`.
+    pub static ref SHAPELLA_BLOCK_NUMBER: BlockNumber = NETWORK_CONSTANTS.shapella_block_number;
 }
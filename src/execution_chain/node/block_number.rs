@@ -0,0 +1,159 @@
+use std::{
+    fmt::{Display, LowerHex},
+    num::ParseIntError,
+    ops::{Add, AddAssign, Sub, SubAssign},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+// Execution chain blocks come in about once every 12s. An i64 won't overflow for as long as the
+// chain may run, and matches the INT8 block number columns.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Eq,
+    Hash,
+    Ord,
+    PartialOrd,
+    PartialEq,
+    Serialize,
+    sqlx::Type,
+)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct BlockNumber(pub i64);
+
+impl BlockNumber {
+    pub const GENESIS: Self = Self(0);
+    pub const MAX: Self = Self(i64::MAX);
+
+    /// Every block number from first to last, including both.
+    pub fn range_inclusive(
+        first: BlockNumber,
+        last: BlockNumber,
+    ) -> impl DoubleEndedIterator<Item = BlockNumber> {
+        (first.0..=last.0).map(BlockNumber)
+    }
+}
+
+impl Display for BlockNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Nodes take and return block numbers as hex strings.
+impl LowerHex for BlockNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for BlockNumber {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+impl Add<i64> for BlockNumber {
+    type Output = Self;
+
+    fn add(self, rhs: i64) -> Self::Output {
+        Self(self.0 + rhs)
+    }
+}
+
+impl AddAssign<i64> for BlockNumber {
+    fn add_assign(&mut self, rhs: i64) {
+        self.0 += rhs;
+    }
+}
+
+impl Sub<i64> for BlockNumber {
+    type Output = Self;
+
+    fn sub(self, rhs: i64) -> Self::Output {
+        Self(self.0 - rhs)
+    }
+}
+
+impl SubAssign<i64> for BlockNumber {
+    fn sub_assign(&mut self, rhs: i64) {
+        self.0 -= rhs;
+    }
+}
+
+/// Number of blocks between two block numbers.
+impl Sub<BlockNumber> for BlockNumber {
+    type Output = i64;
+
+    fn sub(self, rhs: BlockNumber) -> Self::Output {
+        self.0 - rhs.0
+    }
+}
+
+impl From<i64> for BlockNumber {
+    fn from(block_number: i64) -> Self {
+        Self(block_number)
+    }
+}
+
+impl From<BlockNumber> for i64 {
+    fn from(block_number: BlockNumber) -> Self {
+        block_number.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_test() {
+        let block_number = BlockNumber(10);
+
+        assert_eq!(block_number + 5, BlockNumber(15));
+        assert_eq!(block_number - 5, BlockNumber(5));
+        assert_eq!(BlockNumber(15) - block_number, 5);
+
+        let mut block_number = block_number;
+        block_number += 1;
+        assert_eq!(block_number, BlockNumber(11));
+        block_number -= 2;
+        assert_eq!(block_number, BlockNumber(9));
+    }
+
+    #[test]
+    fn range_inclusive_test() {
+        assert_eq!(
+            BlockNumber::range_inclusive(BlockNumber(1), BlockNumber(3)).collect::<Vec<_>>(),
+            vec![BlockNumber(1), BlockNumber(2), BlockNumber(3)]
+        );
+        assert_eq!(
+            BlockNumber::range_inclusive(BlockNumber(2), BlockNumber(1)).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn beyond_i32_test() {
+        let block_number = "3000000000".parse::<BlockNumber>().unwrap();
+        assert_eq!(block_number, BlockNumber(3_000_000_000));
+        assert_eq!(format!("0x{block_number:x}"), "0xb2d05e00");
+    }
+
+    #[test]
+    fn serde_test() {
+        assert_eq!(serde_json::to_string(&BlockNumber(42)).unwrap(), "42");
+        assert_eq!(
+            serde_json::from_str::<BlockNumber>("42").unwrap(),
+            BlockNumber(42)
+        );
+    }
+}
@@ -1,48 +1,170 @@
-use super::decoders::{
-    from_i32_hex_str, from_u128_hex_str, from_u64_hex_str, from_unix_timestamp_hex_str,
+use super::{
+    decoders::{
+        from_block_number_hex_str, from_i32_hex_str, from_option_i32_hex_str,
+        from_option_u128_hex_str, from_option_u64_hex_str, from_u64_hex_str,
+        from_unix_timestamp_hex_str,
+    },
+    BlockNumber,
 };
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 
-// Execution chain blocks come in about once every 13s from genesis. With u32 our program
-// would overflow when the block number passes 2_147_483_648. i32::MAX * 13 seconds = ~885 years.
-pub type BlockNumber = i32;
+use crate::{
+    execution_chain::{LONDON_HARD_FORK_BLOCK_NUMBER, MERGE_BLOCK_NUMBER},
+    units::WeiNewtype,
+};
 
 // Eyeballed this one.
 pub type Difficulty = u64;
 // Final total difficulty on Ethereum is 76 bits. This should never increase anymore.
 pub type TotalDifficulty = u128;
 
+/// Total difficulty stopped growing at the merge, some clients stopped returning it since.
+const TERMINAL_TOTAL_DIFFICULTY: TotalDifficulty = 58_750_003_716_598_352_816_469;
+
 /// Hash for a block on the execution layer.
 pub type BlockHash = String;
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+/// A block as the node sends it. Which fields a block has depends on the fork it's from, e.g. there
+/// is no base fee before London, and some clients leave out difficulty after the merge. Whether the
+/// fields a block should have are there is checked against its number, see `ExecutionNodeBlock`.
+#[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
+struct NodeBlock {
+    #[serde(default, deserialize_with = "from_option_u64_hex_str")]
+    base_fee_per_gas: Option<u64>,
+    #[serde(default, deserialize_with = "from_option_i32_hex_str")]
+    blob_gas_used: Option<i32>,
+    #[serde(default, deserialize_with = "from_option_u64_hex_str")]
+    difficulty: Option<Difficulty>,
+    #[serde(default, deserialize_with = "from_option_u64_hex_str")]
+    excess_blob_gas: Option<u64>,
+    #[serde(deserialize_with = "from_i32_hex_str")]
+    gas_limit: i32,
+    #[serde(deserialize_with = "from_i32_hex_str")]
+    gas_used: i32,
+    hash: BlockHash,
+    #[serde(deserialize_with = "from_block_number_hex_str")]
+    number: BlockNumber,
+    parent_hash: String,
+    #[serde(deserialize_with = "from_unix_timestamp_hex_str")]
+    timestamp: DateTime<Utc>,
+    #[serde(default, deserialize_with = "from_option_u128_hex_str")]
+    total_difficulty: Option<TotalDifficulty>,
+    transactions: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "NodeBlock")]
 pub struct ExecutionNodeBlock {
     // Highest gas price seen, ~4000 Gwei, if we want 1000x to future proof, we need to handle
     // 4000 * 1000 * 1e9 (Gwei) = 4e15, which needs 52 bits. Still fits within FLOAT8 too (2^53).
-    #[serde(deserialize_with = "from_u64_hex_str")]
+    // Zero before London, nothing was burned.
     pub base_fee_per_gas: u64,
-    #[serde(deserialize_with = "from_u64_hex_str")]
+    // Only present from Cancun onwards. At most a handful of blobs of 2^17 gas each.
+    pub blob_gas_used: Option<i32>,
+    // Zero from the merge onwards.
     pub difficulty: Difficulty,
+    // Only present from Cancun onwards.
+    pub excess_blob_gas: Option<u64>,
+    // Currently at 30M, the same headroom as gas_used applies.
+    pub gas_limit: i32,
     // Started at 8M, currently at 30M, seems to fit in 2^31 for the foreseeable future.
-    #[serde(deserialize_with = "from_i32_hex_str")]
     pub gas_used: i32,
     pub hash: BlockHash,
-    #[serde(deserialize_with = "from_i32_hex_str")]
     pub number: BlockNumber,
     pub parent_hash: String,
-    #[serde(deserialize_with = "from_unix_timestamp_hex_str")]
     pub timestamp: DateTime<Utc>,
-    #[serde(deserialize_with = "from_u128_hex_str")]
+    // The terminal total difficulty from the merge onwards.
     pub total_difficulty: TotalDifficulty,
     // Types for blocks coming from the node and from our DB should be split.
     pub transactions: Vec<String>,
 }
 
+impl TryFrom<NodeBlock> for ExecutionNodeBlock {
+    type Error = String;
+
+    fn try_from(block: NodeBlock) -> Result<Self, Self::Error> {
+        let number = block.number;
+        let is_london = number >= *LONDON_HARD_FORK_BLOCK_NUMBER;
+        let is_merged = number >= *MERGE_BLOCK_NUMBER;
+
+        let base_fee_per_gas = match block.base_fee_per_gas {
+            Some(base_fee_per_gas) => base_fee_per_gas,
+            None if !is_london => 0,
+            None => {
+                return Err(format!(
+                    "block {number} is from London on but has no baseFeePerGas"
+                ))
+            }
+        };
+        let difficulty = match block.difficulty {
+            Some(difficulty) => difficulty,
+            None if is_merged => 0,
+            None => {
+                return Err(format!(
+                    "block {number} is from before the merge but has no difficulty"
+                ))
+            }
+        };
+        let total_difficulty = match block.total_difficulty {
+            Some(total_difficulty) => total_difficulty,
+            None if is_merged => TERMINAL_TOTAL_DIFFICULTY,
+            None => {
+                return Err(format!(
+                    "block {number} is from before the merge but has no totalDifficulty"
+                ))
+            }
+        };
+
+        Ok(Self {
+            base_fee_per_gas,
+            blob_gas_used: block.blob_gas_used,
+            difficulty,
+            excess_blob_gas: block.excess_blob_gas,
+            gas_limit: block.gas_limit,
+            gas_used: block.gas_used,
+            hash: block.hash,
+            number,
+            parent_hash: block.parent_hash,
+            timestamp: block.timestamp,
+            total_difficulty,
+            transactions: block.transactions,
+        })
+    }
+}
+
+/// Only what we need of a withdrawal, amounts are in Gwei.
+#[derive(Deserialize)]
+pub struct Withdrawal {
+    #[serde(deserialize_with = "from_u64_hex_str")]
+    pub amount: u64,
+}
+
+/// Only present from Shapella onwards.
+#[derive(Deserialize)]
+pub struct BlockWithWithdrawals {
+    #[serde(default)]
+    pub withdrawals: Vec<Withdrawal>,
+}
+
+/// Only what we need of an uncle, the header of a block that lost the race to become canonical.
+#[derive(Deserialize)]
+pub struct UncleHeader {
+    #[serde(deserialize_with = "from_block_number_hex_str")]
+    pub number: BlockNumber,
+}
+
+impl ExecutionNodeBlock {
+    /// Everything burned in the block, the base fee and, from Cancun onwards, the blob fee.
+    pub fn burn(&self) -> WeiNewtype {
+        WeiNewtype(self.base_fee_per_gas as i128 * self.gas_used as i128) + self.blob_fee_burn()
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
-    use crate::{time_frames::GrowingTimeFrame::*, units::WeiNewtype};
+    use crate::time_frames::GrowingTimeFrame::*;
 
     use super::*;
 
@@ -51,8 +173,11 @@ pub mod tests {
         number: BlockNumber,
         parent_hash: String,
         timestamp: DateTime<Utc>,
+        gas_limit: i32,
         gas_used: i32,
         base_fee_per_gas: u64,
+        blob_gas_used: Option<i32>,
+        excess_blob_gas: Option<u64>,
     }
 
     impl ExecutionNodeBlockBuilder {
@@ -61,11 +186,14 @@ pub mod tests {
 
             Self {
                 timestamp: SinceMerge.start_timestamp(),
-                number: 0,
+                number: BlockNumber::GENESIS,
                 hash,
                 parent_hash: "0x0".to_string(),
+                gas_limit: 30_000_000,
                 gas_used: 0,
                 base_fee_per_gas: 0,
+                blob_gas_used: None,
+                excess_blob_gas: None,
             }
         }
 
@@ -83,17 +211,32 @@ pub mod tests {
             self
         }
 
+        pub fn with_blob_gas_used(mut self, blob_gas_used: i32) -> Self {
+            self.blob_gas_used = Some(blob_gas_used);
+            self
+        }
+
         pub fn with_burn(mut self, burn: WeiNewtype) -> Self {
             self.gas_used = 10;
             self.base_fee_per_gas = (burn.0 / self.gas_used as i128) as u64;
             self
         }
 
+        pub fn with_excess_blob_gas(mut self, excess_blob_gas: u64) -> Self {
+            self.excess_blob_gas = Some(excess_blob_gas);
+            self
+        }
+
         pub fn with_hash(mut self, hash: &str) -> Self {
             self.hash = hash.to_string();
             self
         }
 
+        pub fn with_gas_limit(mut self, gas_limit: i32) -> Self {
+            self.gas_limit = gas_limit;
+            self
+        }
+
         pub fn with_gas_used(mut self, gas_used: i32) -> Self {
             self.gas_used = gas_used;
             self
@@ -129,7 +272,10 @@ pub mod tests {
         pub fn build(&self) -> ExecutionNodeBlock {
             ExecutionNodeBlock {
                 base_fee_per_gas: self.base_fee_per_gas,
+                blob_gas_used: self.blob_gas_used,
                 difficulty: 0,
+                excess_blob_gas: self.excess_blob_gas,
+                gas_limit: self.gas_limit,
                 gas_used: self.gas_used,
                 hash: self.hash.clone(),
                 number: self.number,
@@ -140,4 +286,70 @@ pub mod tests {
             }
         }
     }
+
+    #[test]
+    fn decode_post_merge_block_without_difficulty_test() {
+        let block = serde_json::from_value::<ExecutionNodeBlock>(serde_json::json!({
+            "baseFeePerGas": "0x7",
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x0",
+            "hash": "0xhash",
+            "number": "0x1312d00",
+            "parentHash": "0xparent",
+            "timestamp": "0x65f1b057",
+            "transactions": [],
+            "withdrawalsRoot": "0xroot"
+        }))
+        .unwrap();
+
+        assert_eq!(block.difficulty, 0);
+        assert_eq!(block.total_difficulty, TERMINAL_TOTAL_DIFFICULTY);
+        assert_eq!(block.blob_gas_used, None);
+    }
+
+    #[test]
+    fn decode_pre_london_block_test() {
+        let block = serde_json::from_value::<ExecutionNodeBlock>(serde_json::json!({
+            "difficulty": "0x1",
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x0",
+            "hash": "0xhash",
+            "number": "0x1",
+            "parentHash": "0xparent",
+            "timestamp": "0x55ba4224",
+            "totalDifficulty": "0x2",
+            "transactions": []
+        }))
+        .unwrap();
+
+        assert_eq!(block.base_fee_per_gas, 0);
+        assert_eq!(block.total_difficulty, 2);
+    }
+
+    #[test]
+    fn decode_block_missing_fork_fields_test() {
+        let post_london_err = serde_json::from_value::<ExecutionNodeBlock>(serde_json::json!({
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x0",
+            "hash": "0xhash",
+            "number": "0x1312d00",
+            "parentHash": "0xparent",
+            "timestamp": "0x65f1b057",
+            "transactions": []
+        }))
+        .unwrap_err();
+        assert!(post_london_err.to_string().contains("has no baseFeePerGas"));
+
+        let pre_merge_err = serde_json::from_value::<ExecutionNodeBlock>(serde_json::json!({
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x0",
+            "hash": "0xhash",
+            "number": "0x1",
+            "parentHash": "0xparent",
+            "timestamp": "0x55ba4224",
+            "transactions": []
+        }))
+        .unwrap_err();
+        assert!(pre_merge_err.to_string().contains("has no difficulty"));
+    }
 }
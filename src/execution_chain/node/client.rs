@@ -0,0 +1,85 @@
+//! Which execution client the node runs, from its `web3_clientVersion`. Clients differ in the
+//! APIs they offer beyond the standard ones, and in which block fields they return. The block
+//! decoders tolerate the fields differing, what the client offers decides how we get supply
+//! deltas.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionClient {
+    Besu,
+    Erigon,
+    Geth,
+    Nethermind,
+    Reth,
+    Unknown,
+}
+
+impl ExecutionClient {
+    /// Client versions look like `Geth/v1.13.14-stable/linux-amd64/go1.21.7`, the name comes
+    /// first.
+    pub fn from_client_version(client_version: &str) -> Self {
+        let name = client_version
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match name.as_str() {
+            "besu" => Self::Besu,
+            "erigon" => Self::Erigon,
+            "geth" => Self::Geth,
+            "nethermind" => Self::Nethermind,
+            "reth" => Self::Reth,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Parity style `trace_block`, which geth doesn't offer.
+    pub fn has_trace_block(&self) -> bool {
+        matches!(
+            self,
+            Self::Besu | Self::Erigon | Self::Nethermind | Self::Reth
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_client_version_test() {
+        assert_eq!(
+            ExecutionClient::from_client_version("Geth/v1.13.14-stable/linux-amd64/go1.21.7"),
+            ExecutionClient::Geth
+        );
+        assert_eq!(
+            ExecutionClient::from_client_version(
+                "Nethermind/v1.25.4+20b10b35/linux-x64/dotnet8.0.2"
+            ),
+            ExecutionClient::Nethermind
+        );
+        assert_eq!(
+            ExecutionClient::from_client_version("erigon/2.58.1/linux-amd64/go1.21.7"),
+            ExecutionClient::Erigon
+        );
+        assert_eq!(
+            ExecutionClient::from_client_version("besu/v24.1.2/linux-x86_64/openjdk-java-17"),
+            ExecutionClient::Besu
+        );
+        assert_eq!(
+            ExecutionClient::from_client_version("reth/v0.2.0-beta.1/x86_64-unknown-linux-gnu"),
+            ExecutionClient::Reth
+        );
+        assert_eq!(
+            ExecutionClient::from_client_version("SomethingElse/v1"),
+            ExecutionClient::Unknown
+        );
+    }
+
+    #[test]
+    fn supply_delta_capabilities_test() {
+        assert!(!ExecutionClient::Geth.has_trace_block());
+        assert!(ExecutionClient::Erigon.has_trace_block());
+        assert!(!ExecutionClient::Unknown.has_trace_block());
+    }
+}
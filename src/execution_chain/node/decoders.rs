@@ -1,12 +1,37 @@
+use std::num::ParseIntError;
+
 use chrono::{DateTime, TimeZone, Utc};
-use serde::{Deserialize, Deserializer};
+use serde::{de::Error, Deserialize, Deserializer};
+
+use super::BlockNumber;
+
+/// Nodes encode quantities as 0x prefixed hex. Errors name the value instead of panicking, a
+/// client sending something unexpected shows up as a failed decode of the field.
+fn parse_hex<T, E: Error>(
+    s: &str,
+    from_str_radix: fn(&str, u32) -> Result<T, ParseIntError>,
+) -> Result<T, E> {
+    let digits = s
+        .strip_prefix("0x")
+        .ok_or_else(|| E::custom(format!("expected 0x prefixed hex quantity, got {s:?}")))?;
+    from_str_radix(digits, 16)
+        .map_err(|err| E::custom(format!("failed to parse hex quantity {s:?}, {err}")))
+}
 
 pub fn from_i32_hex_str<'de, D>(deserializer: D) -> Result<i32, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s: String = Deserialize::deserialize(deserializer)?;
-    Ok(i32::from_str_radix(&s[2..], 16).unwrap())
+    parse_hex(&s, i32::from_str_radix)
+}
+
+pub fn from_block_number_hex_str<'de, D>(deserializer: D) -> Result<BlockNumber, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    parse_hex(&s, i64::from_str_radix).map(BlockNumber)
 }
 
 pub fn from_u32_hex_str<'de, D>(deserializer: D) -> Result<u32, D::Error>
@@ -14,7 +39,7 @@ where
     D: Deserializer<'de>,
 {
     let s: String = Deserialize::deserialize(deserializer)?;
-    Ok(u32::from_str_radix(&s[2..], 16).unwrap())
+    parse_hex(&s, u32::from_str_radix)
 }
 
 pub fn from_u64_hex_str<'de, D>(deserializer: D) -> Result<u64, D::Error>
@@ -22,7 +47,15 @@ where
     D: Deserializer<'de>,
 {
     let s: String = Deserialize::deserialize(deserializer)?;
-    Ok(u64::from_str_radix(&s[2..], 16).unwrap())
+    parse_hex(&s, u64::from_str_radix)
+}
+
+pub fn from_i64_hex_str<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    parse_hex(&s, i64::from_str_radix)
 }
 
 pub fn from_u128_hex_str<'de, D>(deserializer: D) -> Result<u128, D::Error>
@@ -30,7 +63,15 @@ where
     D: Deserializer<'de>,
 {
     let s: String = Deserialize::deserialize(deserializer)?;
-    Ok(u128::from_str_radix(&s[2..], 16).unwrap())
+    parse_hex(&s, u128::from_str_radix)
+}
+
+pub fn from_i128_hex_str<'de, D>(deserializer: D) -> Result<i128, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    parse_hex(&s, i128::from_str_radix)
 }
 
 pub fn from_unix_timestamp_hex_str<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
@@ -38,6 +79,66 @@ where
     D: Deserializer<'de>,
 {
     let timestamp_u32 = from_u32_hex_str(deserializer)?;
-    let date_time = Utc.timestamp_opt(timestamp_u32.into(), 0).unwrap();
-    Ok(date_time)
+    Utc.timestamp_opt(timestamp_u32.into(), 0)
+        .single()
+        .ok_or_else(|| D::Error::custom(format!("timestamp {timestamp_u32} out of range")))
+}
+
+pub fn from_option_i32_hex_str<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<String> = Deserialize::deserialize(deserializer)?;
+    s.map(|s| parse_hex(&s, i32::from_str_radix)).transpose()
+}
+
+pub fn from_option_u64_hex_str<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<String> = Deserialize::deserialize(deserializer)?;
+    s.map(|s| parse_hex(&s, u64::from_str_radix)).transpose()
+}
+
+pub fn from_option_u128_hex_str<'de, D>(deserializer: D) -> Result<Option<u128>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<String> = Deserialize::deserialize(deserializer)?;
+    s.map(|s| parse_hex(&s, u128::from_str_radix)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Quantity {
+        #[serde(deserialize_with = "from_i32_hex_str")]
+        value: i32,
+    }
+
+    #[test]
+    fn from_i32_hex_str_test() {
+        let quantity = serde_json::from_value::<Quantity>(json!({ "value": "0x1f" })).unwrap();
+        assert_eq!(quantity.value, 31);
+    }
+
+    #[test]
+    fn missing_prefix_test() {
+        let err = serde_json::from_value::<Quantity>(json!({ "value": "1f" })).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected 0x prefixed hex quantity, got \"1f\""));
+    }
+
+    #[test]
+    fn invalid_digits_test() {
+        let err = serde_json::from_value::<Quantity>(json!({ "value": "0xzz" })).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("failed to parse hex quantity \"0xzz\""));
+    }
 }
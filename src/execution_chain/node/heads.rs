@@ -12,7 +12,7 @@ use crate::execution_chain::{node::BlockNumber, BlockRange};
 #[serde(rename_all = "camelCase")]
 pub struct Head {
     pub hash: String,
-    #[serde(deserialize_with = "from_i32_hex_str")]
+    #[serde(deserialize_with = "from_block_number_hex_str")]
     pub number: BlockNumber,
     pub parent_hash: String,
     #[serde(deserialize_with = "from_unix_timestamp_hex_str")]
@@ -168,12 +168,12 @@ fn stream_historic_block_numbers(block_range: BlockRange) -> impl Stream<Item =
 }
 
 pub async fn stream_heads_from(gte_slot: BlockNumber) -> impl Stream<Item = BlockNumber> {
-    debug!(from = gte_slot, "streaming heads");
+    debug!(from = %gte_slot, "streaming heads");
 
     let execution_node = ExecutionNode::connect().await;
     let last_block_on_start = execution_node.get_latest_block().await;
     debug!(
-        block_number = last_block_on_start.number,
+        block_number = %last_block_on_start.number,
         "last block on chain",
     );
 
@@ -0,0 +1,92 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::execution_chain::BlockRange;
+
+use super::{
+    decoders::{from_block_number_hex_str, from_i32_hex_str},
+    BlockNumber,
+};
+
+/// Nodes refuse log queries over too many blocks, or returning too many logs. We start with
+/// chunks of this many blocks, and halve them when a node refuses.
+pub const LOGS_CHUNK_SIZE: i64 = 2_000;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionNodeLog {
+    pub address: String,
+    pub block_hash: String,
+    #[serde(deserialize_with = "from_block_number_hex_str")]
+    pub block_number: BlockNumber,
+    pub data: String,
+    #[serde(deserialize_with = "from_i32_hex_str")]
+    pub log_index: i32,
+    /// Logs from blocks which were reorged out, only seen when subscribing.
+    #[serde(default)]
+    pub removed: bool,
+    pub topics: Vec<String>,
+    pub transaction_hash: String,
+    #[serde(deserialize_with = "from_i32_hex_str")]
+    pub transaction_index: i32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LogFilter {
+    pub address: Option<String>,
+    /// Matched by position, `None` matches any topic in that position.
+    pub topics: Vec<Option<String>>,
+}
+
+impl LogFilter {
+    pub fn to_params(&self, block_range: &BlockRange) -> Value {
+        json!([{
+            "address": self.address,
+            "fromBlock": format!("0x{:x}", block_range.start),
+            "toBlock": format!("0x{:x}", block_range.end),
+            "topics": self.topics,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_filter_params_test() {
+        let filter = LogFilter {
+            address: Some("0xcontract".to_string()),
+            topics: vec![Some("0xtopic".to_string()), None],
+        };
+
+        assert_eq!(
+            filter.to_params(&BlockRange::new(BlockNumber(16), BlockNumber(31))),
+            json!([{
+                "address": "0xcontract",
+                "fromBlock": "0x10",
+                "toBlock": "0x1f",
+                "topics": ["0xtopic", null],
+            }])
+        );
+    }
+
+    #[test]
+    fn decode_log_test() {
+        let log: ExecutionNodeLog = serde_json::from_value(json!({
+            "address": "0xcontract",
+            "blockHash": "0xblock",
+            "blockNumber": "0x10",
+            "data": "0x",
+            "logIndex": "0x2",
+            "topics": ["0xtopic"],
+            "transactionHash": "0xtransaction",
+            "transactionIndex": "0x1"
+        }))
+        .unwrap();
+
+        assert_eq!(log.block_number, BlockNumber(16));
+        assert_eq!(log.log_index, 2);
+        assert!(!log.removed);
+    }
+}
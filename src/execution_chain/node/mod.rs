@@ -1,14 +1,20 @@
+mod block_number;
 mod blocks;
-mod decoders;
+mod client;
+pub mod decoders;
 mod heads;
+mod logs;
+mod traces;
 mod transaction_receipts;
+mod transactions;
 
 use std::{
     collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
 use async_tungstenite::{
     tokio::{connect_async, TokioAdapter},
     tungstenite::Message,
@@ -23,25 +29,47 @@ use serde_json::{json, Value};
 use thiserror::Error;
 use tokio::{net::TcpStream, sync::mpsc};
 
-use crate::env;
+use crate::{
+    config,
+    execution_chain::BlockRange,
+    health::NodeProbe,
+    units::{GweiNewtype, WeiNewtype},
+};
+
+pub use block_number::BlockNumber;
 
 pub use blocks::BlockHash;
-pub use blocks::BlockNumber;
 pub use blocks::Difficulty;
 pub use blocks::ExecutionNodeBlock;
 pub use blocks::TotalDifficulty;
 
+pub use client::ExecutionClient;
+
 pub use heads::stream_heads_from;
 pub use heads::stream_new_heads;
 pub use heads::Head;
 
+pub use logs::ExecutionNodeLog;
+pub use logs::LogFilter;
+
+pub use traces::self_destruct_burn_sum;
+pub use traces::BlockTrace;
+
+pub use transaction_receipts::TransactionReceipt;
+
+pub use transactions::ExecutionNodeTransaction;
+
 #[cfg(test)]
 pub use blocks::tests::ExecutionNodeBlockBuilder;
 
-use self::transaction_receipts::TransactionReceipt;
+use self::{
+    blocks::{BlockWithWithdrawals, UncleHeader},
+    logs::LOGS_CHUNK_SIZE,
+    transactions::BlockWithTransactions,
+};
 
 lazy_static! {
-    static ref EXECUTION_URL: String = env::get_env_var_unsafe("GETH_URL");
+    static ref EXECUTION_URL: String = config::CONFIG.geth_url().to_string();
 }
 
 #[allow(dead_code)]
@@ -159,6 +187,30 @@ pub struct ExecutionNode {
 #[error("transaction receipt unavailable for tx hash: {0}")]
 pub struct TransactionReceiptUnavailable(String);
 
+#[derive(Error, Debug)]
+#[error("failed to get logs for blocks {block_range}, {message}")]
+pub struct GetLogsError {
+    block_range: BlockRange,
+    message: String,
+}
+
+#[derive(Error, Debug)]
+#[error("eth_call to {to} at block {block_number} failed, {message}")]
+pub struct EthCallError {
+    block_number: BlockNumber,
+    message: String,
+    to: String,
+}
+
+/// A block that fails to decode, e.g. one missing a field its fork should have, is logged and
+/// treated like a bad response.
+fn decode_block(value: serde_json::Value) -> Option<ExecutionNodeBlock> {
+    serde_json::from_value::<Option<ExecutionNodeBlock>>(value).unwrap_or_else(|err| {
+        tracing::error!(%err, "failed to decode block from node");
+        None
+    })
+}
+
 impl ExecutionNode {
     pub async fn connect() -> Self {
         let id_pool_am = Arc::new(Mutex::new(IdPool::new(u16::MAX.into())));
@@ -222,7 +274,7 @@ impl ExecutionNode {
                     tracing::error!("eth_getBlockByHash bad response {:?}", err);
                     None
                 },
-                |value| serde_json::from_value::<Option<ExecutionNodeBlock>>(value).unwrap(),
+                decode_block,
             )
     }
 
@@ -235,8 +287,185 @@ impl ExecutionNode {
                     tracing::error!("eth_getBlockByNumber bad response {:?}", err);
                     None
                 },
-                |value| serde_json::from_value::<Option<ExecutionNodeBlock>>(value).unwrap(),
+                decode_block,
+            )
+    }
+
+    /// Which client the node runs, `Unknown` when it doesn't say.
+    pub async fn get_client(&self) -> ExecutionClient {
+        self.call("web3_clientVersion", &json!([]))
+            .await
+            .ok()
+            .and_then(|value| serde_json::from_value::<String>(value).ok())
+            .map_or(ExecutionClient::Unknown, |client_version| {
+                ExecutionClient::from_client_version(&client_version)
+            })
+    }
+
+    /// Parity style traces of every call in the block with the given number. `None` when the node
+    /// doesn't have the block, or doesn't offer `trace_block`, an error when the traces don't
+    /// decode.
+    pub async fn trace_block(&self, number: &BlockNumber) -> Result<Option<Vec<BlockTrace>>> {
+        let hex_number = format!("0x{number:x}");
+        match self.call("trace_block", &json!([hex_number])).await {
+            Ok(value) => Ok(serde_json::from_value::<Option<Vec<BlockTrace>>>(value)?),
+            Err(err) => {
+                tracing::error!("trace_block bad response {:?}", err);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Everything the withdrawals in the block with the given number minted, zero before Shapella.
+    /// `None` when the node doesn't have the block, an error when the block doesn't decode.
+    pub async fn get_withdrawals_sum(&self, number: &BlockNumber) -> Result<Option<WeiNewtype>> {
+        let hex_number = format!("0x{number:x}");
+        let block = match self
+            .call("eth_getBlockByNumber", &json!([hex_number, false]))
+            .await
+        {
+            Ok(value) => serde_json::from_value::<Option<BlockWithWithdrawals>>(value)?,
+            Err(err) => {
+                tracing::error!("eth_getBlockByNumber bad response {:?}", err);
+                None
+            }
+        };
+
+        Ok(block.map(|block| {
+            WeiNewtype(
+                block
+                    .withdrawals
+                    .iter()
+                    .map(|withdrawal| WeiNewtype::from(GweiNewtype(withdrawal.amount as i64)).0)
+                    .sum(),
             )
+        }))
+    }
+
+    /// Block numbers of the uncles the block with the given number includes, `None` when the node
+    /// doesn't have the block, an error when the uncle count or an uncle doesn't decode.
+    pub async fn get_uncle_numbers(
+        &self,
+        number: &BlockNumber,
+    ) -> Result<Option<Vec<BlockNumber>>> {
+        let hex_number = format!("0x{number:x}");
+        let uncle_count = match self
+            .call("eth_getUncleCountByBlockNumber", &json!([&hex_number]))
+            .await
+        {
+            Ok(value) => serde_json::from_value::<Option<String>>(value)
+                .with_context(|| format!("failed to decode uncle count of block {number}"))?,
+            Err(err) => {
+                tracing::error!("eth_getUncleCountByBlockNumber bad response {:?}", err);
+                None
+            }
+        };
+        let Some(uncle_count) = uncle_count else {
+            return Ok(None);
+        };
+        let uncle_count = u32::from_str_radix(uncle_count.trim_start_matches("0x"), 16)
+            .with_context(|| {
+                format!("expected uncle count of block {number} to be hex, got {uncle_count}")
+            })?;
+
+        let mut uncle_numbers = Vec::new();
+        for index in 0..uncle_count {
+            let value = match self
+                .call(
+                    "eth_getUncleByBlockNumberAndIndex",
+                    &json!([&hex_number, format!("0x{index:x}")]),
+                )
+                .await
+            {
+                Ok(value) => value,
+                Err(err) => {
+                    tracing::error!("eth_getUncleByBlockNumberAndIndex bad response {:?}", err);
+                    return Ok(None);
+                }
+            };
+            let uncle = serde_json::from_value::<UncleHeader>(value)
+                .with_context(|| format!("failed to decode uncle {index} of block {number}"))?;
+            uncle_numbers.push(uncle.number);
+        }
+
+        Ok(Some(uncle_numbers))
+    }
+
+    /// Full transactions for the block with the given hash, in block order. `None` when the node
+    /// doesn't have the block, an error when the block doesn't decode.
+    pub async fn get_transactions_by_block_hash(
+        &self,
+        hash: &str,
+    ) -> Result<Option<Vec<ExecutionNodeTransaction>>> {
+        match self.call("eth_getBlockByHash", &json!((hash, true))).await {
+            Ok(value) => {
+                let block = serde_json::from_value::<Option<BlockWithTransactions>>(value)
+                    .with_context(|| format!("failed to decode transactions of block {hash}"))?;
+                Ok(block.map(|block| block.transactions))
+            }
+            Err(err) => {
+                tracing::error!("eth_getBlockByHash bad response {:?}", err);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Logs matching the filter, in the given block range. Requests the range in chunks, halving
+    /// the chunk size whenever the node refuses, down to single blocks.
+    pub async fn get_logs(
+        &self,
+        filter: &LogFilter,
+        block_range: &BlockRange,
+    ) -> Result<Vec<ExecutionNodeLog>, GetLogsError> {
+        let mut logs = Vec::new();
+        let mut chunk_size = LOGS_CHUNK_SIZE;
+        let mut start = block_range.start;
+
+        while start <= block_range.end {
+            let chunk = BlockRange::new(start, (start + chunk_size - 1).min(block_range.end));
+            match self.call("eth_getLogs", &filter.to_params(&chunk)).await {
+                Ok(value) => {
+                    let chunk_logs = serde_json::from_value::<Vec<ExecutionNodeLog>>(value)
+                        .expect("expect eth_getLogs response to be a list of logs");
+                    logs.extend(chunk_logs);
+                    start = chunk.end + 1;
+                }
+                Err(err) if chunk_size > 1 => {
+                    tracing::debug!(%chunk, ?err, "eth_getLogs refused, halving chunk size");
+                    chunk_size /= 2;
+                }
+                Err(err) => {
+                    return Err(GetLogsError {
+                        block_range: chunk,
+                        message: err.message,
+                    })
+                }
+            }
+        }
+
+        Ok(logs)
+    }
+
+    /// Calls a contract as of the given block, without sending a transaction. Takes and returns
+    /// hex encoded data.
+    pub async fn eth_call(
+        &self,
+        to: &str,
+        data: &str,
+        block_number: &BlockNumber,
+    ) -> Result<String, EthCallError> {
+        let hex_number = format!("0x{block_number:x}");
+        self.call("eth_call", &json!([{ "to": to, "data": data }, hex_number]))
+            .await
+            .map(|value| {
+                serde_json::from_value::<String>(value)
+                    .expect("expect eth_call response to be a hex string")
+            })
+            .map_err(|err| EthCallError {
+                block_number: *block_number,
+                message: err.message,
+                to: to.to_string(),
+            })
     }
 
     async fn call(&self, method: &str, params: &Value) -> Result<serde_json::Value, RpcError> {
@@ -303,6 +532,13 @@ impl ExecutionNode {
     }
 }
 
+#[async_trait]
+impl NodeProbe for ExecutionNode {
+    async fn is_reachable(&self) -> bool {
+        self.call("eth_blockNumber", &json!([])).await.is_ok()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -316,14 +552,14 @@ mod tests {
     #[tokio::test]
     async fn get_block_by_number_test() {
         let node = ExecutionNode::connect().await;
-        let block = node.get_block_by_number(&12965000).await;
-        assert_eq!(block.unwrap().number, 12965000);
+        let block = node.get_block_by_number(&BlockNumber(12965000)).await;
+        assert_eq!(block.unwrap().number, BlockNumber(12965000));
     }
 
     #[tokio::test]
     async fn get_unavailable_block_by_number_test() {
         let node = ExecutionNode::connect().await;
-        let block = node.get_block_by_number(&999_999_999).await;
+        let block = node.get_block_by_number(&BlockNumber(999_999_999)).await;
         assert_eq!(block, None);
     }
 
@@ -333,7 +569,7 @@ mod tests {
         let block = node
             .get_block_by_hash("0x1b9595ee9ccda512b7f60beb1127095854475422ceb754a05fe537ee8163e4e7")
             .await;
-        assert_eq!(block.unwrap().number, 15327142);
+        assert_eq!(block.unwrap().number, BlockNumber(15327142));
     }
 
     #[tokio::test]
@@ -343,6 +579,27 @@ mod tests {
         assert_eq!(block, None);
     }
 
+    #[tokio::test]
+    async fn get_logs_test() {
+        let node = ExecutionNode::connect().await;
+        let filter = LogFilter {
+            // The beacon chain deposit contract.
+            address: Some("0x00000000219ab540356cbb839cbe05303d7705fa".to_string()),
+            topics: vec![],
+        };
+        let block_range = BlockRange::new(
+            BlockNumber(17_000_000),
+            BlockNumber(17_000_000 + LOGS_CHUNK_SIZE * 2),
+        );
+
+        let logs = node.get_logs(&filter, &block_range).await.unwrap();
+
+        assert!(!logs.is_empty());
+        assert!(logs.iter().all(
+            |log| log.block_number >= block_range.start && log.block_number <= block_range.end
+        ));
+    }
+
     #[tokio::test]
     async fn get_transaction_receipt_test() {
         let node = ExecutionNode::connect().await;
@@ -355,7 +612,7 @@ mod tests {
     async fn get_transaction_receipts_for_block_test() {
         let node = ExecutionNode::connect().await;
         let block_number = 17523391; // Replace with a valid Ethereum block number with some transactions
-        let block = node.get_block_by_number(&block_number).await;
+        let block = node.get_block_by_number(&BlockNumber(block_number)).await;
 
         assert!(block.is_some(), "Block not found");
         let block = block.unwrap();
@@ -373,7 +630,8 @@ mod tests {
                 "Mismatch in transaction hash"
             );
             assert_eq!(
-                receipt.block_number, block_number,
+                receipt.block_number,
+                BlockNumber(block_number),
                 "Mismatch in block number"
             );
         }
@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use super::decoders::from_option_u128_hex_str;
+
+/// Only what we need of a `trace_block` action, which fields are present depends on the trace
+/// type.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceAction {
+    pub address: Option<String>,
+    #[serde(default, deserialize_with = "from_option_u128_hex_str")]
+    pub balance: Option<u128>,
+    pub refund_address: Option<String>,
+}
+
+/// Only what we need of a `trace_block` result, the address of a created contract.
+#[derive(Debug, Deserialize)]
+pub struct TraceResult {
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTrace {
+    pub action: TraceAction,
+    pub result: Option<TraceResult>,
+    #[serde(rename = "type")]
+    pub trace_type: String,
+    // Block rewards and withdrawals have no transaction.
+    pub transaction_position: Option<u32>,
+}
+
+impl BlockTrace {
+    /// ETH a self-destruct took out of the supply. A contract naming itself the beneficiary
+    /// destroys its balance, any other beneficiary receives it.
+    pub fn self_destruct_burn(&self) -> Option<u128> {
+        if self.trace_type != "suicide" {
+            return None;
+        }
+
+        match (&self.action.address, &self.action.refund_address) {
+            (Some(address), Some(refund_address))
+                if address.eq_ignore_ascii_case(refund_address) =>
+            {
+                self.action.balance
+            }
+            _ => None,
+        }
+    }
+
+    /// The transaction and address of the contract this trace created, if any.
+    fn created_contract(&self) -> Option<(u32, String)> {
+        if self.trace_type != "create" {
+            return None;
+        }
+
+        let address = self.result.as_ref()?.address.as_ref()?;
+        Some((self.transaction_position?, address.to_lowercase()))
+    }
+}
+
+/// ETH the self-destructs in a block took out of the supply. From Cancun on, EIP-6780 only lets a
+/// contract destroy itself, and so its balance, in the transaction that created it.
+pub fn self_destruct_burn_sum(traces: &[BlockTrace], is_cancun: bool) -> u128 {
+    let created_contracts = traces
+        .iter()
+        .filter_map(BlockTrace::created_contract)
+        .collect::<HashSet<_>>();
+
+    traces
+        .iter()
+        .filter(|trace| {
+            !is_cancun
+                || trace
+                    .transaction_position
+                    .zip(trace.action.address.as_ref())
+                    .is_some_and(|(position, address)| {
+                        created_contracts.contains(&(position, address.to_lowercase()))
+                    })
+        })
+        .filter_map(BlockTrace::self_destruct_burn)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn self_destruct_burn_test() {
+        let traces = serde_json::from_value::<Vec<BlockTrace>>(json!([
+            {
+                "action": { "from": "0xa", "to": "0xb", "value": "0x1" },
+                "type": "call"
+            },
+            {
+                "action": { "address": "0xAb", "balance": "0x10", "refundAddress": "0xab" },
+                "type": "suicide"
+            },
+            {
+                "action": { "address": "0xc", "balance": "0x20", "refundAddress": "0xd" },
+                "type": "suicide"
+            }
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            traces
+                .iter()
+                .filter_map(BlockTrace::self_destruct_burn)
+                .collect::<Vec<_>>(),
+            vec![16]
+        );
+    }
+
+    #[test]
+    fn self_destruct_burn_sum_cancun_test() {
+        let traces = serde_json::from_value::<Vec<BlockTrace>>(json!([
+            {
+                "action": { "from": "0xa", "value": "0x0" },
+                "result": { "address": "0xB" },
+                "transactionPosition": 0,
+                "type": "create"
+            },
+            {
+                "action": { "address": "0xb", "balance": "0x10", "refundAddress": "0xb" },
+                "transactionPosition": 0,
+                "type": "suicide"
+            },
+            {
+                "action": { "address": "0xc", "balance": "0x20", "refundAddress": "0xc" },
+                "transactionPosition": 1,
+                "type": "suicide"
+            }
+        ]))
+        .unwrap();
+
+        assert_eq!(self_destruct_burn_sum(&traces, false), 48);
+        assert_eq!(self_destruct_burn_sum(&traces, true), 16);
+    }
+}
@@ -1,12 +1,20 @@
 use serde::Deserialize;
 
-use super::decoders::from_i32_hex_str;
+use super::{
+    decoders::{from_block_number_hex_str, from_i32_hex_str, from_u128_hex_str},
+    BlockNumber,
+};
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct TransactionReceipt {
-    #[serde(deserialize_with = "from_i32_hex_str")]
-    pub block_number: i32,
+    /// The contract created, only set for contract creations.
+    pub contract_address: Option<String>,
+    #[serde(deserialize_with = "from_block_number_hex_str")]
+    pub block_number: BlockNumber,
+    /// What the transaction paid per unit of gas, base fee and tip together.
+    #[serde(deserialize_with = "from_u128_hex_str")]
+    pub effective_gas_price: u128,
     // Started at 8M, currently at 30M, seems to fit in 2^31 for the foreseeable future.
     #[serde(deserialize_with = "from_i32_hex_str")]
     pub gas_used: i32,
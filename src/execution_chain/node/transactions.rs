@@ -0,0 +1,79 @@
+use serde::Deserialize;
+
+use super::decoders::{from_i128_hex_str, from_i32_hex_str};
+
+/// A transaction as included in a block, when requesting blocks with full transactions.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionNodeTransaction {
+    pub from: String,
+    // The gas limit of the transaction, not what it used.
+    #[serde(deserialize_with = "from_i32_hex_str")]
+    pub gas: i32,
+    pub hash: String,
+    pub input: String,
+    pub to: Option<String>,
+    #[serde(deserialize_with = "from_i32_hex_str")]
+    pub transaction_index: i32,
+    #[serde(rename = "type", deserialize_with = "from_i32_hex_str")]
+    pub transaction_type: i32,
+    // Transfers larger than i128::MAX wei, ~1.7e20 ETH, can't happen.
+    #[serde(deserialize_with = "from_i128_hex_str")]
+    pub value: i128,
+}
+
+impl ExecutionNodeTransaction {
+    /// The first four bytes of the input, identifying the contract function called.
+    pub fn input_selector(&self) -> Option<&str> {
+        self.input.get(..10)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BlockWithTransactions {
+    pub transactions: Vec<ExecutionNodeTransaction>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn decode_transaction_test() {
+        let transaction: ExecutionNodeTransaction = serde_json::from_value(json!({
+            "from": "0xfrom",
+            "gas": "0x5208",
+            "hash": "0xhash",
+            "input": "0xa9059cbb000000000000000000000000",
+            "to": null,
+            "transactionIndex": "0x1",
+            "type": "0x2",
+            "value": "0xde0b6b3a7640000"
+        }))
+        .unwrap();
+
+        assert_eq!(transaction.gas, 21_000);
+        assert_eq!(transaction.to, None);
+        assert_eq!(transaction.transaction_type, 2);
+        assert_eq!(transaction.value, 1_000_000_000_000_000_000);
+        assert_eq!(transaction.input_selector(), Some("0xa9059cbb"));
+    }
+
+    #[test]
+    fn transfer_has_no_input_selector_test() {
+        let transaction = ExecutionNodeTransaction {
+            from: "0xfrom".to_string(),
+            gas: 21_000,
+            hash: "0xhash".to_string(),
+            input: "0x".to_string(),
+            to: Some("0xto".to_string()),
+            transaction_index: 0,
+            transaction_type: 2,
+            value: 1,
+        };
+
+        assert_eq!(transaction.input_selector(), None);
+    }
+}
@@ -0,0 +1,304 @@
+//! # Proof-of-work issuance
+//! What miners were paid in new ETH for each block before the merge. The fixed block reward of the
+//! fork era, 1/32 of it for every uncle the block includes, and for every uncle
+//! (8 + uncle number - block number) / 8 of it to the uncle's miner. Fees were paid by users, they
+//! are not issuance.
+//!
+//! `backfill-pow-issuance` stores the issuance of each block in `pow_issuance`, by default for the
+//! last `DAILY_ISSUANCE_BLOCKS` blocks before the merge. The daily proof-of-work issuance the
+//! gauges and the proof-of-work supply since the merge use is averaged over those blocks. Until
+//! they're all stored, we fall back to an estimate.
+use std::{num::NonZeroU32, sync::OnceLock};
+
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    db, log,
+    network::NETWORK_CONSTANTS,
+    units::{EthNewtype, WeiNewtype},
+};
+
+use super::{BlockNumber, BlockRange, ExecutionNode, MERGE_BLOCK_NUMBER};
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// About a month of blocks.
+const DAILY_ISSUANCE_BLOCKS: i64 = 200_000;
+
+/// Blocks stored per transaction when backfilling.
+const BACKFILL_CHUNK_SIZE: NonZeroU32 = NonZeroU32::new(10_000).unwrap();
+
+/// Used until the daily issuance can be calculated.
+const PROOF_OF_WORK_DAILY_ISSUANCE_ESTIMATE: f64 = 13500.0;
+
+/// Only set once calculated from every block, until then we look again on every call.
+static DAILY_ISSUANCE: OnceLock<EthNewtype> = OnceLock::new();
+
+pub fn base_reward(block_number: BlockNumber) -> WeiNewtype {
+    if block_number < NETWORK_CONSTANTS.byzantium_block_number {
+        WeiNewtype::from_eth(5)
+    } else if block_number < NETWORK_CONSTANTS.constantinople_block_number {
+        WeiNewtype::from_eth(3)
+    } else {
+        WeiNewtype::from_eth(2)
+    }
+}
+
+/// What the block's miner was paid, the fixed block reward and 1/32 of it for every uncle.
+pub fn fixed_reward(block_number: BlockNumber, uncle_count: usize) -> WeiNewtype {
+    let base_reward = base_reward(block_number).0;
+    WeiNewtype(base_reward + base_reward / 32 * uncle_count as i128)
+}
+
+/// What the miners of the block's uncles were paid.
+pub fn uncles_reward(block_number: BlockNumber, uncle_numbers: &[BlockNumber]) -> WeiNewtype {
+    let base_reward = base_reward(block_number).0;
+    WeiNewtype(
+        uncle_numbers
+            .iter()
+            .map(|uncle_number| (8 + (*uncle_number - block_number)) as i128 * base_reward / 8)
+            .sum::<i128>(),
+    )
+}
+
+pub fn block_issuance(block_number: BlockNumber, uncle_numbers: &[BlockNumber]) -> WeiNewtype {
+    fixed_reward(block_number, uncle_numbers.len()) + uncles_reward(block_number, uncle_numbers)
+}
+
+async fn store_block_issuance(
+    executor: impl PgExecutor<'_>,
+    block_number: BlockNumber,
+    timestamp: DateTime<Utc>,
+    issuance: WeiNewtype,
+) {
+    sqlx::query!(
+        "
+        INSERT INTO pow_issuance (block_number, timestamp, issuance)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (block_number) DO UPDATE SET
+            timestamp = excluded.timestamp,
+            issuance = excluded.issuance
+        ",
+        block_number.0,
+        timestamp,
+        issuance as WeiNewtype
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Issuance of the blocks in the time range, excluding the end.
+pub async fn get_pow_issuance_between(
+    executor: impl PgExecutor<'_>,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> WeiNewtype {
+    sqlx::query!(
+        r#"
+        SELECT SUM(issuance) AS "issuance: WeiNewtype"
+        FROM pow_issuance
+        WHERE timestamp >= $1
+        AND timestamp < $2
+        "#,
+        start,
+        end
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .issuance
+    .unwrap_or(WeiNewtype(0))
+}
+
+struct IssuanceWindow {
+    block_count: i64,
+    issuance: Option<WeiNewtype>,
+    first_timestamp: Option<DateTime<Utc>>,
+    last_timestamp: Option<DateTime<Utc>>,
+}
+
+/// `None` until every block of the last `DAILY_ISSUANCE_BLOCKS` is stored.
+async fn calc_daily_issuance(executor: impl PgExecutor<'_>) -> Option<EthNewtype> {
+    let window = sqlx::query_as!(
+        IssuanceWindow,
+        r#"
+        SELECT
+            COUNT(*) AS "block_count!",
+            SUM(issuance) AS "issuance: WeiNewtype",
+            MIN(timestamp) AS first_timestamp,
+            MAX(timestamp) AS last_timestamp
+        FROM pow_issuance
+        WHERE block_number >= $1
+        AND block_number < $2
+        "#,
+        (*MERGE_BLOCK_NUMBER - DAILY_ISSUANCE_BLOCKS).0,
+        MERGE_BLOCK_NUMBER.0
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap();
+
+    if window.block_count != DAILY_ISSUANCE_BLOCKS {
+        return None;
+    }
+
+    let issuance: EthNewtype = window.issuance?.into();
+    let days =
+        (window.last_timestamp? - window.first_timestamp?).num_seconds() as f64 / SECONDS_PER_DAY;
+    Some(EthNewtype(issuance.0 / days))
+}
+
+/// Average proof-of-work issuance per day over the last `DAILY_ISSUANCE_BLOCKS` before the merge.
+/// The estimate until those are stored.
+pub async fn get_daily_pow_issuance(executor: impl PgExecutor<'_>) -> EthNewtype {
+    if let Some(daily_issuance) = DAILY_ISSUANCE.get() {
+        return *daily_issuance;
+    }
+
+    match calc_daily_issuance(executor).await {
+        Some(daily_issuance) => *DAILY_ISSUANCE.get_or_init(|| daily_issuance),
+        None => {
+            debug!("proof-of-work issuance not backfilled, using the estimate");
+            EthNewtype(PROOF_OF_WORK_DAILY_ISSUANCE_ESTIMATE)
+        }
+    }
+}
+
+pub async fn backfill_pow_issuance() {
+    let from = std::env::args()
+        .skip(1)
+        .find_map(|str| str.parse::<BlockNumber>().ok());
+    backfill_pow_issuance_from(from).await;
+}
+
+/// About a month of blocks before the merge when `from` is `None`.
+pub async fn backfill_pow_issuance_from(from: Option<BlockNumber>) {
+    log::init_with_env();
+
+    let from = from
+        .unwrap_or(*MERGE_BLOCK_NUMBER - DAILY_ISSUANCE_BLOCKS)
+        // Genesis has no block reward.
+        .max(BlockNumber(1));
+
+    let db_pool = db::get_db_pool("backfill-pow-issuance").await;
+    let execution_node = ExecutionNode::connect().await;
+
+    // Picks up where a previous run stopped.
+    let last_stored = sqlx::query!(
+        "SELECT MAX(block_number) AS block_number FROM pow_issuance WHERE block_number >= $1",
+        from.0
+    )
+    .fetch_one(&db_pool)
+    .await
+    .unwrap()
+    .block_number
+    .map(BlockNumber);
+    let start = last_stored.map_or(from, |last_stored| last_stored + 1);
+
+    info!(
+        %start,
+        end = %(*MERGE_BLOCK_NUMBER - 1),
+        "backfilling proof-of-work issuance"
+    );
+
+    if start >= *MERGE_BLOCK_NUMBER {
+        info!("proof-of-work issuance up to date");
+        return;
+    }
+
+    let block_range = BlockRange::new(start, *MERGE_BLOCK_NUMBER - 1);
+    'chunks: for chunk in block_range.chunks(BACKFILL_CHUNK_SIZE) {
+        let mut transaction = db_pool.begin().await.unwrap();
+
+        for block_number in chunk.clone() {
+            let block = execution_node.get_block_by_number(&block_number).await;
+            let uncle_numbers = match execution_node.get_uncle_numbers(&block_number).await {
+                Ok(uncle_numbers) => uncle_numbers,
+                Err(err) => {
+                    error!(%block_number, ?err, "failed to get uncles, stopping");
+                    transaction.commit().await.unwrap();
+                    break 'chunks;
+                }
+            };
+            let (block, uncle_numbers) = match (block, uncle_numbers) {
+                (Some(block), Some(uncle_numbers)) => (block, uncle_numbers),
+                _ => {
+                    warn!(%block_number, "node is missing block, stopping");
+                    transaction.commit().await.unwrap();
+                    break 'chunks;
+                }
+            };
+
+            let issuance = block_issuance(block_number, &uncle_numbers);
+            store_block_issuance(&mut *transaction, block_number, block.timestamp, issuance).await;
+        }
+
+        transaction.commit().await.unwrap();
+
+        info!(
+            block_number = %chunk.end,
+            "backfilled proof-of-work issuance"
+        );
+    }
+
+    info!("done backfilling proof-of-work issuance");
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use test_context::test_context;
+
+    use crate::db::tests::TestDb;
+
+    use super::*;
+
+    #[test]
+    fn base_reward_test() {
+        assert_eq!(base_reward(BlockNumber(1)), WeiNewtype::from_eth(5));
+        assert_eq!(
+            base_reward(NETWORK_CONSTANTS.byzantium_block_number),
+            WeiNewtype::from_eth(3)
+        );
+        assert_eq!(
+            base_reward(*MERGE_BLOCK_NUMBER - 1),
+            WeiNewtype::from_eth(2)
+        );
+    }
+
+    #[test]
+    fn block_issuance_test() {
+        let block_number = *MERGE_BLOCK_NUMBER - 1;
+        assert_eq!(block_issuance(block_number, &[]), WeiNewtype::from_eth(2));
+
+        // 2 ETH, 2 / 32 ETH nephew reward, and 7 / 8 of 2 ETH for an uncle one block back.
+        assert_eq!(
+            block_issuance(block_number, &[block_number - 1]),
+            WeiNewtype(3_812_500_000_000_000_000)
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn get_pow_issuance_between_test(test_db: &TestDb) {
+        let start = Utc.with_ymd_and_hms(2022, 9, 1, 0, 0, 0).unwrap();
+        for block_number in 0..3 {
+            store_block_issuance(
+                &test_db.pool,
+                BlockNumber(block_number),
+                start + Duration::seconds(13 * block_number as i64),
+                WeiNewtype::from_eth(2),
+            )
+            .await;
+        }
+
+        assert_eq!(
+            get_pow_issuance_between(&test_db.pool, &start, &(start + Duration::seconds(26))).await,
+            WeiNewtype::from_eth(4)
+        );
+        assert!(calc_daily_issuance(&test_db.pool).await.is_none());
+    }
+}
@@ -0,0 +1,168 @@
+//! # Receipt burn
+//! Opt-in, see `check_receipt_burn` in the config. We derive burn from the gas used and base fee
+//! of the block. As a cross-check, we recompute it from the receipts of the block's transactions,
+//! the gas each used times the base fee, and flag blocks where the two differ. A mismatch points
+//! at a client, or our decoding of it, getting gas used wrong.
+//!
+//! System transactions, sent from the system address, pay no fees and are left out.
+use async_trait::async_trait;
+use sqlx::{PgConnection, PgExecutor};
+use tracing::warn;
+
+use crate::{analysis::Analysis, rollback::RollbackPoint, units::WeiNewtype};
+
+use super::{BlockNumber, BlockTransaction, ExecutionNodeBlock};
+
+const SYSTEM_ADDRESS: &str = "0xfffffffffffffffffffffffffffffffffffffffe";
+
+/// Base fee burn, blob fees aren't in the receipt gas used.
+fn block_burn(block: &ExecutionNodeBlock) -> WeiNewtype {
+    WeiNewtype(block.base_fee_per_gas as i128 * block.gas_used as i128)
+}
+
+fn receipt_burn(block: &ExecutionNodeBlock, transactions: &[BlockTransaction]) -> WeiNewtype {
+    let gas_used = transactions
+        .iter()
+        .filter(|transaction| !transaction.from.eq_ignore_ascii_case(SYSTEM_ADDRESS))
+        .map(|transaction| transaction.gas_used as i128)
+        .sum::<i128>();
+    WeiNewtype(block.base_fee_per_gas as i128 * gas_used)
+}
+
+/// Stores the block as a mismatch when its burn and the burn of its receipts differ.
+pub async fn check_receipt_burn(
+    executor: impl PgExecutor<'_>,
+    block: &ExecutionNodeBlock,
+    transactions: &[BlockTransaction],
+) {
+    let block_burn = block_burn(block);
+    let receipt_burn = receipt_burn(block, transactions);
+
+    if block_burn == receipt_burn {
+        return;
+    }
+
+    warn!(
+        block_number = %block.number,
+        %block_burn,
+        %receipt_burn,
+        "block burn doesn't match the burn of its receipts"
+    );
+
+    sqlx::query!(
+        "
+        INSERT INTO burn_mismatches (
+            block_number,
+            block_hash,
+            timestamp,
+            block_burn,
+            receipt_burn
+        )
+        VALUES ($1, $2, $3, $4, $5)
+        ",
+        block.number.0,
+        block.hash,
+        block.timestamp,
+        block_burn as WeiNewtype,
+        receipt_burn as WeiNewtype,
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+async fn delete_burn_mismatches(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: &BlockNumber,
+) {
+    sqlx::query!(
+        "DELETE FROM burn_mismatches WHERE block_number >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub struct BurnMismatchesRollback;
+
+#[async_trait]
+impl Analysis for BurnMismatchesRollback {
+    fn name(&self) -> &'static str {
+        "burn_mismatches"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            delete_burn_mismatches(transaction, block_number_gte).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{BlockTransactionBuilder, ExecutionNodeBlockBuilder},
+    };
+
+    use super::*;
+
+    fn test_transaction(from: &str, gas_used: i32) -> BlockTransaction {
+        BlockTransactionBuilder::new("receipt_burn", 0)
+            .with_from(from)
+            .with_gas_limit(gas_used)
+            .with_gas_used(gas_used)
+            .build()
+    }
+
+    async fn mismatch_count(executor: impl PgExecutor<'_>) -> i64 {
+        sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM burn_mismatches"#)
+            .fetch_one(executor)
+            .await
+            .unwrap()
+            .count
+    }
+
+    #[test]
+    fn receipt_burn_test() {
+        let block = ExecutionNodeBlockBuilder::new("receipt_burn")
+            .with_base_fee_per_gas(10)
+            .with_gas_used(42_000)
+            .build();
+        let transactions = vec![
+            test_transaction("0xfrom", 21_000),
+            test_transaction("0xfrom", 21_000),
+            test_transaction(SYSTEM_ADDRESS, 30_000_000),
+        ];
+
+        assert_eq!(receipt_burn(&block, &transactions), WeiNewtype(420_000));
+        assert_eq!(receipt_burn(&block, &transactions), block_burn(&block));
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn check_receipt_burn_test(test_db: &TestDb) {
+        let block = ExecutionNodeBlockBuilder::new("check_receipt_burn")
+            .with_base_fee_per_gas(10)
+            .with_gas_used(42_000)
+            .build();
+
+        check_receipt_burn(&test_db.pool, &block, &[test_transaction("0xfrom", 42_000)]).await;
+        assert_eq!(mismatch_count(&test_db.pool).await, 0);
+
+        check_receipt_burn(&test_db.pool, &block, &[test_transaction("0xfrom", 21_000)]).await;
+        assert_eq!(mismatch_count(&test_db.pool).await, 1);
+
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        BurnMismatchesRollback
+            .on_rollback(
+                &mut connection,
+                &RollbackPoint::BlockNumbersGte(block.number),
+            )
+            .await;
+        assert_eq!(mismatch_count(&mut *connection).await, 0);
+    }
+}
@@ -1,13 +1,20 @@
+mod backfill;
 mod export;
 mod logs;
 mod node;
 pub mod snapshot;
 mod sync;
+mod traces;
+mod verify;
+
+pub use backfill::backfill_supply_deltas;
 
 pub use export::export_deltas;
+pub use export::export_deltas_with_format;
 pub use export::summary_from_deltas_csv;
 
 pub use logs::write_deltas_log;
+pub use logs::write_deltas_log_with_format;
 
 pub use node::stream_supply_delta_chunks;
 pub use node::stream_supply_deltas_from;
@@ -15,11 +22,24 @@ pub use node::stream_supply_deltas_from;
 pub use sync::add_delta;
 pub use sync::sync_deltas;
 
+pub use verify::verify_execution_supply;
+pub use verify::verify_execution_supply_with_range_size;
+
 use serde::Serialize;
 
-use crate::units::Wei;
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+
+#[cfg(feature = "parquet")]
+use arrow::{
+    array::{ArrayRef, Decimal128Array, Int64Array, StringArray},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
 
-use super::node::BlockNumber;
+use crate::{row_writer::ExportRow, units::Wei};
+
+use super::{node::BlockNumber, GENESIS_BLOCK_HASH, GENESIS_SUPPLY};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct SupplyDelta {
@@ -32,3 +52,67 @@ pub struct SupplyDelta {
     pub supply_delta: Wei,
     pub uncles_reward: Wei,
 }
+
+impl ExportRow for SupplyDelta {
+    #[cfg(feature = "parquet")]
+    fn schema() -> SchemaRef {
+        let wei = DataType::Decimal128(38, 0);
+        Arc::new(Schema::new(vec![
+            Field::new("block_hash", DataType::Utf8, false),
+            Field::new("block_number", DataType::Int64, false),
+            Field::new("fee_burn", wei.clone(), false),
+            Field::new("fixed_reward", wei.clone(), false),
+            Field::new("parent_hash", DataType::Utf8, false),
+            Field::new("self_destruct", wei.clone(), false),
+            Field::new("supply_delta", wei.clone(), false),
+            Field::new("uncles_reward", wei, false),
+        ]))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn to_record_batch(rows: &[Self]) -> RecordBatch {
+        let wei_column = |wei: fn(&Self) -> Wei| -> ArrayRef {
+            Arc::new(
+                Decimal128Array::from_iter_values(rows.iter().map(wei))
+                    .with_precision_and_scale(38, 0)
+                    .unwrap(),
+            )
+        };
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.block_hash),
+                )) as ArrayRef,
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|row| row.block_number.0),
+                )),
+                wei_column(|row| row.fee_burn),
+                wei_column(|row| row.fixed_reward),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.parent_hash),
+                )),
+                wei_column(|row| row.self_destruct),
+                wei_column(|row| row.supply_delta),
+                wei_column(|row| row.uncles_reward),
+            ],
+        )
+        .unwrap()
+    }
+}
+
+/// The genesis allocation as the delta of block 0. `None` when the network's genesis supply isn't
+/// known.
+fn make_genesis_delta() -> Option<SupplyDelta> {
+    GENESIS_SUPPLY.map(|genesis_supply| SupplyDelta {
+        block_hash: GENESIS_BLOCK_HASH.clone(),
+        block_number: BlockNumber::GENESIS,
+        fee_burn: 0,
+        fixed_reward: 0,
+        parent_hash: sync::GENESIS_PARENT_HASH.to_string(),
+        self_destruct: 0,
+        supply_delta: genesis_supply.0,
+        uncles_reward: 0,
+    })
+}
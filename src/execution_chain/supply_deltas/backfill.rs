@@ -0,0 +1,210 @@
+//! Backfills supply deltas from genesis up to the snapshot the sync starts from, so
+//! `execution_supply` reaches back to block 0. The genesis allocation is stored as the delta of
+//! block 0, every balances sum after it is its parent's plus the block's delta, as in the sync.
+//!
+//! A restart picks up after the last delta stored at or before the snapshot. The checkpoint of
+//! the sync is left alone, it keeps resuming after the snapshot.
+use futures::{future, StreamExt};
+use sqlx::{Connection, PgConnection, PgExecutor};
+use tracing::{debug, info, warn};
+
+use crate::{
+    db,
+    execution_chain::BlockNumber,
+    log,
+    network::NETWORK_CONSTANTS,
+    units::{Wei, WeiNewtype},
+};
+
+use super::{
+    make_genesis_delta, node::stream_supply_deltas_from, snapshot::SupplySnapshot, sync,
+    SupplyDelta,
+};
+
+const BATCH_SIZE: usize = 1000;
+
+/// `backfill-execution-supply` may have stored some of these balances sums already.
+async fn store_backfilled_delta(
+    connection: &mut PgConnection,
+    supply_delta: &SupplyDelta,
+    balances_sum: Wei,
+) {
+    sync::store_delta(&mut *connection, supply_delta).await;
+
+    sqlx::query!(
+        "
+        INSERT INTO execution_supply (block_hash, block_number, balances_sum)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (block_hash) DO NOTHING
+        ",
+        supply_delta.block_hash,
+        supply_delta.block_number.0,
+        WeiNewtype(balances_sum) as WeiNewtype
+    )
+    .execute(&mut *connection)
+    .await
+    .unwrap();
+}
+
+async fn store_genesis(connection: &mut PgConnection) {
+    if sync::get_is_block_number_known(&mut *connection, &BlockNumber::GENESIS).await {
+        debug!("genesis allocation already stored");
+        return;
+    }
+
+    let genesis_delta = make_genesis_delta()
+        .expect("expect genesis supply to be known, set genesis_supply under [network_overrides]");
+    store_backfilled_delta(connection, &genesis_delta, genesis_delta.supply_delta).await;
+    info!(
+        balances_sum = genesis_delta.supply_delta,
+        "stored genesis allocation"
+    );
+}
+
+/// Hash, number and balances sum of the last delta stored at or before the snapshot.
+async fn get_last_backfilled(
+    executor: impl PgExecutor<'_>,
+    snapshot: &SupplySnapshot,
+) -> (String, BlockNumber, Wei) {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            execution_supply_deltas.block_hash,
+            execution_supply_deltas.block_number,
+            execution_supply.balances_sum AS "balances_sum: WeiNewtype"
+        FROM execution_supply_deltas
+        JOIN execution_supply ON execution_supply.block_hash = execution_supply_deltas.block_hash
+        WHERE execution_supply_deltas.block_number <= $1
+        ORDER BY execution_supply_deltas.block_number DESC
+        LIMIT 1
+        "#,
+        snapshot.block_number.0
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap();
+
+    (
+        row.block_hash,
+        BlockNumber(row.block_number),
+        row.balances_sum.0,
+    )
+}
+
+pub async fn backfill_supply_deltas() {
+    log::init_with_env();
+
+    let Some(snapshot) = NETWORK_CONSTANTS.supply_snapshot else {
+        info!("no supply snapshot for this network, the sync starts from genesis");
+        return;
+    };
+
+    let mut connection = PgConnection::connect(&db::get_db_url_with_name("backfill-supply-deltas"))
+        .await
+        .unwrap();
+
+    store_genesis(&mut connection).await;
+
+    let (mut last_hash, last_number, mut balances_sum) =
+        get_last_backfilled(&mut connection, snapshot).await;
+
+    info!(
+        start = %(last_number + 1),
+        end = %snapshot.block_number,
+        "backfilling supply deltas"
+    );
+
+    let mut batches = stream_supply_deltas_from(last_number + 1)
+        .take_while(|supply_delta| {
+            future::ready(supply_delta.block_number <= snapshot.block_number)
+        })
+        .chunks(BATCH_SIZE);
+
+    while let Some(supply_deltas) = batches.next().await {
+        let mut transaction = connection.begin().await.unwrap();
+
+        for supply_delta in supply_deltas.iter() {
+            // Blocks this old don't reorg, a different parent means our deltas disagree with the
+            // node.
+            if supply_delta.parent_hash != last_hash {
+                panic!(
+                    "supply delta {} has parent {}, expected {}",
+                    supply_delta.block_number, supply_delta.parent_hash, last_hash
+                );
+            }
+
+            balances_sum += supply_delta.supply_delta;
+            store_backfilled_delta(&mut transaction, supply_delta, balances_sum).await;
+            last_hash = supply_delta.block_hash.clone();
+        }
+
+        transaction.commit().await.unwrap();
+
+        if let Some(supply_delta) = supply_deltas.last() {
+            info!(
+                block_number = %supply_delta.block_number,
+                balances_sum, "backfilled supply deltas"
+            );
+        }
+    }
+
+    if last_hash != snapshot.block_hash {
+        warn!(
+            last_hash,
+            "stream ended before reaching the snapshot, run again to continue"
+        );
+    } else if balances_sum != snapshot.balances_sum {
+        warn!(
+            balances_sum,
+            snapshot_balances_sum = snapshot.balances_sum,
+            "backfilled balances sum does not match the snapshot"
+        );
+    } else {
+        info!("done backfilling supply deltas, balances sum matches the snapshot");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution_chain::{
+        supply_deltas::snapshot::SUPPLY_SNAPSHOT_15082718, GENESIS_BLOCK_HASH, GENESIS_SUPPLY,
+    };
+
+    #[tokio::test]
+    async fn backfill_from_genesis_test() {
+        let genesis_supply = GENESIS_SUPPLY.unwrap().0;
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        store_genesis(&mut transaction).await;
+        // Stored once.
+        store_genesis(&mut transaction).await;
+
+        assert_eq!(
+            get_last_backfilled(&mut *transaction, &SUPPLY_SNAPSHOT_15082718).await,
+            (
+                GENESIS_BLOCK_HASH.to_string(),
+                BlockNumber::GENESIS,
+                genesis_supply
+            )
+        );
+
+        let block_1 = SupplyDelta {
+            block_hash: "0xblock1".to_string(),
+            block_number: BlockNumber(1),
+            fee_burn: 0,
+            fixed_reward: 5,
+            parent_hash: GENESIS_BLOCK_HASH.clone(),
+            self_destruct: 0,
+            supply_delta: 5,
+            uncles_reward: 0,
+        };
+        store_backfilled_delta(&mut transaction, &block_1, genesis_supply + 5).await;
+
+        assert_eq!(
+            get_last_backfilled(&mut *transaction, &SUPPLY_SNAPSHOT_15082718).await,
+            ("0xblock1".to_string(), BlockNumber(1), genesis_supply + 5)
+        );
+    }
+}
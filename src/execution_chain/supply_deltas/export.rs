@@ -1,41 +1,43 @@
-use futures::prelude::*;
 use serde::Deserialize;
-use tracing::{debug, info};
+use tracing::info;
 
-use crate::{execution_chain::supply_deltas, log};
-
-const SUPPLY_DELTA_BUFFER_SIZE: usize = 10_000;
+use crate::{
+    burn_sums::BurnSumRange,
+    db,
+    execution_chain::{BlockNumber, BlockRange},
+    export::{self, Dataset},
+    log,
+    row_writer::ExportFormat,
+};
 
 pub async fn export_deltas() {
+    export_deltas_with_format(ExportFormat::from_args()).await;
+}
+
+/// Writes every stored supply delta, the same as exporting the `execution_supply_deltas` dataset
+/// for all blocks.
+pub async fn export_deltas_with_format(format: ExportFormat) {
     log::init_with_env();
 
     let timestamp = crate::time::get_timestamp();
 
-    info!("writing supply deltas {timestamp}");
-
-    let mut supply_deltas_rx =
-        supply_deltas::stream_supply_delta_chunks(0, SUPPLY_DELTA_BUFFER_SIZE);
-
-    let mut progress = pit_wall::Progress::new("write supply deltas", 15_000_000);
+    info!(%format, "writing supply deltas {timestamp}");
 
-    let file_path = format!("supply_deltas_{timestamp}.csv");
+    let db_pool = db::get_read_db_pool("export-execution-supply-deltas").await;
 
-    let mut csv_writer = csv::Writer::from_path(&file_path).unwrap();
-
-    while let Some(supply_deltas) = supply_deltas_rx.next().await {
-        let len = supply_deltas.len();
-
-        for supply_delta in supply_deltas {
-            csv_writer.serialize(supply_delta).unwrap();
-        }
-
-        progress.inc_work_done_by(len.try_into().unwrap());
-        debug!("{}", progress.get_progress_string());
-    }
+    let file_path = format!("supply_deltas_{timestamp}.{}", format.extension());
+    let range = BurnSumRange::Blocks(BlockRange::new(BlockNumber::GENESIS, BlockNumber::MAX));
+    let count = export::export_dataset(
+        &db_pool,
+        Dataset::ExecutionSupplyDeltas,
+        &range,
+        &file_path,
+        format,
+    )
+    .await
+    .unwrap();
 
-    // A CSV writer maintains an internal buffer, so it's important
-    // to flush the buffer when you're done.
-    csv_writer.flush().unwrap();
+    info!(file_path, count, "done writing supply deltas");
 }
 
 const STOP_AT_BLOCK_NUMBER: u32 = 11214496;
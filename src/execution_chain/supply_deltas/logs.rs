@@ -1,10 +1,20 @@
 use crate::{
     execution_chain::{supply_deltas, BlockNumber, ExecutionNode},
     log,
+    row_writer::{ExportFormat, ExportRow, RowWriter},
+    shutdown::ShutdownSignal,
+};
+#[cfg(feature = "parquet")]
+use arrow::{
+    array::{ArrayRef, BooleanArray, Int64Array, StringArray},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
 };
 use futures::StreamExt;
 use serde::Serialize;
-use std::collections::HashSet;
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+use std::{collections::HashSet, fs::File};
 use tracing::{debug, info};
 
 #[derive(Serialize)]
@@ -17,21 +27,75 @@ struct SupplyDeltaLog {
     received_at: String,
 }
 
+impl ExportRow for SupplyDeltaLog {
+    #[cfg(feature = "parquet")]
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("block_number", DataType::Int64, false),
+            Field::new("block_hash", DataType::Utf8, false),
+            Field::new("is_duplicate_number", DataType::Boolean, false),
+            Field::new("is_jumping_ahead", DataType::Boolean, false),
+            Field::new("parent_hash", DataType::Utf8, false),
+            Field::new("received_at", DataType::Utf8, false),
+        ]))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn to_record_batch(rows: &[Self]) -> RecordBatch {
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|row| row.block_number.0),
+                )) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.block_hash),
+                )),
+                Arc::new(BooleanArray::from(
+                    rows.iter()
+                        .map(|row| row.is_duplicate_number)
+                        .collect::<Vec<_>>(),
+                )),
+                Arc::new(BooleanArray::from(
+                    rows.iter()
+                        .map(|row| row.is_jumping_ahead)
+                        .collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.parent_hash),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.received_at),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+}
+
 pub async fn write_deltas_log() {
+    write_deltas_log_with_format(ExportFormat::from_args()).await;
+}
+
+pub async fn write_deltas_log_with_format(format: ExportFormat) {
     log::init_with_env();
 
     let timestamp = chrono::offset::Utc::now().timestamp();
 
-    info!("writing supply delta log {timestamp}");
+    info!(%format, "writing supply delta log {timestamp}");
+
+    let shutdown_signal = ShutdownSignal::listen();
 
     let execution_node = ExecutionNode::connect().await;
     let latest_block = execution_node.get_latest_block().await;
 
-    let mut supply_deltas_stream = supply_deltas::stream_supply_deltas_from(latest_block.number);
+    let mut supply_deltas_stream = shutdown_signal.stop_stream(
+        supply_deltas::stream_supply_deltas_from(latest_block.number),
+    );
 
-    let file_path = format!("supply_deltas_log_{timestamp}.csv");
+    let file_path = format!("supply_deltas_log_{timestamp}.{}", format.extension());
 
-    let mut csv_writer = csv::Writer::from_path(&file_path).unwrap();
+    let mut row_writer = RowWriter::create(File::create(&file_path).unwrap(), format);
 
     let mut seen_block_heights = HashSet::<BlockNumber>::new();
     let mut seen_block_hashes = HashSet::<String>::new();
@@ -53,9 +117,12 @@ pub async fn write_deltas_log() {
             received_at: chrono::offset::Utc::now().to_rfc3339(),
         };
 
-        csv_writer.serialize(supply_delta_log).unwrap();
-        csv_writer.flush().unwrap();
+        // Deltas arrive one block at a time, flush each so the log can be followed as it grows.
+        row_writer.write(supply_delta_log);
+        row_writer.flush();
 
         debug!("wrote supply delta log {}", supply_delta.block_number);
     }
+
+    row_writer.finish();
 }
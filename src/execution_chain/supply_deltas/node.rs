@@ -5,21 +5,18 @@ use futures::{channel::mpsc::UnboundedReceiver, SinkExt, Stream, StreamExt};
 use lazy_static::lazy_static;
 use serde::Deserialize;
 use serde_json::json;
-use sqlx::PgConnection;
 use thiserror::Error;
 use tokio::time::timeout;
 
 use crate::{
-    env,
+    config,
     execution_chain::{BlockNumber, SupplyDelta},
     units::Wei,
 };
 
-use super::sync;
-
 lazy_static! {
     // TODO: set to special GETH_DELTA_FORK_URL
-    static ref EXECUTION_URL: String = env::get_env_var_unsafe("GETH_URL");
+    static ref EXECUTION_URL: String = config::CONFIG.geth_url().to_string();
 }
 
 // We started running supply delta analyzation with Geth months ago (V1). Since we started running this
@@ -134,6 +131,32 @@ enum SubscriptionConfirmation {
     Error { error: SubscriptionError },
 }
 
+/// Whether the node offers the supply delta subscription of our geth fork. Asks for it, rather
+/// than going by the client name, as a stock geth reports the same name.
+pub async fn get_has_supply_delta_subscription(block_number: BlockNumber) -> bool {
+    let url = (*EXECUTION_URL).to_string();
+    let mut ws = connect_async(&url).await.unwrap().0;
+
+    ws.send(Message::text(make_supply_delta_subscribe_message(
+        &block_number,
+    )))
+    .await
+    .unwrap();
+
+    let confirmation = ws
+        .next()
+        .await
+        .and_then(|message| message.ok())
+        .and_then(|message| message.into_text().ok())
+        .and_then(|text| serde_json::from_str::<SubscriptionConfirmation>(&text).ok());
+
+    // Dropping the connection ends the subscription again.
+    matches!(
+        confirmation,
+        Some(SubscriptionConfirmation::Confirmation { .. })
+    )
+}
+
 pub fn stream_supply_deltas_from(
     greater_than_or_equal_to: BlockNumber,
 ) -> impl Stream<Item = SupplyDelta> {
@@ -187,22 +210,16 @@ pub fn stream_supply_deltas_from(
                     .map(|message| message.into())
                     .unwrap()
             };
-            tx.send(supply_delta).await.unwrap();
+            // The receiver may stop listening, e.g. after taking the deltas it needed.
+            if tx.send(supply_delta).await.is_err() {
+                break;
+            }
         }
     });
 
     rx
 }
 
-pub async fn stream_supply_deltas_from_last<'a>(
-    executor: &mut PgConnection,
-) -> impl Stream<Item = SupplyDelta> {
-    let last_synced_supply_delta_number = sync::get_last_synced_supply_delta_number(executor)
-        .await
-        .unwrap_or(super::snapshot::SUPPLY_SNAPSHOT_15082718.block_number);
-    stream_supply_deltas_from(last_synced_supply_delta_number + 1)
-}
-
 pub fn stream_supply_delta_chunks(
     from: BlockNumber,
     chunk_size: usize,
@@ -238,6 +255,10 @@ pub enum SupplyDeltaByBlockNumberError {
     Timeout,
     #[error("connection closed before receiving a supply delta")]
     Closed,
+    #[error("node does not have the block, its uncles or its traces")]
+    Unavailable,
+    #[error("failed to derive supply delta from traces: {0:#}")]
+    Traces(anyhow::Error),
 }
 
 pub async fn get_supply_delta_by_block_number(
@@ -292,14 +313,17 @@ mod tests {
     use sqlx::Acquire;
     use tokio::time::timeout;
 
-    use crate::{db, execution_chain::supply_deltas::add_delta};
+    use crate::{
+        db,
+        execution_chain::supply_deltas::{add_delta, sync},
+    };
 
     use super::*;
 
     #[ignore]
     #[tokio::test]
     async fn test_stream_supply_deltas() {
-        let stream = stream_supply_deltas_from(15_000_000);
+        let stream = stream_supply_deltas_from(BlockNumber(15_000_000));
         let ten_deltas_future = stream.take(10).collect::<Vec<_>>();
         match timeout(Duration::from_secs(4), ten_deltas_future).await {
             Err(_) => {
@@ -314,8 +338,10 @@ mod tests {
     #[ignore]
     #[tokio::test]
     async fn test_get_supply_delta_by_block_number() {
-        let supply_delta = get_supply_delta_by_block_number(15_000_000).await.unwrap();
-        assert_eq!(supply_delta.block_number, 15_000_000);
+        let supply_delta = get_supply_delta_by_block_number(BlockNumber(15_000_000))
+            .await
+            .unwrap();
+        assert_eq!(supply_delta.block_number, BlockNumber(15_000_000));
     }
 
     #[tokio::test]
@@ -325,7 +351,7 @@ mod tests {
 
         let supply_delta_test = SupplyDelta {
             supply_delta: 1,
-            block_number: 0,
+            block_number: BlockNumber(0),
             block_hash: "0xtest".to_string(),
             fee_burn: 0,
             fixed_reward: 0,
@@ -339,7 +365,7 @@ mod tests {
         let latest_synced_supply_delta_number =
             sync::get_last_synced_supply_delta_number(&mut *transaction).await;
 
-        assert_eq!(latest_synced_supply_delta_number, Some(0));
+        assert_eq!(latest_synced_supply_delta_number, Some(BlockNumber(0)));
     }
 
     #[tokio::test]
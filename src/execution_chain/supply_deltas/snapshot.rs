@@ -2,6 +2,7 @@ use crate::execution_chain::BlockNumber;
 
 use super::Wei;
 
+#[derive(Debug, PartialEq)]
 pub struct SupplySnapshot {
     #[allow(dead_code)]
     pub accounts_count: u64,
@@ -14,7 +15,7 @@ pub struct SupplySnapshot {
 pub const SUPPLY_SNAPSHOT_15082718: SupplySnapshot = SupplySnapshot {
     accounts_count: 176_496_428,
     block_hash: "0xba7baa960085d0997884135a9c0f04f6b6de53164604084be701f98a31c4124d",
-    block_number: 15_082_718,
+    block_number: BlockNumber(15_082_718),
     root: "655618..cfe0e6",
     balances_sum: 118908973575220938641041929,
 };
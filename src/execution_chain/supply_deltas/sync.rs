@@ -1,28 +1,78 @@
-use futures::StreamExt;
+//! Syncs supply deltas from our geth fork, adding each to the balances sum of its parent in
+//! `execution_supply`. The last added delta is kept as a checkpoint, written in the same
+//! transaction as the delta itself, so a restart resumes exactly where we left off. Nodes running
+//! other clients have their deltas derived from traces instead.
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use sqlx::postgres::{PgConnection, PgQueryResult, PgRow};
 use sqlx::{Connection, PgExecutor, Row};
 use std::collections::VecDeque;
-use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use super::node::{get_supply_delta_by_block_number, stream_supply_deltas_from_last};
-use super::snapshot::SUPPLY_SNAPSHOT_15082718;
+use super::node::{
+    get_has_supply_delta_subscription, get_supply_delta_by_block_number, stream_supply_deltas_from,
+    SupplyDeltaByBlockNumberError,
+};
+use super::traces::{get_supply_delta_from_traces, stream_supply_deltas_from_traces};
 use crate::execution_chain::node::BlockNumber;
+use crate::execution_chain::ExecutionNode;
+use crate::key_value_store;
+use crate::network::NETWORK_CONSTANTS;
 use crate::performance::TimedExt;
+use crate::shutdown::ShutdownSignal;
+use crate::units::WeiNewtype;
 use crate::{db, log};
 
 use super::SupplyDelta;
 
-const GENESIS_PARENT_HASH: &str =
+const SUPPLY_DELTAS_CHECKPOINT_KEY: &str = "sync-execution-supply-deltas-checkpoint";
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SupplyDeltasCheckpoint {
+    pub block_hash: String,
+    pub block_number: BlockNumber,
+}
+
+async fn get_checkpoint(executor: impl PgExecutor<'_>) -> Option<SupplyDeltasCheckpoint> {
+    key_value_store::get(executor, SUPPLY_DELTAS_CHECKPOINT_KEY)
+        .await
+        .unwrap()
+}
+
+async fn set_checkpoint(executor: impl PgExecutor<'_>, checkpoint: &SupplyDeltasCheckpoint) {
+    key_value_store::set(executor, SUPPLY_DELTAS_CHECKPOINT_KEY, checkpoint)
+        .await
+        .unwrap()
+}
+
+async fn delete_checkpoint(executor: impl PgExecutor<'_>) {
+    sqlx::query!(
+        "DELETE FROM key_value_store WHERE key = $1",
+        SUPPLY_DELTAS_CHECKPOINT_KEY
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub const GENESIS_PARENT_HASH: &str =
     "0x0000000000000000000000000000000000000000000000000000000000000000";
 
+fn is_snapshot_hash(block_hash: &str) -> bool {
+    NETWORK_CONSTANTS
+        .supply_snapshot
+        .is_some_and(|snapshot| snapshot.block_hash == block_hash)
+}
+
 async fn get_is_hash_known<'a>(executor: impl PgExecutor<'a>, block_hash: &str) -> bool {
     // Instead of the genesis parent_hash being absent, it is set to GENESIS_PARENT_HASH.
     // We'd like to have all supply deltas making only an exception for the genesis hash, but we
     // don't have all supply deltas and so have to contend with a snapshot eth supply as a
     // "jumping off point".
     if block_hash == GENESIS_PARENT_HASH
-        || block_hash == SUPPLY_SNAPSHOT_15082718.block_hash
+        || is_snapshot_hash(block_hash)
         || block_hash == "0xtestparent"
     {
         return true;
@@ -44,7 +94,7 @@ async fn get_is_hash_known<'a>(executor: impl PgExecutor<'a>, block_hash: &str)
     .exists
 }
 
-async fn get_is_block_number_known<'a>(
+pub async fn get_is_block_number_known<'a>(
     executor: impl PgExecutor<'a>,
     block_number: &BlockNumber,
 ) -> bool {
@@ -56,7 +106,7 @@ async fn get_is_block_number_known<'a>(
             WHERE block_number = $1
         ) AS \"exists!\"
         ",
-        *block_number
+        block_number.0
     )
     .fetch_one(executor)
     .await
@@ -64,7 +114,7 @@ async fn get_is_block_number_known<'a>(
     .exists
 }
 
-async fn store_delta<'a>(executor: impl PgExecutor<'a>, supply_delta: &SupplyDelta) {
+pub async fn store_delta<'a>(executor: impl PgExecutor<'a>, supply_delta: &SupplyDelta) {
     sqlx::query(
         "
         INSERT INTO execution_supply_deltas (
@@ -77,26 +127,17 @@ async fn store_delta<'a>(executor: impl PgExecutor<'a>, supply_delta: &SupplyDel
             supply_delta,
             uncles_reward
         )
-        VALUES (
-            $1,
-            $2,
-            $3::NUMERIC,
-            $4::NUMERIC,
-            $5,
-            $6::NUMERIC,
-            $7::NUMERIC,
-            $8::NUMERIC
-        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         ",
     )
     .bind(supply_delta.block_hash.clone())
     .bind(supply_delta.block_number)
-    .bind(supply_delta.fee_burn.to_string())
-    .bind(supply_delta.fixed_reward.to_string())
-    .bind(supply_delta.parent_hash.to_string())
-    .bind(supply_delta.self_destruct.to_string())
-    .bind(supply_delta.supply_delta.to_string())
-    .bind(supply_delta.uncles_reward.to_string())
+    .bind(WeiNewtype(supply_delta.fee_burn))
+    .bind(WeiNewtype(supply_delta.fixed_reward))
+    .bind(&supply_delta.parent_hash)
+    .bind(WeiNewtype(supply_delta.self_destruct))
+    .bind(WeiNewtype(supply_delta.supply_delta))
+    .bind(WeiNewtype(supply_delta.uncles_reward))
     .execute(executor)
     .await
     .unwrap();
@@ -113,12 +154,12 @@ async fn store_execution_supply(
             block_hash,
             block_number,
             balances_sum
-        ) VALUES ($1, $2, $3::NUMERIC)
+        ) VALUES ($1, $2, $3)
        ",
     )
     .bind(supply_delta.block_hash.clone())
     .bind(supply_delta.block_number)
-    .bind(balances.to_string())
+    .bind(WeiNewtype(*balances))
     .execute(executor)
     .await
 }
@@ -129,12 +170,16 @@ async fn get_balances_at_hash<'a>(executor: impl PgExecutor<'a>, block_hash: &st
     // We'd like to have all supply deltas making only an exception for the genesis hash, but we
     // don't have all supply deltas and so have to contend with a snapshot eth supply as a
     // "jumping off point".
+    // Nothing existed before the genesis allocation, the allocation is the delta of block 0.
     if block_hash == GENESIS_PARENT_HASH {
-        unimplemented!("missing hardcoded genisis parent, that is, pre-genesis balances sum");
+        return 0;
     }
 
-    if block_hash == SUPPLY_SNAPSHOT_15082718.block_hash {
-        return SUPPLY_SNAPSHOT_15082718.balances_sum;
+    if let Some(snapshot) = NETWORK_CONSTANTS
+        .supply_snapshot
+        .filter(|snapshot| snapshot.block_hash == block_hash)
+    {
+        return snapshot.balances_sum;
     }
 
     // Missing this special hash used in testing is okay.
@@ -144,15 +189,12 @@ async fn get_balances_at_hash<'a>(executor: impl PgExecutor<'a>, block_hash: &st
 
     sqlx::query(
         "
-        SELECT balances_sum::TEXT FROM execution_supply
+        SELECT balances_sum FROM execution_supply
         WHERE block_hash = $1
         ",
     )
     .bind(block_hash)
-    .map(|row: PgRow| {
-        let balances_str = row.get::<String, _>("balances_sum");
-        i128::from_str(&balances_str).unwrap()
-    })
+    .map(|row: PgRow| row.get::<WeiNewtype, _>("balances_sum").0)
     .fetch_one(executor)
     .await
     .unwrap()
@@ -179,6 +221,15 @@ pub async fn add_delta(connection: &mut PgConnection, supply_delta: &SupplyDelta
         .await
         .unwrap();
 
+    set_checkpoint(
+        &mut *transaction,
+        &SupplyDeltasCheckpoint {
+            block_hash: supply_delta.block_hash.clone(),
+            block_number: supply_delta.block_number,
+        },
+    )
+    .await;
+
     transaction.commit().await.unwrap();
 }
 
@@ -207,6 +258,28 @@ async fn drop_supply_deltas_from<'a>(executor: &mut PgConnection, gte_block_numb
     .await
     .unwrap();
 
+    // Move the checkpoint back to the last delta we kept.
+    let last_kept = sqlx::query!(
+        "
+        SELECT block_hash, block_number
+        FROM execution_supply_deltas
+        ORDER BY block_number DESC
+        LIMIT 1
+        "
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .unwrap()
+    .map(|row| SupplyDeltasCheckpoint {
+        block_hash: row.block_hash,
+        block_number: BlockNumber(row.block_number),
+    });
+
+    match last_kept {
+        Some(checkpoint) => set_checkpoint(&mut *transaction, &checkpoint).await,
+        None => delete_checkpoint(&mut *transaction).await,
+    }
+
     transaction.commit().await.unwrap();
 }
 
@@ -225,6 +298,82 @@ pub async fn get_last_synced_supply_delta_number(
     .await
     .unwrap()
     .max
+    .map(BlockNumber)
+}
+
+/// The block number to resume syncing from. Prefers the checkpoint, falls back to the last
+/// stored delta when there is none yet, or when its delta is gone, e.g. after a DB restore.
+pub async fn get_resume_block_number(connection: &mut PgConnection) -> BlockNumber {
+    match get_checkpoint(&mut *connection).await {
+        Some(checkpoint) if get_is_hash_known(&mut *connection, &checkpoint.block_hash).await => {
+            return checkpoint.block_number + 1;
+        }
+        Some(checkpoint) => tracing::warn!(
+            block_number = %checkpoint.block_number,
+            block_hash = checkpoint.block_hash,
+            "supply deltas checkpoint points to a delta we don't have, resuming from last stored delta"
+        ),
+        None => tracing::debug!("no supply deltas checkpoint, resuming from last stored delta"),
+    }
+
+    match get_last_synced_supply_delta_number(connection).await {
+        Some(block_number) => block_number + 1,
+        // Without a snapshot we sum from the genesis allocation.
+        None => NETWORK_CONSTANTS
+            .supply_snapshot
+            .map_or(BlockNumber::GENESIS, |snapshot| snapshot.block_number + 1),
+    }
+}
+
+/// Where supply deltas come from, decided by the APIs the node offers.
+enum SupplyDeltaSource {
+    GethFork,
+    Traces(ExecutionNode),
+}
+
+impl SupplyDeltaSource {
+    async fn detect() -> Self {
+        let execution_node = ExecutionNode::connect().await;
+        let latest_block_number = execution_node.get_latest_block().await.number;
+
+        if get_has_supply_delta_subscription(latest_block_number).await {
+            tracing::info!("streaming supply deltas from our geth fork");
+            return Self::GethFork;
+        }
+
+        let client = execution_node.get_client().await;
+        if client.has_trace_block() {
+            tracing::info!(?client, "deriving supply deltas from block traces");
+            Self::Traces(execution_node)
+        } else {
+            panic!("execution node runs {client:?}, which offers neither supply deltas nor traces")
+        }
+    }
+
+    fn stream_supply_deltas_from(
+        &self,
+        greater_than_or_equal_to: BlockNumber,
+    ) -> impl Stream<Item = SupplyDelta> + Unpin {
+        match self {
+            Self::GethFork => stream_supply_deltas_from(greater_than_or_equal_to).boxed(),
+            Self::Traces(_) => stream_supply_deltas_from_traces(greater_than_or_equal_to).boxed(),
+        }
+    }
+
+    async fn get_supply_delta_by_block_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> Result<SupplyDelta, SupplyDeltaByBlockNumberError> {
+        match self {
+            Self::GethFork => get_supply_delta_by_block_number(block_number).await,
+            Self::Traces(execution_node) => {
+                get_supply_delta_from_traces(execution_node, block_number)
+                    .await
+                    .map_err(SupplyDeltaByBlockNumberError::Traces)?
+                    .ok_or(SupplyDeltaByBlockNumberError::Unavailable)
+            }
+        }
+    }
 }
 
 enum NextStep {
@@ -265,16 +414,30 @@ async fn get_next_step(connection: &mut PgConnection, supply_delta: &SupplyDelta
 
 async fn sync_delta(
     connection: &mut PgConnection,
+    supply_delta_source: &SupplyDeltaSource,
     deltas_queue: DeltasQueue,
     delta_to_sync: DeltaToSync,
 ) {
     let supply_delta = match delta_to_sync {
         DeltaToSync::Fetched(supply_delta) => supply_delta,
         DeltaToSync::Refetch(supply_delta_number) => {
-            get_supply_delta_by_block_number(supply_delta_number)
+            match supply_delta_source
+                .get_supply_delta_by_block_number(supply_delta_number)
                 .timed("get supply delta by block number")
                 .await
-                .unwrap()
+            {
+                Ok(supply_delta) => supply_delta,
+                Err(err) => {
+                    // Nothing was stored, try again after a moment.
+                    tracing::warn!(%supply_delta_number, %err, "failed to refetch supply delta, retrying");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    deltas_queue
+                        .lock()
+                        .unwrap()
+                        .push_front(DeltaToSync::Refetch(supply_delta_number));
+                    return;
+                }
+            }
         }
     };
 
@@ -297,7 +460,9 @@ async fn sync_delta(
             let lowest_block_number = last_supply_delta_number.min(supply_delta.block_number);
             tracing::debug!("dropping supply deltas gte {lowest_block_number}");
             drop_supply_deltas_from(connection, &lowest_block_number).await;
-            for block_number in (lowest_block_number..=supply_delta.block_number).rev() {
+            for block_number in
+                BlockNumber::range_inclusive(lowest_block_number, supply_delta.block_number).rev()
+            {
                 tracing::debug!("queuing {block_number} for sync after dropping");
                 deltas_queue
                     .lock()
@@ -341,6 +506,8 @@ pub async fn sync_deltas() {
 
     tracing::info!("syncing supply deltas");
 
+    let shutdown_signal = ShutdownSignal::listen();
+
     let mut connection =
         PgConnection::connect(&db::get_db_url_with_name("sync-execution-supply-deltas"))
             .await
@@ -348,7 +515,13 @@ pub async fn sync_deltas() {
 
     sqlx::migrate!().run(&mut connection).await.unwrap();
 
-    let mut supply_delta_stream = stream_supply_deltas_from_last(&mut connection).await;
+    let supply_delta_source = SupplyDeltaSource::detect().await;
+
+    let resume_block_number = get_resume_block_number(&mut connection).await;
+    tracing::info!(%resume_block_number, "resuming supply deltas sync");
+
+    let mut supply_delta_stream = shutdown_signal
+        .stop_stream(supply_delta_source.stream_supply_deltas_from(resume_block_number));
 
     let deltas_queue: DeltasQueue = Arc::new(Mutex::new(VecDeque::new()));
 
@@ -360,6 +533,10 @@ pub async fn sync_deltas() {
 
         // Work through the queue until it's empty.
         loop {
+            if shutdown_signal.is_requested() {
+                break;
+            }
+
             let next_delta = { deltas_queue.lock().unwrap().pop_front() };
             match next_delta {
                 None => {
@@ -369,11 +546,20 @@ pub async fn sync_deltas() {
                 Some(delta_to_sync) => {
                     // Because we may encounter rollbacks, this step may add more deltas to sync to
                     // the front of the queue.
-                    sync_delta(&mut connection, deltas_queue.clone(), delta_to_sync).await;
+                    sync_delta(
+                        &mut connection,
+                        &supply_delta_source,
+                        deltas_queue.clone(),
+                        delta_to_sync,
+                    )
+                    .await;
                 }
             }
         }
     }
+
+    tracing::info!("stopped syncing supply deltas");
+    connection.close().await.unwrap();
 }
 
 #[cfg(test)]
@@ -397,7 +583,7 @@ mod tests {
 
         let supply_delta_test = SupplyDelta {
             supply_delta: 0,
-            block_number: 0,
+            block_number: BlockNumber(0),
             block_hash: "0xtest".to_string(),
             fee_burn: 0,
             fixed_reward: 0,
@@ -418,7 +604,8 @@ mod tests {
         let mut connection = db::tests::get_test_db_connection().await;
         let mut transaction = connection.begin().await.unwrap();
 
-        let is_block_number_known = get_is_block_number_known(&mut *transaction, &0).await;
+        let is_block_number_known =
+            get_is_block_number_known(&mut *transaction, &BlockNumber(0)).await;
 
         assert!(!is_block_number_known);
     }
@@ -430,7 +617,7 @@ mod tests {
 
         let supply_delta = SupplyDelta {
             supply_delta: 0,
-            block_number: 0,
+            block_number: BlockNumber(0),
             block_hash: "0xtest".to_string(),
             fee_burn: 0,
             fixed_reward: 0,
@@ -453,7 +640,7 @@ mod tests {
 
         let supply_delta_test = SupplyDelta {
             supply_delta: 1,
-            block_number: 0,
+            block_number: BlockNumber(0),
             block_hash: "0xtest".to_string(),
             fee_burn: 0,
             fixed_reward: 0,
@@ -476,7 +663,7 @@ mod tests {
 
         let supply_delta_test = SupplyDelta {
             supply_delta: 1,
-            block_number: 0,
+            block_number: BlockNumber(0),
             block_hash: "0xtest".to_string(),
             fee_burn: 0,
             fixed_reward: 0,
@@ -491,7 +678,57 @@ mod tests {
 
         assert_eq!(balances_sum, 1);
 
-        drop_supply_deltas_from(&mut *transaction, &0).await;
+        drop_supply_deltas_from(&mut *transaction, &BlockNumber(0)).await;
+    }
+
+    #[tokio::test]
+    async fn checkpoint_follows_added_and_dropped_deltas_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let delta_a = SupplyDelta {
+            supply_delta: 1,
+            block_number: BlockNumber(0),
+            block_hash: "0xcheckpoint_a".to_string(),
+            fee_burn: 0,
+            fixed_reward: 0,
+            parent_hash: "0xtestparent".to_string(),
+            self_destruct: 0,
+            uncles_reward: 0,
+        };
+        let delta_b = SupplyDelta {
+            block_number: BlockNumber(1),
+            block_hash: "0xcheckpoint_b".to_string(),
+            parent_hash: delta_a.block_hash.clone(),
+            ..delta_a.clone()
+        };
+
+        add_delta(&mut *transaction, &delta_a).await;
+        add_delta(&mut *transaction, &delta_b).await;
+        assert_eq!(
+            get_checkpoint(&mut *transaction).await,
+            Some(SupplyDeltasCheckpoint {
+                block_hash: delta_b.block_hash.clone(),
+                block_number: BlockNumber(1),
+            })
+        );
+        assert_eq!(
+            get_resume_block_number(&mut *transaction).await,
+            BlockNumber(2)
+        );
+
+        drop_supply_deltas_from(&mut *transaction, &BlockNumber(1)).await;
+        assert_eq!(
+            get_checkpoint(&mut *transaction).await,
+            Some(SupplyDeltasCheckpoint {
+                block_hash: delta_a.block_hash.clone(),
+                block_number: BlockNumber(0),
+            })
+        );
+        assert_eq!(
+            get_resume_block_number(&mut *transaction).await,
+            BlockNumber(1)
+        );
     }
 
     #[ignore]
@@ -506,7 +743,7 @@ mod tests {
         // is bad. Our cluster scheduler may stop trying to restart our app if this happens a lot.
         let delta_b = SupplyDelta {
             supply_delta: 0,
-            block_number: 15_082_719,
+            block_number: BlockNumber(15_082_719),
             block_hash: "0x5c47be526e24f7b58a27d5f6ad16e70df4ffc3bc3e3d3558267e92a4c4fff063"
                 .to_string(),
             fee_burn: 0,
@@ -517,7 +754,7 @@ mod tests {
         };
         let delta_b_prime = SupplyDelta {
             supply_delta: 0,
-            block_number: 15_082_719,
+            block_number: BlockNumber(15_082_719),
             block_hash: "0xB_PRIME".to_string(),
             fee_burn: 0,
             fixed_reward: 0,
@@ -527,7 +764,7 @@ mod tests {
         };
         let delta_c = SupplyDelta {
             supply_delta: 0,
-            block_number: 15_082_720,
+            block_number: BlockNumber(15_082_720),
             block_hash: "0x422fa5aed7e38dac0246e160472962fd385833c6ef0333d7c767e8729e5442c3"
                 .to_string(),
             fee_burn: 0,
@@ -546,6 +783,7 @@ mod tests {
         // Sync a delta.
         sync_delta(
             &mut *transaction,
+            &SupplyDeltaSource::GethFork,
             deltas_queue.clone(),
             DeltaToSync::Fetched(delta_b),
         )
@@ -553,6 +791,7 @@ mod tests {
         // Fork that delta.
         sync_delta(
             &mut *transaction,
+            &SupplyDeltaSource::GethFork,
             deltas_queue.clone(),
             DeltaToSync::Fetched(delta_b_prime.clone()),
         )
@@ -571,14 +810,15 @@ mod tests {
         // Now try to process C which depends on B which we've dropped.
         sync_delta(
             &mut *transaction,
+            &SupplyDeltaSource::GethFork,
             deltas_queue.clone(),
             DeltaToSync::Fetched(delta_c),
         )
         .await;
 
         let expected = VecDeque::from(vec![
-            DeltaToSync::Refetch(15_082_719),
-            DeltaToSync::Refetch(15_082_720),
+            DeltaToSync::Refetch(BlockNumber(15_082_719)),
+            DeltaToSync::Refetch(BlockNumber(15_082_720)),
         ]);
         let actual = deltas_queue.lock().unwrap().clone();
         assert_eq!(actual, expected);
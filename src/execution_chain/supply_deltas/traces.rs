@@ -0,0 +1,185 @@
+//! Derives supply deltas from standard APIs, for nodes other than our geth fork. Issuance follows
+//! from the block number and its uncles, or after Shapella its withdrawals, the burn from the
+//! block's base fee and blob fee, and ETH destroyed by self-destructs from the block's
+//! `trace_block` traces.
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use futures::{SinkExt, Stream};
+use tracing::debug;
+
+use crate::{
+    execution_chain::{
+        node::{self_destruct_burn_sum, BlockTrace},
+        pow_issuance, BlockNumber, ExecutionNode, ExecutionNodeBlock, CANCUN_HARD_FORK_TIMESTAMP,
+        MERGE_BLOCK_NUMBER, SHAPELLA_BLOCK_NUMBER,
+    },
+    units::WeiNewtype,
+};
+
+use super::{make_genesis_delta, SupplyDelta};
+
+/// How long to wait before asking again for a block the node doesn't have yet.
+const NEW_BLOCK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+fn make_supply_delta(
+    block: &ExecutionNodeBlock,
+    uncle_numbers: &[BlockNumber],
+    withdrawals: WeiNewtype,
+    traces: &[BlockTrace],
+) -> SupplyDelta {
+    let (fixed_reward, uncles_reward) = if block.number < *MERGE_BLOCK_NUMBER {
+        (
+            pow_issuance::fixed_reward(block.number, uncle_numbers.len()).0,
+            pow_issuance::uncles_reward(block.number, uncle_numbers).0,
+        )
+    } else {
+        (0, 0)
+    };
+    let fee_burn = block.burn().0;
+    let is_cancun = block.timestamp >= *CANCUN_HARD_FORK_TIMESTAMP;
+    let self_destruct = self_destruct_burn_sum(traces, is_cancun) as i128;
+
+    SupplyDelta {
+        block_hash: block.hash.clone(),
+        block_number: block.number,
+        fee_burn,
+        fixed_reward,
+        parent_hash: block.parent_hash.clone(),
+        self_destruct,
+        supply_delta: fixed_reward + uncles_reward + withdrawals.0 - fee_burn - self_destruct,
+        uncles_reward,
+    }
+}
+
+/// `None` when the node doesn't have the block, its uncles or its traces, an error when what the
+/// node returns doesn't decode. Genesis has no traces, its delta is the genesis allocation, an error
+/// when that isn't known for the network.
+pub async fn get_supply_delta_from_traces(
+    execution_node: &ExecutionNode,
+    block_number: BlockNumber,
+) -> Result<Option<SupplyDelta>> {
+    if block_number == BlockNumber::GENESIS {
+        return make_genesis_delta().map(Some).ok_or_else(|| {
+            anyhow!("no genesis supply known, set genesis_supply under [network_overrides]")
+        });
+    }
+
+    let Some(block) = execution_node.get_block_by_number(&block_number).await else {
+        return Ok(None);
+    };
+    let uncle_numbers = if block_number < *MERGE_BLOCK_NUMBER {
+        match execution_node.get_uncle_numbers(&block_number).await? {
+            Some(uncle_numbers) => uncle_numbers,
+            None => return Ok(None),
+        }
+    } else {
+        vec![]
+    };
+    let withdrawals = if block_number >= *SHAPELLA_BLOCK_NUMBER {
+        match execution_node.get_withdrawals_sum(&block_number).await? {
+            Some(withdrawals) => withdrawals,
+            None => return Ok(None),
+        }
+    } else {
+        WeiNewtype(0)
+    };
+    let Some(traces) = execution_node.trace_block(&block_number).await? else {
+        return Ok(None);
+    };
+
+    Ok(Some(make_supply_delta(
+        &block,
+        &uncle_numbers,
+        withdrawals,
+        &traces,
+    )))
+}
+
+/// Like the supply delta subscription of our geth fork, sends the delta of every block from the
+/// given number on, waiting for new blocks once caught up. Reorgs show up as a delta whose parent
+/// we don't have, which the sync handles.
+pub fn stream_supply_deltas_from_traces(
+    greater_than_or_equal_to: BlockNumber,
+) -> impl Stream<Item = SupplyDelta> {
+    debug!("deriving supply deltas from traces gte {greater_than_or_equal_to}");
+    let (mut tx, rx) = futures::channel::mpsc::unbounded();
+
+    tokio::spawn(async move {
+        let execution_node = ExecutionNode::connect().await;
+        let mut block_number = greater_than_or_equal_to;
+
+        loop {
+            match get_supply_delta_from_traces(&execution_node, block_number).await {
+                Ok(Some(supply_delta)) => {
+                    // The receiver may stop listening, e.g. on shutdown.
+                    if tx.send(supply_delta).await.is_err() {
+                        break;
+                    }
+                    block_number += 1;
+                }
+                Ok(None) => tokio::time::sleep(NEW_BLOCK_POLL_INTERVAL).await,
+                Err(err) => {
+                    tracing::error!(%block_number, "failed to derive supply delta: {err:#}");
+                    tokio::time::sleep(NEW_BLOCK_POLL_INTERVAL).await;
+                }
+            }
+        }
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{execution_chain::ExecutionNodeBlockBuilder, units::WeiNewtype};
+
+    use super::*;
+
+    #[test]
+    fn make_supply_delta_pow_test() {
+        let block = ExecutionNodeBlockBuilder::new("make_supply_delta_pow")
+            .with_number(*MERGE_BLOCK_NUMBER - 1)
+            .build();
+
+        let supply_delta =
+            make_supply_delta(&block, &[*MERGE_BLOCK_NUMBER - 2], WeiNewtype(0), &[]);
+
+        assert_eq!(
+            supply_delta.fixed_reward,
+            WeiNewtype::from_eth(2).0 + WeiNewtype::from_eth(2).0 / 32
+        );
+        assert_eq!(
+            supply_delta.uncles_reward,
+            WeiNewtype::from_eth(2).0 * 7 / 8
+        );
+        assert_eq!(
+            supply_delta.supply_delta,
+            supply_delta.fixed_reward + supply_delta.uncles_reward
+        );
+    }
+
+    #[test]
+    fn make_supply_delta_pos_test() {
+        let block = ExecutionNodeBlockBuilder::new("make_supply_delta_pos")
+            .with_number(*MERGE_BLOCK_NUMBER)
+            .with_burn(WeiNewtype::from_eth(1))
+            .build();
+        let traces = serde_json::from_value::<Vec<BlockTrace>>(json!([
+            {
+                "action": { "address": "0xa", "balance": "0x5", "refundAddress": "0xa" },
+                "type": "suicide"
+            }
+        ]))
+        .unwrap();
+
+        let supply_delta = make_supply_delta(&block, &[], WeiNewtype::from_eth(2), &traces);
+
+        assert_eq!(supply_delta.fixed_reward, 0);
+        assert_eq!(supply_delta.fee_burn, WeiNewtype::from_eth(1).0);
+        assert_eq!(supply_delta.self_destruct, 5);
+        assert_eq!(supply_delta.supply_delta, WeiNewtype::from_eth(1).0 - 5);
+    }
+}
@@ -0,0 +1,259 @@
+//! Execution balances sums are accumulated delta by delta, a single bad delta throws off every sum
+//! after it. Here we walk the stored sums in block ranges, and check how much each range moved
+//! against the deltas we stored for it. Ranges missing a sum at either end, or a delta for any of
+//! their blocks, are reported as gaps.
+use std::num::NonZeroU32;
+
+use sqlx::PgExecutor;
+use tracing::{error, info};
+
+use crate::{
+    db,
+    execution_chain::{BlockNumber, BlockRange},
+    log,
+    units::WeiNewtype,
+};
+
+const DEFAULT_RANGE_SIZE: NonZeroU32 = NonZeroU32::new(10_000).unwrap();
+
+#[derive(Debug, PartialEq, Eq)]
+struct StoredRangeSums {
+    /// The stored balances sum before the range, `None` when missing.
+    balances_sum_before: Option<WeiNewtype>,
+    /// The stored balances sum at the end of the range, `None` when missing.
+    balances_sum_end: Option<WeiNewtype>,
+    /// How many blocks in the range have a stored delta.
+    supply_deltas_count: i64,
+    /// The sum of the stored deltas in the range.
+    supply_deltas_sum: WeiNewtype,
+}
+
+async fn get_stored_range_sums(
+    executor: impl PgExecutor<'_>,
+    block_range: &BlockRange,
+) -> StoredRangeSums {
+    sqlx::query_as!(
+        StoredRangeSums,
+        r#"
+        SELECT
+            (
+                SELECT balances_sum FROM execution_supply WHERE block_number = $1::INT8 - 1
+            ) AS "balances_sum_before?: WeiNewtype",
+            (
+                SELECT balances_sum FROM execution_supply WHERE block_number = $2::INT8
+            ) AS "balances_sum_end?: WeiNewtype",
+            COUNT(DISTINCT block_number) AS "supply_deltas_count!",
+            COALESCE(SUM(supply_delta), 0) AS "supply_deltas_sum!: WeiNewtype"
+        FROM execution_supply_deltas
+        WHERE block_number >= $1 AND block_number <= $2
+        "#,
+        block_range.start.0,
+        block_range.end.0
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+}
+
+async fn get_stored_block_number_bounds(
+    executor: impl PgExecutor<'_>,
+) -> Option<(BlockNumber, BlockNumber)> {
+    let row = sqlx::query!(
+        "
+        SELECT
+            MIN(block_number) AS min_block_number,
+            MAX(block_number) AS max_block_number
+        FROM execution_supply
+        "
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap();
+
+    row.min_block_number
+        .map(BlockNumber)
+        .zip(row.max_block_number.map(BlockNumber))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RangeCheck {
+    Matches,
+    Drifts {
+        balances_sum_change: WeiNewtype,
+        drift: WeiNewtype,
+    },
+    /// A balances sum at either end of the range, or some of its deltas, are missing.
+    Gap,
+}
+
+fn check_range(block_range: &BlockRange, stored: &StoredRangeSums) -> RangeCheck {
+    let block_count = block_range.end - block_range.start + 1;
+    match (stored.balances_sum_before, stored.balances_sum_end) {
+        (Some(balances_sum_before), Some(balances_sum_end))
+            if stored.supply_deltas_count == block_count =>
+        {
+            let balances_sum_change = balances_sum_end - balances_sum_before;
+            let drift = balances_sum_change - stored.supply_deltas_sum;
+            if drift == WeiNewtype(0) {
+                RangeCheck::Matches
+            } else {
+                RangeCheck::Drifts {
+                    balances_sum_change,
+                    drift,
+                }
+            }
+        }
+        _ => RangeCheck::Gap,
+    }
+}
+
+pub async fn verify_execution_supply() {
+    verify_execution_supply_with_range_size(DEFAULT_RANGE_SIZE).await
+}
+
+pub async fn verify_execution_supply_with_range_size(range_size: NonZeroU32) {
+    log::init_with_env();
+
+    info!(range_size, "verifying execution supply");
+
+    let db_pool = db::get_db_pool("verify-execution-supply").await;
+
+    let (first, last) = match get_stored_block_number_bounds(&db_pool).await {
+        Some(bounds) => bounds,
+        None => {
+            info!("no execution supply stored, nothing to verify");
+            return;
+        }
+    };
+
+    // The first stored sum is where we start comparing from, it has no range of its own.
+    let ranges = if first < last {
+        BlockRange::new(first + 1, last)
+            .chunks(range_size)
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let mut drifting_count = 0;
+    let mut gap_count = 0;
+    for block_range in ranges.iter() {
+        let stored = get_stored_range_sums(&db_pool, block_range).await;
+
+        match check_range(block_range, &stored) {
+            RangeCheck::Matches => info!(%block_range, "execution supply matches"),
+            RangeCheck::Drifts {
+                balances_sum_change,
+                drift,
+            } => {
+                drifting_count += 1;
+                error!(
+                    %block_range,
+                    %balances_sum_change,
+                    supply_deltas_sum = %stored.supply_deltas_sum,
+                    %drift,
+                    "execution supply drifts"
+                );
+            }
+            RangeCheck::Gap => {
+                gap_count += 1;
+                error!(
+                    %block_range,
+                    has_balances_sum_before = stored.balances_sum_before.is_some(),
+                    has_balances_sum_end = stored.balances_sum_end.is_some(),
+                    supply_deltas_count = stored.supply_deltas_count,
+                    "execution supply has a gap"
+                );
+            }
+        }
+    }
+
+    info!(
+        checked_count = ranges.len(),
+        drifting_count, gap_count, "done verifying execution supply"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use crate::execution_chain::{add_delta, SupplyDelta};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn get_stored_range_sums_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let delta_0 = SupplyDelta {
+            supply_delta: 1,
+            block_number: BlockNumber(0),
+            block_hash: "0xverify_0".to_string(),
+            fee_burn: 0,
+            fixed_reward: 0,
+            parent_hash: "0xtestparent".to_string(),
+            self_destruct: 0,
+            uncles_reward: 0,
+        };
+        let delta_1 = SupplyDelta {
+            supply_delta: 2,
+            block_number: BlockNumber(1),
+            block_hash: "0xverify_1".to_string(),
+            parent_hash: delta_0.block_hash.clone(),
+            ..delta_0.clone()
+        };
+        let delta_2 = SupplyDelta {
+            supply_delta: 3,
+            block_number: BlockNumber(2),
+            block_hash: "0xverify_2".to_string(),
+            parent_hash: delta_1.block_hash.clone(),
+            ..delta_0.clone()
+        };
+        add_delta(&mut *transaction, &delta_0).await;
+        add_delta(&mut *transaction, &delta_1).await;
+        add_delta(&mut *transaction, &delta_2).await;
+
+        let block_range = BlockRange::new(BlockNumber(1), BlockNumber(2));
+        let stored = get_stored_range_sums(&mut *transaction, &block_range).await;
+
+        assert_eq!(
+            stored,
+            StoredRangeSums {
+                balances_sum_before: Some(WeiNewtype(1)),
+                balances_sum_end: Some(WeiNewtype(6)),
+                supply_deltas_count: 2,
+                supply_deltas_sum: WeiNewtype(5),
+            }
+        );
+        assert_eq!(check_range(&block_range, &stored), RangeCheck::Matches);
+
+        let block_range = BlockRange::new(BlockNumber(2), BlockNumber(3));
+        let stored = get_stored_range_sums(&mut *transaction, &block_range).await;
+        assert_eq!(check_range(&block_range, &stored), RangeCheck::Gap);
+    }
+
+    #[test]
+    fn check_range_drift_test() {
+        let stored = StoredRangeSums {
+            balances_sum_before: Some(WeiNewtype(10)),
+            balances_sum_end: Some(WeiNewtype(20)),
+            supply_deltas_count: 2,
+            supply_deltas_sum: WeiNewtype(7),
+        };
+        assert_eq!(
+            check_range(&BlockRange::new(BlockNumber(1), BlockNumber(2)), &stored),
+            RangeCheck::Drifts {
+                balances_sum_change: WeiNewtype(10),
+                drift: WeiNewtype(3),
+            }
+        );
+
+        // A delta missing in the range is a gap, not drift.
+        assert_eq!(
+            check_range(&BlockRange::new(BlockNumber(1), BlockNumber(3)), &stored),
+            RangeCheck::Gap
+        );
+    }
+}
@@ -6,37 +6,175 @@
 //! code, adding more tests, and improving designs. This side should slowly take over more
 //! responsibilities.
 
+use chrono::Duration;
 use futures::{Stream, StreamExt};
 use sqlx::PgPool;
-use std::{collections::VecDeque, iter::Iterator};
+use std::{collections::VecDeque, iter::Iterator, sync::Arc};
 use tracing::{debug, info, warn};
 
 use crate::{
-    beacon_chain::{IssuanceStore, IssuanceStorePostgres},
-    burn_rates, burn_sums, db, eth_supply,
-    execution_chain::{self, base_fees, BlockStorePostgres, ExecutionNode},
-    gauges, log,
+    analysis::{self, Analyses, NewBlockContext},
+    beacon_chain::IssuanceStorePostgres,
+    burn_records,
+    burn_sums::{self, BurnSums},
+    config, contract_deployments, db,
+    execution_chain::{
+        self, BlockStorePostgres, BlockTransaction, ExecutionNode, ExecutionNodeBlock,
+    },
+    health::{self, SyncHealth},
+    log,
+    network::NETWORK_CONSTANTS,
+    partitions::{self, BLOCKS_PER_PARTITION},
     performance::TimedExt,
-    units::EthNewtype,
-    usd_price::{self, EthPriceStore, EthPriceStorePostgres},
+    rollback::RollbackPoint,
+    shutdown::ShutdownSignal,
+    transaction_stats,
+    usd_price::{EthPriceStore, EthPriceStorePostgres},
 };
 
 use super::{BlockNumber, BlockStore, LONDON_HARD_FORK_BLOCK_HASH};
 
-async fn rollback_numbers(db_pool: &PgPool, greater_than_or_equal: &BlockNumber) {
+async fn rollback_numbers(
+    db_pool: &PgPool,
+    analyses: &Analyses,
+    greater_than_or_equal: &BlockNumber,
+) {
     debug!("rolling back data based on numbers gte {greater_than_or_equal}");
 
+    let mut connection = db_pool.acquire().await.unwrap();
+    analyses
+        .rollback(
+            &mut connection,
+            &RollbackPoint::BlockNumbersGte(*greater_than_or_equal),
+        )
+        .await
+        .unwrap();
+}
+
+/// Stores the block and updates everything maintained incrementally from it, in a single
+/// transaction. Committing it is the point at which a block counts as synced. When we crash
+/// before, nothing of the block is stored, its number is still free, and the sync loop runs it
+/// again from scratch. Everything after only derives cache values from stored data, and is safe
+/// to re-run at any time.
+async fn store_block_and_incremental_state(
+    db_pool: &PgPool,
+    block: &ExecutionNodeBlock,
+    block_transactions: Option<&[BlockTransaction]>,
+    eth_price: f64,
+    is_synced: bool,
+) -> Option<BurnSums> {
+    // Blocks only insert into an existing partition, stay one range ahead.
+    if block.number.0 % BLOCKS_PER_PARTITION == 0 {
+        partitions::ensure_partitions_from(db_pool, block.number)
+            .await
+            .unwrap();
+    }
+
     let mut transaction = db_pool.begin().await.unwrap();
 
-    burn_sums::on_rollback(&mut transaction, greater_than_or_equal).await;
-    execution_chain::delete_blocks(&mut *transaction, greater_than_or_equal).await;
+    execution_chain::store_block(&mut *transaction, block, eth_price)
+        .timed("store_block")
+        .await;
+
+    if let Some(block_transactions) = block_transactions {
+        if config::CONFIG.ingest_transactions() {
+            execution_chain::store_transactions(&mut *transaction, block, block_transactions)
+                .timed("store_transactions")
+                .await;
+            contract_deployments::store_contract_deployments(
+                &mut *transaction,
+                block,
+                block_transactions,
+            )
+            .timed("store_contract_deployments")
+            .await;
+            transaction_stats::store_transaction_stats(
+                &mut *transaction,
+                block,
+                block_transactions,
+            )
+            .timed("store_transaction_stats")
+            .await;
+        }
+
+        if config::CONFIG.check_receipt_burn() {
+            execution_chain::check_receipt_burn(&mut *transaction, block, block_transactions)
+                .timed("check_receipt_burn")
+                .await;
+        }
+    }
+
+    // Burn records are maintained incrementally, every block has to pass through them.
+    burn_records::on_new_block(&mut transaction, block)
+        .timed("burn_records::on_new_block")
+        .await;
+
+    // Burn sums pick up from the last stored sum, and so may skip blocks while we catch up.
+    let burn_sums = if is_synced {
+        let burn_sums = burn_sums::on_new_block(&mut transaction, block)
+            .timed("burn_sums::on_new_block")
+            .await;
+        Some(burn_sums)
+    } else {
+        None
+    };
 
     transaction.commit().await.unwrap();
+
+    burn_sums
+}
+
+/// Gets the price and transactions the incremental state needs for the block, then stores both,
+/// see `store_block_and_incremental_state`.
+async fn fetch_and_store_block(
+    eth_price_store: &EthPriceStorePostgres,
+    execution_node: &ExecutionNode,
+    db_pool: &PgPool,
+    block: &ExecutionNodeBlock,
+    is_synced: bool,
+) -> Option<BurnSums> {
+    let eth_price = eth_price_store
+        .get_eth_price_by_block(block)
+        .timed("get_eth_price_by_block")
+        .await
+        .expect("eth price close to block to be available");
+
+    let block_transactions =
+        if config::CONFIG.ingest_transactions() || config::CONFIG.check_receipt_burn() {
+            let block_transactions = execution_chain::get_block_transactions(execution_node, block)
+                .timed("get_block_transactions")
+                .await
+                .expect("expect transactions of the block we're syncing to be available");
+            Some(block_transactions)
+        } else {
+            None
+        };
+
+    store_block_and_incremental_state(
+        db_pool,
+        block,
+        block_transactions.as_deref(),
+        eth_price,
+        is_synced,
+    )
+    .timed("store_block_and_incremental_state")
+    .await
+}
+
+/// Stores a block the sync skipped over, the way the sync stores blocks while catching up.
+pub async fn store_missing_block(
+    db_pool: &PgPool,
+    execution_node: &ExecutionNode,
+    block: &ExecutionNodeBlock,
+) {
+    let eth_price_store = EthPriceStorePostgres::new(db_pool.clone());
+    fetch_and_store_block(&eth_price_store, execution_node, db_pool, block, false).await;
 }
 
 async fn sync_by_hash(
-    issuance_store: &impl IssuanceStore,
-    eth_price_store: &impl EthPriceStore,
+    analyses: &Analyses,
+    issuance_store: &IssuanceStorePostgres,
+    eth_price_store: &EthPriceStorePostgres,
     execution_node: &ExecutionNode,
     db_pool: &PgPool,
     hash: &str,
@@ -48,51 +186,26 @@ async fn sync_by_hash(
         // block_root the block may have disappeared. Right now we panic, we could do better.
         .expect("block not to disappear between deciding to add it and adding it");
 
-    let eth_price = eth_price_store
-        .get_eth_price_by_block(&block)
-        .timed("get_eth_price_by_block")
-        .await
-        .expect("eth price close to block to be available");
-
-    execution_chain::store_block(db_pool, &block, eth_price)
-        .timed("store_block")
-        .await;
-
     // Some computations can be skipped, others should be ran, and rolled back for every change in
     // the chain of blocks we've assembled. These are the ones that are skippable, and so skipped
     // until we're in-sync with the chain again.
     let is_synced = execution_node.get_latest_block().await.hash == hash;
-    if is_synced {
-        debug!("we're synced, running on_new_head for skippables");
-        base_fees::on_new_block(db_pool, issuance_store, &block)
-            .timed("base_fees::on_new_block")
-            .await;
-        let burn_sums_envelope = burn_sums::on_new_block(db_pool, &block)
-            .timed("burn_sums::on_new_block")
-            .await;
-        let eth_supply: EthNewtype = eth_supply::last_eth_supply(db_pool)
-            .timed("last_eth_supply")
-            .await
-            .into();
-        burn_rates::on_new_block(db_pool, &burn_sums_envelope)
-            .timed("burn_rates::on_new_block")
-            .await;
-        gauges::on_new_block(
+
+    let burn_sums =
+        fetch_and_store_block(eth_price_store, execution_node, db_pool, &block, is_synced).await;
+
+    if let Some(burn_sums) = burn_sums {
+        debug!("we're synced, running analyses");
+        let context = NewBlockContext {
+            block: &block,
+            burn_sums: &burn_sums,
             db_pool,
             eth_price_store,
             issuance_store,
-            &block,
-            &burn_sums_envelope,
-            &eth_supply,
-        )
-        .timed("gauges::on_new_block")
-        .await
-        .unwrap_or_else(|err| warn!("gauges::on_new_block failed: {}", err));
-        usd_price::on_new_block(db_pool, eth_price_store, &block)
-            .timed("usd_price::on_new_block")
-            .await;
+        };
+        analyses.on_new_block(&context).await;
     } else {
-        debug!("not synced, skipping skippables");
+        debug!("not synced, skipping analyses");
     }
 }
 
@@ -124,39 +237,58 @@ async fn find_last_matching_block_number(
 async fn estimate_blocks_remaining(
     block_store: &impl BlockStore,
     execution_node: &ExecutionNode,
-) -> i32 {
+) -> i64 {
     let last_on_chain = execution_node.get_latest_block().await;
     let last_stored = block_store.last().await;
     last_on_chain.number - last_stored.number
 }
 
-pub const EXECUTION_BLOCK_NUMBER_AUG_1ST: BlockNumber = 15253306;
-
 async fn stream_heads_from_last(db: &PgPool) -> impl Stream<Item = BlockNumber> {
     let next_block_to_sync = execution_chain::get_last_block_number(db)
         .await
-        .map_or(EXECUTION_BLOCK_NUMBER_AUG_1ST, |number| number + 1);
+        .map_or(NETWORK_CONSTANTS.sync_start_block_number, |number| {
+            number + 1
+        });
     execution_chain::stream_heads_from(next_block_to_sync).await
 }
 
 type HeadsQueue = VecDeque<BlockNumber>;
 
 pub async fn sync_blocks() {
+    sync_blocks_with_analyses(analysis::built_in_analyses()).await
+}
+
+/// Syncs execution blocks, running the given analyses for every new head once we're in sync. Start
+/// from `analysis::built_in_analyses`, reorgs only roll back the tables of registered analyses.
+pub async fn sync_blocks_with_analyses(analyses: Analyses) {
     log::init_with_env();
 
     info!("syncing execution blocks");
 
+    let shutdown_signal = ShutdownSignal::listen();
+
     let db_pool = PgPool::connect(&db::get_db_url_with_name("sync-execution-blocks"))
         .await
         .unwrap();
 
     sqlx::migrate!().run(&db_pool).await.unwrap();
+    partitions::ensure_partitions(&db_pool).await.unwrap();
+
+    let execution_node = Arc::new(ExecutionNode::connect().await);
+    let sync_health = Arc::new(SyncHealth::new(Duration::minutes(5)));
+    tokio::spawn(health::serve_sync_probes(
+        sync_health.clone(),
+        db_pool.clone(),
+        execution_node.clone(),
+    ));
 
-    let execution_node = ExecutionNode::connect().await;
     let issuance_store = IssuanceStorePostgres::new(db_pool.clone());
     let eth_price_store = EthPriceStorePostgres::new(db_pool.clone());
     let block_store = BlockStorePostgres::new(db_pool.clone());
-    let mut heads_stream = stream_heads_from_last(&db_pool).await;
+
+    analyses.backfill(&db_pool).await;
+
+    let mut heads_stream = shutdown_signal.stop_stream(stream_heads_from_last(&db_pool).await);
     let mut heads_queue: HeadsQueue = VecDeque::new();
 
     let blocks_remaining_on_start = estimate_blocks_remaining(&block_store, &execution_node)
@@ -173,19 +305,25 @@ pub async fn sync_blocks() {
         // dropped in the loop below, and then break to continue where we left off in the outer
         // loop, the heads stream.
         while let Some(next_block_number) = heads_queue.pop_front() {
+            // Blocks are synced one transaction at a time, stopping between them is safe. Whatever
+            // is left in the queue gets picked up again from the last stored block on restart.
+            if shutdown_signal.is_requested() {
+                break;
+            }
+
             let next_block = execution_node
                 .get_block_by_number(&next_block_number)
                 .await
                 .expect("expect chain to never get shorter");
 
-            debug!(number = next_block.number, "syncing next block from queue");
+            debug!(number = %next_block.number, "syncing next block from queue");
 
             // Either we can add a block, or we need to roll back first. We can add a block when the
             // last stored block matches the one on-chain, and nothing is stored for the current block
             // number. If either condition fails, we need to roll back first, and then sync to the
             // current head.
             let last_stored_block = block_store.last().await;
-            let last_matches = if next_block.hash == LONDON_HARD_FORK_BLOCK_HASH {
+            let last_matches = if next_block.hash == *LONDON_HARD_FORK_BLOCK_HASH {
                 true
             } else {
                 last_stored_block.hash == next_block.parent_hash
@@ -195,6 +333,7 @@ pub async fn sync_blocks() {
             if last_matches && current_number_is_free {
                 // Add to the chain.
                 sync_by_hash(
+                    &analyses,
                     &issuance_store,
                     &eth_price_store,
                     &execution_node,
@@ -203,9 +342,11 @@ pub async fn sync_blocks() {
                 )
                 .timed("sync_by_hash")
                 .await;
+
+                sync_health.set_synced();
             } else {
                 warn!(
-                    number = next_block.number,
+                    number = %next_block.number,
                     forks_head = !current_number_is_free,
                     parent_mismatch = !last_matches,
                     "next block is not the next block in our copy of the chain, rolling back"
@@ -220,16 +361,19 @@ pub async fn sync_blocks() {
                 )
                 .await;
 
-                debug!(last_matching_block_number, "rolling back to block number");
+                debug!(%last_matching_block_number, "rolling back to block number");
 
                 let first_invalid_block_number = last_matching_block_number + 1;
 
                 // Roll back
-                rollback_numbers(&db_pool, &first_invalid_block_number).await;
+                rollback_numbers(&db_pool, &analyses, &first_invalid_block_number).await;
 
                 // Requeue
-                for block_number in (first_invalid_block_number..=next_block.number).rev() {
-                    debug!(block_number, "requeueing");
+                for block_number in
+                    BlockNumber::range_inclusive(first_invalid_block_number, next_block.number)
+                        .rev()
+                {
+                    debug!(%block_number, "requeueing");
                     heads_queue.push_front(block_number);
                 }
             }
@@ -237,4 +381,7 @@ pub async fn sync_blocks() {
 
         progress.inc_work_done();
     }
+
+    info!("stopped syncing execution blocks");
+    db_pool.close().await;
 }
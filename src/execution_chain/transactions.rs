@@ -0,0 +1,331 @@
+//! # Transactions
+//! Opt-in, see `ingest_transactions` in the config. Stores every transaction of every synced
+//! block, with the gas it used, so analyses can break burn and transfer volume down by contract.
+//!
+//! Analyses reading these, like blob transactions, transaction stats, contract deployments, fee
+//! suggestions, payload values and proposer tips, need ingestion on. There's no backfill, they
+//! only cover blocks synced while it was.
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use sqlx::{PgConnection, PgExecutor};
+
+use crate::{analysis::Analysis, rollback::RollbackPoint, units::WeiNewtype};
+
+use super::{
+    BlockNumber, ExecutionNode, ExecutionNodeBlock, ExecutionNodeTransaction, TransactionReceipt,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockTransaction {
+    /// The contract created, only set for contract creations.
+    pub contract_address: Option<String>,
+    /// Base fee and priority fee together, per unit of gas.
+    pub effective_gas_price: WeiNewtype,
+    pub from: String,
+    pub gas_limit: i32,
+    pub gas_used: i32,
+    pub hash: String,
+    pub input_selector: Option<String>,
+    pub to: Option<String>,
+    pub transaction_index: i32,
+    pub transaction_type: i32,
+    pub value: WeiNewtype,
+}
+
+impl BlockTransaction {
+    fn from_node(transaction: ExecutionNodeTransaction, receipt: TransactionReceipt) -> Self {
+        Self {
+            input_selector: transaction.input_selector().map(str::to_string),
+            contract_address: receipt.contract_address,
+            effective_gas_price: WeiNewtype(receipt.effective_gas_price as i128),
+            from: transaction.from,
+            gas_limit: transaction.gas,
+            gas_used: receipt.gas_used,
+            hash: transaction.hash,
+            to: transaction.to,
+            transaction_index: transaction.transaction_index,
+            transaction_type: transaction.transaction_type,
+            value: WeiNewtype(transaction.value),
+        }
+    }
+}
+
+/// Fetches the transactions of the block, and their receipts for the gas they used. An error when
+/// the node no longer has the block, or its transactions don't decode.
+pub async fn get_block_transactions(
+    execution_node: &ExecutionNode,
+    block: &ExecutionNodeBlock,
+) -> Result<Vec<BlockTransaction>> {
+    let transactions = execution_node
+        .get_transactions_by_block_hash(&block.hash)
+        .await?
+        .with_context(|| format!("block {} no longer available on the node", block.hash))?;
+    let receipts = execution_node
+        .get_transaction_receipts_for_block(block)
+        .await
+        .expect("expect receipts to be available for the block we're syncing");
+
+    let block_transactions = transactions
+        .into_iter()
+        .zip(receipts)
+        .map(|(transaction, receipt)| {
+            assert_eq!(
+                transaction.hash, receipt.transaction_hash,
+                "expect receipts in the same order as the block transactions"
+            );
+            BlockTransaction::from_node(transaction, receipt)
+        })
+        .collect();
+
+    Ok(block_transactions)
+}
+
+pub async fn store_transactions(
+    executor: impl PgExecutor<'_>,
+    block: &ExecutionNodeBlock,
+    transactions: &[BlockTransaction],
+) {
+    let mut transaction_indices = Vec::new();
+    let mut hashes = Vec::new();
+    let mut from_addresses = Vec::new();
+    let mut to_addresses = Vec::new();
+    let mut values = Vec::new();
+    let mut gas_limits = Vec::new();
+    let mut gas_useds = Vec::new();
+    let mut types = Vec::new();
+    let mut input_selectors = Vec::new();
+    let mut effective_gas_prices = Vec::new();
+    for transaction in transactions {
+        transaction_indices.push(transaction.transaction_index);
+        hashes.push(transaction.hash.clone());
+        from_addresses.push(transaction.from.clone());
+        to_addresses.push(transaction.to.clone());
+        values.push(transaction.value);
+        gas_limits.push(transaction.gas_limit);
+        gas_useds.push(transaction.gas_used);
+        types.push(transaction.transaction_type as i16);
+        input_selectors.push(transaction.input_selector.clone());
+        effective_gas_prices.push(transaction.effective_gas_price);
+    }
+
+    sqlx::query!(
+        "
+        INSERT INTO transactions (
+            block_number,
+            block_hash,
+            transaction_index,
+            hash,
+            from_address,
+            to_address,
+            value_wei,
+            gas_limit,
+            gas_used,
+            type,
+            input_selector,
+            effective_gas_price
+        )
+        SELECT $1, $2, * FROM UNNEST (
+            $3::int[],
+            $4::text[],
+            $5::text[],
+            $6::text[],
+            $7::numeric[],
+            $8::int[],
+            $9::int[],
+            $10::smallint[],
+            $11::text[],
+            $12::numeric[]
+        )
+        ",
+        block.number.0,
+        block.hash,
+        &transaction_indices,
+        &hashes,
+        &from_addresses,
+        &to_addresses as &[Option<String>],
+        &values as &[WeiNewtype],
+        &gas_limits,
+        &gas_useds,
+        &types,
+        &input_selectors as &[Option<String>],
+        &effective_gas_prices as &[WeiNewtype]
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub async fn delete_transactions(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: &BlockNumber,
+) {
+    sqlx::query!(
+        "
+        DELETE FROM transactions
+        WHERE block_number >= $1
+        ",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub struct TransactionsRollback;
+
+#[async_trait]
+impl Analysis for TransactionsRollback {
+    fn name(&self) -> &'static str {
+        "transactions"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            delete_transactions(transaction, block_number_gte).await;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{block_store, ExecutionNodeBlockBuilder},
+    };
+
+    use super::*;
+
+    /// Builds a plain transfer of nothing, to be adjusted with the `with_` methods.
+    pub struct BlockTransactionBuilder {
+        contract_address: Option<String>,
+        effective_gas_price: WeiNewtype,
+        from: String,
+        gas_limit: i32,
+        gas_used: i32,
+        hash: String,
+        input_selector: Option<String>,
+        to: Option<String>,
+        transaction_index: i32,
+        transaction_type: i32,
+        value: WeiNewtype,
+    }
+
+    impl BlockTransactionBuilder {
+        pub fn new(test_id: &str, transaction_index: i32) -> Self {
+            Self {
+                contract_address: None,
+                effective_gas_price: WeiNewtype(10),
+                from: "0xfrom".to_string(),
+                gas_limit: 21_000,
+                gas_used: 21_000,
+                hash: format!("0x{test_id}_{transaction_index}"),
+                input_selector: None,
+                to: Some("0xto".to_string()),
+                transaction_index,
+                transaction_type: 2,
+                value: WeiNewtype(0),
+            }
+        }
+
+        /// Sends to no one, creating the contract.
+        pub fn with_contract_address(mut self, contract_address: &str) -> Self {
+            self.contract_address = Some(contract_address.to_string());
+            self.to = None;
+            self
+        }
+
+        pub fn with_effective_gas_price(mut self, effective_gas_price: WeiNewtype) -> Self {
+            self.effective_gas_price = effective_gas_price;
+            self
+        }
+
+        pub fn with_from(mut self, from: &str) -> Self {
+            self.from = from.to_string();
+            self
+        }
+
+        pub fn with_gas_limit(mut self, gas_limit: i32) -> Self {
+            self.gas_limit = gas_limit;
+            self
+        }
+
+        pub fn with_gas_used(mut self, gas_used: i32) -> Self {
+            self.gas_used = gas_used;
+            self
+        }
+
+        pub fn with_input_selector(mut self, input_selector: &str) -> Self {
+            self.input_selector = Some(input_selector.to_string());
+            self
+        }
+
+        pub fn with_transaction_type(mut self, transaction_type: i32) -> Self {
+            self.transaction_type = transaction_type;
+            self
+        }
+
+        pub fn with_value(mut self, value: WeiNewtype) -> Self {
+            self.value = value;
+            self
+        }
+
+        pub fn build(&self) -> BlockTransaction {
+            BlockTransaction {
+                contract_address: self.contract_address.clone(),
+                effective_gas_price: self.effective_gas_price,
+                from: self.from.clone(),
+                gas_limit: self.gas_limit,
+                gas_used: self.gas_used,
+                hash: self.hash.clone(),
+                input_selector: self.input_selector.clone(),
+                to: self.to.clone(),
+                transaction_index: self.transaction_index,
+                transaction_type: self.transaction_type,
+                value: self.value,
+            }
+        }
+    }
+
+    async fn transaction_count(executor: impl PgExecutor<'_>, block_number: BlockNumber) -> i64 {
+        sqlx::query!(
+            r#"SELECT COUNT(*) AS "count!" FROM transactions WHERE block_number = $1"#,
+            block_number.0
+        )
+        .fetch_one(executor)
+        .await
+        .unwrap()
+        .count
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn store_and_rollback_transactions_test(test_db: &TestDb) {
+        let block = ExecutionNodeBlockBuilder::new("store_transactions").build();
+        block_store::store_block(&test_db.pool, &block, 0.0).await;
+
+        let transactions = vec![
+            BlockTransactionBuilder::new("store_transactions", 0)
+                .with_value(WeiNewtype::from_eth(1))
+                .build(),
+            BlockTransactionBuilder::new("store_transactions", 1)
+                .with_contract_address("0xcontract")
+                .with_gas_limit(100_000)
+                .with_gas_used(50_000)
+                .with_input_selector("0x60806040")
+                .build(),
+        ];
+        store_transactions(&test_db.pool, &block, &transactions).await;
+        assert_eq!(transaction_count(&test_db.pool, block.number).await, 2);
+
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        TransactionsRollback
+            .on_rollback(
+                &mut connection,
+                &RollbackPoint::BlockNumbersGte(block.number),
+            )
+            .await;
+        assert_eq!(transaction_count(&mut *connection, block.number).await, 0);
+    }
+}
@@ -0,0 +1,262 @@
+//! # Export
+//! Dumps an analysis table for a block or time range, e.g. `export eth_supply 2024-01-01
+//! 2024-02-01 --format parquet`. Rows are streamed from the database into the file, so exporting
+//! all of `blocks_next` doesn't need it to fit in memory.
+//!
+//! Ranges follow `sum-burn`, block ranges include both ends, time ranges exclude the end.
+mod rows;
+
+use std::{fmt::Display, fs::File, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use futures::TryStreamExt;
+use sqlx::PgPool;
+use tracing::{debug, info};
+
+use crate::{
+    burn_sums::{self, BurnSumRange},
+    db,
+    execution_chain::SupplyDelta,
+    log,
+    row_writer::{ExportFormat, RowWriter},
+};
+
+use rows::{BeaconIssuanceRow, EthPriceRow};
+
+pub use rows::{BlockRow, DatasetRow, EthSupplyRow};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dataset {
+    BeaconIssuance,
+    Blocks,
+    EthPrices,
+    EthSupply,
+    ExecutionSupplyDeltas,
+}
+
+impl Dataset {
+    fn table_name(&self) -> &'static str {
+        match self {
+            Self::BeaconIssuance => "beacon_issuance",
+            Self::Blocks => "blocks_next",
+            Self::EthPrices => "eth_prices",
+            Self::EthSupply => "eth_supply",
+            Self::ExecutionSupplyDeltas => "execution_supply_deltas",
+        }
+    }
+}
+
+impl Display for Dataset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.table_name())
+    }
+}
+
+impl FromStr for Dataset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "beacon_issuance" => Ok(Self::BeaconIssuance),
+            "blocks_next" => Ok(Self::Blocks),
+            "eth_prices" => Ok(Self::EthPrices),
+            "eth_supply" => Ok(Self::EthSupply),
+            "execution_supply_deltas" => Ok(Self::ExecutionSupplyDeltas),
+            unknown => Err(anyhow!(
+                "unknown dataset {unknown}, expected beacon_issuance, blocks_next, eth_prices, eth_supply or execution_supply_deltas"
+            )),
+        }
+    }
+}
+
+fn range_query<R: DatasetRow>(range: &BurnSumRange) -> Result<String> {
+    let query = match range {
+        BurnSumRange::Blocks(_) => {
+            let column = R::BLOCK_NUMBER_COLUMN
+                .ok_or_else(|| anyhow!("dataset has no block numbers, export a time range"))?;
+            format!(
+                "{} WHERE {column} >= $1 AND {column} <= $2 ORDER BY {column}",
+                R::SELECT
+            )
+        }
+        BurnSumRange::Time { .. } => {
+            let column = R::TIMESTAMP_COLUMN
+                .ok_or_else(|| anyhow!("dataset has no timestamps, export a block range"))?;
+            format!(
+                "{} WHERE {column} >= $1 AND {column} < $2 ORDER BY {column}",
+                R::SELECT
+            )
+        }
+    };
+    Ok(query)
+}
+
+/// Streams the rows in range into the writer, returns how many were written.
+async fn write_rows<R: DatasetRow>(
+    db_pool: &PgPool,
+    range: &BurnSumRange,
+    row_writer: &mut RowWriter<R>,
+) -> Result<u64> {
+    let query = range_query::<R>(range)?;
+    let query = match range {
+        BurnSumRange::Blocks(block_range) => sqlx::query(&query)
+            .bind(block_range.start)
+            .bind(block_range.end),
+        BurnSumRange::Time { start, end } => sqlx::query(&query).bind(start).bind(end),
+    };
+
+    let mut rows = query.map(R::from_row).fetch(db_pool);
+    let mut count = 0;
+    while let Some(row) = rows.try_next().await? {
+        row_writer.write(row);
+        count += 1;
+        if count % 100_000 == 0 {
+            debug!(count, "exported rows");
+        }
+    }
+
+    Ok(count)
+}
+
+async fn export_rows<R: DatasetRow>(
+    db_pool: &PgPool,
+    range: &BurnSumRange,
+    file_path: &str,
+    format: ExportFormat,
+) -> Result<u64> {
+    let mut row_writer = RowWriter::<R>::create(File::create(file_path)?, format);
+    let count = write_rows(db_pool, range, &mut row_writer).await?;
+    // Parquet files need their footer, finish even when we wrote nothing.
+    row_writer.finish();
+    Ok(count)
+}
+
+pub async fn export_dataset(
+    db_pool: &PgPool,
+    dataset: Dataset,
+    range: &BurnSumRange,
+    file_path: &str,
+    format: ExportFormat,
+) -> Result<u64> {
+    match dataset {
+        Dataset::BeaconIssuance => {
+            export_rows::<BeaconIssuanceRow>(db_pool, range, file_path, format).await
+        }
+        Dataset::Blocks => export_rows::<BlockRow>(db_pool, range, file_path, format).await,
+        Dataset::EthPrices => export_rows::<EthPriceRow>(db_pool, range, file_path, format).await,
+        Dataset::EthSupply => export_rows::<EthSupplyRow>(db_pool, range, file_path, format).await,
+        Dataset::ExecutionSupplyDeltas => {
+            export_rows::<SupplyDelta>(db_pool, range, file_path, format).await
+        }
+    }
+}
+
+pub async fn export() -> Result<()> {
+    let args = std::env::args().collect::<Vec<String>>();
+    let (dataset, start, end) = match (args.get(1), args.get(2), args.get(3)) {
+        (Some(dataset), Some(start), Some(end)) => (dataset, start, end),
+        _ => bail!("usage: export-dataset <dataset> <start> <end> [--format csv|jsonl|parquet]"),
+    };
+    export_between(dataset, start, end, ExportFormat::from_args()).await
+}
+
+pub async fn export_between(
+    dataset: &str,
+    start: &str,
+    end: &str,
+    format: ExportFormat,
+) -> Result<()> {
+    log::init_with_env();
+
+    let dataset = dataset.parse::<Dataset>()?;
+    let range = burn_sums::parse_range(start, end)?;
+    let timestamp = crate::time::get_timestamp();
+
+    info!(%dataset, ?range, %format, "exporting dataset");
+
+    let db_pool = db::get_read_db_pool("export-dataset").await;
+
+    let file_path = format!("{dataset}_{timestamp}.{}", format.extension());
+    let count = export_dataset(&db_pool, dataset, &range, &file_path, format).await?;
+
+    info!(file_path, count, "done exporting dataset");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{BlockNumber, BlockRange},
+    };
+
+    use super::*;
+
+    #[test]
+    fn parse_dataset_test() {
+        assert_eq!("blocks_next".parse::<Dataset>().unwrap(), Dataset::Blocks);
+        assert_eq!(Dataset::EthSupply.to_string(), "eth_supply");
+        assert!("blocks".parse::<Dataset>().is_err());
+    }
+
+    #[test]
+    fn block_range_needs_block_numbers_test() {
+        let range = BurnSumRange::Blocks(BlockRange::new(BlockNumber(0), BlockNumber(10)));
+        assert!(range_query::<EthPriceRow>(&range).is_err());
+        assert!(range_query::<BlockRow>(&range).is_ok());
+    }
+
+    #[test]
+    fn time_range_needs_timestamps_test() {
+        let range = BurnSumRange::Time {
+            start: Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap(),
+        };
+        assert!(range_query::<SupplyDelta>(&range).is_err());
+        assert!(range_query::<EthPriceRow>(&range).is_ok());
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn export_eth_prices_jsonl_test(test_db: &TestDb) {
+        sqlx::query!(
+            "
+            INSERT INTO eth_prices (timestamp, ethusd, provider, origin)
+            VALUES
+                ('2024-03-01T00:00:00Z', 3400.0, 'bybit', 'recorded'),
+                ('2024-03-02T00:00:00Z', 3500.0, 'bybit', 'recorded')
+            "
+        )
+        .execute(&test_db.pool)
+        .await
+        .unwrap();
+
+        let file_path = std::env::temp_dir().join("export_eth_prices_jsonl_test.jsonl");
+        let file_path = file_path.to_str().unwrap();
+        let range = BurnSumRange::Time {
+            start: Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap(),
+        };
+
+        let count = export_dataset(
+            &test_db.pool,
+            Dataset::EthPrices,
+            &range,
+            file_path,
+            ExportFormat::Jsonl,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(count, 1);
+        let lines = std::fs::read_to_string(file_path).unwrap();
+        assert_eq!(
+            lines,
+            "{\"timestamp\":\"2024-03-01T00:00:00Z\",\"ethusd\":3400.0,\"provider\":\"bybit\",\"origin\":\"recorded\"}\n"
+        );
+    }
+}
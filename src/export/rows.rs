@@ -0,0 +1,409 @@
+//! One row type per exportable table, each knows the query selecting its columns.
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+
+#[cfg(feature = "parquet")]
+use arrow::{
+    array::{
+        ArrayRef, Decimal128Array, Float64Array, Int32Array, Int64Array, StringArray,
+        TimestampMicrosecondArray,
+    },
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    record_batch::RecordBatch,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{postgres::PgRow, Row};
+
+use crate::{
+    execution_chain::{BlockNumber, SupplyDelta},
+    row_writer::ExportRow,
+    units::WeiNewtype,
+};
+
+pub trait DatasetRow: ExportRow + Send + Unpin + 'static {
+    /// Selects the exported columns, without a WHERE clause.
+    const SELECT: &'static str;
+    /// Datasets without a block number column can only be exported for a time range.
+    const BLOCK_NUMBER_COLUMN: Option<&'static str>;
+    /// Datasets without a timestamp column can only be exported for a block range.
+    const TIMESTAMP_COLUMN: Option<&'static str>;
+
+    fn from_row(row: PgRow) -> Self;
+}
+
+#[cfg(feature = "parquet")]
+fn timestamp_field(name: &str) -> Field {
+    Field::new(
+        name,
+        DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        false,
+    )
+}
+
+#[cfg(feature = "parquet")]
+fn timestamp_column<'a>(timestamps: impl Iterator<Item = &'a DateTime<Utc>>) -> ArrayRef {
+    Arc::new(
+        TimestampMicrosecondArray::from_iter_values(
+            timestamps.map(|timestamp| timestamp.timestamp_micros()),
+        )
+        .with_timezone("UTC"),
+    )
+}
+
+#[derive(Serialize)]
+pub struct BlockRow {
+    number: BlockNumber,
+    hash: String,
+    parent_hash: String,
+    timestamp: DateTime<Utc>,
+    base_fee_per_gas: i64,
+    blob_base_fee: Option<i64>,
+    blob_gas_used: Option<i32>,
+    excess_blob_gas: Option<i64>,
+    gas_limit: Option<i32>,
+    gas_used: i32,
+    difficulty: i64,
+    /// Exceeds what fits in an i64, exported as text.
+    total_difficulty: String,
+    eth_price: f64,
+}
+
+impl BlockRow {
+    pub fn number(&self) -> BlockNumber {
+        self.number
+    }
+}
+
+impl DatasetRow for BlockRow {
+    const SELECT: &'static str = "
+        SELECT
+            number,
+            hash,
+            parent_hash,
+            timestamp,
+            base_fee_per_gas,
+            blob_base_fee,
+            blob_gas_used,
+            excess_blob_gas,
+            gas_limit,
+            gas_used,
+            difficulty,
+            total_difficulty::TEXT AS total_difficulty,
+            eth_price
+        FROM blocks_next
+    ";
+    const BLOCK_NUMBER_COLUMN: Option<&'static str> = Some("number");
+    const TIMESTAMP_COLUMN: Option<&'static str> = Some("timestamp");
+
+    fn from_row(row: PgRow) -> Self {
+        Self {
+            number: row.get("number"),
+            hash: row.get("hash"),
+            parent_hash: row.get("parent_hash"),
+            timestamp: row.get("timestamp"),
+            base_fee_per_gas: row.get("base_fee_per_gas"),
+            blob_base_fee: row.get("blob_base_fee"),
+            blob_gas_used: row.get("blob_gas_used"),
+            excess_blob_gas: row.get("excess_blob_gas"),
+            gas_limit: row.get("gas_limit"),
+            gas_used: row.get("gas_used"),
+            difficulty: row.get("difficulty"),
+            total_difficulty: row.get("total_difficulty"),
+            eth_price: row.get("eth_price"),
+        }
+    }
+}
+
+impl ExportRow for BlockRow {
+    #[cfg(feature = "parquet")]
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("number", DataType::Int64, false),
+            Field::new("hash", DataType::Utf8, false),
+            Field::new("parent_hash", DataType::Utf8, false),
+            timestamp_field("timestamp"),
+            Field::new("base_fee_per_gas", DataType::Int64, false),
+            Field::new("blob_base_fee", DataType::Int64, true),
+            Field::new("blob_gas_used", DataType::Int32, true),
+            Field::new("excess_blob_gas", DataType::Int64, true),
+            Field::new("gas_limit", DataType::Int32, true),
+            Field::new("gas_used", DataType::Int32, false),
+            Field::new("difficulty", DataType::Int64, false),
+            Field::new("total_difficulty", DataType::Utf8, false),
+            Field::new("eth_price", DataType::Float64, false),
+        ]))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn to_record_batch(rows: &[Self]) -> RecordBatch {
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|row| row.number.0),
+                )) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.hash),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.parent_hash),
+                )),
+                timestamp_column(rows.iter().map(|row| &row.timestamp)),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|row| row.base_fee_per_gas),
+                )),
+                Arc::new(Int64Array::from_iter(
+                    rows.iter().map(|row| row.blob_base_fee),
+                )),
+                Arc::new(Int32Array::from_iter(
+                    rows.iter().map(|row| row.blob_gas_used),
+                )),
+                Arc::new(Int64Array::from_iter(
+                    rows.iter().map(|row| row.excess_blob_gas),
+                )),
+                Arc::new(Int32Array::from_iter(rows.iter().map(|row| row.gas_limit))),
+                Arc::new(Int32Array::from_iter_values(
+                    rows.iter().map(|row| row.gas_used),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|row| row.difficulty),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.total_difficulty),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    rows.iter().map(|row| row.eth_price),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Serialize)]
+pub struct EthSupplyRow {
+    timestamp: DateTime<Utc>,
+    block_number: BlockNumber,
+    deposits_slot: i32,
+    balances_slot: i32,
+    supply: WeiNewtype,
+}
+
+impl EthSupplyRow {
+    pub fn balances_slot(&self) -> i32 {
+        self.balances_slot
+    }
+}
+
+impl DatasetRow for EthSupplyRow {
+    const SELECT: &'static str = "
+        SELECT
+            timestamp,
+            block_number,
+            deposits_slot,
+            balances_slot,
+            supply
+        FROM eth_supply
+    ";
+    const BLOCK_NUMBER_COLUMN: Option<&'static str> = Some("block_number");
+    const TIMESTAMP_COLUMN: Option<&'static str> = Some("timestamp");
+
+    fn from_row(row: PgRow) -> Self {
+        Self {
+            timestamp: row.get("timestamp"),
+            block_number: row.get("block_number"),
+            deposits_slot: row.get("deposits_slot"),
+            balances_slot: row.get("balances_slot"),
+            supply: row.get("supply"),
+        }
+    }
+}
+
+impl ExportRow for EthSupplyRow {
+    #[cfg(feature = "parquet")]
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            timestamp_field("timestamp"),
+            Field::new("block_number", DataType::Int64, false),
+            Field::new("deposits_slot", DataType::Int32, false),
+            Field::new("balances_slot", DataType::Int32, false),
+            Field::new("supply", DataType::Decimal128(38, 0), false),
+        ]))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn to_record_batch(rows: &[Self]) -> RecordBatch {
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                timestamp_column(rows.iter().map(|row| &row.timestamp)),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|row| row.block_number.0),
+                )),
+                Arc::new(Int32Array::from_iter_values(
+                    rows.iter().map(|row| row.deposits_slot),
+                )),
+                Arc::new(Int32Array::from_iter_values(
+                    rows.iter().map(|row| row.balances_slot),
+                )),
+                Arc::new(
+                    Decimal128Array::from_iter_values(rows.iter().map(|row| row.supply.0))
+                        .with_precision_and_scale(38, 0)
+                        .unwrap(),
+                ),
+            ],
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Serialize)]
+pub struct BeaconIssuanceRow {
+    timestamp: DateTime<Utc>,
+    state_root: String,
+    gwei: i64,
+}
+
+impl DatasetRow for BeaconIssuanceRow {
+    const SELECT: &'static str = "
+        SELECT
+            timestamp,
+            state_root,
+            gwei
+        FROM beacon_issuance
+    ";
+    const BLOCK_NUMBER_COLUMN: Option<&'static str> = None;
+    const TIMESTAMP_COLUMN: Option<&'static str> = Some("timestamp");
+
+    fn from_row(row: PgRow) -> Self {
+        Self {
+            timestamp: row.get("timestamp"),
+            state_root: row.get("state_root"),
+            gwei: row.get("gwei"),
+        }
+    }
+}
+
+impl ExportRow for BeaconIssuanceRow {
+    #[cfg(feature = "parquet")]
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            timestamp_field("timestamp"),
+            Field::new("state_root", DataType::Utf8, false),
+            Field::new("gwei", DataType::Int64, false),
+        ]))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn to_record_batch(rows: &[Self]) -> RecordBatch {
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                timestamp_column(rows.iter().map(|row| &row.timestamp)),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|row| &row.state_root),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|row| row.gwei),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Serialize)]
+pub struct EthPriceRow {
+    timestamp: DateTime<Utc>,
+    ethusd: f64,
+    /// Unknown for prices stored before provenance was recorded.
+    provider: Option<String>,
+    origin: Option<String>,
+}
+
+impl DatasetRow for EthPriceRow {
+    const SELECT: &'static str = "
+        SELECT
+            timestamp,
+            ethusd,
+            provider,
+            origin
+        FROM eth_prices
+    ";
+    const BLOCK_NUMBER_COLUMN: Option<&'static str> = None;
+    const TIMESTAMP_COLUMN: Option<&'static str> = Some("timestamp");
+
+    fn from_row(row: PgRow) -> Self {
+        Self {
+            timestamp: row.get("timestamp"),
+            ethusd: row.get("ethusd"),
+            provider: row.get("provider"),
+            origin: row.get("origin"),
+        }
+    }
+}
+
+impl ExportRow for EthPriceRow {
+    #[cfg(feature = "parquet")]
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            timestamp_field("timestamp"),
+            Field::new("ethusd", DataType::Float64, false),
+            Field::new("provider", DataType::Utf8, true),
+            Field::new("origin", DataType::Utf8, true),
+        ]))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn to_record_batch(rows: &[Self]) -> RecordBatch {
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                timestamp_column(rows.iter().map(|row| &row.timestamp)),
+                Arc::new(Float64Array::from_iter_values(
+                    rows.iter().map(|row| row.ethusd),
+                )),
+                Arc::new(StringArray::from_iter(
+                    rows.iter().map(|row| row.provider.as_deref()),
+                )),
+                Arc::new(StringArray::from_iter(
+                    rows.iter().map(|row| row.origin.as_deref()),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+}
+
+/// Supply deltas are stored without a timestamp, and export for block ranges only.
+impl DatasetRow for SupplyDelta {
+    const SELECT: &'static str = "
+        SELECT
+            block_hash,
+            block_number,
+            fee_burn,
+            fixed_reward,
+            parent_hash,
+            self_destruct,
+            supply_delta,
+            uncles_reward
+        FROM execution_supply_deltas
+    ";
+    const BLOCK_NUMBER_COLUMN: Option<&'static str> = Some("block_number");
+    const TIMESTAMP_COLUMN: Option<&'static str> = None;
+
+    fn from_row(row: PgRow) -> Self {
+        let wei = |column: &str| row.get::<WeiNewtype, _>(column).0;
+        Self {
+            block_hash: row.get("block_hash"),
+            block_number: row.get("block_number"),
+            fee_burn: wei("fee_burn"),
+            fixed_reward: wei("fixed_reward"),
+            parent_hash: row.get("parent_hash"),
+            self_destruct: wei("self_destruct"),
+            supply_delta: wei("supply_delta"),
+            uncles_reward: wei("uncles_reward"),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! # Fee Suggestions
+//! Suggests a priority fee from what transactions in recent blocks paid. On every head we take
+//! the effective priority fee of each transaction over the last `BLOCK_COUNT` blocks, what it paid
+//! per unit of gas above the base fee, and publish the 10th, 50th and 90th percentile. A low,
+//! regular and fast suggestion, to add to the next base fee.
+//!
+//! Reads stored transactions, see `execution_chain::transactions`, the effective gas price comes
+//! from the receipts fetched with them.
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    caching::{self, CacheKey},
+    execution_chain::{BlockNumber, ExecutionNodeBlock},
+};
+
+/// About four minutes of blocks.
+const BLOCK_COUNT: i64 = 20;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FeeSuggestion {
+    block_count: i64,
+    block_number: BlockNumber,
+    /// Priority fee percentiles in wei per unit of gas, `None` without transactions.
+    p10: Option<f64>,
+    p50: Option<f64>,
+    p90: Option<f64>,
+    transaction_count: i64,
+}
+
+/// Over the blocks up to and including the given one.
+async fn get_fee_suggestion(
+    executor: impl PgExecutor<'_>,
+    block_number: BlockNumber,
+) -> FeeSuggestion {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) AS "transaction_count!",
+            PERCENTILE_CONT(ARRAY[0.1, 0.5, 0.9]) WITHIN GROUP (
+                ORDER BY (
+                    transactions.effective_gas_price - blocks_next.base_fee_per_gas
+                )::FLOAT8
+            ) AS percentiles
+        FROM transactions
+        JOIN blocks_next ON blocks_next.number = transactions.block_number
+        WHERE transactions.block_number > $1::INT8 - $2
+        AND transactions.block_number <= $1
+        AND transactions.effective_gas_price IS NOT NULL
+        "#,
+        block_number.0,
+        BLOCK_COUNT,
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap();
+
+    let percentile = |index: usize| {
+        row.percentiles
+            .as_ref()
+            .and_then(|percentiles| percentiles.get(index).copied())
+    };
+    FeeSuggestion {
+        block_count: BLOCK_COUNT,
+        block_number,
+        p10: percentile(0),
+        p50: percentile(1),
+        p90: percentile(2),
+        transaction_count: row.transaction_count,
+    }
+}
+
+pub async fn on_new_block(db_pool: &PgPool, block: &ExecutionNodeBlock) {
+    let fee_suggestion = get_fee_suggestion(db_pool, block.number).await;
+    debug!(?fee_suggestion, "publishing fee suggestion");
+    caching::update_and_publish(db_pool, &CacheKey::FeeSuggestion, fee_suggestion).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{
+            block_store, store_transactions, BlockTransactionBuilder, ExecutionNodeBlockBuilder,
+        },
+        units::WeiNewtype,
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn fee_suggestion_test(test_db: &TestDb) {
+        let block = ExecutionNodeBlockBuilder::new("fee_suggestion")
+            .with_base_fee_per_gas(100)
+            .build();
+        block_store::store_block(&test_db.pool, &block, 1.0).await;
+        // Priority fees of 0, 10, 20, 30 and 40 wei.
+        let transactions = (0..5)
+            .map(|index| {
+                BlockTransactionBuilder::new("fee_suggestion", index)
+                    .with_effective_gas_price(WeiNewtype(100 + 10 * index as i128))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        store_transactions(&test_db.pool, &block, &transactions).await;
+
+        let fee_suggestion = get_fee_suggestion(&test_db.pool, block.number).await;
+        assert_eq!(fee_suggestion.transaction_count, 5);
+        assert!((fee_suggestion.p10.unwrap() - 4.0).abs() < 1e-9);
+        assert!((fee_suggestion.p50.unwrap() - 20.0).abs() < 1e-9);
+        assert!((fee_suggestion.p90.unwrap() - 36.0).abs() < 1e-9);
+
+        assert_eq!(
+            get_fee_suggestion(&test_db.pool, block.number + BLOCK_COUNT).await,
+            FeeSuggestion {
+                block_count: BLOCK_COUNT,
+                block_number: block.number + BLOCK_COUNT,
+                p10: None,
+                p50: None,
+                p90: None,
+                transaction_count: 0,
+            }
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! # Gas Utilization
+//! EIP-1559 targets blocks that are half full. Blocks above target push the base fee up, blocks
+//! below push it down. Per time frame we measure how full blocks are, and how hard they push.
+use std::collections::HashMap;
+
+use cached::proc_macro::cached;
+use chrono::{DateTime, Utc};
+use enum_iterator::all;
+use futures::future::join_all;
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    caching::{self, CacheKey},
+    execution_chain::{BlockNumber, ExecutionNodeBlock},
+    performance::TimedExt,
+    time_frames::TimeFrame,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GasUtilization {
+    above_target_share: f64,
+    /// Mean gas_used / gas_limit.
+    average_utilization: f64,
+    below_target_share: f64,
+    block_count: i64,
+    /// Mean distance from the gas target as a fraction of the target. Ranges from -1.0, all
+    /// blocks empty, to 1.0, all blocks full. Positive values mean the base fee is rising.
+    elasticity_pressure: f64,
+}
+
+#[derive(Serialize)]
+struct GasUtilizations {
+    block_number: BlockNumber,
+    #[serde(flatten)]
+    time_frames: HashMap<TimeFrame, GasUtilization>,
+}
+
+/// Returns `None` when no blocks with a known gas limit fall within the range.
+async fn gas_utilization_since(
+    executor: impl PgExecutor<'_>,
+    start_timestamp: &DateTime<Utc>,
+) -> Option<GasUtilization> {
+    // The gas target is the gas limit divided by the elasticity multiplier, which is 2.
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) AS "block_count!",
+            COUNT(*) FILTER (WHERE gas_used * 2 > gas_limit) AS "above_target_count!",
+            COUNT(*) FILTER (WHERE gas_used * 2 < gas_limit) AS "below_target_count!",
+            AVG(gas_used::FLOAT8 / gas_limit::FLOAT8) AS average_utilization,
+            AVG((gas_used::FLOAT8 * 2 - gas_limit::FLOAT8) / gas_limit::FLOAT8)
+                AS elasticity_pressure
+        FROM
+            blocks_next
+        WHERE
+            timestamp >= $1
+        AND
+            gas_limit > 0
+        "#,
+        start_timestamp
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap();
+
+    if row.block_count == 0 {
+        return None;
+    }
+
+    Some(GasUtilization {
+        above_target_share: row.above_target_count as f64 / row.block_count as f64,
+        average_utilization: row.average_utilization?,
+        below_target_share: row.below_target_count as f64 / row.block_count as f64,
+        block_count: row.block_count,
+        elasticity_pressure: row.elasticity_pressure?,
+    })
+}
+
+// Growing time frames scan most of the table and barely change from block to block.
+#[cached(
+    key = "String",
+    convert = r#"{start_timestamp.to_string()}"#,
+    time = 3600
+)]
+async fn gas_utilization_since_cached_1h(
+    db_pool: &PgPool,
+    start_timestamp: &DateTime<Utc>,
+) -> Option<GasUtilization> {
+    gas_utilization_since(db_pool, start_timestamp).await
+}
+
+pub async fn on_new_block(db_pool: &PgPool, block: &ExecutionNodeBlock) {
+    debug!("calculating gas utilization");
+
+    let futures = all::<TimeFrame>().map(|time_frame| async move {
+        let start_timestamp = time_frame.start_timestamp(block);
+        let gas_utilization = match time_frame {
+            TimeFrame::Growing(_) => {
+                gas_utilization_since_cached_1h(db_pool, &start_timestamp).await
+            }
+            TimeFrame::Limited(_) => {
+                gas_utilization_since(db_pool, &start_timestamp)
+                    .timed(&format!("gas_utilization_since_{time_frame}"))
+                    .await
+            }
+        };
+        gas_utilization.map(|gas_utilization| (time_frame, gas_utilization))
+    });
+    let time_frames = join_all(futures).await.into_iter().flatten().collect();
+
+    let gas_utilizations = GasUtilizations {
+        block_number: block.number,
+        time_frames,
+    };
+
+    caching::update_and_publish(db_pool, &CacheKey::GasUtilization, gas_utilizations).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{block_store, ExecutionNodeBlockBuilder},
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn gas_utilization_since_test(test_db: &TestDb) {
+        let block_1 = ExecutionNodeBlockBuilder::new("gas_utilization_since")
+            .with_gas_limit(100)
+            .with_gas_used(100)
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_gas_limit(100)
+            .with_gas_used(0)
+            .build();
+        let block_3 = ExecutionNodeBlockBuilder::from_parent(&block_2)
+            .with_gas_limit(100)
+            .with_gas_used(100)
+            .build();
+
+        block_store::store_block(&test_db.pool, &block_1, 1.0).await;
+        block_store::store_block(&test_db.pool, &block_2, 1.0).await;
+        block_store::store_block(&test_db.pool, &block_3, 1.0).await;
+
+        let gas_utilization = gas_utilization_since(&test_db.pool, &block_1.timestamp)
+            .await
+            .unwrap();
+
+        assert_eq!(gas_utilization.block_count, 3);
+        assert_eq!(gas_utilization.above_target_share, 2.0 / 3.0);
+        assert_eq!(gas_utilization.below_target_share, 1.0 / 3.0);
+        assert_eq!(gas_utilization.elasticity_pressure, 1.0 / 3.0);
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn gas_utilization_since_empty_test(test_db: &TestDb) {
+        let gas_utilization = gas_utilization_since(&test_db.pool, &Utc::now()).await;
+        assert_eq!(gas_utilization, None);
+    }
+}
@@ -3,13 +3,13 @@
 /// For since burn issuance we consider beacon chain issuance only giving an idea of the rate of
 /// issuance on the beacon chain based on data since the burn, not the execution chain, which has
 /// issued many ETH since then too. To get a feel for this scenario we offer the "simulate pow"
-/// toggle which sets the rate to an estimate of only the pow issuance.
+/// toggle which sets the rate to only the pow issuance, as paid in the blocks before the merge, see
+/// `execution_chain::pow_issuance`.
 use std::collections::HashMap;
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use enum_iterator::all;
-use futures::join;
 use serde::Serialize;
 use sqlx::PgPool;
 
@@ -17,17 +17,14 @@ use crate::{
     beacon_chain::IssuanceStore,
     burn_sums::{BurnSums, EthUsdAmount},
     caching::{self, CacheKey},
-    execution_chain::{BlockNumber, ExecutionNodeBlock},
+    execution_chain::{self, BlockNumber, ExecutionNodeBlock},
     performance::TimedExt,
     time_frames::TimeFrame,
     units::{EthNewtype, UsdNewtype},
-    usd_price::EthPriceStore,
+    usd_price::AverageEthPrices,
 };
 
-const PROOF_OF_WORK_DAILY_ISSUANCE_ESTIMATE: f64 = 13500.0;
 const DAYS_PER_YEAR: f64 = 365.25;
-const PROOF_OF_WORK_YEARLY_ISSUANCE_ESTIMATE: f64 =
-    PROOF_OF_WORK_DAILY_ISSUANCE_ESTIMATE * DAYS_PER_YEAR;
 const HOURS_PER_DAY: f64 = 24.0;
 const MINUTES_PER_HOUR: f64 = 60.0;
 const MINUTES_PER_YEAR: f64 = MINUTES_PER_HOUR * HOURS_PER_DAY * DAYS_PER_YEAR;
@@ -47,7 +44,7 @@ pub type GaugeRates = HashMap<TimeFrame, GaugeRatesTimeFrame>;
 
 pub async fn on_new_block(
     db_pool: &PgPool,
-    eth_price_store: &impl EthPriceStore,
+    average_prices: &AverageEthPrices,
     issuance_store: &impl IssuanceStore,
     block: &ExecutionNodeBlock,
     burn_sums: &BurnSums,
@@ -55,21 +52,19 @@ pub async fn on_new_block(
 ) -> Result<()> {
     let mut gauge_rates: GaugeRates = HashMap::new();
 
+    let pow_issuance_yearly =
+        execution_chain::get_daily_pow_issuance(db_pool).await.0 * DAYS_PER_YEAR;
+
     for time_frame in all::<TimeFrame>() {
-        let burn_rate_yearly = burn_sums
-            .get(&time_frame)
-            .unwrap()
+        let burn_rate_yearly = burn_sums[time_frame]
             .sum
             .yearly_rate_from_time_frame(time_frame);
 
-        let (issuance_time_frame, usd_price_average) = join!(
-            issuance_store
-                .issuance_from_time_frame(block, &time_frame)
-                .timed(&format!("issuance_from_time_frame_{time_frame}")),
-            eth_price_store
-                .average_from_time_range(time_frame.start_timestamp(block), block.timestamp,)
-                .timed(&format!("usd_price::average_from_time_range_{time_frame}"))
-        );
+        let issuance_time_frame = issuance_store
+            .issuance_from_time_frame(block, &time_frame)
+            .timed(&format!("issuance_from_time_frame_{time_frame}"))
+            .await;
+        let usd_price_average = average_prices.get(&time_frame).unwrap();
 
         let issuance_time_frame_eth: EthNewtype = issuance_time_frame?.into();
         let year_time_frame_fraction =
@@ -84,8 +79,8 @@ pub async fn on_new_block(
         };
 
         let issuance_rate_yearly_pow = EthUsdAmount {
-            eth: EthNewtype(PROOF_OF_WORK_YEARLY_ISSUANCE_ESTIMATE),
-            usd: UsdNewtype(PROOF_OF_WORK_YEARLY_ISSUANCE_ESTIMATE * usd_price_average.0),
+            eth: EthNewtype(pow_issuance_yearly),
+            usd: UsdNewtype(pow_issuance_yearly * usd_price_average.0),
         };
 
         let supply_growth_rate_yearly = {
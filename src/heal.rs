@@ -0,0 +1,94 @@
+//! # Heal
+//! Healers walk through stored data, compare it against a source of truth, and fix whatever
+//! diverged. This module takes care of what they share: chunking the work, tracking progress,
+//! checkpointing so a restarted heal resumes where it left off, and a dry-run mode which only
+//! reports.
+use anyhow::Result;
+use async_trait::async_trait;
+use pit_wall::Progress;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::{job_progress::JobProgress, key_value_store::KeyValueStorePostgres};
+
+#[async_trait]
+pub trait Healer {
+    /// A single unit of work, e.g. a slot. The last item of each completed chunk is stored as the
+    /// checkpoint.
+    type Item: Clone + DeserializeOwned + Send + Serialize + Sync;
+
+    /// Used in logs, and as the key the checkpoint is stored under.
+    fn name(&self) -> &'static str;
+
+    /// Returns the items left to heal, in order. On a fresh run the checkpoint is `None`.
+    async fn items_to_heal(&self, checkpoint: Option<Self::Item>) -> Vec<Self::Item>;
+
+    /// Heals a chunk of items. In a dry run divergences should be logged but not fixed. An error
+    /// stops the heal, it resumes after the last completed chunk.
+    async fn heal_chunk(&self, items: &[Self::Item], dry_run: bool) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HealOptions {
+    pub chunk_size: usize,
+    pub dry_run: bool,
+}
+
+/// Reads the `--dry-run` flag from the command line arguments, for the binaries in `src/bin`.
+pub fn dry_run_from_args() -> bool {
+    std::env::args().any(|arg| arg == "--dry-run")
+}
+
+/// Records a failed chunk on the job record before returning its error.
+pub async fn heal(db_pool: &PgPool, healer: &impl Healer, options: &HealOptions) -> Result<()> {
+    let name = healer.name();
+    let key_value_store = KeyValueStorePostgres::new(db_pool.clone());
+    let job_progress = JobProgress::new(name, &key_value_store);
+
+    let checkpoint = job_progress.get().await;
+    let resumed = checkpoint.is_some();
+    let items = healer.items_to_heal(checkpoint).await;
+
+    info!(
+        name,
+        item_count = items.len(),
+        resumed,
+        dry_run = options.dry_run,
+        "starting heal"
+    );
+
+    // A dry run changes nothing, including the job record.
+    if !options.dry_run {
+        job_progress.start().await;
+    }
+
+    let mut progress = Progress::new(name, items.len().try_into().unwrap());
+
+    for chunk in items.chunks(options.chunk_size) {
+        if let Err(err) = healer.heal_chunk(chunk, options.dry_run).await {
+            if !options.dry_run {
+                job_progress.fail(&err.to_string()).await;
+            }
+            return Err(err);
+        }
+
+        // A dry run fixes nothing, so it should not move the checkpoint forward.
+        if !options.dry_run {
+            job_progress
+                .set(chunk.last().expect("expect chunks to be non-empty"))
+                .await;
+        }
+
+        progress.inc_work_done_by(chunk.len().try_into().unwrap());
+        info!("{}", progress.get_progress_string());
+    }
+
+    if !options.dry_run {
+        job_progress.finish().await;
+    }
+
+    info!(name, "done healing");
+
+    Ok(())
+}
@@ -1,9 +1,20 @@
+use std::{sync::Arc, sync::RwLock, time::Duration as StdDuration};
+
+use async_trait::async_trait;
 use axum::{
     response::{IntoResponse, Response},
-    Json,
+    routing::get,
+    Json, Router, Server,
 };
+use chrono::{DateTime, Duration, Utc};
 use reqwest::StatusCode;
+use serde::Serialize;
 use serde_json::json;
+use sqlx::PgPool;
+use tokio::time::timeout;
+use tracing::info;
+
+use crate::config;
 
 pub enum HealthStatus {
     Healthy,
@@ -27,3 +38,135 @@ impl IntoResponse for HealthStatus {
         }
     }
 }
+
+const PROBE_TIMEOUT: StdDuration = StdDuration::from_secs(4);
+
+/// A node a sync service depends on.
+#[async_trait]
+pub trait NodeProbe: Send + Sync {
+    async fn is_reachable(&self) -> bool;
+}
+
+/// Shared between a sync loop, which reports whenever it synced something, and the probe server.
+pub struct SyncHealth {
+    last_synced: RwLock<Option<DateTime<Utc>>>,
+    max_sync_age: Duration,
+    started_on: DateTime<Utc>,
+}
+
+impl SyncHealth {
+    pub fn new(max_sync_age: Duration) -> Self {
+        Self {
+            last_synced: RwLock::new(None),
+            max_sync_age,
+            started_on: Utc::now(),
+        }
+    }
+
+    pub fn set_synced(&self) {
+        *self.last_synced.write().unwrap() = Some(Utc::now());
+    }
+
+    fn last_synced(&self) -> Option<DateTime<Utc>> {
+        *self.last_synced.read().unwrap()
+    }
+}
+
+impl HealthCheckable for SyncHealth {
+    // Healthy if we synced something recently, or only just started.
+    fn health_status(&self) -> HealthStatus {
+        let last_synced = self.last_synced().unwrap_or(self.started_on);
+        let time_since_last_sync = Utc::now() - last_synced;
+
+        if time_since_last_sync < self.max_sync_age {
+            HealthStatus::Healthy
+        } else {
+            HealthStatus::Unhealthy(Some(format!(
+                "nothing synced in {} seconds",
+                time_since_last_sync.num_seconds()
+            )))
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    database_reachable: bool,
+    last_synced: Option<DateTime<Utc>>,
+    node_reachable: bool,
+}
+
+async fn is_database_reachable(db_pool: &PgPool) -> bool {
+    let ping = sqlx::query!("SELECT 1 AS ping").fetch_one(db_pool);
+    matches!(timeout(PROBE_TIMEOUT, ping).await, Ok(Ok(_)))
+}
+
+async fn readiness(
+    sync_health: &SyncHealth,
+    db_pool: &PgPool,
+    node: &dyn NodeProbe,
+) -> impl IntoResponse {
+    let readiness = Readiness {
+        database_reachable: is_database_reachable(db_pool).await,
+        last_synced: sync_health.last_synced(),
+        node_reachable: timeout(PROBE_TIMEOUT, node.is_reachable())
+            .await
+            .unwrap_or(false),
+    };
+
+    let status_code = if readiness.database_reachable && readiness.node_reachable {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status_code, Json(readiness))
+}
+
+/// Serves `/healthz`, whether the sync is making progress, and `/readyz`, whether the database and
+/// node can be reached, for orchestration to probe sync services.
+pub async fn serve_sync_probes(
+    sync_health: Arc<SyncHealth>,
+    db_pool: PgPool,
+    node: Arc<dyn NodeProbe>,
+) {
+    let sync_health_ref = sync_health.clone();
+    let app = Router::new()
+        .route(
+            "/healthz",
+            get(|| async move { sync_health_ref.health_status().into_response() }),
+        )
+        .route(
+            "/readyz",
+            get(|| async move { readiness(&sync_health, &db_pool, node.as_ref()).await }),
+        );
+
+    // Phoenix serves its own health check on 8080.
+    let port = config::CONFIG.probe_port().unwrap_or("8081");
+    let socket_addr = format!("0.0.0.0:{port}").parse().unwrap();
+    info!(%socket_addr, "starting sync probe server");
+    Server::bind(&socket_addr)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_health_test() {
+        let sync_health = SyncHealth::new(Duration::minutes(5));
+        assert!(matches!(sync_health.health_status(), HealthStatus::Healthy));
+
+        *sync_health.last_synced.write().unwrap() = Some(Utc::now() - Duration::minutes(6));
+        assert!(matches!(
+            sync_health.health_status(),
+            HealthStatus::Unhealthy(_)
+        ));
+
+        sync_health.set_synced();
+        assert!(matches!(sync_health.health_status(), HealthStatus::Healthy));
+    }
+}
@@ -2,39 +2,156 @@
 //! ============
 //! Small module to help with tracking the progress of long running jobs. Uses the DB key value
 //! store and a progress value.
+//!
+//! Besides the checkpoint to resume from, we keep a record of each job's runs: how many were
+//! attempted, when the last one started and finished, and what the last error was. Any type
+//! which (de)serializes may serve as a checkpoint, e.g. a slot, a block number or a timestamp.
 
-use serde::{de::DeserializeOwned, Serialize};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::key_value_store::KeyValueStore;
+use crate::key_value_store::{KeyValueStore, KeyValueStoreExt};
 
-pub struct JobProgress<'a, A: Serialize + DeserializeOwned> {
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct JobRecord<A> {
+    pub attempts: u32,
+    pub checkpoint: Option<A>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+impl<A> Default for JobRecord<A> {
+    fn default() -> Self {
+        Self {
+            attempts: 0,
+            checkpoint: None,
+            finished_at: None,
+            last_error: None,
+            started_at: None,
+        }
+    }
+}
+
+// Before job records, only the checkpoint itself was stored.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredProgress<A> {
+    Record(JobRecord<A>),
+    Checkpoint(A),
+}
+
+pub struct JobProgress<'a, A: Clone + DeserializeOwned + Serialize + Sync> {
     key_value_store: &'a dyn KeyValueStore,
-    key: &'static str,
+    key: String,
     phantom: std::marker::PhantomData<A>,
 }
 
-impl<A: Serialize + DeserializeOwned> JobProgress<'_, A> {
+impl<A: Clone + DeserializeOwned + Serialize + Sync> JobProgress<'_, A> {
     pub fn new<'a>(
-        key: &'static str,
+        key: impl Into<String>,
         key_value_store: &'a impl KeyValueStore,
     ) -> JobProgress<'a, A> {
         JobProgress {
-            key,
+            key: key.into(),
             key_value_store,
             phantom: std::marker::PhantomData,
         }
     }
 
+    pub async fn record(&self) -> JobRecord<A> {
+        match self.key_value_store.get(&self.key).await.unwrap() {
+            None => JobRecord::default(),
+            Some(StoredProgress::Record(record)) => record,
+            Some(StoredProgress::Checkpoint(checkpoint)) => JobRecord {
+                checkpoint: Some(checkpoint),
+                ..JobRecord::default()
+            },
+        }
+    }
+
+    async fn set_record(&self, record: &JobRecord<A>) {
+        self.key_value_store.set(&self.key, record).await.unwrap()
+    }
+
+    /// The checkpoint to resume from, if any.
     pub async fn get(&self) -> Option<A> {
-        self.key_value_store
-            .get_value(self.key)
-            .await
-            .map(|value| serde_json::from_value(value).unwrap())
+        self.record().await.checkpoint
     }
 
     pub async fn set(&self, value: &A) {
-        self.key_value_store
-            .set_value(self.key, &serde_json::to_value(value).unwrap())
-            .await
+        let mut record = self.record().await;
+        record.checkpoint = Some(value.clone());
+        self.set_record(&record).await
+    }
+
+    /// Records the start of an attempt. The checkpoint is kept, the attempt resumes from it.
+    pub async fn start(&self) {
+        let mut record = self.record().await;
+        record.attempts += 1;
+        record.started_at = Some(Utc::now());
+        record.finished_at = None;
+        self.set_record(&record).await
+    }
+
+    pub async fn finish(&self) {
+        let mut record = self.record().await;
+        record.finished_at = Some(Utc::now());
+        record.last_error = None;
+        self.set_record(&record).await
+    }
+
+    pub async fn fail(&self, error: &str) {
+        let mut record = self.record().await;
+        record.last_error = Some(error.to_string());
+        self.set_record(&record).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{db, key_value_store::KeyValueStorePostgres};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn record_attempts_test() {
+        let test_db = db::tests::TestDb::new().await;
+        let key_value_store = KeyValueStorePostgres::new(test_db.pool.clone());
+        let job_progress = JobProgress::<i32>::new("record-attempts-test", &key_value_store);
+
+        job_progress.start().await;
+        job_progress.set(&10).await;
+        job_progress.fail("node unavailable").await;
+        job_progress.start().await;
+
+        let record = job_progress.record().await;
+        assert_eq!(record.attempts, 2);
+        assert_eq!(record.checkpoint, Some(10));
+        assert_eq!(record.last_error.as_deref(), Some("node unavailable"));
+        assert!(record.started_at.is_some());
+        assert!(record.finished_at.is_none());
+
+        job_progress.finish().await;
+
+        let record = job_progress.record().await;
+        assert!(record.finished_at.is_some());
+        assert!(record.last_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn read_bare_checkpoint_test() {
+        let test_db = db::tests::TestDb::new().await;
+        let key_value_store = KeyValueStorePostgres::new(test_db.pool.clone());
+        key_value_store
+            .set_value("read-bare-checkpoint-test", &json!(1234))
+            .await;
+
+        let job_progress = JobProgress::<i32>::new("read-bare-checkpoint-test", &key_value_store);
+
+        assert_eq!(job_progress.get().await, Some(1234));
+        assert_eq!(job_progress.record().await.attempts, 0);
     }
 }
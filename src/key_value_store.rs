@@ -1,9 +1,47 @@
+//! Key Value Store
+//! ===============
+//! JSON values stored by key. Values are permanent by default. Transient ones, like temporary
+//! exports, may be stored with a time to live, once expired they read as missing and get deleted
+//! by the `delete-expired-keys` job.
+//!
+//! Prefer the typed `get` and `set`, through `KeyValueStoreExt` or with an executor, over
+//! handling `Value`s directly.
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use chrono::{Duration, Utc};
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use sqlx::{PgExecutor, PgPool};
+use thiserror::Error;
 use tracing::debug;
 
+#[derive(Debug, Error)]
+pub enum KeyValueStoreError {
+    #[error("failed to deserialize value for key {key}")]
+    Deserialize {
+        key: String,
+        source: serde_json::Error,
+    },
+    #[error("failed to serialize value for key {key}")]
+    Serialize {
+        key: String,
+        source: serde_json::Error,
+    },
+}
+
+fn deserialize<T: DeserializeOwned>(key: &str, value: Value) -> Result<T, KeyValueStoreError> {
+    serde_json::from_value(value).map_err(|source| KeyValueStoreError::Deserialize {
+        key: key.to_string(),
+        source,
+    })
+}
+
+fn serialize<T: Serialize + ?Sized>(key: &str, value: &T) -> Result<Value, KeyValueStoreError> {
+    serde_json::to_value(value).map_err(|source| KeyValueStoreError::Serialize {
+        key: key.to_string(),
+        source,
+    })
+}
+
 pub async fn get_value(executor: impl PgExecutor<'_>, key: &str) -> Option<Value> {
     debug!(key = key, "getting key value pair");
 
@@ -11,6 +49,7 @@ pub async fn get_value(executor: impl PgExecutor<'_>, key: &str) -> Option<Value
         "
         SELECT value FROM key_value_store
         WHERE key = $1
+        AND (expires_at IS NULL OR expires_at > NOW())
         ",
         key,
     )
@@ -27,7 +66,8 @@ pub async fn set_value(executor: impl PgExecutor<'_>, key: &str, value: &Value)
         "
         INSERT INTO key_value_store (key, value) VALUES ($1, $2)
         ON CONFLICT (key) DO UPDATE SET
-            value = excluded.value
+            value = excluded.value,
+            expires_at = NULL
         ",
         key,
         value
@@ -37,37 +77,109 @@ pub async fn set_value(executor: impl PgExecutor<'_>, key: &str, value: &Value)
     .unwrap();
 }
 
+pub async fn get<T: DeserializeOwned>(
+    executor: impl PgExecutor<'_>,
+    key: &str,
+) -> Result<Option<T>, KeyValueStoreError> {
+    get_value(executor, key)
+        .await
+        .map(|value| deserialize(key, value))
+        .transpose()
+}
+
+pub async fn set<T: Serialize + ?Sized>(
+    executor: impl PgExecutor<'_>,
+    key: &str,
+    value: &T,
+) -> Result<(), KeyValueStoreError> {
+    let value = serialize(key, value)?;
+    set_value(executor, key, &value).await;
+    Ok(())
+}
+
+/// Like `set_value`, but the value expires after the given time to live.
+pub async fn set_value_with_ttl(
+    executor: impl PgExecutor<'_>,
+    key: &str,
+    value: &Value,
+    ttl: &Duration,
+) {
+    debug!(key, ttl_secs = ttl.num_seconds(), "storing key with ttl");
+
+    sqlx::query!(
+        "
+        INSERT INTO key_value_store (key, value, expires_at) VALUES ($1, $2, $3)
+        ON CONFLICT (key) DO UPDATE SET
+            value = excluded.value,
+            expires_at = excluded.expires_at
+        ",
+        key,
+        value,
+        Utc::now() + *ttl
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Deletes expired keys, returns how many were deleted.
+pub async fn delete_expired(executor: impl PgExecutor<'_>) -> u64 {
+    sqlx::query!(
+        "
+        DELETE FROM key_value_store
+        WHERE expires_at <= NOW()
+        "
+    )
+    .execute(executor)
+    .await
+    .unwrap()
+    .rows_affected()
+}
+
 #[async_trait]
-pub trait KeyValueStore {
+pub trait KeyValueStore: Send + Sync {
     async fn get_value(&self, key: &str) -> Option<Value>;
     async fn set_value(&self, key: &str, value: &Value);
 }
 
-pub struct KeyValueStorePostgres {
-    db_pool: PgPool,
+/// Typed access on top of any `KeyValueStore`.
+#[async_trait]
+pub trait KeyValueStoreExt {
+    async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, KeyValueStoreError>;
+    async fn set<T: Serialize + Sync + ?Sized>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), KeyValueStoreError>;
 }
 
-impl KeyValueStorePostgres {
-    pub fn new(db_pool: PgPool) -> Self {
-        Self { db_pool }
+#[async_trait]
+impl<S: KeyValueStore + ?Sized> KeyValueStoreExt for S {
+    async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, KeyValueStoreError> {
+        self.get_value(key)
+            .await
+            .map(|value| deserialize(key, value))
+            .transpose()
     }
 
-    pub async fn get_deserializable_value<D: for<'de> Deserialize<'de>>(
+    async fn set<T: Serialize + Sync + ?Sized>(
         &self,
         key: &str,
-    ) -> Option<D> {
-        get_value(&self.db_pool, key)
-            .await
-            .and_then(|value| serde_json::from_value(value).ok())
+        value: &T,
+    ) -> Result<(), KeyValueStoreError> {
+        let value = serialize(key, value)?;
+        self.set_value(key, &value).await;
+        Ok(())
     }
+}
 
-    pub async fn set_serializable_value<S: Serialize>(&self, key: &str, value: S) {
-        set_value(
-            &self.db_pool,
-            key,
-            &serde_json::to_value(value).expect("expect value to be serializable"),
-        )
-        .await
+pub struct KeyValueStorePostgres {
+    db_pool: PgPool,
+}
+
+impl KeyValueStorePostgres {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
     }
 }
 
@@ -108,14 +220,13 @@ mod tests {
             age: 29,
         };
 
-        set_value(
-            &mut *transaction,
-            "test-key",
-            &serde_json::to_value(&test_json).unwrap(),
-        )
-        .await;
-        let value = get_value(&mut *transaction, "test-key").await.unwrap();
-        let test_json_from_db = serde_json::from_value::<TestJson>(value).unwrap();
+        set(&mut *transaction, "test-key", &test_json)
+            .await
+            .unwrap();
+        let test_json_from_db = get::<TestJson>(&mut *transaction, "test-key")
+            .await
+            .unwrap()
+            .unwrap();
 
         assert_eq!(test_json_from_db, test_json)
     }
@@ -138,6 +249,49 @@ mod tests {
         assert_eq!(test_json_from_db, None)
     }
 
+    #[tokio::test]
+    async fn expired_value_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        set_value_with_ttl(
+            &mut *transaction,
+            "expired-key",
+            &json!("transient"),
+            &Duration::seconds(-1),
+        )
+        .await;
+        set_value_with_ttl(
+            &mut *transaction,
+            "transient-key",
+            &json!("transient"),
+            &Duration::hours(1),
+        )
+        .await;
+
+        assert_eq!(get_value(&mut *transaction, "expired-key").await, None);
+        assert_eq!(
+            get_value(&mut *transaction, "transient-key").await,
+            Some(json!("transient"))
+        );
+
+        assert_eq!(delete_expired(&mut *transaction).await, 1);
+
+        // Storing without a ttl makes a key permanent again.
+        set_value_with_ttl(
+            &mut *transaction,
+            "expired-key",
+            &json!("transient"),
+            &Duration::seconds(-1),
+        )
+        .await;
+        set_value(&mut *transaction, "expired-key", &json!("permanent")).await;
+        assert_eq!(
+            get_value(&mut *transaction, "expired-key").await,
+            Some(json!("permanent"))
+        );
+    }
+
     #[tokio::test]
     async fn test_set_and_get_value() {
         let test_db = db::tests::TestDb::new().await;
@@ -146,10 +300,10 @@ mod tests {
         // Set a value for a key
         let key = "test_key";
         let value = "test_value";
-        store.set_serializable_value(key, value).await;
+        store.set(key, value).await.unwrap();
 
         // Get the value for the key and deserialize it
-        let retrieved_value: Option<String> = store.get_deserializable_value(key).await;
+        let retrieved_value: Option<String> = store.get(key).await.unwrap();
 
         // Assert that the value retrieved is the same as the one set
         assert_eq!(retrieved_value, Some(value.to_owned()));
@@ -162,9 +316,23 @@ mod tests {
 
         // Get the value for a nonexistent key
         let key = "nonexistent_key";
-        let retrieved_value: Option<String> = store.get_deserializable_value(key).await;
+        let retrieved_value: Option<String> = store.get(key).await.unwrap();
 
         // Assert that the retrieved value is None
         assert_eq!(retrieved_value, None);
     }
+
+    #[tokio::test]
+    async fn get_wrong_type_test() {
+        let test_db = db::tests::TestDb::new().await;
+        let store = KeyValueStorePostgres::new(test_db.pool.clone());
+
+        store.set("wrong_type_key", &json!("text")).await.unwrap();
+        let result = store.get::<i32>("wrong_type_key").await;
+
+        assert!(matches!(
+            result,
+            Err(KeyValueStoreError::Deserialize { .. })
+        ));
+    }
 }
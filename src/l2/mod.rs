@@ -0,0 +1,269 @@
+//! # L2
+//! Follows OP stack and Arbitrum chains, listed under `[[l2_chains]]` in the config file, and
+//! puts their fees next to the L1 burn. For every block we store the L2 base fee, and the L1 data
+//! fee, what users paid the L2 to post their transactions to L1.
+//!
+//! Neither kind of chain destroys its base fee, it goes to a fee vault. The combined view still
+//! adds it to the L1 burn, it's what users paid beyond tips. L1 data fees are left out of the
+//! total, what the L2 spends on L1 already shows up in the L1 burn.
+//!
+//! Chains are followed from their head when first configured, growing time frames only include
+//! blocks from then on.
+mod node;
+mod store;
+
+use std::{collections::HashMap, time::Duration};
+
+use enum_iterator::all;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use crate::{
+    burn_sums,
+    caching::{self, CacheKey},
+    config, db, log,
+    time_frames::TimeFrame,
+    units::EthNewtype,
+};
+
+use self::node::L2Node;
+
+/// Arbitrum One passes i32::MAX blocks within the decade, L2 block numbers are i64.
+pub type L2BlockNumber = i64;
+
+/// Blocks requested per round trip, enough to catch up on chains producing several a second.
+const BLOCKS_PER_BATCH: i64 = 50;
+/// How long to wait before asking again for a block the node doesn't have yet.
+const NEW_BLOCK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long to wait after a failed RPC call.
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum L2Kind {
+    Arbitrum,
+    OpStack,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct L2ChainConfig {
+    pub kind: L2Kind,
+    /// Stored with each block, e.g. `base`.
+    pub name: String,
+    pub rpc_url: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct L2ChainFees {
+    pub base_fees: EthNewtype,
+    /// Last block included.
+    pub block_number: L2BlockNumber,
+    pub chain: String,
+    pub l1_data_fees: EthNewtype,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct CombinedFees {
+    l1_burn: EthNewtype,
+    l2_base_fees: EthNewtype,
+    l2_l1_data_fees: EthNewtype,
+    /// The L1 burn plus the L2 base fees.
+    total: EthNewtype,
+}
+
+impl CombinedFees {
+    fn new(l1_burn: EthNewtype, l2_fees: &[L2ChainFees]) -> Self {
+        let l2_base_fees = l2_fees
+            .iter()
+            .fold(EthNewtype(0.0), |sum, fees| sum + fees.base_fees);
+        let l2_l1_data_fees = l2_fees
+            .iter()
+            .fold(EthNewtype(0.0), |sum, fees| sum + fees.l1_data_fees);
+        Self {
+            l1_burn,
+            l2_base_fees,
+            l2_l1_data_fees,
+            total: l1_burn + l2_base_fees,
+        }
+    }
+}
+
+/// Stores the next batch of blocks of the chain. Returns whether there were any, `false` when
+/// we're at the head. When the first block doesn't build on the last stored one, the last stored
+/// one was reorged out, and is dropped instead. A batch is only stored up to where it stops
+/// building on itself, the next batch sorts out the reorg.
+async fn sync_next_blocks(db_pool: &PgPool, chain: &str, node: &L2Node) -> anyhow::Result<bool> {
+    let last_block = store::last_block(db_pool, chain).await;
+    let next_number = match &last_block {
+        Some((number, _)) => number + 1,
+        None => {
+            let head = node.get_latest_block_number().await?;
+            info!(
+                chain,
+                head, "no blocks stored for l2 chain, starting from its head"
+            );
+            head
+        }
+    };
+
+    let mut blocks = node.get_blocks_from(next_number, BLOCKS_PER_BATCH).await?;
+    let first_block = match blocks.first() {
+        Some(block) => block,
+        None => return Ok(false),
+    };
+
+    if let Some((number, hash)) = last_block {
+        if first_block.parent_hash != hash {
+            warn!(chain, number, "l2 block reorged out, dropping it");
+            store::delete_block(db_pool, chain, number).await;
+            return Ok(true);
+        }
+    }
+
+    if let Some(break_index) = blocks
+        .windows(2)
+        .position(|pair| pair[1].parent_hash != pair[0].hash)
+    {
+        blocks.truncate(break_index + 1);
+    }
+
+    let numbers = blocks.iter().map(|block| block.number).collect::<Vec<_>>();
+    let l1_data_fees = node.get_l1_data_fees(&numbers).await?;
+
+    let mut transaction = db_pool.begin().await?;
+    for (block, l1_data_fee) in blocks.iter().zip(l1_data_fees.iter()) {
+        store::store_block(&mut *transaction, chain, block, l1_data_fee).await;
+    }
+    transaction.commit().await?;
+
+    debug!(
+        chain,
+        from = numbers.first(),
+        to = numbers.last(),
+        "stored l2 blocks"
+    );
+
+    Ok(true)
+}
+
+async fn sync_chain(db_pool: PgPool, chain: &L2ChainConfig) {
+    let node = L2Node::new(chain);
+
+    loop {
+        match sync_next_blocks(&db_pool, &chain.name, &node).await {
+            Ok(true) => (),
+            Ok(false) => sleep(NEW_BLOCK_POLL_INTERVAL).await,
+            Err(err) => {
+                warn!(chain = %chain.name, %err, "failed to sync l2 blocks, retrying");
+                sleep(RETRY_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Sums L2 fees over the same windows the last burn sums cover, so the combined view compares
+/// like with like.
+async fn update_l2_fees(db_pool: &PgPool) {
+    let mut connection = db_pool.acquire().await.unwrap();
+    let burn_sums = match burn_sums::last_burn_sums(&mut connection).await {
+        Some(burn_sums) => burn_sums,
+        None => {
+            warn!("no burn sums stored yet, skipping l2 fees");
+            return;
+        }
+    };
+
+    let arbitrum_chains = config::CONFIG
+        .l2_chains()
+        .iter()
+        .filter(|chain| chain.kind == L2Kind::Arbitrum)
+        .map(|chain| chain.name.clone())
+        .collect::<Vec<_>>();
+
+    let mut l2_fees = HashMap::new();
+    let mut combined_fees = HashMap::new();
+    for time_frame in all::<TimeFrame>() {
+        let burn_sum = &burn_sums[time_frame];
+        let start = match time_frame {
+            TimeFrame::Growing(growing_time_frame) => growing_time_frame.start_timestamp(),
+            TimeFrame::Limited(limited_time_frame) => {
+                burn_sum.timestamp - limited_time_frame.duration()
+            }
+        };
+        let fees = store::fees_between(
+            &mut *connection,
+            &start,
+            &burn_sum.timestamp,
+            &arbitrum_chains,
+        )
+        .await;
+        combined_fees.insert(time_frame, CombinedFees::new(burn_sum.sum.eth, &fees));
+        l2_fees.insert(time_frame, fees);
+    }
+
+    caching::update_and_publish(db_pool, &CacheKey::L2Fees, &l2_fees).await;
+    caching::update_and_publish(db_pool, &CacheKey::CombinedFees, &combined_fees).await;
+    debug!("published l2 fees");
+}
+
+pub async fn sync_l2_fees() {
+    log::init_with_env();
+
+    let chains = config::CONFIG.l2_chains();
+    if chains.is_empty() {
+        warn!("no l2 chains configured, set them under [[l2_chains]] in the config file");
+        return;
+    }
+
+    info!(count = chains.len(), "syncing l2 fees");
+
+    let db_pool = db::get_db_pool("sync-l2-fees").await;
+
+    for chain in chains {
+        tokio::spawn(sync_chain(db_pool.clone(), chain));
+    }
+
+    loop {
+        sleep(PUBLISH_INTERVAL).await;
+        update_l2_fees(&db_pool).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combined_fees_test() {
+        let l2_fees = vec![
+            L2ChainFees {
+                base_fees: EthNewtype(1.0),
+                block_number: 1,
+                chain: "arbitrum".to_string(),
+                l1_data_fees: EthNewtype(0.5),
+            },
+            L2ChainFees {
+                base_fees: EthNewtype(2.0),
+                block_number: 1,
+                chain: "base".to_string(),
+                l1_data_fees: EthNewtype(0.25),
+            },
+        ];
+
+        let combined_fees = CombinedFees::new(EthNewtype(10.0), &l2_fees);
+
+        assert_eq!(
+            combined_fees,
+            CombinedFees {
+                l1_burn: EthNewtype(10.0),
+                l2_base_fees: EthNewtype(3.0),
+                l2_l1_data_fees: EthNewtype(0.75),
+                total: EthNewtype(13.0),
+            }
+        );
+    }
+}
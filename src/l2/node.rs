@@ -0,0 +1,336 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use crate::{
+    execution_chain::decoders::{
+        from_i64_hex_str, from_option_u128_hex_str, from_u64_hex_str, from_unix_timestamp_hex_str,
+    },
+    units::WeiNewtype,
+};
+
+use super::{L2BlockNumber, L2ChainConfig, L2Kind};
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct L2Block {
+    #[serde(deserialize_with = "from_u64_hex_str")]
+    pub base_fee_per_gas: u64,
+    #[serde(deserialize_with = "from_u64_hex_str")]
+    pub gas_used: u64,
+    pub hash: String,
+    #[serde(deserialize_with = "from_i64_hex_str")]
+    pub number: L2BlockNumber,
+    pub parent_hash: String,
+    #[serde(deserialize_with = "from_unix_timestamp_hex_str")]
+    pub timestamp: DateTime<Utc>,
+}
+
+/// The fields either kind of chain adds to receipts to charge for posting the transaction to L1.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct L2Receipt {
+    #[serde(default, deserialize_with = "from_option_u128_hex_str")]
+    effective_gas_price: Option<u128>,
+    /// Arbitrum, L2 gas bought to pay for the L1 calldata.
+    #[serde(default, deserialize_with = "from_option_u128_hex_str")]
+    gas_used_for_l1: Option<u128>,
+    /// OP stack, in wei. Absent on deposit transactions, which L1 already paid for.
+    #[serde(default, deserialize_with = "from_option_u128_hex_str")]
+    l1_fee: Option<u128>,
+}
+
+impl L2Receipt {
+    fn l1_data_fee(&self, kind: L2Kind) -> u128 {
+        match kind {
+            L2Kind::Arbitrum => {
+                self.gas_used_for_l1.unwrap_or(0) * self.effective_gas_price.unwrap_or(0)
+            }
+            L2Kind::OpStack => self.l1_fee.unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i32,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse<T> {
+    error: Option<RpcError>,
+    #[serde(default)]
+    id: usize,
+    result: Option<T>,
+}
+
+impl<T> RpcResponse<T> {
+    fn into_result(self, method: &str) -> Result<Option<T>> {
+        match self.error {
+            Some(RpcError { code, message }) => Err(anyhow!("{method} failed, {code} {message}")),
+            None => Ok(self.result),
+        }
+    }
+}
+
+/// Talks JSON-RPC over HTTP, L2 RPC providers rarely offer websockets.
+pub struct L2Node {
+    client: reqwest::Client,
+    kind: L2Kind,
+    rpc_url: String,
+}
+
+impl L2Node {
+    pub fn new(chain: &L2ChainConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            kind: chain.kind,
+            rpc_url: chain.rpc_url.clone(),
+        }
+    }
+
+    /// `None` when the node answers with a null result, e.g. for a block it doesn't have yet.
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<Option<T>> {
+        let response = self
+            .client
+            .post(&self.rpc_url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<RpcResponse<T>>()
+            .await?;
+
+        response.into_result(method)
+    }
+
+    /// Sends one request per params in a single JSON-RPC batch, L2 blocks come faster than one
+    /// round trip each allows. Results are in the order of the params.
+    async fn call_batch<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+    ) -> Result<Vec<Option<T>>> {
+        let requests = params
+            .into_iter()
+            .enumerate()
+            .map(|(id, params)| {
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": method,
+                    "params": params,
+                })
+            })
+            .collect::<Vec<_>>();
+        let count = requests.len();
+
+        let mut responses = self
+            .client
+            .post(&self.rpc_url)
+            .json(&requests)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<RpcResponse<T>>>()
+            .await?;
+
+        if responses.len() != count {
+            return Err(anyhow!(
+                "{method} batch of {count} got {} responses",
+                responses.len()
+            ));
+        }
+
+        // Servers may answer a batch in any order.
+        responses.sort_by_key(|response| response.id);
+        responses
+            .into_iter()
+            .map(|response| response.into_result(method))
+            .collect()
+    }
+
+    pub async fn get_latest_block_number(&self) -> Result<L2BlockNumber> {
+        let number = self
+            .call::<String>("eth_blockNumber", json!([]))
+            .await?
+            .ok_or_else(|| anyhow!("eth_blockNumber returned no block number"))?;
+        let digits = number.strip_prefix("0x").unwrap_or(&number);
+        Ok(L2BlockNumber::from_str_radix(digits, 16)?)
+    }
+
+    /// The blocks from `start` on, up to `count` of them. Stops before the first block the node
+    /// doesn't have yet.
+    pub async fn get_blocks_from(&self, start: L2BlockNumber, count: i64) -> Result<Vec<L2Block>> {
+        let params = (start..start + count)
+            .map(|number| json!([format!("{number:#x}"), false]))
+            .collect();
+        let blocks = self
+            .call_batch::<L2Block>("eth_getBlockByNumber", params)
+            .await?
+            .into_iter()
+            .map_while(|block| block)
+            .collect();
+        Ok(blocks)
+    }
+
+    /// What the transactions of each block paid to have the L2 post them to L1.
+    pub async fn get_l1_data_fees(&self, numbers: &[L2BlockNumber]) -> Result<Vec<WeiNewtype>> {
+        let params = numbers
+            .iter()
+            .map(|number| json!([format!("{number:#x}")]))
+            .collect();
+        self.call_batch::<Vec<L2Receipt>>("eth_getBlockReceipts", params)
+            .await?
+            .into_iter()
+            .zip(numbers)
+            .map(|(receipts, number)| {
+                let receipts =
+                    receipts.ok_or_else(|| anyhow!("no receipts for l2 block {number}"))?;
+                let l1_data_fee = receipts
+                    .iter()
+                    .map(|receipt| receipt.l1_data_fee(self.kind))
+                    .sum::<u128>();
+                Ok(WeiNewtype(l1_data_fee as i128))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_node(kind: L2Kind, rpc_url: &str) -> L2Node {
+        L2Node::new(&L2ChainConfig {
+            kind,
+            name: "test".to_string(),
+            rpc_url: rpc_url.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn get_blocks_from_test() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_body(
+                json!([
+                    { "jsonrpc": "2.0", "id": 2, "result": null },
+                    {
+                        "jsonrpc": "2.0",
+                        "id": 0,
+                        "result": {
+                            "baseFeePerGas": "0xfa",
+                            "gasUsed": "0x5208",
+                            "hash": "0xb",
+                            "number": "0x10",
+                            "parentHash": "0xa",
+                            "timestamp": "0x65f1d8b0"
+                        }
+                    },
+                    {
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": {
+                            "baseFeePerGas": "0xfa",
+                            "gasUsed": "0x0",
+                            "hash": "0xc",
+                            "number": "0x11",
+                            "parentHash": "0xb",
+                            "timestamp": "0x65f1d8b1"
+                        }
+                    }
+                ])
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let node = test_node(L2Kind::OpStack, &server.url());
+        let blocks = node.get_blocks_from(16, 3).await.unwrap();
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].number, 16);
+        assert_eq!(blocks[0].base_fee_per_gas, 250);
+        assert_eq!(blocks[0].gas_used, 21000);
+        assert_eq!(blocks[0].parent_hash, "0xa");
+        assert_eq!(blocks[1].number, 17);
+    }
+
+    #[tokio::test]
+    async fn get_l1_data_fees_op_stack_test() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_body(
+                json!([
+                    {
+                        "jsonrpc": "2.0",
+                        "id": 0,
+                        "result": [
+                            { "effectiveGasPrice": "0x0" },
+                            { "effectiveGasPrice": "0x10", "l1Fee": "0x64" },
+                            { "effectiveGasPrice": "0x10", "l1Fee": "0x1" }
+                        ]
+                    },
+                    { "jsonrpc": "2.0", "id": 1, "result": [] }
+                ])
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let node = test_node(L2Kind::OpStack, &server.url());
+        let l1_data_fees = node.get_l1_data_fees(&[16, 17]).await.unwrap();
+
+        assert_eq!(l1_data_fees, vec![WeiNewtype(101), WeiNewtype(0)]);
+    }
+
+    #[test]
+    fn l1_data_fee_arbitrum_test() {
+        let receipt = serde_json::from_value::<L2Receipt>(json!({
+            "effectiveGasPrice": "0xa",
+            "gasUsedForL1": "0x3"
+        }))
+        .unwrap();
+
+        assert_eq!(receipt.l1_data_fee(L2Kind::Arbitrum), 30);
+        assert_eq!(receipt.l1_data_fee(L2Kind::OpStack), 0);
+    }
+
+    #[tokio::test]
+    async fn rpc_error_test() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_body(
+                json!([{
+                    "jsonrpc": "2.0",
+                    "id": 0,
+                    "error": { "code": -32601, "message": "method not found" }
+                }])
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let node = test_node(L2Kind::Arbitrum, &server.url());
+        let err = node.get_l1_data_fees(&[16]).await.unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "eth_getBlockReceipts failed, -32601 method not found"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgExecutor;
+
+use crate::units::{EthNewtype, WeiNewtype};
+
+use super::{node::L2Block, L2BlockNumber, L2ChainFees};
+
+pub async fn store_block(
+    executor: impl PgExecutor<'_>,
+    chain: &str,
+    block: &L2Block,
+    l1_data_fee: &WeiNewtype,
+) {
+    sqlx::query!(
+        "
+        INSERT INTO l2_blocks (
+            chain,
+            number,
+            hash,
+            parent_hash,
+            timestamp,
+            base_fee_per_gas,
+            gas_used,
+            l1_data_fee
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ",
+        chain,
+        block.number,
+        block.hash,
+        block.parent_hash,
+        block.timestamp,
+        block.base_fee_per_gas as i64,
+        block.gas_used as i64,
+        l1_data_fee as &WeiNewtype,
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Number and hash of the last block stored for the chain.
+pub async fn last_block(
+    executor: impl PgExecutor<'_>,
+    chain: &str,
+) -> Option<(L2BlockNumber, String)> {
+    sqlx::query!(
+        "
+        SELECT number, hash
+        FROM l2_blocks
+        WHERE chain = $1
+        ORDER BY number DESC
+        LIMIT 1
+        ",
+        chain,
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| (row.number, row.hash))
+}
+
+pub async fn delete_block(executor: impl PgExecutor<'_>, chain: &str, number: L2BlockNumber) {
+    sqlx::query!(
+        "DELETE FROM l2_blocks WHERE chain = $1 AND number = $2",
+        chain,
+        number
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Fees of every chain with blocks in `start..=end`. Arbitrum charges the L1 data fee as L2 gas,
+/// `gasUsedForL1`, so for `arbitrum_chains` it is taken out of the base fees.
+pub async fn fees_between(
+    executor: impl PgExecutor<'_>,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    arbitrum_chains: &[String],
+) -> Vec<L2ChainFees> {
+    sqlx::query!(
+        r#"
+        SELECT
+            chain,
+            MAX(number) AS "block_number!",
+            SUM(
+                base_fee_per_gas::NUMERIC(78) * gas_used::NUMERIC(78)
+                - CASE WHEN chain = ANY($3) THEN l1_data_fee ELSE 0 END
+            )::NUMERIC(78) AS "base_fees!: WeiNewtype",
+            SUM(l1_data_fee)::NUMERIC(78) AS "l1_data_fees!: WeiNewtype"
+        FROM
+            l2_blocks
+        WHERE
+            timestamp >= $1 AND timestamp <= $2
+        GROUP BY chain
+        ORDER BY chain ASC
+        "#,
+        start,
+        end,
+        arbitrum_chains,
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| L2ChainFees {
+        base_fees: EthNewtype::from(row.base_fees),
+        block_number: row.block_number,
+        chain: row.chain,
+        l1_data_fees: EthNewtype::from(row.l1_data_fees),
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, SubsecRound};
+    use test_context::test_context;
+
+    use crate::db::tests::TestDb;
+
+    use super::*;
+
+    fn test_block(number: L2BlockNumber, timestamp: DateTime<Utc>) -> L2Block {
+        L2Block {
+            base_fee_per_gas: 1_000_000_000,
+            gas_used: 1_000_000_000,
+            hash: format!("0x{number}"),
+            number,
+            parent_hash: format!("0x{}", number - 1),
+            timestamp,
+        }
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn last_block_test(test_db: &TestDb) {
+        let now = Utc::now().trunc_subsecs(0);
+        store_block(&test_db.pool, "base", &test_block(1, now), &WeiNewtype(0)).await;
+        store_block(&test_db.pool, "base", &test_block(2, now), &WeiNewtype(0)).await;
+
+        assert_eq!(
+            last_block(&test_db.pool, "base").await,
+            Some((2, "0x2".to_string()))
+        );
+        assert_eq!(last_block(&test_db.pool, "arbitrum").await, None);
+
+        delete_block(&test_db.pool, "base", 2).await;
+
+        assert_eq!(
+            last_block(&test_db.pool, "base").await,
+            Some((1, "0x1".to_string()))
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn fees_between_test(test_db: &TestDb) {
+        let now = Utc::now().trunc_subsecs(0);
+        let one_eth = WeiNewtype::from_eth(1);
+        store_block(
+            &test_db.pool,
+            "base",
+            &test_block(1, now - Duration::hours(2)),
+            &one_eth,
+        )
+        .await;
+        store_block(&test_db.pool, "base", &test_block(2, now), &one_eth).await;
+        store_block(
+            &test_db.pool,
+            "arbitrum",
+            &test_block(1, now),
+            &WeiNewtype(WeiNewtype::from_eth(1).0 / 2),
+        )
+        .await;
+
+        let fees = fees_between(
+            &test_db.pool,
+            &(now - Duration::hours(1)),
+            &now,
+            &["arbitrum".to_string()],
+        )
+        .await;
+
+        assert_eq!(
+            fees,
+            vec![
+                L2ChainFees {
+                    // 1 ETH of gas of which 0.5 ETH paid for L1.
+                    base_fees: EthNewtype(0.5),
+                    block_number: 1,
+                    chain: "arbitrum".to_string(),
+                    l1_data_fees: EthNewtype(0.5),
+                },
+                L2ChainFees {
+                    base_fees: EthNewtype(1.0),
+                    block_number: 2,
+                    chain: "base".to_string(),
+                    l1_data_fees: EthNewtype(1.0),
+                },
+            ]
+        );
+    }
+}
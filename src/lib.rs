@@ -1,59 +1,125 @@
+pub mod analysis;
+mod analytics_sinks;
 pub mod beacon_chain;
+mod blob_transactions;
+mod blob_usage;
+#[cfg(feature = "nats")]
+mod block_events;
+mod btc_price;
+mod burn_anomalies;
 mod burn_rates;
+mod burn_records;
 mod burn_sums;
 pub mod caching;
+mod censorship;
+mod cli;
+mod config;
+mod contract_deployments;
 mod data_integrity;
 pub mod db;
+mod difficulty;
+mod downsampling;
 mod env;
 pub mod eth_supply;
-mod eth_time;
 mod etherscan;
 pub mod execution_chain;
+mod export;
+mod fee_suggestions;
+mod gas_utilization;
 mod gauges;
+mod heal;
 mod health;
 mod issuance_breakdown;
 pub mod job_progress;
 mod json_codecs;
 pub mod key_value_store;
+mod l2;
 pub mod log;
+mod market_caps;
 pub mod mev_blocks;
+mod moving_averages;
+mod network;
+mod partitions;
 mod performance;
 mod phoenix;
+pub mod rollback;
+mod row_writer;
+mod scheduler;
 mod serve;
+mod shutdown;
 mod supply_dashboard_analysis;
+mod supply_milestones;
 pub mod time;
 mod time_frames;
+mod transaction_stats;
 pub mod units;
 mod update_by_hand;
 mod usd_price;
+mod warm_caches;
+mod webhooks;
 
+pub use beacon_chain::backfill_balances_to_london;
+pub use beacon_chain::backfill_daily_balances_to_london;
+pub use beacon_chain::backfill_hourly_balances;
+pub use beacon_chain::backfill_hourly_balances_to_london;
 pub use beacon_chain::effective_balance_sums;
+pub use beacon_chain::effective_balance_sums::update_effective_balance_sum;
 pub use beacon_chain::heal_beacon_states;
 pub use beacon_chain::heal_block_hashes;
+pub use beacon_chain::rewards::update_validator_rewards;
 pub use beacon_chain::sync_beacon_states;
 pub use beacon_chain::update_issuance_estimate;
 
+#[cfg(feature = "redis")]
+pub use caching::relay_cache_updates_to_redis;
+
+pub use cli::run_cli;
+
+pub use btc_price::heal_btc_prices;
+pub use btc_price::record_btc_price;
+
+pub use burn_records::export_burn_records;
+
+pub use burn_sums::backfill_burn_sums;
+pub use burn_sums::sum_burn;
+pub use burn_sums::verify_burn_sums;
+
+pub use data_integrity::backfill_gaps;
 pub use data_integrity::check_beacon_state_gaps;
 pub use data_integrity::check_blocks_gaps;
 
+pub use eth_supply::audit_supply;
 pub use eth_supply::export_daily_supply_since_merge;
 pub use eth_supply::export_thousandth_epoch_supply;
 pub use eth_supply::fill_eth_supply_gaps;
+pub use eth_supply::update_supply_projection_inputs;
 pub use eth_supply::SupplyAtTime;
 
+pub use execution_chain::backfill_execution_supply;
+pub use execution_chain::backfill_pow_issuance;
+pub use execution_chain::backfill_supply_deltas;
 pub use execution_chain::export_blocks_from_august;
 pub use execution_chain::export_blocks_from_london;
 pub use execution_chain::export_execution_supply_deltas;
+pub use execution_chain::heal_blob_gas;
 pub use execution_chain::summary_from_deltas_csv;
 pub use execution_chain::sync_execution_blocks;
+pub use execution_chain::sync_execution_blocks_with_analyses;
 pub use execution_chain::sync_execution_supply_deltas;
+pub use execution_chain::verify_execution_supply;
 pub use execution_chain::write_execution_heads_log;
 pub use execution_chain::write_execution_supply_deltas_log;
 
+pub use export::export as export_dataset;
+
 pub use issuance_breakdown::update_issuance_breakdown;
 
+pub use l2::sync_l2_fees;
+
 pub use phoenix::monitor_critical_services;
 
+pub use scheduler::run_scheduler;
+
 pub use serve::start_server;
 
 pub use update_by_hand::run_cli as update_by_hand;
@@ -61,3 +127,5 @@ pub use update_by_hand::run_cli as update_by_hand;
 pub use usd_price::heal_eth_prices;
 pub use usd_price::record_eth_price;
 pub use usd_price::resync_all;
+
+pub use warm_caches::warm_caches;
@@ -6,10 +6,15 @@ use tracing_subscriber::{
     EnvFilter,
 };
 
-use crate::env::{self, Env};
+use crate::{
+    config,
+    env::{self, Env},
+};
 
 lazy_static! {
-    static ref PRETTY_PRINT: bool = env::get_env_bool("PRETTY_PRINT");
+    // Every binary starts by initializing logging, reading the config here means a broken config
+    // fails at startup.
+    static ref PRETTY_PRINT: bool = config::CONFIG.pretty_print();
 }
 
 pub fn init_with_env() {
@@ -0,0 +1,289 @@
+//! # Market Caps
+//! ETH and BTC market caps, and how far ETH is along to flipping BTC, as of every new block. The
+//! caps as of the last block of each day are kept as daily snapshots, from when we started
+//! storing them, there's no BTC supply history to backfill from.
+//!
+//! BTC supply comes from blockchain.info and moves slowly, it's fetched at most once an hour.
+use std::sync::Mutex;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    btc_price,
+    caching::{self, CacheKey},
+    execution_chain::{BlockNumber, ExecutionNodeBlock},
+    units::{EthNewtype, WeiNewtype},
+    usd_price::{EthPriceStore, EthPriceStorePostgres},
+};
+
+const BTC_SUPPLY_URL: &str = "https://blockchain.info/q/totalbc";
+const SATOSHIS_PER_BTC: f64 = 100_000_000.0;
+
+fn btc_supply_max_age() -> Duration {
+    Duration::hours(1)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarketCaps {
+    pub block_number: BlockNumber,
+    pub btc_market_cap: f64,
+    pub btc_price: f64,
+    pub btc_supply: f64,
+    pub eth_btc_price: f64,
+    pub eth_market_cap: f64,
+    pub eth_price: f64,
+    pub eth_supply: f64,
+    /// ETH market cap over BTC market cap, the flippening is at 1.
+    pub flippening_ratio: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl MarketCaps {
+    fn new(
+        block_number: BlockNumber,
+        timestamp: DateTime<Utc>,
+        eth_supply: f64,
+        eth_price: f64,
+        btc_supply: f64,
+        btc_price: f64,
+    ) -> Self {
+        let eth_market_cap = eth_supply * eth_price;
+        let btc_market_cap = btc_supply * btc_price;
+        Self {
+            block_number,
+            btc_market_cap,
+            btc_price,
+            btc_supply,
+            eth_btc_price: eth_price / btc_price,
+            eth_market_cap,
+            eth_price,
+            eth_supply,
+            flippening_ratio: eth_market_cap / btc_market_cap,
+            timestamp,
+        }
+    }
+}
+
+async fn fetch_btc_supply() -> Result<f64> {
+    let satoshis = reqwest::get(BTC_SUPPLY_URL)
+        .await?
+        .error_for_status()?
+        .text()
+        .await?
+        .trim()
+        .parse::<f64>()?;
+    Ok(satoshis / SATOSHIS_PER_BTC)
+}
+
+async fn get_eth_supply(
+    executor: impl PgExecutor<'_>,
+    timestamp: &DateTime<Utc>,
+) -> Option<EthNewtype> {
+    sqlx::query!(
+        r#"
+        SELECT supply AS "supply: WeiNewtype"
+        FROM eth_supply
+        WHERE timestamp <= $1
+        ORDER BY timestamp DESC
+        LIMIT 1
+        "#,
+        timestamp
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+    .map(|row| row.supply.into())
+}
+
+async fn store_snapshot(executor: impl PgExecutor<'_>, market_caps: &MarketCaps) {
+    sqlx::query!(
+        "
+        INSERT INTO market_cap_snapshots (
+            timestamp,
+            block_number,
+            btc_price,
+            btc_supply,
+            eth_price,
+            eth_supply
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (timestamp) DO UPDATE SET
+            block_number = EXCLUDED.block_number,
+            btc_price = EXCLUDED.btc_price,
+            btc_supply = EXCLUDED.btc_supply,
+            eth_price = EXCLUDED.eth_price,
+            eth_supply = EXCLUDED.eth_supply
+        ",
+        market_caps
+            .timestamp
+            .duration_trunc(Duration::days(1))
+            .unwrap(),
+        market_caps.block_number.0,
+        market_caps.btc_price,
+        market_caps.btc_supply,
+        market_caps.eth_price,
+        market_caps.eth_supply
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Daily snapshots, oldest first. Timestamps are the start of the day.
+async fn get_snapshots(executor: impl PgExecutor<'_>) -> Vec<MarketCaps> {
+    sqlx::query!(
+        "
+        SELECT timestamp, block_number, btc_price, btc_supply, eth_price, eth_supply
+        FROM market_cap_snapshots
+        ORDER BY timestamp ASC
+        "
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| {
+        MarketCaps::new(
+            BlockNumber(row.block_number),
+            row.timestamp,
+            row.eth_supply,
+            row.eth_price,
+            row.btc_supply,
+            row.btc_price,
+        )
+    })
+    .collect()
+}
+
+pub async fn delete_snapshots(executor: impl PgExecutor<'_>, greater_than_or_equal: &BlockNumber) {
+    sqlx::query!(
+        "DELETE FROM market_cap_snapshots WHERE block_number >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+#[derive(Default)]
+pub struct MarketCapsTracker {
+    /// The last BTC supply we fetched, and when.
+    btc_supply: Mutex<Option<(DateTime<Utc>, f64)>>,
+}
+
+impl MarketCapsTracker {
+    async fn get_btc_supply(&self) -> Result<f64> {
+        let cached = *self.btc_supply.lock().unwrap();
+        match cached {
+            Some((fetched_at, btc_supply)) if Utc::now() - fetched_at < btc_supply_max_age() => {
+                Ok(btc_supply)
+            }
+            _ => {
+                let btc_supply = fetch_btc_supply().await?;
+                *self.btc_supply.lock().unwrap() = Some((Utc::now(), btc_supply));
+                Ok(btc_supply)
+            }
+        }
+    }
+
+    pub async fn on_new_block(
+        &self,
+        db_pool: &PgPool,
+        eth_price_store: &EthPriceStorePostgres,
+        block: &ExecutionNodeBlock,
+    ) -> Result<()> {
+        let btc_price = match btc_price::get_price_by_timestamp(db_pool, &block.timestamp).await {
+            Some(btc_price) => btc_price,
+            None => {
+                debug!(%block.number, "no btc price for block, skipping market caps");
+                return Ok(());
+            }
+        };
+        let eth_supply = match get_eth_supply(db_pool, &block.timestamp).await {
+            Some(eth_supply) => eth_supply,
+            None => {
+                debug!(%block.number, "no eth supply yet, skipping market caps");
+                return Ok(());
+            }
+        };
+        let eth_price = eth_price_store.get_eth_price_by_block(block).await?;
+        let btc_supply = self.get_btc_supply().await?;
+
+        let market_caps = MarketCaps::new(
+            block.number,
+            block.timestamp,
+            eth_supply.0,
+            eth_price,
+            btc_supply,
+            btc_price,
+        );
+
+        store_snapshot(db_pool, &market_caps).await;
+        caching::update_and_publish(db_pool, &CacheKey::MarketCaps, market_caps).await;
+
+        let snapshots = get_snapshots(db_pool).await;
+        caching::update_and_publish(db_pool, &CacheKey::MarketCapsOverTime, snapshots).await;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use test_context::test_context;
+
+    use crate::db::tests::TestDb;
+
+    use super::*;
+
+    #[test]
+    fn flippening_ratio_test() {
+        let market_caps =
+            MarketCaps::new(BlockNumber(0), Utc::now(), 120.0, 3_000.0, 20.0, 60_000.0);
+        assert_eq!(market_caps.eth_market_cap, 360_000.0);
+        assert_eq!(market_caps.btc_market_cap, 1_200_000.0);
+        assert_eq!(market_caps.flippening_ratio, 0.3);
+        assert_eq!(market_caps.eth_btc_price, 0.05);
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn last_block_of_day_is_snapshot_test(test_db: &TestDb) {
+        let day = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let first = MarketCaps::new(
+            BlockNumber(1),
+            day + Duration::hours(1),
+            120.0,
+            3_000.0,
+            20.0,
+            60_000.0,
+        );
+        let last = MarketCaps::new(
+            BlockNumber(2),
+            day + Duration::hours(23),
+            120.0,
+            3_100.0,
+            20.0,
+            61_000.0,
+        );
+        store_snapshot(&test_db.pool, &first).await;
+        store_snapshot(&test_db.pool, &last).await;
+
+        let snapshots = get_snapshots(&test_db.pool).await;
+        assert_eq!(
+            snapshots,
+            vec![MarketCaps {
+                timestamp: day,
+                ..last
+            }]
+        );
+
+        delete_snapshots(&test_db.pool, &BlockNumber(2)).await;
+        assert!(get_snapshots(&test_db.pool).await.is_empty());
+    }
+}
@@ -1,9 +1,14 @@
+pub mod payload_values;
 mod relay_api;
 mod store;
+mod sync;
 
 use serde::Deserialize;
 
-use crate::units::WeiNewtype;
+use crate::{execution_chain::BlockNumber, units::WeiNewtype};
+
+pub use payload_values::update_payload_values;
+pub use payload_values::PayloadValuesRollback;
 
 pub use relay_api::MockRelayApi;
 pub use relay_api::RelayApi;
@@ -14,10 +19,12 @@ pub use store::MevBlocksStore;
 pub use store::MevBlocksStorePostgres;
 pub use store::MockMevBlocksStore;
 
+pub use sync::sync_mev_blocks;
+
 #[derive(Clone, Deserialize, PartialEq, Debug)]
 pub struct MevBlock {
     pub slot: i32,
-    pub block_number: i32,
+    pub block_number: BlockNumber,
     pub block_hash: String,
     pub bid: WeiNewtype,
 }
@@ -0,0 +1,261 @@
+//! # Payload values
+//! Compares what relays report builders paid proposers for a block, the bid in `mev_blocks`, to
+//! the priority fees its transactions paid, computed from the transactions we store. The delta is
+//! tips minus bid, positive when the block earned more in tips than the proposer was paid, a sign
+//! the proposer likely lost value. Direct payments to the builder, e.g. coinbase transfers, don't
+//! show up as tips, so what the block earned can be higher still.
+//!
+//! Reads stored transactions, see `execution_chain::transactions`. Each run only compares blocks
+//! after the last one stored, blocks without a stored effective gas price for every transaction
+//! by then are skipped.
+use async_trait::async_trait;
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap, HeaderValue},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    analysis::Analysis, beacon_chain::Slot, execution_chain::BlockNumber, rollback::RollbackPoint,
+    serve::StateExtension, units::WeiNewtype,
+};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// Stores the payload value of every MEV block after the last stored one we have transactions for.
+pub async fn update_payload_values(db_pool: &PgPool) {
+    let stored_count = sqlx::query!(
+        "
+        INSERT INTO payload_values (
+            block_hash,
+            block_number,
+            slot,
+            timestamp,
+            bid_wei,
+            tips_wei
+        )
+        SELECT
+            mev_blocks.block_hash,
+            mev_blocks.block_number,
+            mev_blocks.slot,
+            mev_blocks.timestamp,
+            mev_blocks.bid_wei,
+            SUM(
+                (transactions.effective_gas_price - blocks_next.base_fee_per_gas)
+                * transactions.gas_used
+            )
+        FROM mev_blocks
+        JOIN blocks_next
+            ON blocks_next.number = mev_blocks.block_number
+            AND blocks_next.hash = mev_blocks.block_hash
+        JOIN transactions
+            ON transactions.block_number = blocks_next.number
+            AND transactions.block_hash = blocks_next.hash
+        WHERE mev_blocks.block_number > (
+            SELECT COALESCE(MAX(block_number), -1) FROM payload_values
+        )
+        GROUP BY mev_blocks.block_hash
+        HAVING BOOL_AND(transactions.effective_gas_price IS NOT NULL)
+        ",
+    )
+    .execute(db_pool)
+    .await
+    .unwrap()
+    .rows_affected();
+
+    debug!(stored_count, "stored payload values");
+}
+
+async fn delete_payload_values(executor: impl PgExecutor<'_>, greater_than_or_equal: &BlockNumber) {
+    sqlx::query!(
+        "DELETE FROM payload_values WHERE block_number >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub struct PayloadValuesRollback;
+
+#[async_trait]
+impl Analysis for PayloadValuesRollback {
+    fn name(&self) -> &'static str {
+        "payload_values"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            delete_payload_values(transaction, block_number_gte).await;
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayloadValue {
+    pub bid: WeiNewtype,
+    pub block_hash: String,
+    pub block_number: BlockNumber,
+    /// Tips minus bid.
+    pub delta: WeiNewtype,
+    pub slot: Slot,
+    pub timestamp: DateTime<Utc>,
+    pub tips: WeiNewtype,
+}
+
+/// Largest delta first.
+async fn get_payload_values(
+    executor: impl PgExecutor<'_>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    limit: i64,
+) -> Vec<PayloadValue> {
+    sqlx::query!(
+        r#"
+        SELECT
+            block_hash,
+            block_number,
+            slot,
+            timestamp,
+            bid_wei AS "bid_wei: WeiNewtype",
+            tips_wei AS "tips_wei: WeiNewtype",
+            delta_wei AS "delta_wei!: WeiNewtype"
+        FROM payload_values
+        WHERE ($1::TIMESTAMPTZ IS NULL OR timestamp >= $1)
+        AND ($2::TIMESTAMPTZ IS NULL OR timestamp < $2)
+        ORDER BY delta_wei DESC, block_number DESC
+        LIMIT $3
+        "#,
+        start,
+        end,
+        limit,
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| PayloadValue {
+        bid: row.bid_wei,
+        block_hash: row.block_hash,
+        block_number: BlockNumber(row.block_number),
+        delta: row.delta_wei,
+        slot: Slot(row.slot),
+        timestamp: row.timestamp,
+        tips: row.tips_wei,
+    })
+    .collect()
+}
+
+#[derive(Deserialize)]
+pub struct PayloadValuesParams {
+    end: Option<DateTime<Utc>>,
+    limit: Option<i64>,
+    start: Option<DateTime<Utc>>,
+}
+
+/// Takes optional RFC 3339 `start` and `end` timestamps, and a `limit`, 100 by default and at
+/// most 1000. Blocks where the proposer likely lost the most value come first.
+pub async fn payload_values(
+    state: StateExtension,
+    Query(params): Query<PayloadValuesParams>,
+) -> impl IntoResponse {
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let payload_values =
+        get_payload_values(&state.read_db_pool, params.start, params.end, limit).await;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=60, stale-while-revalidate=600"),
+    );
+
+    (headers, Json(payload_values)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{
+            block_store, store_transactions, BlockTransactionBuilder, ExecutionNodeBlockBuilder,
+        },
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn payload_values_test(test_db: &TestDb) {
+        let block = ExecutionNodeBlockBuilder::new("payload_values")
+            .with_base_fee_per_gas(100)
+            .build();
+        block_store::store_block(&test_db.pool, &block, 1.0).await;
+        // Tips of 10 * 10 and 10 * 20 wei.
+        store_transactions(
+            &test_db.pool,
+            &block,
+            &[
+                BlockTransactionBuilder::new("payload_values", 0)
+                    .with_effective_gas_price(WeiNewtype(110))
+                    .with_gas_used(10)
+                    .build(),
+                BlockTransactionBuilder::new("payload_values", 1)
+                    .with_effective_gas_price(WeiNewtype(120))
+                    .with_gas_used(10)
+                    .build(),
+            ],
+        )
+        .await;
+        sqlx::query!(
+            "
+            INSERT INTO mev_blocks (bid_wei, block_hash, block_number, slot, timestamp)
+            VALUES (250, $1, $2, 1, $3)
+            ",
+            block.hash,
+            block.number.0,
+            block.timestamp,
+        )
+        .execute(&test_db.pool)
+        .await
+        .unwrap();
+
+        update_payload_values(&test_db.pool).await;
+        // Stored payload values are left alone.
+        update_payload_values(&test_db.pool).await;
+
+        assert_eq!(
+            get_payload_values(&test_db.pool, None, None, DEFAULT_LIMIT).await,
+            vec![PayloadValue {
+                bid: WeiNewtype(250),
+                block_hash: block.hash.clone(),
+                block_number: block.number,
+                delta: WeiNewtype(50),
+                slot: Slot(1),
+                timestamp: block.timestamp,
+                tips: WeiNewtype(300),
+            }]
+        );
+
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        PayloadValuesRollback
+            .on_rollback(
+                &mut connection,
+                &RollbackPoint::BlockNumbersGte(block.number),
+            )
+            .await;
+        assert!(
+            get_payload_values(&mut *connection, None, None, DEFAULT_LIMIT)
+                .await
+                .is_empty()
+        );
+    }
+}
@@ -2,7 +2,7 @@ use async_trait::async_trait;
 use mockall::{automock, predicate::*};
 use serde::Deserialize;
 
-use crate::units::WeiNewtype;
+use crate::{execution_chain::BlockNumber, units::WeiNewtype};
 
 use super::MevBlock;
 
@@ -15,7 +15,7 @@ pub struct MaybeMevBlock {
     #[serde(rename = "slotNumber")]
     slot_number: i32,
     #[serde(rename = "blockNumber")]
-    block_number: i32,
+    block_number: BlockNumber,
     #[serde(rename = "blockHash")]
     block_hash: String,
     #[serde(rename = "value")]
@@ -121,7 +121,7 @@ mod tests {
 
         let block = &blocks[0];
         assert_eq!(block.slot, 1);
-        assert_eq!(block.block_number, 9191911);
+        assert_eq!(block.block_number, BlockNumber(9191911));
         assert_eq!(block.block_hash, "abc");
         assert_eq!(block.bid.0, 100);
     }
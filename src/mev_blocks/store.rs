@@ -3,7 +3,7 @@ use chrono::{DateTime, Utc};
 use mockall::{automock, predicate::*};
 use sqlx::{Pool, Postgres};
 
-use crate::beacon_chain::Slot;
+use crate::{beacon_chain::Slot, execution_chain::BlockNumber};
 
 use super::MevBlock;
 
@@ -43,7 +43,7 @@ impl MevBlocksStore for MevBlocksStorePostgres {
 
     async fn store_blocks(&self, blocks: &[MevBlock]) {
         let slots: Vec<i32> = blocks.iter().map(|b| b.slot).collect();
-        let block_numbers: Vec<i32> = blocks.iter().map(|b| b.block_number).collect();
+        let block_numbers: Vec<BlockNumber> = blocks.iter().map(|b| b.block_number).collect();
         let block_hashes: Vec<String> = blocks.iter().map(|b| b.block_hash.clone()).collect();
         let timestamps: Vec<DateTime<Utc>> = blocks.iter().map(|b| Slot(b.slot).into()).collect();
         let payments: Vec<String> = blocks.iter().map(|b| b.bid.to_string()).collect();
@@ -51,7 +51,7 @@ impl MevBlocksStore for MevBlocksStorePostgres {
         sqlx::query!(
             "
             INSERT INTO mev_blocks (slot, block_number, block_hash, bid_wei, timestamp)
-            SELECT * FROM UNNEST($1::int[], $2::int[], $3::text[], $4::numeric[], $5::timestamptz[])
+            SELECT * FROM UNNEST($1::int[], $2::int8[], $3::text[], $4::numeric[], $5::timestamptz[])
             ON CONFLICT (block_hash) DO UPDATE SET
                 bid_wei = excluded.bid_wei,
                 block_number = excluded.block_number,
@@ -59,7 +59,7 @@ impl MevBlocksStore for MevBlocksStorePostgres {
                 timestamp = excluded.timestamp
             ",
             &slots,
-            &block_numbers,
+            &block_numbers as &[BlockNumber],
             &block_hashes,
             &payments as &Vec<String>,
             &timestamps,
@@ -83,14 +83,14 @@ mod tests {
 
         let block_1 = MevBlock {
             slot: 10,
-            block_number: 100,
+            block_number: BlockNumber(100),
             block_hash: String::from("abc"),
             bid: WeiNewtype::from_eth(1),
         };
 
         let block_2 = MevBlock {
             slot: 20,
-            block_number: 200,
+            block_number: BlockNumber(200),
             block_hash: String::from("def"),
             bid: WeiNewtype::from_eth(2),
         };
@@ -108,14 +108,14 @@ mod tests {
 
         let block_1 = MevBlock {
             slot: 10,
-            block_number: 100,
+            block_number: BlockNumber(100),
             block_hash: String::from("abc"),
             bid: WeiNewtype::from_eth(1),
         };
 
         let block_2 = MevBlock {
             slot: 20,
-            block_number: 200,
+            block_number: BlockNumber(200),
             block_hash: String::from("def"),
             bid: WeiNewtype::from_eth(2),
         };
@@ -128,7 +128,7 @@ mod tests {
         // Now insert a block with the same hash as block_2 but different slot and block_number
         let block_3 = MevBlock {
             slot: 30,
-            block_number: 300,
+            block_number: BlockNumber(300),
             block_hash: String::from("def"), // Same hash as block_2
             bid: WeiNewtype::from_eth(3),
         };
@@ -1,13 +1,8 @@
-use anyhow::{Context, Result};
-use eth_analysis::{
-    beacon_chain::BeaconNode,
-    mev_blocks::{MevBlocksStore, RelayApi, EARLIEST_AVAILABLE_SLOT},
-    units::{EthNewtype, GweiNewtype, WeiNewtype},
-};
-use sqlx::PgExecutor;
 use tracing::{debug, info};
 
-use crate::{ValidatorReward, SLOTS_PER_YEAR};
+use crate::beacon_chain::BeaconNode;
+
+use super::{MevBlocksStore, RelayApi, EARLIEST_AVAILABLE_SLOT};
 
 pub async fn sync_mev_blocks(
     mev_blocks_store: &impl MevBlocksStore,
@@ -37,55 +32,18 @@ pub async fn sync_mev_blocks(
     info!(start_slot, "no more blocks to process");
 }
 
-pub async fn get_mev_reward(
-    executor: impl PgExecutor<'_>,
-    effective_balance_sum: GweiNewtype,
-) -> Result<ValidatorReward> {
-    let mev_per_slot: EthNewtype = sqlx::query!(
-        "
-        SELECT AVG(bid_wei)::TEXT
-        FROM mev_blocks
-        WHERE timestamp > NOW() - INTERVAL '6 months'
-        "
-    )
-    .fetch_one(executor)
-    .await
-    .unwrap()
-    .avg
-    .context("expect at least one block in mev_blocks table before computing MEV reward")?
-    .parse::<WeiNewtype>()
-    .context("failed to parse MEV per slot as Wei")?
-    .into();
-
-    let effective_balance_sum_eth: EthNewtype = effective_balance_sum.into();
-    let active_validators: f64 = (effective_balance_sum_eth.0 / 32.0).floor();
-    let annual_reward_eth = mev_per_slot.0 * SLOTS_PER_YEAR / active_validators;
-    let annual_reward = EthNewtype(annual_reward_eth).into();
-    let apr = annual_reward_eth / 32f64;
-
-    debug!(
-        "average MEV per slot in the last 6 months: {} ETH",
-        mev_per_slot.0
-    );
-    debug!(
-        "total effective balance: {} ETH",
-        effective_balance_sum_eth.0
-    );
-    debug!("nr of active validators: {}", active_validators);
-    debug!("MEV annual reward: {} ETH", annual_reward_eth);
-    debug!("MEV APR: {:.2}%", apr * 100f64);
-
-    Ok(ValidatorReward { annual_reward, apr })
-}
-
 #[cfg(test)]
 mod tests {
-    use super::*;
-    use eth_analysis::{
+    use mockall::predicate::*;
+
+    use crate::{
         beacon_chain::{BeaconHeaderSignedEnvelopeBuilder, MockBeaconNode, Slot},
+        execution_chain::BlockNumber,
         mev_blocks::{MevBlock, MockMevBlocksStore, MockRelayApi},
+        units::WeiNewtype,
     };
-    use mockall::predicate::*;
+
+    use super::*;
 
     #[tokio::test]
     async fn test_sync_mev_blocks() {
@@ -108,7 +66,7 @@ mod tests {
 
         let mev_block = MevBlock {
             slot: 1,
-            block_number: 1,
+            block_number: BlockNumber(1),
             block_hash: "0x000000".to_string(),
             bid: WeiNewtype::from(10),
         };
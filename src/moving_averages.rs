@@ -0,0 +1,404 @@
+//! # Moving Averages
+//! Burn and issuance per day, smoothed with a 7 and a 30 day moving average. Only completed days
+//! are stored, a day is computed once the first block of the next comes in, and the beacon chain
+//! sync stored issuance for the next day. Issuance of a day is the difference between the first
+//! issuance of the day and the next, a day stored before that would miss it for good.
+//!
+//! A moving average is `None` until there are enough days before it, or when issuance is missing
+//! for a day in its window.
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tracing::{debug, info};
+
+use crate::{
+    caching::{self, CacheKey},
+    execution_chain::{BlockNumber, ExecutionNodeBlock, LONDON_HARD_FORK_TIMESTAMP},
+};
+
+const WEEK_DAYS: usize = 7;
+const MONTH_DAYS: usize = 30;
+
+#[derive(Debug, PartialEq)]
+struct DailyTotals {
+    block_number: BlockNumber,
+    burn_eth: f64,
+    issuance_eth: Option<f64>,
+    timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct MovingAveragesAtDay {
+    pub eth: Option<f64>,
+    pub ma_7d: Option<f64>,
+    pub ma_30d: Option<f64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// The average of the `days` values up to and including each value.
+fn moving_average(values: &[Option<f64>], days: usize) -> Vec<Option<f64>> {
+    (0..values.len())
+        .map(|i| {
+            if i + 1 < days {
+                return None;
+            }
+            values[i + 1 - days..=i]
+                .iter()
+                .copied()
+                .sum::<Option<f64>>()
+                .map(|sum| sum / days as f64)
+        })
+        .collect()
+}
+
+/// Totals of the completed days in `[start, end)`.
+async fn get_daily_totals(
+    executor: impl PgExecutor<'_>,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Vec<DailyTotals> {
+    sqlx::query!(
+        r#"
+        WITH daily_burn AS (
+            SELECT
+                DATE_TRUNC('day', timestamp) AS day,
+                MAX(number) AS block_number,
+                SUM(
+                    base_fee_per_gas::NUMERIC(78) * gas_used::NUMERIC(78)
+                    + COALESCE(blob_base_fee::NUMERIC(78) * blob_gas_used::NUMERIC(78), 0)
+                )::FLOAT8 / 1e18 AS burn_eth
+            FROM blocks_next
+            WHERE timestamp >= $1 AND timestamp < $2
+            GROUP BY 1
+        ),
+        start_of_day_issuance AS (
+            SELECT DISTINCT ON (DATE_TRUNC('day', timestamp))
+                DATE_TRUNC('day', timestamp) AS day,
+                gwei
+            FROM beacon_issuance
+            WHERE timestamp >= $1 AND timestamp < $2::TIMESTAMPTZ + INTERVAL '1 day'
+            ORDER BY DATE_TRUNC('day', timestamp), timestamp ASC
+        ),
+        daily_issuance AS (
+            SELECT
+                day,
+                (LEAD(gwei) OVER (ORDER BY day) - gwei)::FLOAT8 / 1e9 AS issuance_eth,
+                LEAD(day) OVER (ORDER BY day) = day + INTERVAL '1 day' AS is_next_day
+            FROM start_of_day_issuance
+        )
+        SELECT
+            daily_burn.day AS "timestamp!",
+            daily_burn.block_number AS "block_number!",
+            daily_burn.burn_eth AS "burn_eth!",
+            CASE WHEN daily_issuance.is_next_day THEN daily_issuance.issuance_eth END AS issuance_eth
+        FROM daily_burn
+        LEFT JOIN daily_issuance ON daily_issuance.day = daily_burn.day
+        ORDER BY daily_burn.day ASC
+        "#,
+        start,
+        end
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| DailyTotals {
+        block_number: BlockNumber(row.block_number),
+        burn_eth: row.burn_eth,
+        issuance_eth: row.issuance_eth,
+        timestamp: row.timestamp,
+    })
+    .collect()
+}
+
+async fn store_day(
+    executor: impl PgExecutor<'_>,
+    totals: &DailyTotals,
+    burn: (Option<f64>, Option<f64>),
+    issuance: (Option<f64>, Option<f64>),
+) {
+    sqlx::query!(
+        "
+        INSERT INTO daily_moving_averages (
+            timestamp,
+            block_number,
+            burn_eth,
+            burn_ma_7d,
+            burn_ma_30d,
+            issuance_eth,
+            issuance_ma_7d,
+            issuance_ma_30d
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (timestamp) DO UPDATE SET
+            block_number = EXCLUDED.block_number,
+            burn_eth = EXCLUDED.burn_eth,
+            burn_ma_7d = EXCLUDED.burn_ma_7d,
+            burn_ma_30d = EXCLUDED.burn_ma_30d,
+            issuance_eth = EXCLUDED.issuance_eth,
+            issuance_ma_7d = EXCLUDED.issuance_ma_7d,
+            issuance_ma_30d = EXCLUDED.issuance_ma_30d
+        ",
+        totals.timestamp,
+        totals.block_number.0,
+        totals.burn_eth,
+        burn.0,
+        burn.1,
+        totals.issuance_eth,
+        issuance.0,
+        issuance.1
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+async fn get_last_stored_day(executor: impl PgExecutor<'_>) -> Option<DateTime<Utc>> {
+    sqlx::query!("SELECT MAX(timestamp) AS timestamp FROM daily_moving_averages")
+        .fetch_one(executor)
+        .await
+        .unwrap()
+        .timestamp
+}
+
+/// Computes and stores the completed days from `start` up to `end`, returns how many.
+async fn update_days(db_pool: &PgPool, start: &DateTime<Utc>, end: &DateTime<Utc>) -> usize {
+    // The first days need the month before them for their averages.
+    let lookback_start = *start - Duration::days(MONTH_DAYS as i64 - 1);
+    let daily_totals = get_daily_totals(db_pool, &lookback_start, end).await;
+
+    let burns = daily_totals
+        .iter()
+        .map(|totals| Some(totals.burn_eth))
+        .collect::<Vec<_>>();
+    let issuances = daily_totals
+        .iter()
+        .map(|totals| totals.issuance_eth)
+        .collect::<Vec<_>>();
+    let burn_ma_7d = moving_average(&burns, WEEK_DAYS);
+    let burn_ma_30d = moving_average(&burns, MONTH_DAYS);
+    let issuance_ma_7d = moving_average(&issuances, WEEK_DAYS);
+    let issuance_ma_30d = moving_average(&issuances, MONTH_DAYS);
+
+    let mut count = 0;
+    for (i, totals) in daily_totals.iter().enumerate() {
+        if totals.timestamp < *start {
+            continue;
+        }
+        store_day(
+            db_pool,
+            totals,
+            (burn_ma_7d[i], burn_ma_30d[i]),
+            (issuance_ma_7d[i], issuance_ma_30d[i]),
+        )
+        .await;
+        count += 1;
+    }
+    count
+}
+
+async fn get_moving_averages(
+    executor: impl PgExecutor<'_>,
+) -> (Vec<MovingAveragesAtDay>, Vec<MovingAveragesAtDay>) {
+    sqlx::query!(
+        "
+        SELECT
+            timestamp,
+            burn_eth,
+            burn_ma_7d,
+            burn_ma_30d,
+            issuance_eth,
+            issuance_ma_7d,
+            issuance_ma_30d
+        FROM daily_moving_averages
+        ORDER BY timestamp ASC
+        "
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| {
+        let burn = MovingAveragesAtDay {
+            eth: Some(row.burn_eth),
+            ma_7d: row.burn_ma_7d,
+            ma_30d: row.burn_ma_30d,
+            timestamp: row.timestamp,
+        };
+        let issuance = MovingAveragesAtDay {
+            eth: row.issuance_eth,
+            ma_7d: row.issuance_ma_7d,
+            ma_30d: row.issuance_ma_30d,
+            timestamp: row.timestamp,
+        };
+        (burn, issuance)
+    })
+    .unzip()
+}
+
+pub async fn publish_stored_moving_averages(db_pool: &PgPool) {
+    let (burn, issuance) = get_moving_averages(db_pool).await;
+    caching::update_and_publish(db_pool, &CacheKey::BurnMovingAverages, burn).await;
+    caching::update_and_publish(db_pool, &CacheKey::IssuanceMovingAverages, issuance).await;
+}
+
+pub async fn delete_days(executor: impl PgExecutor<'_>, greater_than_or_equal: &BlockNumber) {
+    sqlx::query!(
+        "DELETE FROM daily_moving_averages WHERE block_number >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+async fn get_last_issuance_timestamp(executor: impl PgExecutor<'_>) -> Option<DateTime<Utc>> {
+    sqlx::query!("SELECT MAX(timestamp) AS timestamp FROM beacon_issuance")
+        .fetch_one(executor)
+        .await
+        .unwrap()
+        .timestamp
+}
+
+/// Stores any completed days we're missing, since London when none are stored yet. Days the
+/// stored issuance doesn't reach the end of yet are left for a later call.
+pub async fn update_missing_days(db_pool: &PgPool, now: &DateTime<Utc>) -> usize {
+    let today = now.duration_trunc(Duration::days(1)).unwrap();
+    let end = match get_last_issuance_timestamp(db_pool).await {
+        Some(last_issuance_timestamp) => today.min(
+            last_issuance_timestamp
+                .duration_trunc(Duration::days(1))
+                .unwrap(),
+        ),
+        None => today,
+    };
+    let start = match get_last_stored_day(db_pool).await {
+        Some(last_stored_day) => last_stored_day + Duration::days(1),
+        None => LONDON_HARD_FORK_TIMESTAMP
+            .duration_trunc(Duration::days(1))
+            .unwrap(),
+    };
+
+    if start >= end {
+        return 0;
+    }
+
+    update_days(db_pool, &start, &end).await
+}
+
+pub async fn on_new_block(db_pool: &PgPool, block: &ExecutionNodeBlock) {
+    let count = update_missing_days(db_pool, &block.timestamp).await;
+    if count == 0 {
+        debug!(%block.number, "no completed days to add moving averages for");
+        return;
+    }
+
+    info!(count, "stored daily moving averages");
+    publish_stored_moving_averages(db_pool).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use test_context::test_context;
+
+    use crate::{
+        beacon_chain::{store_issuance, store_state, Slot},
+        db::tests::TestDb,
+        execution_chain::{block_store, ExecutionNodeBlockBuilder},
+        units::GweiNewtype,
+    };
+
+    use super::*;
+
+    #[test]
+    fn moving_average_test() {
+        let values = [Some(1.0), Some(2.0), Some(3.0), None, Some(5.0), Some(6.0)];
+        assert_eq!(
+            moving_average(&values, 2),
+            vec![None, Some(1.5), Some(2.5), None, None, Some(5.5)]
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn get_daily_totals_test(test_db: &TestDb) {
+        let block_1 = ExecutionNodeBlockBuilder::new("moving_averages")
+            .with_timestamp(&Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap())
+            .with_gas_used(1_000_000_000)
+            .with_base_fee_per_gas(1_000_000_000)
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_gas_used(2_000_000_000)
+            .with_base_fee_per_gas(1_000_000_000)
+            .build();
+        block_store::store_block(&test_db.pool, &block_1, 0.0).await;
+        block_store::store_block(&test_db.pool, &block_2, 0.0).await;
+
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2024, 5, 2, 0, 0, 0).unwrap();
+        let daily_totals = get_daily_totals(&test_db.pool, &start, &end).await;
+
+        assert_eq!(
+            daily_totals,
+            vec![DailyTotals {
+                block_number: block_2.number,
+                burn_eth: 3.0,
+                issuance_eth: None,
+                timestamp: start,
+            }]
+        );
+    }
+
+    async fn store_test_issuance(test_db: &TestDb, timestamp: &DateTime<Utc>, gwei: i64) {
+        let slot = Slot::from_date_time_rounded_down(timestamp);
+        let state_root = format!("0xmoving_averages_{slot}");
+        store_state(&test_db.pool, &state_root, &slot).await;
+        store_issuance(&test_db.pool, &state_root, &slot, &GweiNewtype(gwei)).await;
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn update_missing_days_waits_for_issuance_test(test_db: &TestDb) {
+        let day_1 = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let day_2 = day_1 + Duration::days(1);
+        let day_3 = day_2 + Duration::days(1);
+
+        let block_1 = ExecutionNodeBlockBuilder::new("update_missing_days")
+            .with_timestamp(&(day_1 + Duration::hours(12)))
+            .build();
+        let block_2 = ExecutionNodeBlockBuilder::from_parent(&block_1)
+            .with_timestamp(&(day_2 + Duration::hours(12)))
+            .build();
+        let block_3 = ExecutionNodeBlockBuilder::from_parent(&block_2)
+            .with_timestamp(&(day_3 + Duration::hours(12)))
+            .build();
+        for block in [&block_1, &block_2, &block_3] {
+            block_store::store_block(&test_db.pool, block, 0.0).await;
+        }
+        store_test_issuance(test_db, &(day_1 + Duration::hours(1)), 100).await;
+        store_test_issuance(test_db, &(day_2 + Duration::hours(1)), 110).await;
+
+        // Issuance for the start of day 3 isn't stored yet, day 2 has to wait.
+        assert_eq!(
+            update_missing_days(&test_db.pool, &block_3.timestamp).await,
+            1
+        );
+        assert_eq!(
+            update_missing_days(&test_db.pool, &block_3.timestamp).await,
+            0
+        );
+
+        store_test_issuance(test_db, &(day_3 + Duration::hours(1)), 125).await;
+        assert_eq!(
+            update_missing_days(&test_db.pool, &block_3.timestamp).await,
+            1
+        );
+
+        let (_, issuance) = get_moving_averages(&test_db.pool).await;
+        assert_eq!(
+            issuance.iter().map(|day| day.eth).collect::<Vec<_>>(),
+            vec![Some(10e-9), Some(15e-9)]
+        );
+    }
+}
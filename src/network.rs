@@ -0,0 +1,461 @@
+//! # Network
+//! Which chain we analyze, set with `network` in the config file or `NETWORK` in the env. Each
+//! network comes with the genesis and fork points the beacon and execution modules start from.
+//!
+//! Any listed constant can be overridden under `[network_overrides]`. Gnosis isn't supported, its
+//! blob parameters differ and its base fees go to a fee collector instead of being burned.
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::{
+    beacon_chain::Slot,
+    config,
+    execution_chain::{
+        supply_deltas::snapshot::{SupplySnapshot, SUPPLY_SNAPSHOT_15082718},
+        BlockNumber,
+    },
+    units::WeiNewtype,
+};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Holesky,
+    Sepolia,
+}
+
+impl Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mainnet => write!(f, "mainnet"),
+            Self::Holesky => write!(f, "holesky"),
+            Self::Sepolia => write!(f, "sepolia"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(Self::Mainnet),
+            "holesky" => Ok(Self::Holesky),
+            "sepolia" => Ok(Self::Sepolia),
+            _ => Err(format!(
+                "unknown network {s}, expected mainnet, holesky or sepolia"
+            )),
+        }
+    }
+}
+
+/// Genesis and fork points of a network, either known for the network or set in the config file
+/// under `[network_overrides]`. Timestamps are RFC 3339 strings.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkOverrides {
+    pub beacon_genesis_timestamp: Option<DateTime<Utc>>,
+    /// The first blob parameter only fork after Osaka.
+    pub bpo1_timestamp: Option<DateTime<Utc>>,
+    pub bpo2_timestamp: Option<DateTime<Utc>>,
+    pub byzantium_block_number: Option<BlockNumber>,
+    pub cancun_timestamp: Option<DateTime<Utc>>,
+    pub constantinople_block_number: Option<BlockNumber>,
+    pub first_post_london_slot: Option<Slot>,
+    pub first_post_merge_slot: Option<Slot>,
+    /// Where `sync-eth-supply-gaps` starts looking, the first merge slot when not set.
+    pub first_stored_eth_supply_slot: Option<Slot>,
+    pub genesis_block_hash: Option<String>,
+    /// The genesis allocation, in wei. Only known for mainnet.
+    pub genesis_supply: Option<WeiNewtype>,
+    pub london_block_hash: Option<String>,
+    pub london_block_number: Option<BlockNumber>,
+    pub london_timestamp: Option<DateTime<Utc>>,
+    pub merge_block_number: Option<BlockNumber>,
+    pub osaka_timestamp: Option<DateTime<Utc>>,
+    pub paris_timestamp: Option<DateTime<Utc>>,
+    pub prague_timestamp: Option<DateTime<Utc>>,
+    pub seconds_per_slot: Option<i32>,
+    pub shapella_block_number: Option<BlockNumber>,
+    pub shapella_slot: Option<Slot>,
+    pub slots_per_epoch: Option<i32>,
+    /// Where `sync-execution-blocks` starts when no blocks are stored yet.
+    pub sync_start_block_number: Option<BlockNumber>,
+}
+
+fn timestamp(str: &str) -> Option<DateTime<Utc>> {
+    Some(str.parse().unwrap())
+}
+
+impl Network {
+    /// Only mainnet has a snapshot, the other networks are summed from genesis.
+    fn supply_snapshot(&self) -> Option<&'static SupplySnapshot> {
+        match self {
+            Self::Mainnet => Some(&SUPPLY_SNAPSHOT_15082718),
+            Self::Holesky | Self::Sepolia => None,
+        }
+    }
+
+    fn known_constants(&self) -> NetworkOverrides {
+        match self {
+            Self::Mainnet => NetworkOverrides {
+                beacon_genesis_timestamp: timestamp("2020-12-01T12:00:23Z"),
+                bpo1_timestamp: timestamp("2025-12-09T14:21:11Z"),
+                bpo2_timestamp: timestamp("2026-01-07T01:01:11Z"),
+                byzantium_block_number: Some(BlockNumber(4_370_000)),
+                cancun_timestamp: timestamp("2024-03-13T13:55:35Z"),
+                constantinople_block_number: Some(BlockNumber(7_280_000)),
+                first_post_london_slot: Some(Slot(1778566)),
+                first_post_merge_slot: Some(Slot(4700013)),
+                first_stored_eth_supply_slot: Some(Slot(4697813)),
+                genesis_block_hash: Some(
+                    "0xd4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3"
+                        .to_string(),
+                ),
+                genesis_supply: Some(WeiNewtype(72_009_990_499_480_000_000_000_000)),
+                london_block_hash: Some(
+                    "0x9b83c12c69edb74f6c8dd5d052765c1adf940e320bd1291696e6fa07829eee71"
+                        .to_string(),
+                ),
+                london_block_number: Some(BlockNumber(12_965_000)),
+                london_timestamp: timestamp("2021-08-05T12:33:42Z"),
+                merge_block_number: Some(BlockNumber(15_537_394)),
+                osaka_timestamp: timestamp("2025-12-03T21:49:11Z"),
+                paris_timestamp: timestamp("2022-09-15T06:42:59Z"),
+                prague_timestamp: timestamp("2025-05-07T10:05:11Z"),
+                seconds_per_slot: Some(12),
+                shapella_block_number: Some(BlockNumber(17_034_870)),
+                shapella_slot: Some(Slot(6209536)),
+                slots_per_epoch: Some(32),
+                // 2022-08-01, a good month of blocks before the merge.
+                sync_start_block_number: Some(BlockNumber(15_253_306)),
+            },
+            // Holesky launched with every execution fork up to Paris active from genesis.
+            Self::Holesky => NetworkOverrides {
+                beacon_genesis_timestamp: timestamp("2023-09-28T12:00:00Z"),
+                bpo1_timestamp: timestamp("2025-10-07T01:20:00Z"),
+                bpo2_timestamp: timestamp("2025-10-13T21:10:24Z"),
+                byzantium_block_number: Some(BlockNumber(0)),
+                cancun_timestamp: timestamp("2024-02-07T11:34:24Z"),
+                constantinople_block_number: Some(BlockNumber(0)),
+                first_post_london_slot: Some(Slot::GENESIS),
+                first_post_merge_slot: Some(Slot::GENESIS),
+                first_stored_eth_supply_slot: None,
+                // The same block as London's, which activated at genesis.
+                genesis_block_hash: Some(
+                    "0xb5f7f912443c940f21fd611f12828d75b534364ed9e95ca4e307729a4661bde4"
+                        .to_string(),
+                ),
+                genesis_supply: None,
+                london_block_hash: Some(
+                    "0xb5f7f912443c940f21fd611f12828d75b534364ed9e95ca4e307729a4661bde4"
+                        .to_string(),
+                ),
+                london_block_number: Some(BlockNumber(0)),
+                london_timestamp: timestamp("2023-09-28T11:55:00Z"),
+                merge_block_number: Some(BlockNumber(0)),
+                osaka_timestamp: timestamp("2025-10-01T08:48:00Z"),
+                paris_timestamp: timestamp("2023-09-28T11:55:00Z"),
+                prague_timestamp: timestamp("2025-02-24T21:55:12Z"),
+                seconds_per_slot: Some(12),
+                shapella_block_number: Some(BlockNumber(6_698)),
+                shapella_slot: Some(Slot(8192)),
+                slots_per_epoch: Some(32),
+                sync_start_block_number: Some(BlockNumber(0)),
+            },
+            // Sepolia's execution chain had London from genesis, its beacon chain came later and
+            // merged with it on 2022-07-06.
+            Self::Sepolia => NetworkOverrides {
+                beacon_genesis_timestamp: timestamp("2022-06-20T14:00:00Z"),
+                bpo1_timestamp: timestamp("2025-10-21T03:26:24Z"),
+                bpo2_timestamp: timestamp("2025-10-27T23:16:48Z"),
+                byzantium_block_number: Some(BlockNumber(0)),
+                cancun_timestamp: timestamp("2024-01-30T22:51:12Z"),
+                constantinople_block_number: Some(BlockNumber(0)),
+                first_post_london_slot: Some(Slot::GENESIS),
+                first_post_merge_slot: Some(Slot(115193)),
+                first_stored_eth_supply_slot: None,
+                // The same block as London's, which activated at genesis.
+                genesis_block_hash: Some(
+                    "0x25a5cc106eea7138acab33231d7160d69cb777ee0c2c553fcddf5138993e6dd9"
+                        .to_string(),
+                ),
+                genesis_supply: None,
+                london_block_hash: Some(
+                    "0x25a5cc106eea7138acab33231d7160d69cb777ee0c2c553fcddf5138993e6dd9"
+                        .to_string(),
+                ),
+                london_block_number: Some(BlockNumber(0)),
+                london_timestamp: timestamp("2021-10-03T13:24:41Z"),
+                merge_block_number: Some(BlockNumber(1_450_409)),
+                osaka_timestamp: timestamp("2025-10-14T07:36:00Z"),
+                paris_timestamp: timestamp("2022-07-06T13:58:36Z"),
+                prague_timestamp: timestamp("2025-03-05T07:29:36Z"),
+                seconds_per_slot: Some(12),
+                shapella_block_number: Some(BlockNumber(2_990_908)),
+                shapella_slot: Some(Slot(1818624)),
+                slots_per_epoch: Some(32),
+                // The merge, the pre-merge testnet history isn't worth syncing.
+                sync_start_block_number: Some(BlockNumber(1_450_409)),
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NetworkConstants {
+    pub beacon_genesis_timestamp: DateTime<Utc>,
+    pub bpo1_timestamp: DateTime<Utc>,
+    pub bpo2_timestamp: DateTime<Utc>,
+    pub byzantium_block_number: BlockNumber,
+    pub cancun_timestamp: DateTime<Utc>,
+    pub constantinople_block_number: BlockNumber,
+    pub first_post_london_slot: Slot,
+    pub first_post_merge_slot: Slot,
+    pub first_stored_eth_supply_slot: Slot,
+    pub genesis_block_hash: String,
+    /// Supply deltas summed from genesis need it, they refuse to start without it.
+    pub genesis_supply: Option<WeiNewtype>,
+    pub london_block_hash: String,
+    pub london_block_number: BlockNumber,
+    pub london_timestamp: DateTime<Utc>,
+    pub merge_block_number: BlockNumber,
+    pub osaka_timestamp: DateTime<Utc>,
+    pub paris_timestamp: DateTime<Utc>,
+    pub prague_timestamp: DateTime<Utc>,
+    pub seconds_per_slot: i32,
+    /// The first execution block included in a Shapella beacon block.
+    pub shapella_block_number: BlockNumber,
+    pub shapella_slot: Slot,
+    pub slots_per_epoch: i32,
+    /// Execution supply sums start from this snapshot, from genesis when there is none.
+    pub supply_snapshot: Option<&'static SupplySnapshot>,
+    pub sync_start_block_number: BlockNumber,
+}
+
+fn resolve<T>(network: Network, name: &str, overridden: Option<T>, known: Option<T>) -> T {
+    overridden.or(known).unwrap_or_else(|| {
+        panic!("no {name} known for {network}, set it under [network_overrides]")
+    })
+}
+
+impl NetworkConstants {
+    /// Panics when a constant is neither known for the network nor overridden, a deployment
+    /// missing one can't start anywhere sensible.
+    pub fn new(network: Network, overrides: &NetworkOverrides) -> Self {
+        let known = network.known_constants();
+        let overrides = overrides.clone();
+        let first_post_merge_slot = resolve(
+            network,
+            "first_post_merge_slot",
+            overrides.first_post_merge_slot,
+            known.first_post_merge_slot,
+        );
+
+        Self {
+            beacon_genesis_timestamp: resolve(
+                network,
+                "beacon_genesis_timestamp",
+                overrides.beacon_genesis_timestamp,
+                known.beacon_genesis_timestamp,
+            ),
+            bpo1_timestamp: resolve(
+                network,
+                "bpo1_timestamp",
+                overrides.bpo1_timestamp,
+                known.bpo1_timestamp,
+            ),
+            bpo2_timestamp: resolve(
+                network,
+                "bpo2_timestamp",
+                overrides.bpo2_timestamp,
+                known.bpo2_timestamp,
+            ),
+            byzantium_block_number: resolve(
+                network,
+                "byzantium_block_number",
+                overrides.byzantium_block_number,
+                known.byzantium_block_number,
+            ),
+            cancun_timestamp: resolve(
+                network,
+                "cancun_timestamp",
+                overrides.cancun_timestamp,
+                known.cancun_timestamp,
+            ),
+            constantinople_block_number: resolve(
+                network,
+                "constantinople_block_number",
+                overrides.constantinople_block_number,
+                known.constantinople_block_number,
+            ),
+            first_post_london_slot: resolve(
+                network,
+                "first_post_london_slot",
+                overrides.first_post_london_slot,
+                known.first_post_london_slot,
+            ),
+            first_post_merge_slot,
+            first_stored_eth_supply_slot: overrides
+                .first_stored_eth_supply_slot
+                .or(known.first_stored_eth_supply_slot)
+                .unwrap_or(first_post_merge_slot),
+            genesis_block_hash: resolve(
+                network,
+                "genesis_block_hash",
+                overrides.genesis_block_hash,
+                known.genesis_block_hash,
+            ),
+            genesis_supply: overrides.genesis_supply.or(known.genesis_supply),
+            london_block_hash: resolve(
+                network,
+                "london_block_hash",
+                overrides.london_block_hash,
+                known.london_block_hash,
+            ),
+            london_block_number: resolve(
+                network,
+                "london_block_number",
+                overrides.london_block_number,
+                known.london_block_number,
+            ),
+            london_timestamp: resolve(
+                network,
+                "london_timestamp",
+                overrides.london_timestamp,
+                known.london_timestamp,
+            ),
+            merge_block_number: resolve(
+                network,
+                "merge_block_number",
+                overrides.merge_block_number,
+                known.merge_block_number,
+            ),
+            osaka_timestamp: resolve(
+                network,
+                "osaka_timestamp",
+                overrides.osaka_timestamp,
+                known.osaka_timestamp,
+            ),
+            paris_timestamp: resolve(
+                network,
+                "paris_timestamp",
+                overrides.paris_timestamp,
+                known.paris_timestamp,
+            ),
+            prague_timestamp: resolve(
+                network,
+                "prague_timestamp",
+                overrides.prague_timestamp,
+                known.prague_timestamp,
+            ),
+            seconds_per_slot: resolve(
+                network,
+                "seconds_per_slot",
+                overrides.seconds_per_slot,
+                known.seconds_per_slot,
+            ),
+            shapella_block_number: resolve(
+                network,
+                "shapella_block_number",
+                overrides.shapella_block_number,
+                known.shapella_block_number,
+            ),
+            shapella_slot: resolve(
+                network,
+                "shapella_slot",
+                overrides.shapella_slot,
+                known.shapella_slot,
+            ),
+            slots_per_epoch: resolve(
+                network,
+                "slots_per_epoch",
+                overrides.slots_per_epoch,
+                known.slots_per_epoch,
+            ),
+            supply_snapshot: network.supply_snapshot(),
+            sync_start_block_number: resolve(
+                network,
+                "sync_start_block_number",
+                overrides.sync_start_block_number,
+                known.sync_start_block_number,
+            ),
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref NETWORK_CONSTANTS: NetworkConstants =
+        NetworkConstants::new(config::CONFIG.network(), config::CONFIG.network_overrides());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mainnet_test() {
+        let constants = NetworkConstants::new(Network::Mainnet, &NetworkOverrides::default());
+        assert_eq!(constants.first_post_merge_slot, Slot(4700013));
+        assert_eq!(constants.merge_block_number, BlockNumber(15_537_394));
+        assert_eq!(constants.first_stored_eth_supply_slot, Slot(4697813));
+        assert_eq!(constants.sync_start_block_number, BlockNumber(15_253_306));
+        assert_eq!(
+            constants
+                .supply_snapshot
+                .map(|snapshot| snapshot.block_number),
+            Some(BlockNumber(15_082_718))
+        );
+        assert_eq!(
+            constants.beacon_genesis_timestamp,
+            "2020-12-01T12:00:23Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn override_test() {
+        let overrides = NetworkOverrides {
+            shapella_block_number: Some(BlockNumber(10)),
+            ..NetworkOverrides::default()
+        };
+        let constants = NetworkConstants::new(Network::Holesky, &overrides);
+        assert_eq!(constants.shapella_block_number, BlockNumber(10));
+        assert_eq!(constants.first_post_merge_slot, Slot::GENESIS);
+    }
+
+    #[test]
+    fn holesky_test() {
+        let constants = NetworkConstants::new(Network::Holesky, &NetworkOverrides::default());
+        assert_eq!(constants.shapella_block_number, BlockNumber(6_698));
+        assert_eq!(constants.seconds_per_slot, 12);
+        assert_eq!(constants.slots_per_epoch, 32);
+    }
+
+    #[test]
+    #[should_panic(expected = "no merge_block_number known for sepolia")]
+    fn missing_constant_test() {
+        resolve::<BlockNumber>(Network::Sepolia, "merge_block_number", None, None);
+    }
+
+    #[test]
+    fn sepolia_test() {
+        let constants = NetworkConstants::new(Network::Sepolia, &NetworkOverrides::default());
+        assert_eq!(constants.merge_block_number, BlockNumber(1_450_409));
+        assert_eq!(constants.first_stored_eth_supply_slot, Slot(115193));
+        assert_eq!(constants.supply_snapshot, None);
+        assert_eq!(constants.genesis_supply, None);
+        assert_eq!(constants.byzantium_block_number, BlockNumber(0));
+    }
+
+    #[test]
+    fn from_str_test() {
+        assert_eq!("sepolia".parse::<Network>(), Ok(Network::Sepolia));
+        assert!("gnosis".parse::<Network>().is_err());
+        assert!("chiado".parse::<Network>().is_err());
+    }
+}
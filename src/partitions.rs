@@ -0,0 +1,106 @@
+//! # Partitions
+//! `blocks_next` is partitioned by ranges of block numbers, `eth_supply` by year. There are no
+//! default partitions, rows outside every partition fail to insert. The block sync creates the
+//! next partitions ahead of time, when it starts and when it reaches the first block of a range.
+//! Names follow the migration which introduced partitioning, e.g. `blocks_next_p19` and
+//! `eth_supply_y2024`.
+use anyhow::Result;
+use chrono::{Datelike, TimeZone, Utc};
+use sqlx::PgPool;
+use tracing::debug;
+
+use crate::execution_chain::{self, BlockNumber};
+
+pub const BLOCKS_PER_PARTITION: i64 = 1_000_000;
+
+async fn create_blocks_partition(db_pool: &PgPool, index: i64) -> Result<()> {
+    let from = index * BLOCKS_PER_PARTITION;
+    let to = from + BLOCKS_PER_PARTITION;
+    debug!(from, to, "ensuring blocks_next partition");
+
+    sqlx::query(&format!(
+        "
+        CREATE TABLE IF NOT EXISTS blocks_next_p{index}
+        PARTITION OF blocks_next FOR VALUES FROM ({from}) TO ({to})
+        "
+    ))
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn create_supply_partition(db_pool: &PgPool, year: i32) -> Result<()> {
+    let from = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+    let to = Utc.with_ymd_and_hms(year + 1, 1, 1, 0, 0, 0).unwrap();
+    debug!(%from, %to, "ensuring eth_supply partition");
+
+    sqlx::query(&format!(
+        "
+        CREATE TABLE IF NOT EXISTS eth_supply_y{year}
+        PARTITION OF eth_supply FOR VALUES FROM ('{}') TO ('{}')
+        ",
+        from.to_rfc3339(),
+        to.to_rfc3339()
+    ))
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Makes sure the partitions for the range of the last stored block, the current year, and the
+/// ones after exist.
+pub async fn ensure_partitions(db_pool: &PgPool) -> Result<()> {
+    let last_block_number = execution_chain::get_last_block_number(db_pool)
+        .await
+        .unwrap_or(BlockNumber::GENESIS);
+    ensure_partitions_from(db_pool, last_block_number).await
+}
+
+/// Makes sure the partitions for the range of the given block, the current year, and the ones
+/// after exist.
+pub async fn ensure_partitions_from(db_pool: &PgPool, block_number: BlockNumber) -> Result<()> {
+    let index = block_number.0 / BLOCKS_PER_PARTITION;
+    create_blocks_partition(db_pool, index).await?;
+    create_blocks_partition(db_pool, index + 1).await?;
+
+    let year = Utc::now().year();
+    create_supply_partition(db_pool, year).await?;
+    create_supply_partition(db_pool, year + 1).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db;
+
+    use super::*;
+
+    async fn table_exists(db_pool: &PgPool, name: &str) -> bool {
+        sqlx::query!(r#"SELECT to_regclass($1) IS NOT NULL AS "exists!""#, name)
+            .fetch_one(db_pool)
+            .await
+            .unwrap()
+            .exists
+    }
+
+    #[tokio::test]
+    async fn ensure_partitions_test() {
+        let test_db = db::tests::TestDb::new().await;
+
+        // Creating partitions which already exist is fine.
+        ensure_partitions(&test_db.pool).await.unwrap();
+        ensure_partitions(&test_db.pool).await.unwrap();
+
+        let next_year = Utc::now().year() + 1;
+        assert!(table_exists(&test_db.pool, "blocks_next_p1").await);
+        assert!(table_exists(&test_db.pool, &format!("eth_supply_y{next_year}")).await);
+
+        ensure_partitions_from(&test_db.pool, BlockNumber(40 * BLOCKS_PER_PARTITION))
+            .await
+            .unwrap();
+        assert!(table_exists(&test_db.pool, "blocks_next_p41").await);
+    }
+}
@@ -6,10 +6,10 @@ use std::task::{Context, Poll};
 use std::time::Instant;
 use tracing::debug;
 
-use crate::env;
+use crate::config;
 
 lazy_static! {
-    static ref LOG_PERF: bool = env::get_env_bool("LOG_PERF");
+    static ref LOG_PERF: bool = config::CONFIG.log_perf();
 }
 
 /// A wrapper around a Future which adds timing data.
@@ -1,8 +1,12 @@
+mod alerts;
+mod finality_lag;
 mod grouped_analysis_1;
+mod max_age;
 mod price_stats;
 mod supply_changes;
 mod supply_over_time;
 mod supply_parts;
+mod sync_lag;
 
 use std::sync::{Arc, Mutex};
 
@@ -12,128 +16,69 @@ use axum::{routing::get, Router, Server};
 use chrono::{DateTime, Duration, Utc};
 
 use futures::try_join;
-use lazy_static::lazy_static;
-use reqwest::{
-    header::{HeaderMap, HeaderValue},
-    StatusCode,
-};
-use serde::Deserialize;
-use serde_json::json;
+use reqwest::StatusCode;
 use tokio::time::sleep;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info};
 
 use crate::{
-    env, log,
+    beacon_chain::BeaconNodeHttp,
+    config, db,
+    execution_chain::ExecutionNode,
+    log,
     phoenix::{
-        grouped_analysis_1::GroupedAnalysis1Monitor, price_stats::EthPriceStatsMonitor,
-        supply_changes::SupplyChangesMonitor, supply_over_time::SupplyOverTimeMonitor,
+        alerts::Alerts,
+        finality_lag::FinalityLagMonitor,
+        grouped_analysis_1::GroupedAnalysis1Monitor,
+        max_age::MaxAge,
+        price_stats::EthPriceStatsMonitor,
+        supply_changes::SupplyChangesMonitor,
+        supply_over_time::SupplyOverTimeMonitor,
         supply_parts::SupplyPartsMonitor,
+        sync_lag::{BeaconSyncLagMonitor, ExecutionSyncLagMonitor},
     },
 };
 
-lazy_static! {
-    static ref OPSGENIE_AUTH_HEADER: String = {
-        let opsgenie_api_key = env::get_env_var_unsafe("OPSGENIE_API_KEY");
-        format!("GenieKey {opsgenie_api_key}")
-    };
-    static ref MIN_ALARM_WAIT: Duration = Duration::minutes(30);
-}
-
-#[derive(Deserialize)]
-struct OpsGenieError {
-    message: String,
-}
-
-struct Alarm {
-    client: reqwest::Client,
-    last_fired: Option<DateTime<Utc>>,
-}
-
-impl Alarm {
-    fn new() -> Self {
-        Self {
-            client: reqwest::Client::new(),
-            last_fired: None,
-        }
-    }
-
-    fn is_throttled(&self) -> bool {
-        self.last_fired.map_or(false, |last_fired| {
-            Utc::now() - last_fired < *MIN_ALARM_WAIT
-        })
-    }
-
-    async fn fire(&mut self, message: &str) {
-        if self.is_throttled() {
-            warn!("alarm is throttled, ignoring request to fire alarm");
-            return;
-        }
-
-        error!(message, "firing alarm");
-
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            "Authorization",
-            HeaderValue::from_static(&OPSGENIE_AUTH_HEADER),
-        );
-
-        let res = self
-            .client
-            .post("https://api.opsgenie.com/v2/alerts")
-            .headers(headers)
-            .json(&json!({ "message": message }))
-            .send()
-            .await
-            .unwrap();
-
-        if res.status() != 202 {
-            match res.json::<OpsGenieError>().await {
-                Err(_) => {
-                    panic!("failed to create alarm with OpsGenie")
-                }
-                Ok(body) => {
-                    panic!(
-                        "failed to create alarm with OpsGenie, message: {}",
-                        body.message
-                    )
-                }
-            }
-        }
-
-        self.last_fired = Some(Utc::now());
-    }
-
-    async fn fire_dashboard_stalled(&mut self, name: &str) {
-        let message = format!(
-            "{} hasn't updated for more than {} seconds!",
-            name,
-            MAX_WAIT.num_seconds()
-        );
-
-        self.fire(&message).await
-    }
-}
+async fn fire_dashboard_stalled(alerts: &mut Alerts, name: &str, max_age: &Duration) {
+    let message = format!(
+        "{} hasn't updated for more than {} seconds!",
+        name,
+        max_age.num_seconds()
+    );
 
-lazy_static! {
-    static ref MAX_WAIT: Duration = Duration::minutes(8);
+    alerts.fire(name, &message).await
 }
 
 struct Phoenix {
     name: &'static str,
     last_seen: DateTime<Utc>,
+    max_age: MaxAge,
     monitor: Box<dyn PhoenixMonitor + Send + Sync>,
 }
 
 impl Phoenix {
-    fn is_age_over_limit(&self) -> bool {
-        let age = Utc::now() - self.last_seen;
+    fn new(name: &'static str, monitor: Box<dyn PhoenixMonitor + Send + Sync>) -> Self {
+        let max_age = MaxAge::from_config(config::CONFIG.phoenix().monitors.get(name));
+        debug!(name, ?max_age, "configured phoenix max age");
+        Self {
+            name,
+            last_seen: Utc::now(),
+            max_age,
+            monitor,
+        }
+    }
+
+    /// Returns the max age which was exceeded, if any.
+    fn exceeded_max_age(&self) -> Option<Duration> {
+        let now = Utc::now();
+        let age = now - self.last_seen;
+        let max_age = self.max_age.at(&now);
         debug!(
             name = self.name,
             age = age.num_seconds(),
-            limit = MAX_WAIT.num_seconds(),
+            limit = max_age.num_seconds(),
             "checking age"
         );
-        age >= *MAX_WAIT
+        (age >= max_age).then_some(max_age)
     }
 
     fn set_last_seen(&mut self, last_seen: DateTime<Utc>) {
@@ -161,7 +106,7 @@ async fn run_health_check_server(last_checked: Arc<Mutex<DateTime<Utc>>>) {
         }),
     );
 
-    let port = env::get_env_var("PORT").unwrap_or_else(|| "8080".to_string());
+    let port = config::CONFIG.port().unwrap_or("8080");
     let socket_addr = format!("0.0.0.0:{port}").parse().unwrap();
     info!(%socket_addr, "starting health check server");
     Server::bind(&socket_addr)
@@ -171,45 +116,52 @@ async fn run_health_check_server(last_checked: Arc<Mutex<DateTime<Utc>>>) {
 }
 
 async fn run_alarm_loop(last_checked: Arc<Mutex<DateTime<Utc>>>) {
-    info!(
-        "releasing phoenix, dies after {} seconds",
-        MAX_WAIT.num_seconds()
-    );
+    info!("releasing phoenix");
+
+    let mut alerts = Alerts::from_config();
 
-    let mut alarm = Alarm::new();
+    let db_pool = db::get_db_pool("phoenix").await;
+    let monitors_config = &config::CONFIG.phoenix().monitors;
 
     let mut phoenixes = vec![
-        Phoenix {
-            last_seen: Utc::now(),
-            monitor: Box::new(EthPriceStatsMonitor::new()),
-            name: "eth-price-stats",
-        },
-        Phoenix {
-            last_seen: Utc::now(),
-            name: "grouped-analysis-1",
-            monitor: Box::new(GroupedAnalysis1Monitor::new()),
-        },
-        Phoenix {
-            last_seen: Utc::now(),
-            name: "supply-over-time",
-            monitor: Box::new(SupplyOverTimeMonitor::new()),
-        },
-        Phoenix {
-            last_seen: Utc::now(),
-            name: "supply-parts",
-            monitor: Box::new(SupplyPartsMonitor::new()),
-        },
-        Phoenix {
-            last_seen: Utc::now(),
-            name: "supply-changes",
-            monitor: Box::new(SupplyChangesMonitor::new()),
-        },
+        Phoenix::new("eth-price-stats", Box::new(EthPriceStatsMonitor::new())),
+        Phoenix::new(
+            "grouped-analysis-1",
+            Box::new(GroupedAnalysis1Monitor::new()),
+        ),
+        Phoenix::new("supply-over-time", Box::new(SupplyOverTimeMonitor::new())),
+        Phoenix::new("supply-parts", Box::new(SupplyPartsMonitor::new())),
+        Phoenix::new("supply-changes", Box::new(SupplyChangesMonitor::new())),
+        Phoenix::new(
+            "execution-sync-lag",
+            Box::new(ExecutionSyncLagMonitor::new(
+                db_pool.clone(),
+                ExecutionNode::connect().await,
+                monitors_config.get("execution-sync-lag"),
+            )),
+        ),
+        Phoenix::new(
+            "beacon-sync-lag",
+            Box::new(BeaconSyncLagMonitor::new(
+                db_pool.clone(),
+                BeaconNodeHttp::new(),
+                monitors_config.get("beacon-sync-lag"),
+            )),
+        ),
+        Phoenix::new(
+            "finality-lag",
+            Box::new(FinalityLagMonitor::new(
+                db_pool,
+                monitors_config.get("finality-lag"),
+            )),
+        ),
     ];
 
     loop {
         for phoenix in phoenixes.iter_mut() {
-            if phoenix.is_age_over_limit() {
-                alarm.fire_dashboard_stalled(phoenix.name).await;
+            match phoenix.exceeded_max_age() {
+                Some(max_age) => fire_dashboard_stalled(&mut alerts, phoenix.name, &max_age).await,
+                None => alerts.resolve(phoenix.name).await,
             }
 
             let current = phoenix.monitor.refresh().await;
@@ -0,0 +1,432 @@
+//! Where phoenix sends its alerts. A sink is enabled by configuring its credentials, every
+//! configured sink is notified unless a monitor's config routes it to specific sinks. Repeat
+//! alerts for the same monitor are throttled. Once a stalled monitor recovers, its alert is
+//! resolved, sinks which track incidents close them.
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{debug, error, info, warn};
+
+use crate::config::{self, Config};
+
+lazy_static! {
+    static ref MIN_ALARM_WAIT: Duration = Duration::minutes(30);
+}
+
+#[async_trait]
+pub trait AlertSink: Send + Sync {
+    /// Used to route monitors to sinks in the config.
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, monitor: &str, message: &str) -> Result<()>;
+
+    /// Called when a monitor we alerted for recovers. Sinks which only send messages have
+    /// nothing to resolve.
+    async fn resolve(&self, _monitor: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct OpsGenieError {
+    message: String,
+}
+
+pub struct OpsGenieSink {
+    auth_header: String,
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl AlertSink for OpsGenieSink {
+    fn name(&self) -> &'static str {
+        "opsgenie"
+    }
+
+    async fn send(&self, _monitor: &str, message: &str) -> Result<()> {
+        let res = self
+            .client
+            .post("https://api.opsgenie.com/v2/alerts")
+            .header("Authorization", &self.auth_header)
+            .json(&json!({ "message": message }))
+            .send()
+            .await?;
+
+        if res.status() != 202 {
+            return match res.json::<OpsGenieError>().await {
+                Err(_) => Err(anyhow!("failed to create alarm with OpsGenie")),
+                Ok(body) => Err(anyhow!(
+                    "failed to create alarm with OpsGenie, message: {}",
+                    body.message
+                )),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+pub struct TelegramSink {
+    bot_token: String,
+    chat_id: String,
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl AlertSink for TelegramSink {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn send(&self, _monitor: &str, message: &str) -> Result<()> {
+        self.client
+            .post(format!(
+                "https://api.telegram.org/bot{}/sendMessage",
+                self.bot_token
+            ))
+            .json(&json!({ "chat_id": self.chat_id, "text": message }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+pub struct DiscordSink {
+    client: reqwest::Client,
+    webhook_url: String,
+}
+
+#[async_trait]
+impl AlertSink for DiscordSink {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    async fn send(&self, _monitor: &str, message: &str) -> Result<()> {
+        self.client
+            .post(&self.webhook_url)
+            .json(&json!({ "content": message }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Opens an incident through the PagerDuty Events API, and resolves it when the monitor
+/// recovers. Events for a monitor share a dedup key, repeat alerts update the open incident
+/// instead of opening new ones.
+pub struct PagerDutySink {
+    client: reqwest::Client,
+    routing_key: String,
+}
+
+impl PagerDutySink {
+    fn dedup_key(monitor: &str) -> String {
+        format!("phoenix-{monitor}")
+    }
+
+    async fn send_event(&self, event: serde_json::Value) -> Result<()> {
+        let res = self
+            .client
+            .post(PAGERDUTY_EVENTS_URL)
+            .json(&event)
+            .send()
+            .await?;
+
+        if res.status() != 202 {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "failed to send event to PagerDuty, status: {status}, body: {body}"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AlertSink for PagerDutySink {
+    fn name(&self) -> &'static str {
+        "pagerduty"
+    }
+
+    async fn send(&self, monitor: &str, message: &str) -> Result<()> {
+        self.send_event(json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "dedup_key": Self::dedup_key(monitor),
+            "payload": {
+                "summary": message,
+                "source": "phoenix",
+                "severity": "critical",
+            },
+        }))
+        .await
+    }
+
+    async fn resolve(&self, monitor: &str) -> Result<()> {
+        self.send_event(json!({
+            "routing_key": self.routing_key,
+            "event_action": "resolve",
+            "dedup_key": Self::dedup_key(monitor),
+        }))
+        .await
+    }
+}
+
+fn sinks_from_config(config: &Config) -> Vec<Box<dyn AlertSink>> {
+    let client = reqwest::Client::new();
+    let mut sinks: Vec<Box<dyn AlertSink>> = Vec::new();
+
+    if let Some(opsgenie_api_key) = config.opsgenie_api_key() {
+        sinks.push(Box::new(OpsGenieSink {
+            auth_header: format!("GenieKey {opsgenie_api_key}"),
+            client: client.clone(),
+        }));
+    }
+
+    if let (Some(bot_token), Some(chat_id)) =
+        (config.telegram_bot_token(), config.telegram_chat_id())
+    {
+        sinks.push(Box::new(TelegramSink {
+            bot_token: bot_token.to_string(),
+            chat_id: chat_id.to_string(),
+            client: client.clone(),
+        }));
+    }
+
+    if let Some(webhook_url) = config.discord_webhook_url() {
+        sinks.push(Box::new(DiscordSink {
+            client: client.clone(),
+            webhook_url: webhook_url.to_string(),
+        }));
+    }
+
+    if let Some(routing_key) = config.pagerduty_routing_key() {
+        sinks.push(Box::new(PagerDutySink {
+            client,
+            routing_key: routing_key.to_string(),
+        }));
+    }
+
+    sinks
+}
+
+pub struct Alerts {
+    /// Monitors we alerted for, which haven't recovered yet.
+    firing: HashSet<String>,
+    last_fired: HashMap<String, DateTime<Utc>>,
+    /// Monitor name to the names of the sinks it alerts.
+    routes: HashMap<String, Vec<String>>,
+    sinks: Vec<Box<dyn AlertSink>>,
+}
+
+impl Alerts {
+    fn new(sinks: Vec<Box<dyn AlertSink>>, routes: HashMap<String, Vec<String>>) -> Self {
+        Self {
+            firing: HashSet::new(),
+            last_fired: HashMap::new(),
+            routes,
+            sinks,
+        }
+    }
+
+    pub fn from_config() -> Self {
+        let config = &*config::CONFIG;
+        let sinks = sinks_from_config(config);
+
+        if sinks.is_empty() {
+            panic!("no alert sinks configured, configure at least one of OpsGenie, PagerDuty, Telegram or Discord");
+        }
+
+        let routes = config
+            .phoenix()
+            .monitors
+            .iter()
+            .filter_map(|(monitor, monitor_config)| {
+                monitor_config
+                    .sinks
+                    .clone()
+                    .map(|sinks| (monitor.clone(), sinks))
+            })
+            .collect();
+
+        Self::new(sinks, routes)
+    }
+
+    fn is_throttled(&self, monitor: &str) -> bool {
+        self.last_fired
+            .get(monitor)
+            .is_some_and(|last_fired| Utc::now() - *last_fired < *MIN_ALARM_WAIT)
+    }
+
+    fn routed_sinks<'a>(&'a self, monitor: &'a str) -> impl Iterator<Item = &'a dyn AlertSink> {
+        self.sinks
+            .iter()
+            .map(|sink| sink.as_ref())
+            .filter(move |sink| {
+                self.routes
+                    .get(monitor)
+                    .is_none_or(|sink_names| sink_names.iter().any(|name| name == sink.name()))
+            })
+    }
+
+    /// Notifies the sinks the monitor is routed to. A failing sink is logged, and doesn't keep
+    /// the others from being notified.
+    pub async fn fire(&mut self, monitor: &str, message: &str) {
+        if self.is_throttled(monitor) {
+            warn!(
+                monitor,
+                "alarm is throttled, ignoring request to fire alarm"
+            );
+            return;
+        }
+
+        error!(monitor, message, "firing alarm");
+
+        for sink in self.routed_sinks(monitor) {
+            debug!(monitor, sink = sink.name(), "sending alert");
+            if let Err(err) = sink.send(monitor, message).await {
+                error!(monitor, sink = sink.name(), %err, "failed to send alert");
+            }
+        }
+
+        self.firing.insert(monitor.to_string());
+        self.last_fired.insert(monitor.to_string(), Utc::now());
+    }
+
+    /// Resolves the alert for a recovered monitor, if we fired one. A next stall fires right
+    /// away, it is a new incident.
+    pub async fn resolve(&mut self, monitor: &str) {
+        if !self.firing.remove(monitor) {
+            return;
+        }
+
+        info!(monitor, "monitor recovered, resolving alarm");
+
+        for sink in self.routed_sinks(monitor) {
+            if let Err(err) = sink.resolve(monitor).await {
+                error!(monitor, sink = sink.name(), %err, "failed to resolve alert");
+            }
+        }
+
+        self.last_fired.remove(monitor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    struct RecordingSink {
+        name: &'static str,
+        resolved: Arc<Mutex<Vec<(&'static str, String)>>>,
+        sent: Arc<Mutex<Vec<(&'static str, String)>>>,
+    }
+
+    #[async_trait]
+    impl AlertSink for RecordingSink {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn send(&self, monitor: &str, _message: &str) -> Result<()> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((self.name, monitor.to_string()));
+            Ok(())
+        }
+
+        async fn resolve(&self, monitor: &str) -> Result<()> {
+            self.resolved
+                .lock()
+                .unwrap()
+                .push((self.name, monitor.to_string()));
+            Ok(())
+        }
+    }
+
+    type Calls = Arc<Mutex<Vec<(&'static str, String)>>>;
+
+    fn recording_alerts(sent: &Calls, resolved: &Calls) -> Alerts {
+        let sinks: Vec<Box<dyn AlertSink>> = vec![
+            Box::new(RecordingSink {
+                name: "telegram",
+                resolved: Arc::new(Mutex::new(Vec::new())),
+                sent: sent.clone(),
+            }),
+            Box::new(RecordingSink {
+                name: "discord",
+                resolved: resolved.clone(),
+                sent: sent.clone(),
+            }),
+        ];
+        let routes = HashMap::from([("supply-parts".to_string(), vec!["discord".to_string()])]);
+        Alerts::new(sinks, routes)
+    }
+
+    #[tokio::test]
+    async fn fire_routes_alerts_test() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut alerts = recording_alerts(&sent, &Arc::new(Mutex::new(Vec::new())));
+
+        alerts.fire("supply-parts", "stalled").await;
+        alerts.fire("supply-changes", "stalled").await;
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                ("discord", "supply-parts".to_string()),
+                ("telegram", "supply-changes".to_string()),
+                ("discord", "supply-changes".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fire_throttles_repeat_alerts_test() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut alerts = recording_alerts(&sent, &Arc::new(Mutex::new(Vec::new())));
+
+        alerts.fire("supply-parts", "stalled").await;
+        alerts.fire("supply-parts", "still stalled").await;
+
+        assert_eq!(sent.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn resolve_recovered_monitor_test() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let resolved = Arc::new(Mutex::new(Vec::new()));
+        let mut alerts = recording_alerts(&sent, &resolved);
+
+        // Nothing fired, nothing to resolve.
+        alerts.resolve("supply-parts").await;
+        assert!(resolved.lock().unwrap().is_empty());
+
+        alerts.fire("supply-parts", "stalled").await;
+        alerts.resolve("supply-parts").await;
+        alerts.resolve("supply-parts").await;
+        assert_eq!(
+            *resolved.lock().unwrap(),
+            vec![("discord", "supply-parts".to_string())]
+        );
+
+        // A new stall after recovering isn't throttled.
+        alerts.fire("supply-parts", "stalled again").await;
+        assert_eq!(sent.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,76 @@
+//! Monitors how far finality trails the chain, as stored by the beacon sync. Reports the last
+//! time the finalized checkpoint was within `max_lag` epochs of the epoch of the last synced state,
+//! phoenix alerts once that is too long ago.
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::debug;
+
+use crate::{beacon_chain::finality, config::MonitorConfig};
+
+use super::{sync_lag::update_last_in_sync, PhoenixMonitor};
+
+/// Finality normally trails by two epochs.
+const DEFAULT_MAX_FINALITY_LAG: i64 = 4;
+
+fn max_finality_lag_from_config(monitor_config: Option<&MonitorConfig>) -> i64 {
+    monitor_config
+        .and_then(|monitor_config| monitor_config.max_lag)
+        .map_or(DEFAULT_MAX_FINALITY_LAG, |max_lag| max_lag as i64)
+}
+
+pub struct FinalityLagMonitor {
+    db_pool: PgPool,
+    last_in_sync: Mutex<DateTime<Utc>>,
+    max_lag: i64,
+}
+
+impl FinalityLagMonitor {
+    pub fn new(db_pool: PgPool, monitor_config: Option<&MonitorConfig>) -> Self {
+        Self {
+            db_pool,
+            last_in_sync: Mutex::new(Utc::now()),
+            max_lag: max_finality_lag_from_config(monitor_config),
+        }
+    }
+}
+
+#[async_trait]
+impl PhoenixMonitor for FinalityLagMonitor {
+    async fn refresh(&self) -> Result<DateTime<Utc>> {
+        let last_finality = finality::get_last_finality(&self.db_pool)
+            .await
+            .ok_or_else(|| anyhow!("no beacon finality stored"))?;
+        debug!(
+            slot = %last_finality.slot,
+            justified_epoch = last_finality.justified_epoch,
+            finalized_epoch = last_finality.finalized_epoch,
+            finality_lag = last_finality.finality_lag,
+            "finality lag"
+        );
+        Ok(update_last_in_sync(
+            &self.last_in_sync,
+            last_finality.finality_lag as i64,
+            self.max_lag,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_finality_lag_from_config_test() {
+        assert_eq!(max_finality_lag_from_config(None), DEFAULT_MAX_FINALITY_LAG);
+
+        let monitor_config = MonitorConfig {
+            max_lag: Some(8),
+            ..MonitorConfig::default()
+        };
+        assert_eq!(max_finality_lag_from_config(Some(&monitor_config)), 8);
+    }
+}
@@ -0,0 +1,113 @@
+//! How long a monitor may go without updates before we alert. Defaults to `DEFAULT_MAX_AGE`, may
+//! be set per monitor in the config, and overridden for windows of the day.
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use lazy_static::lazy_static;
+
+use crate::config::MonitorConfig;
+
+lazy_static! {
+    static ref DEFAULT_MAX_AGE: Duration = Duration::minutes(8);
+}
+
+#[derive(Debug)]
+struct Window {
+    from: NaiveTime,
+    to: NaiveTime,
+    max_age: Duration,
+}
+
+impl Window {
+    fn contains(&self, time: &NaiveTime) -> bool {
+        if self.from <= self.to {
+            self.from <= *time && *time < self.to
+        } else {
+            // Wraps past midnight.
+            self.from <= *time || *time < self.to
+        }
+    }
+}
+
+fn parse_time(str: &str) -> NaiveTime {
+    NaiveTime::parse_from_str(str, "%H:%M")
+        .unwrap_or_else(|err| panic!("expect max age override time {str} to be HH:MM, {err}"))
+}
+
+#[derive(Debug)]
+pub struct MaxAge {
+    default: Duration,
+    /// The first window containing the current time applies.
+    windows: Vec<Window>,
+}
+
+impl MaxAge {
+    pub fn from_config(monitor_config: Option<&MonitorConfig>) -> Self {
+        match monitor_config {
+            None => Self {
+                default: *DEFAULT_MAX_AGE,
+                windows: vec![],
+            },
+            Some(monitor_config) => Self {
+                default: monitor_config
+                    .max_age_secs
+                    .map_or(*DEFAULT_MAX_AGE, |secs| Duration::seconds(secs as i64)),
+                windows: monitor_config
+                    .max_age_overrides
+                    .iter()
+                    .map(|max_age_override| Window {
+                        from: parse_time(&max_age_override.from),
+                        to: parse_time(&max_age_override.to),
+                        max_age: Duration::seconds(max_age_override.max_age_secs as i64),
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    pub fn at(&self, date_time: &DateTime<Utc>) -> Duration {
+        let time = date_time.time();
+        self.windows
+            .iter()
+            .find(|window| window.contains(&time))
+            .map_or(self.default, |window| window.max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::config::MaxAgeOverride;
+
+    use super::*;
+
+    #[test]
+    fn default_max_age_test() {
+        let max_age = MaxAge::from_config(None);
+        assert_eq!(max_age.at(&Utc::now()), *DEFAULT_MAX_AGE);
+    }
+
+    #[test]
+    fn max_age_override_test() {
+        let monitor_config = MonitorConfig {
+            max_age_secs: Some(600),
+            max_age_overrides: vec![MaxAgeOverride {
+                from: "22:00".to_string(),
+                to: "06:00".to_string(),
+                max_age_secs: 3600,
+            }],
+            max_lag: None,
+            sinks: None,
+        };
+        let max_age = MaxAge::from_config(Some(&monitor_config));
+
+        let noon = Utc.with_ymd_and_hms(2023, 1, 1, 12, 0, 0).unwrap();
+        let before_midnight = Utc.with_ymd_and_hms(2023, 1, 1, 23, 0, 0).unwrap();
+        let after_midnight = Utc.with_ymd_and_hms(2023, 1, 1, 5, 59, 0).unwrap();
+        let end_of_window = Utc.with_ymd_and_hms(2023, 1, 1, 6, 0, 0).unwrap();
+
+        assert_eq!(max_age.at(&noon), Duration::minutes(10));
+        assert_eq!(max_age.at(&before_midnight), Duration::hours(1));
+        assert_eq!(max_age.at(&after_midnight), Duration::hours(1));
+        assert_eq!(max_age.at(&end_of_window), Duration::minutes(10));
+    }
+}
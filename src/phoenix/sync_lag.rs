@@ -0,0 +1,138 @@
+//! Monitors how far our analysis DB is behind the nodes. Stale API timestamps tell us something
+//! stopped updating, these tell us which sync fell behind. Each monitor reports the last time we
+//! were within `max_lag` blocks or slots of the node head, phoenix alerts once that is too long
+//! ago.
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::debug;
+
+use crate::{
+    beacon_chain::{self, BeaconNode, BeaconNodeHttp},
+    config::MonitorConfig,
+    execution_chain::{self, ExecutionNode},
+};
+
+use super::PhoenixMonitor;
+
+const DEFAULT_MAX_LAG: i64 = 10;
+
+fn max_lag_from_config(monitor_config: Option<&MonitorConfig>) -> i64 {
+    monitor_config
+        .and_then(|monitor_config| monitor_config.max_lag)
+        .map_or(DEFAULT_MAX_LAG, |max_lag| max_lag as i64)
+}
+
+/// Moves the last in sync time forward when the lag is acceptable, and returns it.
+pub fn update_last_in_sync(
+    last_in_sync: &Mutex<DateTime<Utc>>,
+    lag: i64,
+    max_lag: i64,
+) -> DateTime<Utc> {
+    let mut last_in_sync = last_in_sync.lock().unwrap();
+    if lag <= max_lag {
+        *last_in_sync = Utc::now();
+    }
+    *last_in_sync
+}
+
+pub struct ExecutionSyncLagMonitor {
+    db_pool: PgPool,
+    execution_node: ExecutionNode,
+    last_in_sync: Mutex<DateTime<Utc>>,
+    max_lag: i64,
+}
+
+impl ExecutionSyncLagMonitor {
+    pub fn new(
+        db_pool: PgPool,
+        execution_node: ExecutionNode,
+        monitor_config: Option<&MonitorConfig>,
+    ) -> Self {
+        Self {
+            db_pool,
+            execution_node,
+            last_in_sync: Mutex::new(Utc::now()),
+            max_lag: max_lag_from_config(monitor_config),
+        }
+    }
+}
+
+#[async_trait]
+impl PhoenixMonitor for ExecutionSyncLagMonitor {
+    async fn refresh(&self) -> Result<DateTime<Utc>> {
+        let last_stored = execution_chain::get_last_block_number(&self.db_pool)
+            .await
+            .ok_or_else(|| anyhow!("no execution blocks stored"))?;
+        let head = self.execution_node.get_latest_block().await.number;
+        let lag = (head - last_stored) as i64;
+        debug!(%head, %last_stored, lag, "execution sync lag");
+        Ok(update_last_in_sync(&self.last_in_sync, lag, self.max_lag))
+    }
+}
+
+pub struct BeaconSyncLagMonitor {
+    beacon_node: BeaconNodeHttp,
+    db_pool: PgPool,
+    last_in_sync: Mutex<DateTime<Utc>>,
+    max_lag: i64,
+}
+
+impl BeaconSyncLagMonitor {
+    pub fn new(
+        db_pool: PgPool,
+        beacon_node: BeaconNodeHttp,
+        monitor_config: Option<&MonitorConfig>,
+    ) -> Self {
+        Self {
+            beacon_node,
+            db_pool,
+            last_in_sync: Mutex::new(Utc::now()),
+            max_lag: max_lag_from_config(monitor_config),
+        }
+    }
+}
+
+#[async_trait]
+impl PhoenixMonitor for BeaconSyncLagMonitor {
+    async fn refresh(&self) -> Result<DateTime<Utc>> {
+        let last_stored = beacon_chain::get_last_state(&self.db_pool)
+            .await
+            .ok_or_else(|| anyhow!("no beacon states stored"))?
+            .slot;
+        let head = self.beacon_node.get_last_header().await?.slot();
+        let lag = (head.0 - last_stored.0) as i64;
+        debug!(%head, %last_stored, lag, "beacon sync lag");
+        Ok(update_last_in_sync(&self.last_in_sync, lag, self.max_lag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn update_last_in_sync_test() {
+        let an_hour_ago = Utc::now() - Duration::hours(1);
+        let last_in_sync = Mutex::new(an_hour_ago);
+
+        assert_eq!(update_last_in_sync(&last_in_sync, 20, 10), an_hour_ago);
+        assert!(update_last_in_sync(&last_in_sync, 10, 10) > an_hour_ago);
+    }
+
+    #[test]
+    fn max_lag_from_config_test() {
+        assert_eq!(max_lag_from_config(None), DEFAULT_MAX_LAG);
+
+        let monitor_config = MonitorConfig {
+            max_lag: Some(3),
+            ..MonitorConfig::default()
+        };
+        assert_eq!(max_lag_from_config(Some(&monitor_config)), 3);
+    }
+}
@@ -0,0 +1,15 @@
+//! # Rollback
+//! When the chain reorgs, everything derived from the dropped blocks or slots has to go. Modules
+//! implement `Analysis::on_rollback`, the syncs roll back every registered analysis in a single
+//! transaction, see `analysis`.
+use crate::{beacon_chain::Slot, execution_chain::BlockNumber};
+
+#[derive(Debug, Clone, Copy)]
+pub enum RollbackPoint {
+    /// Execution blocks with a number greater than or equal to this.
+    BlockNumbersGte(BlockNumber),
+    /// Only this slot.
+    Slot(Slot),
+    /// Slots greater than or equal to this.
+    SlotsGte(Slot),
+}
@@ -0,0 +1,649 @@
+//! # Scheduler
+//! Runs periodic jobs, like updating the issuance estimate, from one long running service instead
+//! of ad hoc. Each job runs on its own interval, with a random delay added to spread load, and a
+//! Postgres advisory lock makes sure a job never runs twice at the same time, even when several
+//! schedulers are running.
+//!
+//! Default schedules may be overridden per job in the config file, under `[jobs.<job name>]`.
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::future::join_all;
+use sqlx::{PgConnection, PgPool};
+use tokio::time::{self, sleep, MissedTickBehavior};
+use tracing::{debug, error, info};
+
+use crate::{
+    analytics_sinks::AnalyticsSinks,
+    beacon_chain::{
+        self, effective_balance_sums, graffiti, health_score, proposers, BeaconNodeHttp,
+    },
+    btc_price, censorship,
+    config::{self, JobConfig},
+    db, downsampling,
+    execution_chain::{self, ExecutionNode},
+    heal::HealOptions,
+    job_progress::JobProgress,
+    key_value_store::{self, KeyValueStorePostgres},
+    log, mev_blocks, partitions,
+    shutdown::ShutdownSignal,
+    usd_price,
+};
+
+#[async_trait]
+pub trait Job: Send + Sync {
+    /// Used in logs, the config file, and as the advisory lock key.
+    fn name(&self) -> &'static str;
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Schedule {
+    pub interval: Duration,
+    /// Each run is delayed by a random duration up to this.
+    pub jitter: Duration,
+}
+
+/// Applies the config overrides to the default schedule. Returns `None` for disabled jobs.
+fn schedule_from_config(default: Schedule, job_config: Option<&JobConfig>) -> Option<Schedule> {
+    match job_config {
+        None => Some(default),
+        Some(JobConfig {
+            enabled: Some(false),
+            ..
+        }) => None,
+        Some(job_config) => Some(Schedule {
+            interval: job_config
+                .interval_secs
+                .map_or(default.interval, Duration::from_secs),
+            jitter: job_config
+                .jitter_secs
+                .map_or(default.jitter, Duration::from_secs),
+        }),
+    }
+}
+
+fn random_jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+
+    // Every RandomState is seeded randomly, good enough to spread out job runs.
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(random % max.as_millis() as u64)
+}
+
+async fn try_lock(connection: &mut PgConnection, name: &str) -> bool {
+    sqlx::query!(
+        r#"SELECT pg_try_advisory_lock(hashtext($1)) AS "is_locked!""#,
+        name
+    )
+    .fetch_one(connection)
+    .await
+    .unwrap()
+    .is_locked
+}
+
+async fn unlock(connection: &mut PgConnection, name: &str) {
+    sqlx::query!("SELECT pg_advisory_unlock(hashtext($1))", name)
+        .fetch_one(connection)
+        .await
+        .unwrap();
+}
+
+async fn run_job_once(db_pool: &PgPool, job: &Arc<dyn Job>) {
+    let name = job.name();
+
+    // Advisory locks belong to a session, we hold on to the connection until we unlock.
+    let mut connection = db_pool.acquire().await.unwrap();
+    if !try_lock(&mut connection, name).await {
+        info!(name, "job is still running elsewhere, skipping this run");
+        return;
+    }
+
+    // Jobs may keep their own progress under their name, runs are recorded separately.
+    let key_value_store = KeyValueStorePostgres::new(db_pool.clone());
+    let job_progress = JobProgress::<()>::new(format!("scheduled-job-{name}"), &key_value_store);
+
+    info!(name, "running job");
+    job_progress.start().await;
+    let started_on = Instant::now();
+
+    // A panicking job shouldn't take the scheduler, and every other job, down with it.
+    let run = tokio::spawn({
+        let db_pool = db_pool.clone();
+        let job = job.clone();
+        async move { job.run(&db_pool).await }
+    });
+
+    match run.await {
+        Ok(Ok(())) => {
+            info!(
+                name,
+                duration_ms = started_on.elapsed().as_millis() as u64,
+                "job done"
+            );
+            job_progress.finish().await;
+        }
+        Ok(Err(err)) => {
+            error!(
+                name,
+                duration_ms = started_on.elapsed().as_millis() as u64,
+                %err,
+                "job failed"
+            );
+            job_progress.fail(&err.to_string()).await;
+        }
+        Err(join_error) => {
+            error!(
+                name,
+                duration_ms = started_on.elapsed().as_millis() as u64,
+                %join_error,
+                "job panicked"
+            );
+            job_progress.fail(&join_error.to_string()).await;
+        }
+    }
+
+    unlock(&mut connection, name).await;
+}
+
+async fn run_job_periodically(
+    db_pool: &PgPool,
+    job: &Arc<dyn Job>,
+    schedule: &Schedule,
+    shutdown_signal: &ShutdownSignal,
+) {
+    let mut interval = time::interval(schedule.interval);
+    // When a run takes longer than the interval, the next run starts an interval after it
+    // finished, instead of immediately catching up.
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown_signal.clone().requested() => break,
+        }
+
+        tokio::select! {
+            _ = sleep(random_jitter(schedule.jitter)) => {}
+            _ = shutdown_signal.clone().requested() => break,
+        }
+
+        // A run in progress is finished before shutting down.
+        run_job_once(db_pool, job).await;
+    }
+
+    debug!(name = job.name(), "stopped scheduling job");
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Vec<(Arc<dyn Job>, Schedule)>,
+}
+
+impl Scheduler {
+    /// Registers a job with its default schedule, unless the config disables it.
+    pub fn register(mut self, job: impl Job + 'static, default_schedule: Schedule) -> Self {
+        let name = job.name();
+        match schedule_from_config(default_schedule, config::CONFIG.job(name)) {
+            Some(schedule) => {
+                debug!(name, ?schedule, "registered job");
+                self.jobs.push((Arc::new(job), schedule));
+            }
+            None => info!(name, "job disabled in config"),
+        }
+        self
+    }
+
+    /// Runs all jobs until shutdown is requested.
+    pub async fn run(&self, db_pool: &PgPool, shutdown_signal: &ShutdownSignal) {
+        join_all(
+            self.jobs.iter().map(|(job, schedule)| {
+                run_job_periodically(db_pool, job, schedule, shutdown_signal)
+            }),
+        )
+        .await;
+    }
+}
+
+pub struct IssuanceEstimateJob;
+
+#[async_trait]
+impl Job for IssuanceEstimateJob {
+    fn name(&self) -> &'static str {
+        "update-issuance-estimate"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        beacon_chain::update_issuance_estimate_with_pool(db_pool).await;
+        Ok(())
+    }
+}
+
+pub struct EffectiveBalanceSumJob {
+    beacon_node: BeaconNodeHttp,
+}
+
+#[async_trait]
+impl Job for EffectiveBalanceSumJob {
+    fn name(&self) -> &'static str {
+        "update-effective-balance-sum"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        effective_balance_sums::update_effective_balance_sum_with_pool(db_pool, &self.beacon_node)
+            .await;
+        Ok(())
+    }
+}
+
+pub struct HealEthPricesJob;
+
+#[async_trait]
+impl Job for HealEthPricesJob {
+    fn name(&self) -> &'static str {
+        "heal-eth-prices"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        let options = HealOptions {
+            chunk_size: 1000,
+            dry_run: false,
+        };
+        usd_price::heal_eth_prices_with_pool(
+            db_pool,
+            10,
+            &options,
+            &usd_price::HealEthPricesOptions::from_config(),
+        )
+        .await
+    }
+}
+
+pub struct HealBtcPricesJob;
+
+#[async_trait]
+impl Job for HealBtcPricesJob {
+    fn name(&self) -> &'static str {
+        "heal-btc-prices"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        let options = HealOptions {
+            chunk_size: 1000,
+            dry_run: false,
+        };
+        btc_price::heal_btc_prices_with_pool(db_pool, 10, &options).await
+    }
+}
+
+pub struct DeleteExpiredKeysJob;
+
+#[async_trait]
+impl Job for DeleteExpiredKeysJob {
+    fn name(&self) -> &'static str {
+        "delete-expired-keys"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        let deleted = key_value_store::delete_expired(db_pool).await;
+        debug!(deleted, "deleted expired keys");
+        Ok(())
+    }
+}
+
+pub struct PendingDepositsJob {
+    beacon_node: BeaconNodeHttp,
+}
+
+#[async_trait]
+impl Job for PendingDepositsJob {
+    fn name(&self) -> &'static str {
+        "update-pending-deposits"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        let execution_node = ExecutionNode::connect().await;
+        execution_chain::update_pending_deposits(db_pool, &execution_node, &self.beacon_node).await
+    }
+}
+
+pub struct CreatePartitionsJob;
+
+#[async_trait]
+impl Job for CreatePartitionsJob {
+    fn name(&self) -> &'static str {
+        "create-partitions"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        partitions::ensure_partitions(db_pool).await
+    }
+}
+
+pub struct DownsampleJob;
+
+#[async_trait]
+impl Job for DownsampleJob {
+    fn name(&self) -> &'static str {
+        "downsample"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        downsampling::downsample(db_pool).await
+    }
+}
+
+pub struct CensorshipJob;
+
+#[async_trait]
+impl Job for CensorshipJob {
+    fn name(&self) -> &'static str {
+        "update-censorship"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        censorship::update_censorship(db_pool).await
+    }
+}
+
+pub struct GraffitiCountsJob;
+
+#[async_trait]
+impl Job for GraffitiCountsJob {
+    fn name(&self) -> &'static str {
+        "update-graffiti-counts"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        graffiti::update_graffiti_counts(db_pool).await;
+        Ok(())
+    }
+}
+
+pub struct ProposerLeaderboardJob;
+
+#[async_trait]
+impl Job for ProposerLeaderboardJob {
+    fn name(&self) -> &'static str {
+        "update-proposer-leaderboard"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        proposers::update_proposer_leaderboard(db_pool).await;
+        Ok(())
+    }
+}
+
+pub struct PayloadValuesJob;
+
+#[async_trait]
+impl Job for PayloadValuesJob {
+    fn name(&self) -> &'static str {
+        "update-payload-values"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        mev_blocks::update_payload_values(db_pool).await;
+        Ok(())
+    }
+}
+
+pub struct HealthScoreJob {
+    beacon_node: BeaconNodeHttp,
+}
+
+#[async_trait]
+impl Job for HealthScoreJob {
+    fn name(&self) -> &'static str {
+        "update-health-score"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        health_score::update_health_score(db_pool, &self.beacon_node).await
+    }
+}
+
+pub struct ExportAnalyticsJob {
+    analytics_sinks: AnalyticsSinks,
+}
+
+#[async_trait]
+impl Job for ExportAnalyticsJob {
+    fn name(&self) -> &'static str {
+        "export-analytics"
+    }
+
+    async fn run(&self, db_pool: &PgPool) -> Result<()> {
+        self.analytics_sinks.export(db_pool).await
+    }
+}
+
+pub async fn run_scheduler() {
+    log::init_with_env();
+
+    info!("starting scheduler");
+
+    let shutdown_signal = ShutdownSignal::listen();
+
+    let db_pool = db::get_db_pool("scheduler").await;
+
+    sqlx::migrate!().run(&db_pool).await.unwrap();
+
+    let scheduler = Scheduler::default()
+        .register(
+            IssuanceEstimateJob,
+            Schedule {
+                interval: Duration::from_secs(60 * 60),
+                jitter: Duration::from_secs(60),
+            },
+        )
+        .register(
+            EffectiveBalanceSumJob {
+                beacon_node: BeaconNodeHttp::new(),
+            },
+            Schedule {
+                interval: Duration::from_secs(60 * 60),
+                jitter: Duration::from_secs(60),
+            },
+        )
+        .register(
+            HealEthPricesJob,
+            Schedule {
+                interval: Duration::from_secs(24 * 60 * 60),
+                jitter: Duration::from_secs(10 * 60),
+            },
+        )
+        .register(
+            HealBtcPricesJob,
+            Schedule {
+                interval: Duration::from_secs(24 * 60 * 60),
+                jitter: Duration::from_secs(10 * 60),
+            },
+        )
+        .register(
+            DeleteExpiredKeysJob,
+            Schedule {
+                interval: Duration::from_secs(60 * 60),
+                jitter: Duration::from_secs(5 * 60),
+            },
+        )
+        .register(
+            PendingDepositsJob {
+                beacon_node: BeaconNodeHttp::new(),
+            },
+            Schedule {
+                interval: Duration::from_secs(15 * 60),
+                jitter: Duration::from_secs(60),
+            },
+        )
+        .register(
+            CreatePartitionsJob,
+            Schedule {
+                interval: Duration::from_secs(24 * 60 * 60),
+                jitter: Duration::from_secs(10 * 60),
+            },
+        )
+        .register(
+            DownsampleJob,
+            Schedule {
+                interval: Duration::from_secs(60 * 60),
+                jitter: Duration::from_secs(5 * 60),
+            },
+        )
+        .register(
+            CensorshipJob,
+            Schedule {
+                interval: Duration::from_secs(15 * 60),
+                jitter: Duration::from_secs(60),
+            },
+        )
+        .register(
+            GraffitiCountsJob,
+            Schedule {
+                interval: Duration::from_secs(15 * 60),
+                jitter: Duration::from_secs(60),
+            },
+        )
+        .register(
+            ProposerLeaderboardJob,
+            Schedule {
+                interval: Duration::from_secs(15 * 60),
+                jitter: Duration::from_secs(60),
+            },
+        )
+        .register(
+            PayloadValuesJob,
+            Schedule {
+                interval: Duration::from_secs(15 * 60),
+                jitter: Duration::from_secs(60),
+            },
+        )
+        .register(
+            HealthScoreJob {
+                beacon_node: BeaconNodeHttp::new(),
+            },
+            Schedule {
+                interval: Duration::from_secs(60),
+                jitter: Duration::from_secs(10),
+            },
+        );
+
+    // Analytics are only exported when a sink is configured.
+    let scheduler = match AnalyticsSinks::from_config() {
+        Some(analytics_sinks) => scheduler.register(
+            ExportAnalyticsJob { analytics_sinks },
+            Schedule {
+                interval: Duration::from_secs(60),
+                jitter: Duration::from_secs(10),
+            },
+        ),
+        None => scheduler,
+    };
+
+    scheduler.run(&db_pool, &shutdown_signal).await;
+
+    info!("stopped scheduler");
+    db_pool.close().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_SCHEDULE: Schedule = Schedule {
+        interval: Duration::from_secs(60),
+        jitter: Duration::from_secs(10),
+    };
+
+    #[test]
+    fn schedule_from_config_test() {
+        assert_eq!(
+            schedule_from_config(DEFAULT_SCHEDULE, None),
+            Some(DEFAULT_SCHEDULE)
+        );
+
+        let disabled = JobConfig {
+            enabled: Some(false),
+            ..JobConfig::default()
+        };
+        assert_eq!(
+            schedule_from_config(DEFAULT_SCHEDULE, Some(&disabled)),
+            None
+        );
+
+        let faster = JobConfig {
+            interval_secs: Some(30),
+            ..JobConfig::default()
+        };
+        assert_eq!(
+            schedule_from_config(DEFAULT_SCHEDULE, Some(&faster)),
+            Some(Schedule {
+                interval: Duration::from_secs(30),
+                jitter: Duration::from_secs(10),
+            })
+        );
+    }
+
+    #[test]
+    fn random_jitter_test() {
+        assert_eq!(random_jitter(Duration::ZERO), Duration::ZERO);
+        let jitter = random_jitter(Duration::from_secs(1));
+        assert!(jitter < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn advisory_lock_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let mut other_connection = db::tests::get_test_db_connection().await;
+
+        assert!(try_lock(&mut connection, "advisory_lock_test").await);
+        assert!(!try_lock(&mut other_connection, "advisory_lock_test").await);
+
+        unlock(&mut connection, "advisory_lock_test").await;
+        assert!(try_lock(&mut other_connection, "advisory_lock_test").await);
+        unlock(&mut other_connection, "advisory_lock_test").await;
+    }
+
+    struct PanickingJob;
+
+    #[async_trait]
+    impl Job for PanickingJob {
+        fn name(&self) -> &'static str {
+            "panicking-job-test"
+        }
+
+        async fn run(&self, _db_pool: &PgPool) -> Result<()> {
+            panic!("panicking job");
+        }
+    }
+
+    #[tokio::test]
+    async fn run_panicking_job_once_test() {
+        let db_pool = PgPool::connect(&db::tests::get_test_db_url())
+            .await
+            .unwrap();
+        let job: Arc<dyn Job> = Arc::new(PanickingJob);
+
+        run_job_once(&db_pool, &job).await;
+
+        let key_value_store = KeyValueStorePostgres::new(db_pool.clone());
+        let record = JobProgress::<()>::new("scheduled-job-panicking-job-test", &key_value_store)
+            .record()
+            .await;
+        assert!(record.last_error.unwrap().contains("panicked"));
+
+        // The lock was released, the job can run again.
+        let mut connection = db_pool.acquire().await.unwrap();
+        assert!(try_lock(&mut connection, "panicking-job-test").await);
+        unlock(&mut connection, "panicking-job-test").await;
+    }
+}
@@ -0,0 +1,62 @@
+//! Relays cache updates to connected frontends as server-sent events. Each event is named after
+//! the cache key, and carries the new value, so clients no longer need to poll.
+use std::convert::Infallible;
+
+use axum::{
+    response::sse::{Event, KeepAlive, Sse},
+    Extension,
+};
+use futures::{stream, Stream};
+use serde_json::Value;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+use crate::caching::CacheKey;
+
+use super::StateExtension;
+
+// Clients which fall further behind than this skip the updates they missed.
+const CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct CacheUpdate {
+    pub cache_key: CacheKey,
+    pub value: Value,
+}
+
+pub fn channel() -> broadcast::Sender<CacheUpdate> {
+    let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+    sender
+}
+
+fn event_from_update(update: &CacheUpdate) -> Event {
+    Event::default()
+        .event(update.cache_key.to_db_key())
+        .json_data(&update.value)
+        .expect("expect cache value to be serializable")
+}
+
+pub async fn stream_cache_updates(
+    Extension(state): StateExtension,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    debug!("client subscribed to cache updates");
+
+    let receiver = state.cache_updates.subscribe();
+
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(update) => return Some((Ok(event_from_update(&update)), receiver)),
+                Err(RecvError::Lagged(skipped_count)) => {
+                    warn!(
+                        skipped_count,
+                        "cache update subscriber lagging, skipped updates"
+                    );
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
@@ -5,36 +5,34 @@ use axum::{
 };
 use chrono::Duration;
 use enum_iterator::all;
-use futures::{Stream, TryStreamExt};
+use futures::{Stream, StreamExt};
 use lazy_static::lazy_static;
 use reqwest::{header, StatusCode};
 use serde_json::Value;
-use sqlx::{postgres::PgNotification, PgPool};
 use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
 };
 use tokio::task::JoinHandle;
-use tracing::{debug, trace, warn};
+use tracing::{debug, warn};
 
-use crate::{
-    caching::{self, CacheKey, ParseCacheKeyError},
-    db,
-    key_value_store::{KeyValueStore, KeyValueStorePostgres},
-};
+use crate::caching::{CacheKey, CacheStore};
 
-use super::{State, StateExtension};
+use super::{cache_updates::CacheUpdate, State, StateExtension};
 
+/// In-memory copy of every cached analysis value. Requests are answered from here, without a DB
+/// round-trip. Warmed on start, and kept fresh by the cache update notifications, see
+/// `update_cache_from_notifications`.
 #[derive(Debug)]
 pub struct Cache(RwLock<HashMap<CacheKey, Value>>);
 
 impl Cache {
-    pub async fn new(key_value_store: &impl KeyValueStore) -> Self {
+    pub async fn new(cache_store: &dyn CacheStore) -> Self {
         let map = RwLock::new(HashMap::new());
 
-        // Tries to fetch a value from the key value store for every cached analysis value.
+        // Tries to fetch a value from the cache store for every cached analysis value.
         for key in all::<CacheKey>().collect::<Vec<_>>() {
-            let value = caching::get_serialized_caching_value(key_value_store, &key).await;
+            let value = cache_store.get(&key).await;
             if let Some(value) = value {
                 map.write().unwrap().insert(key, value);
             }
@@ -81,53 +79,40 @@ pub async fn cached_get(state: StateExtension, analysis_cache_key: &CacheKey) ->
 }
 
 async fn process_notifications(
-    mut notification_stream: impl Stream<Item = Result<PgNotification, sqlx::Error>> + Unpin,
+    mut updates: impl Stream<Item = CacheKey> + Unpin,
     state: Arc<State>,
-    key_value_store: impl KeyValueStore,
+    cache_store: Arc<dyn CacheStore>,
 ) {
-    while let Some(notification) = notification_stream.try_next().await.unwrap() {
-        let payload = notification.payload();
-
-        match payload.parse::<CacheKey>() {
-            Err(ParseCacheKeyError::UnknownCacheKey(cache_key)) => {
-                trace!(
-                    %cache_key,
-                    "unspported cache update, skipping"
-                );
-            }
-            Ok(cache_key) => {
-                debug!(%cache_key, "cache update");
-                let value =
-                    caching::get_serialized_caching_value(&key_value_store, &cache_key).await;
-                if let Some(value) = value {
-                    state.cache.0.write().unwrap().insert(cache_key, value);
-                } else {
-                    warn!(
-                        %cache_key,
-                        "got a message to update our served cache, but DB had no value to give"
-                    );
-                }
-                state.health.set_cache_updated();
-            }
+    while let Some(cache_key) = updates.next().await {
+        debug!(%cache_key, "cache update");
+        let value = cache_store.get(&cache_key).await;
+        if let Some(value) = value {
+            state
+                .cache
+                .0
+                .write()
+                .unwrap()
+                .insert(cache_key, value.clone());
+            // Sending only fails when nobody is subscribed.
+            let _ = state.cache_updates.send(CacheUpdate { cache_key, value });
+        } else {
+            warn!(
+                %cache_key,
+                "got a message to update our served cache, but the cache store had no value to give"
+            );
         }
+        state.health.set_cache_updated();
     }
 }
 
 pub async fn update_cache_from_notifications(
     state: Arc<State>,
-    db_pool: &PgPool,
+    cache_store: Arc<dyn CacheStore>,
 ) -> JoinHandle<()> {
-    let mut listener =
-        sqlx::postgres::PgListener::connect(&db::get_db_url_with_name("serve-rs-cache-update"))
-            .await
-            .unwrap();
-    listener.listen("cache-update").await.unwrap();
+    let updates = cache_store.subscribe().await;
     debug!("listening for cache updates");
 
-    let notification_stream = listener.into_stream();
-    let key_value_store = KeyValueStorePostgres::new(db_pool.clone());
-
     tokio::spawn(async move {
-        process_notifications(notification_stream, state, key_value_store).await;
+        process_notifications(updates, state, cache_store).await;
     })
 }
@@ -16,6 +16,17 @@ pub async fn middleware_fn<B: std::fmt::Debug>(
     let if_none_match_header = req.headers().get(header::IF_NONE_MATCH).cloned();
     let path = req.uri().path().to_owned();
     let res = next.run(req).await;
+
+    // Event streams never end, there is no body to hash.
+    let is_event_stream = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type == "text/event-stream");
+    if is_event_stream {
+        trace!(path, "event stream response, skipping etag");
+        return Ok(res);
+    }
+
     let (mut parts, mut body) = res.into_parts();
 
     let bytes = {
@@ -0,0 +1,243 @@
+//! # GraphQL
+//! A read-only GraphQL schema over our analysis tables. Lets analysts compose queries over blocks,
+//! burn, supply, issuance and prices for a time range, without writing SQL against tables whose
+//! layout we may change. Only compiled with the `graphql` feature.
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::routing::{post, MethodRouter};
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgRow, PgPool, Row};
+
+use crate::{
+    burn_sums::{self, BurnSumRange},
+    downsampling,
+    units::WeiNewtype,
+};
+
+// Keeps a single query from pulling whole tables.
+const MAX_LIMIT: i64 = 10_000;
+
+pub type AnalysisSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+#[derive(SimpleObject)]
+struct Block {
+    base_fee_per_gas: i64,
+    eth_price: f64,
+    gas_used: i32,
+    hash: String,
+    number: i64,
+    timestamp: DateTime<Utc>,
+}
+
+#[derive(SimpleObject)]
+struct BurnSum {
+    eth: f64,
+    usd: f64,
+}
+
+/// Hourly where raw rows were pruned, see `downsampling`.
+#[derive(SimpleObject)]
+struct SupplyPoint {
+    /// Unknown for hourly points.
+    block_number: Option<i64>,
+    /// Wei, as a string as it doesn't fit any GraphQL number type.
+    supply: String,
+    timestamp: DateTime<Utc>,
+}
+
+#[derive(SimpleObject)]
+struct IssuancePoint {
+    gwei: i64,
+    timestamp: DateTime<Utc>,
+}
+
+/// Hourly where raw rows were pruned, see `downsampling`.
+#[derive(SimpleObject)]
+struct EthPricePoint {
+    timestamp: DateTime<Utc>,
+    usd: f64,
+    /// The price source, e.g. `bybit`. Unknown for older prices.
+    provider: Option<String>,
+    /// `recorded`, `healed` or `resynced`. Unknown for older prices.
+    origin: Option<String>,
+}
+
+fn limit_or_max(limit: Option<i64>) -> i64 {
+    limit.map_or(MAX_LIMIT, |limit| limit.clamp(0, MAX_LIMIT))
+}
+
+pub struct QueryRoot;
+
+/// Time ranges include the start, and exclude the end.
+#[Object]
+impl QueryRoot {
+    async fn blocks(
+        &self,
+        context: &Context<'_>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: Option<i64>,
+    ) -> async_graphql::Result<Vec<Block>> {
+        let db_pool = context.data::<PgPool>()?;
+        let blocks = sqlx::query_as!(
+            Block,
+            "
+            SELECT base_fee_per_gas, eth_price, gas_used, hash, number, timestamp
+            FROM blocks_next
+            WHERE timestamp >= $1
+            AND timestamp < $2
+            ORDER BY number ASC
+            LIMIT $3
+            ",
+            start,
+            end,
+            limit_or_max(limit)
+        )
+        .fetch_all(db_pool)
+        .await?;
+        Ok(blocks)
+    }
+
+    async fn burn_sum(
+        &self,
+        context: &Context<'_>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> async_graphql::Result<BurnSum> {
+        let db_pool = context.data::<PgPool>()?;
+        let burn_sum =
+            burn_sums::burn_sum_from_range(db_pool, &BurnSumRange::Time { start, end }).await;
+        Ok(BurnSum {
+            eth: burn_sum.eth.0,
+            usd: burn_sum.usd.0,
+        })
+    }
+
+    async fn supply(
+        &self,
+        context: &Context<'_>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: Option<i64>,
+    ) -> async_graphql::Result<Vec<SupplyPoint>> {
+        let db_pool = context.data::<PgPool>()?;
+        let points = sqlx::query(&format!(
+            "
+            SELECT eth_supply.block_number, supply.value AS supply, supply.timestamp
+            FROM ({supply}) supply
+            LEFT JOIN eth_supply
+                ON eth_supply.timestamp = supply.timestamp
+                AND NOT supply.is_hourly
+            WHERE supply.timestamp >= $1
+            AND supply.timestamp < $2
+            ORDER BY supply.timestamp ASC
+            LIMIT $3
+            ",
+            supply = downsampling::hourly_or_raw_sql(&downsampling::ETH_SUPPLY),
+        ))
+        .bind(start)
+        .bind(end)
+        .bind(limit_or_max(limit))
+        .map(|row: PgRow| SupplyPoint {
+            block_number: row.get("block_number"),
+            supply: row.get::<WeiNewtype, _>("supply").into(),
+            timestamp: row.get("timestamp"),
+        })
+        .fetch_all(db_pool)
+        .await?;
+        Ok(points)
+    }
+
+    async fn issuance(
+        &self,
+        context: &Context<'_>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: Option<i64>,
+    ) -> async_graphql::Result<Vec<IssuancePoint>> {
+        let db_pool = context.data::<PgPool>()?;
+        let points = sqlx::query_as!(
+            IssuancePoint,
+            "
+            SELECT gwei, timestamp
+            FROM beacon_issuance
+            WHERE timestamp >= $1
+            AND timestamp < $2
+            ORDER BY timestamp ASC
+            LIMIT $3
+            ",
+            start,
+            end,
+            limit_or_max(limit)
+        )
+        .fetch_all(db_pool)
+        .await?;
+        Ok(points)
+    }
+
+    async fn eth_prices(
+        &self,
+        context: &Context<'_>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: Option<i64>,
+    ) -> async_graphql::Result<Vec<EthPricePoint>> {
+        let db_pool = context.data::<PgPool>()?;
+        let points = sqlx::query(&format!(
+            "
+            SELECT
+                prices.value::FLOAT8 AS ethusd,
+                prices.timestamp,
+                eth_prices.provider,
+                eth_prices.origin
+            FROM ({prices}) prices
+            LEFT JOIN eth_prices
+                ON eth_prices.timestamp = prices.timestamp
+                AND NOT prices.is_hourly
+            WHERE prices.timestamp >= $1
+            AND prices.timestamp < $2
+            ORDER BY prices.timestamp ASC
+            LIMIT $3
+            ",
+            prices = downsampling::hourly_or_raw_sql(&downsampling::ETH_PRICES),
+        ))
+        .bind(start)
+        .bind(end)
+        .bind(limit_or_max(limit))
+        .map(|row: PgRow| EthPricePoint {
+            timestamp: row.get("timestamp"),
+            usd: row.get("ethusd"),
+            provider: row.get("provider"),
+            origin: row.get("origin"),
+        })
+        .fetch_all(db_pool)
+        .await?;
+        Ok(points)
+    }
+}
+
+pub fn schema(db_pool: PgPool) -> AnalysisSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(db_pool)
+        .finish()
+}
+
+pub fn route(db_pool: PgPool) -> MethodRouter {
+    let schema = schema(db_pool);
+    post(move |request: GraphQLRequest| {
+        let schema = schema.clone();
+        async move { GraphQLResponse::from(schema.execute(request.into_inner()).await) }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_or_max_test() {
+        assert_eq!(limit_or_max(None), MAX_LIMIT);
+        assert_eq!(limit_or_max(Some(10)), 10);
+        assert_eq!(limit_or_max(Some(MAX_LIMIT + 1)), MAX_LIMIT);
+    }
+}
@@ -1,5 +1,8 @@
+mod cache_updates;
 mod caching;
 mod etag_middleware;
+#[cfg(feature = "graphql")]
+mod graphql;
 mod health;
 
 use axum::response::IntoResponse;
@@ -12,16 +15,23 @@ use futures::{try_join, TryFutureExt};
 use lazy_static::lazy_static;
 use sqlx::PgPool;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 use tower::ServiceBuilder;
 use tower_http::compression::CompressionLayer;
 use tracing::{debug, error, info};
 
 use crate::health::HealthCheckable;
-use crate::key_value_store::KeyValueStorePostgres;
 use crate::serve::health::ServeHealth;
-use crate::{caching::CacheKey, db, env, execution_chain, log};
+use crate::{
+    beacon_chain::{self, rewards},
+    burn_sums,
+    caching::{cache_store_from_config, CacheKey},
+    config, db, difficulty, eth_supply, execution_chain, log,
+    mev_blocks::payload_values,
+    usd_price,
+};
 
-use self::caching::Cache;
+use self::{cache_updates::CacheUpdate, caching::Cache};
 
 lazy_static! {
     static ref FOUR_SECONDS: Duration = Duration::seconds(4);
@@ -33,8 +43,11 @@ pub type StateExtension = Extension<Arc<State>>;
 
 pub struct State {
     pub cache: Cache,
+    pub cache_updates: broadcast::Sender<CacheUpdate>,
     pub db_pool: PgPool,
     pub health: ServeHealth,
+    /// The replica, when configured, for handlers which query rather than read the cache.
+    pub read_db_pool: PgPool,
 }
 
 pub async fn start_server() {
@@ -46,13 +59,13 @@ pub async fn start_server() {
         .await
         .unwrap();
 
-    let key_value_store = KeyValueStorePostgres::new(db_pool.clone());
-
     sqlx::migrate!().run(&db_pool).await.unwrap();
 
+    let cache_store = cache_store_from_config(&db_pool).await;
+
     debug!("warming cache");
 
-    let cache = Cache::new(&key_value_store).await;
+    let cache = Cache::new(cache_store.as_ref()).await;
 
     info!("cache ready");
 
@@ -60,12 +73,14 @@ pub async fn start_server() {
 
     let shared_state = Arc::new(State {
         cache,
+        cache_updates: cache_updates::channel(),
         db_pool,
         health,
+        read_db_pool: db::get_read_db_pool("eth-analysis-serve-read").await,
     });
 
     let update_cache_thread =
-        caching::update_cache_from_notifications(shared_state.clone(), &shared_state.db_pool).await;
+        caching::update_cache_from_notifications(shared_state.clone(), cache_store).await;
 
     let app = Router::new()
         .route(
@@ -95,48 +110,176 @@ pub async fn start_server() {
             "/api/v2/fees/base-fee-per-gas-stats",
             get(execution_chain::routes::base_fee_per_gas_stats),
         )
+        .route(
+            "/api/v2/fees/blob-transactions",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::BlobTransactions).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/blob-usage",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::BlobUsage).await
+            }),
+        )
         .route(
             "/api/v2/fees/block-lag",
             get(
                 |state: StateExtension| async move { cached_get(state, &CacheKey::BlockLag).await },
             ),
         )
+        .route(
+            "/api/v2/fees/btc-price-stats",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::BtcPrice).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/builder-censorship",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::BuilderCensorship).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/burn-records",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::BurnRecords).await
+            }),
+        )
         .route(
             "/api/v2/fees/burn-sums",
             get(
                 |state: StateExtension| async move { cached_get(state, &CacheKey::BurnSums).await },
             ),
         )
+        .route(
+            "/api/v2/fees/burn-sums-at-block",
+            get(burn_sums::burn_sums_at_block_handler),
+        )
+        .route(
+            "/api/v2/fees/burn-anomalies",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::BurnAnomalies).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/burn-moving-averages",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::BurnMovingAverages).await
+            }),
+        )
         .route(
             "/api/v2/fees/burn-rates",
             get(|state: StateExtension| async move {
                 cached_get(state, &CacheKey::BurnRates).await
             }),
         )
+        .route(
+            "/api/v2/fees/burn-rates-over-time",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::BurnRatesOverTime).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/fee-suggestion",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::FeeSuggestion).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/gas-utilization",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::GasUtilization).await
+            }),
+        )
         .route(
             "/api/v2/fees/gauge-rates",
             get(|state: StateExtension| async move {
                 cached_get(state, &CacheKey::GaugeRates).await
             }),
         )
+        .route(
+            "/api/v2/fees/graffiti-counts",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::GraffitiCounts).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/health-score",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::HealthScore).await
+            }),
+        )
         .route(
             "/api/v2/fees/healthz",
             get(|state: StateExtension| async move {
                 state.health.health_status().into_response()
             }),
         )
+        .route(
+            "/api/v2/fees/cache-updates",
+            get(cache_updates::stream_cache_updates),
+        )
+        .route(
+            "/api/v2/fees/chain-health",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::ChainHealth).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/combined-fees",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::CombinedFees).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/consolidations-by-day",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::ConsolidationsByDay).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/contract-deployments",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::ContractDeployments).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/daily-supply-deltas",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::DailySupplyDeltas).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/daily-supply-deltas-range",
+            get(eth_supply::daily_supply_deltas),
+        )
+        .route("/api/v2/fees/difficulty", get(difficulty::difficulty))
         .route(
             "/api/v2/fees/effective-balance-sum",
             get(|state: StateExtension| async move {
                 cached_get(state, &CacheKey::EffectiveBalanceSum).await
             }),
         )
+        .route("/api/v2/fees/eth-candles", get(usd_price::eth_candles))
         .route(
             "/api/v2/fees/eth-price-stats",
             get(|state: StateExtension| async move {
                 cached_get(state, &CacheKey::EthPrice).await
             }),
         )
+        .route(
+            "/api/v2/fees/eth-price-sources",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::EthPriceSources).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/eth-price-time-frame-stats",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::EthPriceStats).await
+            }),
+        )
         // Deprecated, remove after frontend switches over.
         .route(
             "/api/v2/fees/eth-supply-parts",
@@ -144,12 +287,72 @@ pub async fn start_server() {
                 cached_get(state, &CacheKey::SupplyParts).await
             }),
         )
+        .route(
+            "/api/v2/fees/issuance-breakdown",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::IssuanceBreakdown).await
+            }),
+        )
         .route(
             "/api/v2/fees/issuance-estimate",
             get(|state: StateExtension| async move {
                 cached_get(state, &CacheKey::IssuanceEstimate).await
             }),
         )
+        .route(
+            "/api/v2/fees/issuance-moving-averages",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::IssuanceMovingAverages).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/issuance-scenarios",
+            get(beacon_chain::issuance_scenarios),
+        )
+        .route(
+            "/api/v2/fees/l2-fees",
+            get(|state: StateExtension| async move { cached_get(state, &CacheKey::L2Fees).await }),
+        )
+        .route(
+            "/api/v2/fees/market-caps",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::MarketCaps).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/market-caps-over-time",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::MarketCapsOverTime).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/next-base-fee",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::NextBaseFee).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/payload-values",
+            get(payload_values::payload_values),
+        )
+        .route(
+            "/api/v2/fees/pending-deposits",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::PendingDeposits).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/pow-supply-since-merge",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::PowSupplySinceMerge).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/proposer-leaderboard",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::ProposerLeaderboard).await
+            }),
+        )
         .route(
             "/api/v2/fees/supply-changes",
             get(|state: StateExtension| async move {
@@ -162,6 +365,12 @@ pub async fn start_server() {
                 cached_get(state, &CacheKey::SupplyDashboardAnalysis).await
             }),
         )
+        .route(
+            "/api/v2/fees/supply-milestones",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::SupplyMilestones).await
+            }),
+        )
         .route(
             "/api/v2/fees/supply-over-time",
             get(|state: StateExtension| async move {
@@ -192,26 +401,49 @@ pub async fn start_server() {
                 cached_get(state, &CacheKey::TotalDifficultyProgress).await
             }),
         )
+        .route(
+            "/api/v2/fees/transaction-stats",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::TransactionStats).await
+            }),
+        )
+        .route(
+            "/api/v2/fees/transaction-stats-by-day",
+            get(|state: StateExtension| async move {
+                cached_get(state, &CacheKey::TransactionStatsByDay).await
+            }),
+        )
         .route(
             "/api/v2/fees/validator-rewards",
             get(|state: StateExtension| async move {
                 cached_get(state, &CacheKey::ValidatorRewards).await
             }),
         )
+        .route(
+            "/api/v2/fees/validator-rewards-by-validator",
+            get(rewards::validator_rewards),
+        )
         .route(
             "/healthz",
             get(|state: StateExtension| async move {
                 state.health.health_status().into_response()
             }),
-        )
-        .layer(
-            ServiceBuilder::new()
-                .layer(middleware::from_fn(etag_middleware::middleware_fn))
-                .layer(CompressionLayer::new())
-                .layer(Extension(shared_state)),
         );
 
-    let port = env::get_env_var("PORT").unwrap_or_else(|| "3002".to_string());
+    #[cfg(feature = "graphql")]
+    let app = app.route(
+        "/api/v2/graphql",
+        graphql::route(shared_state.read_db_pool.clone()),
+    );
+
+    let app = app.layer(
+        ServiceBuilder::new()
+            .layer(middleware::from_fn(etag_middleware::middleware_fn))
+            .layer(CompressionLayer::new())
+            .layer(Extension(shared_state)),
+    );
+
+    let port = config::CONFIG.port().unwrap_or("3002");
 
     info!(port, "server listening");
     let socket_addr = format!("0.0.0.0:{port}").parse().unwrap();
@@ -0,0 +1,71 @@
+//! # Shutdown
+//! Deploys stop services with SIGTERM. Long running loops listen for it, stop taking new work,
+//! finish what is in flight, and return, instead of being killed halfway through a block or slot.
+use futures::{Stream, StreamExt};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+};
+use tracing::info;
+
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    receiver: watch::Receiver<bool>,
+}
+
+async fn wait_for_signal() {
+    let mut sigterm = signal(SignalKind::terminate()).unwrap();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => info!("received SIGINT, shutting down"),
+        _ = sigterm.recv() => info!("received SIGTERM, shutting down"),
+    }
+}
+
+impl ShutdownSignal {
+    /// Starts listening for SIGINT and SIGTERM.
+    pub fn listen() -> Self {
+        let (sender, receiver) = watch::channel(false);
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            sender.send_replace(true);
+        });
+        Self { receiver }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once shutdown has been requested.
+    pub async fn requested(mut self) {
+        // An error means the sender is gone, it never is before sending.
+        let _ = self.receiver.wait_for(|is_requested| *is_requested).await;
+    }
+
+    /// Ends the stream once shutdown has been requested. Items already taken are unaffected, the
+    /// caller finishes processing them.
+    pub fn stop_stream<S: Stream + Unpin>(&self, stream: S) -> impl Stream<Item = S::Item> + Unpin {
+        stream.take_until(Box::pin(self.clone().requested()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn stop_stream_test() {
+        let (sender, receiver) = watch::channel(false);
+        let shutdown_signal = ShutdownSignal { receiver };
+
+        let mut numbers = shutdown_signal.stop_stream(stream::iter(1..).boxed());
+        assert_eq!(numbers.next().await, Some(1));
+        assert!(!shutdown_signal.is_requested());
+
+        sender.send_replace(true);
+        assert!(shutdown_signal.is_requested());
+        assert_eq!(numbers.next().await, None);
+    }
+}
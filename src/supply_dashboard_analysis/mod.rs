@@ -12,8 +12,10 @@ use crate::{
     beacon_chain::Slot,
     caching::{self, CacheKey},
     eth_supply::{
-        self, SupplyChanges, SupplyOverTime, SupplyParts, SupplyPartsError, SupplyPartsStore,
+        self, DailySupplyDeltas, PowSupplySinceMerge, SupplyChanges, SupplyOverTime, SupplyParts,
+        SupplyPartsError, SupplyPartsStore, SupplySinceMerge,
     },
+    execution_chain,
     performance::TimedExt,
 };
 
@@ -26,7 +28,8 @@ struct SupplyDashboardAnalysis {
     timestamp: DateTime<Utc>,
 }
 
-pub async fn update_cache(db_pool: &PgPool) -> Result<()> {
+/// The last stored supply slot and its supply parts, `None` when either isn't available.
+async fn get_limit(db_pool: &PgPool) -> Result<Option<(Slot, SupplyParts)>> {
     // Our limit is whatever the youngest of the table we depend on has stored, currently that is
     // the last stored supply slot.
     let limit_slot = {
@@ -35,7 +38,7 @@ pub async fn update_cache(db_pool: &PgPool) -> Result<()> {
             Some(limit_slot) => limit_slot,
             None => {
                 warn!("no last stored supply slot available, skipping supply dashboard update");
-                return Ok(());
+                return Ok(None);
             }
         }
     };
@@ -48,17 +51,54 @@ pub async fn update_cache(db_pool: &PgPool) -> Result<()> {
             Ok(supply_parts) => supply_parts,
             Err(SupplyPartsError::NoValidatorBalancesAvailable(_)) => {
                 warn!(%limit_slot, "no supply parts available for slot, skipping supply dashboard update");
-                return Ok(());
+                return Ok(None);
             }
         }
     };
 
+    Ok(Some((limit_slot, supply_parts)))
+}
+
+/// Runs right after a slot is stored, reads from the primary, a replica may not have the slot yet.
+pub async fn update_cache(db_pool: &PgPool) -> Result<()> {
+    let Some((limit_slot, supply_parts)) = get_limit(db_pool).await? else {
+        return Ok(());
+    };
+
     let supply_over_time =
         eth_supply::get_supply_over_time(db_pool, &limit_slot, supply_parts.block_number())
             .timed("get-supply-over-time")
             .await?;
     let supply_changes: SupplyChanges = (&supply_over_time).into();
 
+    let supply_since_merge = SupplySinceMerge {
+        block_number: supply_parts.block_number(),
+        slot: limit_slot,
+        supply_by_minute: eth_supply::get_supply_since_merge_by_minute(db_pool)
+            .timed("get-supply-since-merge-by-minute")
+            .await,
+        timestamp: limit_slot.date_time(),
+    };
+
+    let daily_supply_deltas = DailySupplyDeltas {
+        block_number: supply_parts.block_number(),
+        deltas: eth_supply::get_daily_supply_deltas(db_pool, None, None).await,
+        slot: limit_slot,
+        timestamp: limit_slot.date_time(),
+    };
+
+    let pow_supply_since_merge = PowSupplySinceMerge {
+        block_number: supply_parts.block_number(),
+        slot: limit_slot,
+        pow_supply_by_minute: eth_supply::get_pow_supply_since_merge_by_minute(
+            db_pool,
+            execution_chain::get_daily_pow_issuance(db_pool).await,
+        )
+        .timed("get-pow-supply-since-merge-by-minute")
+        .await,
+        timestamp: limit_slot.date_time(),
+    };
+
     // let supply_dashboard_analysis = SupplyDashboardAnalysis {
     //     supply_parts: supply_parts.clone(),
     //     fees_burned: None,
@@ -82,7 +122,14 @@ pub async fn update_cache(db_pool: &PgPool) -> Result<()> {
     join!(
         caching::update_and_publish(db_pool, &CacheKey::SupplyParts, supply_parts),
         caching::update_and_publish(db_pool, &CacheKey::SupplyOverTime, supply_over_time),
-        caching::update_and_publish(db_pool, &CacheKey::SupplyChanges, &supply_changes)
+        caching::update_and_publish(db_pool, &CacheKey::SupplyChanges, &supply_changes),
+        caching::update_and_publish(db_pool, &CacheKey::SupplySinceMerge, &supply_since_merge),
+        caching::update_and_publish(db_pool, &CacheKey::DailySupplyDeltas, &daily_supply_deltas),
+        caching::update_and_publish(
+            db_pool,
+            &CacheKey::PowSupplySinceMerge,
+            &pow_supply_since_merge
+        )
     );
 
     Ok(())
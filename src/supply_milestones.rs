@@ -0,0 +1,356 @@
+//! # Supply Milestones
+//! Records when the supply crosses a round number, by default every 100k ETH, reaches a new
+//! all-time high, or drops to a new post-merge low. Set `supply_milestone_step_eth` in the config
+//! file to use a different step.
+//!
+//! Supply is only stored since the merge, so the all-time high and post-merge low are both taken
+//! from it. Where raw supply was pruned, see `downsampling`, the last supply of each hour stands
+//! in. A high or low is recorded when a run of new highs or lows starts, not for every slot
+//! in it. A round number is only recorded the first time it's crossed in each direction, so
+//! supply going back and forth across it doesn't flood anyone.
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{postgres::PgRow, PgConnection, PgExecutor, PgPool, Row};
+use tracing::info;
+
+use crate::{
+    analysis::Analysis,
+    beacon_chain::Slot,
+    caching::{self, CacheKey},
+    config, downsampling,
+    execution_chain::BlockNumber,
+    rollback::RollbackPoint,
+    units::{EthNewtype, WeiNewtype},
+    webhooks,
+};
+
+const DEFAULT_STEP_ETH: u64 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SupplyMilestoneKind {
+    AllTimeHigh,
+    CrossedAbove,
+    CrossedBelow,
+    PostMergeLow,
+}
+
+impl SupplyMilestoneKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::AllTimeHigh => "all_time_high",
+            Self::CrossedAbove => "crossed_above",
+            Self::CrossedBelow => "crossed_below",
+            Self::PostMergeLow => "post_merge_low",
+        }
+    }
+
+    fn from_name(s: &str) -> Self {
+        match s {
+            "all_time_high" => Self::AllTimeHigh,
+            "crossed_above" => Self::CrossedAbove,
+            "crossed_below" => Self::CrossedBelow,
+            "post_merge_low" => Self::PostMergeLow,
+            unknown => panic!("unknown supply milestone kind {unknown}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SupplyMilestone {
+    pub block_hash: String,
+    pub block_number: BlockNumber,
+    pub kind: SupplyMilestoneKind,
+    pub slot: Slot,
+    pub supply: WeiNewtype,
+    /// The round number crossed, only for crossings.
+    pub threshold: Option<WeiNewtype>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// The supply at a slot and what came before it.
+#[derive(Debug)]
+struct SupplyContext {
+    block_hash: String,
+    block_number: BlockNumber,
+    max_before: Option<WeiNewtype>,
+    min_before: Option<WeiNewtype>,
+    previous: Option<WeiNewtype>,
+    supply: WeiNewtype,
+}
+
+/// The milestones reached going from `previous` to `current`, given the highest and lowest supply
+/// before `current`.
+fn find_milestones(
+    previous: WeiNewtype,
+    current: WeiNewtype,
+    max_before: WeiNewtype,
+    min_before: WeiNewtype,
+    step: WeiNewtype,
+) -> Vec<(SupplyMilestoneKind, Option<WeiNewtype>)> {
+    let mut milestones = Vec::new();
+
+    let previous_step = previous.0.div_euclid(step.0);
+    let current_step = current.0.div_euclid(step.0);
+    if current_step > previous_step {
+        for i in previous_step + 1..=current_step {
+            milestones.push((
+                SupplyMilestoneKind::CrossedAbove,
+                Some(WeiNewtype(i * step.0)),
+            ));
+        }
+    } else if current_step < previous_step {
+        for i in (current_step + 1..=previous_step).rev() {
+            milestones.push((
+                SupplyMilestoneKind::CrossedBelow,
+                Some(WeiNewtype(i * step.0)),
+            ));
+        }
+    }
+
+    // The previous slot is included in the max and min before, when it was the high or low itself
+    // we're continuing a run.
+    if current.0 > max_before.0 && previous.0 < max_before.0 {
+        milestones.push((SupplyMilestoneKind::AllTimeHigh, None));
+    }
+    if current.0 < min_before.0 && previous.0 > min_before.0 {
+        milestones.push((SupplyMilestoneKind::PostMergeLow, None));
+    }
+
+    milestones
+}
+
+async fn get_supply_context(executor: impl PgExecutor<'_>, slot: &Slot) -> Option<SupplyContext> {
+    sqlx::query(&format!(
+        "
+        SELECT
+            eth_supply.supply,
+            eth_supply.block_number,
+            blocks_next.hash AS block_hash,
+            (
+                SELECT supply FROM eth_supply
+                WHERE timestamp < $1
+                ORDER BY timestamp DESC
+                LIMIT 1
+            ) AS previous,
+            (SELECT MAX(value) FROM ({supply}) supply WHERE timestamp < $1) AS max_before,
+            (SELECT MIN(value) FROM ({supply}) supply WHERE timestamp < $1) AS min_before
+        FROM eth_supply
+        JOIN blocks_next ON blocks_next.number = eth_supply.block_number
+        WHERE eth_supply.timestamp = $1
+        ",
+        supply = downsampling::hourly_or_raw_sql(&downsampling::ETH_SUPPLY),
+    ))
+    .bind(slot.date_time())
+    .map(|row: PgRow| SupplyContext {
+        block_hash: row.get("block_hash"),
+        block_number: row.get("block_number"),
+        max_before: row.get("max_before"),
+        min_before: row.get("min_before"),
+        previous: row.get("previous"),
+        supply: row.get("supply"),
+    })
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+}
+
+/// Returns false when the milestone was already recorded.
+async fn store_milestone(executor: impl PgExecutor<'_>, milestone: &SupplyMilestone) -> bool {
+    sqlx::query!(
+        "
+        INSERT INTO supply_milestones (
+            kind,
+            slot,
+            block_number,
+            block_hash,
+            timestamp,
+            supply_wei,
+            threshold_wei
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT DO NOTHING
+        ",
+        milestone.kind.as_str(),
+        milestone.slot.0,
+        milestone.block_number.0,
+        milestone.block_hash,
+        milestone.timestamp,
+        milestone.supply as WeiNewtype,
+        milestone.threshold as Option<WeiNewtype>
+    )
+    .execute(executor)
+    .await
+    .unwrap()
+    .rows_affected()
+        == 1
+}
+
+pub async fn get_milestones(executor: impl PgExecutor<'_>) -> Vec<SupplyMilestone> {
+    sqlx::query!(
+        r#"
+        SELECT
+            kind,
+            slot,
+            block_number,
+            block_hash,
+            timestamp,
+            supply_wei AS "supply_wei: WeiNewtype",
+            threshold_wei AS "threshold_wei: WeiNewtype"
+        FROM supply_milestones
+        ORDER BY slot DESC, kind
+        "#
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| SupplyMilestone {
+        block_hash: row.block_hash,
+        block_number: BlockNumber(row.block_number),
+        kind: SupplyMilestoneKind::from_name(&row.kind),
+        slot: Slot(row.slot),
+        supply: row.supply_wei,
+        threshold: row.threshold_wei,
+        timestamp: row.timestamp,
+    })
+    .collect()
+}
+
+fn step() -> WeiNewtype {
+    let step_eth = config::CONFIG
+        .supply_milestone_step_eth()
+        .unwrap_or(DEFAULT_STEP_ETH);
+    WeiNewtype(step_eth as i128 * EthNewtype::WEI_PER_ETH)
+}
+
+/// Checks the supply stored for each slot, returns the milestones newly recorded.
+pub async fn check_slots(connection: &mut PgConnection, slots: &[Slot]) -> Vec<SupplyMilestone> {
+    let step = step();
+    let mut new_milestones = Vec::new();
+
+    for slot in slots {
+        let context = match get_supply_context(&mut *connection, slot).await {
+            Some(context) => context,
+            None => continue,
+        };
+        // The first supply stored has nothing to compare to.
+        let (previous, max_before, min_before) =
+            match (context.previous, context.max_before, context.min_before) {
+                (Some(previous), Some(max_before), Some(min_before)) => {
+                    (previous, max_before, min_before)
+                }
+                _ => continue,
+            };
+
+        for (kind, threshold) in
+            find_milestones(previous, context.supply, max_before, min_before, step)
+        {
+            let milestone = SupplyMilestone {
+                block_hash: context.block_hash.clone(),
+                block_number: context.block_number,
+                kind,
+                slot: *slot,
+                supply: context.supply,
+                threshold,
+                timestamp: slot.date_time(),
+            };
+            if store_milestone(&mut *connection, &milestone).await {
+                info!(kind = kind.as_str(), %slot, supply = %milestone.supply, "supply milestone reached");
+                new_milestones.push(milestone);
+            }
+        }
+    }
+
+    new_milestones
+}
+
+pub async fn publish_stored_milestones(db_pool: &PgPool) {
+    let milestones = get_milestones(db_pool).await;
+    caching::update_and_publish(db_pool, &CacheKey::SupplyMilestones, milestones).await;
+}
+
+/// Publishes all milestones and calls the supply milestone webhooks, once the new milestones are
+/// committed.
+pub async fn on_new_milestones(db_pool: &PgPool, new_milestones: &[SupplyMilestone]) {
+    publish_stored_milestones(db_pool).await;
+    webhooks::on_supply_milestones(new_milestones).await;
+}
+
+pub struct SupplyMilestonesRollback;
+
+#[async_trait]
+impl Analysis for SupplyMilestonesRollback {
+    fn name(&self) -> &'static str {
+        "supply_milestones"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        match point {
+            RollbackPoint::BlockNumbersGte(_) => (),
+            RollbackPoint::Slot(slot) => {
+                sqlx::query!("DELETE FROM supply_milestones WHERE slot = $1", slot.0)
+                    .execute(transaction)
+                    .await
+                    .unwrap();
+            }
+            RollbackPoint::SlotsGte(slot) => {
+                sqlx::query!("DELETE FROM supply_milestones WHERE slot >= $1", slot.0)
+                    .execute(transaction)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eth(eth: i128) -> WeiNewtype {
+        WeiNewtype::from_eth(eth)
+    }
+
+    #[test]
+    fn crossed_above_test() {
+        let milestones = find_milestones(eth(99), eth(201), eth(99), eth(90), eth(100));
+        assert_eq!(
+            milestones,
+            vec![
+                (SupplyMilestoneKind::CrossedAbove, Some(eth(100))),
+                (SupplyMilestoneKind::CrossedAbove, Some(eth(200))),
+            ]
+        );
+    }
+
+    #[test]
+    fn crossed_below_test() {
+        let milestones = find_milestones(eth(201), eth(199), eth(300), eth(190), eth(100));
+        assert_eq!(
+            milestones,
+            vec![(SupplyMilestoneKind::CrossedBelow, Some(eth(200)))]
+        );
+    }
+
+    #[test]
+    fn all_time_high_starts_run_test() {
+        // Coming back up to a new high.
+        let milestones = find_milestones(eth(140), eth(160), eth(150), eth(100), eth(1_000));
+        assert_eq!(milestones, vec![(SupplyMilestoneKind::AllTimeHigh, None)]);
+
+        // Continuing a run of highs.
+        let milestones = find_milestones(eth(160), eth(170), eth(160), eth(100), eth(1_000));
+        assert!(milestones.is_empty());
+    }
+
+    #[test]
+    fn post_merge_low_starts_run_test() {
+        let milestones = find_milestones(eth(120), eth(90), eth(150), eth(100), eth(1_000));
+        assert_eq!(milestones, vec![(SupplyMilestoneKind::PostMergeLow, None)]);
+
+        let milestones = find_milestones(eth(90), eth(80), eth(150), eth(90), eth(1_000));
+        assert!(milestones.is_empty());
+    }
+}
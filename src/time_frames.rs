@@ -6,8 +6,12 @@ use serde::{Serialize, Serializer};
 use sqlx::postgres::types::PgInterval;
 use thiserror::Error;
 
-use crate::execution_chain::{
-    self, BlockNumber, ExecutionNodeBlock, LONDON_HARD_FORK_BLOCK_NUMBER, MERGE_BLOCK_NUMBER,
+use crate::{
+    beacon_chain::{Epoch, SECONDS_PER_SLOT, SHAPELLA_SLOT},
+    execution_chain::{
+        self, BlockNumber, ExecutionNodeBlock, LONDON_HARD_FORK_BLOCK_NUMBER, MERGE_BLOCK_NUMBER,
+        SHAPELLA_BLOCK_NUMBER,
+    },
 };
 
 use GrowingTimeFrame::*;
@@ -24,23 +28,11 @@ pub enum LimitedTimeFrame {
 
 impl LimitedTimeFrame {
     pub fn epoch_count(self) -> f64 {
-        match self {
-            Day1 => 225.0,
-            Day30 => 6750.0,
-            Day7 => 1575.0,
-            Hour1 => 9.375,
-            Minute5 => 0.78125,
-        }
+        Epoch::count_in(&self.duration())
     }
 
     pub fn slot_count(self) -> u32 {
-        match self {
-            Day1 => 7200,
-            Day30 => 216000,
-            Day7 => 50400,
-            Hour1 => 300,
-            Minute5 => 25,
-        }
+        (self.duration().num_seconds() / i64::from(*SECONDS_PER_SLOT)) as u32
     }
 
     pub fn postgres_interval(&self) -> PgInterval {
@@ -131,10 +123,16 @@ impl Display for LimitedTimeFrame {
     }
 }
 
+/// Time frames which start at a hard fork and grow with every block.
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Sequence)]
 pub enum GrowingTimeFrame {
+    /// Since London, which introduced the burn. `since_london` parses to this frame, it is the same
+    /// frame under another name, not one with its own cache keys.
     SinceBurn,
     SinceMerge,
+    /// Since Shapella, which enabled withdrawals.
+    SinceShapella,
 }
 
 impl GrowingTimeFrame {
@@ -142,13 +140,15 @@ impl GrowingTimeFrame {
         match self {
             SinceBurn => *execution_chain::LONDON_HARD_FORK_TIMESTAMP,
             SinceMerge => *execution_chain::PARIS_HARD_FORK_TIMESTAMP,
+            SinceShapella => SHAPELLA_SLOT.date_time(),
         }
     }
 
     pub fn start_block_number(&self) -> BlockNumber {
         match self {
-            SinceBurn => LONDON_HARD_FORK_BLOCK_NUMBER,
-            SinceMerge => MERGE_BLOCK_NUMBER,
+            SinceBurn => *LONDON_HARD_FORK_BLOCK_NUMBER,
+            SinceMerge => *MERGE_BLOCK_NUMBER,
+            SinceShapella => *SHAPELLA_BLOCK_NUMBER,
         }
     }
 
@@ -169,6 +169,7 @@ impl Display for GrowingTimeFrame {
         match self {
             SinceBurn => write!(f, "since_burn"),
             SinceMerge => write!(f, "since_merge"),
+            SinceShapella => write!(f, "since_shapella"),
         }
     }
 }
@@ -197,11 +198,14 @@ impl TimeFrame {
     }
 
     pub fn start_timestamp(&self, block: &ExecutionNodeBlock) -> DateTime<Utc> {
+        self.start_timestamp_at(&block.timestamp)
+    }
+
+    /// Where the time frame starts when it ends at `timestamp`.
+    pub fn start_timestamp_at(&self, timestamp: &DateTime<Utc>) -> DateTime<Utc> {
         match self {
             TimeFrame::Growing(growing_time_frame) => growing_time_frame.start_timestamp(),
-            TimeFrame::Limited(limited_time_frame) => {
-                block.timestamp - limited_time_frame.duration()
-            }
+            TimeFrame::Limited(limited_time_frame) => *timestamp - limited_time_frame.duration(),
         }
     }
 }
@@ -222,7 +226,10 @@ impl FromStr for TimeFrame {
         match s {
             "all" => Ok(Growing(SinceBurn)),
             "since_burn" => Ok(Growing(SinceBurn)),
+            // An alias, London is where the burn starts.
+            "since_london" => Ok(Growing(SinceBurn)),
             "since_merge" => Ok(Growing(SinceMerge)),
+            "since_shapella" => Ok(Growing(SinceShapella)),
             unknown_time_frame => unknown_time_frame.parse().map(Limited),
         }
     }
@@ -259,6 +266,7 @@ mod tests {
         let expected = vec![
             TimeFrame::Growing(SinceBurn),
             TimeFrame::Growing(SinceMerge),
+            TimeFrame::Growing(SinceShapella),
             TimeFrame::Limited(Day1),
             TimeFrame::Limited(Day30),
             TimeFrame::Limited(Day7),
@@ -269,6 +277,24 @@ mod tests {
         assert_eq!(expected, time_frames);
     }
 
+    #[test]
+    fn epoch_count_test() {
+        assert_eq!(Day1.epoch_count(), 225.0);
+        assert_eq!(Day30.epoch_count(), 6750.0);
+        assert_eq!(Day7.epoch_count(), 1575.0);
+        assert_eq!(Hour1.epoch_count(), 9.375);
+        assert_eq!(Minute5.epoch_count(), 0.78125);
+    }
+
+    #[test]
+    fn slot_count_test() {
+        assert_eq!(Day1.slot_count(), 7200);
+        assert_eq!(Day30.slot_count(), 216000);
+        assert_eq!(Day7.slot_count(), 50400);
+        assert_eq!(Hour1.slot_count(), 300);
+        assert_eq!(Minute5.slot_count(), 25);
+    }
+
     #[test]
     fn parse_test() {
         let time_frame = "all".parse::<TimeFrame>().unwrap();
@@ -277,4 +303,23 @@ mod tests {
         let limited_time_frame = "d30".parse::<TimeFrame>().unwrap();
         assert_eq!(limited_time_frame, TimeFrame::Limited(Day30))
     }
+
+    #[test]
+    fn parse_since_hard_fork_test() {
+        let time_frame = "since_london".parse::<TimeFrame>().unwrap();
+        assert_eq!(time_frame, TimeFrame::Growing(SinceBurn));
+        assert_eq!(time_frame.to_string(), "since_burn");
+
+        let time_frame = "since_shapella".parse::<TimeFrame>().unwrap();
+        assert_eq!(time_frame, TimeFrame::Growing(SinceShapella));
+        assert_eq!(time_frame.to_string(), "since_shapella");
+    }
+
+    #[test]
+    fn since_shapella_start_test() {
+        assert_eq!(
+            SinceShapella.start_timestamp(),
+            "2023-04-12T22:27:35Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
 }
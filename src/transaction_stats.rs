@@ -0,0 +1,285 @@
+//! # Transaction Stats
+//! How much the chain is used, counted from the transactions we store. For every time frame, and
+//! per day since we started storing them, we publish the number of transactions, and the number
+//! and ETH volume of plain transfers, transactions sending ETH without calling a contract.
+//!
+//! The stats of each block are stored as it syncs, time frames sum those instead of the
+//! transactions themselves.
+//!
+//! Reads stored transactions, see `execution_chain::transactions`.
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use cached::proc_macro::{cached, once};
+use chrono::{DateTime, Utc};
+use enum_iterator::all;
+use serde::Serialize;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use tracing::debug;
+
+use crate::{
+    analysis::Analysis,
+    caching::{self, CacheKey},
+    execution_chain::{BlockNumber, BlockTransaction, ExecutionNodeBlock},
+    rollback::RollbackPoint,
+    time_frames::TimeFrame,
+    units::WeiNewtype,
+};
+
+/// Transactions with no input that send a value, to an account rather than creating a contract.
+fn is_transfer(transaction: &BlockTransaction) -> bool {
+    transaction.input_selector.is_none() && transaction.to.is_some() && transaction.value.0 > 0
+}
+
+pub async fn store_transaction_stats(
+    executor: impl PgExecutor<'_>,
+    block: &ExecutionNodeBlock,
+    transactions: &[BlockTransaction],
+) {
+    let transfers = transactions
+        .iter()
+        .filter(|transaction| is_transfer(transaction))
+        .collect::<Vec<_>>();
+    let transfer_volume = transfers
+        .iter()
+        .fold(WeiNewtype(0), |sum, transaction| sum + transaction.value);
+
+    sqlx::query!(
+        "
+        INSERT INTO transaction_stats (
+            block_number,
+            timestamp,
+            transaction_count,
+            transfer_count,
+            transfer_volume_wei
+        )
+        VALUES ($1, $2, $3, $4, $5)
+        ",
+        block.number.0,
+        block.timestamp,
+        transactions.len() as i32,
+        transfers.len() as i32,
+        transfer_volume as WeiNewtype,
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+async fn delete_transaction_stats(
+    executor: impl PgExecutor<'_>,
+    greater_than_or_equal: &BlockNumber,
+) {
+    sqlx::query!(
+        "DELETE FROM transaction_stats WHERE block_number >= $1",
+        greater_than_or_equal.0
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+pub struct TransactionStatsRollback;
+
+#[async_trait]
+impl Analysis for TransactionStatsRollback {
+    fn name(&self) -> &'static str {
+        "transaction_stats"
+    }
+
+    async fn on_rollback(&self, transaction: &mut PgConnection, point: &RollbackPoint) {
+        if let RollbackPoint::BlockNumbersGte(block_number_gte) = point {
+            delete_transaction_stats(transaction, block_number_gte).await;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TransactionStats {
+    transaction_count: i64,
+    transfer_count: i64,
+    transfer_volume: WeiNewtype,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TransactionStatsOnDay {
+    #[serde(flatten)]
+    stats: TransactionStats,
+    /// Start of the day.
+    timestamp: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct TransactionStatsByTimeFrame {
+    block_number: BlockNumber,
+    #[serde(flatten)]
+    time_frames: HashMap<TimeFrame, TransactionStats>,
+}
+
+async fn get_transaction_stats(
+    executor: impl PgExecutor<'_>,
+    start_timestamp: &DateTime<Utc>,
+) -> TransactionStats {
+    sqlx::query_as!(
+        TransactionStats,
+        r#"
+        SELECT
+            COALESCE(SUM(transaction_count), 0)::BIGINT AS "transaction_count!",
+            COALESCE(SUM(transfer_count), 0)::BIGINT AS "transfer_count!",
+            COALESCE(SUM(transfer_volume_wei), 0)::NUMERIC(78, 0) AS "transfer_volume!: WeiNewtype"
+        FROM transaction_stats
+        WHERE timestamp >= $1
+        "#,
+        start_timestamp,
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+}
+
+async fn get_transaction_stats_by_day(executor: impl PgExecutor<'_>) -> Vec<TransactionStatsOnDay> {
+    sqlx::query!(
+        r#"
+        SELECT
+            DATE_TRUNC('day', timestamp) AS "day_timestamp!",
+            SUM(transaction_count)::BIGINT AS "transaction_count!",
+            SUM(transfer_count)::BIGINT AS "transfer_count!",
+            SUM(transfer_volume_wei)::NUMERIC(78, 0) AS "transfer_volume!: WeiNewtype"
+        FROM transaction_stats
+        GROUP BY 1
+        ORDER BY 1 ASC
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| TransactionStatsOnDay {
+        stats: TransactionStats {
+            transaction_count: row.transaction_count,
+            transfer_count: row.transfer_count,
+            transfer_volume: row.transfer_volume,
+        },
+        timestamp: row.day_timestamp,
+    })
+    .collect()
+}
+
+// Growing time frames sum millions of blocks and barely change from block to block.
+#[cached(
+    key = "String",
+    convert = r#"{start_timestamp.to_string()}"#,
+    time = 3600
+)]
+async fn get_transaction_stats_cached_1h(
+    db_pool: &PgPool,
+    start_timestamp: &DateTime<Utc>,
+) -> TransactionStats {
+    get_transaction_stats(db_pool, start_timestamp).await
+}
+
+// Past days don't change, today's count lagging an hour is fine for a daily chart.
+#[once(time = 3600)]
+async fn get_transaction_stats_by_day_cached_1h(db_pool: &PgPool) -> Vec<TransactionStatsOnDay> {
+    get_transaction_stats_by_day(db_pool).await
+}
+
+pub async fn on_new_block(db_pool: &PgPool, block: &ExecutionNodeBlock) {
+    debug!("calculating transaction stats");
+
+    let mut time_frames = HashMap::new();
+    for time_frame in all::<TimeFrame>() {
+        let start_timestamp = time_frame.start_timestamp(block);
+        let stats = match time_frame {
+            TimeFrame::Growing(_) => {
+                get_transaction_stats_cached_1h(db_pool, &start_timestamp).await
+            }
+            TimeFrame::Limited(_) => get_transaction_stats(db_pool, &start_timestamp).await,
+        };
+        time_frames.insert(time_frame, stats);
+    }
+
+    let transaction_stats = TransactionStatsByTimeFrame {
+        block_number: block.number,
+        time_frames,
+    };
+    caching::update_and_publish(db_pool, &CacheKey::TransactionStats, transaction_stats).await;
+
+    let transaction_stats_by_day = get_transaction_stats_by_day_cached_1h(db_pool).await;
+    caching::update_and_publish(
+        db_pool,
+        &CacheKey::TransactionStatsByDay,
+        transaction_stats_by_day,
+    )
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, DurationRound};
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        execution_chain::{BlockTransactionBuilder, ExecutionNodeBlockBuilder},
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn transaction_stats_test(test_db: &TestDb) {
+        let block = ExecutionNodeBlockBuilder::new("transaction_stats").build();
+        store_transaction_stats(
+            &test_db.pool,
+            &block,
+            &[
+                BlockTransactionBuilder::new("transaction_stats", 0)
+                    .with_value(WeiNewtype(10))
+                    .build(),
+                BlockTransactionBuilder::new("transaction_stats", 1)
+                    .with_value(WeiNewtype(5))
+                    .build(),
+                BlockTransactionBuilder::new("transaction_stats", 2)
+                    .with_input_selector("0xa9059cbb")
+                    .with_value(WeiNewtype(7))
+                    .build(),
+                BlockTransactionBuilder::new("transaction_stats", 3).build(),
+            ],
+        )
+        .await;
+
+        let expected = TransactionStats {
+            transaction_count: 4,
+            transfer_count: 2,
+            transfer_volume: WeiNewtype(15),
+        };
+        assert_eq!(
+            get_transaction_stats(&test_db.pool, &block.timestamp).await,
+            expected
+        );
+        assert_eq!(
+            get_transaction_stats_by_day(&test_db.pool).await,
+            vec![TransactionStatsOnDay {
+                stats: expected,
+                timestamp: block.timestamp.duration_trunc(Duration::days(1)).unwrap(),
+            }]
+        );
+
+        let mut connection = test_db.pool.acquire().await.unwrap();
+        TransactionStatsRollback
+            .on_rollback(
+                &mut connection,
+                &RollbackPoint::BlockNumbersGte(block.number),
+            )
+            .await;
+        assert_eq!(
+            get_transaction_stats(&mut *connection, &block.timestamp).await,
+            TransactionStats {
+                transaction_count: 0,
+                transfer_count: 0,
+                transfer_volume: WeiNewtype(0),
+            }
+        );
+    }
+}
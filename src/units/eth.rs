@@ -3,14 +3,15 @@ use std::{
     ops::{Add, Sub},
 };
 
-use serde::Serialize;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Serialize, Serializer};
 
 use super::{GweiNewtype, WeiNewtype};
 
 /// This type tracks an amount of ETH. It is likely you'd want to a more precise type such as
 /// GweiNewtype or WeiNewtype instead. Converting to ETH only at the last moment if imprecise is
 /// fine.
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(transparent)]
 pub struct EthNewtype(pub f64);
 
@@ -46,6 +47,12 @@ impl Display for EthNewtype {
     }
 }
 
+impl From<f64> for EthNewtype {
+    fn from(amount: f64) -> Self {
+        EthNewtype(amount)
+    }
+}
+
 /// NOTE: this loses precision.
 impl From<GweiNewtype> for EthNewtype {
     fn from(GweiNewtype(amount): GweiNewtype) -> Self {
@@ -59,3 +66,102 @@ impl From<WeiNewtype> for EthNewtype {
         EthNewtype(amount as f64 / EthNewtype::WEI_PER_ETH as f64)
     }
 }
+
+/// Precise amount of ETH, for values we aggregate, where f64 would drift. Holds at most ~7.9e10
+/// ETH to the wei. Serializes as a number, rounded to `JSON_DECIMALS`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EthDecimal(pub Decimal);
+
+impl EthDecimal {
+    /// Gwei precision, the f64 we serialize to can't hold much more for amounts like the supply.
+    pub const JSON_DECIMALS: u32 = 9;
+}
+
+impl Add for EthDecimal {
+    type Output = Self;
+
+    fn add(self, EthDecimal(rhs): Self) -> Self::Output {
+        let EthDecimal(lhs) = self;
+        EthDecimal(lhs + rhs)
+    }
+}
+
+impl Sub for EthDecimal {
+    type Output = Self;
+
+    fn sub(self, EthDecimal(rhs): Self) -> Self::Output {
+        let EthDecimal(lhs) = self;
+        EthDecimal(lhs - rhs)
+    }
+}
+
+impl Display for EthDecimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let EthDecimal(amount) = self;
+        write!(f, "{amount}")
+    }
+}
+
+impl From<WeiNewtype> for EthDecimal {
+    fn from(WeiNewtype(amount): WeiNewtype) -> Self {
+        let amount = Decimal::try_from_i128_with_scale(amount, 18)
+            .expect("expect wei amount to fit in a decimal");
+        EthDecimal(amount.normalize())
+    }
+}
+
+impl From<EthDecimal> for WeiNewtype {
+    fn from(EthDecimal(amount): EthDecimal) -> Self {
+        let mut amount = amount.round_dp(18);
+        amount.rescale(18);
+        WeiNewtype(amount.mantissa())
+    }
+}
+
+/// Rounds to `EthDecimal::JSON_DECIMALS`.
+impl From<EthDecimal> for EthNewtype {
+    fn from(EthDecimal(amount): EthDecimal) -> Self {
+        let amount = amount
+            .round_dp(EthDecimal::JSON_DECIMALS)
+            .to_f64()
+            .expect("expect decimal eth amount to fit in f64");
+        EthNewtype(amount)
+    }
+}
+
+/// NOTE: this takes on the imprecision of the f64.
+impl TryFrom<EthNewtype> for EthDecimal {
+    type Error = rust_decimal::Error;
+
+    fn try_from(EthNewtype(amount): EthNewtype) -> Result<Self, Self::Error> {
+        Decimal::try_from(amount).map(EthDecimal)
+    }
+}
+
+impl Serialize for EthDecimal {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        EthNewtype::from(*self).serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eth_decimal_wei_round_trip_test() {
+        let wei = WeiNewtype(120_123_456_789_012_345_678_901_234);
+        let eth = EthDecimal::from(wei);
+        assert_eq!(eth.to_string(), "120123456.789012345678901234");
+        assert_eq!(WeiNewtype::from(eth), wei);
+    }
+
+    #[test]
+    fn eth_decimal_rounds_at_edge_test() {
+        let eth = EthDecimal::from(WeiNewtype(1_234_567_890_123_456_789));
+        assert_eq!(EthNewtype::from(eth), EthNewtype(1.234567890));
+    }
+}
@@ -7,7 +7,7 @@ use std::{
 
 use serde::{de, de::Visitor, Deserialize, Serialize};
 
-use super::{eth::EthNewtype, WeiNewtype};
+use super::{eth::EthNewtype, UnitArithmeticError, WeiNewtype};
 
 // Can handle at most 1.84e19 Gwei, or 9.22e18 when we need to convert to signed i64 sometimes.
 // That is ~9_000_000_000 ETH, which is more than the entire supply. When serializing, it defaults
@@ -25,6 +25,30 @@ impl fmt::Display for GweiNewtype {
 
 impl GweiNewtype {
     pub const WEI_PER_GWEI: u32 = 1_000_000_000;
+
+    pub fn checked_add(self, GweiNewtype(rhs): Self) -> Result<Self, UnitArithmeticError> {
+        self.0.checked_add(rhs).map(GweiNewtype).ok_or(if rhs > 0 {
+            UnitArithmeticError::Overflow("gwei")
+        } else {
+            UnitArithmeticError::Underflow("gwei")
+        })
+    }
+
+    pub fn checked_sub(self, GweiNewtype(rhs): Self) -> Result<Self, UnitArithmeticError> {
+        self.0.checked_sub(rhs).map(GweiNewtype).ok_or(if rhs > 0 {
+            UnitArithmeticError::Underflow("gwei")
+        } else {
+            UnitArithmeticError::Overflow("gwei")
+        })
+    }
+
+    pub fn saturating_add(self, GweiNewtype(rhs): Self) -> Self {
+        GweiNewtype(self.0.saturating_add(rhs))
+    }
+
+    pub fn saturating_sub(self, GweiNewtype(rhs): Self) -> Self {
+        GweiNewtype(self.0.saturating_sub(rhs))
+    }
 }
 
 impl Add<GweiNewtype> for GweiNewtype {
@@ -102,6 +126,12 @@ impl From<WeiNewtype> for GweiNewtype {
 #[serde(transparent)]
 pub struct GweiImprecise(pub f64);
 
+impl fmt::Display for GweiImprecise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl From<GweiNewtype> for GweiImprecise {
     fn from(GweiNewtype(amount): GweiNewtype) -> Self {
         GweiImprecise(amount as f64)
@@ -181,4 +211,32 @@ mod tests {
     fn gwei_sub_test() {
         assert_eq!(GweiNewtype(1) - GweiNewtype(1), GweiNewtype(0));
     }
+
+    #[test]
+    fn gwei_checked_test() {
+        assert_eq!(
+            GweiNewtype(1).checked_add(GweiNewtype(1)),
+            Ok(GweiNewtype(2))
+        );
+        assert_eq!(
+            GweiNewtype(i64::MAX).checked_add(GweiNewtype(1)),
+            Err(UnitArithmeticError::Overflow("gwei"))
+        );
+        assert_eq!(
+            GweiNewtype(i64::MIN).checked_sub(GweiNewtype(1)),
+            Err(UnitArithmeticError::Underflow("gwei"))
+        );
+    }
+
+    #[test]
+    fn gwei_saturating_test() {
+        assert_eq!(
+            GweiNewtype(i64::MAX).saturating_add(GweiNewtype(1)),
+            GweiNewtype(i64::MAX)
+        );
+        assert_eq!(
+            GweiNewtype(i64::MIN).saturating_sub(GweiNewtype(1)),
+            GweiNewtype(i64::MIN)
+        );
+    }
 }
@@ -1,8 +1,13 @@
+//! # Units
+//! Amounts of ETH, in wei, gwei and ETH, and amounts of USD. Precise types are integer backed,
+//! imprecise ones f64 backed. Convert to the imprecise types as late as possible.
 mod eth;
 mod gwei;
 mod usd;
 mod wei;
 
+use thiserror::Error;
+
 pub use gwei::GweiImprecise;
 pub use gwei::GweiNewtype;
 
@@ -10,10 +15,21 @@ pub use wei::Wei;
 pub use wei::WeiF64;
 pub use wei::WeiNewtype;
 
+pub use eth::EthDecimal;
 pub use eth::EthNewtype;
 
+pub use usd::UsdDecimal;
 pub use usd::UsdNewtype;
 
 pub const GWEI_PER_ETH_F64: f64 = 1_000_000_000_f64;
 
 pub const WEI_PER_ETH: i128 = 1_000_000_000_000_000_000;
+
+/// Returned by the checked arithmetic on unit newtypes, instead of panicking.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum UnitArithmeticError {
+    #[error("overflow in {0} arithmetic")]
+    Overflow(&'static str),
+    #[error("underflow in {0} arithmetic")]
+    Underflow(&'static str),
+}
@@ -3,14 +3,15 @@ use std::{
     ops::{Add, Sub},
 };
 
-use serde::Serialize;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Serialize, Serializer};
 
 use super::{EthNewtype, GweiNewtype, WeiNewtype};
 
 /// An amount of USD.
 /// We use the imprecise f64 here because most USD amounts we track are based on ETH amounts,
 /// converted to USD, which is also imprecise.
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(transparent)]
 pub struct UsdNewtype(pub f64);
 
@@ -66,3 +67,84 @@ impl From<f64> for UsdNewtype {
         UsdNewtype(amount)
     }
 }
+
+/// Precise amount of USD, for sums we keep adding to and subtracting from, where f64 would drift.
+/// Stored as NUMERIC, serializes as a number rounded to `JSON_DECIMALS`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct UsdDecimal(pub Decimal);
+
+impl UsdDecimal {
+    /// Cents.
+    pub const JSON_DECIMALS: u32 = 2;
+}
+
+impl Add for UsdDecimal {
+    type Output = Self;
+
+    fn add(self, UsdDecimal(rhs): Self) -> Self::Output {
+        let UsdDecimal(lhs) = self;
+        UsdDecimal(lhs + rhs)
+    }
+}
+
+impl Sub for UsdDecimal {
+    type Output = Self;
+
+    fn sub(self, UsdDecimal(rhs): Self) -> Self::Output {
+        let UsdDecimal(lhs) = self;
+        UsdDecimal(lhs - rhs)
+    }
+}
+
+impl Display for UsdDecimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let UsdDecimal(amount) = self;
+        write!(f, "{amount}")
+    }
+}
+
+impl From<Decimal> for UsdDecimal {
+    fn from(amount: Decimal) -> Self {
+        UsdDecimal(amount)
+    }
+}
+
+/// Rounds to `UsdDecimal::JSON_DECIMALS`.
+impl From<UsdDecimal> for UsdNewtype {
+    fn from(UsdDecimal(amount): UsdDecimal) -> Self {
+        let amount = amount
+            .round_dp(UsdDecimal::JSON_DECIMALS)
+            .to_f64()
+            .expect("expect decimal usd amount to fit in f64");
+        UsdNewtype(amount)
+    }
+}
+
+impl Serialize for UsdDecimal {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        UsdNewtype::from(*self).serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usd_decimal_rounds_at_edge_test() {
+        let usd = UsdDecimal(Decimal::new(123_456, 3));
+        assert_eq!(UsdNewtype::from(usd), UsdNewtype(123.46));
+        assert_eq!(serde_json::to_string(&usd).unwrap(), "123.46");
+    }
+
+    #[test]
+    fn usd_decimal_no_drift_test() {
+        let cent = UsdDecimal(Decimal::new(1, 2));
+        let sum = (0..1_000).fold(UsdDecimal::default(), |sum, _| sum + cent);
+        assert_eq!(sum, UsdDecimal(Decimal::new(10, 0)));
+    }
+}
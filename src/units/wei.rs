@@ -7,9 +7,16 @@ use std::{
     str::FromStr,
 };
 
+use bigdecimal::{num_bigint::BigInt, BigDecimal, ToPrimitive};
 use serde::{Deserialize, Serialize};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres,
+};
 
-use super::{EthNewtype, GweiNewtype, WEI_PER_ETH};
+use super::{EthNewtype, GweiNewtype, UnitArithmeticError, WEI_PER_ETH};
 
 pub type WeiF64 = f64;
 
@@ -25,6 +32,30 @@ impl WeiNewtype {
     pub fn from_eth(eth: i128) -> Self {
         Self(eth * WEI_PER_ETH)
     }
+
+    pub fn checked_add(self, WeiNewtype(rhs): Self) -> Result<Self, UnitArithmeticError> {
+        self.0.checked_add(rhs).map(WeiNewtype).ok_or(if rhs > 0 {
+            UnitArithmeticError::Overflow("wei")
+        } else {
+            UnitArithmeticError::Underflow("wei")
+        })
+    }
+
+    pub fn checked_sub(self, WeiNewtype(rhs): Self) -> Result<Self, UnitArithmeticError> {
+        self.0.checked_sub(rhs).map(WeiNewtype).ok_or(if rhs > 0 {
+            UnitArithmeticError::Underflow("wei")
+        } else {
+            UnitArithmeticError::Overflow("wei")
+        })
+    }
+
+    pub fn saturating_add(self, WeiNewtype(rhs): Self) -> Self {
+        WeiNewtype(self.0.saturating_add(rhs))
+    }
+
+    pub fn saturating_sub(self, WeiNewtype(rhs): Self) -> Self {
+        WeiNewtype(self.0.saturating_sub(rhs))
+    }
 }
 
 impl Add<WeiNewtype> for WeiNewtype {
@@ -104,4 +135,103 @@ impl From<EthNewtype> for WeiNewtype {
     }
 }
 
+// Wei amounts are stored as NUMERIC(78, 0), large enough for any uint256.
+impl sqlx::Type<Postgres> for WeiNewtype {
+    fn type_info() -> PgTypeInfo {
+        <BigDecimal as sqlx::Type<Postgres>>::type_info()
+    }
+}
+
+impl PgHasArrayType for WeiNewtype {
+    fn array_type_info() -> PgTypeInfo {
+        <BigDecimal as PgHasArrayType>::array_type_info()
+    }
+}
+
+impl Encode<'_, Postgres> for WeiNewtype {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        let WeiNewtype(amount) = self;
+        <BigDecimal as Encode<Postgres>>::encode(BigDecimal::new(BigInt::from(*amount), 0), buf)
+    }
+}
+
+impl Decode<'_, Postgres> for WeiNewtype {
+    fn decode(value: PgValueRef<'_>) -> Result<Self, BoxDynError> {
+        let decimal = <BigDecimal as Decode<Postgres>>::decode(value)?;
+        if !decimal.is_integer() {
+            return Err(format!("expect wei amount to be a whole number, got {decimal}").into());
+        }
+        let (amount, _) = decimal.with_scale(0).into_bigint_and_exponent();
+        amount
+            .to_i128()
+            .map(WeiNewtype)
+            .ok_or_else(|| format!("expect wei amount {amount} to fit in an i128").into())
+    }
+}
+
 pub type Wei = i128;
+
+#[cfg(test)]
+mod tests {
+    use crate::db;
+
+    use super::*;
+
+    #[test]
+    fn wei_checked_add_test() {
+        assert_eq!(WeiNewtype(1).checked_add(WeiNewtype(1)), Ok(WeiNewtype(2)));
+        assert_eq!(
+            WeiNewtype(i128::MAX).checked_add(WeiNewtype(1)),
+            Err(UnitArithmeticError::Overflow("wei"))
+        );
+    }
+
+    #[test]
+    fn wei_checked_sub_test() {
+        assert_eq!(WeiNewtype(2).checked_sub(WeiNewtype(1)), Ok(WeiNewtype(1)));
+        assert_eq!(
+            WeiNewtype(i128::MIN).checked_sub(WeiNewtype(1)),
+            Err(UnitArithmeticError::Underflow("wei"))
+        );
+    }
+
+    #[test]
+    fn wei_saturating_test() {
+        assert_eq!(
+            WeiNewtype(i128::MAX).saturating_add(WeiNewtype(1)),
+            WeiNewtype(i128::MAX)
+        );
+        assert_eq!(
+            WeiNewtype(i128::MIN).saturating_sub(WeiNewtype(1)),
+            WeiNewtype(i128::MIN)
+        );
+    }
+
+    #[tokio::test]
+    async fn wei_numeric_round_trip_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+        let supply = WeiNewtype(120_000_000 * WEI_PER_ETH + 1);
+
+        let stored = sqlx::query!(
+            r#"SELECT $1::NUMERIC(78, 0) AS "wei!: WeiNewtype""#,
+            supply as WeiNewtype
+        )
+        .fetch_one(&mut connection)
+        .await
+        .unwrap()
+        .wei;
+
+        assert_eq!(stored, supply);
+    }
+
+    #[tokio::test]
+    async fn wei_fractional_numeric_test() {
+        let mut connection = db::tests::get_test_db_connection().await;
+
+        let result = sqlx::query!(r#"SELECT 1.5::NUMERIC AS "wei!: WeiNewtype""#)
+            .fetch_one(&mut connection)
+            .await;
+
+        assert!(result.is_err());
+    }
+}
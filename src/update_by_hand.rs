@@ -2,9 +2,11 @@ use anyhow::Result;
 use console::Term;
 use dialoguer::{Input, MultiSelect, Select};
 use serde::Serialize;
-use sqlx::{types::Json, PgConnection};
+use sqlx::PgConnection;
 use std::env;
 
+use crate::key_value_store;
+
 #[derive(Serialize)]
 struct EthInDefiOld {
     #[serde(rename = "ethLocked")]
@@ -35,31 +37,8 @@ async fn set_eth_in_defi_for_env(env: &str, eth: f64) -> Result<()> {
         timestamp: chrono::Utc::now().timestamp(),
     };
 
-    sqlx::query(
-        "
-            INSERT INTO key_value_store (key, value)
-            VALUES ($1, $2)
-            ON CONFLICT (key) DO UPDATE SET
-                value = $2
-        ",
-    )
-    .bind("eth-in-defi")
-    .bind(Json(eth_in_defi_at_date))
-    .execute(&mut conn)
-    .await?;
-
-    sqlx::query(
-        "
-            INSERT INTO key_value_store (key, value)
-            VALUES ($1, $2)
-            ON CONFLICT (key) DO UPDATE SET
-                value = $2
-        ",
-    )
-    .bind("eth-locked")
-    .bind(Json(eth_in_defi_at_date_old))
-    .execute(&mut conn)
-    .await?;
+    key_value_store::set(&mut conn, "eth-in-defi", &eth_in_defi_at_date).await?;
+    key_value_store::set(&mut conn, "eth-locked", &eth_in_defi_at_date_old).await?;
 
     Ok(())
 }
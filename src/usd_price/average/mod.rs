@@ -1,3 +1,6 @@
+//! Average ETH price over every time frame, up to a block. Calculated once per block and
+//! published under `CacheKey::AverageEthPrice`, analyses needing an average price, like the
+//! gauges, take them from here instead of querying their own.
 use std::collections::HashMap;
 
 use enum_iterator::all;
@@ -11,16 +14,19 @@ use crate::{
     execution_chain::ExecutionNodeBlock,
     performance::TimedExt,
     time_frames::TimeFrame,
+    units::UsdNewtype,
 };
 
-pub async fn on_new_block(
-    db_pool: &PgPool,
+pub type AverageEthPrices = HashMap<TimeFrame, UsdNewtype>;
+
+/// Growing time frames average from their start, e.g. the merge, not from a duration back.
+pub async fn get_average_prices(
     eth_price_store: &impl EthPriceStore,
     block: &ExecutionNodeBlock,
-) {
+) -> AverageEthPrices {
     let futures = all::<TimeFrame>().map(|time_frame| async move {
         let average = eth_price_store
-            .average_from_block_plus_time_range(block, &time_frame)
+            .average_from_time_range(time_frame.start_timestamp(block), block.timestamp)
             .timed(&format!(
                 "average_price_from_block {} {}",
                 block.number, time_frame,
@@ -30,11 +36,12 @@ pub async fn on_new_block(
     });
 
     let pairs = join_all(futures).await;
-    let eth_prices = pairs.into_iter().collect::<HashMap<_, _>>();
-
     debug!("calculated new average prices");
+    pairs.into_iter().collect()
+}
 
-    caching::update_and_publish(db_pool, &CacheKey::AverageEthPrice, &eth_prices).await;
+pub async fn publish_average_prices(db_pool: &PgPool, average_prices: &AverageEthPrices) {
+    caching::update_and_publish(db_pool, &CacheKey::AverageEthPrice, average_prices).await;
 }
 
 #[cfg(test)]
@@ -44,8 +51,8 @@ mod tests {
 
     use crate::{
         db::tests::TestDb,
-        execution_chain::ExecutionNodeBlockBuilder,
-        usd_price::store::{EthPriceStore, EthPriceStorePostgres},
+        execution_chain::{BlockNumber, ExecutionNodeBlockBuilder},
+        usd_price::store::{EthPriceStore, EthPriceStorePostgres, PriceOrigin},
     };
 
     #[test_context(TestDb)]
@@ -55,7 +62,7 @@ mod tests {
         let test_id = "average_from_block_range_test";
 
         let test_block_1 = ExecutionNodeBlockBuilder::new(test_id)
-            .with_number(1)
+            .with_number(BlockNumber(1))
             .with_timestamp(&"2023-02-08T00:00:00Z".parse::<DateTime<Utc>>().unwrap())
             .build();
         let test_block_2 = ExecutionNodeBlockBuilder::from_parent(&test_block_1)
@@ -66,13 +73,13 @@ mod tests {
             .build();
 
         eth_price_store
-            .store_price(&test_block_1.timestamp, 1.0)
+            .store_price(&test_block_1.timestamp, 1.0, "test", PriceOrigin::Recorded)
             .await;
         eth_price_store
-            .store_price(&test_block_2.timestamp, 2.0)
+            .store_price(&test_block_2.timestamp, 2.0, "test", PriceOrigin::Recorded)
             .await;
         eth_price_store
-            .store_price(&test_block_3.timestamp, 3.0)
+            .store_price(&test_block_3.timestamp, 3.0, "test", PriceOrigin::Recorded)
             .await;
 
         let average = eth_price_store
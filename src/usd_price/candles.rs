@@ -0,0 +1,328 @@
+//! Stores the 1min OHLC candles `record-eth-price` gets from its sources, and rolls them up into
+//! hourly and daily candles as they come in, so we can chart prices straight from our DB. Served at
+//! `/api/v2/fees/eth-candles`.
+//!
+//! Candles only exist from when we started recording them. Healing fills in missing prices, not
+//! candles.
+use std::collections::HashMap;
+
+use axum::{
+    extract::Query,
+    http::{HeaderMap, HeaderValue},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use reqwest::{header, StatusCode};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+
+use crate::serve::StateExtension;
+
+use super::EthPrice;
+
+/// Most candles a single request returns.
+const MAX_CANDLES: i64 = 1000;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EthCandle {
+    pub timestamp: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+impl EthCandle {
+    /// A candle for a single price, for sources that don't have candles.
+    pub fn flat(timestamp: DateTime<Utc>, usd: f64) -> Self {
+        Self {
+            timestamp,
+            open: usd,
+            high: usd,
+            low: usd,
+            close: usd,
+        }
+    }
+
+    /// We store the open as the price of the minute.
+    pub fn price(&self) -> EthPrice {
+        EthPrice {
+            timestamp: self.timestamp,
+            usd: self.open,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CandleResolution {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl CandleResolution {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Minute => "m1",
+            Self::Hour => "h1",
+            Self::Day => "d1",
+        }
+    }
+
+    fn from_param(param: &str) -> Option<Self> {
+        match param {
+            "m1" => Some(Self::Minute),
+            "h1" => Some(Self::Hour),
+            "d1" => Some(Self::Day),
+            _ => None,
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        match self {
+            Self::Minute => Duration::minutes(1),
+            Self::Hour => Duration::hours(1),
+            Self::Day => Duration::days(1),
+        }
+    }
+}
+
+async fn store_minute_candle(executor: impl PgExecutor<'_>, candle: &EthCandle) {
+    sqlx::query!(
+        "
+        INSERT INTO eth_candles (resolution, timestamp, open, high, low, close)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (resolution, timestamp) DO UPDATE SET
+            open = excluded.open,
+            high = excluded.high,
+            low = excluded.low,
+            close = excluded.close
+        ",
+        CandleResolution::Minute.as_str(),
+        candle.timestamp,
+        candle.open,
+        candle.high,
+        candle.low,
+        candle.close
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Rebuilds the candle of the given resolution containing the timestamp, from the candles one
+/// resolution down, hours from minutes, days from hours.
+async fn roll_up(
+    executor: impl PgExecutor<'_>,
+    resolution: CandleResolution,
+    timestamp: DateTime<Utc>,
+) {
+    let from_resolution = match resolution {
+        CandleResolution::Minute => panic!("minute candles are stored, not rolled up"),
+        CandleResolution::Hour => CandleResolution::Minute,
+        CandleResolution::Day => CandleResolution::Hour,
+    };
+    let start = timestamp.duration_trunc(resolution.duration()).unwrap();
+    let end = start + resolution.duration();
+
+    sqlx::query!(
+        "
+        INSERT INTO eth_candles (resolution, timestamp, open, high, low, close)
+        SELECT
+            $1,
+            $2,
+            (ARRAY_AGG(open ORDER BY timestamp ASC))[1],
+            MAX(high),
+            MIN(low),
+            (ARRAY_AGG(close ORDER BY timestamp DESC))[1]
+        FROM eth_candles
+        WHERE resolution = $3
+        AND timestamp >= $2
+        AND timestamp < $4
+        HAVING COUNT(*) > 0
+        ON CONFLICT (resolution, timestamp) DO UPDATE SET
+            open = excluded.open,
+            high = excluded.high,
+            low = excluded.low,
+            close = excluded.close
+        ",
+        resolution.as_str(),
+        start,
+        from_resolution.as_str(),
+        end
+    )
+    .execute(executor)
+    .await
+    .unwrap();
+}
+
+/// Stores 1min candles, and updates the hourly and daily candles they fall in.
+pub async fn store_candles(db_pool: &PgPool, candles: &[EthCandle]) {
+    let mut transaction = db_pool.begin().await.unwrap();
+
+    for candle in candles {
+        store_minute_candle(&mut *transaction, candle).await;
+    }
+
+    let mut hours = candles
+        .iter()
+        .map(|candle| candle.timestamp.duration_trunc(Duration::hours(1)).unwrap())
+        .collect::<Vec<_>>();
+    hours.dedup();
+    for hour in hours.iter() {
+        roll_up(&mut *transaction, CandleResolution::Hour, *hour).await;
+    }
+
+    let mut days = hours
+        .iter()
+        .map(|hour| hour.duration_trunc(Duration::days(1)).unwrap())
+        .collect::<Vec<_>>();
+    days.dedup();
+    for day in days {
+        roll_up(&mut *transaction, CandleResolution::Day, day).await;
+    }
+
+    transaction.commit().await.unwrap();
+}
+
+/// Oldest first, at most `MAX_CANDLES`.
+async fn get_candles(
+    executor: impl PgExecutor<'_>,
+    resolution: CandleResolution,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<EthCandle> {
+    sqlx::query_as!(
+        EthCandle,
+        "
+        SELECT timestamp, open, high, low, close
+        FROM eth_candles
+        WHERE resolution = $1
+        AND timestamp >= $2
+        AND timestamp < $3
+        ORDER BY timestamp ASC
+        LIMIT $4
+        ",
+        resolution.as_str(),
+        start,
+        end,
+        MAX_CANDLES
+    )
+    .fetch_all(executor)
+    .await
+    .unwrap()
+}
+
+fn parse_timestamp_param(
+    params: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<DateTime<Utc>>, StatusCode> {
+    params
+        .get(name)
+        .map(|param| param.parse::<DateTime<Utc>>())
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+/// Takes a `resolution` of `m1`, `h1` or `d1`, `h1` by default, and optional RFC 3339 `start` and
+/// `end` timestamps. Without a start, returns the most recent candles up to `end`.
+pub async fn eth_candles(
+    state: StateExtension,
+    Query(params): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let resolution = match params.get("resolution") {
+        None => CandleResolution::Hour,
+        Some(param) => match CandleResolution::from_param(param) {
+            Some(resolution) => resolution,
+            None => return StatusCode::BAD_REQUEST.into_response(),
+        },
+    };
+    let (start, end) = match (
+        parse_timestamp_param(&params, "start"),
+        parse_timestamp_param(&params, "end"),
+    ) {
+        (Ok(start), Ok(end)) => {
+            let end = end.unwrap_or_else(Utc::now);
+            let start = start.unwrap_or(end - resolution.duration() * MAX_CANDLES as i32);
+            (start, end)
+        }
+        (Err(status), _) | (_, Err(status)) => return status.into_response(),
+    };
+
+    let candles = get_candles(&state.db_pool, resolution, start, end).await;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=6, stale-while-revalidate=120"),
+    );
+
+    (headers, Json(candles)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::db::tests::TestDb;
+
+    use super::*;
+
+    fn candle(timestamp: &str, open: f64, high: f64, low: f64, close: f64) -> EthCandle {
+        EthCandle {
+            timestamp: timestamp.parse().unwrap(),
+            open,
+            high,
+            low,
+            close,
+        }
+    }
+
+    #[test]
+    fn candle_resolution_from_param_test() {
+        assert_eq!(
+            CandleResolution::from_param("d1"),
+            Some(CandleResolution::Day)
+        );
+        assert_eq!(CandleResolution::from_param("w1"), None);
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn store_candles_rolls_up_test(test_db: &TestDb) {
+        store_candles(
+            &test_db.pool,
+            &[
+                candle("2024-05-01T10:58:00Z", 3000.0, 3010.0, 2990.0, 3005.0),
+                candle("2024-05-01T10:59:00Z", 3005.0, 3030.0, 3000.0, 3020.0),
+                candle("2024-05-01T11:00:00Z", 3020.0, 3025.0, 2980.0, 2985.0),
+            ],
+        )
+        .await;
+
+        let start = "2024-05-01T00:00:00Z".parse().unwrap();
+        let end = "2024-05-02T00:00:00Z".parse().unwrap();
+
+        let hours = get_candles(&test_db.pool, CandleResolution::Hour, start, end).await;
+        assert_eq!(
+            hours,
+            vec![
+                candle("2024-05-01T10:00:00Z", 3000.0, 3030.0, 2990.0, 3020.0),
+                candle("2024-05-01T11:00:00Z", 3020.0, 3025.0, 2980.0, 2985.0),
+            ]
+        );
+
+        let days = get_candles(&test_db.pool, CandleResolution::Day, start, end).await;
+        assert_eq!(
+            days,
+            vec![candle(
+                "2024-05-01T00:00:00Z",
+                3000.0,
+                3030.0,
+                2980.0,
+                2985.0
+            )]
+        );
+    }
+}
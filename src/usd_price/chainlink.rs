@@ -0,0 +1,209 @@
+//! Reads the Chainlink ETH/USD aggregator through our own execution node, so we still have prices
+//! when every exchange API is unreachable. The aggregator only updates when the price moves 0.5%,
+//! or after an hour, so its prices are coarser than exchange candles.
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, DurationRound, TimeZone, Utc};
+use tokio::sync::OnceCell;
+use tracing::debug;
+
+use crate::{
+    beacon_chain::SECONDS_PER_SLOT,
+    execution_chain::{BlockNumber, ExecutionNode, ExecutionNodeBlock, PARIS_HARD_FORK_TIMESTAMP},
+};
+
+use super::{candles::EthCandle, sources::PriceSource};
+
+const ETH_USD_AGGREGATOR: &str = "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419";
+
+/// `latestRoundData()`
+const LATEST_ROUND_DATA_SELECTOR: &str = "0xfeaf968c";
+
+const ANSWER_DECIMALS: i32 = 8;
+
+#[derive(Debug, PartialEq)]
+struct RoundData {
+    usd: f64,
+    updated_at: DateTime<Utc>,
+}
+
+/// `latestRoundData` returns `(roundId, answer, startedAt, updatedAt, answeredInRound)`, each
+/// padded to 32 bytes.
+fn decode_round_data(data: &str) -> Result<RoundData> {
+    let data = data.strip_prefix("0x").unwrap_or(data);
+    if data.len() < 5 * 64 {
+        bail!("expected five words of round data, got {data}");
+    }
+    let word = |index: usize| &data[index * 64..(index + 1) * 64];
+
+    // The answer is an int256, a price fits comfortably in the lower 128 bits.
+    let answer = u128::from_str_radix(&word(1)[32..], 16)?;
+    let updated_at = i64::from_str_radix(&word(3)[48..], 16)?;
+
+    Ok(RoundData {
+        usd: answer as f64 / 10f64.powi(ANSWER_DECIMALS),
+        updated_at: Utc.timestamp_opt(updated_at, 0).unwrap(),
+    })
+}
+
+/// Since the merge every slot takes the same number of seconds, and missed slots only mean fewer
+/// blocks, so counting back slots from the latest block gets us a block at or before the
+/// timestamp. Every missed slot since puts it one block further back than it needs to be.
+fn lowest_block_number_at_or_before(
+    latest_block: &ExecutionNodeBlock,
+    timestamp: DateTime<Utc>,
+) -> BlockNumber {
+    let seconds_back = (latest_block.timestamp - timestamp).num_seconds();
+    if seconds_back <= 0 {
+        return latest_block.number;
+    }
+    let seconds_per_slot = i64::from(*SECONDS_PER_SLOT);
+    let slots_back = (seconds_back + seconds_per_slot - 1) / seconds_per_slot;
+    latest_block.number - slots_back
+}
+
+/// Binary searches block timestamps for the last block at or before the timestamp. Starting from
+/// the slot count estimate means the range is only as wide as the number of slots missed since.
+async fn block_number_at_or_before(
+    execution_node: &ExecutionNode,
+    latest_block: &ExecutionNodeBlock,
+    timestamp: DateTime<Utc>,
+) -> Result<BlockNumber> {
+    if latest_block.timestamp <= timestamp {
+        return Ok(latest_block.number);
+    }
+
+    // The block at `low` is at or before the timestamp, the block at `high` is after it.
+    let mut low = lowest_block_number_at_or_before(latest_block, timestamp);
+    let mut high = latest_block.number;
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        let block = execution_node
+            .get_block_by_number(&middle)
+            .await
+            .ok_or_else(|| anyhow!("block {middle} not found on execution node"))?;
+        if block.timestamp <= timestamp {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    Ok(low)
+}
+
+#[derive(Default)]
+pub struct Chainlink {
+    execution_node: OnceCell<ExecutionNode>,
+}
+
+impl Chainlink {
+    async fn get_round_data(
+        &self,
+        execution_node: &ExecutionNode,
+        block_number: &BlockNumber,
+    ) -> Result<RoundData> {
+        let data = execution_node
+            .eth_call(ETH_USD_AGGREGATOR, LATEST_ROUND_DATA_SELECTOR, block_number)
+            .await?;
+        decode_round_data(&data)
+    }
+}
+
+#[async_trait]
+impl PriceSource for Chainlink {
+    fn name(&self) -> &'static str {
+        "chainlink"
+    }
+
+    /// An answer holds from when it was updated until the next update, so one call covers every
+    /// minute since the update. Before the merge we can't find blocks by time, and return nothing.
+    async fn get_eth_candles(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<EthCandle>> {
+        let start_minute = std::cmp::max(start, *PARIS_HARD_FORK_TIMESTAMP)
+            .duration_trunc(Duration::minutes(1))?;
+        let mut minute = end.duration_trunc(Duration::minutes(1))?;
+        if minute < start_minute {
+            return Ok(Vec::new());
+        }
+
+        let execution_node = self
+            .execution_node
+            .get_or_init(ExecutionNode::connect)
+            .await;
+        let latest_block = execution_node.get_latest_block().await;
+
+        let mut candles = Vec::new();
+        while minute >= start_minute {
+            let block_number =
+                block_number_at_or_before(execution_node, &latest_block, minute).await?;
+            let round_data = self.get_round_data(execution_node, &block_number).await?;
+            debug!(%block_number, %minute, ?round_data, "read chainlink round data");
+
+            loop {
+                candles.push(EthCandle::flat(minute, round_data.usd));
+                minute -= Duration::minutes(1);
+                if minute < start_minute || minute < round_data.updated_at {
+                    break;
+                }
+            }
+        }
+
+        // Oldest first, like the other sources.
+        candles.reverse();
+
+        Ok(candles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::execution_chain::ExecutionNodeBlockBuilder;
+
+    use super::*;
+
+    #[test]
+    fn decode_round_data_test() {
+        let data = concat!(
+            "0x",
+            "0000000000000000000000000000000000000000000000060000000000002840",
+            "00000000000000000000000000000000000000000000000000000045da21194e",
+            "00000000000000000000000000000000000000000000000000000000663185f6",
+            "0000000000000000000000000000000000000000000000000000000066318600",
+            "0000000000000000000000000000000000000000000000060000000000002840",
+        );
+        assert_eq!(
+            decode_round_data(data).unwrap(),
+            RoundData {
+                usd: 3000.12345678,
+                updated_at: "2024-05-01T00:00:00Z".parse().unwrap(),
+            }
+        );
+        assert!(decode_round_data("0x").is_err());
+    }
+
+    #[test]
+    fn lowest_block_number_at_or_before_test() {
+        let timestamp: DateTime<Utc> = "2024-05-01T00:00:11Z".parse().unwrap();
+        let latest_block = ExecutionNodeBlockBuilder::new("latest")
+            .with_number(BlockNumber(100))
+            .with_timestamp(&timestamp)
+            .build();
+
+        assert_eq!(
+            lowest_block_number_at_or_before(&latest_block, timestamp + Duration::minutes(1)),
+            BlockNumber(100)
+        );
+        assert_eq!(
+            lowest_block_number_at_or_before(&latest_block, timestamp - Duration::seconds(1)),
+            BlockNumber(99)
+        );
+        assert_eq!(
+            lowest_block_number_at_or_before(&latest_block, timestamp - Duration::seconds(13)),
+            BlockNumber(98)
+        );
+    }
+}
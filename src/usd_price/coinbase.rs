@@ -0,0 +1,111 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use format_url::FormatUrl;
+use tracing::debug;
+
+use super::{candles::EthCandle, sources::PriceSource};
+
+const COINBASE_API: &str = "https://api.exchange.coinbase.com";
+
+/// Coinbase rejects requests without a user agent.
+const USER_AGENT: &str = "eth-analysis-rs";
+
+/// Candles come as `[time, low, high, open, close, volume]`, newest first.
+fn parse_candles(candles: &[[f64; 6]]) -> Vec<EthCandle> {
+    candles
+        .iter()
+        .map(|candle| EthCandle {
+            timestamp: Utc.timestamp_opt(candle[0] as i64, 0).unwrap(),
+            open: candle[3],
+            high: candle[2],
+            low: candle[1],
+            close: candle[4],
+        })
+        .rev()
+        .collect()
+}
+
+/// 1min candles of the Coinbase ETH-USD spot price. Returns at most 300 candles.
+async fn get_eth_candles(start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<EthCandle>> {
+    get_candles("ETH-USD", start, end).await
+}
+
+/// 1min spot price candles for a product, e.g. `BTC-USD`. Returns at most 300 candles.
+pub async fn get_candles(
+    product: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<EthCandle>> {
+    let url = FormatUrl::new(COINBASE_API)
+        .with_path_template("/products/:product/candles")
+        .with_substitutes(vec![("product", product)])
+        .with_query_params(vec![
+            ("granularity", "60"),
+            ("start", &start.to_rfc3339()),
+            ("end", &end.to_rfc3339()),
+        ])
+        .format_url();
+
+    debug!("sending request to {}", url);
+
+    let candles = reqwest::Client::new()
+        .get(&url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Vec<[f64; 6]>>()
+        .await?;
+
+    Ok(parse_candles(&candles))
+}
+
+pub struct Coinbase;
+
+#[async_trait]
+impl PriceSource for Coinbase {
+    fn name(&self) -> &'static str {
+        "coinbase"
+    }
+
+    async fn get_eth_candles(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<EthCandle>> {
+        get_eth_candles(start, end).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_candles_test() {
+        let candles: Vec<[f64; 6]> = serde_json::from_str(
+            "[[1714521660,3010.5,3020,3012.1,3015,31.2],[1714521600,2999,3013,3000,3012.1,40.1]]",
+        )
+        .unwrap();
+        assert_eq!(
+            parse_candles(&candles),
+            vec![
+                EthCandle {
+                    timestamp: "2024-05-01T00:00:00Z".parse().unwrap(),
+                    open: 3000.0,
+                    high: 3013.0,
+                    low: 2999.0,
+                    close: 3012.1,
+                },
+                EthCandle {
+                    timestamp: "2024-05-01T00:01:00Z".parse().unwrap(),
+                    open: 3012.1,
+                    high: 3020.0,
+                    low: 3010.5,
+                    close: 3015.0,
+                },
+            ]
+        );
+    }
+}
@@ -1,107 +1,347 @@
+//! Fills in minutes without a price since London. Which sources are asked can be picked with
+//! `--sources bybit,coinbase`, or `heal_eth_price_sources` in the config file, and the minutes
+//! healed restricted with `--start` and `--end`, dates or RFC 3339 timestamps. With `--dry-run`
+//! the missing minutes and the price we'd store for each are logged, nothing is written.
+//!
+//! A restricted run always heals its whole range. It keeps its own checkpoint, so it doesn't make
+//! a later full run skip the minutes before its range.
 use std::collections::HashSet;
 
-use chrono::{Duration, DurationRound, TimeZone, Utc};
-use sqlx::Postgres;
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, DurationRound, NaiveDate, TimeZone, Utc};
+use futures::stream::{self, StreamExt};
+use sqlx::{PgPool, Postgres};
 use store::EthPriceStore;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-use crate::{db, execution_chain, log};
+use crate::{
+    config, db, execution_chain,
+    heal::{self, HealOptions, Healer},
+    log,
+};
 
-use super::{bybit, store, EthPriceTimestamp};
-use futures::stream::{self, StreamExt};
-use tracing::warn;
+use super::{store, EthPriceTimestamp, PriceOrigin, PriceSources};
 
 const CONCURRENT_REQUESTS: usize = 8;
 
-pub async fn heal_eth_prices() {
-    log::init_with_env();
+const HEAL_ETH_PRICES_KEY: &str = "heal-eth-prices";
+const HEAL_ETH_PRICES_RANGE_KEY: &str = "heal-eth-prices-range";
 
-    info!("healing missing eth prices");
-    let max_distance_in_minutes: i64 = std::env::args()
-        .collect::<Vec<String>>()
-        .get(1)
-        .and_then(|str| str.parse::<i64>().ok())
-        .unwrap_or(10);
+/// Which minutes to heal and where to get prices from.
+#[derive(Debug, Default)]
+pub struct HealEthPricesOptions {
+    /// Exclusive, now when `None`.
+    pub end: Option<DateTime<Utc>>,
+    /// Names of the sources to ask, in order. Every source when `None`.
+    pub sources: Option<Vec<String>>,
+    /// London when `None`.
+    pub start: Option<DateTime<Utc>>,
+}
 
-    info!("getting all eth prices");
-    let db_pool = db::get_db_pool("heal-eth-prices").await;
+/// Accepts a date like 2024-03-01, or an RFC 3339 timestamp.
+fn parse_date_or_timestamp(str: &str) -> Result<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(str, "%Y-%m-%d") {
+        return Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()));
+    }
 
-    let eth_price_store = store::EthPriceStorePostgres::new(db_pool.clone());
+    str.parse::<DateTime<Utc>>()
+        .map_err(|_| anyhow!("expected date or timestamp, got {str}"))
+}
 
-    let eth_prices = sqlx::query_as::<Postgres, EthPriceTimestamp>(
-        "
-            SELECT
-                timestamp
-            FROM
-                eth_prices
-        ",
-    )
-    .fetch_all(&db_pool)
-    .await
-    .unwrap();
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let prefix = format!("{name}=");
+    args.iter()
+        .enumerate()
+        .find_map(|(index, arg)| match arg.strip_prefix(&prefix) {
+            Some(value) => Some(value),
+            None if arg == name => args.get(index + 1).map(String::as_str),
+            None => None,
+        })
+}
 
-    if eth_prices.is_empty() {
-        warn!("no eth prices found, are you running against a DB with prices?")
+impl HealEthPricesOptions {
+    pub fn from_config() -> Self {
+        Self {
+            sources: config::CONFIG
+                .heal_eth_price_sources()
+                .map(<[String]>::to_vec),
+            ..Self::default()
+        }
     }
 
-    info!("building set of known minutes");
-    let mut known_minutes = HashSet::new();
+    /// Given flags take precedence over the options. Start and end are dates or timestamps.
+    pub fn with_flags(
+        self,
+        sources: Option<Vec<String>>,
+        start: Option<&str>,
+        end: Option<&str>,
+    ) -> Result<Self> {
+        let mut options = self;
+        if sources.is_some() {
+            options.sources = sources;
+        }
+        if let Some(start) = start {
+            options.start = Some(parse_date_or_timestamp(start)?);
+        }
+        if let Some(end) = end {
+            options.end = Some(parse_date_or_timestamp(end)?);
+        }
+        Ok(options)
+    }
 
-    for eth_price in eth_prices.iter() {
-        known_minutes.insert(eth_price.timestamp.timestamp());
+    /// Reads the flags from the command line arguments, for the binaries in `src/bin`.
+    fn from_args(args: &[String], defaults: Self) -> Result<Self> {
+        let sources = flag_value(args, "--sources")
+            .map(|sources| sources.split(',').map(str::to_string).collect());
+        defaults.with_flags(
+            sources,
+            flag_value(args, "--start"),
+            flag_value(args, "--end"),
+        )
     }
+}
+
+struct EthPricesHealer {
+    db_pool: PgPool,
+    end: Option<DateTime<Utc>>,
+    eth_price_store: store::EthPriceStorePostgres,
+    max_distance: Duration,
+    price_sources: PriceSources,
+    start: Option<DateTime<Utc>>,
+}
+
+impl EthPricesHealer {
+    fn is_restricted(&self) -> bool {
+        self.start.is_some() || self.end.is_some()
+    }
+}
+
+#[async_trait]
+impl Healer for EthPricesHealer {
+    /// The unix timestamp of a minute without a price.
+    type Item = i64;
+
+    fn name(&self) -> &'static str {
+        if self.is_restricted() {
+            HEAL_ETH_PRICES_RANGE_KEY
+        } else {
+            HEAL_ETH_PRICES_KEY
+        }
+    }
+
+    async fn items_to_heal(&self, checkpoint: Option<i64>) -> Vec<i64> {
+        // A restricted run heals its whole range, the range may differ from the last run.
+        let checkpoint = if self.is_restricted() {
+            None
+        } else {
+            checkpoint
+        };
+        let start = self.start.map(|start| start.timestamp());
+        let end = self.end.map(|end| end.timestamp());
+
+        info!("getting all eth prices");
+        let eth_prices = sqlx::query_as::<Postgres, EthPriceTimestamp>(
+            "
+                SELECT
+                    timestamp
+                FROM
+                    eth_prices
+            ",
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .unwrap();
+
+        if eth_prices.is_empty() {
+            warn!("no eth prices found, are you running against a DB with prices?")
+        }
+
+        info!("building set of known minutes");
+        let mut known_minutes = HashSet::new();
+
+        for eth_price in eth_prices.iter() {
+            known_minutes.insert(eth_price.timestamp.timestamp());
+        }
+
+        info!("walking through all minutes since London hardfork to look for missing minutes");
 
-    info!("walking through all minutes since London hardfork to look for missing minutes");
-
-    let duration_since_london = Utc::now().duration_round(Duration::minutes(1)).unwrap()
-        - *execution_chain::LONDON_HARD_FORK_TIMESTAMP;
-    let minutes_since_london = duration_since_london.num_minutes();
-
-    let london_minute_timestamp = execution_chain::LONDON_HARD_FORK_TIMESTAMP
-        .duration_round(Duration::minutes(1))
-        .unwrap()
-        .timestamp();
-
-    let missing_minutes_timestamps = (0..minutes_since_london)
-        .map(|minutes| london_minute_timestamp + minutes * 60)
-        .filter(|timestamp| !known_minutes.contains(timestamp))
-        .collect::<Vec<i64>>();
-
-    info!("found {} missing minutes", missing_minutes_timestamps.len());
-    let mut missing_minutes_stream = stream::iter(missing_minutes_timestamps)
-        .map(|timestamp| async move {
-            let timestamp_date_time = Utc.timestamp_opt(timestamp, 0).unwrap();
-            debug!(minute = timestamp_date_time.to_string(), "missing minute");
-            let usd = bybit::get_closest_price_by_minute(
-                timestamp_date_time,
-                Duration::minutes(max_distance_in_minutes),
-            )
-            .await;
-            match usd {
-                None => {
-                    panic!(
-                        "no Bybit price available for timestamp: {}",
-                        timestamp_date_time,
+        let duration_since_london = Utc::now().duration_round(Duration::minutes(1)).unwrap()
+            - *execution_chain::LONDON_HARD_FORK_TIMESTAMP;
+        let minutes_since_london = duration_since_london.num_minutes();
+
+        let london_minute_timestamp = execution_chain::LONDON_HARD_FORK_TIMESTAMP
+            .duration_round(Duration::minutes(1))
+            .unwrap()
+            .timestamp();
+
+        let missing_minutes_timestamps = (0..minutes_since_london)
+            .map(|minutes| london_minute_timestamp + minutes * 60)
+            .filter(|timestamp| checkpoint.is_none_or(|checkpoint| *timestamp > checkpoint))
+            .filter(|timestamp| start.is_none_or(|start| *timestamp >= start))
+            .filter(|timestamp| end.is_none_or(|end| *timestamp < end))
+            .filter(|timestamp| !known_minutes.contains(timestamp))
+            .collect::<Vec<i64>>();
+
+        info!("found {} missing minutes", missing_minutes_timestamps.len());
+
+        missing_minutes_timestamps
+    }
+
+    async fn heal_chunk(&self, timestamps: &[i64], dry_run: bool) -> Result<()> {
+        let mut missing_minutes_stream = stream::iter(timestamps.iter().copied())
+            .map(|timestamp| async move {
+                let timestamp_date_time = Utc.timestamp_opt(timestamp, 0).unwrap();
+                debug!(minute = timestamp_date_time.to_string(), "missing minute");
+                let sourced_price = self
+                    .price_sources
+                    .get_closest_price_by_minute(timestamp_date_time, self.max_distance)
+                    .await;
+                match &sourced_price {
+                    None => {
+                        warn!(
+                            minute = timestamp_date_time.to_string(),
+                            "no price available from any source"
+                        );
+                    }
+                    Some(sourced_price) => {
+                        info!(
+                            source = sourced_price.source,
+                            "found a price for timestamp: {} - {}",
+                            timestamp,
+                            sourced_price.candle.open
+                        );
+                    }
+                };
+                (sourced_price, timestamp_date_time)
+            })
+            .buffer_unordered(CONCURRENT_REQUESTS);
+
+        while let Some((sourced_price, timestamp)) = missing_minutes_stream.next().await {
+            match sourced_price {
+                // A dry run reports every missing minute, including those we can't heal.
+                None if dry_run => {}
+                None => bail!("no price available from any source for timestamp: {timestamp}"),
+                Some(_) if dry_run => {
+                    debug!(
+                        "dry run, skipping storing price for timestamp: {:?}",
+                        timestamp
                     );
                 }
-                Some(usd) => {
-                    info!(
-                        "found a price on Bybit for timestamp: {} - {}",
-                        timestamp, usd
-                    );
+                Some(sourced_price) => {
+                    debug!("Storing price for timestamp: {:?}", timestamp);
+                    self.eth_price_store
+                        .store_price(
+                            &timestamp,
+                            sourced_price.candle.open,
+                            sourced_price.source,
+                            PriceOrigin::Healed,
+                        )
+                        .await;
+                    debug!("Stored price for timestamp: {:?}", timestamp);
                 }
-            };
-            (usd, timestamp_date_time)
-        })
-        .buffer_unordered(CONCURRENT_REQUESTS);
-
-    while let Some((usd, timestamp)) = missing_minutes_stream.next().await {
-        if let Some(usd) = usd {
-            debug!("Storing price for timestamp: {:?}", timestamp);
-            eth_price_store.store_price(&timestamp, usd).await;
-            debug!("Stored price for timestamp: {:?}", timestamp);
+            }
         }
+
+        Ok(())
     }
+}
+
+pub async fn heal_eth_prices() {
+    let args = std::env::args().collect::<Vec<String>>();
+    let max_distance_in_minutes: i64 = args
+        .iter()
+        .skip(1)
+        .find_map(|str| str.parse::<i64>().ok())
+        .unwrap_or(10);
+    let heal_eth_prices_options =
+        HealEthPricesOptions::from_args(&args, HealEthPricesOptions::from_config())
+            .unwrap_or_else(|err| panic!("{err}"));
+    heal_eth_prices_with_options(
+        max_distance_in_minutes,
+        heal::dry_run_from_args(),
+        &heal_eth_prices_options,
+    )
+    .await;
+}
+
+pub async fn heal_eth_prices_with_options(
+    max_distance_in_minutes: i64,
+    dry_run: bool,
+    heal_eth_prices_options: &HealEthPricesOptions,
+) {
+    log::init_with_env();
+
+    let db_pool = db::get_db_pool("heal-eth-prices").await;
+
+    heal_eth_prices_with_pool(
+        &db_pool,
+        max_distance_in_minutes,
+        &HealOptions {
+            chunk_size: 1000,
+            dry_run,
+        },
+        heal_eth_prices_options,
+    )
+    .await
+    .unwrap();
+}
+
+pub async fn heal_eth_prices_with_pool(
+    db_pool: &PgPool,
+    max_distance_in_minutes: i64,
+    options: &HealOptions,
+    heal_eth_prices_options: &HealEthPricesOptions,
+) -> Result<()> {
+    info!(?heal_eth_prices_options, "healing missing eth prices");
+
+    let price_sources = match &heal_eth_prices_options.sources {
+        Some(names) => PriceSources::from_names(names).unwrap_or_else(|err| panic!("{err}")),
+        None => PriceSources::default(),
+    };
+
+    let healer = EthPricesHealer {
+        db_pool: db_pool.clone(),
+        end: heal_eth_prices_options.end,
+        eth_price_store: store::EthPriceStorePostgres::new(db_pool.clone()),
+        max_distance: Duration::minutes(max_distance_in_minutes),
+        price_sources,
+        start: heal_eth_prices_options.start,
+    };
+
+    heal::heal(db_pool, &healer, options).await?;
 
     info!("done healing eth prices");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heal_eth_prices_options_from_args_test() {
+        let args = [
+            "heal-eth-prices",
+            "--sources",
+            "kraken,bybit",
+            "--start=2024-03-01",
+        ]
+        .map(str::to_string);
+        let options =
+            HealEthPricesOptions::from_args(&args, HealEthPricesOptions::default()).unwrap();
+        assert_eq!(
+            options.sources,
+            Some(vec!["kraken".to_string(), "bybit".to_string()])
+        );
+        assert_eq!(
+            options.start,
+            Some("2024-03-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap())
+        );
+        assert_eq!(options.end, None);
+
+        let args = ["heal-eth-prices", "--end", "March"].map(str::to_string);
+        assert!(HealEthPricesOptions::from_args(&args, HealEthPricesOptions::default()).is_err());
+    }
 }
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use format_url::FormatUrl;
+use serde::Deserialize;
+use tracing::debug;
+
+use super::{candles::EthCandle, sources::PriceSource};
+
+const KRAKEN_API: &str = "https://api.kraken.com";
+
+/// `[time, open, high, low, close, vwap, volume, count]`, prices are strings.
+type KrakenCandle = (i64, String, String, String, String, String, String, i64);
+
+#[derive(Debug, Deserialize)]
+struct KrakenOhlcResponse {
+    error: Vec<String>,
+    /// Holds the candles under the pair name, e.g. `XETHZUSD`, next to a `last` cursor.
+    #[serde(default)]
+    result: HashMap<String, serde_json::Value>,
+}
+
+/// Candles come oldest first.
+fn parse_response(
+    response: KrakenOhlcResponse,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<EthCandle>> {
+    if !response.error.is_empty() {
+        return Err(anyhow!("kraken returned errors: {:?}", response.error));
+    }
+
+    let candles = response
+        .result
+        .into_iter()
+        .find(|(key, _)| key != "last")
+        .context("expected kraken response to contain candles")?
+        .1;
+    let candles: Vec<KrakenCandle> = serde_json::from_value(candles)?;
+
+    candles
+        .into_iter()
+        .map(|(time, open, high, low, close, ..)| {
+            Ok(EthCandle {
+                timestamp: Utc.timestamp_opt(time, 0).unwrap(),
+                open: open.parse()?,
+                high: high.parse()?,
+                low: low.parse()?,
+                close: close.parse()?,
+            })
+        })
+        .filter(|candle| {
+            candle.as_ref().map_or(true, |candle| {
+                candle.timestamp >= start && candle.timestamp <= end
+            })
+        })
+        .collect()
+}
+
+/// 1min candles of the Kraken ETH/USD spot price. Kraken only serves the most recent 720 candles,
+/// so periods older than twelve hours come back empty.
+async fn get_eth_candles(start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<EthCandle>> {
+    // `since` is exclusive.
+    let since = (start.timestamp() - 1).to_string();
+    let url = FormatUrl::new(KRAKEN_API)
+        .with_path_template("/0/public/OHLC")
+        .with_query_params(vec![
+            ("pair", "ETHUSD"),
+            ("interval", "1"),
+            ("since", &since),
+        ])
+        .format_url();
+
+    debug!("sending request to {}", url);
+
+    let response = reqwest::get(&url)
+        .await?
+        .error_for_status()?
+        .json::<KrakenOhlcResponse>()
+        .await?;
+
+    parse_response(response, start, end)
+}
+
+pub struct Kraken;
+
+#[async_trait]
+impl PriceSource for Kraken {
+    fn name(&self) -> &'static str {
+        "kraken"
+    }
+
+    async fn get_eth_candles(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<EthCandle>> {
+        get_eth_candles(start, end).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_response_test() {
+        let response: KrakenOhlcResponse = serde_json::from_str(
+            r#"{
+                "error": [],
+                "result": {
+                    "XETHZUSD": [
+                        [1714521600, "3000.00", "3013.00", "2999.00", "3012.10", "3005.1", "40.1", 120],
+                        [1714521660, "3012.10", "3020.00", "3010.50", "3015.00", "3014.2", "31.2", 98]
+                    ],
+                    "last": 1714521600
+                }
+            }"#,
+        )
+        .unwrap();
+        let start = "2024-05-01T00:00:00Z".parse().unwrap();
+        assert_eq!(
+            parse_response(response, start, start).unwrap(),
+            vec![EthCandle {
+                timestamp: start,
+                open: 3000.0,
+                high: 3013.0,
+                low: 2999.0,
+                close: 3012.1,
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_error_response_test() {
+        let response: KrakenOhlcResponse =
+            serde_json::from_str(r#"{"error": ["EQuery:Unknown asset pair"]}"#).unwrap();
+        let start = "2024-05-01T00:00:00Z".parse().unwrap();
+        assert!(parse_response(response, start, start).is_err());
+    }
+}
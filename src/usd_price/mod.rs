@@ -1,31 +1,59 @@
+//! # USD Price
+//! Records the ETHUSD price every minute. Prices come from Bybit, falling back to Coinbase, Kraken,
+//! then the on-chain Chainlink oracle, see `sources`. How available each source has been is served
+//! at `/api/v2/fees/eth-price-sources`. Full OHLC candles are kept too, see `candles`.
 mod average;
-mod bybit;
+pub mod bybit;
+mod candles;
+mod chainlink;
+pub mod coinbase;
 mod heal;
+mod kraken;
 mod resync;
+mod sources;
+mod stats;
 mod store;
 
 pub use heal::heal_eth_prices;
+pub use heal::heal_eth_prices_with_options;
+pub use heal::heal_eth_prices_with_pool;
+pub use heal::HealEthPricesOptions;
 pub use resync::resync_all;
+pub use resync::resync_all_with_max_distance;
+
+pub use sources::PriceSources;
+pub use sources::SourcedCandles;
+
+pub use stats::update_time_frame_stats;
 
 pub use store::EthPriceStore;
 pub use store::EthPriceStorePostgres;
+pub use store::PriceOrigin;
+
+pub use average::get_average_prices;
+pub use average::publish_average_prices;
+pub use average::AverageEthPrices;
 
-pub use average::on_new_block;
+pub use candles::eth_candles;
 
 use anyhow::Result;
 use chrono::{DateTime, Duration, Utc};
 use serde::Serialize;
 use sqlx::{FromRow, PgPool};
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 use tracing::{debug, info};
 
-use crate::key_value_store::KeyValueStore;
 use crate::key_value_store::KeyValueStorePostgres;
+use crate::key_value_store::{KeyValueStore, KeyValueStoreExt};
 use crate::{
     caching::{self, CacheKey},
-    db, log,
+    config, db, log,
 };
 
+/// Source availability changes with every request, no need to publish it that often.
+const SOURCE_AVAILABILITY_PUBLISH_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(60);
+
 #[derive(Debug, FromRow)]
 struct EthPriceTimestamp {
     timestamp: DateTime<Utc>,
@@ -44,6 +72,8 @@ pub struct EthPriceStats {
     timestamp: DateTime<Utc>,
     usd: f64,
     h24_change: f64,
+    /// Where the price came from, e.g. `bybit`.
+    source: &'static str,
 }
 
 fn calc_h24_change(current_price: &EthPrice, price_h24_ago: &EthPrice) -> f64 {
@@ -54,9 +84,32 @@ async fn update_eth_price_with_most_recent(
     db_pool: &PgPool,
     key_value_store: &impl KeyValueStore,
     eth_price_store: &impl EthPriceStore,
+    price_sources: &PriceSources,
     last_price: &mut EthPrice,
 ) -> Result<()> {
-    let most_recent_price = bybit::get_eth_price().await?;
+    let SourcedCandles { candles, source } = match config::CONFIG.eth_price_max_divergence_percent()
+    {
+        Some(max_divergence_percent) => {
+            match price_sources
+                .get_cross_validated_recent_candles(max_divergence_percent)
+                .await?
+            {
+                Some(sourced_candles) => sourced_candles,
+                // Skip the minute rather than store a price we can't trust, we'll try again
+                // soon.
+                None => return Ok(()),
+            }
+        }
+        None => price_sources.get_recent_candles().await?,
+    };
+
+    // The current candle changes until its minute ends, so store candles on every update.
+    candles::store_candles(db_pool, &candles).await;
+
+    let most_recent_price = candles
+        .last()
+        .expect("expect recent candles to include the current candle")
+        .price();
     if last_price == &most_recent_price {
         debug!(
             price = last_price.usd,
@@ -75,12 +128,18 @@ async fn update_eth_price_with_most_recent(
             debug!(
                 timestamp = most_recent_price.timestamp.to_string(),
                 price = most_recent_price.usd,
+                source,
                 "new most recent price",
             );
         }
 
         eth_price_store
-            .store_price(&most_recent_price.timestamp, most_recent_price.usd)
+            .store_price(
+                &most_recent_price.timestamp,
+                most_recent_price.usd,
+                source,
+                PriceOrigin::Recorded,
+            )
             .await;
 
         *last_price = most_recent_price;
@@ -94,14 +153,12 @@ async fn update_eth_price_with_most_recent(
             timestamp: last_price.timestamp,
             usd: last_price.usd,
             h24_change: calc_h24_change(last_price, &price_h24_ago),
+            source,
         };
 
         key_value_store
-            .set_value(
-                CacheKey::EthPrice.to_db_key(),
-                &serde_json::to_value(&eth_price_stats).unwrap(),
-            )
-            .await;
+            .set(&CacheKey::EthPrice.to_db_key(), &eth_price_stats)
+            .await?;
 
         caching::publish_cache_update(db_pool, &CacheKey::EthPrice).await;
     }
@@ -114,20 +171,40 @@ pub async fn record_eth_price() -> Result<()> {
 
     info!("recording eth prices");
 
+    // The Chainlink fallback reads through our execution node. Fail now, not once every exchange
+    // is down and we need it.
+    config::CONFIG.geth_url();
+
     let db_pool = db::get_db_pool("record-eth-price").await;
     let key_value_store = KeyValueStorePostgres::new(db_pool.clone());
     let eth_price_store = EthPriceStorePostgres::new(db_pool.clone());
+    let price_sources = PriceSources::default();
 
     let mut last_price = eth_price_store.get_most_recent_price().await?;
+    let mut last_availability_publish: Option<Instant> = None;
 
     loop {
         update_eth_price_with_most_recent(
             &db_pool,
             &key_value_store,
             &eth_price_store,
+            &price_sources,
             &mut last_price,
         )
         .await?;
+
+        if last_availability_publish
+            .is_none_or(|instant| instant.elapsed() >= SOURCE_AVAILABILITY_PUBLISH_INTERVAL)
+        {
+            caching::update_and_publish(
+                &db_pool,
+                &CacheKey::EthPriceSources,
+                price_sources.availability(),
+            )
+            .await;
+            last_availability_publish = Some(Instant::now());
+        }
+
         sleep(std::time::Duration::from_secs(10)).await;
     }
 }
@@ -153,7 +230,12 @@ mod tests {
         };
 
         eth_price_store
-            .store_price(&(Utc::now() - Duration::hours(24)), 0.0)
+            .store_price(
+                &(Utc::now() - Duration::hours(24)),
+                0.0,
+                "test",
+                PriceOrigin::Recorded,
+            )
             .await;
 
         let mut last_price = test_price.clone();
@@ -162,6 +244,7 @@ mod tests {
             &test_db.pool,
             &key_value_store,
             &eth_price_store,
+            &PriceSources::default(),
             &mut last_price,
         )
         .await
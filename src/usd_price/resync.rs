@@ -1,65 +1,43 @@
 use chrono::{Duration, DurationRound, TimeZone, Utc};
-use serde_json::{json, Value};
-use sqlx::{postgres::PgRow, PgExecutor, Row};
+use sqlx::PgExecutor;
 use store::EthPriceStore;
 use tracing::{debug, info};
 
-use crate::{db, execution_chain, log};
+use crate::{db, execution_chain, key_value_store, log};
 
-use super::{bybit, store};
+use super::{store, PriceOrigin, PriceSources};
 
 const RESYNC_ETH_PRICES_KEY: &str = "resync-eth-prices";
 
 async fn get_last_synced_minute(executor: impl PgExecutor<'_>) -> Option<u32> {
-    sqlx::query(
-        "
-        SELECT
-            value
-        FROM
-            key_value_store
-        WHERE
-            key = $1
-        ",
-    )
-    .bind(RESYNC_ETH_PRICES_KEY)
-    .map(|row: PgRow| {
-        let value: Value = row.get("value");
-        serde_json::from_value::<u32>(value).unwrap()
-    })
-    .fetch_optional(executor)
-    .await
-    .unwrap()
+    key_value_store::get(executor, RESYNC_ETH_PRICES_KEY)
+        .await
+        .unwrap()
 }
 
 async fn set_last_synced_minute(executor: impl PgExecutor<'_>, minute: u32) {
-    sqlx::query(
-        "
-        INSERT INTO
-            key_value_store (key, value)
-        VALUES ($1, $2)
-        ON CONFLICT (key) DO UPDATE SET
-            value = excluded.value
-        ",
-    )
-    .bind(RESYNC_ETH_PRICES_KEY)
-    .bind(json!(minute))
-    .execute(executor)
-    .await
-    .unwrap();
+    key_value_store::set(executor, RESYNC_ETH_PRICES_KEY, &minute)
+        .await
+        .unwrap();
 }
 
 pub async fn resync_all() {
-    log::init_with_env();
-
-    info!("resyncing all eth prices");
     let max_distance_in_minutes: i64 = std::env::args()
         .collect::<Vec<String>>()
         .get(1)
         .and_then(|str| str.parse::<i64>().ok())
         .unwrap_or(10);
+    resync_all_with_max_distance(max_distance_in_minutes).await;
+}
+
+pub async fn resync_all_with_max_distance(max_distance_in_minutes: i64) {
+    log::init_with_env();
+
+    info!("resyncing all eth prices");
 
     let db_pool = db::get_db_pool("resync-all-prices").await;
     let eth_price_store = store::EthPriceStorePostgres::new(db_pool.clone());
+    let price_sources = PriceSources::default();
 
     debug!("walking through all minutes since London hardfork");
 
@@ -94,21 +72,29 @@ pub async fn resync_all() {
         let timestamp = london_minute_timestamp + minute_n * 60;
         let timestamp_date_time = Utc.timestamp_opt(timestamp.into(), 0).unwrap();
 
-        let usd = bybit::get_closest_price_by_minute(
-            timestamp_date_time,
-            Duration::minutes(max_distance_in_minutes),
-        )
-        .await;
+        let sourced_price = price_sources
+            .get_closest_price_by_minute(
+                timestamp_date_time,
+                Duration::minutes(max_distance_in_minutes),
+            )
+            .await;
 
-        match usd {
+        match sourced_price {
             None => {
                 debug!(
                     timestamp = timestamp_date_time.to_string(),
-                    "no Bybit price available",
+                    "no price available from any source",
                 );
             }
-            Some(usd) => {
-                eth_price_store.store_price(&timestamp_date_time, usd).await;
+            Some(sourced_price) => {
+                eth_price_store
+                    .store_price(
+                        &timestamp_date_time,
+                        sourced_price.candle.open,
+                        sourced_price.source,
+                        PriceOrigin::Resynced,
+                    )
+                    .await;
             }
         }
 
@@ -0,0 +1,372 @@
+//! Price change, high, low and realized volatility of the ETH price over every time frame, up to
+//! a block, calculated from the stored minute prices. Published under `CacheKey::EthPriceStats`,
+//! the `d1` time frame gives the 24h stats.
+//!
+//! Realized volatility is the standard deviation of the log returns between consecutive minute
+//! prices, annualized. Returns spanning a missing minute are ignored. Where raw prices were
+//! pruned, see `downsampling`, hourly mean prices stand in, and only count towards change, high
+//! and low.
+//!
+//! Scanning every price of the longer time frames on every block is slow. Instead we store an
+//! aggregate per completed hour, once, in `eth_price_hourly_stats`. A time frame combines the
+//! aggregates of the hours it spans with the prices of the partial hours at either end. Prices
+//! healed into an hour after it was aggregated don't count, and the return into the first price of
+//! a time frame may come from the minute before it.
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use enum_iterator::all;
+use serde::Serialize;
+use sqlx::{postgres::PgRow, PgExecutor, PgPool, Row};
+use tracing::debug;
+
+use crate::{
+    caching::{self, CacheKey},
+    downsampling,
+    execution_chain::{BlockNumber, ExecutionNodeBlock},
+    time_frames::TimeFrame,
+};
+
+const MINUTES_PER_YEAR: f64 = 365.25 * 24.0 * 60.0;
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceStats {
+    /// Change from the first to the last price, in percent.
+    change_percent: f64,
+    high: f64,
+    low: f64,
+    /// Annualized, `None` with fewer than two returns.
+    realized_volatility: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EthPriceTimeFrameStats {
+    block_number: BlockNumber,
+    time_frames: HashMap<TimeFrame, PriceStats>,
+    timestamp: DateTime<Utc>,
+}
+
+/// What we need of a run of prices to combine it with the runs before and after it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PriceAggregate {
+    first: f64,
+    last: f64,
+    high: f64,
+    low: f64,
+    return_count: i64,
+    return_sum: f64,
+    return_square_sum: f64,
+}
+
+impl PriceAggregate {
+    fn from_row(row: PgRow) -> Self {
+        Self {
+            first: row.get("first"),
+            last: row.get("last"),
+            high: row.get("high"),
+            low: row.get("low"),
+            return_count: row.get("return_count"),
+            return_sum: row.get("return_sum"),
+            return_square_sum: row.get("return_square_sum"),
+        }
+    }
+
+    /// Combines with the aggregate of the prices right after these.
+    fn then(self, next: Self) -> Self {
+        Self {
+            first: self.first,
+            last: next.last,
+            high: self.high.max(next.high),
+            low: self.low.min(next.low),
+            return_count: self.return_count + next.return_count,
+            return_sum: self.return_sum + next.return_sum,
+            return_square_sum: self.return_square_sum + next.return_square_sum,
+        }
+    }
+
+    fn stats(&self) -> PriceStats {
+        let realized_volatility = (self.return_count > 1).then(|| {
+            let count = self.return_count as f64;
+            let variance = (self.return_square_sum - self.return_sum * self.return_sum / count)
+                / (count - 1.0);
+            // Rounding may take a variance of zero just below it.
+            variance.max(0.0).sqrt() * MINUTES_PER_YEAR.sqrt()
+        });
+
+        PriceStats {
+            change_percent: (self.last - self.first) / self.first * 100.0,
+            high: self.high,
+            low: self.low,
+            realized_volatility,
+        }
+    }
+}
+
+/// Prices from `$1` up to `$2`, with the log return from the minute before each. Looks back one
+/// minute so the first price gets a return too.
+fn prices_with_returns_sql() -> String {
+    format!(
+        "
+        SELECT
+            timestamp,
+            ethusd,
+            CASE
+                WHEN is_hourly OR previous_is_hourly THEN NULL
+                WHEN timestamp - previous_timestamp != INTERVAL '1 minute' THEN NULL
+                ELSE LN(ethusd / previous_ethusd)
+            END AS log_return
+        FROM (
+            SELECT
+                timestamp,
+                value::FLOAT8 AS ethusd,
+                is_hourly,
+                LAG(timestamp) OVER (ORDER BY timestamp) AS previous_timestamp,
+                LAG(value::FLOAT8) OVER (ORDER BY timestamp) AS previous_ethusd,
+                LAG(is_hourly) OVER (ORDER BY timestamp) AS previous_is_hourly
+            FROM ({prices}) prices
+            WHERE timestamp >= $1 - INTERVAL '1 minute'
+            AND timestamp < $2
+        ) prices
+        WHERE timestamp >= $1
+        ",
+        prices = downsampling::hourly_or_raw_sql(&downsampling::ETH_PRICES),
+    )
+}
+
+const AGGREGATE_COLUMNS_SQL: &str = "
+    (ARRAY_AGG(ethusd ORDER BY timestamp ASC))[1] AS first,
+    (ARRAY_AGG(ethusd ORDER BY timestamp DESC))[1] AS last,
+    MAX(ethusd) AS high,
+    MIN(ethusd) AS low,
+    COUNT(log_return) AS return_count,
+    COALESCE(SUM(log_return), 0) AS return_sum,
+    COALESCE(SUM(log_return * log_return), 0) AS return_square_sum
+";
+
+/// Aggregates the prices from `start` up to `end`, `None` when there are none.
+async fn get_price_aggregate(
+    executor: impl PgExecutor<'_>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Option<PriceAggregate> {
+    sqlx::query(&format!(
+        "
+        SELECT {AGGREGATE_COLUMNS_SQL}
+        FROM ({prices}) prices
+        HAVING COUNT(*) > 0
+        ",
+        prices = prices_with_returns_sql(),
+    ))
+    .bind(start)
+    .bind(end)
+    .map(PriceAggregate::from_row)
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+}
+
+/// Combines the stored aggregates of the hours from `start` up to `end`, in order.
+async fn get_hourly_aggregate(
+    executor: impl PgExecutor<'_>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Option<PriceAggregate> {
+    sqlx::query_as!(
+        PriceAggregate,
+        r#"
+        SELECT
+            (ARRAY_AGG(first ORDER BY timestamp ASC))[1] AS "first!",
+            (ARRAY_AGG(last ORDER BY timestamp DESC))[1] AS "last!",
+            MAX(high) AS "high!",
+            MIN(low) AS "low!",
+            SUM(return_count)::INT8 AS "return_count!",
+            SUM(return_sum) AS "return_sum!",
+            SUM(return_square_sum) AS "return_square_sum!"
+        FROM eth_price_hourly_stats
+        WHERE timestamp >= $1
+        AND timestamp < $2
+        HAVING COUNT(*) > 0
+        "#,
+        start,
+        end
+    )
+    .fetch_optional(executor)
+    .await
+    .unwrap()
+}
+
+/// Aggregates every hour with prices since the last stored hour, up to the hour `timestamp` falls
+/// in.
+async fn store_completed_hours(executor: &PgPool, timestamp: &DateTime<Utc>) {
+    let end = timestamp.duration_trunc(Duration::hours(1)).unwrap();
+    let start = sqlx::query!(
+        "
+        SELECT MAX(timestamp) + INTERVAL '1 hour' AS start
+        FROM eth_price_hourly_stats
+        "
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .start;
+
+    // Without stored hours, aggregate all prices.
+    let start = start.unwrap_or(DateTime::UNIX_EPOCH);
+    if start >= end {
+        return;
+    }
+
+    let result = sqlx::query(&format!(
+        "
+        INSERT INTO eth_price_hourly_stats (
+            timestamp,
+            first,
+            last,
+            high,
+            low,
+            return_count,
+            return_sum,
+            return_square_sum
+        )
+        SELECT
+            DATE_TRUNC('hour', timestamp) AS timestamp,
+            {AGGREGATE_COLUMNS_SQL}
+        FROM ({prices}) prices
+        GROUP BY 1
+        ON CONFLICT (timestamp) DO NOTHING
+        ",
+        prices = prices_with_returns_sql(),
+    ))
+    .bind(start)
+    .bind(end)
+    .execute(executor)
+    .await
+    .unwrap();
+
+    debug!(
+        count = result.rows_affected(),
+        "stored hourly eth price aggregates"
+    );
+}
+
+/// `None` when there are no prices in the range, which includes `end`.
+async fn get_price_stats(
+    executor: &PgPool,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Option<PriceStats> {
+    let hour = Duration::hours(1);
+    let first_full_hour = start.duration_round_up(hour).unwrap();
+    let last_hour = end.duration_trunc(hour).unwrap();
+    // Block timestamps are whole seconds, this includes a price at the block's timestamp.
+    let end_exclusive = end + Duration::seconds(1);
+
+    let aggregate = if first_full_hour >= last_hour {
+        get_price_aggregate(executor, start, end_exclusive).await
+    } else {
+        [
+            get_price_aggregate(executor, start, first_full_hour).await,
+            get_hourly_aggregate(executor, first_full_hour, last_hour).await,
+            get_price_aggregate(executor, last_hour, end_exclusive).await,
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(PriceAggregate::then)
+    };
+
+    aggregate.map(|aggregate| aggregate.stats())
+}
+
+/// Time frames without prices are left out.
+pub async fn update_time_frame_stats(db_pool: &PgPool, block: &ExecutionNodeBlock) {
+    store_completed_hours(db_pool, &block.timestamp).await;
+
+    let mut time_frames = HashMap::new();
+    for time_frame in all::<TimeFrame>() {
+        let start = time_frame.start_timestamp(block);
+        if let Some(price_stats) = get_price_stats(db_pool, start, block.timestamp).await {
+            time_frames.insert(time_frame, price_stats);
+        }
+    }
+
+    let eth_price_stats = EthPriceTimeFrameStats {
+        block_number: block.number,
+        time_frames,
+        timestamp: block.timestamp,
+    };
+
+    caching::update_and_publish(db_pool, &CacheKey::EthPriceStats, &eth_price_stats).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use test_context::test_context;
+
+    use crate::{
+        db::tests::TestDb,
+        usd_price::store::{EthPriceStore, EthPriceStorePostgres, PriceOrigin},
+    };
+
+    use super::*;
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn get_price_stats_test(test_db: &TestDb) {
+        let eth_price_store = EthPriceStorePostgres::new(test_db.pool.clone());
+        let start = "2024-03-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        for (minute, usd) in [3000.0, 3300.0, 3000.0, 3150.0].into_iter().enumerate() {
+            let timestamp = start + Duration::minutes(minute as i64);
+            eth_price_store
+                .store_price(&timestamp, usd, "test", PriceOrigin::Recorded)
+                .await;
+        }
+
+        let price_stats = get_price_stats(&test_db.pool, start, start + Duration::minutes(3))
+            .await
+            .unwrap();
+        assert_eq!(price_stats.change_percent, 5.0);
+        assert_eq!(price_stats.high, 3300.0);
+        assert_eq!(price_stats.low, 3000.0);
+        assert!(price_stats.realized_volatility.unwrap() > 0.0);
+
+        let empty_start = start - Duration::days(1);
+        assert!(
+            get_price_stats(&test_db.pool, empty_start, empty_start + Duration::hours(1))
+                .await
+                .is_none()
+        );
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn get_price_stats_stored_hours_test(test_db: &TestDb) {
+        let eth_price_store = EthPriceStorePostgres::new(test_db.pool.clone());
+        let first = "2024-03-01T00:50:00Z".parse::<DateTime<Utc>>().unwrap();
+        for minute in 0..80 {
+            let timestamp = first + Duration::minutes(minute);
+            let usd = 3000.0 + (minute % 7) as f64 * 10.0;
+            eth_price_store
+                .store_price(&timestamp, usd, "test", PriceOrigin::Recorded)
+                .await;
+        }
+
+        let end = first + Duration::minutes(75);
+        store_completed_hours(&test_db.pool, &end).await;
+
+        let start = first + Duration::minutes(5);
+        let price_stats = get_price_stats(&test_db.pool, start, end).await.unwrap();
+        let expected = get_price_aggregate(&test_db.pool, start, end + Duration::seconds(1))
+            .await
+            .unwrap()
+            .stats();
+        assert_eq!(price_stats.change_percent, expected.change_percent);
+        assert_eq!(price_stats.high, expected.high);
+        assert_eq!(price_stats.low, expected.low);
+        let volatility_difference =
+            price_stats.realized_volatility.unwrap() - expected.realized_volatility.unwrap();
+        assert!(volatility_difference.abs() < 1e-9);
+    }
+}
@@ -3,10 +3,31 @@ use chrono::{DateTime, Duration, DurationRound, Utc};
 use sqlx::PgPool;
 use thiserror::Error;
 
-use crate::{execution_chain::ExecutionNodeBlock, time_frames::TimeFrame, units::UsdNewtype};
+use crate::{execution_chain::ExecutionNodeBlock, units::UsdNewtype};
 
 use super::EthPrice;
 
+/// How a price got into `eth_prices`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceOrigin {
+    /// Recorded as it happened, by `record-eth-price`.
+    Recorded,
+    /// Filled in for a missing minute, by `heal-eth-prices`.
+    Healed,
+    /// Overwritten by a resync, see `resync_all`.
+    Resynced,
+}
+
+impl PriceOrigin {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Recorded => "recorded",
+            Self::Healed => "healed",
+            Self::Resynced => "resynced",
+        }
+    }
+}
+
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum GetEthPriceError {
     #[error("closest price to given block was too old")]
@@ -20,13 +41,15 @@ pub trait EthPriceStore {
         start_timestamp: DateTime<Utc>,
         end_timestamp: DateTime<Utc>,
     ) -> UsdNewtype;
-    async fn average_from_block_plus_time_range(
-        &self,
-        block: &ExecutionNodeBlock,
-        time_frame: &TimeFrame,
-    ) -> UsdNewtype;
     async fn get_most_recent_price(&self) -> sqlx::Result<EthPrice>;
-    async fn store_price(&self, timestamp: &DateTime<Utc>, usd: f64);
+    /// `provider` is the price source the price came from, e.g. `bybit`.
+    async fn store_price(
+        &self,
+        timestamp: &DateTime<Utc>,
+        usd: f64,
+        provider: &str,
+        origin: PriceOrigin,
+    );
     async fn get_h24_average(&self) -> f64;
     async fn get_price_h24_ago(&self, duration: &Duration) -> Option<EthPrice>;
     async fn get_eth_price_by_minute(&self, minute: DateTime<Utc>) -> Option<f64>;
@@ -57,10 +80,25 @@ impl EthPriceStore for EthPriceStorePostgres {
         start_timestamp: DateTime<Utc>,
         end_timestamp: DateTime<Utc>,
     ) -> UsdNewtype {
+        // Prices from `downsampling::hourly_or_raw_sql`, inlined for the macro. Hourly means
+        // weigh as the minutes they stand in for.
         sqlx::query!(
             r#"
-            SELECT AVG(ethusd) AS "avg!"
-            FROM eth_prices
+            SELECT
+                (
+                    SUM(value * CASE WHEN is_hourly THEN 60 ELSE 1 END)
+                    / SUM(CASE WHEN is_hourly THEN 60 ELSE 1 END)
+                )::FLOAT8 AS "avg!"
+            FROM (
+                SELECT timestamp, value, TRUE AS is_hourly
+                FROM downsampled_series
+                WHERE series = 'eth_prices'
+                AND resolution = 'h1'
+                AND timestamp < (SELECT MIN(timestamp) FROM eth_prices)
+                UNION ALL
+                SELECT timestamp, ethusd::NUMERIC AS value, FALSE AS is_hourly
+                FROM eth_prices
+            ) prices
             WHERE timestamp >= $1
             AND timestamp <= $2
             "#,
@@ -74,18 +112,6 @@ impl EthPriceStore for EthPriceStorePostgres {
         .into()
     }
 
-    async fn average_from_block_plus_time_range(
-        &self,
-        block: &ExecutionNodeBlock,
-        time_frame: &TimeFrame,
-    ) -> UsdNewtype {
-        let start_timestamp = block.timestamp - time_frame.duration();
-        let end_timestamp = block.timestamp;
-
-        self.average_from_time_range(start_timestamp, end_timestamp)
-            .await
-    }
-
     async fn get_most_recent_price(&self) -> sqlx::Result<EthPrice> {
         sqlx::query_as!(
             EthPrice,
@@ -102,17 +128,27 @@ impl EthPriceStore for EthPriceStorePostgres {
         .await
     }
 
-    async fn store_price(&self, timestamp: &DateTime<Utc>, usd: f64) {
+    async fn store_price(
+        &self,
+        timestamp: &DateTime<Utc>,
+        usd: f64,
+        provider: &str,
+        origin: PriceOrigin,
+    ) {
         sqlx::query!(
             "
             INSERT INTO
-                eth_prices (timestamp, ethusd)
-            VALUES ($1, $2)
+                eth_prices (timestamp, ethusd, provider, origin)
+            VALUES ($1, $2, $3, $4)
             ON CONFLICT (timestamp) DO UPDATE SET
-                ethusd = excluded.ethusd
+                ethusd = excluded.ethusd,
+                provider = excluded.provider,
+                origin = excluded.origin
             ",
             timestamp,
-            usd
+            usd,
+            provider,
+            origin.as_str()
         )
         .execute(&self.db_pool)
         .await
@@ -237,17 +273,20 @@ mod tests {
     use chrono::SubsecRound;
     use test_context::test_context;
 
-    use crate::db::tests::TestDb;
+    use crate::{db::tests::TestDb, execution_chain::BlockNumber};
 
     use super::*;
 
     fn make_test_block() -> ExecutionNodeBlock {
         ExecutionNodeBlock {
             base_fee_per_gas: 0,
+            blob_gas_used: None,
             difficulty: 0,
+            excess_blob_gas: None,
+            gas_limit: 0,
             gas_used: 0,
             hash: "0xtest".to_string(),
-            number: 0,
+            number: BlockNumber(0),
             parent_hash: "0xparent".to_string(),
             timestamp: Utc::now(),
             total_difficulty: 0,
@@ -265,12 +304,41 @@ mod tests {
         };
 
         eth_price_store
-            .store_price(&test_price.timestamp, test_price.usd)
+            .store_price(
+                &test_price.timestamp,
+                test_price.usd,
+                "test",
+                PriceOrigin::Recorded,
+            )
             .await;
         let eth_price = eth_price_store.get_most_recent_price().await.unwrap();
         assert_eq!(eth_price, test_price);
     }
 
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn store_price_provenance_test(test_db: &TestDb) {
+        let eth_price_store = EthPriceStorePostgres::new(test_db.pool.clone());
+        let timestamp = Utc::now().trunc_subsecs(0);
+
+        eth_price_store
+            .store_price(&timestamp, 1.0, "bybit", PriceOrigin::Recorded)
+            .await;
+        eth_price_store
+            .store_price(&timestamp, 2.0, "coinbase", PriceOrigin::Healed)
+            .await;
+
+        let row = sqlx::query!(
+            "SELECT provider, origin FROM eth_prices WHERE timestamp = $1",
+            timestamp
+        )
+        .fetch_one(&test_db.pool)
+        .await
+        .unwrap();
+        assert_eq!(row.provider.as_deref(), Some("coinbase"));
+        assert_eq!(row.origin.as_deref(), Some("healed"));
+    }
+
     #[test_context(TestDb)]
     #[tokio::test]
     async fn get_most_recent_price_test(test_db: &TestDb) {
@@ -285,10 +353,20 @@ mod tests {
         };
 
         eth_price_store
-            .store_price(&test_price_1.timestamp, test_price_1.usd)
+            .store_price(
+                &test_price_1.timestamp,
+                test_price_1.usd,
+                "test",
+                PriceOrigin::Recorded,
+            )
             .await;
         eth_price_store
-            .store_price(&test_price_2.timestamp, test_price_2.usd)
+            .store_price(
+                &test_price_2.timestamp,
+                test_price_2.usd,
+                "test",
+                PriceOrigin::Recorded,
+            )
             .await;
         let eth_price = eth_price_store.get_most_recent_price().await.unwrap();
         assert_eq!(eth_price, test_price_2);
@@ -300,7 +378,9 @@ mod tests {
         let eth_price_store = EthPriceStorePostgres::new(test_db.pool.clone());
         let test_block = make_test_block();
 
-        eth_price_store.store_price(&Utc::now(), 5.2).await;
+        eth_price_store
+            .store_price(&Utc::now(), 5.2, "test", PriceOrigin::Recorded)
+            .await;
         let ethusd = eth_price_store
             .get_closest_price_by_block(&test_block)
             .await
@@ -316,7 +396,12 @@ mod tests {
         let test_block = make_test_block();
 
         eth_price_store
-            .store_price(&(Utc::now() - Duration::minutes(21)), 5.2)
+            .store_price(
+                &(Utc::now() - Duration::minutes(21)),
+                5.2,
+                "test",
+                PriceOrigin::Recorded,
+            )
             .await;
         let ethusd = eth_price_store
             .get_closest_price_by_block(&test_block)
@@ -331,7 +416,12 @@ mod tests {
         let test_block = make_test_block();
 
         eth_price_store
-            .store_price(&(Utc::now() - Duration::minutes(6)), 4.0)
+            .store_price(
+                &(Utc::now() - Duration::minutes(6)),
+                4.0,
+                "test",
+                PriceOrigin::Recorded,
+            )
             .await;
         let ethusd = eth_price_store
             .get_closest_price_by_block(&ExecutionNodeBlock {
@@ -357,10 +447,20 @@ mod tests {
         };
 
         eth_price_store
-            .store_price(&test_price_1.timestamp, test_price_1.usd)
+            .store_price(
+                &test_price_1.timestamp,
+                test_price_1.usd,
+                "test",
+                PriceOrigin::Recorded,
+            )
             .await;
         eth_price_store
-            .store_price(&test_price_2.timestamp, test_price_2.usd)
+            .store_price(
+                &test_price_2.timestamp,
+                test_price_2.usd,
+                "test",
+                PriceOrigin::Recorded,
+            )
             .await;
 
         let price_h24_average = eth_price_store.get_h24_average().await;
@@ -378,7 +478,12 @@ mod tests {
         };
 
         eth_price_store
-            .store_price(&test_price.timestamp, test_price.usd)
+            .store_price(
+                &test_price.timestamp,
+                test_price.usd,
+                "test",
+                PriceOrigin::Recorded,
+            )
             .await;
 
         let price = eth_price_store
@@ -398,7 +503,12 @@ mod tests {
         };
 
         eth_price_store
-            .store_price(&test_price.timestamp, test_price.usd)
+            .store_price(
+                &test_price.timestamp,
+                test_price.usd,
+                "test",
+                PriceOrigin::Recorded,
+            )
             .await;
 
         let price = eth_price_store
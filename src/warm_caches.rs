@@ -0,0 +1,60 @@
+//! # Warm Caches
+//! After a fresh deploy or a DB restore cached values are missing until the next head comes in.
+//! This recomputes and publishes them from stored data, on demand.
+use tracing::{info, warn};
+
+use crate::{
+    analysis::{self, NewBlockContext},
+    beacon_chain::IssuanceStorePostgres,
+    burn_records, burn_sums, db,
+    execution_chain::{BlockStore, BlockStorePostgres},
+    log, moving_averages, supply_dashboard_analysis, supply_milestones,
+    usd_price::EthPriceStorePostgres,
+};
+
+pub async fn warm_caches() {
+    log::init_with_env();
+
+    info!("warming caches");
+
+    let db_pool = db::get_db_pool("warm-caches").await;
+
+    let mut connection = db_pool.acquire().await.unwrap();
+    burn_records::publish_stored_records(&mut connection).await;
+    let burn_sums = burn_sums::publish_stored_sums(&mut connection).await;
+    drop(connection);
+
+    // Moving averages and supply milestones only publish when something new is stored, not on
+    // every block.
+    moving_averages::publish_stored_moving_averages(&db_pool).await;
+    supply_milestones::publish_stored_milestones(&db_pool).await;
+
+    // Analyses derive their values from stored data, running them for the last stored block
+    // republishes what they published when it came in.
+    match burn_sums {
+        Some(burn_sums) => {
+            let block = BlockStorePostgres::new(db_pool.clone()).last().await;
+            let eth_price_store = EthPriceStorePostgres::new(db_pool.clone());
+            let issuance_store = IssuanceStorePostgres::new(db_pool.clone());
+            let context = NewBlockContext {
+                block: &block,
+                burn_sums: &burn_sums,
+                db_pool: &db_pool,
+                eth_price_store: &eth_price_store,
+                issuance_store: &issuance_store,
+            };
+            info!(
+                block_number = %block.number,
+                "running analyses for last stored block"
+            );
+            analysis::cache_analyses().on_new_block(&context).await;
+        }
+        None => warn!("no burn sums stored for every time frame, skipping block analyses"),
+    }
+
+    supply_dashboard_analysis::update_cache(&db_pool)
+        .await
+        .unwrap_or_else(|err| warn!("failed to warm supply dashboard caches: {err}"));
+
+    info!("done warming caches");
+}
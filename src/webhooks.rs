@@ -0,0 +1,325 @@
+//! # Webhooks
+//! POSTs a JSON payload to a configured url when a new block meets a condition. Webhooks are
+//! configured in the config file, e.g.
+//!
+//! ```toml
+//! [[webhooks]]
+//! url = "https://example.com/big-burn"
+//! condition = { kind = "block_burn_above", eth = 10.0 }
+//!
+//! [[webhooks]]
+//! url = "https://example.com/ultra-sound"
+//! condition = { kind = "becomes_deflationary", time_frame = "d1" }
+//!
+//! [[webhooks]]
+//! url = "https://example.com/milestones"
+//! condition = { kind = "supply_milestone" }
+//! ```
+//!
+//! `supply_milestone` fires for every milestone `supply_milestones` records. Supply is synced by
+//! the beacon sync, so that's where these are called from, not from new execution blocks.
+//!
+//! `becomes_deflationary` fires once when the supply change over the time frame turns negative,
+//! and again only after it has been positive in between. Which side we're on is kept in memory, so
+//! the first block after a restart never fires it.
+use std::{sync::Mutex, time::Duration};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use tracing::{debug, warn};
+
+use crate::{
+    config,
+    execution_chain::{BlockNumber, ExecutionNodeBlock},
+    supply_milestones::SupplyMilestone,
+    time_frames::TimeFrame,
+    units::{EthNewtype, WeiNewtype},
+};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum WebhookCondition {
+    BlockBurnAbove { eth: f64 },
+    BecomesDeflationary { time_frame: String },
+    SupplyMilestone,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub condition: WebhookCondition,
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload {
+    block_hash: String,
+    block_number: BlockNumber,
+    condition: &'static str,
+    details: serde_json::Value,
+    timestamp: DateTime<Utc>,
+}
+
+enum Trigger {
+    BlockBurnAbove(WeiNewtype),
+    BecomesDeflationary {
+        time_frame: TimeFrame,
+        /// Whether the supply change was negative at the last block we checked.
+        was_deflationary: Mutex<Option<bool>>,
+    },
+    /// Called by `on_supply_milestones` instead.
+    SupplyMilestone,
+}
+
+/// Only a change from not deflationary to deflationary fires.
+fn becomes_deflationary(was_deflationary: Option<bool>, is_deflationary: bool) -> bool {
+    was_deflationary == Some(false) && is_deflationary
+}
+
+async fn get_supply_change_since(
+    executor: impl PgExecutor<'_>,
+    start: &DateTime<Utc>,
+) -> Option<WeiNewtype> {
+    sqlx::query!(
+        r#"
+        SELECT
+            (
+                SELECT supply FROM eth_supply ORDER BY timestamp DESC LIMIT 1
+            ) - (
+                SELECT supply FROM eth_supply WHERE timestamp >= $1 ORDER BY timestamp ASC LIMIT 1
+            ) AS "change?: WeiNewtype"
+        "#,
+        start
+    )
+    .fetch_one(executor)
+    .await
+    .unwrap()
+    .change
+}
+
+struct Webhook {
+    trigger: Trigger,
+    url: String,
+}
+
+impl Webhook {
+    fn from_config(webhook_config: &WebhookConfig) -> Result<Self> {
+        let trigger = match &webhook_config.condition {
+            WebhookCondition::BlockBurnAbove { eth } => {
+                Trigger::BlockBurnAbove(EthNewtype(*eth).into())
+            }
+            WebhookCondition::BecomesDeflationary { time_frame } => Trigger::BecomesDeflationary {
+                time_frame: time_frame
+                    .parse()
+                    .map_err(|_| anyhow!("unknown webhook time frame {time_frame}"))?,
+                was_deflationary: Mutex::new(None),
+            },
+            WebhookCondition::SupplyMilestone => Trigger::SupplyMilestone,
+        };
+
+        Ok(Self {
+            trigger,
+            url: webhook_config.url.clone(),
+        })
+    }
+
+    /// Returns the condition name and details to send when the block meets the condition.
+    async fn check(
+        &self,
+        executor: impl PgExecutor<'_>,
+        block: &ExecutionNodeBlock,
+    ) -> Option<(&'static str, serde_json::Value)> {
+        match &self.trigger {
+            Trigger::BlockBurnAbove(threshold) => {
+                let burn = block.burn();
+                (burn.0 > threshold.0).then(|| {
+                    (
+                        "block_burn_above",
+                        json!({ "burn": burn, "threshold": threshold }),
+                    )
+                })
+            }
+            Trigger::BecomesDeflationary {
+                time_frame,
+                was_deflationary,
+            } => {
+                let start = time_frame.start_timestamp(block);
+                let change = get_supply_change_since(executor, &start).await?;
+                let is_deflationary = change.0 < 0;
+                let was = was_deflationary.lock().unwrap().replace(is_deflationary);
+                becomes_deflationary(was, is_deflationary).then(|| {
+                    (
+                        "becomes_deflationary",
+                        json!({ "supply_change": change, "time_frame": time_frame }),
+                    )
+                })
+            }
+            Trigger::SupplyMilestone => None,
+        }
+    }
+}
+
+fn build_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .unwrap()
+}
+
+/// A failing webhook is logged, not retried.
+async fn call(client: &reqwest::Client, url: &str, payload: &WebhookPayload) {
+    debug!(
+        condition = payload.condition,
+        url, %payload.block_number, "calling webhook"
+    );
+
+    let result = client
+        .post(url)
+        .json(payload)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status());
+    if let Err(err) = result {
+        warn!(
+            condition = payload.condition,
+            url, "failed to call webhook: {err}"
+        );
+    }
+}
+
+pub struct Webhooks {
+    client: reqwest::Client,
+    webhooks: Vec<Webhook>,
+}
+
+impl Webhooks {
+    /// Returns `None` when no webhooks are configured.
+    pub fn from_config() -> Option<Self> {
+        let webhook_configs = config::CONFIG.webhooks();
+        if webhook_configs.is_empty() {
+            return None;
+        }
+
+        let webhooks = webhook_configs
+            .iter()
+            .map(|webhook_config| {
+                Webhook::from_config(webhook_config).unwrap_or_else(|err| panic!("{err}"))
+            })
+            .collect();
+
+        Some(Self {
+            client: build_client(),
+            webhooks,
+        })
+    }
+
+    /// A failing webhook is logged and skipped, the block is not retried.
+    pub async fn on_new_block(&self, db_pool: &PgPool, block: &ExecutionNodeBlock) -> Result<()> {
+        for webhook in self.webhooks.iter() {
+            let (condition, details) = match webhook.check(db_pool, block).await {
+                Some(triggered) => triggered,
+                None => continue,
+            };
+
+            let payload = WebhookPayload {
+                block_hash: block.hash.clone(),
+                block_number: block.number,
+                condition,
+                details,
+                timestamp: block.timestamp,
+            };
+
+            call(&self.client, &webhook.url, &payload).await;
+        }
+
+        Ok(())
+    }
+}
+
+/// Calls every `supply_milestone` webhook for each milestone.
+pub async fn on_supply_milestones(milestones: &[SupplyMilestone]) {
+    let urls = config::CONFIG
+        .webhooks()
+        .iter()
+        .filter(|webhook_config| webhook_config.condition == WebhookCondition::SupplyMilestone)
+        .map(|webhook_config| webhook_config.url.as_str())
+        .collect::<Vec<_>>();
+    if urls.is_empty() {
+        return;
+    }
+
+    let client = build_client();
+    for milestone in milestones {
+        let payload = WebhookPayload {
+            block_hash: milestone.block_hash.clone(),
+            block_number: milestone.block_number,
+            condition: "supply_milestone",
+            details: json!(milestone),
+            timestamp: milestone.timestamp,
+        };
+        for url in urls.iter() {
+            call(&client, url, &payload).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_context::test_context;
+
+    use crate::{db::tests::TestDb, execution_chain::ExecutionNodeBlockBuilder};
+
+    use super::*;
+
+    #[test]
+    fn parse_webhook_config_test() {
+        let webhook_config: WebhookConfig = toml::from_str(
+            r#"
+            url = "https://example.com"
+            condition = { kind = "becomes_deflationary", time_frame = "d1" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            webhook_config.condition,
+            WebhookCondition::BecomesDeflationary {
+                time_frame: "d1".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn becomes_deflationary_test() {
+        assert!(becomes_deflationary(Some(false), true));
+        assert!(!becomes_deflationary(Some(true), true));
+        assert!(!becomes_deflationary(None, true));
+        assert!(!becomes_deflationary(Some(false), false));
+    }
+
+    #[test_context(TestDb)]
+    #[tokio::test]
+    async fn block_burn_above_test(test_db: &TestDb) {
+        let webhook = Webhook::from_config(&WebhookConfig {
+            url: "https://example.com".to_string(),
+            condition: WebhookCondition::BlockBurnAbove { eth: 1.0 },
+        })
+        .unwrap();
+
+        let small_burn = ExecutionNodeBlockBuilder::new("small_burn")
+            .with_gas_used(1)
+            .with_burn(WeiNewtype::from_eth(1))
+            .build();
+        assert!(webhook.check(&test_db.pool, &small_burn).await.is_none());
+
+        let big_burn = ExecutionNodeBlockBuilder::new("big_burn")
+            .with_gas_used(1)
+            .with_burn(WeiNewtype::from_eth(2))
+            .build();
+        assert!(webhook.check(&test_db.pool, &big_burn).await.is_some());
+    }
+}